@@ -0,0 +1,248 @@
+//! Article Preset
+//!
+//! Schema.org `Article` (and `NewsArticle`, `BlogPosting`, ...) for the
+//! metadata, `article:*` and other meta tags next, and readability for the
+//! body and anything still missing. Dates are normalized with
+//! [`normalize_date`], authors are split into one name per entry.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::date::normalize_date;
+use super::{collapse_whitespace, json_text, Page};
+use crate::language::detect_language;
+use crate::readability::extract_article;
+
+/// Normalized article data
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ArticleData {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    /// ISO 8601
+    pub published_at: Option<String>,
+    /// ISO 8601
+    pub modified_at: Option<String>,
+    pub canonical_url: Option<String>,
+    pub body_markdown: String,
+    pub word_count: usize,
+    /// Language tag (`en`, `de-AT`, ...), declared or detected
+    pub lang: Option<String>,
+}
+
+impl ArticleData {
+    /// Whether neither a title nor a body was found
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.body_markdown.is_empty()
+    }
+}
+
+const ARTICLE_TYPES: &[&str] = &[
+    "Article",
+    "NewsArticle",
+    "ReportageNewsArticle",
+    "AnalysisNewsArticle",
+    "OpinionNewsArticle",
+    "BlogPosting",
+    "LiveBlogPosting",
+    "TechArticle",
+    "ScholarlyArticle",
+    "Report",
+];
+
+pub(super) fn extract(page: &Page, html: &str) -> ArticleData {
+    let ld = page
+        .json_ld_of_type(ARTICLE_TYPES)
+        .first()
+        .copied()
+        .unwrap_or(&Value::Null);
+    let readable = extract_article(html, Some(&page.url));
+    let body_markdown = readable.markdown();
+
+    let title = [&ld["headline"], &ld["name"]]
+        .into_iter()
+        .find_map(json_text)
+        .or_else(|| page.meta(&["og:title", "twitter:title"]))
+        .or_else(|| Some(collapse_whitespace(&readable.title)).filter(|t| !t.is_empty()));
+
+    let mut authors = json_authors(&ld["author"]);
+    if authors.is_empty() {
+        let meta = page
+            .meta_all("author")
+            .into_iter()
+            .chain(page.meta_all("article:author"))
+            .chain(page.meta_all("parsely-author"))
+            .filter(|a| !a.starts_with("http"));
+        authors = split_authors(meta.chain(readable.byline.clone()));
+    }
+
+    let lang = json_text(&ld["inLanguage"])
+        .or_else(|| page.select_attrs("html[lang]", "lang").into_iter().next())
+        .or_else(|| page.meta(&["og:locale"]).map(|l| l.replace('_', "-")))
+        .filter(|l| !l.trim().is_empty())
+        .or_else(|| detect_language(&body_markdown).map(|d| d.code.to_string()));
+
+    let published_at = json_text(&ld["datePublished"])
+        .or_else(|| {
+            page.meta(&[
+                "article:published_time",
+                "og:published_time",
+                "pubdate",
+                "publish-date",
+                "date",
+                "DC.date.issued",
+            ])
+        })
+        .or(readable.published)
+        .and_then(|d| normalize_date(&d, lang.as_deref()));
+    let modified_at = json_text(&ld["dateModified"])
+        .or_else(|| page.meta(&["article:modified_time", "og:updated_time", "last-modified"]))
+        .and_then(|d| normalize_date(&d, lang.as_deref()));
+
+    let canonical_url = page
+        .select_attrs("link[rel~='canonical']", "href")
+        .into_iter()
+        .chain(page.meta(&["og:url"]))
+        .chain(json_url(&ld["mainEntityOfPage"]))
+        .chain(json_text(&ld["url"]))
+        .find_map(|href| page.absolute(&href));
+
+    ArticleData {
+        title,
+        authors,
+        published_at,
+        modified_at,
+        canonical_url,
+        word_count: word_count(&body_markdown),
+        body_markdown,
+        lang,
+    }
+}
+
+/// `author` as a name, a `Person`/`Organization`, or a list of either
+fn json_authors(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(name) => split_authors([name.clone()]),
+        Value::Array(items) => {
+            let mut authors: Vec<String> = Vec::new();
+            for author in items.iter().flat_map(json_authors) {
+                if !authors.contains(&author) {
+                    authors.push(author);
+                }
+            }
+            authors
+        }
+        Value::Object(_) => json_text(&value["name"])
+            .map(|name| split_authors([name]))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// `mainEntityOfPage` as a URL or a `WebPage` with an `@id`
+fn json_url(value: &Value) -> Option<String> {
+    json_text(value).or_else(|| json_text(&value["@id"]))
+}
+
+/// One name per author from bylines like `By Jane Doe and John Roe`
+fn split_authors(bylines: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut authors: Vec<String> = Vec::new();
+    for byline in bylines {
+        let byline = collapse_whitespace(&byline);
+        let byline = ["By ", "by ", "Von ", "von ", "Par ", "par ", "Por ", "por "]
+            .iter()
+            .find_map(|prefix| byline.strip_prefix(prefix))
+            .unwrap_or(&byline);
+        for name in byline
+            .split([',', '&', '|', ';'])
+            .flat_map(|part| part.split(" and "))
+            .map(str::trim)
+            .filter(|name| !name.is_empty() && !name.starts_with("http"))
+        {
+            if !authors.iter().any(|a| a == name) {
+                authors.push(name.to_string());
+            }
+        }
+    }
+    authors
+}
+
+/// Words in Markdown, not counting its syntax
+fn word_count(markdown: &str) -> usize {
+    markdown
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    const BODY: &str = "<article><p>The city council voted on Tuesday to extend the tram line \
+        to the harbour, after years of debate about the cost of the project.</p>\
+        <p>Construction is expected to start next spring and take three years, \
+        the council said in a statement.</p></article>";
+
+    fn article(head: &str) -> ArticleData {
+        let html = format!("<html lang=\"en-GB\"><head>{head}</head><body>{BODY}</body></html>");
+        let url = Url::parse("https://news.example/2024/03/tram?utm_source=x").unwrap();
+        extract(&Page::parse(&html, &url), &html)
+    }
+
+    #[test]
+    fn test_json_ld_article() {
+        let article = article(
+            r#"<script type="application/ld+json">{"@context": "https://schema.org",
+                "@type": "NewsArticle", "headline": "Tram line to reach the harbour",
+                "author": [{"@type": "Person", "name": "Jane Doe"}, {"@type": "Person", "name": "John Roe"}],
+                "datePublished": "2024-03-05T10:00:00+02:00", "dateModified": "2024-03-06",
+                "mainEntityOfPage": {"@type": "WebPage", "@id": "https://news.example/2024/03/tram"}}</script>"#,
+        );
+        assert_eq!(
+            article.title.as_deref(),
+            Some("Tram line to reach the harbour")
+        );
+        assert_eq!(article.authors, ["Jane Doe", "John Roe"]);
+        assert_eq!(
+            article.published_at.as_deref(),
+            Some("2024-03-05T08:00:00Z")
+        );
+        assert_eq!(article.modified_at.as_deref(), Some("2024-03-06"));
+        assert_eq!(
+            article.canonical_url.as_deref(),
+            Some("https://news.example/2024/03/tram")
+        );
+        assert_eq!(article.lang.as_deref(), Some("en-GB"));
+        assert!(article.body_markdown.contains("tram line"));
+        assert_eq!(article.word_count, 41);
+    }
+
+    #[test]
+    fn test_meta_fallbacks() {
+        let article = article(
+            r#"<title>Tram | News</title>
+            <link rel="canonical" href="/2024/03/tram">
+            <meta name="author" content="By Jane Doe and John Roe">
+            <meta property="article:author" content="https://news.example/staff/jane">
+            <meta name="date" content="5/3/2024">"#,
+        );
+        assert_eq!(article.title.as_deref(), Some("Tram | News"));
+        assert_eq!(article.authors, ["Jane Doe", "John Roe"]);
+        // en-GB: day first
+        assert_eq!(article.published_at.as_deref(), Some("2024-03-05"));
+        assert_eq!(
+            article.canonical_url.as_deref(),
+            Some("https://news.example/2024/03/tram")
+        );
+    }
+
+    #[test]
+    fn test_split_authors() {
+        assert_eq!(
+            split_authors(["by Ann Lee, Bo Chan & Cy Diaz".to_string()]),
+            ["Ann Lee", "Bo Chan", "Cy Diaz"]
+        );
+    }
+}
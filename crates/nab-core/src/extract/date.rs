@@ -0,0 +1,211 @@
+//! Date Normalization
+//!
+//! Pages declare dates as RFC 3339, RFC 2822, bare numbers (`05.03.2024`,
+//! `3/5/2024`), or words in the page's language (`5. März 2024`,
+//! `5 de marzo de 2024`, `5. maaliskuuta 2024`). Everything becomes ISO 8601:
+//! UTC when the source has an offset, local time when it only has a time,
+//! and a bare `YYYY-MM-DD` when it only has a day.
+
+use std::sync::LazyLock;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
+use regex::Regex;
+
+/// Month names and abbreviations, English, German, French, Spanish, Italian,
+/// Portuguese, Dutch, Swedish, and Polish (Finnish is matched by stem)
+const MONTHS: [&str; 12] = [
+    "january jan januar jänner jän janvier janv enero ene gennaio gen janeiro januari stycznia styczeń sty",
+    "february feb februar février févr fevr febrero febbraio fevereiro fev februari lutego luty lut",
+    "march mar märz mär mars marzo março maart mrt marca marzec",
+    "april apr avril avr abril abr aprile kwietnia kwiecień kwi",
+    "may mai mayo maggio mag maio mei maj maja",
+    "june jun juni juin junio giugno giu junho czerwca czerwiec cze",
+    "july jul juli juillet juil julio luglio lug julho lipca lipiec lip",
+    "august aug août agosto ago augusti augustus sierpnia sierpień sie",
+    "september sep sept septembre septiembre setiembre settembre set setembro września wrzesień wrz",
+    "october oct oktober okt octobre octubre ottobre ott outubro out października październik paź",
+    "november nov novembre noviembre novembro listopada listopad lis",
+    "december dec dezember dez décembre déc diciembre dicembre dic dezembro grudnia grudzień gru",
+];
+
+/// Finnish month stems, followed by `kuu` (`maaliskuu`, `maaliskuuta`)
+const FINNISH_MONTHS: [&str; 12] = [
+    "tammi", "helmi", "maalis", "huhti", "touko", "kesä", "heinä", "elo", "syys", "loka", "marras",
+    "joulu",
+];
+
+/// `10:30`, `10:30:15`, `10.30 pm`
+static TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(\d{1,2})[:.](\d{2})(?:[:.](\d{2}))?\s*([ap])?\.?m?\.?(?:\s|$|[^a-z])")
+        .unwrap()
+});
+
+/// Numeric dates: `2024-03-05`, `2024/3/5`, `05.03.2024`, `3/5/24`
+static NUMERIC: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d{1,4})[-/.](\d{1,2})[-/.](\d{2,4})\b").unwrap());
+
+/// Normalize a date found on a page
+///
+/// `lang` (e.g. `en-US`) decides whether `3/5/2024` is March 5 or 3 May.
+#[must_use]
+pub(crate) fn normalize_date(text: &str, lang: Option<&str>) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(utc(dt.with_timezone(&Utc)));
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f%z",
+        "%Y-%m-%dT%H:%M%z",
+        "%Y-%m-%d %H:%M:%S%.f%z",
+    ] {
+        if let Ok(dt) = DateTime::parse_from_str(text, format) {
+            return Some(utc(dt.with_timezone(&Utc)));
+        }
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc2822(text) {
+        return Some(utc(dt.with_timezone(&Utc)));
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S%.f",
+    ] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(dt.format("%Y-%m-%dT%H:%M:%S").to_string());
+        }
+    }
+
+    // Look for the time outside the date, so `05.03.2024` isn't read as 05:03
+    let time = time_of_day(&NUMERIC.replace(text, " "));
+    let date = numeric_date(text, lang).or_else(|| worded_date(text))?;
+    Some(match time {
+        Some(time) => date.and_time(time).format("%Y-%m-%dT%H:%M:%S").to_string(),
+        None => date.format("%Y-%m-%d").to_string(),
+    })
+}
+
+fn utc(dt: DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn time_of_day(text: &str) -> Option<NaiveTime> {
+    let caps = TIME.captures(text)?;
+    let mut hour: u32 = caps[1].parse().ok()?;
+    let minute: u32 = caps[2].parse().ok()?;
+    let second: u32 = caps.get(3).map_or(Some(0), |s| s.as_str().parse().ok())?;
+    match caps
+        .get(4)
+        .map(|m| m.as_str().to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("p") if hour < 12 => hour += 12,
+        Some("a") if hour == 12 => hour = 0,
+        _ => {}
+    }
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+fn numeric_date(text: &str, lang: Option<&str>) -> Option<NaiveDate> {
+    let caps = NUMERIC.captures(text)?;
+    let parts: Vec<u32> = (1..=3).filter_map(|i| caps[i].parse().ok()).collect();
+    let &[a, b, c] = parts.as_slice() else {
+        return None;
+    };
+    let separator = text[caps.get(1)?.end()..].chars().next()?;
+    let (year, month, day) = if caps[1].len() == 4 {
+        (a, b, c)
+    } else {
+        let year = if caps[3].len() == 2 { 2000 + c } else { c };
+        // Dots are always day-first; slashes follow the page's locale
+        let month_first = separator == '/' && (b > 12 || (a <= 12 && month_first_locale(lang)));
+        if month_first {
+            (year, a, b)
+        } else {
+            (year, b, a)
+        }
+    };
+    NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, month, day)
+}
+
+/// Locales writing the month before the day (`3/5/2024` is March 5)
+fn month_first_locale(lang: Option<&str>) -> bool {
+    let lang = lang
+        .unwrap_or("en-US")
+        .to_ascii_lowercase()
+        .replace('_', "-");
+    matches!(lang.as_str(), "en" | "en-us" | "en-ph" | "en-ca")
+}
+
+fn worded_date(text: &str) -> Option<NaiveDate> {
+    let text = TIME.replace_all(text, " ");
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let month = words.iter().find_map(|w| month_number(w))?;
+    let numbers: Vec<u32> = words.iter().filter_map(|w| w.parse().ok()).collect();
+    let year = numbers
+        .iter()
+        .copied()
+        .find(|n| (1900..=2100).contains(n))?;
+    let day = numbers.iter().copied().find(|n| (1..=31).contains(n))?;
+    NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, month, day)
+}
+
+fn month_number(word: &str) -> Option<u32> {
+    let index = MONTHS
+        .iter()
+        .position(|names| names.split(' ').any(|name| name == word))
+        .or_else(|| {
+            FINNISH_MONTHS.iter().position(|stem| {
+                word.strip_prefix(stem)
+                    .is_some_and(|rest| rest.starts_with("kuu"))
+            })
+        })?;
+    u32::try_from(index + 1).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_date() {
+        let date = |text: &str, lang: Option<&str>| normalize_date(text, lang).unwrap_or_default();
+        assert_eq!(
+            date("2024-03-05T10:00:00+02:00", None),
+            "2024-03-05T08:00:00Z"
+        );
+        assert_eq!(
+            date("2024-03-05T10:00:00.123+0000", None),
+            "2024-03-05T10:00:00Z"
+        );
+        assert_eq!(
+            date("Tue, 05 Mar 2024 10:00:00 GMT", None),
+            "2024-03-05T10:00:00Z"
+        );
+        assert_eq!(date("2024-03-05T10:00", None), "2024-03-05T10:00:00");
+        assert_eq!(date("2024-03-05", None), "2024-03-05");
+        assert_eq!(date("05.03.2024 14:30", Some("de")), "2024-03-05T14:30:00");
+        assert_eq!(date("3/5/2024", Some("en-US")), "2024-03-05");
+        assert_eq!(date("3/5/2024", Some("en-GB")), "2024-05-03");
+        assert_eq!(date("25/12/2024", None), "2024-12-25");
+        assert_eq!(
+            date("March 5, 2024 at 4:15 pm", None),
+            "2024-03-05T16:15:00"
+        );
+        assert_eq!(date("5. März 2024", None), "2024-03-05");
+        assert_eq!(date("martes, 5 de marzo de 2024", None), "2024-03-05");
+        assert_eq!(date("5 mars 2024", None), "2024-03-05");
+        assert_eq!(
+            date("5.3. klo 9.05, päivitetty 5. maaliskuuta 2024", None),
+            "2024-03-05T09:05:00"
+        );
+        assert_eq!(date("12 marca 2024", None), "2024-03-12");
+        assert_eq!(date("yesterday", None), "");
+    }
+}
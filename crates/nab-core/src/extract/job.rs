@@ -0,0 +1,205 @@
+//! Job Posting Preset
+//!
+//! Schema.org `JobPosting` (as required for Google's job search, so most job
+//! boards have it), with meta tags and readability filling in the title,
+//! company, and description when it's missing.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::date::normalize_date;
+use super::product::{json_price, normalize_currency};
+use super::{json_address, json_text, Page};
+use crate::readability::extract_article;
+
+/// Normalized job posting
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct JobPosting {
+    pub title: Option<String>,
+    pub company: Option<String>,
+    pub locations: Vec<String>,
+    pub remote: bool,
+    /// `full_time`, `part_time`, `contractor`, ...
+    pub employment_type: Vec<String>,
+    pub salary: Option<Salary>,
+    /// ISO 8601
+    pub posted_at: Option<String>,
+    /// ISO 8601
+    pub valid_through: Option<String>,
+    pub description_markdown: String,
+    pub apply_url: Option<String>,
+}
+
+/// Pay range
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Salary {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub currency: Option<String>,
+    /// `hour`, `day`, `week`, `month`, or `year`
+    pub period: Option<String>,
+}
+
+impl JobPosting {
+    /// Whether no title was found
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+    }
+}
+
+pub(super) fn extract(page: &Page, html: &str) -> JobPosting {
+    let Some(ld) = page.json_ld_of_type(&["JobPosting"]).first().copied() else {
+        return fallback(page, html);
+    };
+
+    let locations = match &ld["jobLocation"] {
+        Value::Array(places) => places
+            .iter()
+            .filter_map(|p| json_address(&p["address"]))
+            .collect(),
+        place => json_address(&place["address"]).into_iter().collect(),
+    };
+    let employment_type: Vec<String> = match &ld["employmentType"] {
+        Value::Array(types) => types.iter().filter_map(json_text).collect(),
+        value => json_text(value)
+            .map(|t| t.split(',').map(String::from).collect())
+            .unwrap_or_default(),
+    };
+    let description_markdown = json_text(&ld["description"])
+        .map(|html| html2md::parse_html(&html).trim().to_string())
+        .unwrap_or_else(|| extract_article(html, Some(&page.url)).markdown());
+
+    JobPosting {
+        title: json_text(&ld["title"]).or_else(|| json_text(&ld["name"])),
+        company: json_text(&ld["hiringOrganization"]["name"])
+            .or_else(|| json_text(&ld["hiringOrganization"]))
+            .or_else(|| page.meta(&["og:site_name"])),
+        locations,
+        remote: json_text(&ld["jobLocationType"])
+            .is_some_and(|t| t.eq_ignore_ascii_case("TELECOMMUTE")),
+        employment_type: employment_type.iter().map(|t| snake_case(t)).collect(),
+        salary: salary(&ld["baseSalary"]).or_else(|| salary(&ld["estimatedSalary"])),
+        posted_at: json_text(&ld["datePosted"]).and_then(|d| normalize_date(&d, None)),
+        valid_through: json_text(&ld["validThrough"]).and_then(|d| normalize_date(&d, None)),
+        description_markdown,
+        apply_url: json_text(&ld["url"])
+            .or_else(|| page.meta(&["og:url"]))
+            .and_then(|href| page.absolute(&href))
+            .or_else(|| Some(page.url.to_string())),
+    }
+}
+
+/// Title, company, and description of a page without `JobPosting` data
+fn fallback(page: &Page, html: &str) -> JobPosting {
+    let article = extract_article(html, Some(&page.url));
+    JobPosting {
+        title: page
+            .meta(&["og:title"])
+            .or_else(|| page.select_value(&["h1"])),
+        company: page.meta(&["og:site_name"]),
+        description_markdown: article.markdown(),
+        apply_url: Some(page.url.to_string()),
+        ..JobPosting::default()
+    }
+}
+
+/// `MonetaryAmount` with a `QuantitativeValue` (or a bare number) as its value
+fn salary(value: &Value) -> Option<Salary> {
+    let amount = match value {
+        Value::Array(amounts) => amounts.first()?,
+        amount => amount,
+    };
+    let range = &amount["value"];
+    let (min, max) = match json_price(range) {
+        Some(exact) => (Some(exact), Some(exact)),
+        None => {
+            let exact = json_price(&range["value"]);
+            (
+                json_price(&range["minValue"]).or(exact),
+                json_price(&range["maxValue"]).or(exact),
+            )
+        }
+    };
+    if min.is_none() && max.is_none() {
+        return None;
+    }
+    let period = json_text(&range["unitText"]).or_else(|| json_text(&amount["unitText"]));
+    Some(Salary {
+        min,
+        max,
+        currency: json_text(&amount["currency"]).and_then(|c| normalize_currency(&c)),
+        period: period.and_then(|p| normalize_period(&p)),
+    })
+}
+
+/// `HOUR`, `Hourly`, `per year`, ... as `hour`, `year`, ...
+fn normalize_period(text: &str) -> Option<String> {
+    let text = text.to_lowercase();
+    ["hour", "day", "week", "month", "year"]
+        .into_iter()
+        .find(|period| text.contains(period) || (*period == "year" && text.contains("annual")))
+        .map(String::from)
+}
+
+fn snake_case(text: &str) -> String {
+    text.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    fn job(html: &str) -> JobPosting {
+        let url = Url::parse("https://jobs.example/postings/42").unwrap();
+        extract(&Page::parse(html, &url), html)
+    }
+
+    #[test]
+    fn test_job_posting() {
+        let job = job(
+            r#"<script type="application/ld+json">{"@context": "https://schema.org/",
+            "@type": "JobPosting", "title": "Rust Engineer",
+            "description": "<p>Build <b>fast</b> things.</p>",
+            "hiringOrganization": {"@type": "Organization", "name": "Example Oy"},
+            "jobLocation": [{"@type": "Place", "address": {"@type": "PostalAddress",
+                "addressLocality": "Helsinki", "addressCountry": "FI"}}],
+            "jobLocationType": "TELECOMMUTE",
+            "employmentType": ["FULL_TIME", "CONTRACTOR"],
+            "baseSalary": {"@type": "MonetaryAmount", "currency": "EUR",
+                "value": {"@type": "QuantitativeValue", "minValue": 5000, "maxValue": "6500", "unitText": "MONTH"}},
+            "datePosted": "2024-03-05", "validThrough": "2024-04-05T23:59:00+00:00"}</script>"#,
+        );
+        assert_eq!(job.title.as_deref(), Some("Rust Engineer"));
+        assert_eq!(job.company.as_deref(), Some("Example Oy"));
+        assert_eq!(job.locations, ["Helsinki, FI"]);
+        assert!(job.remote);
+        assert_eq!(job.employment_type, ["full_time", "contractor"]);
+        assert_eq!(
+            job.salary,
+            Some(Salary {
+                min: Some(5000.0),
+                max: Some(6500.0),
+                currency: Some("EUR".into()),
+                period: Some("month".into()),
+            })
+        );
+        assert_eq!(job.posted_at.as_deref(), Some("2024-03-05"));
+        assert_eq!(job.valid_through.as_deref(), Some("2024-04-05T23:59:00Z"));
+        assert_eq!(job.description_markdown, "Build **fast** things.");
+        assert_eq!(
+            job.apply_url.as_deref(),
+            Some("https://jobs.example/postings/42")
+        );
+    }
+
+    #[test]
+    fn test_fallback() {
+        let job =
+            job(r#"<meta property="og:site_name" content="Example Careers"><h1>Data Analyst</h1>"#);
+        assert_eq!(job.title.as_deref(), Some("Data Analyst"));
+        assert_eq!(job.company.as_deref(), Some("Example Careers"));
+        assert_eq!(job.salary, None);
+    }
+}
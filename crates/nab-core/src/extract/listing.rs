@@ -0,0 +1,264 @@
+//! Real-Estate Listing Preset
+//!
+//! Schema.org `RealEstateListing` and the residence it's about (`Apartment`,
+//! `House`, `SingleFamilyResidence`, ...), or an `Offer` for one. Prices and
+//! areas come from the offer and `floorSize`; meta tags and page markup fill
+//! in the title, price, and images.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::date::normalize_date;
+use super::product::{json_images, json_price, normalize_currency, parse_price};
+use super::{json_address, json_text, Page};
+
+/// Normalized property listing
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Listing {
+    pub title: Option<String>,
+    pub price: Option<f64>,
+    pub currency: Option<String>,
+    /// `sale` or `rent`
+    pub offer_type: Option<String>,
+    /// Rent period (`month`, `week`, ...)
+    pub price_period: Option<String>,
+    pub address: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub rooms: Option<f64>,
+    pub bedrooms: Option<f64>,
+    pub bathrooms: Option<f64>,
+    pub floor_area: Option<f64>,
+    /// `m2` or `ft2`
+    pub floor_area_unit: Option<String>,
+    pub images: Vec<String>,
+    /// ISO 8601
+    pub posted_at: Option<String>,
+}
+
+impl Listing {
+    /// Whether neither a title nor a price was found
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.price.is_none()
+    }
+}
+
+const RESIDENCE_TYPES: &[&str] = &[
+    "Residence",
+    "Accommodation",
+    "Apartment",
+    "House",
+    "SingleFamilyResidence",
+    "ApartmentComplex",
+    "GatedResidenceCommunity",
+    "Room",
+    "Suite",
+];
+
+pub(super) fn extract(page: &Page) -> Listing {
+    let listing = page
+        .json_ld_of_type(&["RealEstateListing"])
+        .first()
+        .copied()
+        .unwrap_or(&Value::Null);
+    // The residence: the listing's subject, an offered item, or a top-level object
+    let residence = [
+        &listing["mainEntity"],
+        &listing["about"],
+        &listing["offers"]["itemOffered"],
+    ]
+    .into_iter()
+    .find(|v| v.is_object())
+    .or_else(|| page.json_ld_of_type(RESIDENCE_TYPES).first().copied())
+    .or_else(|| {
+        page.json_ld_of_type(&["Offer"])
+            .into_iter()
+            .map(|offer| &offer["itemOffered"])
+            .find(|item| item.is_object())
+    })
+    .unwrap_or(&Value::Null);
+    let offer = [&listing["offers"], &residence["offers"]]
+        .into_iter()
+        .map(|offers| match offers {
+            Value::Array(offers) => offers.first().unwrap_or(&Value::Null),
+            offer => offer,
+        })
+        .find(|offer| offer.is_object())
+        .or_else(|| page.json_ld_of_type(&["Offer"]).first().copied())
+        .unwrap_or(&Value::Null);
+    let spec = match &offer["priceSpecification"] {
+        Value::Array(specs) => specs.first().unwrap_or(&Value::Null),
+        spec => spec,
+    };
+
+    let mut result = Listing {
+        title: [&listing["name"], &residence["name"]]
+            .into_iter()
+            .find_map(json_text)
+            .or_else(|| page.meta(&["og:title"]))
+            .or_else(|| page.select_value(&["h1"])),
+        price: [&offer["price"], &spec["price"]]
+            .into_iter()
+            .find_map(json_price),
+        currency: [&offer["priceCurrency"], &spec["priceCurrency"]]
+            .into_iter()
+            .find_map(|c| json_text(c).and_then(|c| normalize_currency(&c))),
+        offer_type: json_text(&offer["businessFunction"]).and_then(|f| offer_type(&f)),
+        price_period: [&spec["unitCode"], &spec["unitText"]]
+            .into_iter()
+            .find_map(|unit| json_text(unit).and_then(|u| period(&u))),
+        address: json_address(&residence["address"]),
+        latitude: json_price(&residence["geo"]["latitude"]),
+        longitude: json_price(&residence["geo"]["longitude"]),
+        rooms: quantity(&residence["numberOfRooms"]),
+        bedrooms: quantity(&residence["numberOfBedrooms"]),
+        bathrooms: quantity(&residence["numberOfBathroomsTotal"])
+            .or_else(|| quantity(&residence["numberOfFullBathrooms"])),
+        floor_area: quantity(&residence["floorSize"]),
+        floor_area_unit: [
+            &residence["floorSize"]["unitCode"],
+            &residence["floorSize"]["unitText"],
+        ]
+        .into_iter()
+        .find_map(|unit| json_text(unit).and_then(|u| area_unit(&u))),
+        images: Vec::new(),
+        posted_at: json_text(&listing["datePosted"]).and_then(|d| normalize_date(&d, None)),
+    };
+    if result.price_period.is_some() && result.offer_type.is_none() {
+        result.offer_type = Some("rent".to_string());
+    }
+
+    if result.price.is_none() {
+        let (price, currency) = page
+            .meta(&["product:price:amount", "og:price:amount"])
+            .or_else(|| page.select_value(&["[itemprop='price']", "[class*='price']"]))
+            .and_then(|text| parse_price(&text))
+            .unzip();
+        result.price = price;
+        result.currency = result.currency.or(currency.flatten());
+    }
+    let images = [
+        &listing["image"],
+        &residence["image"],
+        &listing["photo"],
+        &residence["photo"],
+    ]
+    .into_iter()
+    .flat_map(json_images)
+    .chain(page.meta_all("og:image"));
+    for image in images.filter_map(|i| page.absolute(&i)) {
+        if !result.images.contains(&image) {
+            result.images.push(image);
+        }
+    }
+    result
+}
+
+/// A number, or a `QuantitativeValue`'s `value`
+fn quantity(value: &Value) -> Option<f64> {
+    json_price(value).or_else(|| json_price(&value["value"]))
+}
+
+/// `sale` or `rent` from a `businessFunction` (`http://purl.org/goodrelations/v1#LeaseOut`)
+fn offer_type(function: &str) -> Option<String> {
+    let function = function.rsplit(['#', '/']).next()?.to_lowercase();
+    match function.as_str() {
+        "sell" => Some("sale".to_string()),
+        "leaseout" => Some("rent".to_string()),
+        _ => None,
+    }
+}
+
+/// Rent period from a UN/CEFACT code (`MON`) or text (`per month`)
+fn period(unit: &str) -> Option<String> {
+    let unit = unit.to_lowercase();
+    let period = match unit.as_str() {
+        "mon" => "month",
+        "wee" => "week",
+        "day" => "day",
+        "ann" => "year",
+        _ => ["month", "week", "day", "year"]
+            .into_iter()
+            .find(|p| unit.contains(p))?,
+    };
+    Some(period.to_string())
+}
+
+/// `m2` or `ft2` from a UN/CEFACT code (`MTK`, `FTK`) or text
+fn area_unit(unit: &str) -> Option<String> {
+    let unit = unit.to_lowercase();
+    let normalized = match unit.as_str() {
+        "mtk" | "m2" | "m²" | "sqm" => "m2",
+        "ftk" | "ft2" | "ft²" | "sqft" | "sq ft" => "ft2",
+        _ if unit.contains("met") => "m2",
+        _ if unit.contains("f") => "ft2",
+        _ => return None,
+    };
+    Some(normalized.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    fn listing(html: &str) -> Listing {
+        extract(&Page::parse(
+            html,
+            &Url::parse("https://homes.example/listing/7").unwrap(),
+        ))
+    }
+
+    #[test]
+    fn test_real_estate_listing() {
+        let listing = listing(
+            r#"<script type="application/ld+json">{"@context": "https://schema.org",
+            "@type": "RealEstateListing", "name": "Two-room flat in Kallio", "datePosted": "2024-03-05",
+            "image": "/photos/7-1.jpg",
+            "offers": {"@type": "Offer", "businessFunction": "http://purl.org/goodrelations/v1#LeaseOut",
+                "priceSpecification": {"@type": "UnitPriceSpecification", "price": 1150, "priceCurrency": "EUR", "unitCode": "MON"}},
+            "mainEntity": {"@type": "Apartment", "numberOfRooms": 2, "numberOfBedrooms": 1,
+                "numberOfBathroomsTotal": 1,
+                "floorSize": {"@type": "QuantitativeValue", "value": 48.5, "unitCode": "MTK"},
+                "address": {"@type": "PostalAddress", "streetAddress": "Helsinginkatu 1",
+                    "postalCode": "00500", "addressLocality": "Helsinki", "addressCountry": "FI"},
+                "geo": {"@type": "GeoCoordinates", "latitude": "60.1841", "longitude": 24.9496}}}</script>
+            <meta property="og:image" content="https://homes.example/photos/7-1.jpg">"#,
+        );
+        assert_eq!(
+            listing,
+            Listing {
+                title: Some("Two-room flat in Kallio".into()),
+                price: Some(1150.0),
+                currency: Some("EUR".into()),
+                offer_type: Some("rent".into()),
+                price_period: Some("month".into()),
+                address: Some("Helsinginkatu 1, 00500 Helsinki, FI".into()),
+                latitude: Some(60.1841),
+                longitude: Some(24.9496),
+                rooms: Some(2.0),
+                bedrooms: Some(1.0),
+                bathrooms: Some(1.0),
+                floor_area: Some(48.5),
+                floor_area_unit: Some("m2".into()),
+                images: vec!["https://homes.example/photos/7-1.jpg".into()],
+                posted_at: Some("2024-03-05".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_residence_with_markup_price() {
+        let listing = listing(
+            r#"<script type="application/ld+json">{"@type": "SingleFamilyResidence",
+            "name": "Family house", "floorSize": {"value": 1800, "unitText": "sq ft"}}</script>
+            <span class="listing-price">$450,000</span>"#,
+        );
+        assert_eq!(listing.title.as_deref(), Some("Family house"));
+        assert_eq!(listing.price, Some(450_000.0));
+        assert_eq!(listing.currency.as_deref(), Some("USD"));
+        assert_eq!(listing.floor_area_unit.as_deref(), Some("ft2"));
+        assert_eq!(listing.offer_type, None);
+    }
+}
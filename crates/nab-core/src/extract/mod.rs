@@ -0,0 +1,282 @@
+//! Structured Extraction Presets (`nab extract --preset`)
+//!
+//! Each preset turns a page into one normalized JSON shape, whatever the site,
+//! by combining the sources pages commonly carry, best first:
+//! 1. JSON-LD (`<script type="application/ld+json">`, including `@graph`)
+//! 2. OpenGraph and other `<meta>` tags
+//! 3. Microdata (`itemprop`) and heuristic selectors
+//!
+//! Every field is filled from the first source that has it.
+
+mod article;
+mod date;
+mod job;
+mod listing;
+mod product;
+
+pub use article::ArticleData;
+pub use job::{JobPosting, Salary};
+pub use listing::Listing;
+pub use product::Product;
+
+use anyhow::Result;
+use scraper::{Html, Selector};
+use serde_json::Value;
+use url::Url;
+
+/// Shape of the extracted data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// `{name, price, currency, availability, sku, images[]}`
+    Product,
+    /// `{title, authors[], published_at, modified_at, canonical_url, body_markdown, word_count, lang}`
+    Article,
+    /// `{title, company, locations[], remote, employment_type[], salary, posted_at, ...}`
+    Job,
+    /// `{title, price, currency, offer_type, address, rooms, floor_area, images[], ...}`
+    Listing,
+}
+
+impl Preset {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Product => "product",
+            Self::Article => "article",
+            Self::Job => "job",
+            Self::Listing => "listing",
+        }
+    }
+}
+
+/// Extract `preset` data from `html`, loaded from `url`
+///
+/// Fails when the page has none of the preset's key fields.
+pub fn extract(preset: Preset, html: &str, url: &Url) -> Result<Value> {
+    let page = Page::parse(html, url);
+    let value = match preset {
+        Preset::Product => {
+            let product = product::extract(&page);
+            if product.is_empty() {
+                anyhow::bail!("No {} data found on {url}", preset.name());
+            }
+            serde_json::to_value(product)?
+        }
+        Preset::Article => {
+            let article = article::extract(&page, html);
+            if article.is_empty() {
+                anyhow::bail!("No {} data found on {url}", preset.name());
+            }
+            serde_json::to_value(article)?
+        }
+        Preset::Job => {
+            let job = job::extract(&page, html);
+            if job.is_empty() {
+                anyhow::bail!("No {} data found on {url}", preset.name());
+            }
+            serde_json::to_value(job)?
+        }
+        Preset::Listing => {
+            let listing = listing::extract(&page);
+            if listing.is_empty() {
+                anyhow::bail!("No {} data found on {url}", preset.name());
+            }
+            serde_json::to_value(listing)?
+        }
+    };
+    Ok(value)
+}
+
+/// A parsed page and its JSON-LD objects
+pub(crate) struct Page {
+    document: Html,
+    url: Url,
+    json_ld: Vec<Value>,
+}
+
+impl Page {
+    pub(crate) fn parse(html: &str, url: &Url) -> Self {
+        let document = Html::parse_document(html);
+        let selector = Selector::parse("script[type='application/ld+json']").unwrap();
+        let mut json_ld = Vec::new();
+        for script in document.select(&selector) {
+            let text: String = script.text().collect();
+            if let Ok(value) = serde_json::from_str::<Value>(text.trim()) {
+                flatten_json_ld(value, &mut json_ld);
+            }
+        }
+        Self {
+            document,
+            url: url.clone(),
+            json_ld,
+        }
+    }
+
+    /// JSON-LD objects whose `@type` is (or includes) one of `types`
+    pub(crate) fn json_ld_of_type(&self, types: &[&str]) -> Vec<&Value> {
+        self.json_ld
+            .iter()
+            .filter(|object| match &object["@type"] {
+                Value::String(t) => types.contains(&t.as_str()),
+                Value::Array(ts) => ts
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .any(|t| types.contains(&t)),
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Content of the first non-empty `<meta property|name=...>` among `names`
+    pub(crate) fn meta(&self, names: &[&str]) -> Option<String> {
+        names.iter().find_map(|name| {
+            let selector =
+                Selector::parse(&format!("meta[property='{name}'], meta[name='{name}']")).ok()?;
+            self.document
+                .select(&selector)
+                .filter_map(|el| el.value().attr("content"))
+                .map(str::trim)
+                .find(|content| !content.is_empty())
+                .map(String::from)
+        })
+    }
+
+    /// Contents of every `<meta property|name=name>`
+    pub(crate) fn meta_all(&self, name: &str) -> Vec<String> {
+        let Ok(selector) =
+            Selector::parse(&format!("meta[property='{name}'], meta[name='{name}']"))
+        else {
+            return Vec::new();
+        };
+        self.document
+            .select(&selector)
+            .filter_map(|el| el.value().attr("content"))
+            .map(str::trim)
+            .filter(|content| !content.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// `content`, `href`, or text of the first match of any of `selectors`
+    pub(crate) fn select_value(&self, selectors: &[&str]) -> Option<String> {
+        selectors.iter().find_map(|selector| {
+            let selector = Selector::parse(selector).ok()?;
+            self.document.select(&selector).find_map(|el| {
+                let value = el.value();
+                let text = match value.attr("content").or_else(|| value.attr("href")) {
+                    Some(attr) => attr.to_string(),
+                    None => el.text().collect(),
+                };
+                let text = collapse_whitespace(&text);
+                (!text.is_empty()).then_some(text)
+            })
+        })
+    }
+
+    /// `attr` of every match of `selector`
+    pub(crate) fn select_attrs(&self, selector: &str, attr: &str) -> Vec<String> {
+        let Ok(selector) = Selector::parse(selector) else {
+            return Vec::new();
+        };
+        self.document
+            .select(&selector)
+            .filter_map(|el| el.value().attr(attr))
+            .map(String::from)
+            .collect()
+    }
+
+    /// `href` resolved against the page URL (http(s) only)
+    pub(crate) fn absolute(&self, href: &str) -> Option<String> {
+        let url = self.url.join(href.trim()).ok()?;
+        matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+    }
+}
+
+/// Top-level JSON-LD objects, with arrays and `@graph` unpacked
+fn flatten_json_ld(value: Value, out: &mut Vec<Value>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                flatten_json_ld(item, out);
+            }
+        }
+        Value::Object(mut map) => {
+            if let Some(graph) = map.remove("@graph") {
+                flatten_json_ld(graph, out);
+            }
+            if map.contains_key("@type") {
+                out.push(Value::Object(map));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// String value of a JSON-LD field, also accepting numbers
+pub(crate) fn json_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(collapse_whitespace(s)).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// `PostalAddress` (or a plain string) as one line, e.g. `Mannerheimintie 1, Helsinki, FI`
+pub(crate) fn json_address(value: &Value) -> Option<String> {
+    if let Some(text) = json_text(value) {
+        return Some(text);
+    }
+    let country =
+        json_text(&value["addressCountry"]).or_else(|| json_text(&value["addressCountry"]["name"]));
+    let parts: Vec<String> = [
+        json_text(&value["streetAddress"]),
+        json_text(&value["postalCode"])
+            .into_iter()
+            .chain(json_text(&value["addressLocality"]))
+            .reduce(|code, city| format!("{code} {city}")),
+        json_text(&value["addressRegion"]),
+        country,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+pub(crate) fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_ld_graph() {
+        let html = r#"<html><head>
+            <script type="application/ld+json">{"@context":"https://schema.org","@graph":[
+                {"@type":"WebSite","name":"Shop"},
+                {"@type":["Product","Thing"],"name":"Kettle"}]}</script>
+            <script type="application/ld+json">not json</script>
+            <meta property="og:title" content="  ">
+            <meta name="og:title" content="Kettle | Shop">
+            </head></html>"#;
+        let page = Page::parse(html, &Url::parse("https://shop.example/p/1").unwrap());
+        let products = page.json_ld_of_type(&["Product"]);
+        assert_eq!(products.len(), 1);
+        assert_eq!(products[0]["name"], "Kettle");
+        assert_eq!(page.meta(&["og:title"]).as_deref(), Some("Kettle | Shop"));
+        assert_eq!(
+            page.absolute("../img/k.jpg").as_deref(),
+            Some("https://shop.example/img/k.jpg")
+        );
+        assert_eq!(page.absolute("javascript:void(0)"), None);
+    }
+
+    #[test]
+    fn test_extract_requires_data() {
+        let url = Url::parse("https://shop.example/").unwrap();
+        let err = extract(Preset::Product, "<html><p>Hello</p></html>", &url).unwrap_err();
+        assert!(err.to_string().contains("No product data"));
+    }
+}
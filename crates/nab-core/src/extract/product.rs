@@ -0,0 +1,365 @@
+//! Product Preset
+//!
+//! Schema.org `Product` (with its `Offer`/`AggregateOffer`), `product:*` and
+//! `og:*` meta tags, then microdata and common price markup. Prices become
+//! numbers, currencies ISO 4217 codes, and availability a `snake_case` token
+//! (`in_stock`, `out_of_stock`, `pre_order`, ...).
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{collapse_whitespace, json_text, Page};
+
+/// Normalized product data
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Product {
+    pub name: Option<String>,
+    pub price: Option<f64>,
+    pub currency: Option<String>,
+    pub availability: Option<String>,
+    pub sku: Option<String>,
+    pub images: Vec<String>,
+}
+
+impl Product {
+    /// Whether neither a name nor a price was found
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.price.is_none()
+    }
+}
+
+/// Currency symbols, longest first so `CA$` wins over `$`
+const SYMBOLS: &[(&str, &str)] = &[
+    ("US$", "USD"),
+    ("CA$", "CAD"),
+    ("AU$", "AUD"),
+    ("NZ$", "NZD"),
+    ("HK$", "HKD"),
+    ("R$", "BRL"),
+    ("C$", "CAD"),
+    ("A$", "AUD"),
+    ("€", "EUR"),
+    ("£", "GBP"),
+    ("¥", "JPY"),
+    ("₹", "INR"),
+    ("₩", "KRW"),
+    ("₽", "RUB"),
+    ("zł", "PLN"),
+    ("$", "USD"),
+];
+
+/// ISO codes recognized next to a price in page text
+const CODES: &[&str] = &[
+    "USD", "EUR", "GBP", "JPY", "CHF", "CAD", "AUD", "NZD", "SEK", "NOK", "DKK", "PLN", "CZK",
+    "HUF", "INR", "CNY", "BRL", "MXN", "KRW", "HKD", "SGD",
+];
+
+pub(super) fn extract(page: &Page) -> Product {
+    let mut product = Product::default();
+    if let Some(ld) = page.json_ld_of_type(&["Product", "ProductGroup"]).first() {
+        from_json_ld(ld, page, &mut product);
+    }
+    from_meta(page, &mut product);
+    from_markup(page, &mut product);
+    product
+}
+
+fn from_json_ld(ld: &Value, page: &Page, product: &mut Product) {
+    product.name = json_text(&ld["name"]);
+    product.sku = json_text(&ld["sku"]).or_else(|| json_text(&ld["productID"]));
+    push_images(product, page, json_images(&ld["image"]));
+
+    let offers = match &ld["offers"] {
+        Value::Array(offers) => offers.iter().collect(),
+        offer @ Value::Object(_) => vec![offer],
+        _ => Vec::new(),
+    };
+    for offer in offers {
+        let spec = match &offer["priceSpecification"] {
+            Value::Array(specs) => specs.first().unwrap_or(&Value::Null),
+            spec => spec,
+        };
+        let price = [&offer["price"], &offer["lowPrice"], &spec["price"]]
+            .into_iter()
+            .find_map(json_price);
+        if product.price.is_none() {
+            product.price = price;
+        }
+        if product.currency.is_none() {
+            product.currency = [&offer["priceCurrency"], &spec["priceCurrency"]]
+                .into_iter()
+                .find_map(|v| json_text(v).and_then(|c| normalize_currency(&c)));
+        }
+        if product.availability.is_none() {
+            product.availability =
+                json_text(&offer["availability"]).and_then(|a| normalize_availability(&a));
+        }
+        if price.is_some() {
+            break;
+        }
+    }
+}
+
+fn from_meta(page: &Page, product: &mut Product) {
+    if product.name.is_none() {
+        product.name = page.meta(&["og:title", "twitter:title"]);
+    }
+    if product.price.is_none() {
+        product.price = page
+            .meta(&["product:price:amount", "og:price:amount"])
+            .and_then(|p| parse_price(&p).map(|(price, _)| price));
+    }
+    if product.currency.is_none() {
+        product.currency = page
+            .meta(&["product:price:currency", "og:price:currency"])
+            .and_then(|c| normalize_currency(&c));
+    }
+    if product.availability.is_none() {
+        product.availability = page
+            .meta(&["product:availability", "og:availability"])
+            .and_then(|a| normalize_availability(&a));
+    }
+    if product.sku.is_none() {
+        product.sku = page.meta(&["product:retailer_item_id"]);
+    }
+    let images = page.meta_all("og:image");
+    push_images(product, page, images);
+}
+
+fn from_markup(page: &Page, product: &mut Product) {
+    if product.name.is_none() {
+        product.name = page.select_value(&["[itemprop='name']", "h1"]);
+    }
+    if product.price.is_none() || product.currency.is_none() {
+        let (price, currency) = page
+            .select_value(&[
+                "[itemprop='price']",
+                "[data-price]",
+                ".price",
+                "[class*='price']",
+            ])
+            .and_then(|text| parse_price(&text))
+            .unzip();
+        product.price = product.price.or(price);
+        product.currency = product.currency.take().or(currency.flatten());
+    }
+    if product.currency.is_none() {
+        product.currency = page
+            .select_value(&["[itemprop='priceCurrency']"])
+            .and_then(|c| normalize_currency(&c));
+    }
+    if product.availability.is_none() {
+        product.availability = page
+            .select_value(&["[itemprop='availability']"])
+            .and_then(|a| normalize_availability(&a));
+    }
+    if product.sku.is_none() {
+        product.sku = page.select_value(&["[itemprop='sku']"]);
+    }
+    if product.images.is_empty() {
+        let images = page.select_attrs("[itemprop='image']", "src");
+        push_images(product, page, images);
+    }
+}
+
+fn push_images(product: &mut Product, page: &Page, images: Vec<String>) {
+    for image in images.iter().filter_map(|i| page.absolute(i)) {
+        if !product.images.contains(&image) {
+            product.images.push(image);
+        }
+    }
+}
+
+/// `image` as a URL, a list of URLs, or `ImageObject`s
+pub(super) fn json_images(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(url) => vec![url.clone()],
+        Value::Array(items) => items.iter().flat_map(json_images).collect(),
+        Value::Object(_) => [&value["url"], &value["contentUrl"]]
+            .into_iter()
+            .find_map(Value::as_str)
+            .map(|url| vec![url.to_string()])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// JSON-LD price: a number, or a string with a `.` decimal point
+pub(super) fn json_price(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s
+            .trim()
+            .parse()
+            .ok()
+            .or_else(|| parse_price(s).map(|(price, _)| price)),
+        _ => None,
+    }
+}
+
+/// Price and currency in display text such as `€1.299,00` or `USD 19.99`
+pub(super) fn parse_price(text: &str) -> Option<(f64, Option<String>)> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let number: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '\'' | ' ' | '\u{a0}'))
+        .filter(|c| !matches!(c, '\'' | ' ' | '\u{a0}'))
+        .collect();
+    let number = number.trim_end_matches(['.', ',']);
+
+    let decimal = match (number.rfind('.'), number.rfind(',')) {
+        // Both used: the later one is the decimal separator
+        (Some(dot), Some(comma)) => Some(if dot > comma { '.' } else { ',' }),
+        // One separator, once, not followed by exactly three digits: decimal
+        (Some(at), None) | (None, Some(at)) => {
+            let sep = number[at..].chars().next()?;
+            let once = number.matches(sep).count() == 1;
+            (once && number.len() - at - 1 != 3).then_some(sep)
+        }
+        (None, None) => None,
+    };
+    let normalized: String = number
+        .chars()
+        .filter_map(|c| match c {
+            '0'..='9' => Some(c),
+            c if Some(c) == decimal => Some('.'),
+            _ => None,
+        })
+        .collect();
+    let price = normalized.parse().ok()?;
+    Some((price, currency_in(text)))
+}
+
+/// Currency named by a symbol or ISO code in `text`
+fn currency_in(text: &str) -> Option<String> {
+    let upper = text.to_uppercase();
+    let code = upper
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .find(|word| CODES.contains(word));
+    if let Some(code) = code {
+        return Some(code.to_string());
+    }
+    SYMBOLS
+        .iter()
+        .find(|(symbol, _)| upper.contains(&symbol.to_uppercase()))
+        .map(|(_, code)| (*code).to_string())
+}
+
+/// ISO 4217 code for a code or symbol
+pub(super) fn normalize_currency(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    if trimmed.len() == 3 && trimmed.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Some(trimmed.to_uppercase());
+    }
+    currency_in(trimmed)
+}
+
+/// `snake_case` availability from a schema.org URL or OpenGraph value
+fn normalize_availability(text: &str) -> Option<String> {
+    let token = text.trim().rsplit('/').next().unwrap_or_default();
+    let squashed: String = token
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_lowercase();
+    let normalized = match squashed.as_str() {
+        "" => return None,
+        "instock" | "available" => "in_stock",
+        "outofstock" | "oos" | "unavailable" => "out_of_stock",
+        "preorder" | "presale" => "pre_order",
+        "backorder" => "back_order",
+        "soldout" => "sold_out",
+        "discontinued" => "discontinued",
+        "limitedavailability" => "limited_availability",
+        "instoreonly" => "in_store_only",
+        "onlineonly" => "online_only",
+        "pending" => "pending",
+        _ => return Some(collapse_whitespace(token).to_lowercase().replace(' ', "_")),
+    };
+    Some(normalized.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    fn product(html: &str) -> Product {
+        extract(&Page::parse(
+            html,
+            &Url::parse("https://shop.example/kettles/k1").unwrap(),
+        ))
+    }
+
+    #[test]
+    fn test_json_ld_product() {
+        let product = product(
+            r#"<script type="application/ld+json">{
+                "@context": "https://schema.org", "@type": "Product",
+                "name": "Steel  Kettle", "sku": "K-100",
+                "image": ["/img/k1.jpg", {"@type": "ImageObject", "url": "https://cdn.example/k1b.jpg"}],
+                "offers": {"@type": "Offer", "price": "49.90", "priceCurrency": "eur",
+                           "availability": "https://schema.org/InStock"}}</script>
+            <meta property="og:image" content="https://shop.example/img/k1.jpg">
+            <meta property="og:title" content="Steel Kettle | Shop">"#,
+        );
+        assert_eq!(
+            product,
+            Product {
+                name: Some("Steel Kettle".into()),
+                price: Some(49.9),
+                currency: Some("EUR".into()),
+                availability: Some("in_stock".into()),
+                sku: Some("K-100".into()),
+                images: vec![
+                    "https://shop.example/img/k1.jpg".into(),
+                    "https://cdn.example/k1b.jpg".into()
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_aggregate_offer_and_meta_fallback() {
+        let product = product(
+            r#"<script type="application/ld+json">[{"@type": "Product", "name": "Kettle",
+                "offers": [{"@type": "AggregateOffer", "lowPrice": 39, "highPrice": 59}]}]</script>
+            <meta property="product:price:currency" content="USD">
+            <meta property="og:availability" content="oos">
+            <meta property="product:retailer_item_id" content="K-200">"#,
+        );
+        assert_eq!(product.price, Some(39.0));
+        assert_eq!(product.currency.as_deref(), Some("USD"));
+        assert_eq!(product.availability.as_deref(), Some("out_of_stock"));
+        assert_eq!(product.sku.as_deref(), Some("K-200"));
+    }
+
+    #[test]
+    fn test_markup_heuristics() {
+        let product = product(
+            r#"<h1> Glass
+                Kettle </h1>
+            <div itemprop="offers"><span class="product-price">1.299,00 €</span>
+            <link itemprop="availability" href="http://schema.org/PreOrder"></div>
+            <img itemprop="image" src="k3.png">"#,
+        );
+        assert_eq!(product.name.as_deref(), Some("Glass Kettle"));
+        assert_eq!(product.price, Some(1299.0));
+        assert_eq!(product.currency.as_deref(), Some("EUR"));
+        assert_eq!(product.availability.as_deref(), Some("pre_order"));
+        assert_eq!(product.images, ["https://shop.example/kettles/k3.png"]);
+    }
+
+    #[test]
+    fn test_parse_price() {
+        let price = |text: &str| parse_price(text).map(|(p, c)| (p, c.unwrap_or_default()));
+        assert_eq!(price("$19.99"), Some((19.99, "USD".into())));
+        assert_eq!(price("CA$1,299.50"), Some((1299.5, "CAD".into())));
+        assert_eq!(price("1 299,95 zł"), Some((1299.95, "PLN".into())));
+        assert_eq!(price("CHF 1'250.–"), Some((1250.0, "CHF".into())));
+        assert_eq!(price("€1.299"), Some((1299.0, "EUR".into())));
+        assert_eq!(price("12,5"), Some((12.5, String::new())));
+        assert_eq!(price("Sold out"), None);
+    }
+}
@@ -0,0 +1,335 @@
+//! Language Detection
+//!
+//! Lightweight, dependency-free detection for extracted page text:
+//! - Non-Latin scripts map directly (Cyrillic, Greek, Arabic, Hebrew, CJK, ...)
+//! - Latin-script text is scored against per-language stopword lists
+//!
+//! Good enough to label articles and pick a translation source; short or
+//! mixed-language snippets return `None` rather than a guess.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+/// Minimum stopword hits before a Latin-script guess is reported
+const MIN_STOPWORD_HITS: usize = 3;
+
+/// Minimum letters before a script-based guess is reported
+const MIN_LETTERS: usize = 12;
+
+/// Detected language of a text
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DetectedLanguage {
+    /// ISO 639-1 code (`en`, `fi`, ...)
+    pub code: &'static str,
+    /// English name
+    pub name: &'static str,
+    /// 0.0-1.0; share of the winning signal
+    pub confidence: f64,
+}
+
+/// Stopwords per Latin-script language: (code, name, words)
+const STOPWORDS: &[(&str, &str, &[&str])] = &[
+    (
+        "en",
+        "English",
+        &[
+            "the", "and", "of", "to", "is", "in", "that", "it", "was", "for", "with", "are",
+            "this", "have", "from", "not", "but", "they", "which", "you", "be", "been", "would",
+            "their",
+        ],
+    ),
+    (
+        "de",
+        "German",
+        &[
+            "der", "die", "und", "das", "ist", "nicht", "ein", "eine", "zu", "den", "von", "mit",
+            "sich", "auf", "für", "auch", "dem", "wird", "sind", "noch", "nach", "wie", "bei",
+            "oder",
+        ],
+    ),
+    (
+        "fr",
+        "French",
+        &[
+            "le", "la", "les", "et", "des", "est", "une", "du", "que", "dans", "pour", "pas",
+            "qui", "sur", "au", "avec", "sont", "mais", "nous", "vous", "ce", "cette", "aux",
+            "été",
+        ],
+    ),
+    (
+        "es",
+        "Spanish",
+        &[
+            "el", "los", "las", "y", "es", "por", "una", "con", "para", "del", "que", "se", "su",
+            "al", "como", "más", "pero", "sus", "fue", "está", "muy", "también", "entre", "cuando",
+        ],
+    ),
+    (
+        "it",
+        "Italian",
+        &[
+            "il", "di", "che", "è", "della", "per", "non", "una", "sono", "gli", "nel", "del",
+            "alla", "anche", "più", "delle", "come", "questo", "dei", "ma", "hanno", "essere",
+            "nella", "lo",
+        ],
+    ),
+    (
+        "pt",
+        "Portuguese",
+        &[
+            "o", "os", "e", "do", "da", "em", "um", "uma", "não", "com", "para", "que", "dos",
+            "das", "na", "no", "mais", "foi", "ao", "pelo", "pela", "são", "também", "está",
+        ],
+    ),
+    (
+        "nl",
+        "Dutch",
+        &[
+            "de", "het", "een", "en", "van", "is", "dat", "niet", "zijn", "op", "te", "met",
+            "voor", "ook", "aan", "maar", "wordt", "bij", "naar", "heeft", "deze", "worden", "nog",
+            "om",
+        ],
+    ),
+    (
+        "sv",
+        "Swedish",
+        &[
+            "och", "att", "det", "som", "är", "en", "på", "av", "för", "med", "den", "till", "har",
+            "inte", "om", "ett", "var", "kan", "men", "eller", "sig", "så", "från", "också",
+        ],
+    ),
+    (
+        "da",
+        "Danish",
+        &[
+            "og", "at", "det", "er", "en", "til", "på", "af", "med", "for", "den", "ikke", "som",
+            "har", "de", "var", "men", "et", "fra", "kan", "blev", "også", "efter", "eller",
+        ],
+    ),
+    (
+        "no",
+        "Norwegian",
+        &[
+            "og", "det", "er", "til", "på", "som", "en", "av", "for", "med", "ikke", "har", "de",
+            "var", "jeg", "men", "om", "et", "fra", "kan", "ble", "også", "etter", "eller",
+        ],
+    ),
+    (
+        "fi",
+        "Finnish",
+        &[
+            "ja", "on", "ei", "että", "se", "oli", "hän", "mutta", "ovat", "kun", "myös", "tai",
+            "joka", "sen", "mukaan", "jo", "vain", "niin", "kuin", "ole", "hänen", "tämä", "sekä",
+            "jotka",
+        ],
+    ),
+    (
+        "pl",
+        "Polish",
+        &[
+            "i", "w", "nie", "się", "na", "to", "że", "z", "do", "jest", "jak", "ale", "po", "co",
+            "tak", "od", "za", "przez", "jego", "oraz", "być", "są", "był", "także",
+        ],
+    ),
+    (
+        "cs",
+        "Czech",
+        &[
+            "a", "se", "na", "je", "že", "v", "to", "s", "z", "do", "jako", "ale", "by", "pro",
+            "jsou", "už", "jeho", "také", "bylo", "který", "která", "které", "podle", "byl",
+        ],
+    ),
+    (
+        "tr",
+        "Turkish",
+        &[
+            "ve", "bir", "bu", "da", "de", "için", "ile", "çok", "olarak", "daha", "gibi", "olan",
+            "ama", "en", "kadar", "sonra", "ise", "değil", "var", "her", "ancak", "göre", "şey",
+            "olduğu",
+        ],
+    ),
+];
+
+/// Writing systems that identify a language (or family) on their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Han,
+    Kana,
+    Hangul,
+    Thai,
+    Devanagari,
+}
+
+fn script_of(c: char) -> Option<Script> {
+    match c as u32 {
+        0x0041..=0x024F if c.is_alphabetic() => Some(Script::Latin),
+        0x0370..=0x03FF => Some(Script::Greek),
+        0x0400..=0x04FF => Some(Script::Cyrillic),
+        0x0590..=0x05FF => Some(Script::Hebrew),
+        0x0600..=0x06FF => Some(Script::Arabic),
+        0x0900..=0x097F => Some(Script::Devanagari),
+        0x0E00..=0x0E7F => Some(Script::Thai),
+        0x3040..=0x30FF => Some(Script::Kana),
+        0x4E00..=0x9FFF => Some(Script::Han),
+        0xAC00..=0xD7AF => Some(Script::Hangul),
+        _ => None,
+    }
+}
+
+/// Detect the dominant language of `text`
+#[must_use]
+pub fn detect_language(text: &str) -> Option<DetectedLanguage> {
+    let mut scripts: HashMap<Script, usize> = HashMap::new();
+    for script in text.chars().filter_map(script_of) {
+        *scripts.entry(script).or_default() += 1;
+    }
+    let letters: usize = scripts.values().sum();
+    if letters < MIN_LETTERS {
+        return None;
+    }
+    let count = |s: Script| scripts.get(&s).copied().unwrap_or(0);
+    let (script, dominant) = scripts
+        .iter()
+        .max_by_key(|(_, n)| **n)
+        .map(|(s, n)| (*s, *n))?;
+
+    let (code, name, share) = match script {
+        Script::Latin => return detect_latin(text),
+        // Japanese mixes kana with kanji; any notable kana share means Japanese
+        Script::Han | Script::Kana if count(Script::Kana) * 10 >= letters => {
+            ("ja", "Japanese", count(Script::Kana) + count(Script::Han))
+        }
+        Script::Han => ("zh", "Chinese", dominant),
+        Script::Kana => ("ja", "Japanese", dominant),
+        Script::Hangul => ("ko", "Korean", dominant),
+        Script::Cyrillic if text.contains(['і', 'ї', 'є', 'ґ']) => {
+            ("uk", "Ukrainian", dominant)
+        }
+        Script::Cyrillic => ("ru", "Russian", dominant),
+        Script::Greek => ("el", "Greek", dominant),
+        Script::Arabic => ("ar", "Arabic", dominant),
+        Script::Hebrew => ("he", "Hebrew", dominant),
+        Script::Thai => ("th", "Thai", dominant),
+        Script::Devanagari => ("hi", "Hindi", dominant),
+    };
+    Some(DetectedLanguage {
+        code,
+        name,
+        confidence: round2(share as f64 / letters as f64),
+    })
+}
+
+/// Score Latin-script text against the stopword lists
+fn detect_latin(text: &str) -> Option<DetectedLanguage> {
+    let lower = text.to_lowercase();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for word in lower
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+    {
+        *counts.entry(word).or_default() += 1;
+    }
+
+    let mut scores: Vec<(usize, &str, &str)> = STOPWORDS
+        .iter()
+        .map(|(code, name, words)| {
+            let hits = words
+                .iter()
+                .map(|w| counts.get(w).copied().unwrap_or(0))
+                .sum();
+            (hits, *code, *name)
+        })
+        .collect();
+    scores.sort_by_key(|s| std::cmp::Reverse(s.0));
+
+    let (best, code, name) = scores[0];
+    let runner_up = scores.get(1).map_or(0, |s| s.0);
+    if best < MIN_STOPWORD_HITS || best == runner_up {
+        return None;
+    }
+    Some(DetectedLanguage {
+        code,
+        name,
+        confidence: round2(best as f64 / (best + runner_up) as f64),
+    })
+}
+
+fn round2(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(text: &str) -> Option<&'static str> {
+        detect_language(text).map(|l| l.code)
+    }
+
+    #[test]
+    fn test_latin_languages() {
+        assert_eq!(
+            code("The quick brown fox jumps over the lazy dog, and it was not amused by this."),
+            Some("en")
+        );
+        assert_eq!(
+            code("Der schnelle braune Fuchs springt über den faulen Hund, und das ist nicht neu."),
+            Some("de")
+        );
+        assert_eq!(
+            code(
+                "Nopea ruskea kettu hyppää laiskan koiran yli, ja se on myös hauskaa kun ei sada."
+            ),
+            Some("fi")
+        );
+        assert_eq!(
+            code("Le renard brun rapide saute par-dessus le chien paresseux et les chats sont dans la maison."),
+            Some("fr")
+        );
+        assert_eq!(
+            code("El rápido zorro marrón salta sobre el perro perezoso y los gatos están en la casa con su dueño."),
+            Some("es")
+        );
+    }
+
+    #[test]
+    fn test_script_languages() {
+        assert_eq!(
+            code("Быстрая коричневая лиса прыгает через ленивую собаку."),
+            Some("ru")
+        );
+        assert_eq!(
+            code("Швидка руда лисиця перестрибує через ледачого пса і кота."),
+            Some("uk")
+        );
+        assert_eq!(
+            code("素早い茶色の狐がのろまな犬を飛び越えます。これはテストです。"),
+            Some("ja")
+        );
+        assert_eq!(
+            code("敏捷的棕色狐狸跳过了懒惰的狗，这是一个测试句子。"),
+            Some("zh")
+        );
+        assert_eq!(
+            code("빠른 갈색 여우가 게으른 개를 뛰어넘습니다. 테스트입니다."),
+            Some("ko")
+        );
+    }
+
+    #[test]
+    fn test_undetermined() {
+        assert_eq!(code("hello"), None);
+        assert_eq!(code("https://example.com/a/b/c 12345 67890"), None);
+
+        let detected =
+            detect_language("This is the text of the page, and it is in English.").unwrap();
+        assert!(detected.confidence > 0.5 && detected.confidence <= 1.0);
+        assert_eq!(detected.name, "English");
+    }
+}
@@ -0,0 +1,32 @@
+//! `nab-core` - nab's extraction core, without the network
+//!
+//! Everything `nab` does to a page after fetching it, as pure functions of
+//! the HTML: Markdown conversion, readability extraction, structured presets,
+//! language detection, and CSS selection. There is no tokio, reqwest, or
+//! filesystem access here, so the crate builds for `wasm32-wasip1` and runs
+//! in serverless and edge runtimes while the fetching happens elsewhere.
+//!
+//! ```bash
+//! rustup target add wasm32-wasip1
+//! cargo build -p nab-core --target wasm32-wasip1 --release
+//! ```
+//!
+//! # Example
+//!
+//! ```rust
+//! let html = "<html><body><h1>Hello</h1><p>From the edge.</p></body></html>";
+//! let markdown = nab_core::html_to_markdown(html);
+//! assert!(markdown.contains("From the edge."));
+//! ```
+
+pub mod extract;
+pub mod language;
+pub mod markdown;
+pub mod page;
+pub mod readability;
+
+pub use extract::{ArticleData, JobPosting, Listing, Preset, Product};
+pub use language::{detect_language, DetectedLanguage};
+pub use markdown::MarkdownStream;
+pub use page::{extract_links, html_to_markdown, is_boilerplate, markdown_lines, select_text};
+pub use readability::{extract_article, Article};
@@ -0,0 +1,588 @@
+//! Streaming HTML to Markdown
+//!
+//! [`MarkdownStream`] converts a page while it downloads: `push` takes the
+//! next chunk of the body and returns the Markdown lines it completed,
+//! `finish` flushes the rest. Tokens are borrowed from the input buffer, and
+//! only the unfinished tail (a tag, entity, or UTF-8 sequence cut by a chunk
+//! boundary) plus the current line are kept between chunks, so memory stays
+//! flat however large the page is.
+//!
+//! Covers what reading a page needs: headings, paragraphs, lists, quotes,
+//! code blocks, links, images, emphasis, and table rows. Scripts, styles,
+//! and other invisible elements are dropped.
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+
+/// Elements whose content is never shown (skipped up to their end tag)
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "title", "svg", "textarea",
+];
+
+/// Elements that start and end a line
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "body",
+    "details",
+    "div",
+    "dd",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "header",
+    "main",
+    "nav",
+    "p",
+    "section",
+    "summary",
+    "table",
+    "tr",
+];
+
+/// Longest entity waited for at a chunk boundary (`&CounterClockwiseContourIntegral;`)
+const MAX_ENTITY: usize = 33;
+
+/// Incremental HTML to Markdown converter
+#[derive(Debug, Default)]
+pub struct MarkdownStream {
+    /// Input not converted yet
+    pending: Vec<u8>,
+    /// Inside a [`SKIPPED`] element until its end tag
+    skip_until: Option<&'static str>,
+    /// Text of the line being built
+    line: String,
+    /// List item or heading marker for `line`
+    marker: String,
+    /// Whitespace seen since the last text
+    space: bool,
+    /// `<pre>` depth
+    pre: usize,
+    /// `<blockquote>` depth
+    quote: usize,
+    /// Open lists: next item number, `None` for bullets
+    lists: Vec<Option<usize>>,
+    /// `href` of each open `<a>`
+    links: Vec<Option<String>>,
+    /// Finished Markdown not handed out yet
+    out: String,
+}
+
+impl MarkdownStream {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next chunk of the body, getting back the lines it completed
+    pub fn push(&mut self, chunk: &[u8]) -> String {
+        let mut input = std::mem::take(&mut self.pending);
+        input.extend_from_slice(chunk);
+        let consumed = self.convert(&input, false);
+        input.drain(..consumed);
+        self.pending = input;
+        std::mem::take(&mut self.out)
+    }
+
+    /// End of the body: everything still buffered
+    pub fn finish(mut self) -> String {
+        let input = std::mem::take(&mut self.pending);
+        self.convert(&input, true);
+        self.flush();
+        if self.pre > 0 {
+            self.out.push_str("```\n");
+        }
+        self.out
+    }
+
+    /// Convert what's complete in `input`, returning how many bytes were used
+    fn convert(&mut self, input: &[u8], last: bool) -> usize {
+        let mut pos = 0;
+        loop {
+            if let Some(name) = self.skip_until {
+                match find_end_tag(&input[pos..], name) {
+                    Some(i) => {
+                        pos += i;
+                        self.skip_until = None;
+                    }
+                    None if last => return input.len(),
+                    // Keep what could be the start of the end tag
+                    None => return pos.max(input.len().saturating_sub(name.len() + 2)),
+                }
+            }
+            if pos >= input.len() {
+                return pos;
+            }
+            if input[pos] == b'<' {
+                match self.tag(&input[pos..]) {
+                    Some(len) => pos += len,
+                    None if last => {
+                        self.text(&String::from_utf8_lossy(&input[pos..]));
+                        return input.len();
+                    }
+                    None => return pos,
+                }
+                continue;
+            }
+            let end = input[pos..]
+                .iter()
+                .position(|&b| b == b'<')
+                .map_or(input.len(), |i| pos + i);
+            let text_end = if end == input.len() && !last {
+                complete_text_end(&input[pos..end]) + pos
+            } else {
+                end
+            };
+            self.text(&String::from_utf8_lossy(&input[pos..text_end]));
+            pos = text_end;
+            if text_end < end {
+                return pos;
+            }
+        }
+    }
+
+    /// Handle the tag (or comment) at the start of `input`, returning its
+    /// length, or `None` if it isn't complete yet
+    fn tag(&mut self, input: &[u8]) -> Option<usize> {
+        if input.starts_with(b"<!--") {
+            return find(input, b"-->", 4).map(|i| i + 3);
+        }
+        match input.get(1)? {
+            b'!' | b'?' => find(input, b">", 1).map(|i| i + 1),
+            b'/' => {
+                let end = tag_end(input)?;
+                let name = tag_name(&input[2..end]);
+                self.close(&name);
+                Some(end + 1)
+            }
+            c if c.is_ascii_alphabetic() => {
+                let end = tag_end(input)?;
+                let tag = String::from_utf8_lossy(&input[1..end]);
+                let name = tag_name(tag.as_bytes());
+                self.open(&name, &tag);
+                Some(end + 1)
+            }
+            // A lone `<` is text
+            _ => {
+                self.text("<");
+                Some(1)
+            }
+        }
+    }
+
+    fn open(&mut self, name: &str, tag: &str) {
+        if let Some(skipped) = SKIPPED.iter().find(|s| **s == name) {
+            if !tag.trim_end().ends_with('/') {
+                self.skip_until = Some(skipped);
+            }
+            return;
+        }
+        match name {
+            "br" => {
+                if self.pre > 0 {
+                    self.flush_pre_line();
+                } else {
+                    self.flush();
+                }
+            }
+            "hr" => {
+                self.flush();
+                self.emit("---");
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush();
+                let level = usize::from(name.as_bytes()[1] - b'0');
+                self.marker = format!("{} ", "#".repeat(level));
+            }
+            "ul" => {
+                self.flush();
+                self.lists.push(None);
+            }
+            "ol" => {
+                self.flush();
+                let start = attr(tag, "start").and_then(|s| s.parse().ok());
+                self.lists.push(Some(start.unwrap_or(1)));
+            }
+            "li" => {
+                self.flush();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                self.marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{indent}{}. ", *n - 1)
+                    }
+                    _ => format!("{indent}- "),
+                };
+            }
+            "blockquote" => {
+                self.flush();
+                self.quote += 1;
+            }
+            "pre" => {
+                self.flush();
+                self.pre += 1;
+                if self.pre == 1 {
+                    self.emit("```");
+                }
+            }
+            "td" | "th" if !self.line.is_empty() => {
+                self.line.push_str(" |");
+                self.space = true;
+            }
+            "strong" | "b" => self.inline("**"),
+            "em" | "i" => self.inline("*"),
+            "code" if self.pre == 0 => self.inline("`"),
+            "a" => {
+                let href = attr(tag, "href").filter(|h| !h.starts_with("javascript:"));
+                if href.is_some() {
+                    self.inline("[");
+                }
+                self.links.push(href);
+            }
+            "img" => {
+                if let Some(src) = attr(tag, "src") {
+                    let alt = attr(tag, "alt").unwrap_or_default();
+                    self.inline(&format!("![{alt}]({src})"));
+                }
+            }
+            _ if BLOCKS.contains(&name) => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "li" => self.flush(),
+            "ul" | "ol" => {
+                self.flush();
+                self.lists.pop();
+            }
+            "blockquote" => {
+                self.flush();
+                self.quote = self.quote.saturating_sub(1);
+            }
+            "pre" if self.pre > 0 => {
+                if !self.line.is_empty() {
+                    self.flush_pre_line();
+                }
+                if self.pre == 1 {
+                    self.emit("```");
+                }
+                self.pre -= 1;
+            }
+            "strong" | "b" => self.line.push_str("**"),
+            "em" | "i" => self.line.push('*'),
+            "code" if self.pre == 0 => self.line.push('`'),
+            "a" => {
+                if let Some(Some(href)) = self.links.pop() {
+                    let _ = write!(self.line, "]({href})");
+                }
+            }
+            _ if BLOCKS.contains(&name) => self.flush(),
+            _ => {}
+        }
+    }
+
+    /// Markup that starts inline content (takes the pending space)
+    fn inline(&mut self, markup: &str) {
+        if self.space && !self.line.is_empty() {
+            self.line.push(' ');
+        }
+        self.space = false;
+        self.line.push_str(markup);
+    }
+
+    fn text(&mut self, raw: &str) {
+        let text = decode_entities(raw);
+        if self.pre > 0 {
+            for (i, part) in text.split('\n').enumerate() {
+                if i > 0 {
+                    self.flush_pre_line();
+                }
+                self.line.push_str(part);
+            }
+            return;
+        }
+        for (i, word) in text.split(char::is_whitespace).enumerate() {
+            if i > 0 {
+                self.space = true;
+            }
+            if !word.is_empty() {
+                self.inline(word);
+            }
+        }
+    }
+
+    /// End the current line
+    fn flush(&mut self) {
+        self.space = false;
+        if self.pre > 0 {
+            if !self.line.is_empty() {
+                self.flush_pre_line();
+            }
+            return;
+        }
+        let text = std::mem::take(&mut self.line);
+        let text = text.trim();
+        if text.is_empty() {
+            // The marker waits for the item's text (e.g. `<li><p>...`)
+            return;
+        }
+        let marker = std::mem::take(&mut self.marker);
+        self.emit(&format!("{marker}{text}"));
+    }
+
+    fn flush_pre_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.emit(&line);
+    }
+
+    fn emit(&mut self, line: &str) {
+        for _ in 0..self.quote {
+            self.out.push_str("> ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+}
+
+/// Convert a whole document
+#[must_use]
+pub fn to_markdown(html: &str) -> String {
+    let mut stream = MarkdownStream::new();
+    let mut markdown = stream.push(html.as_bytes());
+    markdown.push_str(&stream.finish());
+    markdown
+}
+
+/// How much of a text run at the end of the input can be converted now:
+/// not an entity or UTF-8 character the next chunk may complete
+fn complete_text_end(text: &[u8]) -> usize {
+    let mut end = text.len();
+    if let Some(amp) = text.iter().rposition(|&b| b == b'&') {
+        let tail = &text[amp + 1..];
+        if tail.len() < MAX_ENTITY && tail.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'#') {
+            end = amp;
+        }
+    }
+    match std::str::from_utf8(&text[..end]) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => end,
+    }
+}
+
+/// Position of the `>` closing the tag at the start of `input`, outside quotes
+fn tag_end(input: &[u8]) -> Option<usize> {
+    let mut quote = None;
+    for (i, &b) in input.iter().enumerate().skip(1) {
+        match (quote, b) {
+            (None, b'"' | b'\'') => quote = Some(b),
+            (Some(q), _) if q == b => quote = None,
+            (None, b'>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tag_name(tag: &[u8]) -> String {
+    tag.iter()
+        .take_while(|b| b.is_ascii_alphanumeric())
+        .map(|b| char::from(b.to_ascii_lowercase()))
+        .collect()
+}
+
+/// Value of attribute `name` in the text of a start tag
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag.trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let attr_name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+        let value = if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (value, remainder) = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let close = after[1..].find(q).map_or(after.len(), |i| i + 1);
+                    (&after[1..close], after.get(close + 1..).unwrap_or_default())
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            rest = remainder;
+            value
+        } else {
+            ""
+        };
+        if attr_name.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value).into_owned());
+        }
+    }
+}
+
+/// Where `</name` starts in `input` (case-insensitive)
+fn find_end_tag(input: &[u8], name: &str) -> Option<usize> {
+    let mut from = 0;
+    while let Some(i) = find(input, b"</", from) {
+        let candidate = input.get(i + 2..i + 2 + name.len())?;
+        if candidate.eq_ignore_ascii_case(name.as_bytes()) {
+            return Some(i);
+        }
+        from = i + 2;
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|i| from + i)
+}
+
+/// Decode character references (`&amp;`, `&#233;`, `&#x2014;`, common named ones)
+fn decode_entities(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end < MAX_ENTITY)
+            .and_then(|end| Some((entity_char(&rest[1..=end])?, end + 2)));
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+fn entity_char(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "copy" => '©',
+        "reg" => '®',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "euro" => '€',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<!DOCTYPE html><html><head><title>Skip me</title>
+<style>p { color: red }</style><script>if (a < b) document.write("<p>no</p>")</script></head>
+<body><h1>Café &amp; Bar</h1>
+<p>Hello   <strong>bold</strong> and <a href="/menu?a=1&amp;b=2">the menu</a>.</p>
+<!-- a <p>comment</p> -->
+<ul><li>One</li><li><p>Two</p><ol start="3"><li>Three</li></ol></li></ul>
+<blockquote><p>Quoted &#8212; text</p></blockquote>
+<pre><code>fn main() {
+    println!("&lt;hi&gt;");
+}</code></pre>
+<img src="cat.png" alt="A cat"><br>Tail &mdash; end
+</body></html>"#;
+
+    const MARKDOWN: &str = "# Café & Bar
+Hello **bold** and [the menu](/menu?a=1&b=2).
+- One
+- Two
+  3. Three
+> Quoted — text
+```
+fn main() {
+    println!(\"<hi>\");
+}
+```
+![A cat](cat.png)
+Tail — end
+";
+
+    #[test]
+    fn test_to_markdown() {
+        assert_eq!(to_markdown(PAGE), MARKDOWN);
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        // Every split point: tags, entities, comments, script end tags, and
+        // multi-byte characters cut in two
+        for size in [1, 2, 3, 5, 7, 64] {
+            let mut stream = MarkdownStream::new();
+            let mut markdown = String::new();
+            for chunk in PAGE.as_bytes().chunks(size) {
+                markdown.push_str(&stream.push(chunk));
+            }
+            markdown.push_str(&stream.finish());
+            assert_eq!(markdown, MARKDOWN, "chunk size {size}");
+        }
+    }
+
+    #[test]
+    fn test_lines_are_emitted_early() {
+        let mut stream = MarkdownStream::new();
+        assert_eq!(stream.push(b"<h2>Title</h2><p>Still "), "## Title\n");
+        assert_eq!(stream.push(b"going</p><p>"), "Still going\n");
+        assert_eq!(stream.finish(), "");
+    }
+
+    #[test]
+    fn test_attr() {
+        let tag = r#"a class=nav href='/x?y=1&amp;z' data-x="a>b" hidden"#;
+        assert_eq!(attr(tag, "href").as_deref(), Some("/x?y=1&z"));
+        assert_eq!(attr(tag, "class").as_deref(), Some("nav"));
+        assert_eq!(attr(tag, "hidden").as_deref(), Some(""));
+        assert_eq!(attr(tag, "id"), None);
+        assert_eq!(
+            decode_entities("AT&T &#x41;&#66; &bogus;"),
+            "AT&T AB &bogus;"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! Page Conversion
+//!
+//! What `nab fetch` does to an HTML body once it has arrived: Markdown with
+//! navigation and legal boilerplate dropped, the page's links (or a link
+//! report with `rel` attributes), and the text of CSS-selected elements.
+
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use scraper::{Html, Selector};
+use serde::Serialize;
+use url::Url;
+
+/// Markdown for `html`, one non-empty line per block, boilerplate removed
+///
+/// Converted by [`MarkdownStream`](crate::MarkdownStream), so a page read
+/// whole and one streamed chunk by chunk (`fetch --stream`) come out the
+/// same.
+#[must_use]
+pub fn html_to_markdown(html: &str) -> String {
+    let md = crate::markdown::to_markdown(html);
+    markdown_lines(&md).collect::<Vec<_>>().join("\n")
+}
+
+/// The lines of converted Markdown that are kept: trimmed, neither empty
+/// nor boilerplate
+pub fn markdown_lines(markdown: &str) -> impl Iterator<Item = &str> {
+    markdown
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .filter(|l| !is_boilerplate(l))
+}
+
+/// Navigation and legal lines ("Skip to content", cookie banners, copyright)
+#[must_use]
+pub fn is_boilerplate(line: &str) -> bool {
+    // Preserve markdown links - never filter lines containing link syntax
+    if line.contains("](") {
+        return false;
+    }
+
+    let lower = line.to_lowercase();
+    // Skip common navigation/boilerplate patterns
+    lower.contains("skip to content")
+        || lower.contains("cookie")
+        || lower.contains("privacy policy")
+        || lower.contains("terms of service")
+        || lower.starts_with("©")
+        || lower.starts_with("copyright")
+        || (lower.len() < 3 && !lower.chars().any(char::is_alphanumeric))
+}
+
+/// `(text, href)` of each distinct link, skipping anchors and `javascript:`
+#[must_use]
+pub fn extract_links(html: &str) -> Vec<(String, String)> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("a[href]").unwrap();
+
+    let mut links = Vec::new();
+    let mut seen = HashSet::new();
+
+    for element in document.select(&selector) {
+        if let Some(href) = element.value().attr("href") {
+            // Skip anchors, javascript, and duplicates
+            if href.starts_with('#') || href.starts_with("javascript:") || seen.contains(href) {
+                continue;
+            }
+            seen.insert(href.to_string());
+
+            let text = element
+                .text()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .trim()
+                .to_string();
+
+            links.push((text, href.to_string()));
+        }
+    }
+
+    links
+}
+
+/// A link as an SEO report shows it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageLink {
+    /// Absolute URL
+    pub url: String,
+    /// Anchor text, whitespace collapsed (an image link's `alt` text)
+    pub text: String,
+    /// `rel` tokens, lowercase
+    pub rel: Vec<String>,
+    /// `rel=nofollow`, or a `nofollow` robots meta tag on the page
+    pub nofollow: bool,
+    pub sponsored: bool,
+    pub ugc: bool,
+    /// Points to another host than the page
+    pub external: bool,
+}
+
+/// Every distinct http(s) link of a page at `base`, in document order
+#[must_use]
+pub fn link_report(html: &str, base: &Url) -> Vec<PageLink> {
+    let document = Html::parse_document(html);
+    let robots = Selector::parse(r#"meta[name="robots" i], meta[name="googlebot" i]"#).unwrap();
+    let page_nofollow = document.select(&robots).any(|meta| {
+        meta.value()
+            .attr("content")
+            .is_some_and(|content| content.to_lowercase().contains("nofollow"))
+    });
+    // Relative links resolve against <base href> when the page has one
+    let base = Selector::parse("base[href]")
+        .ok()
+        .and_then(|s| document.select(&s).next())
+        .and_then(|b| base.join(b.value().attr("href")?).ok())
+        .unwrap_or_else(|| base.clone());
+
+    let selector = Selector::parse("a[href], area[href]").unwrap();
+    let mut seen = HashSet::new();
+    let mut links = Vec::new();
+    for element in document.select(&selector) {
+        let Some(href) = element.value().attr("href") else {
+            continue;
+        };
+        let Ok(url) = base.join(href.trim()) else {
+            continue;
+        };
+        if !matches!(url.scheme(), "http" | "https") || href.starts_with('#') {
+            continue;
+        }
+        let mut text = element
+            .text()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if text.is_empty() {
+            text = element
+                .select(&Selector::parse("img[alt]").unwrap())
+                .next()
+                .and_then(|img| img.value().attr("alt"))
+                .or_else(|| element.value().attr("alt"))
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+        }
+        let rel: Vec<String> = element
+            .value()
+            .attr("rel")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+        if !seen.insert((url.to_string(), text.clone(), rel.clone())) {
+            continue;
+        }
+        let has = |token: &str| rel.iter().any(|r| r == token);
+        links.push(PageLink {
+            external: url.host_str() != base.host_str(),
+            nofollow: page_nofollow || has("nofollow"),
+            sponsored: has("sponsored"),
+            ugc: has("ugc"),
+            url: url.to_string(),
+            text,
+            rel,
+        });
+    }
+    links
+}
+
+/// Whitespace-collapsed text of every element matching the CSS `selector`
+pub fn select_text(html: &str, selector: &str) -> Result<Vec<String>> {
+    let selector =
+        Selector::parse(selector).map_err(|e| anyhow!("Invalid selector '{selector}': {e}"))?;
+    Ok(Html::parse_document(html)
+        .select(&selector)
+        .map(|element| element.text().collect::<Vec<_>>().join(" "))
+        .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r##"<html><body>
+        <a href="#main">Skip to content</a>
+        <h1>Release notes</h1>
+        <p>Version  <b>2.0</b> is out.</p>
+        <p>We use cookies.</p>
+        <a href="/download">Download</a> <a href="/download">Again</a>
+        <a href="javascript:void(0)">Menu</a>
+        <footer>© 2026 Example</footer>
+    </body></html>"##;
+
+    #[test]
+    fn test_html_to_markdown() {
+        let md = html_to_markdown(PAGE);
+        assert!(md.lines().any(|line| line == "# Release notes"), "{md}");
+        assert!(md.contains("Version **2.0** is out."));
+        assert!(md.contains("[Download](/download)"));
+        assert!(!md.contains("cookies"));
+        assert!(!md.contains("© 2026"));
+        assert!(!md.lines().any(str::is_empty));
+    }
+
+    #[test]
+    fn test_streamed_markdown_matches_whole_page() {
+        for chunk_size in [1, 7, 64, PAGE.len()] {
+            let mut stream = crate::MarkdownStream::new();
+            let mut streamed = Vec::new();
+            for chunk in PAGE.as_bytes().chunks(chunk_size) {
+                streamed.extend(markdown_lines(&stream.push(chunk)).map(String::from));
+            }
+            streamed.extend(markdown_lines(&stream.finish()).map(String::from));
+            assert_eq!(
+                streamed.join("\n"),
+                html_to_markdown(PAGE),
+                "chunks of {chunk_size}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_links() {
+        assert_eq!(
+            extract_links(PAGE),
+            [("Download".to_string(), "/download".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_link_report() {
+        let base = Url::parse("https://example.com/blog/post").unwrap();
+        let links = link_report(
+            r##"<a href="/about">About
+                 us</a>
+            <a href="https://partner.test/deal" rel="Sponsored NOFOLLOW">Deal</a>
+            <a href="https://forum.test/u/1" rel="ugc"><img src="a.png" alt="Profile"></a>
+            <a href="mailto:me@example.com">Mail</a> <a href="#top">Top</a>
+            <a href="/about">About us</a>"##,
+            &base,
+        );
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].url, "https://example.com/about");
+        assert_eq!(links[0].text, "About us");
+        assert!(!links[0].external && !links[0].nofollow);
+        assert_eq!(links[1].rel, ["sponsored", "nofollow"]);
+        assert!(links[1].external && links[1].nofollow && links[1].sponsored);
+        assert_eq!(links[2].text, "Profile");
+        assert!(links[2].ugc && !links[2].nofollow);
+
+        let nofollow = link_report(
+            r#"<meta name="robots" content="noindex, nofollow"><base href="/docs/"><a href="intro">Intro</a>"#,
+            &base,
+        );
+        assert_eq!(nofollow[0].url, "https://example.com/docs/intro");
+        assert!(nofollow[0].nofollow);
+    }
+
+    #[test]
+    fn test_select_text() {
+        assert_eq!(
+            select_text(PAGE, "p").unwrap(),
+            ["Version 2.0 is out.", "We use cookies."]
+        );
+        assert!(select_text(PAGE, "p[").is_err());
+    }
+}
@@ -0,0 +1,488 @@
+//! Readability Extraction
+//!
+//! Pulls the main article out of a page, Readability-style:
+//! - Scores paragraph containers by text length and link density
+//! - Penalizes navigation, sidebars, comments, and promo blocks
+//! - Re-serializes the winner as clean, well-formed XHTML (safe for EPUB)
+//! - Collects metadata from `OpenGraph`, `<meta>`, and `<title>`
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use ego_tree::NodeRef;
+use scraper::{ElementRef, Html, Node, Selector};
+
+/// Main content and metadata of an article page
+#[derive(Debug, Clone, Default)]
+pub struct Article {
+    /// Article title
+    pub title: String,
+    /// Author, when the page declares one
+    pub byline: Option<String>,
+    /// Publication or site name
+    pub site_name: Option<String>,
+    /// Short description or standfirst
+    pub description: Option<String>,
+    /// Lead image URL (absolute when a base URL was given)
+    pub image: Option<String>,
+    /// Document language (`<html lang>`)
+    pub language: Option<String>,
+    /// Publication date as declared by the page
+    pub published: Option<String>,
+    /// Cleaned main content as an XHTML fragment
+    pub content_html: String,
+}
+
+impl Article {
+    /// Main content as Markdown
+    #[must_use]
+    pub fn markdown(&self) -> String {
+        let md = html2md::parse_html(&self.content_html);
+        let mut out = String::with_capacity(md.len());
+        let mut blank = 0;
+        for line in md.lines().map(str::trim_end) {
+            if line.is_empty() {
+                blank += 1;
+                if blank > 1 {
+                    continue;
+                }
+            } else {
+                blank = 0;
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.trim().to_string()
+    }
+
+    /// Visible text length of the extracted content
+    #[must_use]
+    pub fn text_len(&self) -> usize {
+        Html::parse_fragment(&self.content_html)
+            .root_element()
+            .text()
+            .map(|t| t.trim().len())
+            .sum()
+    }
+}
+
+/// Tags kept in extracted content (everything else is unwrapped or dropped)
+const KEEP_TAGS: &[&str] = &[
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "li",
+    "blockquote",
+    "pre",
+    "code",
+    "em",
+    "i",
+    "strong",
+    "b",
+    "a",
+    "img",
+    "figure",
+    "figcaption",
+    "table",
+    "thead",
+    "tbody",
+    "tfoot",
+    "tr",
+    "th",
+    "td",
+    "caption",
+    "br",
+    "hr",
+    "sup",
+    "sub",
+    "dl",
+    "dt",
+    "dd",
+    "q",
+    "cite",
+    "abbr",
+    "mark",
+    "s",
+    "del",
+    "ins",
+    "small",
+];
+
+/// Tags dropped together with their contents
+const DROP_TAGS: &[&str] = &[
+    "script", "style", "noscript", "nav", "aside", "form", "footer", "header", "iframe", "button",
+    "svg", "input", "select", "textarea", "object", "embed", "template", "canvas", "video",
+    "audio", "dialog", "link", "meta",
+];
+
+const VOID_TAGS: &[&str] = &["br", "hr", "img"];
+
+/// Class/id fragments that mark non-content blocks
+const UNLIKELY: &[&str] = &[
+    "comment",
+    "sidebar",
+    "footer",
+    "navbar",
+    "menu",
+    "share",
+    "social",
+    "related",
+    "promo",
+    "advert",
+    "sponsor",
+    "newsletter",
+    "subscribe",
+    "cookie",
+    "banner",
+    "popup",
+    "modal",
+    "breadcrumb",
+    "pagination",
+    "widget",
+];
+
+/// Class/id fragments that mark content blocks
+const LIKELY: &[&str] = &[
+    "article", "content", "entry", "main", "post", "story", "body", "text",
+];
+
+/// Paragraphs shorter than this are ignored when scoring
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+/// Extract the main article from an HTML page
+///
+/// `base_url` resolves relative links and image sources.
+#[must_use]
+pub fn extract_article(html: &str, base_url: Option<&url::Url>) -> Article {
+    let document = Html::parse_document(html);
+
+    let title = meta_content(&document, "meta[property='og:title']")
+        .or_else(|| select_text(&document, "title"))
+        .or_else(|| select_text(&document, "h1"))
+        .unwrap_or_default();
+
+    let content_html = best_candidate(&document).map_or_else(String::new, |el| {
+        let mut out = String::new();
+        serialize_children(*el, base_url, &title, &mut out);
+        out.trim().to_string()
+    });
+
+    Article {
+        byline: meta_content(&document, "meta[name='author']")
+            .or_else(|| meta_content(&document, "meta[property='article:author']"))
+            .filter(|a| !a.starts_with("http"))
+            .or_else(|| select_text(&document, "[rel='author']"))
+            .or_else(|| select_text(&document, ".byline")),
+        site_name: meta_content(&document, "meta[property='og:site_name']"),
+        description: meta_content(&document, "meta[property='og:description']")
+            .or_else(|| meta_content(&document, "meta[name='description']")),
+        image: meta_content(&document, "meta[property='og:image']")
+            .map(|src| resolve(&src, base_url)),
+        published: meta_content(&document, "meta[property='article:published_time']")
+            .or_else(|| meta_content(&document, "meta[name='date']"))
+            .or_else(|| select_first_attr(&document, "time[datetime]", "datetime")),
+        language: Selector::parse("html")
+            .ok()
+            .and_then(|s| document.select(&s).next())
+            .and_then(|el| el.value().attr("lang"))
+            .map(String::from),
+        title,
+        content_html,
+    }
+}
+
+/// Pick the element holding the article body
+fn best_candidate(document: &Html) -> Option<ElementRef<'_>> {
+    let paragraphs = Selector::parse("p, pre, blockquote").unwrap();
+    let mut scores: HashMap<ego_tree::NodeId, f64> = HashMap::new();
+
+    for p in document.select(&paragraphs) {
+        let len = text_len(p);
+        if len < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        // Base point + one per 100 chars, like Readability
+        #[allow(clippy::cast_precision_loss)]
+        let score = 1.0 + (len as f64 / 100.0).min(3.0) + len as f64 / 1000.0;
+        let mut ancestors = p.ancestors().filter_map(ElementRef::wrap);
+        if let Some(parent) = ancestors.next() {
+            *scores.entry(parent.id()).or_default() += score;
+        }
+        if let Some(grandparent) = ancestors.next() {
+            *scores.entry(grandparent.id()).or_default() += score / 2.0;
+        }
+    }
+
+    let best = scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let el = ElementRef::wrap(document.tree.get(id)?)?;
+            Some((el, score * (1.0 - link_density(el)) + class_weight(el)))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(el, _)| el);
+
+    best.or_else(|| {
+        ["article", "main", "[role='main']", "body"]
+            .iter()
+            .filter_map(|s| Selector::parse(s).ok())
+            .find_map(|s| document.select(&s).next())
+    })
+}
+
+fn class_weight(el: ElementRef<'_>) -> f64 {
+    let name = el.value().name();
+    let hint = format!(
+        "{} {}",
+        el.value().attr("class").unwrap_or_default(),
+        el.value().attr("id").unwrap_or_default()
+    )
+    .to_lowercase();
+
+    let mut weight = 0.0;
+    if name == "article" || name == "main" {
+        weight += 10.0;
+    }
+    if LIKELY.iter().any(|l| hint.contains(l)) {
+        weight += 5.0;
+    }
+    if UNLIKELY.iter().any(|u| hint.contains(u)) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+fn text_len(el: ElementRef<'_>) -> usize {
+    el.text().map(|t| t.trim().len()).sum()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn link_density(el: ElementRef<'_>) -> f64 {
+    let total = text_len(el);
+    if total == 0 {
+        return 0.0;
+    }
+    let links = Selector::parse("a").unwrap();
+    let linked: usize = el.select(&links).map(text_len).sum();
+    linked as f64 / total as f64
+}
+
+fn is_unlikely(el: &scraper::node::Element) -> bool {
+    let hint = format!(
+        "{} {}",
+        el.attr("class").unwrap_or_default(),
+        el.attr("id").unwrap_or_default()
+    )
+    .to_lowercase();
+    !LIKELY.iter().any(|l| hint.contains(l)) && UNLIKELY.iter().any(|u| hint.contains(u))
+}
+
+fn serialize_children(
+    node: NodeRef<'_, Node>,
+    base_url: Option<&url::Url>,
+    title: &str,
+    out: &mut String,
+) {
+    for child in node.children() {
+        serialize_node(child, base_url, title, out);
+    }
+}
+
+/// Write a node as XHTML, keeping only content tags and safe attributes
+fn serialize_node(
+    node: NodeRef<'_, Node>,
+    base_url: Option<&url::Url>,
+    title: &str,
+    out: &mut String,
+) {
+    match node.value() {
+        Node::Text(text) => out.push_str(&escape_xml(text)),
+        Node::Element(el) => {
+            let name = el.name();
+            if DROP_TAGS.contains(&name) || is_unlikely(el) {
+                return;
+            }
+            // The title is shown separately
+            if name == "h1" {
+                let text: String = ElementRef::wrap(node)
+                    .map(|e| e.text().collect())
+                    .unwrap_or_default();
+                if text.trim() == title.trim() {
+                    return;
+                }
+            }
+            if !KEEP_TAGS.contains(&name) {
+                serialize_children(node, base_url, title, out);
+                return;
+            }
+
+            out.push('<');
+            out.push_str(name);
+            match name {
+                "a" => {
+                    if let Some(href) = el.attr("href").filter(|h| !h.starts_with("javascript:")) {
+                        let _ = write!(out, " href=\"{}\"", escape_xml(&resolve(href, base_url)));
+                    }
+                }
+                "img" => {
+                    let src = el
+                        .attr("src")
+                        .filter(|s| !s.starts_with("data:"))
+                        .or_else(|| el.attr("data-src"))
+                        .unwrap_or_default();
+                    let _ = write!(
+                        out,
+                        " src=\"{}\" alt=\"{}\"",
+                        escape_xml(&resolve(src, base_url)),
+                        escape_xml(el.attr("alt").unwrap_or_default())
+                    );
+                }
+                "td" | "th" => {
+                    for attr in ["colspan", "rowspan"] {
+                        if let Some(v) = el.attr(attr) {
+                            let _ = write!(out, " {attr}=\"{}\"", escape_xml(v));
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            if VOID_TAGS.contains(&name) {
+                out.push_str("/>");
+                return;
+            }
+            out.push('>');
+            serialize_children(node, base_url, title, out);
+            let _ = write!(out, "</{name}>");
+        }
+        _ => {}
+    }
+}
+
+/// Resolve `href` against `base_url` (unchanged without a base)
+fn resolve(href: &str, base_url: Option<&url::Url>) -> String {
+    base_url
+        .and_then(|base| base.join(href).ok())
+        .map_or_else(|| href.to_string(), |u| u.to_string())
+}
+
+/// Escape text for XML content and attribute values
+#[must_use]
+pub fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\u{a0}' => out.push_str("&#160;"),
+            c if c.is_control() && c != '\n' && c != '\t' && c != '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn meta_content(document: &Html, selector: &str) -> Option<String> {
+    let selector = Selector::parse(selector).ok()?;
+    document
+        .select(&selector)
+        .find_map(|el| el.value().attr("content"))
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(String::from)
+}
+
+fn select_first_attr(document: &Html, selector: &str, attr: &str) -> Option<String> {
+    let selector = Selector::parse(selector).ok()?;
+    document
+        .select(&selector)
+        .find_map(|el| el.value().attr(attr))
+        .map(String::from)
+}
+
+fn select_text(document: &Html, selector: &str) -> Option<String> {
+    let selector = Selector::parse(selector).ok()?;
+    document
+        .select(&selector)
+        .map(|el| el.text().collect::<String>().trim().to_string())
+        .find(|t| !t.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html lang="en"><head>
+        <title>Fallback Title</title>
+        <meta property="og:title" content="The Real Headline">
+        <meta property="og:site_name" content="Example Times">
+        <meta property="og:image" content="/img/lead.jpg">
+        <meta name="author" content="Jane Writer">
+        <meta property="article:published_time" content="2024-05-01T08:00:00Z">
+        </head><body>
+        <nav><a href="/">Home</a><a href="/news">News</a></nav>
+        <div class="sidebar"><p>Trending: some other story you might like to read today.</p></div>
+        <article class="post-content">
+            <h1>The Real Headline</h1>
+            <p>First paragraph of the article, long enough to count as real content.</p>
+            <p>Second paragraph with a <a href="/more">relative link</a> &amp; an entity.</p>
+            <img data-src="photo.png" alt="A photo">
+            <script>trackReader();</script>
+            <div class="share-buttons"><p>Share this article on every network out there.</p></div>
+        </article>
+        <footer><p>Copyright notice and a long list of footer links for the site.</p></footer>
+        </body></html>"#;
+
+    #[test]
+    fn test_extracts_main_content() {
+        let base = url::Url::parse("https://example.com/news/story").unwrap();
+        let article = extract_article(PAGE, Some(&base));
+
+        assert_eq!(article.title, "The Real Headline");
+        assert_eq!(article.byline.as_deref(), Some("Jane Writer"));
+        assert_eq!(article.site_name.as_deref(), Some("Example Times"));
+        assert_eq!(article.language.as_deref(), Some("en"));
+        assert_eq!(article.published.as_deref(), Some("2024-05-01T08:00:00Z"));
+        assert_eq!(
+            article.image.as_deref(),
+            Some("https://example.com/img/lead.jpg")
+        );
+
+        let html = &article.content_html;
+        assert!(html.contains("First paragraph"));
+        assert!(html.contains("href=\"https://example.com/more\""));
+        assert!(html.contains("src=\"https://example.com/news/photo.png\""));
+        assert!(html.contains("&amp; an entity"));
+        assert!(!html.contains("Trending"));
+        assert!(!html.contains("trackReader"));
+        assert!(!html.contains("Share this"));
+        assert!(!html.contains("<h1>"));
+    }
+
+    #[test]
+    fn test_xhtml_void_tags() {
+        let article = extract_article(
+            "<body><div><p>Line one of a reasonably long paragraph<br>line two</p><hr></div></body>",
+            None,
+        );
+        assert!(article.content_html.contains("<br/>"));
+        assert!(article.content_html.contains("<hr/>"));
+    }
+
+    #[test]
+    fn test_markdown() {
+        let article = extract_article(PAGE, None);
+        let md = article.markdown();
+        assert!(md.contains("First paragraph"));
+        assert!(!md.contains("\n\n\n"));
+        assert!(article.text_len() > 100);
+    }
+}
@@ -0,0 +1,42 @@
+/*
+ * nab C API: fingerprinted fetch to Markdown, in-process.
+ *
+ * Link against libnab_ffi (cargo build -p nab-ffi --release).
+ * Calls block the calling thread and are safe from several threads at once.
+ */
+
+#ifndef NAB_H
+#define NAB_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Fetch url and return its Markdown, or NULL on error (see nab_last_error).
+ *
+ * options_json may be NULL or a JSON object with any of:
+ *   "profile":  "chrome" | "firefox" | "safari" | "random" (default)
+ *   "proxy":    proxy URL (http, https, socks5, socks5h)
+ *   "cookies":  browser to send cookies from ("brave", "chrome", "edge", "firefox", "safari")
+ *   "headers":  object of extra request headers
+ *   "max_body": cut the Markdown at this many bytes (0 = no limit)
+ *
+ * Free the result with nab_string_free.
+ */
+char *nab_fetch_markdown(const char *url, const char *options_json);
+
+/*
+ * Why the last call on this thread returned NULL, or NULL if it didn't.
+ * Owned by nab; valid until the thread's next call.
+ */
+const char *nab_last_error(void);
+
+/* Free a string returned by nab_fetch_markdown. NULL is ignored. */
+void nab_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* NAB_H */
@@ -0,0 +1,279 @@
+//! C ABI and Python Bindings
+//!
+//! nab's fingerprinted fetch and Markdown conversion, callable in-process
+//! instead of spawning `nab fetch` and parsing its stdout:
+//!
+//! ```c
+//! char *md = nab_fetch_markdown("https://example.com", "{\"profile\": \"firefox\"}");
+//! if (md == NULL) {
+//!     fprintf(stderr, "%s\n", nab_last_error());
+//! } else {
+//!     puts(md);
+//!     nab_string_free(md);
+//! }
+//! ```
+//!
+//! The header is `include/nab.h`. With the `python` feature the same library
+//! is the `nab` Python module (`nab.fetch_markdown(url, profile="firefox")`),
+//! built by maturin from `pyproject.toml`.
+//!
+//! Calls block the calling thread; they share one tokio runtime, started on
+//! first use. The domain policy (`nab::policy`) is installed then too, and
+//! a policy that can't be read fails every call.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, bail, Context, Result};
+use nab::{AcceleratedClient, FetchContext, RequestOptions};
+use reqwest::header::{CONTENT_TYPE, COOKIE};
+use serde::Deserialize;
+
+#[cfg(feature = "python")]
+mod python;
+
+/// What `options_json` can set; every field is optional
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchOptions {
+    /// Browser fingerprint: `chrome`, `firefox`, `safari`, or `random` (default)
+    pub profile: Option<String>,
+    /// Proxy URL (http, https, socks5, or socks5h)
+    pub proxy: Option<String>,
+    /// Browser to send cookies from (`brave`, `chrome`, `edge`, `firefox`, `safari`)
+    pub cookies: Option<String>,
+    /// Extra request headers
+    pub headers: BTreeMap<String, String>,
+    /// Cut the Markdown at this many bytes (0 = no limit)
+    pub max_body: usize,
+}
+
+impl FetchOptions {
+    /// Parse an options object; an empty string means the defaults
+    pub fn from_json(json: &str) -> Result<Self> {
+        if json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(json).context("Invalid options JSON")
+    }
+}
+
+static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+fn runtime() -> Result<&'static tokio::runtime::Runtime> {
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    nab::policy::install(nab::policy::load()?);
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start the tokio runtime")?;
+    // Another thread may have won the race; its runtime is used instead
+    let _ = RUNTIME.set(runtime);
+    Ok(RUNTIME.get().expect("runtime was just set"))
+}
+
+/// Fetch `url` like `nab fetch` and return its Markdown
+///
+/// Non-HTML bodies are returned as they are. Error statuses are errors.
+pub fn fetch_markdown(url: &str, options: &FetchOptions) -> Result<String> {
+    runtime()?.block_on(fetch(url, options))
+}
+
+async fn fetch(url: &str, options: &FetchOptions) -> Result<String> {
+    let page = reqwest::Url::parse(url).with_context(|| format!("Invalid URL '{url}'"))?;
+    nab::policy::check_url(&page)?;
+    let mut builder = RequestOptions::builder()
+        .profile_name(options.profile.as_deref().unwrap_or("random"))
+        .cookies(options.cookies.as_deref().unwrap_or("none"))
+        .max_body(options.max_body);
+    if let Some(proxy) = &options.proxy {
+        builder = builder.proxy(proxy);
+    }
+    for (name, value) in &options.headers {
+        builder = builder.header(format!("{name}: {value}"));
+    }
+    let request_options = builder.build()?;
+    let client = AcceleratedClient::for_request(&request_options)?;
+    let profile = client.profile().await;
+
+    let mut request = client
+        .inner()
+        .get(page.clone())
+        .headers(profile.request_headers(FetchContext::Navigate, "none"));
+    let cookies = request_options
+        .cookies
+        .header_for(page.host_str().unwrap_or_default());
+    if !cookies.is_empty() {
+        request = request.header(COOKIE, cookies);
+    }
+    request = request.headers(request_options.headers.clone());
+
+    let response = request_options
+        .send(request)
+        .await
+        .map_err(|e| request_options.client.explain(url, e))?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("HTTP {status} from {}", response.url());
+    }
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|ct| ct.contains("html"));
+    let body = response.text().await?;
+    let mut markdown = if is_html {
+        nab::page::html_to_markdown(&body)
+    } else {
+        body
+    };
+    let max_body = request_options.max_body;
+    if max_body > 0 && markdown.len() > max_body {
+        let mut end = max_body;
+        while !markdown.is_char_boundary(end) {
+            end -= 1;
+        }
+        markdown.truncate(end);
+    }
+    Ok(markdown)
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(error: Option<&anyhow::Error>) {
+    let message =
+        error.map(|e| CString::new(format!("{e:#}").replace('\0', "")).unwrap_or_default());
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Borrow a C string argument
+///
+/// # Safety
+///
+/// `s` must be NULL or point to a NUL-terminated string that outlives the call.
+unsafe fn c_str<'a>(s: *const c_char, what: &str) -> Result<Option<&'a str>> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: non-NULL and NUL-terminated, as the caller promised
+    let s = unsafe { CStr::from_ptr(s) };
+    s.to_str()
+        .map(Some)
+        .map_err(|_| anyhow!("{what} is not valid UTF-8"))
+}
+
+/// Fetch `url` and return its Markdown as a new string, or NULL on error
+///
+/// `options_json` may be NULL or a JSON object of [`FetchOptions`]. Free the
+/// result with [`nab_string_free`]; on NULL, [`nab_last_error`] says why.
+///
+/// # Safety
+///
+/// `url` and `options_json` must each be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nab_fetch_markdown(
+    url: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: both are NULL or NUL-terminated (this function's contract)
+        let url = unsafe { c_str(url, "url") }?.context("url is NULL")?;
+        let options = unsafe { c_str(options_json, "options_json") }?;
+        let options = FetchOptions::from_json(options.unwrap_or_default())?;
+        let markdown = fetch_markdown(url, &options)?;
+        Ok(CString::new(markdown.replace('\0', ""))?)
+    }))
+    .unwrap_or_else(|_| Err(anyhow!("nab panicked while fetching")));
+    match result {
+        Ok(markdown) => {
+            set_last_error(None);
+            markdown.into_raw()
+        }
+        Err(e) => {
+            set_last_error(Some(&e));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Why the last call on this thread returned NULL, or NULL if it didn't
+///
+/// The string belongs to nab and stays valid until the thread's next call.
+#[no_mangle]
+pub extern "C" fn nab_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Free a string returned by nab
+///
+/// # Safety
+///
+/// `s` must be NULL or a pointer returned by [`nab_fetch_markdown`] that
+/// hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn nab_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: allocated by CString::into_raw in nab_fetch_markdown
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        let error = nab_last_error();
+        assert!(!error.is_null());
+        // SAFETY: nab_last_error returns a live NUL-terminated string
+        unsafe { CStr::from_ptr(error) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_options_json() {
+        assert_eq!(
+            FetchOptions::from_json("").unwrap(),
+            FetchOptions::default()
+        );
+        let options =
+            FetchOptions::from_json(r#"{"profile": "safari", "headers": {"X-Test": "1"}}"#)
+                .unwrap();
+        assert_eq!(options.profile.as_deref(), Some("safari"));
+        assert_eq!(options.headers["X-Test"], "1");
+        assert!(FetchOptions::from_json(r#"{"proflie": "safari"}"#).is_err());
+    }
+
+    #[test]
+    fn test_errors_through_the_c_abi() {
+        // SAFETY: NULL and NUL-terminated literals are valid arguments
+        let result = unsafe { nab_fetch_markdown(ptr::null(), ptr::null()) };
+        assert!(result.is_null());
+        assert_eq!(last_error(), "url is NULL");
+
+        let result = unsafe { nab_fetch_markdown(c"not a url".as_ptr(), ptr::null()) };
+        assert!(result.is_null());
+        assert!(last_error().starts_with("Invalid URL 'not a url'"));
+
+        let result = unsafe {
+            nab_fetch_markdown(
+                c"http://127.0.0.1:9/".as_ptr(),
+                cr#"{"profile": "netscape"}"#.as_ptr(),
+            )
+        };
+        assert!(result.is_null());
+        assert!(last_error().contains("Unknown profile 'netscape'"));
+
+        // SAFETY: NULL is a no-op
+        unsafe { nab_string_free(ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,44 @@
+//! `nab` Python module (`--features python`, built with maturin)
+//!
+//! ```python
+//! import nab
+//! markdown = nab.fetch_markdown("https://example.com", profile="firefox", max_body=20_000)
+//! ```
+
+use std::collections::BTreeMap;
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+use crate::FetchOptions;
+
+/// Fetch a page with a browser fingerprint and return its Markdown
+#[pyfunction]
+#[pyo3(signature = (url, *, profile=None, proxy=None, cookies=None, headers=None, max_body=0))]
+fn fetch_markdown(
+    py: Python<'_>,
+    url: &str,
+    profile: Option<String>,
+    proxy: Option<String>,
+    cookies: Option<String>,
+    headers: Option<BTreeMap<String, String>>,
+    max_body: usize,
+) -> PyResult<String> {
+    let options = FetchOptions {
+        profile,
+        proxy,
+        cookies,
+        headers: headers.unwrap_or_default(),
+        max_body,
+    };
+    // Other Python threads keep running while the page downloads
+    py.allow_threads(|| crate::fetch_markdown(url, &options))
+        .map_err(|e| PyRuntimeError::new_err(format!("{e:#}")))
+}
+
+#[pymodule]
+#[pyo3(name = "nab")]
+fn nab_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(fetch_markdown, m)?)?;
+    Ok(())
+}
@@ -37,6 +37,7 @@ async fn main() -> Result<()> {
         quality: nab::stream::StreamQuality::Best,
         headers: std::collections::HashMap::new(),
         cookies: None,
+        audio_languages: Vec::new(),
     };
 
     // Progress callback
@@ -0,0 +1,705 @@
+//! Accessibility Quick Report (`nab audit a11y`)
+//!
+//! Fetches a page like [`audit::fetch`](crate::audit::fetch) and checks its
+//! parsed DOM for the structural problems that are cheap to find without a
+//! browser:
+//!
+//! | Rule | Severity | Flags |
+//! |------|----------|-------|
+//! | `image-alt` | error | `img`, `area`, and image inputs without `alt` (unless hidden from assistive technology) |
+//! | `document-lang` | error | `<html>` without `lang` |
+//! | `document-title` | error | a missing or empty `<title>` |
+//! | `heading-order` | error | a heading more than one level below the previous one (`h2` → `h4`) |
+//! | `heading-one` | warning | no `h1`, or more than one |
+//! | `empty-heading` | warning | headings without text |
+//! | `landmark-main` | error/warning | no `main` landmark (error), or several (warning) |
+//! | `link-name` | error | links without text, `aria-label`, `title`, or an image with `alt` |
+//! | `button-name` | error | buttons without an accessible name |
+//! | `form-label` | error | form fields without a `<label>`, `aria-label`, `aria-labelledby`, or `title` |
+//! | `duplicate-id` | warning | `id`s used more than once |
+//! | `meta-viewport` | error | `user-scalable=no` or a `maximum-scale` below 2 |
+//! | `color-contrast` | error/warning | text whose inline `color` and `background` contrast less than 3:1 (error) or 4.5:1 (warning) |
+//!
+//! Contrast is a heuristic: only colors set in `style` attributes are known,
+//! so text is checked when it and its nearest inline background both are.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use reqwest::header::CONTENT_TYPE;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use url::Url;
+
+use crate::RequestOptions;
+
+/// Longest element snippet or heading text kept in a report
+const MAX_SNIPPET: usize = 120;
+
+/// Landmark roles counted, with the elements that imply them
+const LANDMARKS: [(&str, &str); 6] = [
+    ("main", "main, [role=main]"),
+    ("navigation", "nav, [role=navigation]"),
+    ("banner", "header, [role=banner]"),
+    ("contentinfo", "footer, [role=contentinfo]"),
+    ("complementary", "aside, [role=complementary]"),
+    ("search", "search, [role=search]"),
+];
+
+/// Sectioning elements inside which `header`/`footer` aren't landmarks
+const SECTIONING: [&str; 5] = ["article", "aside", "main", "nav", "section"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One problem found in the page
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// Opening tag of the offending element
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element: Option<String>,
+}
+
+/// A heading of the page outline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+}
+
+/// Accessibility report of one page
+#[derive(Debug, Clone, Serialize)]
+pub struct A11yReport {
+    pub url: String,
+    pub final_url: String,
+    pub status: u16,
+    pub audited_at: DateTime<Utc>,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Landmarks found, by role
+    pub landmarks: BTreeMap<&'static str, usize>,
+    /// Headings in document order
+    pub headings: Vec<Heading>,
+    pub issues: Vec<Issue>,
+}
+
+/// Fetch `url` like a browser navigation and check its HTML
+pub async fn run(url: &str, options: &RequestOptions) -> Result<A11yReport> {
+    let response = crate::audit::fetch(url, options).await?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("{url} answered {status}");
+    }
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if !content_type.is_empty() && !content_type.contains("html") {
+        bail!("{url} isn't HTML ({content_type})");
+    }
+    let final_url = response.url().clone();
+    let html = response.text().await?;
+    Ok(audit(url, &final_url, status.as_u16(), &html))
+}
+
+/// Check the HTML `final_url` answered with
+#[must_use]
+pub fn audit(url: &str, final_url: &Url, status: u16, html: &str) -> A11yReport {
+    let document = Html::parse_document(html);
+    let mut issues = Vec::new();
+    check_document(&document, &mut issues);
+    let headings = check_headings(&document, &mut issues);
+    let landmarks = check_landmarks(&document, &mut issues);
+    check_images(&document, &mut issues);
+    check_names(&document, &mut issues);
+    check_form_labels(&document, &mut issues);
+    check_ids(&document, &mut issues);
+    check_viewport(&document, &mut issues);
+    check_contrast(&document, &mut issues);
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    A11yReport {
+        url: url.to_string(),
+        final_url: final_url.to_string(),
+        status,
+        audited_at: Utc::now(),
+        errors,
+        warnings: issues.len() - errors,
+        landmarks,
+        headings,
+        issues,
+    }
+}
+
+fn issue(
+    rule: &'static str,
+    severity: Severity,
+    message: impl Into<String>,
+    element: Option<ElementRef<'_>>,
+) -> Issue {
+    Issue {
+        rule,
+        severity,
+        message: message.into(),
+        element: element.map(snippet),
+    }
+}
+
+fn select<'a>(document: &'a Html, selector: &str) -> Vec<ElementRef<'a>> {
+    Selector::parse(selector)
+        .map(|s| document.select(&s).collect())
+        .unwrap_or_default()
+}
+
+fn check_document(document: &Html, issues: &mut Vec<Issue>) {
+    let lang = document.root_element().value().attr("lang");
+    if lang.is_none_or(|lang| lang.trim().is_empty()) {
+        issues.push(issue(
+            "document-lang",
+            Severity::Error,
+            "<html> has no lang attribute",
+            None,
+        ));
+    }
+    let titled = select(document, "title")
+        .first()
+        .is_some_and(|title| !text_of(*title).is_empty());
+    if !titled {
+        issues.push(issue(
+            "document-title",
+            Severity::Error,
+            "The page has no <title>",
+            None,
+        ));
+    }
+}
+
+fn check_headings(document: &Html, issues: &mut Vec<Issue>) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut previous: Option<u8> = None;
+    for el in select(document, "h1, h2, h3, h4, h5, h6") {
+        let level = el.value().name()[1..].parse().unwrap_or(1);
+        let text = text_of(el);
+        if text.is_empty() && !has_accessible_name(el) {
+            issues.push(issue(
+                "empty-heading",
+                Severity::Warning,
+                format!("Empty h{level}"),
+                Some(el),
+            ));
+        }
+        if let Some(previous) = previous.filter(|&previous| level > previous + 1) {
+            issues.push(issue(
+                "heading-order",
+                Severity::Error,
+                format!("h{level} follows h{previous}, skipping a level"),
+                Some(el),
+            ));
+        }
+        previous = Some(level);
+        headings.push(Heading {
+            level,
+            text: truncate(&text),
+        });
+    }
+    match headings.iter().filter(|h| h.level == 1).count() {
+        0 => issues.push(issue(
+            "heading-one",
+            Severity::Warning,
+            "The page has no h1",
+            None,
+        )),
+        1 => {}
+        n => issues.push(issue(
+            "heading-one",
+            Severity::Warning,
+            format!("The page has {n} h1 headings"),
+            None,
+        )),
+    }
+    headings
+}
+
+fn check_landmarks(document: &Html, issues: &mut Vec<Issue>) -> BTreeMap<&'static str, usize> {
+    let landmarks: BTreeMap<_, _> = LANDMARKS
+        .into_iter()
+        .map(|(role, selector)| {
+            let count = select(document, selector)
+                .into_iter()
+                .filter(|el| {
+                    !matches!(el.value().name(), "header" | "footer")
+                        || el.value().attr("role").is_some()
+                        || !el
+                            .ancestors()
+                            .filter_map(ElementRef::wrap)
+                            .any(|a| SECTIONING.contains(&a.value().name()))
+                })
+                .count();
+            (role, count)
+        })
+        .collect();
+    match landmarks["main"] {
+        0 => issues.push(issue(
+            "landmark-main",
+            Severity::Error,
+            "The page has no main landmark (<main> or role=main)",
+            None,
+        )),
+        1 => {}
+        n => issues.push(issue(
+            "landmark-main",
+            Severity::Warning,
+            format!("The page has {n} main landmarks"),
+            None,
+        )),
+    }
+    landmarks
+}
+
+fn check_images(document: &Html, issues: &mut Vec<Issue>) {
+    for el in select(document, "img, area[href], input[type=image]") {
+        let attrs = el.value();
+        let decorative = matches!(attrs.attr("role"), Some("presentation" | "none"));
+        if attrs.attr("alt").is_some() || decorative || is_hidden(el) {
+            continue;
+        }
+        if attrs.name() == "input" && has_accessible_name(el) {
+            continue;
+        }
+        issues.push(issue(
+            "image-alt",
+            Severity::Error,
+            format!("<{}> has no alt text", attrs.name()),
+            Some(el),
+        ));
+    }
+}
+
+fn check_names(document: &Html, issues: &mut Vec<Issue>) {
+    for (rule, selector, what) in [
+        ("link-name", "a[href]", "Link"),
+        (
+            "button-name",
+            "button, input[type=button], input[type=submit], input[type=reset], [role=button]",
+            "Button",
+        ),
+    ] {
+        for el in select(document, selector) {
+            let named = has_accessible_name(el)
+                || !text_of(el).is_empty()
+                || (el.value().name() == "input"
+                    && (el.value().attr("value").is_some()
+                        || matches!(el.value().attr("type"), Some("submit" | "reset"))))
+                || select_in(el, "img[alt]").iter().any(|img| {
+                    !img.value()
+                        .attr("alt")
+                        .unwrap_or_default()
+                        .trim()
+                        .is_empty()
+                });
+            if !named && !is_hidden(el) {
+                issues.push(issue(
+                    rule,
+                    Severity::Error,
+                    format!("{what} has no accessible name"),
+                    Some(el),
+                ));
+            }
+        }
+    }
+}
+
+fn check_form_labels(document: &Html, issues: &mut Vec<Issue>) {
+    let labelled: HashSet<&str> = select(document, "label[for]")
+        .into_iter()
+        .filter_map(|label| label.value().attr("for"))
+        .collect();
+    for el in select(document, "input, select, textarea") {
+        let kind = el.value().attr("type").unwrap_or("text").to_lowercase();
+        if matches!(
+            kind.as_str(),
+            "hidden" | "submit" | "button" | "reset" | "image"
+        ) {
+            continue;
+        }
+        let named = has_accessible_name(el)
+            || el.value().id().is_some_and(|id| labelled.contains(id))
+            || el
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|a| a.value().name() == "label");
+        if !named && !is_hidden(el) {
+            issues.push(issue(
+                "form-label",
+                Severity::Error,
+                format!("<{}> has no label", el.value().name()),
+                Some(el),
+            ));
+        }
+    }
+}
+
+fn check_ids(document: &Html, issues: &mut Vec<Issue>) {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for el in select(document, "[id]") {
+        if let Some(id) = el.value().id() {
+            *seen.entry(id).or_default() += 1;
+        }
+    }
+    let mut duplicates: Vec<_> = seen.into_iter().filter(|(_, n)| *n > 1).collect();
+    duplicates.sort_unstable();
+    for (id, n) in duplicates {
+        issues.push(issue(
+            "duplicate-id",
+            Severity::Warning,
+            format!("id \"{id}\" is used {n} times"),
+            None,
+        ));
+    }
+}
+
+fn check_viewport(document: &Html, issues: &mut Vec<Issue>) {
+    for el in select(document, "meta[name=viewport]") {
+        let content = el
+            .value()
+            .attr("content")
+            .unwrap_or_default()
+            .to_lowercase();
+        let blocks_zoom = content.split([',', ';']).any(|part| {
+            let Some((key, value)) = part.split_once('=') else {
+                return false;
+            };
+            match key.trim() {
+                "user-scalable" => matches!(value.trim(), "no" | "0"),
+                "maximum-scale" => value.trim().parse::<f64>().is_ok_and(|scale| scale < 2.0),
+                _ => false,
+            }
+        });
+        if blocks_zoom {
+            issues.push(issue(
+                "meta-viewport",
+                Severity::Error,
+                "The viewport blocks zooming",
+                Some(el),
+            ));
+        }
+    }
+}
+
+fn check_contrast(document: &Html, issues: &mut Vec<Issue>) {
+    for el in select(document, "body [style]") {
+        let style = inline_style(el);
+        if !style.contains_key("color")
+            && !style.contains_key("background-color")
+            && !style.contains_key("background")
+        {
+            continue;
+        }
+        if text_of(el).is_empty() || is_hidden(el) {
+            continue;
+        }
+        let (Some(foreground), Some(background)) =
+            (inherited(el, foreground), inherited(el, background))
+        else {
+            continue;
+        };
+        let ratio = contrast_ratio(foreground, background);
+        let severity = if ratio < 3.0 {
+            Severity::Error
+        } else if ratio < 4.5 {
+            Severity::Warning
+        } else {
+            continue;
+        };
+        issues.push(issue(
+            "color-contrast",
+            severity,
+            format!(
+                "Contrast {ratio:.2}:1 ({} on {})",
+                hex(foreground),
+                hex(background)
+            ),
+            Some(el),
+        ));
+    }
+}
+
+/// The nearest color `pick` finds on `el` or its ancestors
+fn inherited(
+    el: ElementRef<'_>,
+    pick: fn(&HashMap<String, String>) -> Option<Option<Rgb>>,
+) -> Option<Rgb> {
+    std::iter::once(el)
+        .chain(el.ancestors().filter_map(ElementRef::wrap))
+        .find_map(|el| pick(&inline_style(el)))
+        .flatten()
+}
+
+/// Inline text color: `Some(None)` when set to something unknown
+fn foreground(style: &HashMap<String, String>) -> Option<Option<Rgb>> {
+    style.get("color").map(|value| parse_color(value))
+}
+
+/// Inline background color: `Some(None)` when set to something unknown,
+/// like an image
+fn background(style: &HashMap<String, String>) -> Option<Option<Rgb>> {
+    if let Some(value) = style.get("background-color") {
+        return Some(parse_color(value));
+    }
+    let value = style.get("background")?;
+    if value.contains("url(") || value.contains("gradient(") {
+        return Some(None);
+    }
+    if let Some(start) = value.find("rgb") {
+        let end = value[start..]
+            .find(')')
+            .map_or(value.len(), |end| start + end + 1);
+        return Some(parse_color(&value[start..end]));
+    }
+    Some(value.split_whitespace().find_map(parse_color))
+}
+
+fn inline_style(el: ElementRef<'_>) -> HashMap<String, String> {
+    el.value()
+        .attr("style")
+        .unwrap_or_default()
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let value = value.trim().trim_end_matches("!important").trim();
+            Some((property.trim().to_lowercase(), value.to_lowercase()))
+        })
+        .collect()
+}
+
+type Rgb = [u8; 3];
+
+/// `#rgb`, `#rrggbb`, opaque `rgb()`/`rgba()`, or a basic color name
+fn parse_color(value: &str) -> Option<Rgb> {
+    let value = value.trim().to_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+        return match hex.len() {
+            3 => {
+                let mut rgb = [0; 3];
+                for (i, c) in hex.chars().enumerate() {
+                    rgb[i] = channel(&c.to_string())? * 17;
+                }
+                Some(rgb)
+            }
+            6 => Some([
+                channel(hex.get(0..2)?)?,
+                channel(hex.get(2..4)?)?,
+                channel(hex.get(4..6)?)?,
+            ]),
+            _ => None,
+        };
+    }
+    if let Some(args) = value
+        .strip_prefix("rgba(")
+        .or_else(|| value.strip_prefix("rgb("))
+    {
+        let parts: Vec<&str> = args
+            .trim_end_matches(')')
+            .split([',', ' ', '/'])
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.len() == 4 && parts[3].parse::<f64>().ok() != Some(1.0) {
+            return None;
+        }
+        if parts.len() < 3 {
+            return None;
+        }
+        let mut rgb = [0; 3];
+        for (channel, part) in rgb.iter_mut().zip(&parts) {
+            *channel = part.parse().ok()?;
+        }
+        return Some(rgb);
+    }
+    Some(match value.as_str() {
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        "gray" | "grey" => [128, 128, 128],
+        "silver" => [192, 192, 192],
+        "red" => [255, 0, 0],
+        "maroon" => [128, 0, 0],
+        "orange" => [255, 165, 0],
+        "yellow" => [255, 255, 0],
+        "green" => [0, 128, 0],
+        "lime" => [0, 255, 0],
+        "blue" => [0, 0, 255],
+        "navy" => [0, 0, 128],
+        "purple" => [128, 0, 128],
+        _ => return None,
+    })
+}
+
+fn hex(rgb: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+/// WCAG contrast ratio, 1 to 21
+fn contrast_ratio(a: Rgb, b: Rgb) -> f64 {
+    let luminance = |rgb: Rgb| {
+        let linear = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if c <= 0.039_28 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(rgb[0]) + 0.7152 * linear(rgb[1]) + 0.0722 * linear(rgb[2])
+    };
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+fn select_in<'a>(el: ElementRef<'a>, selector: &str) -> Vec<ElementRef<'a>> {
+    Selector::parse(selector)
+        .map(|s| el.select(&s).collect())
+        .unwrap_or_default()
+}
+
+/// Named by `aria-label`, `aria-labelledby`, or `title`
+fn has_accessible_name(el: ElementRef<'_>) -> bool {
+    ["aria-label", "aria-labelledby", "title"]
+        .iter()
+        .any(|attr| el.value().attr(attr).is_some_and(|v| !v.trim().is_empty()))
+}
+
+/// `hidden` or `aria-hidden=true` on the element or an ancestor
+fn is_hidden(el: ElementRef<'_>) -> bool {
+    std::iter::once(el)
+        .chain(el.ancestors().filter_map(ElementRef::wrap))
+        .any(|el| {
+            el.value().attr("hidden").is_some() || el.value().attr("aria-hidden") == Some("true")
+        })
+}
+
+/// Text content with whitespace collapsed
+fn text_of(el: ElementRef<'_>) -> String {
+    el.text()
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Opening tag of `el`, shortened to [`MAX_SNIPPET`] characters
+fn snippet(el: ElementRef<'_>) -> String {
+    let mut tag = format!("<{}", el.value().name());
+    for (name, value) in el.value().attrs() {
+        tag.push_str(&format!(" {name}=\"{value}\""));
+    }
+    tag.push('>');
+    truncate(&tag)
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_SNIPPET {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(MAX_SNIPPET - 1).collect();
+    short.push('…');
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(report: &A11yReport) -> Vec<(&str, Severity)> {
+        report
+            .issues
+            .iter()
+            .map(|issue| (issue.rule, issue.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_audit_flags_structure() {
+        let html = r##"<!DOCTYPE html><html><head>
+            <meta name="viewport" content="width=device-width, user-scalable=no">
+            </head><body>
+            <header><nav><a href="/"><img src="logo.png"></a></nav></header>
+            <h1>Shop</h1><h3 id="x">Deals</h3><h2 id="x"></h2>
+            <img src="hero.jpg"><img src="spacer.gif" alt="">
+            <form><input type="email"><label>Name <input name="name"></label>
+            <button></button><input type="submit"></form>
+            <p style="color: #999; background: white">Faint</p>
+            <div style="background-color: rgb(0, 0, 0)"><span style="color:#666">Dim</span></div>
+            <footer>(c)</footer>
+            </body></html>"##;
+        let url = Url::parse("https://example.com/").unwrap();
+        let report = audit(url.as_str(), &url, 200, html);
+
+        assert_eq!(
+            rules(&report),
+            [
+                ("document-lang", Severity::Error),
+                ("document-title", Severity::Error),
+                ("heading-order", Severity::Error),
+                ("empty-heading", Severity::Warning),
+                ("landmark-main", Severity::Error),
+                ("image-alt", Severity::Error),
+                ("image-alt", Severity::Error),
+                ("link-name", Severity::Error),
+                ("button-name", Severity::Error),
+                ("form-label", Severity::Error),
+                ("duplicate-id", Severity::Warning),
+                ("meta-viewport", Severity::Error),
+                ("color-contrast", Severity::Error),
+                ("color-contrast", Severity::Warning),
+            ]
+        );
+        assert_eq!((report.errors, report.warnings), (11, 3));
+        assert_eq!(report.landmarks["banner"], 1);
+        assert_eq!(report.landmarks["contentinfo"], 1);
+        assert_eq!(report.landmarks["navigation"], 1);
+        let outline: Vec<_> = report.headings.iter().map(|h| h.level).collect();
+        assert_eq!(outline, [1, 3, 2]);
+        assert_eq!(
+            report.issues[5].element.as_deref(),
+            Some("<img src=\"logo.png\">")
+        );
+        assert_eq!(
+            report.issues[12].message,
+            "Contrast 2.85:1 (#999999 on #ffffff)"
+        );
+    }
+
+    #[test]
+    fn test_audit_clean_page() {
+        let html = r#"<html lang="en"><head><title>Ok</title></head><body>
+            <main><h1>Ok</h1><h2>Part</h2>
+            <a href="/x" aria-label="Next"><svg></svg></a>
+            <p style="color:#000;background:#fff">Readable</p></main>
+            </body></html>"#;
+        let url = Url::parse("https://example.com/").unwrap();
+        let report = audit(url.as_str(), &url, 200, html);
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        assert_eq!(parse_color("#fff"), Some([255, 255, 255]));
+        assert_eq!(parse_color("rgba(10, 20, 30, 1)"), Some([10, 20, 30]));
+        assert_eq!(parse_color("rgba(10, 20, 30, 0.5)"), None);
+        assert_eq!(parse_color("inherit"), None);
+        let ratio = contrast_ratio([0, 0, 0], [255, 255, 255]);
+        assert!((ratio - 21.0).abs() < 1e-9);
+    }
+}
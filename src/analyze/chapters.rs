@@ -0,0 +1,330 @@
+//! Chapter markers for captured media
+//!
+//! Chapters come from the container when it has them (`ffprobe
+//! -show_chapters`). Otherwise they're inferred in one ffmpeg pass: long
+//! silences mark the boundaries, snapped to a scene cut inside them when there
+//! is one; media without silences is split at scene cuts instead.
+//! [`to_ffmetadata`] writes the FFmetadata file that `ffmpeg -map_chapters`
+//! (and `nab annotate --chapters`) embeds in a container.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+use tokio::process::Command;
+
+use super::{AnalysisError, Result};
+
+/// Where a chapter boundary came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChapterSource {
+    Container,
+    Silence,
+    Scene,
+}
+
+/// One chapter, in seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+    pub source: ChapterSource,
+}
+
+/// Chapter detection settings
+#[derive(Debug, Clone)]
+pub struct ChapterDetector {
+    /// Audio below this level counts as silence (dB)
+    pub noise_db: f64,
+    /// Shortest silence that can end a chapter (seconds)
+    pub min_silence: f64,
+    /// Scene change score for a cut (0.0-1.0)
+    pub scene_threshold: f32,
+    /// Shortest inferred chapter (seconds)
+    pub min_length: f64,
+}
+
+impl Default for ChapterDetector {
+    fn default() -> Self {
+        Self {
+            noise_db: -35.0,
+            min_silence: 1.5,
+            scene_threshold: 0.4,
+            min_length: 30.0,
+        }
+    }
+}
+
+impl ChapterDetector {
+    /// Container chapters, or chapters inferred from silences and scene cuts
+    pub async fn detect(&self, path: &Path) -> Result<Vec<Chapter>> {
+        let path = path.to_str().ok_or_else(|| {
+            AnalysisError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid video path",
+            ))
+        })?;
+        let output = Command::new("ffprobe")
+            .args([
+                "-v",
+                "quiet",
+                "-print_format",
+                "json",
+                "-show_format",
+                "-show_streams",
+                "-show_chapters",
+                path,
+            ])
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(AnalysisError::Ffmpeg("ffprobe failed".to_string()));
+        }
+        let probe: Probe = serde_json::from_slice(&output.stdout)?;
+        let chapters = probe.chapters();
+        if !chapters.is_empty() {
+            return Ok(chapters);
+        }
+
+        let has = |kind: &str| probe.streams.iter().any(|s| s.codec_type == kind);
+        let mut args = vec!["-hide_banner", "-nostats", "-i", path];
+        let silence = format!(
+            "silencedetect=noise={}dB:d={}",
+            self.noise_db, self.min_silence
+        );
+        let scenes = format!("select='gt(scene,{:.2})',showinfo", self.scene_threshold);
+        if has("audio") {
+            args.extend(["-af", &silence]);
+        }
+        if has("video") {
+            args.extend(["-vf", &scenes]);
+        }
+        args.extend(["-f", "null", "-"]);
+        let output = Command::new("ffmpeg").args(&args).output().await?;
+        let log = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            let last = log.lines().last().unwrap_or_default();
+            return Err(AnalysisError::Ffmpeg(format!(
+                "Chapter detection failed: {last}"
+            )));
+        }
+
+        let duration = probe.format.duration.parse().unwrap_or(0.0);
+        Ok(infer(
+            duration,
+            &parse_silences(&log),
+            &parse_scene_cuts(&log),
+            self.min_length,
+        ))
+    }
+}
+
+/// `(start, end)` of each silence in ffmpeg `silencedetect` output
+#[must_use]
+pub fn parse_silences(log: &str) -> Vec<(f64, f64)> {
+    let mut silences = Vec::new();
+    let mut start = None;
+    for line in log.lines() {
+        if let Some(value) = field(line, "silence_start:") {
+            start = Some(value);
+        } else if let Some(end) = field(line, "silence_end:") {
+            if let Some(start) = start.take() {
+                silences.push((start, end));
+            }
+        }
+    }
+    silences
+}
+
+/// Scene cut times in ffmpeg `showinfo` output
+#[must_use]
+pub fn parse_scene_cuts(log: &str) -> Vec<f64> {
+    log.lines()
+        .filter(|line| line.contains("showinfo"))
+        .filter_map(|line| field(line, "pts_time:"))
+        .collect()
+}
+
+/// The number after `name` in a log line
+fn field(line: &str, name: &str) -> Option<f64> {
+    let rest = line.split_once(name)?.1.trim_start();
+    rest.split(|c: char| c.is_whitespace() || c == '|')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Chapters between boundaries at silences (or scene cuts, without
+/// silences), none shorter than `min_length`
+#[must_use]
+pub fn infer(
+    duration: f64,
+    silences: &[(f64, f64)],
+    cuts: &[f64],
+    min_length: f64,
+) -> Vec<Chapter> {
+    let (source, mut boundaries): (_, Vec<f64>) = if silences.is_empty() {
+        (ChapterSource::Scene, cuts.to_vec())
+    } else {
+        let boundaries = silences
+            .iter()
+            .map(|&(start, end)| {
+                // A cut during the silence is where the next part really starts
+                let middle = (start + end) / 2.0;
+                cuts.iter()
+                    .copied()
+                    .filter(|cut| (start..=end).contains(cut))
+                    .min_by(|a, b| (a - middle).abs().total_cmp(&(b - middle).abs()))
+                    .unwrap_or(middle)
+            })
+            .collect();
+        (ChapterSource::Silence, boundaries)
+    };
+    boundaries.sort_by(f64::total_cmp);
+
+    let mut starts = vec![0.0];
+    for boundary in boundaries {
+        let last = starts[starts.len() - 1];
+        if boundary - last >= min_length && duration - boundary >= min_length {
+            starts.push(boundary);
+        }
+    }
+    let round = |t: f64| (t * 1000.0).round() / 1000.0;
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| Chapter {
+            start: round(start),
+            end: round(starts.get(i + 1).copied().unwrap_or(duration)),
+            title: format!("Chapter {}", i + 1),
+            source,
+        })
+        .collect()
+}
+
+/// Chapters as an FFmetadata file (millisecond timebase)
+#[must_use]
+pub fn to_ffmetadata(chapters: &[Chapter]) -> String {
+    let mut out = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        let title: String = chapter
+            .title
+            .chars()
+            .flat_map(|c| match c {
+                '=' | ';' | '#' | '\\' | '\n' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect();
+        let _ = write!(
+            out,
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={title}\n",
+            (chapter.start * 1000.0).round() as u64,
+            (chapter.end * 1000.0).round() as u64,
+        );
+    }
+    out
+}
+
+/// The parts of `ffprobe -show_chapters -show_streams -show_format` used here
+#[derive(Debug, Deserialize)]
+struct Probe {
+    #[serde(default)]
+    chapters: Vec<ProbeChapter>,
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: ProbeFormat,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeChapter {
+    start_time: String,
+    end_time: String,
+    #[serde(default)]
+    tags: std::collections::HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    #[serde(default)]
+    codec_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFormat {
+    #[serde(default)]
+    duration: String,
+}
+
+impl Probe {
+    fn chapters(&self) -> Vec<Chapter> {
+        self.chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| Chapter {
+                start: chapter.start_time.parse().unwrap_or(0.0),
+                end: chapter.end_time.parse().unwrap_or(0.0),
+                title: chapter
+                    .tags
+                    .get("title")
+                    .cloned()
+                    .unwrap_or_else(|| format!("Chapter {}", i + 1)),
+                source: ChapterSource::Container,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_from_ffmpeg_log() {
+        let log = "\
+[silencedetect @ 0x1] silence_start: 10.5
+[silencedetect @ 0x1] silence_end: 12.5 | silence_duration: 2
+[Parsed_showinfo_1 @ 0x2] n:   0 pts: 1000 pts_time:12.04  duration:1
+[silencedetect @ 0x1] silence_start: 95
+[silencedetect @ 0x1] silence_end: 97 | silence_duration: 2
+[silencedetect @ 0x1] silence_start: 170
+[silencedetect @ 0x1] silence_end: 171.5 | silence_duration: 1.5
+";
+        let silences = parse_silences(log);
+        assert_eq!(silences, [(10.5, 12.5), (95.0, 97.0), (170.0, 171.5)]);
+        let cuts = parse_scene_cuts(log);
+        assert_eq!(cuts, [12.04]);
+
+        // The first silence is snapped to the cut in it; the last would leave
+        // a chapter under 30s, so it's dropped
+        let chapters = infer(190.0, &silences, &cuts, 30.0);
+        let spans: Vec<(f64, f64)> = chapters.iter().map(|c| (c.start, c.end)).collect();
+        assert_eq!(spans, [(0.0, 96.0), (96.0, 190.0)]);
+        assert_eq!(chapters[1].title, "Chapter 2");
+        assert_eq!(chapters[0].source, ChapterSource::Silence);
+
+        let chapters = infer(190.0, &silences, &cuts, 10.0);
+        assert_eq!(chapters[1].start, 12.04);
+        assert_eq!(
+            infer(100.0, &[], &[40.0], 30.0)[1].source,
+            ChapterSource::Scene
+        );
+    }
+
+    #[test]
+    fn test_ffmetadata_and_container_chapters() {
+        let probe: Probe = serde_json::from_str(
+            r#"{"chapters": [{"id": 0, "start_time": "0.000000", "end_time": "61.500000",
+                "tags": {"title": "Intro; part=1"}}],
+                "streams": [{"codec_type": "video"}], "format": {"duration": "61.5"}}"#,
+        )
+        .unwrap();
+        let chapters = probe.chapters();
+        assert_eq!(chapters[0].source, ChapterSource::Container);
+        assert_eq!(
+            to_ffmetadata(&chapters),
+            ";FFMETADATA1\n\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=61500\n\
+             title=Intro\\; part\\=1\n"
+        );
+    }
+}
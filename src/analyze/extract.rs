@@ -98,64 +98,7 @@ impl FrameExtractor {
 
     /// Get video metadata using ffprobe
     async fn get_metadata(&self, video_path: &Path) -> Result<VideoMetadata> {
-        let output = Command::new("ffprobe")
-            .args([
-                "-v",
-                "quiet",
-                "-print_format",
-                "json",
-                "-show_format",
-                "-show_streams",
-                video_path.to_str().unwrap(),
-            ])
-            .output()
-            .await?;
-
-        if !output.status.success() {
-            return Err(AnalysisError::Ffmpeg("ffprobe failed".to_string()));
-        }
-
-        let probe: FfprobeOutput = serde_json::from_slice(&output.stdout)?;
-
-        // Find video stream
-        let video_stream = probe
-            .streams
-            .iter()
-            .find(|s| s.codec_type.as_deref() == Some("video"))
-            .ok_or_else(|| AnalysisError::Ffmpeg("No video stream found".to_string()))?;
-
-        // Find audio stream
-        let audio_stream = probe
-            .streams
-            .iter()
-            .find(|s| s.codec_type.as_deref() == Some("audio"));
-
-        // Parse frame rate (e.g., "30/1" or "30000/1001")
-        let fps = video_stream
-            .r_frame_rate
-            .as_ref()
-            .and_then(|r| {
-                let parts: Vec<&str> = r.split('/').collect();
-                if parts.len() == 2 {
-                    let num: f32 = parts[0].parse().ok()?;
-                    let den: f32 = parts[1].parse().ok()?;
-                    Some(num / den)
-                } else {
-                    r.parse().ok()
-                }
-            })
-            .unwrap_or(30.0);
-
-        Ok(VideoMetadata {
-            duration: probe.format.duration.parse().unwrap_or(0.0),
-            width: video_stream.width.unwrap_or(0),
-            height: video_stream.height.unwrap_or(0),
-            fps,
-            audio_channels: audio_stream.and_then(|a| a.channels),
-            audio_sample_rate: audio_stream
-                .and_then(|a| a.sample_rate.as_ref())
-                .and_then(|r| r.parse().ok()),
-        })
+        probe_metadata(video_path).await
     }
 
     /// Read extracted frames from directory
@@ -315,6 +258,68 @@ impl Default for AudioExtractor {
     }
 }
 
+/// Video metadata via ffprobe
+pub async fn probe_metadata(video_path: &Path) -> Result<VideoMetadata> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "quiet",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+            video_path.to_str().unwrap(),
+        ])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(AnalysisError::Ffmpeg("ffprobe failed".to_string()));
+    }
+
+    let probe: FfprobeOutput = serde_json::from_slice(&output.stdout)?;
+
+    // Find video stream
+    let video_stream = probe
+        .streams
+        .iter()
+        .find(|s| s.codec_type.as_deref() == Some("video"))
+        .ok_or_else(|| AnalysisError::Ffmpeg("No video stream found".to_string()))?;
+
+    // Find audio stream
+    let audio_stream = probe
+        .streams
+        .iter()
+        .find(|s| s.codec_type.as_deref() == Some("audio"));
+
+    // Parse frame rate (e.g., "30/1" or "30000/1001")
+    let fps = video_stream
+        .r_frame_rate
+        .as_ref()
+        .and_then(|r| {
+            let parts: Vec<&str> = r.split('/').collect();
+            if parts.len() == 2 {
+                let num: f32 = parts[0].parse().ok()?;
+                let den: f32 = parts[1].parse().ok()?;
+                Some(num / den)
+            } else {
+                r.parse().ok()
+            }
+        })
+        .unwrap_or(30.0);
+
+    Ok(VideoMetadata {
+        duration: probe.format.duration.parse().unwrap_or(0.0),
+        width: video_stream.width.unwrap_or(0),
+        height: video_stream.height.unwrap_or(0),
+        fps,
+        audio_channels: audio_stream.and_then(|a| a.channels),
+        audio_sample_rate: audio_stream
+            .and_then(|a| a.sample_rate.as_ref())
+            .and_then(|r| r.parse().ok()),
+    })
+}
+
 /// `FFprobe` JSON output structure
 #[derive(Debug, Deserialize)]
 struct FfprobeOutput {
@@ -0,0 +1,794 @@
+//! Stream integrity checks for MPEG-TS captures
+//!
+//! Reads the transport stream `nab stream` writes (natively or through
+//! ffmpeg) and reports each problem with its byte range and stream time, so a
+//! capture pipeline can re-fetch only the damaged part:
+//! - Lost or corrupt packets: sync loss, transport error flags, and
+//!   continuity counter gaps
+//! - Missing frames: timestamp jumps longer than the stream's frame spacing
+//! - Audio/video desync: the audio timeline drifting from the video's
+//! - Bitrate anomalies: seconds far below or above the median bitrate
+//!
+//! The file is read sequentially, so captures of any size are fine.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use serde::Serialize;
+
+use super::{AnalysisError, Result};
+
+const PACKET: usize = 188;
+const SYNC: u8 = 0x47;
+const NULL_PID: u16 = 0x1fff;
+/// 90 kHz PES clock
+const CLOCK: f64 = 90_000.0;
+/// PTS/DTS are 33-bit and wrap about every 26.5 hours
+const WRAP: i64 = 1 << 33;
+
+/// A timestamp step this many times the usual frame spacing means lost frames
+const GAP_FACTOR: f64 = 2.5;
+/// Audio further than this from its usual offset to the video is out of sync
+const DESYNC_SECONDS: f64 = 0.5;
+/// A second below this fraction of the median bitrate is a drop...
+const DROP_FACTOR: f64 = 0.25;
+/// ...and above this multiple a spike
+const SPIKE_FACTOR: f64 = 4.0;
+
+/// What went wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueKind {
+    /// Bytes that aren't 188-byte packets (truncated or spliced writes)
+    SyncLoss,
+    /// Packets the sender flagged as corrupt
+    TransportError,
+    /// Packets missing from a stream (continuity counter skipped)
+    ContinuityGap,
+    /// Frames missing from a stream (timestamps jump) or time running backwards
+    TimestampGap,
+    /// Audio and video timestamps drifting apart
+    AvDesync,
+    BitrateDrop,
+    BitrateSpike,
+}
+
+/// One problem, with where to find it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    /// Stream PID, if the problem is in one stream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u16>,
+    /// Seconds from the start of the capture (the first timestamp seen)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<f64>,
+    /// Affected bytes, `byte_start..byte_end`
+    pub byte_start: u64,
+    pub byte_end: u64,
+    pub detail: String,
+}
+
+/// An elementary stream found in the PMT
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamSummary {
+    pub pid: u16,
+    /// `video`, `audio`, or `other`
+    pub kind: &'static str,
+    pub codec: &'static str,
+    /// PES packets (frames for video)
+    pub frames: u64,
+    /// Seconds covered by its timestamps
+    pub duration: f64,
+}
+
+/// Integrity report for one capture
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    pub bytes: u64,
+    pub packets: u64,
+    /// Seconds from the first to the last timestamp
+    pub duration: f64,
+    pub streams: Vec<StreamSummary>,
+    pub issues: Vec<Issue>,
+}
+
+impl IntegrityReport {
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Check an MPEG-TS stream read from `reader`
+pub fn check(reader: impl Read) -> Result<IntegrityReport> {
+    let mut input = Input::new(reader);
+    let mut checker = Checker::default();
+    // Packets follow each other; sync is only searched for after losing it
+    let mut locked = false;
+    loop {
+        input.fill(PACKET * 3)?;
+        let available = input.unread();
+        if available.len() < PACKET {
+            if !available.is_empty() && checker.packets > 0 {
+                let start = input.offset;
+                let end = start + available.len() as u64;
+                checker.issue(
+                    IssueKind::SyncLoss,
+                    None,
+                    start,
+                    end,
+                    "truncated packet at the end".into(),
+                );
+            }
+            break;
+        }
+        if available[0] == SYNC && (locked || is_synced(available)) {
+            let mut packet = [0u8; PACKET];
+            packet.copy_from_slice(&available[..PACKET]);
+            checker.packet(&packet, input.offset);
+            input.consume(PACKET);
+            locked = true;
+            continue;
+        }
+        // Skip to where 188-byte packets start again (three in a row, or up to the end)
+        locked = false;
+        let skip = (1..available.len())
+            .find(|&i| {
+                is_synced(&available[i..]) && (input.eof || available.len() - i > 2 * PACKET)
+            })
+            .unwrap_or(available.len().saturating_sub(2 * PACKET).max(1));
+        if checker.packets == 0 && input.offset + skip as u64 > PACKET as u64 * 8 {
+            return Err(AnalysisError::UnsupportedFormat(
+                "not an MPEG transport stream; integrity checks read MPEG-TS \
+                 (remux with ffmpeg -c copy -f mpegts)"
+                    .into(),
+            ));
+        }
+        if checker.packets > 0 {
+            let start = input.offset;
+            checker.issue(
+                IssueKind::SyncLoss,
+                None,
+                start,
+                start + skip as u64,
+                format!("{skip} bytes outside packets"),
+            );
+        }
+        input.consume(skip);
+    }
+    if checker.packets == 0 {
+        return Err(AnalysisError::UnsupportedFormat(
+            "no MPEG-TS packets found".into(),
+        ));
+    }
+    Ok(checker.finish(input.offset))
+}
+
+/// A sync byte here and at the next packet boundaries that are present
+fn is_synced(bytes: &[u8]) -> bool {
+    bytes.first() == Some(&SYNC) && (1..3).all(|i| bytes.get(i * PACKET).is_none_or(|&b| b == SYNC))
+}
+
+/// Buffered reader that tracks the file offset of the unread bytes
+struct Input<R> {
+    inner: R,
+    buf: Vec<u8>,
+    start: usize,
+    offset: u64,
+    eof: bool,
+}
+
+impl<R: Read> Input<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            start: 0,
+            offset: 0,
+            eof: false,
+        }
+    }
+
+    /// Read until at least `want` bytes are unread, or the input ends
+    fn fill(&mut self, want: usize) -> std::io::Result<()> {
+        if self.buf.len() - self.start < want && !self.eof {
+            self.buf.drain(..self.start);
+            self.start = 0;
+            let mut chunk = vec![0u8; 64 * 1024];
+            while self.buf.len() < want && !self.eof {
+                let n = self.inner.read(&mut chunk)?;
+                self.eof = n == 0;
+                self.buf.extend_from_slice(&chunk[..n]);
+            }
+        }
+        Ok(())
+    }
+
+    fn unread(&self) -> &[u8] {
+        &self.buf[self.start..]
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.start += n;
+        self.offset += n as u64;
+    }
+}
+
+/// Per-PID state
+#[derive(Default)]
+struct Pid {
+    continuity: Option<u8>,
+    /// From the PMT
+    stream: Option<(&'static str, &'static str)>,
+    /// Unwrapped decode timestamps of each PES with the byte offset it starts at
+    timestamps: Vec<(i64, u64)>,
+}
+
+#[derive(Default)]
+struct Checker {
+    packets: u64,
+    pids: HashMap<u16, Pid>,
+    pmt_pids: Vec<u16>,
+    /// PIDs in PMT order
+    streams: Vec<u16>,
+    /// Latest timestamp of any stream
+    latest: Option<i64>,
+    /// Bytes per second of stream time: (second, first byte, end byte, bytes)
+    seconds: Vec<(i64, u64, u64, u64)>,
+    /// Packet-level issues with the latest timestamp when they were found
+    issues: Vec<(Issue, Option<i64>)>,
+}
+
+impl Checker {
+    fn issue(&mut self, kind: IssueKind, pid: Option<u16>, start: u64, end: u64, detail: String) {
+        let issue = Issue {
+            kind,
+            pid,
+            time: None,
+            end_time: None,
+            byte_start: start,
+            byte_end: end,
+            detail,
+        };
+        self.issues.push((issue, self.latest));
+    }
+
+    fn packet(&mut self, packet: &[u8; PACKET], offset: u64) {
+        self.packets += 1;
+        let end = offset + PACKET as u64;
+        let error = packet[1] & 0x80 != 0;
+        let unit_start = packet[1] & 0x40 != 0;
+        let pid = (u16::from(packet[1] & 0x1f) << 8) | u16::from(packet[2]);
+        let control = (packet[3] >> 4) & 3;
+        let counter = packet[3] & 0x0f;
+        if let Some(t) = self.latest {
+            let second = t.div_euclid(CLOCK as i64);
+            match self.seconds.last_mut() {
+                Some(bucket) if bucket.0 == second => {
+                    bucket.2 = end;
+                    bucket.3 += PACKET as u64;
+                }
+                _ => self.seconds.push((second, offset, end, PACKET as u64)),
+            }
+        }
+        if pid == NULL_PID {
+            return;
+        }
+        if error {
+            self.issue(
+                IssueKind::TransportError,
+                Some(pid),
+                offset,
+                end,
+                "packet flagged as corrupt".into(),
+            );
+            return;
+        }
+
+        let mut payload_at = 4;
+        let mut discontinuity = false;
+        if control & 2 != 0 {
+            let length = usize::from(packet[4]);
+            discontinuity = length > 0 && packet[5] & 0x80 != 0;
+            payload_at = 5 + length;
+        }
+        let has_payload = control & 1 != 0 && payload_at < PACKET;
+
+        // The counter steps once per packet with payload; one repeat is allowed
+        let state = self.pids.entry(pid).or_default();
+        if has_payload {
+            if let Some(last) = state.continuity.filter(|_| !discontinuity) {
+                let lost = counter.wrapping_sub(last.wrapping_add(1)) & 0x0f;
+                if lost > 0 && counter != last {
+                    let detail = format!(
+                        "{lost} packet{} lost (counter {last} → {counter})",
+                        if lost == 1 { "" } else { "s" }
+                    );
+                    self.issue(IssueKind::ContinuityGap, Some(pid), offset, end, detail);
+                }
+            }
+            self.pids.entry(pid).or_default().continuity = Some(counter);
+        }
+        if !has_payload {
+            return;
+        }
+        let payload = &packet[payload_at..];
+
+        if pid == 0 && unit_start {
+            self.pat(payload);
+        } else if self.pmt_pids.contains(&pid) && unit_start {
+            self.pmt(payload);
+        } else if unit_start && self.streams.contains(&pid) {
+            if let Some(timestamp) = pes_timestamp(payload) {
+                let state = self.pids.entry(pid).or_default();
+                let previous = state.timestamps.last().map(|&(t, _)| t);
+                let timestamp = unwrap_timestamp(timestamp, previous.or(self.latest));
+                state.timestamps.push((timestamp, offset));
+                self.latest = Some(self.latest.map_or(timestamp, |t| t.max(timestamp)));
+            }
+        }
+    }
+
+    /// Program association table: where the PMTs are
+    fn pat(&mut self, payload: &[u8]) {
+        let Some(section) = section(payload) else {
+            return;
+        };
+        for entry in section.chunks_exact(4) {
+            let program = u16::from_be_bytes([entry[0], entry[1]]);
+            let pid = (u16::from(entry[2] & 0x1f) << 8) | u16::from(entry[3]);
+            if program != 0 && !self.pmt_pids.contains(&pid) {
+                self.pmt_pids.push(pid);
+            }
+        }
+    }
+
+    /// Program map table: the elementary streams and their types
+    fn pmt(&mut self, payload: &[u8]) {
+        let Some(section) = section(payload) else {
+            return;
+        };
+        let Some(&[info_hi, info_lo]) = section.get(2..4) else {
+            return;
+        };
+        let mut at = 4 + usize::from(u16::from_be_bytes([info_hi & 0x0f, info_lo]));
+        while let Some(entry) = section.get(at..at + 5) {
+            let pid = (u16::from(entry[1] & 0x1f) << 8) | u16::from(entry[2]);
+            let info = usize::from(u16::from_be_bytes([entry[3] & 0x0f, entry[4]]));
+            self.pids.entry(pid).or_default().stream = Some(stream_type(entry[0]));
+            if !self.streams.contains(&pid) {
+                self.streams.push(pid);
+            }
+            at += 5 + info;
+        }
+    }
+
+    fn finish(mut self, bytes: u64) -> IntegrityReport {
+        let origin = self
+            .pids
+            .values()
+            .filter_map(|p| p.timestamps.first().map(|&(t, _)| t))
+            .min();
+        let seconds = |t: i64| ((t - origin.unwrap_or(t)) as f64 / CLOCK * 1000.0).round() / 1000.0;
+
+        // Packet-level issues get the time of the stream around them
+        let mut issues: Vec<Issue> = std::mem::take(&mut self.issues)
+            .into_iter()
+            .map(|(issue, t)| Issue {
+                time: t.map(&seconds),
+                ..issue
+            })
+            .collect();
+        let mut streams = Vec::new();
+        for &pid in &self.streams {
+            let state = &self.pids[&pid];
+            let (kind, codec) = state.stream.unwrap_or(("other", "unknown"));
+            let timestamps = &state.timestamps;
+            let duration = match (timestamps.first(), timestamps.last()) {
+                (Some(&(first, _)), Some(&(last, _))) => (last - first) as f64 / CLOCK,
+                _ => 0.0,
+            };
+            streams.push(StreamSummary {
+                pid,
+                kind,
+                codec,
+                frames: timestamps.len() as u64,
+                duration: (duration * 1000.0).round() / 1000.0,
+            });
+            if kind != "other" {
+                issues.extend(timestamp_gaps(pid, timestamps, &seconds));
+            }
+        }
+        issues.extend(self.desync(&seconds));
+        issues.extend(self.bitrate(&seconds));
+        issues.sort_by(|a, b| {
+            a.byte_start
+                .cmp(&b.byte_start)
+                .then(a.byte_end.cmp(&b.byte_end))
+        });
+        let issues = merge(issues);
+
+        let duration = self.latest.map_or(0.0, &seconds);
+        IntegrityReport {
+            bytes,
+            packets: self.packets,
+            duration,
+            streams,
+            issues,
+        }
+    }
+
+    /// Audio PES against the latest video timestamp before it, relative to
+    /// the usual offset between them
+    fn desync(&self, seconds: &impl Fn(i64) -> f64) -> Vec<Issue> {
+        let first = |kind: &str| {
+            self.streams
+                .iter()
+                .find(|pid| self.pids[pid].stream.is_some_and(|(k, _)| k == kind))
+        };
+        let (Some(video), Some(audio)) = (first("video"), first("audio")) else {
+            return Vec::new();
+        };
+        let (video, audio) = (&self.pids[video].timestamps, &self.pids[audio].timestamps);
+        let mut offsets = Vec::new();
+        for &(t, at) in audio {
+            let i = video.partition_point(|&(_, o)| o < at);
+            if let Some(&(v, _)) = i.checked_sub(1).and_then(|i| video.get(i)) {
+                offsets.push(((t - v) as f64 / CLOCK, t, at));
+            }
+        }
+        if offsets.len() < 3 {
+            return Vec::new();
+        }
+        let usual = median(offsets.iter().map(|o| o.0).collect());
+        // One issue per run of out-of-sync audio, with its largest drift
+        let mut issues: Vec<Issue> = Vec::new();
+        let mut worst = 0.0f64;
+        let mut in_run = false;
+        for &(offset, t, at) in &offsets {
+            let drift = offset - usual;
+            if drift.abs() <= DESYNC_SECONDS {
+                in_run = false;
+                continue;
+            }
+            match issues.last_mut().filter(|_| in_run) {
+                Some(issue) => {
+                    issue.end_time = Some(seconds(t));
+                    issue.byte_end = at + PACKET as u64;
+                    if drift.abs() > worst.abs() {
+                        worst = drift;
+                    }
+                    issue.detail = format!("audio up to {worst:+.2}s from video");
+                }
+                None => {
+                    worst = drift;
+                    issues.push(Issue {
+                        kind: IssueKind::AvDesync,
+                        pid: None,
+                        time: Some(seconds(t)),
+                        end_time: Some(seconds(t)),
+                        byte_start: at,
+                        byte_end: at + PACKET as u64,
+                        detail: format!("audio {drift:+.2}s from video"),
+                    });
+                }
+            }
+            in_run = true;
+        }
+        issues
+    }
+
+    /// Bytes per second of stream time against the median second
+    fn bitrate(&self, seconds: &impl Fn(i64) -> f64) -> Vec<Issue> {
+        // The first and last seconds are partial
+        let buckets = &self.seconds;
+        if buckets.len() < 5 {
+            return Vec::new();
+        }
+        let inner = &buckets[1..buckets.len() - 1];
+        let usual = median(inner.iter().map(|b| b.3 as f64).collect());
+        inner
+            .iter()
+            .filter_map(|&(second, start, end, bytes)| {
+                let ratio = bytes as f64 / usual;
+                let kind = if ratio < DROP_FACTOR {
+                    IssueKind::BitrateDrop
+                } else if ratio > SPIKE_FACTOR {
+                    IssueKind::BitrateSpike
+                } else {
+                    return None;
+                };
+                let t = second * CLOCK as i64;
+                Some(Issue {
+                    kind,
+                    pid: None,
+                    time: Some(seconds(t).max(0.0)),
+                    end_time: Some(seconds(t + CLOCK as i64)),
+                    byte_start: start,
+                    byte_end: end,
+                    detail: format!(
+                        "{} kbit/s against a median of {}",
+                        bytes * 8 / 1000,
+                        (usual * 8.0 / 1000.0) as u64
+                    ),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Steps between a stream's timestamps much longer than usual, or backwards
+fn timestamp_gaps(
+    pid: u16,
+    timestamps: &[(i64, u64)],
+    seconds: &impl Fn(i64) -> f64,
+) -> Vec<Issue> {
+    let steps: Vec<f64> = timestamps
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0) as f64)
+        .filter(|&d| d > 0.0)
+        .collect();
+    if steps.len() < 2 {
+        return Vec::new();
+    }
+    let usual = median(steps);
+    timestamps
+        .windows(2)
+        .filter_map(|w| {
+            let ((before, start), (after, end)) = (w[0], w[1]);
+            let step = (after - before) as f64;
+            let detail = if step < -usual {
+                format!("timestamps jump back {:.3}s", -step / CLOCK)
+            } else if step > usual * GAP_FACTOR {
+                let missing = (step / usual).round() as u64 - 1;
+                format!("about {missing} frames missing ({:.3}s)", step / CLOCK)
+            } else {
+                return None;
+            };
+            Some(Issue {
+                kind: IssueKind::TimestampGap,
+                pid: Some(pid),
+                time: Some(seconds(before)),
+                end_time: Some(seconds(after)),
+                byte_start: start,
+                byte_end: end,
+                detail,
+            })
+        })
+        .collect()
+}
+
+/// Join issues of one kind and stream whose byte ranges touch, keeping the first detail
+fn merge(issues: Vec<Issue>) -> Vec<Issue> {
+    let mut merged: Vec<Issue> = Vec::new();
+    for issue in issues {
+        if let Some(last) = merged.iter_mut().rev().take(4).find(|last| {
+            last.kind == issue.kind && last.pid == issue.pid && issue.byte_start <= last.byte_end
+        }) {
+            last.byte_end = last.byte_end.max(issue.byte_end);
+            last.end_time = match (last.end_time, issue.end_time.or(issue.time)) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (a, b) => a.or(b),
+            };
+            continue;
+        }
+        merged.push(issue);
+    }
+    merged
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    values[values.len() / 2]
+}
+
+/// The section body after the pointer field and 8-byte header, without its CRC
+fn section(payload: &[u8]) -> Option<&[u8]> {
+    let start = 1 + usize::from(*payload.first()?);
+    let header = payload.get(start..start + 3)?;
+    let length = usize::from(u16::from_be_bytes([header[1] & 0x0f, header[2]]));
+    payload.get(start + 8..(start + 3 + length).checked_sub(4)?)
+}
+
+/// (kind, codec) for a PMT stream type
+fn stream_type(kind: u8) -> (&'static str, &'static str) {
+    match kind {
+        0x01 | 0x02 => ("video", "mpeg2"),
+        0x1b => ("video", "h264"),
+        0x24 => ("video", "hevc"),
+        0x03 | 0x04 => ("audio", "mp3"),
+        0x0f => ("audio", "aac"),
+        0x11 => ("audio", "aac-latm"),
+        0x81 => ("audio", "ac3"),
+        0x87 => ("audio", "eac3"),
+        _ => ("other", "unknown"),
+    }
+}
+
+/// The DTS (or PTS, without a DTS) of a PES header
+fn pes_timestamp(payload: &[u8]) -> Option<i64> {
+    if payload.get(..3)? != [0, 0, 1] {
+        return None;
+    }
+    let flags = payload.get(7)? >> 6;
+    let at = match flags {
+        2 => 9,
+        3 => 14,
+        _ => return None,
+    };
+    let b = payload.get(at..at + 5)?;
+    Some(
+        (i64::from(b[0] >> 1 & 0x07) << 30)
+            | (i64::from(b[1]) << 22)
+            | (i64::from(b[2] >> 1) << 15)
+            | (i64::from(b[3]) << 7)
+            | i64::from(b[4] >> 1),
+    )
+}
+
+/// Continue a 33-bit timestamp past a wrap of the previous one
+fn unwrap_timestamp(raw: i64, previous: Option<i64>) -> i64 {
+    let Some(previous) = previous else {
+        return raw;
+    };
+    let base = previous - previous.rem_euclid(WRAP);
+    [base - WRAP, base, base + WRAP]
+        .into_iter()
+        .map(|b| b + raw)
+        .min_by_key(|t| (t - previous).abs())
+        .unwrap_or(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A transport stream muxer just good enough for the checks
+    #[derive(Default)]
+    struct Mux {
+        out: Vec<u8>,
+        counters: HashMap<u16, u8>,
+    }
+
+    impl Mux {
+        fn packet(&mut self, pid: u16, start: bool, payload: &[u8]) {
+            let counter = self.counters.entry(pid).or_default();
+            let mut packet = vec![SYNC, (u8::from(start) << 6) | (pid >> 8) as u8, pid as u8];
+            let stuffing = PACKET - 4 - payload.len();
+            if stuffing > 0 {
+                packet.push(0x30 | *counter);
+                packet.push((stuffing - 1) as u8);
+                if stuffing > 1 {
+                    packet.push(0);
+                    packet.resize(4 + stuffing, 0xff);
+                }
+            } else {
+                packet.push(0x10 | *counter);
+            }
+            packet.extend_from_slice(payload);
+            *counter = (*counter + 1) & 0x0f;
+            self.out.extend_from_slice(&packet);
+        }
+
+        fn tables(&mut self) {
+            let section = |table: u8, body: &[u8]| {
+                let mut s = vec![0, table, 0xb0, (body.len() + 9) as u8, 0, 1, 0xc1, 0, 0];
+                s.extend_from_slice(body);
+                s.extend_from_slice(&[0; 4]);
+                s
+            };
+            self.packet(0, true, &section(0, &[0, 1, 0xf0, 0x00]));
+            let streams = [
+                0xe1, 0x00, 0xf0, 0x00, 0x1b, 0xe1, 0x00, 0xf0, 0x00, 0x0f, 0xe1, 0x01, 0xf0, 0x00,
+            ];
+            self.packet(0x1000, true, &section(2, &streams));
+        }
+
+        /// A PES of `size` bytes with a PTS (ticks)
+        fn pes(&mut self, pid: u16, pts: i64, size: usize) {
+            let mut pes = vec![0, 0, 1, 0xe0, 0, 0, 0x80, 0x80, 5];
+            pes.extend_from_slice(&[
+                0x21 | ((pts >> 29) as u8 & 0x0e),
+                (pts >> 22) as u8,
+                ((pts >> 14) as u8) | 1,
+                (pts >> 7) as u8,
+                ((pts << 1) as u8) | 1,
+            ]);
+            pes.resize(size.max(pes.len()), 0xaa);
+            for (i, chunk) in pes.chunks(PACKET - 4).enumerate() {
+                self.packet(pid, i == 0, chunk);
+            }
+        }
+
+        /// 25 fps video with a 20 ms audio frame after each video frame
+        fn seconds(&mut self, from: i64, to: i64, video_size: usize) {
+            for frame in from * 25..to * 25 {
+                self.pes(0x100, frame * 3600, video_size);
+                self.pes(0x101, frame * 3600, 200);
+            }
+        }
+    }
+
+    #[test]
+    fn test_clean_stream() {
+        let mut mux = Mux::default();
+        mux.tables();
+        mux.seconds(0, 6, 2000);
+        let report = check(mux.out.as_slice()).unwrap();
+        assert!(report.is_clean(), "{:?}", report.issues);
+        assert_eq!(report.streams.len(), 2);
+        assert_eq!(
+            (report.streams[0].kind, report.streams[0].codec),
+            ("video", "h264")
+        );
+        assert_eq!(report.streams[0].frames, 150);
+        assert_eq!(report.duration, 5.96);
+        assert!(check(&b"not a transport stream"[..]).is_err());
+    }
+
+    #[test]
+    fn test_finds_damage() {
+        let mut mux = Mux::default();
+        mux.tables();
+        mux.seconds(0, 3, 2000);
+        // A dropped packet in the 76th video frame
+        let lost_at = mux.out.len() as u64;
+        mux.pes(0x100, 75 * 3600, 2000);
+        let cut = lost_at as usize + PACKET;
+        mux.out.drain(cut..cut + PACKET);
+        mux.pes(0x101, 75 * 3600, 200);
+        // Video stops for a second while the audio runs on
+        for frame in 76..96 {
+            mux.pes(0x101, frame * 3600, 200);
+        }
+        mux.seconds(4, 8, 2000);
+        // A quiet second, then garbage between packets
+        mux.seconds(8, 9, 100);
+        let garbage_at = mux.out.len() as u64;
+        mux.out.extend_from_slice(&[0u8; 50]);
+        mux.seconds(9, 12, 2000);
+
+        let report = check(mux.out.as_slice()).unwrap();
+        let find = |kind: IssueKind| {
+            report
+                .issues
+                .iter()
+                .find(|i| i.kind == kind)
+                .unwrap_or_else(|| panic!("no {kind:?} in {:?}", report.issues))
+        };
+        let lost = find(IssueKind::ContinuityGap);
+        assert_eq!(
+            (lost.pid, lost.byte_start),
+            (Some(0x100), lost_at + PACKET as u64)
+        );
+        assert_eq!(lost.time, Some(3.0));
+        let gap = find(IssueKind::TimestampGap);
+        assert_eq!(gap.pid, Some(0x100));
+        assert_eq!((gap.time, gap.end_time), (Some(3.0), Some(4.0)));
+        assert!(
+            gap.detail.starts_with("about 24 frames missing"),
+            "{}",
+            gap.detail
+        );
+        let desync = find(IssueKind::AvDesync);
+        assert!(desync.time.unwrap() > 3.0 && desync.time.unwrap() < 4.0);
+        assert!(report
+            .issues
+            .iter()
+            .any(|i| i.kind == IssueKind::BitrateDrop && i.time == Some(8.0)));
+        let sync = find(IssueKind::SyncLoss);
+        assert_eq!(
+            (sync.byte_start, sync.byte_end),
+            (garbage_at, garbage_at + 50)
+        );
+    }
+
+    #[test]
+    fn test_timestamp_wrap() {
+        assert_eq!(unwrap_timestamp(100, Some(WRAP - 100)), WRAP + 100);
+        assert_eq!(unwrap_timestamp(WRAP - 100, Some(WRAP + 100)), WRAP - 100);
+    }
+}
@@ -6,11 +6,17 @@
 //! - Speaker diarization (pyannote)
 //! - Visual analysis (local models or Claude Vision API)
 //! - Multimodal fusion with timestamp alignment
+//! - Capture integrity checks for MPEG-TS streams ([`integrity`])
+//! - Chapter markers from the container, silences, or scene cuts ([`chapters`])
+//! - Thumbnail sprite sheets with a WebVTT storyboard for seek previews ([`storyboard`])
 
+pub mod chapters;
 pub mod diarize;
 pub mod extract;
 pub mod fusion;
+pub mod integrity;
 pub mod report;
+pub mod storyboard;
 pub mod transcribe;
 pub mod vision;
 
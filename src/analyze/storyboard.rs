@@ -0,0 +1,184 @@
+//! Thumbnail sprite sheets and WebVTT storyboards
+//!
+//! One ffmpeg pass samples a frame every `interval` seconds, scales it down,
+//! and tiles the thumbnails into JPEG sprite sheets. The WebVTT file maps each
+//! time range to its tile (`storyboard_001.jpg#xywh=x,y,w,h`), the format video
+//! players (video.js, Plyr, JW Player, ...) read for seek-bar previews.
+
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use super::extract::probe_metadata;
+use super::{AnalysisError, Result};
+
+/// Storyboard settings
+#[derive(Debug, Clone)]
+pub struct StoryboardConfig {
+    /// Seconds between thumbnails
+    pub interval: f64,
+    /// Thumbnail width in pixels (height keeps the aspect ratio)
+    pub width: u32,
+    /// Thumbnails per sprite sheet row
+    pub columns: u32,
+    /// Rows per sprite sheet
+    pub rows: u32,
+}
+
+impl Default for StoryboardConfig {
+    fn default() -> Self {
+        Self {
+            interval: 10.0,
+            width: 160,
+            columns: 10,
+            rows: 10,
+        }
+    }
+}
+
+/// A generated storyboard
+#[derive(Debug, Clone, Serialize)]
+pub struct Storyboard {
+    /// The WebVTT file
+    pub vtt: PathBuf,
+    /// Sprite sheets, in order
+    pub sprites: Vec<PathBuf>,
+    pub thumbnails: usize,
+    pub thumbnail_width: u32,
+    pub thumbnail_height: u32,
+}
+
+impl StoryboardConfig {
+    /// Write `storyboard_NNN.jpg` sprite sheets and `storyboard.vtt` to `out_dir`
+    pub async fn generate(&self, video: &Path, out_dir: &Path) -> Result<Storyboard> {
+        let metadata = probe_metadata(video).await?;
+        if metadata.duration <= 0.0 || metadata.width == 0 {
+            return Err(AnalysisError::UnsupportedFormat(
+                "storyboards need a video stream with a known duration".to_string(),
+            ));
+        }
+        let height = thumbnail_height(self.width, metadata.width, metadata.height);
+        std::fs::create_dir_all(out_dir)?;
+
+        let status = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+            .arg(video)
+            .args([
+                "-vf",
+                &format!(
+                    "fps=1/{},scale={}:{height},tile={}x{}",
+                    self.interval, self.width, self.columns, self.rows
+                ),
+                "-q:v",
+                "4",
+            ])
+            .arg(out_dir.join("storyboard_%03d.jpg"))
+            .status()
+            .await?;
+        if !status.success() {
+            return Err(AnalysisError::Ffmpeg(
+                "Storyboard generation failed".to_string(),
+            ));
+        }
+
+        let thumbnails = (metadata.duration / self.interval).ceil() as usize;
+        let per_sheet = (self.columns * self.rows) as usize;
+        let sprites = (1..=thumbnails.div_ceil(per_sheet))
+            .map(|n| out_dir.join(sprite_name(n)))
+            .filter(|path| path.exists())
+            .collect();
+        let vtt = out_dir.join("storyboard.vtt");
+        std::fs::write(&vtt, self.webvtt(metadata.duration, height))?;
+
+        Ok(Storyboard {
+            vtt,
+            sprites,
+            thumbnails,
+            thumbnail_width: self.width,
+            thumbnail_height: height,
+        })
+    }
+
+    /// WebVTT cues for a `duration`-second video, tiles `thumbnail_height` high
+    #[must_use]
+    pub fn webvtt(&self, duration: f64, thumbnail_height: u32) -> String {
+        let per_sheet = (self.columns * self.rows) as usize;
+        let mut out = String::from("WEBVTT\n");
+        let mut start = 0.0;
+        let mut index = 0;
+        while start < duration {
+            let end = (start + self.interval).min(duration);
+            let tile = index % per_sheet;
+            let x = (tile as u32 % self.columns) * self.width;
+            let y = (tile as u32 / self.columns) * thumbnail_height;
+            let _ = write!(
+                out,
+                "\n{} --> {}\n{}#xywh={x},{y},{},{thumbnail_height}\n",
+                timestamp(start),
+                timestamp(end),
+                sprite_name(index / per_sheet + 1),
+                self.width,
+            );
+            index += 1;
+            start = index as f64 * self.interval;
+        }
+        out
+    }
+}
+
+/// Sprite sheet file name, numbered from 1 like ffmpeg's `%03d`
+fn sprite_name(n: usize) -> String {
+    format!("storyboard_{n:03}.jpg")
+}
+
+/// Height for a `width`-wide thumbnail, rounded to an even number for the encoder
+fn thumbnail_height(width: u32, video_width: u32, video_height: u32) -> u32 {
+    let height = f64::from(width) * f64::from(video_height) / f64::from(video_width.max(1));
+    ((height / 2.0).round() as u32 * 2).max(2)
+}
+
+/// `HH:MM:SS.mmm`
+fn timestamp(seconds: f64) -> String {
+    let ms = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webvtt_tiles() {
+        let config = StoryboardConfig {
+            interval: 10.0,
+            width: 160,
+            columns: 2,
+            rows: 2,
+        };
+        assert_eq!(thumbnail_height(160, 1920, 1080), 90);
+
+        let vtt = config.webvtt(45.5, 90);
+        let cues: Vec<&str> = vtt.split("\n\n").skip(1).collect();
+        assert_eq!(cues.len(), 5);
+        assert_eq!(
+            cues[0],
+            "00:00:00.000 --> 00:00:10.000\nstoryboard_001.jpg#xywh=0,0,160,90"
+        );
+        assert_eq!(
+            cues[3],
+            "00:00:30.000 --> 00:00:40.000\nstoryboard_001.jpg#xywh=160,90,160,90"
+        );
+        assert_eq!(
+            cues[4],
+            "00:00:40.000 --> 00:00:45.500\nstoryboard_002.jpg#xywh=0,0,160,90\n"
+        );
+        assert_eq!(timestamp(3723.25), "01:02:03.250");
+    }
+}
@@ -352,7 +352,9 @@ Return ONLY valid JSON, no markdown."#;
             }]
         });
 
-        let client = reqwest::Client::new();
+        let client = crate::policy::client_builder()
+            .build()
+            .map_err(|e| AnalysisError::Vision(e.to_string()))?;
         let response = client
             .post("https://api.anthropic.com/v1/messages")
             .header("x-api-key", api_key)
@@ -7,7 +7,7 @@
 //! - Batch file processing
 
 use anyhow::{anyhow, Result};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::process::Command;
@@ -57,6 +57,84 @@ impl CompositorOutput {
     }
 }
 
+/// Loudness normalization target (single-pass ffmpeg `loudnorm`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoudnessTarget {
+    /// EBU R128 broadcast: -23 LUFS, -1 dBTP
+    Ebu,
+    /// Podcast platforms: -16 LUFS, -1.5 dBTP
+    Podcast,
+    /// Video/music streaming services: -14 LUFS, -1 dBTP
+    Streaming,
+}
+
+impl LoudnessTarget {
+    /// ffmpeg audio filter (`loudnorm` upsamples, so resample back to 48 kHz)
+    #[must_use]
+    pub fn filter(&self) -> &'static str {
+        match self {
+            Self::Ebu => "loudnorm=I=-23:TP=-1:LRA=7,aresample=48000",
+            Self::Podcast => "loudnorm=I=-16:TP=-1.5:LRA=11,aresample=48000",
+            Self::Streaming => "loudnorm=I=-14:TP=-1:LRA=11,aresample=48000",
+        }
+    }
+}
+
+/// Audio codec for an extracted audio file, by extension
+fn audio_codec_for(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "mp3" => "libmp3lame",
+        "opus" | "ogg" => "libopus",
+        "flac" => "flac",
+        "wav" => "pcm_s16le",
+        _ => "aac",
+    }
+}
+
+/// Compositing progress, from ffmpeg's stats line
+#[derive(Debug, Clone, Copy)]
+pub struct CompositeProgress {
+    /// Seconds of output written
+    pub time_seconds: f64,
+    /// Input duration, when ffprobe could tell
+    pub duration_seconds: Option<f64>,
+    /// Encoding speed relative to realtime
+    pub speed: Option<f64>,
+}
+
+impl CompositeProgress {
+    /// Percent done, when the duration is known
+    #[must_use]
+    pub fn percent(&self) -> Option<f64> {
+        self.duration_seconds
+            .filter(|d| *d > 0.0)
+            .map(|d| (self.time_seconds / d * 100.0).clamp(0.0, 100.0))
+    }
+}
+
+/// Callback for compositing progress
+pub type ProgressCallback = Box<dyn Fn(CompositeProgress) + Send + Sync>;
+
+/// `(time, speed)` from an ffmpeg stats line
+/// ("frame=  123 fps= 30 ... time=00:01:23.45 bitrate=... speed=1.5x")
+fn parse_stats(line: &str) -> Option<(f64, Option<f64>)> {
+    let time = line.split("time=").nth(1)?.split_whitespace().next()?;
+    let mut seconds = 0.0;
+    for part in time.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    let speed = line
+        .split("speed=")
+        .nth(1)
+        .and_then(|s| s.split_whitespace().next())
+        .and_then(|s| s.trim_end_matches('x').parse().ok());
+    Some((seconds, speed))
+}
+
 /// Configuration for the compositor
 #[derive(Debug, Clone)]
 pub struct CompositorConfig {
@@ -80,6 +158,12 @@ pub struct CompositorConfig {
     pub output_args: Vec<String>,
     /// Buffer size for streaming (bytes)
     pub buffer_size: usize,
+    /// FFmetadata file whose chapters are embedded in the output
+    pub chapters: Option<PathBuf>,
+    /// Normalize audio loudness (re-encodes audio)
+    pub loudness: Option<LoudnessTarget>,
+    /// Also write the audio alone to this file (codec from the extension)
+    pub audio_output: Option<PathBuf>,
 }
 
 impl Default for CompositorConfig {
@@ -98,6 +182,9 @@ impl Default for CompositorConfig {
             input_args: Vec::new(),
             output_args: Vec::new(),
             buffer_size: 64 * 1024, // 64KB
+            chapters: None,
+            loudness: None,
+            audio_output: None,
         }
     }
 }
@@ -154,11 +241,33 @@ impl CompositorConfig {
 
         self
     }
+
+    /// Embed the chapters of an FFmetadata file
+    #[must_use]
+    pub fn with_chapters(mut self, path: impl Into<PathBuf>) -> Self {
+        self.chapters = Some(path.into());
+        self
+    }
+
+    /// Normalize audio loudness to a target
+    #[must_use]
+    pub fn with_loudness(mut self, target: LoudnessTarget) -> Self {
+        self.loudness = Some(target);
+        self
+    }
+
+    /// Also write the audio track to its own file
+    #[must_use]
+    pub fn with_audio_output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audio_output = Some(path.into());
+        self
+    }
 }
 
 /// ffmpeg-based video compositor
 pub struct Compositor {
     config: CompositorConfig,
+    progress: Option<ProgressCallback>,
 }
 
 impl Compositor {
@@ -166,13 +275,24 @@ impl Compositor {
     pub fn new() -> Result<Self> {
         Ok(Self {
             config: CompositorConfig::default(),
+            progress: None,
         })
     }
 
     /// Create a new compositor with custom config
     #[must_use]
     pub fn with_config(config: CompositorConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            progress: None,
+        }
+    }
+
+    /// Report progress while compositing to a file
+    #[must_use]
+    pub fn with_progress(mut self, callback: ProgressCallback) -> Self {
+        self.progress = Some(callback);
+        self
     }
 
     /// Check if ffmpeg is available
@@ -277,12 +397,26 @@ impl Compositor {
         args.push("-i".to_string());
         args.push(input.to_string());
 
+        // Chapters, as a second input that contributes no streams
+        if let Some(ref chapters) = self.config.chapters {
+            args.push("-i".to_string());
+            args.push(chapters.to_string_lossy().to_string());
+            args.push("-map_chapters".to_string());
+            args.push("1".to_string());
+        }
+
         // Video filter
         if !filter_complex.is_empty() {
             args.push("-vf".to_string());
             args.push(filter_complex.to_string());
         }
 
+        // Loudness normalization
+        if let Some(loudness) = self.config.loudness {
+            args.push("-af".to_string());
+            args.push(loudness.filter().to_string());
+        }
+
         // Video codec
         if let Some(ref codec) = self.config.video_codec {
             args.push("-c:v".to_string());
@@ -302,6 +436,10 @@ impl Compositor {
         if let Some(ref codec) = self.config.audio_codec {
             args.push("-c:a".to_string());
             args.push(codec.clone());
+        } else if self.config.loudness.is_some() {
+            // Filtered audio can't be stream-copied
+            args.push("-c:a".to_string());
+            args.push("aac".to_string());
         } else {
             args.push("-c:a".to_string());
             args.push("copy".to_string());
@@ -334,9 +472,52 @@ impl Compositor {
             args.push("pipe:1".to_string());
         }
 
+        // Audio-only second output
+        if let Some(ref audio) = self.config.audio_output {
+            args.extend(
+                ["-vn", "-sn", "-dn"]
+                    .iter()
+                    .map(std::string::ToString::to_string),
+            );
+            if self.config.chapters.is_some() {
+                args.push("-map_chapters".to_string());
+                args.push("1".to_string());
+            }
+            if let Some(loudness) = self.config.loudness {
+                args.push("-af".to_string());
+                args.push(loudness.filter().to_string());
+            }
+            args.push("-c:a".to_string());
+            args.push(audio_codec_for(audio).to_string());
+            if let Some(ref bitrate) = self.config.audio_bitrate {
+                args.push("-b:a".to_string());
+                args.push(bitrate.clone());
+            }
+            args.push("-y".to_string());
+            args.push(audio.to_string_lossy().to_string());
+        }
+
         args
     }
 
+    /// Input duration in seconds, via ffprobe
+    async fn probe_duration(input: &str) -> Option<f64> {
+        let output = Command::new("ffprobe")
+            .args([
+                "-v",
+                "error",
+                "-show_entries",
+                "format=duration",
+                "-of",
+                "csv=p=0",
+                input,
+            ])
+            .output()
+            .await
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+
     /// Composite video with subtitles to a file
     pub async fn composite_to_file(
         &self,
@@ -350,12 +531,55 @@ impl Compositor {
 
         debug!("ffmpeg args: {:?}", args);
 
-        let status = Command::new(&self.config.ffmpeg_path)
+        let Some(ref progress) = self.progress else {
+            let status = Command::new(&self.config.ffmpeg_path)
+                .args(&args)
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit())
+                .status()
+                .await?;
+            if !status.success() {
+                return Err(anyhow!("ffmpeg exited with status: {status}"));
+            }
+            info!("Composited video to {:?}", output);
+            return Ok(());
+        };
+
+        let duration_seconds = Self::probe_duration(input).await;
+        let mut child = Command::new(&self.config.ffmpeg_path)
             .args(&args)
             .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .status()
-            .await?;
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow!("Failed to capture ffmpeg stderr"))?;
+
+        // Stats lines end in \r, messages in \n
+        let mut pending = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let n = stderr.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            pending.extend_from_slice(&buffer[..n]);
+            while let Some(end) = pending.iter().position(|&b| b == b'\r' || b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line[..end]);
+                if let Some((time_seconds, speed)) = parse_stats(&line) {
+                    progress(CompositeProgress {
+                        time_seconds,
+                        duration_seconds,
+                        speed,
+                    });
+                } else if !line.trim().is_empty() {
+                    warn!("ffmpeg: {}", line);
+                }
+            }
+        }
+        let status = child.wait().await?;
 
         if !status.success() {
             return Err(anyhow!("ffmpeg exited with status: {status}"));
@@ -519,6 +743,57 @@ mod tests {
         assert!(args.contains(&"output.mp4".to_string()));
     }
 
+    #[test]
+    fn test_build_args_chapters() {
+        let compositor =
+            Compositor::with_config(CompositorConfig::default().with_chapters("chapters.ffmeta"));
+        let args = compositor.build_args("input.mp4", Some("output.mp4"), "");
+
+        let at = args.iter().position(|a| a == "chapters.ffmeta").unwrap();
+        assert_eq!(args[at - 1], "-i");
+        assert_eq!(args[at - 2], "input.mp4");
+        assert_eq!(args[at + 1..at + 3], ["-map_chapters", "1"]);
+    }
+
+    #[test]
+    fn test_build_args_loudness_and_audio_output() {
+        let config = CompositorConfig::default()
+            .with_loudness(LoudnessTarget::Ebu)
+            .with_audio_output("talk.mp3");
+        let compositor = Compositor::with_config(config);
+        let args = compositor.build_args("input.mp4", Some("output.mp4"), "");
+
+        let video = args.iter().position(|a| a == "output.mp4").unwrap();
+        let (main, audio) = args.split_at(video + 1);
+        let af = main.iter().position(|a| a == "-af").unwrap();
+        assert!(main[af + 1].starts_with("loudnorm=I=-23:"));
+        let ca = main.iter().position(|a| a == "-c:a").unwrap();
+        assert_eq!(main[ca + 1], "aac");
+
+        assert_eq!(audio[..3], ["-vn", "-sn", "-dn"]);
+        assert!(audio.contains(&LoudnessTarget::Ebu.filter().to_string()));
+        assert_eq!(
+            audio[audio.len() - 4..],
+            ["-c:a", "libmp3lame", "-y", "talk.mp3"]
+        );
+    }
+
+    #[test]
+    fn test_parse_stats() {
+        let line = "frame= 1234 fps=240 q=28.0 size=   12345kB time=00:01:23.45 bitrate=1211.9kbits/s speed=8.02x";
+        let (time, speed) = parse_stats(line).unwrap();
+        assert!((time - 83.45).abs() < 1e-9);
+        assert_eq!(speed, Some(8.02));
+        assert!(parse_stats("time=N/A bitrate=N/A speed=N/A").is_none());
+
+        let progress = CompositeProgress {
+            time_seconds: 30.0,
+            duration_seconds: Some(120.0),
+            speed,
+        };
+        assert_eq!(progress.percent(), Some(25.0));
+    }
+
     #[test]
     fn test_build_args_pipe_output() {
         let compositor = Compositor::default();
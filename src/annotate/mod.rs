@@ -27,7 +27,10 @@ pub mod overlay;
 pub mod pipeline;
 pub mod subtitle;
 
-pub use compositor::{Compositor, CompositorConfig, CompositorOutput};
+pub use compositor::{
+    CompositeProgress, Compositor, CompositorConfig, CompositorOutput, LoudnessTarget,
+    ProgressCallback,
+};
 pub use overlay::{
     AnalysisOverlay, OverlayEntry, OverlayPosition, OverlayStyle, OverlayTrack, SpeakerLabelOverlay,
 };
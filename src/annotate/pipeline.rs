@@ -14,7 +14,7 @@ use tokio::io::AsyncWrite;
 use tokio::process::Command;
 use tracing::{debug, info};
 
-use super::compositor::{Compositor, CompositorConfig};
+use super::compositor::{Compositor, CompositorConfig, ProgressCallback};
 use super::overlay::{AnalysisOverlay, OverlayPosition, SpeakerLabelOverlay};
 use super::subtitle::{AssGenerator, SubtitleEntry, SubtitleGenerator};
 
@@ -264,6 +264,13 @@ impl AnnotationPipeline {
         Ok(Self { config, compositor })
     }
 
+    /// Report compositing progress
+    #[must_use]
+    pub fn with_progress(mut self, callback: ProgressCallback) -> Self {
+        self.compositor = self.compositor.with_progress(callback);
+        self
+    }
+
     /// Create pipeline with default config
     pub fn default_pipeline() -> Result<Self> {
         Self::new(PipelineConfig::default())
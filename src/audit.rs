@@ -0,0 +1,689 @@
+//! Security Header Audits (`nab audit headers`)
+//!
+//! Fetches a page the way a browser would (fingerprinted client, navigation
+//! headers) and grades its response headers against a [`HeaderPolicy`]:
+//!
+//! | Check | Weight | Passes with |
+//! |-------|--------|-------------|
+//! | `hsts` | 25 | `Strict-Transport-Security` with a long enough `max-age` (HTTPS only) |
+//! | `csp` | 25 | an enforced `Content-Security-Policy` whose script sources avoid `'unsafe-inline'` and friends |
+//! | `framing` | 15 | `X-Frame-Options: DENY`/`SAMEORIGIN` or CSP `frame-ancestors` |
+//! | `nosniff` | 10 | `X-Content-Type-Options: nosniff` |
+//! | `referrer` | 5 | a `Referrer-Policy` that doesn't leak full URLs |
+//! | `cookies` | 15 | every `Set-Cookie` has `Secure`, `HttpOnly`, and `SameSite` |
+//! | `disclosure` | 5 | no `X-Powered-By` or versioned `Server` header |
+//! | `required` | 5 | every header the policy lists (only with a non-empty list) |
+//!
+//! A pass earns the check's weight and a warning half of it. The score is the
+//! share earned of the checks that ran, graded A (90+) to F (below 60).
+//!
+//! The policy comes from `--policy FILE` or the `audit` section of the config;
+//! unset fields keep the defaults below:
+//!
+//! ```json
+//! {
+//!   "hsts_min_age": 31536000,
+//!   "hsts_include_subdomains": true,
+//!   "csp_forbidden_sources": ["'unsafe-inline'", "'unsafe-eval'", "*", "data:"],
+//!   "required": ["Permissions-Policy"],
+//!   "skip": ["referrer"]
+//! }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::fingerprint::FetchContext;
+use crate::request_options::RequestOptions;
+use crate::AcceleratedClient;
+
+/// Names of the checks, as used in [`HeaderPolicy::skip`]
+pub const CHECKS: &[&str] = &[
+    "hsts",
+    "csp",
+    "framing",
+    "nosniff",
+    "referrer",
+    "cookies",
+    "disclosure",
+    "required",
+];
+
+/// What the audited headers must look like
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeaderPolicy {
+    /// Minimum HSTS `max-age`, in seconds (180 days)
+    pub hsts_min_age: u64,
+    /// Whether HSTS must cover subdomains
+    pub hsts_include_subdomains: bool,
+    /// Whether a missing CSP fails the `csp` check (a warning otherwise)
+    pub require_csp: bool,
+    /// Script sources the CSP must not allow
+    pub csp_forbidden_sources: Vec<String>,
+    /// Accepted `X-Frame-Options` values
+    pub frame_options: Vec<String>,
+    /// Accepted `Referrer-Policy` values
+    pub referrer_policies: Vec<String>,
+    /// Whether cookies must be `HttpOnly`
+    pub cookie_http_only: bool,
+    /// Whether cookies must set `SameSite`
+    pub cookie_same_site: bool,
+    /// Headers that must be present
+    pub required: Vec<String>,
+    /// Headers that give away the server stack
+    pub disclosing: Vec<String>,
+    /// Checks not to run
+    pub skip: Vec<String>,
+}
+
+impl Default for HeaderPolicy {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(ToString::to_string).collect();
+        Self {
+            hsts_min_age: 15_552_000,
+            hsts_include_subdomains: false,
+            require_csp: true,
+            csp_forbidden_sources: strings(&["'unsafe-inline'", "'unsafe-eval'", "*"]),
+            frame_options: strings(&["DENY", "SAMEORIGIN"]),
+            referrer_policies: strings(&[
+                "no-referrer",
+                "same-origin",
+                "strict-origin",
+                "strict-origin-when-cross-origin",
+            ]),
+            cookie_http_only: true,
+            cookie_same_site: true,
+            required: Vec::new(),
+            disclosing: strings(&["X-Powered-By", "X-AspNet-Version", "X-AspNetMvc-Version"]),
+            skip: Vec::new(),
+        }
+    }
+}
+
+impl HeaderPolicy {
+    /// Fail on checks in [`skip`](Self::skip) that don't exist
+    pub fn validate(&self) -> Result<()> {
+        for check in &self.skip {
+            if !CHECKS.contains(&check.as_str()) {
+                bail!("Unknown check '{check}' in skip ({})", CHECKS.join(", "));
+            }
+        }
+        Ok(())
+    }
+
+    fn runs(&self, check: &str) -> bool {
+        !self.skip.iter().any(|skipped| skipped == check)
+    }
+}
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Pass => "✅ pass",
+            Self::Warn => "⚠️ warn",
+            Self::Fail => "❌ fail",
+        }
+    }
+}
+
+/// Letter grade of a score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Grade {
+    A,
+    B,
+    C,
+    D,
+    F,
+}
+
+impl Grade {
+    /// Grade of a 0–100 score
+    #[must_use]
+    pub fn of(score: u32) -> Self {
+        match score {
+            90.. => Self::A,
+            80..=89 => Self::B,
+            70..=79 => Self::C,
+            60..=69 => Self::D,
+            _ => Self::F,
+        }
+    }
+}
+
+impl FromStr for Grade {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "A" => Ok(Self::A),
+            "B" => Ok(Self::B),
+            "C" => Ok(Self::C),
+            "D" => Ok(Self::D),
+            "F" => Ok(Self::F),
+            _ => bail!("Unknown grade '{s}' (A, B, C, D, F)"),
+        }
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Result of one check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub check: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+/// Graded headers of one URL
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditReport {
+    pub url: String,
+    pub final_url: String,
+    pub status: u16,
+    pub audited_at: DateTime<Utc>,
+    pub grade: Grade,
+    pub score: u32,
+    pub findings: Vec<Finding>,
+}
+
+impl AuditReport {
+    /// Markdown section with the grade and a table of findings
+    #[must_use]
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "## {}: {} ({}/100)\n\nStatus {}",
+            self.url, self.grade, self.score, self.status
+        );
+        if self.final_url != self.url {
+            out.push_str(&format!(", redirected to {}", self.final_url));
+        }
+        out.push_str(&format!(
+            ", audited {}\n\n| Check | Result | Details |\n|-------|--------|---------|\n",
+            self.audited_at.format("%Y-%m-%d %H:%M UTC")
+        ));
+        for finding in &self.findings {
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                finding.check,
+                finding.status.label(),
+                finding.detail.replace('|', "\\|")
+            ));
+        }
+        out
+    }
+}
+
+/// Fetch `url` like a browser navigation and grade its headers
+pub async fn run(
+    url: &str,
+    options: &RequestOptions,
+    policy: &HeaderPolicy,
+) -> Result<AuditReport> {
+    let response = fetch(url, options).await?;
+    Ok(audit(
+        url,
+        response.url(),
+        response.status().as_u16(),
+        response.headers(),
+        policy,
+    ))
+}
+
+/// GET `url` as a browser navigation: fingerprinted client, navigation
+/// headers, and `options`' cookies, headers, and retries
+pub async fn fetch(url: &str, options: &RequestOptions) -> Result<reqwest::Response> {
+    let profile = options.browser_profile();
+    let client = AcceleratedClient::with_profile_and_options(profile.clone(), &options.client)?;
+    let parsed = Url::parse(url)?;
+    let mut request = client
+        .inner()
+        .get(parsed.clone())
+        .headers(profile.request_headers(FetchContext::Navigate, "none"));
+    let cookies = options
+        .cookies
+        .header_for(parsed.host_str().unwrap_or_default());
+    if !cookies.is_empty() {
+        request = request.header(reqwest::header::COOKIE, cookies);
+    }
+    options.send(request.headers(options.headers.clone())).await
+}
+
+/// Grade the headers `final_url` answered with
+#[must_use]
+pub fn audit(
+    url: &str,
+    final_url: &Url,
+    status: u16,
+    headers: &HeaderMap,
+    policy: &HeaderPolicy,
+) -> AuditReport {
+    let https = final_url.scheme() == "https";
+    let csp = header(headers, "content-security-policy").map(directives);
+    let mut findings = Vec::new();
+    let mut earned = 0;
+    let mut possible = 0;
+    let mut record = |check: &str, weight: u32, (status, detail): (Status, String)| {
+        if !policy.runs(check) {
+            return;
+        }
+        possible += weight;
+        earned += match status {
+            Status::Pass => weight * 2,
+            Status::Warn => weight,
+            Status::Fail => 0,
+        };
+        findings.push(Finding {
+            check: check.to_string(),
+            status,
+            detail,
+        });
+    };
+
+    record("hsts", 25, check_hsts(headers, https, policy));
+    record("csp", 25, check_csp(headers, csp.as_deref(), policy));
+    record(
+        "framing",
+        15,
+        check_framing(headers, csp.as_deref(), policy),
+    );
+    record("nosniff", 10, check_nosniff(headers));
+    record("referrer", 5, check_referrer(headers, policy));
+    record("cookies", 15, check_cookies(headers, https, policy));
+    record("disclosure", 5, check_disclosure(headers, policy));
+    if !policy.required.is_empty() {
+        record("required", 5, check_required(headers, policy));
+    }
+
+    // Weights count double so a warning's half stays whole; nothing ran scores 100
+    let score = (earned * 50 + possible / 2)
+        .checked_div(possible)
+        .unwrap_or(100);
+    AuditReport {
+        url: url.to_string(),
+        final_url: final_url.to_string(),
+        status,
+        audited_at: Utc::now(),
+        grade: Grade::of(score),
+        score,
+        findings,
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// `Content-Security-Policy` as `(directive, sources)` pairs
+fn directives(csp: &str) -> Vec<(String, Vec<String>)> {
+    csp.split(';')
+        .filter_map(|directive| {
+            let mut parts = directive.split_whitespace();
+            let name = parts.next()?.to_ascii_lowercase();
+            Some((name, parts.map(str::to_ascii_lowercase).collect()))
+        })
+        .collect()
+}
+
+fn sources<'a>(csp: &'a [(String, Vec<String>)], directive: &str) -> Option<&'a [String]> {
+    csp.iter()
+        .find(|(name, _)| name == directive)
+        .map(|(_, sources)| sources.as_slice())
+}
+
+fn check_hsts(headers: &HeaderMap, https: bool, policy: &HeaderPolicy) -> (Status, String) {
+    if !https {
+        return (Status::Fail, "served over plain HTTP".to_string());
+    }
+    let Some(value) = header(headers, "strict-transport-security") else {
+        return (
+            Status::Fail,
+            "Strict-Transport-Security missing".to_string(),
+        );
+    };
+    let mut max_age = None;
+    let mut subdomains = false;
+    for directive in value.split(';').map(str::trim) {
+        let lower = directive.to_ascii_lowercase();
+        if let Some(age) = lower.strip_prefix("max-age=") {
+            max_age = age.trim_matches('"').parse::<u64>().ok();
+        } else if lower == "includesubdomains" {
+            subdomains = true;
+        }
+    }
+    match max_age {
+        None => (Status::Fail, format!("no valid max-age in '{value}'")),
+        Some(age) if age < policy.hsts_min_age => (
+            Status::Warn,
+            format!("max-age={age} is below {}", policy.hsts_min_age),
+        ),
+        Some(_) if policy.hsts_include_subdomains && !subdomains => {
+            (Status::Warn, format!("'{value}' lacks includeSubDomains"))
+        }
+        Some(_) => (Status::Pass, value.to_string()),
+    }
+}
+
+fn check_csp(
+    headers: &HeaderMap,
+    csp: Option<&[(String, Vec<String>)]>,
+    policy: &HeaderPolicy,
+) -> (Status, String) {
+    let Some(csp) = csp else {
+        let missing = if header(headers, "content-security-policy-report-only").is_some() {
+            "only Content-Security-Policy-Report-Only, nothing enforced"
+        } else {
+            "Content-Security-Policy missing"
+        };
+        let status = if policy.require_csp {
+            Status::Fail
+        } else {
+            Status::Warn
+        };
+        return (status, missing.to_string());
+    };
+    let Some(scripts) = sources(csp, "script-src").or_else(|| sources(csp, "default-src")) else {
+        return (
+            Status::Warn,
+            "no script-src or default-src, scripts are unrestricted".to_string(),
+        );
+    };
+    let allowed: Vec<&str> = policy
+        .csp_forbidden_sources
+        .iter()
+        .filter(|forbidden| scripts.contains(&forbidden.to_ascii_lowercase()))
+        .map(String::as_str)
+        .collect();
+    // 'unsafe-inline' is ignored by browsers once a nonce or hash is present
+    let nonced = scripts
+        .iter()
+        .any(|source| source.starts_with("'nonce-") || source.starts_with("'sha"));
+    let allowed: Vec<&str> = allowed
+        .into_iter()
+        .filter(|source| !(nonced && *source == "'unsafe-inline'"))
+        .collect();
+    if allowed.is_empty() {
+        (
+            Status::Pass,
+            format!("script sources: {}", scripts.join(" ")),
+        )
+    } else {
+        (
+            Status::Warn,
+            format!("scripts allow {}", allowed.join(", ")),
+        )
+    }
+}
+
+fn check_framing(
+    headers: &HeaderMap,
+    csp: Option<&[(String, Vec<String>)]>,
+    policy: &HeaderPolicy,
+) -> (Status, String) {
+    if let Some(ancestors) = csp.and_then(|csp| sources(csp, "frame-ancestors")) {
+        return if ancestors.iter().any(|source| source == "*") {
+            (Status::Fail, "CSP frame-ancestors allows *".to_string())
+        } else {
+            (
+                Status::Pass,
+                format!("CSP frame-ancestors {}", ancestors.join(" ")),
+            )
+        };
+    }
+    match header(headers, "x-frame-options") {
+        Some(value)
+            if policy
+                .frame_options
+                .iter()
+                .any(|accepted| accepted.eq_ignore_ascii_case(value.trim())) =>
+        {
+            (Status::Pass, format!("X-Frame-Options: {value}"))
+        }
+        Some(value) => (
+            Status::Fail,
+            format!("X-Frame-Options: {value} not accepted"),
+        ),
+        None => (
+            Status::Fail,
+            "neither X-Frame-Options nor CSP frame-ancestors".to_string(),
+        ),
+    }
+}
+
+fn check_nosniff(headers: &HeaderMap) -> (Status, String) {
+    match header(headers, "x-content-type-options") {
+        Some(value) if value.trim().eq_ignore_ascii_case("nosniff") => {
+            (Status::Pass, "nosniff".to_string())
+        }
+        Some(value) => (Status::Fail, format!("X-Content-Type-Options: {value}")),
+        None => (Status::Fail, "X-Content-Type-Options missing".to_string()),
+    }
+}
+
+fn check_referrer(headers: &HeaderMap, policy: &HeaderPolicy) -> (Status, String) {
+    let Some(value) = header(headers, "referrer-policy") else {
+        return (
+            Status::Warn,
+            "Referrer-Policy missing (browsers default to strict-origin-when-cross-origin)"
+                .to_string(),
+        );
+    };
+    // The last policy a browser understands wins
+    let effective = value.rsplit(',').next().unwrap_or(value).trim();
+    if policy
+        .referrer_policies
+        .iter()
+        .any(|accepted| accepted.eq_ignore_ascii_case(effective))
+    {
+        (Status::Pass, effective.to_string())
+    } else if effective.eq_ignore_ascii_case("unsafe-url") {
+        (
+            Status::Fail,
+            "unsafe-url sends full URLs everywhere".to_string(),
+        )
+    } else {
+        (Status::Warn, format!("{effective} not accepted"))
+    }
+}
+
+fn check_cookies(headers: &HeaderMap, https: bool, policy: &HeaderPolicy) -> (Status, String) {
+    let mut status = Status::Pass;
+    let mut problems = Vec::new();
+    let mut count = 0;
+    for cookie in headers.get_all(SET_COOKIE) {
+        let Ok(cookie) = cookie.to_str() else {
+            continue;
+        };
+        count += 1;
+        let mut parts = cookie.split(';').map(str::trim);
+        let name = parts
+            .next()
+            .and_then(|pair| pair.split('=').next())
+            .unwrap_or_default();
+        let attributes: Vec<String> = parts.map(str::to_ascii_lowercase).collect();
+        let has = |attribute: &str| {
+            attributes
+                .iter()
+                .any(|a| a == attribute || a.starts_with(&format!("{attribute}=")))
+        };
+        let mut missing = Vec::new();
+        if https && !has("secure") {
+            missing.push("Secure");
+            status = Status::Fail;
+        }
+        if policy.cookie_http_only && !has("httponly") {
+            missing.push("HttpOnly");
+        }
+        if policy.cookie_same_site && !has("samesite") {
+            missing.push("SameSite");
+        }
+        if !missing.is_empty() {
+            if status == Status::Pass {
+                status = Status::Warn;
+            }
+            problems.push(format!("{name} lacks {}", missing.join(", ")));
+        }
+    }
+    match count {
+        0 => (Status::Pass, "no cookies set".to_string()),
+        _ if problems.is_empty() => (Status::Pass, format!("{count} cookies, all flagged")),
+        _ => (status, problems.join("; ")),
+    }
+}
+
+fn check_disclosure(headers: &HeaderMap, policy: &HeaderPolicy) -> (Status, String) {
+    let mut disclosed: Vec<String> = policy
+        .disclosing
+        .iter()
+        .filter_map(|name| header(headers, name).map(|value| format!("{name}: {value}")))
+        .collect();
+    if let Some(server) =
+        header(headers, "server").filter(|s| s.contains(|c: char| c.is_ascii_digit()))
+    {
+        disclosed.push(format!("Server: {server}"));
+    }
+    if disclosed.is_empty() {
+        (Status::Pass, "no stack details".to_string())
+    } else {
+        (Status::Warn, disclosed.join("; "))
+    }
+}
+
+fn check_required(headers: &HeaderMap, policy: &HeaderPolicy) -> (Status, String) {
+    let missing: Vec<&str> = policy
+        .required
+        .iter()
+        .filter(|name| !headers.contains_key(name.as_str()))
+        .map(String::as_str)
+        .collect();
+    if missing.is_empty() {
+        (Status::Pass, policy.required.join(", "))
+    } else {
+        (Status::Fail, format!("missing {}", missing.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.append(*name, value.parse().unwrap());
+        }
+        map
+    }
+
+    fn status_of(report: &AuditReport, check: &str) -> Status {
+        report
+            .findings
+            .iter()
+            .find(|finding| finding.check == check)
+            .unwrap()
+            .status
+    }
+
+    #[test]
+    fn test_hardened_site_grades_a() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let report = audit(
+            url.as_str(),
+            &url,
+            200,
+            &headers(&[
+                ("strict-transport-security", "max-age=63072000; includeSubDomains"),
+                (
+                    "content-security-policy",
+                    "default-src 'self'; script-src 'self' 'nonce-abc' 'unsafe-inline'; frame-ancestors 'none'",
+                ),
+                ("x-content-type-options", "nosniff"),
+                ("referrer-policy", "no-referrer, strict-origin-when-cross-origin"),
+                ("set-cookie", "sid=1; Path=/; Secure; HttpOnly; SameSite=Lax"),
+                ("server", "nginx"),
+            ]),
+            &HeaderPolicy::default(),
+        );
+        assert!(
+            report.findings.iter().all(|f| f.status == Status::Pass),
+            "{report:?}"
+        );
+        assert_eq!((report.grade, report.score), (Grade::A, 100));
+        assert_eq!(report.findings.len(), 7);
+    }
+
+    #[test]
+    fn test_weak_headers_and_policy() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let weak = headers(&[
+            ("strict-transport-security", "max-age=3600"),
+            ("content-security-policy", "script-src 'self' 'unsafe-eval'"),
+            ("x-frame-options", "ALLOW-FROM https://a.example"),
+            ("set-cookie", "sid=1; HttpOnly"),
+            ("set-cookie", "theme=dark; Secure"),
+            ("x-powered-by", "PHP/8.1"),
+            ("server", "Apache/2.4.1"),
+        ]);
+        let report = audit(url.as_str(), &url, 200, &weak, &HeaderPolicy::default());
+        assert_eq!(status_of(&report, "hsts"), Status::Warn);
+        assert_eq!(status_of(&report, "csp"), Status::Warn);
+        assert_eq!(status_of(&report, "framing"), Status::Fail);
+        assert_eq!(status_of(&report, "nosniff"), Status::Fail);
+        assert_eq!(status_of(&report, "referrer"), Status::Warn);
+        assert_eq!(status_of(&report, "cookies"), Status::Fail);
+        assert_eq!(status_of(&report, "disclosure"), Status::Warn);
+        assert_eq!(report.grade, Grade::F);
+        assert!(report.to_markdown().contains(
+            "| cookies | ❌ fail | sid lacks Secure, SameSite; theme lacks HttpOnly, SameSite |"
+        ));
+
+        let policy: HeaderPolicy = serde_json::from_str(
+            r#"{"hsts_min_age": 60, "required": ["Permissions-Policy"], "skip": ["cookies", "disclosure"]}"#,
+        )
+        .unwrap();
+        policy.validate().unwrap();
+        let report = audit(url.as_str(), &url, 200, &weak, &policy);
+        assert_eq!(status_of(&report, "hsts"), Status::Pass);
+        assert_eq!(status_of(&report, "required"), Status::Fail);
+        assert!(report.findings.iter().all(|f| f.check != "cookies"));
+
+        let http = Url::parse("http://example.com/").unwrap();
+        let report = audit(
+            http.as_str(),
+            &http,
+            200,
+            &HeaderMap::new(),
+            &HeaderPolicy::default(),
+        );
+        assert_eq!(status_of(&report, "hsts"), Status::Fail);
+        assert_eq!((report.grade, report.score), (Grade::F, 23));
+
+        assert!(
+            serde_json::from_str::<HeaderPolicy>(r#"{"skip": ["typo"]}"#)
+                .unwrap()
+                .validate()
+                .is_err()
+        );
+        assert!("b".parse::<Grade>().unwrap() < Grade::C);
+    }
+}
@@ -0,0 +1,391 @@
+//! Batch Scheduling
+//!
+//! Runs many fetches under two limits:
+//! - global: total requests in flight
+//! - per-host: requests in flight to any single origin
+//!
+//! URLs are grouped into per-host queues and dispatched round-robin, so a
+//! list sorted by site still spreads across hosts instead of draining one
+//! origin at a time.
+//!
+//! Converting fetched pages to Markdown is CPU-bound, so it runs on a
+//! [`ParsePool`] rather than the async fetch workers: network and parsing
+//! overlap, and a slow conversion never holds a host's fetch slot.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+
+use anyhow::{Context, Result};
+use futures::stream::{FuturesUnordered, StreamExt};
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+/// In-flight request limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConcurrencyLimits {
+    /// Maximum simultaneous requests to one host
+    pub per_host: usize,
+    /// Maximum simultaneous requests overall
+    pub global: usize,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            per_host: 2,
+            global: 16,
+        }
+    }
+}
+
+impl ConcurrencyLimits {
+    /// Limits clamped to at least one request each
+    #[must_use]
+    pub fn new(per_host: usize, global: usize) -> Self {
+        Self {
+            per_host: per_host.max(1),
+            global: global.max(1),
+        }
+    }
+}
+
+/// Scheduling key for a URL: lower-case `host[:port]` (empty if unparseable)
+#[must_use]
+pub fn host_key(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            let host = u.host_str()?.to_lowercase();
+            Some(match u.port() {
+                Some(port) => format!("{host}:{port}"),
+                None => host,
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// Per-host URL queues with round-robin dispatch under [`ConcurrencyLimits`]
+#[derive(Debug)]
+pub struct HostQueues {
+    limits: ConcurrencyLimits,
+    queues: HashMap<String, VecDeque<String>>,
+    /// Hosts with queued URLs, in rotation order
+    rotation: VecDeque<String>,
+    in_flight: HashMap<String, usize>,
+    total_in_flight: usize,
+}
+
+impl HostQueues {
+    #[must_use]
+    pub fn new(limits: ConcurrencyLimits) -> Self {
+        Self {
+            limits: ConcurrencyLimits::new(limits.per_host, limits.global),
+            queues: HashMap::new(),
+            rotation: VecDeque::new(),
+            in_flight: HashMap::new(),
+            total_in_flight: 0,
+        }
+    }
+
+    /// Queue a URL behind others for the same host
+    pub fn push(&mut self, url: String) {
+        let host = host_key(&url);
+        let queue = self.queues.entry(host.clone()).or_insert_with(|| {
+            self.rotation.push_back(host);
+            VecDeque::new()
+        });
+        queue.push_back(url);
+    }
+
+    /// Next `(host, url)` allowed to start, rotating through hosts
+    pub fn next_ready(&mut self) -> Option<(String, String)> {
+        if self.total_in_flight >= self.limits.global {
+            return None;
+        }
+        for _ in 0..self.rotation.len() {
+            let host = self.rotation.pop_front()?;
+            let busy = self.in_flight.get(&host).copied().unwrap_or(0);
+            if busy >= self.limits.per_host {
+                self.rotation.push_back(host);
+                continue;
+            }
+
+            let queue = self.queues.get_mut(&host)?;
+            let url = queue.pop_front()?;
+            if queue.is_empty() {
+                self.queues.remove(&host);
+            } else {
+                self.rotation.push_back(host.clone());
+            }
+            *self.in_flight.entry(host.clone()).or_default() += 1;
+            self.total_in_flight += 1;
+            return Some((host, url));
+        }
+        None
+    }
+
+    /// Mark a request to `host` as finished
+    pub fn finish(&mut self, host: &str) {
+        if let Some(busy) = self.in_flight.get_mut(host) {
+            *busy = busy.saturating_sub(1);
+            if *busy == 0 {
+                self.in_flight.remove(host);
+            }
+        }
+        self.total_in_flight = self.total_in_flight.saturating_sub(1);
+    }
+
+    /// URLs still waiting to start
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.queues.values().map(VecDeque::len).sum()
+    }
+
+    /// Requests currently running
+    #[must_use]
+    pub fn in_flight(&self) -> usize {
+        self.total_in_flight
+    }
+}
+
+/// Run `fetch` for every URL under `limits`, calling `on_done` as each finishes
+///
+/// Results arrive in completion order, not input order.
+pub async fn run_scheduled<T, F, Fut>(
+    urls: impl IntoIterator<Item = String>,
+    limits: ConcurrencyLimits,
+    fetch: F,
+    mut on_done: impl FnMut(String, T),
+) where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = T>,
+{
+    let mut queues = HostQueues::new(limits);
+    for url in urls {
+        queues.push(url);
+    }
+
+    let mut running = FuturesUnordered::new();
+    loop {
+        while let Some((host, url)) = queues.next_ready() {
+            let request = fetch(url.clone());
+            running.push(async move { (host, url, request.await) });
+        }
+        let Some((host, url, result)) = running.next().await else {
+            break;
+        };
+        queues.finish(&host);
+        on_done(url, result);
+    }
+}
+
+/// Dedicated rayon pool for CPU-bound work on fetched pages
+///
+/// Fetchers hand jobs over a bounded channel and go back to the network;
+/// `submit` only waits when twice as many jobs as threads are queued.
+pub struct ParsePool<J> {
+    sender: mpsc::Sender<J>,
+    worker: tokio::task::JoinHandle<usize>,
+    threads: usize,
+}
+
+impl<J: Send + 'static> ParsePool<J> {
+    /// `threads` workers (0 = one per core) running `work` on each job; the
+    /// pool counts the jobs `work` returns `true` for
+    pub fn new(threads: usize, work: impl Fn(J) -> bool + Send + Sync + 'static) -> Result<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("nab-parse-{i}"))
+            .build()
+            .context("Failed to start parse threads")?;
+        let threads = pool.current_num_threads();
+        let (sender, mut receiver) = mpsc::channel(threads * 2);
+        let worker = tokio::task::spawn_blocking(move || {
+            pool.install(|| {
+                std::iter::from_fn(|| receiver.blocking_recv())
+                    .par_bridge()
+                    .map(work)
+                    .filter(|done| *done)
+                    .count()
+            })
+        });
+        Ok(Self {
+            sender,
+            worker,
+            threads,
+        })
+    }
+
+    /// Worker threads
+    #[must_use]
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Queue a job, waiting while the backlog is full
+    pub async fn submit(&self, job: J) -> Result<()> {
+        self.sender
+            .send(job)
+            .await
+            .map_err(|_| anyhow::anyhow!("Parse pool stopped"))
+    }
+
+    /// Wait for queued jobs to finish, returning how many succeeded
+    pub async fn finish(self) -> Result<usize> {
+        drop(self.sender);
+        Ok(self.worker.await?)
+    }
+}
+
+/// File name for a fetched URL's Markdown (`host_path-hash.md`)
+///
+/// The hash of the full URL keeps URLs that slug alike (`?id=2` and `/id/2`,
+/// or a shared 100-character prefix) from overwriting each other.
+#[must_use]
+pub fn url_file_name(url: &str) -> String {
+    let trimmed = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .trim_end_matches('/');
+    let mut slug: String = trimmed
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if slug.len() > 100 {
+        slug.truncate(100);
+    }
+    let hash = Sha256::digest(url.as_bytes());
+    format!(
+        "{slug}-{:02x}{:02x}{:02x}{:02x}.md",
+        hash[0], hash[1], hash[2], hash[3]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_round_robin_with_limits() {
+        let mut queues = HostQueues::new(ConcurrencyLimits::new(1, 2));
+        for url in [
+            "https://a.example/1",
+            "https://a.example/2",
+            "https://a.example/3",
+            "https://b.example/1",
+            "https://c.example/1",
+        ] {
+            queues.push(url.to_string());
+        }
+
+        assert_eq!(queues.next_ready().unwrap().1, "https://a.example/1");
+        assert_eq!(queues.next_ready().unwrap().1, "https://b.example/1");
+        // Global limit reached
+        assert!(queues.next_ready().is_none());
+
+        queues.finish("a.example");
+        assert_eq!(queues.next_ready().unwrap().1, "https://c.example/1");
+        // a.example is free, but the global limit is hit again
+        assert!(queues.next_ready().is_none());
+
+        queues.finish("b.example");
+        queues.finish("c.example");
+        assert_eq!(queues.next_ready().unwrap().1, "https://a.example/2");
+        // Only a.example is left and it's at its per-host limit
+        assert!(queues.next_ready().is_none());
+        assert_eq!(queues.queued(), 1);
+        assert_eq!(queues.in_flight(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_scheduled_respects_per_host_limit() {
+        let urls: Vec<String> = (0..6)
+            .flat_map(|i| {
+                [
+                    format!("https://a.example/{i}"),
+                    format!("https://b.example/{i}"),
+                ]
+            })
+            .collect();
+        let running: Mutex<HashMap<String, usize>> = Mutex::new(HashMap::new());
+        let peak = AtomicUsize::new(0);
+        let mut done = Vec::new();
+
+        run_scheduled(
+            urls,
+            ConcurrencyLimits::new(2, 16),
+            |url| {
+                let (running, peak) = (&running, &peak);
+                async move {
+                    let host = host_key(&url);
+                    {
+                        let mut map = running.lock().unwrap();
+                        let n = map.entry(host.clone()).or_default();
+                        *n += 1;
+                        peak.fetch_max(*n, Ordering::SeqCst);
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                    *running.lock().unwrap().get_mut(&host).unwrap() -= 1;
+                    url.len()
+                }
+            },
+            |url, _| done.push(url),
+        )
+        .await;
+
+        assert_eq!(done.len(), 12);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_parse_pool() {
+        let pool = ParsePool::new(2, |n: usize| {
+            // Busy enough that jobs queue up behind the backlog
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            n.is_multiple_of(2)
+        })
+        .unwrap();
+        assert_eq!(pool.threads(), 2);
+        for n in 0..20 {
+            pool.submit(n).await.unwrap();
+        }
+        assert_eq!(pool.finish().await.unwrap(), 10);
+    }
+
+    #[test]
+    fn test_host_key_and_file_name() {
+        assert_eq!(host_key("https://Docs.Example.com/a"), "docs.example.com");
+        assert_eq!(host_key("http://localhost:8080/"), "localhost:8080");
+        assert_eq!(host_key("not a url"), "");
+        let name = url_file_name("https://example.com/blog/post?id=2");
+        assert!(name.starts_with("example.com_blog_post_id_2-"), "{name}");
+        assert_eq!(name.len(), "example.com_blog_post_id_2-12345678.md".len());
+        assert_eq!(name, url_file_name("https://example.com/blog/post?id=2"));
+    }
+
+    #[test]
+    fn test_file_names_dont_collide() {
+        let long = format!("https://example.com/{}", "a".repeat(200));
+        let pairs = [
+            (
+                "https://example.com/post?id=2",
+                "https://example.com/post/id/2",
+            ),
+            ("https://example.com/a b", "https://example.com/a_b"),
+            ("https://example.com/docs/", "https://example.com/docs"),
+            (&format!("{long}/1"), &format!("{long}/2")),
+        ];
+        for (a, b) in pairs {
+            assert_ne!(url_file_name(a), url_file_name(b), "{a} vs {b}");
+        }
+    }
+}
@@ -575,6 +575,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_writer(std::io::stderr)
         .init();
 
+    // Before the first client, so its lookups and redirects are checked too
+    nab::policy::install(nab::policy::load()?);
+
     // Pre-initialize the HTTP client
     let _ = get_client().await;
 
@@ -0,0 +1,421 @@
+//! Canonical and Hreflang Analysis (`nab audit canonical`)
+//!
+//! `nab crawl` records each HTML page's `rel=canonical`, its `hreflang`
+//! alternates, and whether it's `noindex` or `nofollow` (robots meta tag or
+//! `X-Robots-Tag`, whichever bot it names).
+//! [`analyze`] reads those results back (a crawl manifest or the crawl's JSON
+//! lines), groups pages into clusters by the canonical URL they declare, and
+//! flags what search engines would reject or ignore:
+//!
+//! | Issue | Meaning |
+//! |-------|---------|
+//! | `canonical_to_noindex` | the canonical page is `noindex` |
+//! | `canonical_to_error` | the canonical page didn't answer 2xx |
+//! | `canonical_chain` | the canonical page declares another canonical |
+//! | `canonical_loop` | two pages declare each other canonical |
+//! | `hreflang_missing_return` | an alternate doesn't link back |
+//! | `hreflang_missing_self` | a page's alternates don't include itself |
+//! | `hreflang_conflict` | an alternate names another URL for the page's language |
+//! | `hreflang_to_non_canonical` | an alternate canonicalizes to a third page |
+//! | `hreflang_loop` | an alternate canonicalizes back to the page listing it |
+//!
+//! Pages the crawl didn't reach can't be checked and are left out. URLs are
+//! compared after [`normalize_url`].
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result};
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::crawl::normalize_url;
+
+/// What a page says about its canonical URL, alternates, and indexing
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageSignals {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical: Option<String>,
+    /// Language (lowercase, or `x-default`) to alternate URL
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hreflang: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub noindex: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub nofollow: bool,
+}
+
+impl PageSignals {
+    /// Robots directives of an `X-Robots-Tag` header, for any content type
+    #[must_use]
+    pub fn from_robots_header(robots_header: Option<&str>) -> Self {
+        let mut signals = Self::default();
+        if let Some(directives) = robots_header {
+            signals.add_robots(directives);
+        }
+        signals
+    }
+
+    /// `noindex`, `nofollow`, and `none` (both) from robots directives,
+    /// with or without a bot name (`googlebot: noindex`)
+    fn add_robots(&mut self, directives: &str) {
+        for directive in directives.split(',') {
+            let directive = directive.rsplit(':').next().unwrap_or_default();
+            match directive.trim().to_lowercase().as_str() {
+                "noindex" => self.noindex = true,
+                "nofollow" => self.nofollow = true,
+                "none" => (self.noindex, self.nofollow) = (true, true),
+                _ => {}
+            }
+        }
+    }
+
+    /// Signals in an HTML page at `base`, with its `X-Robots-Tag` header if any
+    #[must_use]
+    pub fn from_html(html: &str, base: &Url, robots_header: Option<&str>) -> Self {
+        let document = Html::parse_document(html);
+        let select = |css: &str| Selector::parse(css).unwrap();
+        let resolve = |href: &str| {
+            base.join(href.trim())
+                .ok()
+                .map(|u| normalize_url(u.as_str()))
+        };
+
+        let canonical = document
+            .select(&select(r#"link[rel~="canonical" i][href]"#))
+            .find_map(|link| resolve(link.value().attr("href")?));
+        let hreflang = document
+            .select(&select(r#"link[rel~="alternate" i][hreflang][href]"#))
+            .filter_map(|link| {
+                let language = link.value().attr("hreflang")?.trim().to_lowercase();
+                Some((language, resolve(link.value().attr("href")?)?))
+            })
+            .collect();
+        let mut signals = Self {
+            canonical,
+            hreflang,
+            ..Self::from_robots_header(robots_header)
+        };
+        for meta in document.select(&select(
+            r#"meta[name="robots" i], meta[name="googlebot" i]"#,
+        )) {
+            signals.add_robots(meta.value().attr("content").unwrap_or_default());
+        }
+        signals
+    }
+}
+
+/// A crawled page, as the crawl's JSON line or manifest entry has it
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CrawledPage {
+    pub url: String,
+    /// Missing for pages that failed
+    #[serde(default)]
+    pub status: Option<u16>,
+    #[serde(flatten)]
+    pub signals: PageSignals,
+}
+
+/// Crawled pages from a crawl manifest (`{"pages": [...]}`) or JSON lines
+pub fn read_pages(text: &str) -> Result<Vec<CrawledPage>> {
+    #[derive(Deserialize)]
+    struct Manifest {
+        pages: Vec<CrawledPage>,
+    }
+    if let Ok(manifest) = serde_json::from_str::<Manifest>(text) {
+        return Ok(manifest.pages);
+    }
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim_start().starts_with('{'))
+        // Other JSON lines, like a trailing {"stats": ...}, aren't pages
+        .filter(|(_, line)| !line.contains(r#""stats""#) || line.contains(r#""url""#))
+        .map(|(n, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Line {} isn't a crawled page", n + 1))
+        })
+        .collect()
+}
+
+/// Kind of problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    CanonicalToNoindex,
+    CanonicalToError,
+    CanonicalChain,
+    CanonicalLoop,
+    HreflangMissingReturn,
+    HreflangMissingSelf,
+    HreflangConflict,
+    HreflangToNonCanonical,
+    HreflangLoop,
+}
+
+/// One problem, on `page`, about its link to `target`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub page: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub message: String,
+}
+
+/// Pages declaring the same canonical URL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Cluster {
+    pub canonical: String,
+    pub pages: Vec<String>,
+}
+
+/// Clusters and issues of a crawl
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub pages: usize,
+    /// Canonical clusters with more than one page
+    pub clusters: Vec<Cluster>,
+    pub issues: Vec<Issue>,
+    pub summary: BTreeMap<IssueKind, usize>,
+}
+
+/// Group `pages` by canonical URL and check canonicals and hreflang alternates
+#[must_use]
+pub fn analyze(pages: &[CrawledPage]) -> Report {
+    let by_url: HashMap<String, &CrawledPage> = pages
+        .iter()
+        .map(|page| (normalize_url(&page.url), page))
+        .collect();
+    // A page's canonical, when it's another URL
+    let canonical_of = |url: &str| {
+        by_url
+            .get(url)?
+            .signals
+            .canonical
+            .as_deref()
+            .filter(|canonical| *canonical != url)
+    };
+
+    let mut clusters: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut issues = Vec::new();
+    let mut issue = |kind, page: &str, target: &str, language: Option<&str>, message: String| {
+        issues.push(Issue {
+            kind,
+            page: page.to_string(),
+            target: target.to_string(),
+            language: language.map(String::from),
+            message,
+        });
+    };
+
+    let mut urls: Vec<&String> = by_url.keys().collect();
+    urls.sort();
+    for url in urls {
+        let page = by_url[url];
+        let canonical = canonical_of(url).unwrap_or(url);
+        clusters
+            .entry(canonical.to_string())
+            .or_default()
+            .push(url.clone());
+
+        if let (Some(canonical), Some(target)) = (canonical_of(url), by_url.get(canonical)) {
+            if target.signals.noindex {
+                issue(
+                    IssueKind::CanonicalToNoindex,
+                    url,
+                    canonical,
+                    None,
+                    "canonical page is noindex".to_string(),
+                );
+            }
+            match target.status {
+                Some(status) if !(200..300).contains(&status) => issue(
+                    IssueKind::CanonicalToError,
+                    url,
+                    canonical,
+                    None,
+                    format!("canonical page answered {status}"),
+                ),
+                None => issue(
+                    IssueKind::CanonicalToError,
+                    url,
+                    canonical,
+                    None,
+                    "canonical page failed to load".to_string(),
+                ),
+                _ => {}
+            }
+            match canonical_of(canonical) {
+                // Reported once, from the first page of the pair
+                Some(back) if back == url && url.as_str() < canonical => issue(
+                    IssueKind::CanonicalLoop,
+                    url,
+                    canonical,
+                    None,
+                    "pages declare each other canonical".to_string(),
+                ),
+                Some(back) if back != url => issue(
+                    IssueKind::CanonicalChain,
+                    url,
+                    canonical,
+                    None,
+                    format!("canonical page declares {back} canonical"),
+                ),
+                _ => {}
+            }
+        }
+
+        let alternates = &page.signals.hreflang;
+        if !alternates.is_empty() && !alternates.values().any(|alternate| alternate == url) {
+            issue(
+                IssueKind::HreflangMissingSelf,
+                url,
+                url,
+                None,
+                "hreflang alternates don't include the page itself".to_string(),
+            );
+        }
+        for (language, alternate) in alternates {
+            let Some(target) = by_url.get(alternate).filter(|_| alternate != url) else {
+                continue;
+            };
+            match canonical_of(alternate) {
+                Some(back) if back == url => issue(
+                    IssueKind::HreflangLoop,
+                    url,
+                    alternate,
+                    Some(language),
+                    "alternate declares this page canonical".to_string(),
+                ),
+                Some(other) => issue(
+                    IssueKind::HreflangToNonCanonical,
+                    url,
+                    alternate,
+                    Some(language),
+                    format!("alternate declares {other} canonical"),
+                ),
+                None => {}
+            }
+            if !target.signals.hreflang.values().any(|back| back == url) {
+                issue(
+                    IssueKind::HreflangMissingReturn,
+                    url,
+                    alternate,
+                    Some(language),
+                    "alternate doesn't link back".to_string(),
+                );
+            }
+            // The alternate should agree on which URL is this page's language
+            let own = alternates.iter().filter(|(_, own)| *own == url);
+            for (own_language, other) in own.filter_map(|(own_language, _)| {
+                let other = target.signals.hreflang.get(own_language)?;
+                (other != url).then_some((own_language, other))
+            }) {
+                issue(
+                    IssueKind::HreflangConflict,
+                    url,
+                    alternate,
+                    Some(own_language),
+                    format!("alternate names {other} for this page's language"),
+                );
+            }
+        }
+    }
+
+    let mut summary = BTreeMap::new();
+    for issue in &issues {
+        *summary.entry(issue.kind).or_default() += 1;
+    }
+    Report {
+        pages: by_url.len(),
+        clusters: clusters
+            .into_iter()
+            .filter(|(_, pages)| pages.len() > 1)
+            .map(|(canonical, pages)| Cluster { canonical, pages })
+            .collect(),
+        issues,
+        summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_signals() {
+        let base = Url::parse("https://shop.test/en/shoes?ref=nav").unwrap();
+        let signals = PageSignals::from_html(
+            r#"<head>
+            <link rel="canonical" href="/en/shoes">
+            <link rel="alternate" hreflang="en" href="/en/shoes">
+            <link rel="alternate" hreflang="de-DE" href="https://shop.test/de/schuhe#top">
+            <meta name="robots" content="index, nofollow">
+            </head>"#,
+            &base,
+            Some("noarchive, googlebot: noindex"),
+        );
+        assert_eq!(
+            signals.canonical.as_deref(),
+            Some("https://shop.test/en/shoes")
+        );
+        assert_eq!(signals.hreflang["de-de"], "https://shop.test/de/schuhe");
+        assert!(signals.noindex && signals.nofollow);
+        let none = PageSignals::from_robots_header(Some("NONE"));
+        assert!(none.noindex && none.nofollow);
+        assert!(!PageSignals::from_html("<p>hi</p>", &base, None).noindex);
+    }
+
+    #[test]
+    fn test_clusters_and_issues() {
+        let lines = r#"{"url": "https://a.test/en", "status": 200, "hreflang": {"en": "https://a.test/en", "de": "https://a.test/de", "fr": "https://a.test/fr"}}
+{"url": "https://a.test/de", "status": 200, "hreflang": {"de": "https://a.test/de", "en": "https://a.test/en-gb"}}
+{"url": "https://a.test/fr", "status": 200, "canonical": "https://a.test/en", "hreflang": {"fr": "https://a.test/fr", "en": "https://a.test/en"}}
+{"url": "https://a.test/p?color=red", "status": 200, "canonical": "https://a.test/p"}
+{"url": "https://a.test/p?color=blue", "status": 200, "canonical": "https://a.test/p"}
+{"url": "https://a.test/p", "status": 200, "noindex": true}
+{"url": "https://a.test/x", "status": 200, "canonical": "https://a.test/y"}
+{"url": "https://a.test/y", "status": 404, "canonical": "https://a.test/x"}
+{"stats": {"total": {}}}"#;
+        let report = analyze(&read_pages(lines).unwrap());
+        assert_eq!(report.pages, 8);
+        assert_eq!(
+            report.clusters[1],
+            Cluster {
+                canonical: "https://a.test/p".to_string(),
+                pages: vec![
+                    "https://a.test/p".to_string(),
+                    "https://a.test/p?color=blue".to_string(),
+                    "https://a.test/p?color=red".to_string(),
+                ],
+            }
+        );
+        let count = |kind| report.summary.get(&kind).copied().unwrap_or(0);
+        assert_eq!(count(IssueKind::CanonicalToNoindex), 2);
+        assert_eq!(count(IssueKind::CanonicalToError), 1);
+        assert_eq!(count(IssueKind::CanonicalLoop), 1);
+        assert_eq!(count(IssueKind::CanonicalChain), 0);
+        // /de doesn't link /en back, and names /en-gb for English
+        let de: Vec<IssueKind> = report
+            .issues
+            .iter()
+            .filter(|issue| issue.target == "https://a.test/de")
+            .map(|issue| issue.kind)
+            .collect();
+        assert_eq!(
+            de,
+            [
+                IssueKind::HreflangMissingReturn,
+                IssueKind::HreflangConflict
+            ]
+        );
+        // /fr canonicalizes to /en, which lists it
+        assert_eq!(count(IssueKind::HreflangLoop), 1);
+        assert_eq!(count(IssueKind::HreflangToNonCanonical), 0);
+
+        let manifest =
+            r#"{"seeds": [], "pages": [{"url": "https://a.test/", "status": 200, "links": 3}]}"#;
+        assert_eq!(read_pages(manifest).unwrap()[0].status, Some(200));
+    }
+}
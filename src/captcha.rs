@@ -0,0 +1,482 @@
+//! CAPTCHA Detection and Solver Hook
+//!
+//! nab doesn't solve CAPTCHAs itself. It recognizes pages blocked by a
+//! reCAPTCHA, hCaptcha, or Cloudflare Turnstile widget, so a fetch reports the
+//! challenge instead of returning it as content, and hands the challenge to a
+//! [`CaptchaSolver`] when one is configured (`--captcha-solver`):
+//! - `command:./solve.sh` runs a program with the challenge as JSON on stdin
+//! - `webhook:https://solver.internal/solve` POSTs the same JSON
+//!
+//! Solvers answer with the response token, either as plain text or as
+//! `{"token": "...", "cookies": "name=value; ..."}`. nab submits the widget's
+//! form with the token, or resends the request with the cookies.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use url::Url;
+
+use crate::challenge::visible_text_chars;
+
+/// How long a solver (possibly a human in the loop) may take
+const SOLVER_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Pages with more text than this are content with a CAPTCHA-protected form,
+/// not a CAPTCHA wall
+const MAX_TEXT_CHARS: usize = 1500;
+
+/// CAPTCHA vendor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptchaKind {
+    Recaptcha,
+    Hcaptcha,
+    Turnstile,
+}
+
+impl CaptchaKind {
+    const ALL: [Self; 3] = [Self::Turnstile, Self::Hcaptcha, Self::Recaptcha];
+
+    /// Name for reporting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Recaptcha => "reCAPTCHA",
+            Self::Hcaptcha => "hCaptcha",
+            Self::Turnstile => "Turnstile",
+        }
+    }
+
+    /// Form field the widget fills with its token
+    #[must_use]
+    pub fn response_field(self) -> &'static str {
+        match self {
+            Self::Recaptcha => "g-recaptcha-response",
+            Self::Hcaptcha => "h-captcha-response",
+            Self::Turnstile => "cf-turnstile-response",
+        }
+    }
+
+    fn widget_selector(self) -> &'static str {
+        match self {
+            Self::Recaptcha => ".g-recaptcha",
+            Self::Hcaptcha => ".h-captcha",
+            Self::Turnstile => ".cf-turnstile",
+        }
+    }
+
+    /// Substrings of the widget's script URL
+    fn script_markers(self) -> &'static [&'static str] {
+        match self {
+            Self::Recaptcha => &["google.com/recaptcha/", "recaptcha.net/recaptcha/"],
+            Self::Hcaptcha => &["hcaptcha.com/1/api.js", "js.hcaptcha.com"],
+            Self::Turnstile => &["challenges.cloudflare.com/turnstile"],
+        }
+    }
+}
+
+/// A CAPTCHA blocking a page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CaptchaChallenge {
+    pub kind: CaptchaKind,
+    pub page_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_key: Option<String>,
+    /// `data-action` of the widget (reCAPTCHA v3, Turnstile)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// Form the widget belongs to
+    #[serde(skip)]
+    pub form: Option<CaptchaForm>,
+}
+
+/// Form to submit with the solved token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptchaForm {
+    pub method: reqwest::Method,
+    pub action: Url,
+    /// Prefilled fields (hidden inputs and defaults)
+    pub fields: Vec<(String, String)>,
+}
+
+/// What a solver returned
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct CaptchaSolution {
+    pub token: String,
+    /// Cookies to send instead of (or after) submitting the token
+    #[serde(default)]
+    pub cookies: Option<String>,
+}
+
+impl CaptchaSolution {
+    /// Parse solver output: a bare token or a JSON object
+    pub fn parse(output: &str) -> Result<Self> {
+        let output = output.trim();
+        let solution = if output.starts_with('{') {
+            serde_json::from_str(output).context("Invalid solver JSON")?
+        } else {
+            Self {
+                token: output.to_string(),
+                cookies: None,
+            }
+        };
+        if solution.token.is_empty() && solution.cookies.is_none() {
+            anyhow::bail!("Solver returned neither a token nor cookies");
+        }
+        Ok(solution)
+    }
+}
+
+/// Something that turns a CAPTCHA challenge into a token
+#[async_trait]
+pub trait CaptchaSolver: Send + Sync {
+    /// Solver name for logs
+    fn name(&self) -> &str;
+
+    /// Solve `challenge`
+    async fn solve(&self, challenge: &CaptchaChallenge) -> Result<CaptchaSolution>;
+}
+
+/// Solver from `--captcha-solver`: `command:<shell command>` or `webhook:<url>`
+pub fn solver_from_spec(spec: &str) -> Result<Box<dyn CaptchaSolver>> {
+    match spec.split_once(':') {
+        Some(("command", command)) if !command.trim().is_empty() => Ok(Box::new(CommandSolver {
+            command: command.trim().to_string(),
+        })),
+        Some(("webhook", url)) => {
+            let url =
+                Url::parse(url.trim()).with_context(|| format!("Invalid webhook URL '{url}'"))?;
+            Ok(Box::new(WebhookSolver { url }))
+        }
+        _ => {
+            anyhow::bail!("Unknown CAPTCHA solver '{spec}'. Use command:<program> or webhook:<url>")
+        }
+    }
+}
+
+/// Runs a program with the challenge JSON on stdin; the token is its stdout
+pub struct CommandSolver {
+    command: String,
+}
+
+#[async_trait]
+impl CaptchaSolver for CommandSolver {
+    fn name(&self) -> &str {
+        &self.command
+    }
+
+    async fn solve(&self, challenge: &CaptchaChallenge) -> Result<CaptchaSolution> {
+        let mut child = tokio::process::Command::new(if cfg!(windows) { "cmd" } else { "sh" })
+            .arg(if cfg!(windows) { "/C" } else { "-c" })
+            .arg(&self.command)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start CAPTCHA solver: {}", self.command))?;
+        // Written while the output is read, so neither side waits on a full pipe
+        let mut stdin = child
+            .stdin
+            .take()
+            .context("CAPTCHA solver stdin unavailable")?;
+        let input = serde_json::to_vec(challenge)?;
+        let writer = tokio::spawn(async move { stdin.write_all(&input).await });
+        let output = tokio::time::timeout(SOLVER_TIMEOUT, child.wait_with_output())
+            .await
+            .with_context(|| format!("CAPTCHA solver timed out after {SOLVER_TIMEOUT:?}"))??;
+        // A solver that stops reading early is judged by its exit status
+        let _ = writer.await;
+        if !output.status.success() {
+            anyhow::bail!(
+                "CAPTCHA solver '{}' failed ({})",
+                self.command,
+                output.status
+            );
+        }
+        CaptchaSolution::parse(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// POSTs the challenge JSON; the token is the response body
+pub struct WebhookSolver {
+    url: Url,
+}
+
+#[async_trait]
+impl CaptchaSolver for WebhookSolver {
+    fn name(&self) -> &str {
+        self.url.as_str()
+    }
+
+    async fn solve(&self, challenge: &CaptchaChallenge) -> Result<CaptchaSolution> {
+        let response = crate::policy::client_builder()
+            .build()?
+            .post(self.url.clone())
+            .timeout(SOLVER_TIMEOUT)
+            .json(challenge)
+            .send()
+            .await
+            .context("CAPTCHA webhook unreachable")?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            anyhow::bail!("CAPTCHA webhook answered {status}: {}", body.trim());
+        }
+        CaptchaSolution::parse(&body)
+    }
+}
+
+/// Find a CAPTCHA wall in `html`, loaded from `page`
+#[must_use]
+pub fn detect(html: &str, page: &Url) -> Option<CaptchaChallenge> {
+    let document = Html::parse_document(html);
+    if visible_text_chars(&document) > MAX_TEXT_CHARS {
+        return None;
+    }
+    let scripts: Vec<&str> = Selector::parse("script[src]")
+        .map(|s| {
+            document
+                .select(&s)
+                .filter_map(|el| el.value().attr("src"))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    CaptchaKind::ALL.into_iter().find_map(|kind| {
+        let widget = Selector::parse(kind.widget_selector())
+            .ok()
+            .and_then(|s| document.select(&s).next());
+        let script = scripts
+            .iter()
+            .find(|src| kind.script_markers().iter().any(|m| src.contains(m)));
+        if widget.is_none() && script.is_none() {
+            return None;
+        }
+        let attr = |name: &str| {
+            widget
+                .and_then(|w| w.value().attr(name))
+                .map(str::to_string)
+        };
+        // reCAPTCHA v3 has no widget; its key is the script's `render` parameter
+        let script_key = script.and_then(|src| page.join(src).ok()).and_then(|url| {
+            url.query_pairs()
+                .find(|(k, v)| k == "render" && v != "explicit")
+                .map(|(_, v)| v.into_owned())
+        });
+        Some(CaptchaChallenge {
+            kind,
+            page_url: page.to_string(),
+            site_key: attr("data-sitekey").or(script_key),
+            action: attr("data-action"),
+            form: widget.and_then(|w| enclosing_form(w, page)),
+        })
+    })
+}
+
+fn enclosing_form(widget: ElementRef<'_>, page: &Url) -> Option<CaptchaForm> {
+    let form = widget
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .find(|el| el.value().name() == "form")?;
+    let action = form
+        .value()
+        .attr("action")
+        .filter(|a| !a.trim().is_empty())
+        .map_or_else(|| Some(page.clone()), |a| page.join(a.trim()).ok())?;
+    let method = if form
+        .value()
+        .attr("method")
+        .is_some_and(|m| m.eq_ignore_ascii_case("post"))
+    {
+        reqwest::Method::POST
+    } else {
+        reqwest::Method::GET
+    };
+    let inputs = Selector::parse("input[name], textarea[name]").ok()?;
+    let fields = form
+        .select(&inputs)
+        .filter(|el| {
+            let kind = el.value().attr("type").unwrap_or("text").to_lowercase();
+            match kind.as_str() {
+                "submit" | "button" | "image" | "file" | "reset" => false,
+                "checkbox" | "radio" => el.value().attr("checked").is_some(),
+                _ => true,
+            }
+        })
+        .filter_map(|el| {
+            let name = el.value().attr("name")?.to_string();
+            let value = if el.value().name() == "textarea" {
+                el.text().collect()
+            } else {
+                el.value().attr("value").unwrap_or_default().to_string()
+            };
+            Some((name, value))
+        })
+        .collect();
+    Some(CaptchaForm {
+        method,
+        action,
+        fields,
+    })
+}
+
+impl CaptchaChallenge {
+    /// Request submitting `token` through the widget's form, if it has one
+    #[must_use]
+    pub fn submission(
+        &self,
+        client: &reqwest::Client,
+        token: &str,
+    ) -> Option<reqwest::RequestBuilder> {
+        let form = self.form.as_ref()?;
+        let field = self.kind.response_field();
+        let mut fields: Vec<(&str, &str)> = form
+            .fields
+            .iter()
+            .filter(|(name, _)| name != field)
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        fields.push((field, token));
+        // reCAPTCHA-compatible backends often read the g-recaptcha field too
+        if self.kind == CaptchaKind::Hcaptcha {
+            fields.push((CaptchaKind::Recaptcha.response_field(), token));
+        }
+
+        let request = client
+            .request(form.method.clone(), form.action.clone())
+            .header(reqwest::header::REFERER, &self.page_url);
+        Some(if form.method == reqwest::Method::POST {
+            request.form(&fields)
+        } else {
+            request.query(&fields)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HCAPTCHA_WALL: &str = r#"<html><body>
+        <h1>One more step</h1>
+        <form action="/verify" method="POST">
+            <input type="hidden" name="ray" value="8a1b">
+            <input type="checkbox" name="remember">
+            <div class="h-captcha" data-sitekey="10000000-ffff-ffff-ffff-000000000001"></div>
+            <input type="submit" value="Continue">
+        </form>
+        <script src="https://js.hcaptcha.com/1/api.js" async defer></script>
+    </body></html>"#;
+
+    #[test]
+    fn test_detect_widget_and_form() {
+        let page = Url::parse("https://shop.example.com/item/7").unwrap();
+        let challenge = detect(HCAPTCHA_WALL, &page).unwrap();
+        assert_eq!(challenge.kind, CaptchaKind::Hcaptcha);
+        assert_eq!(
+            challenge.site_key.as_deref(),
+            Some("10000000-ffff-ffff-ffff-000000000001")
+        );
+        let form = challenge.form.as_ref().unwrap();
+        assert_eq!(form.method, reqwest::Method::POST);
+        assert_eq!(form.action.as_str(), "https://shop.example.com/verify");
+        assert_eq!(form.fields, [("ray".to_string(), "8a1b".to_string())]);
+
+        let request = challenge
+            .submission(&reqwest::Client::new(), "P0_token")
+            .unwrap()
+            .build()
+            .unwrap();
+        let body = std::str::from_utf8(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            "ray=8a1b&h-captcha-response=P0_token&g-recaptcha-response=P0_token"
+        );
+
+        let json = serde_json::to_value(&challenge).unwrap();
+        assert_eq!(json["kind"], "hcaptcha");
+        assert!(json.get("form").is_none());
+    }
+
+    #[test]
+    fn test_detect_other_vendors() {
+        let page = Url::parse("https://example.com/").unwrap();
+        let turnstile =
+            r#"<div class="cf-turnstile" data-sitekey="0x4AAA" data-action="login"></div>"#;
+        let challenge = detect(turnstile, &page).unwrap();
+        assert_eq!(challenge.kind, CaptchaKind::Turnstile);
+        assert_eq!(challenge.action.as_deref(), Some("login"));
+        assert!(challenge.form.is_none());
+
+        let v3 = r#"<script src="https://www.google.com/recaptcha/api.js?render=6LcKEY"></script>"#;
+        let challenge = detect(v3, &page).unwrap();
+        assert_eq!(challenge.kind, CaptchaKind::Recaptcha);
+        assert_eq!(challenge.site_key.as_deref(), Some("6LcKEY"));
+
+        // An article with a CAPTCHA-protected comment form isn't a wall
+        let article = format!(
+            r#"<article><p>{}</p></article><div class="g-recaptcha" data-sitekey="k"></div>"#,
+            "Plenty of article text. ".repeat(100)
+        );
+        assert!(detect(&article, &page).is_none());
+        assert!(detect("<p>Hello</p>", &page).is_none());
+    }
+
+    #[test]
+    fn test_solution_parsing() {
+        assert_eq!(
+            CaptchaSolution::parse("  tok-123\n").unwrap().token,
+            "tok-123"
+        );
+        let solution =
+            CaptchaSolution::parse(r#"{"token": "", "cookies": "cf_clearance=abc"}"#).unwrap();
+        assert_eq!(solution.cookies.as_deref(), Some("cf_clearance=abc"));
+        assert!(CaptchaSolution::parse("").is_err());
+        assert!(CaptchaSolution::parse("{nope").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_solver() {
+        let solver =
+            solver_from_spec("command:read -r challenge; echo \"tok-${#challenge}\"").unwrap();
+        let challenge = CaptchaChallenge {
+            kind: CaptchaKind::Turnstile,
+            page_url: "https://example.com/".to_string(),
+            site_key: Some("0x4AAA".to_string()),
+            action: None,
+            form: None,
+        };
+        let expected = serde_json::to_string(&challenge).unwrap().len();
+        let solution = solver.solve(&challenge).await.unwrap();
+        assert_eq!(solution.token, format!("tok-{expected}"));
+
+        // A solver echoing a large challenge as it reads it doesn't block
+        let large = CaptchaChallenge {
+            page_url: format!("https://example.com/?q={}", "a".repeat(1 << 20)),
+            ..challenge.clone()
+        };
+        let echoed = solver_from_spec("command:tr '{' x")
+            .unwrap()
+            .solve(&large)
+            .await
+            .unwrap();
+        assert_eq!(
+            echoed.token.len(),
+            serde_json::to_string(&large).unwrap().len()
+        );
+
+        assert!(solver_from_spec("command:exit 3")
+            .unwrap()
+            .solve(&challenge)
+            .await
+            .is_err());
+        assert!(solver_from_spec("2captcha:key").is_err());
+        assert!(solver_from_spec("webhook:not a url").is_err());
+    }
+}
@@ -0,0 +1,312 @@
+//! Record and Replay (`--record` / `--replay`)
+//!
+//! `--record cassette.json` saves every response an extraction needs (the
+//! page, discovered API endpoints, and the page scripts' `fetch()` calls),
+//! and `--replay cassette.json` answers the same requests from the file
+//! without touching the network, so presets and SPA extractions can be
+//! developed against a fixed snapshot and tested in CI.
+//!
+//! Requests are matched by method and URL; repeated requests replay their
+//! recordings in order and then keep getting the last one. A request missing
+//! from the cassette is an error, never a network fetch. `Set-Cookie` values
+//! are not stored.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{Context, Result};
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+/// Response headers whose values are never written to a cassette
+const REDACTED_HEADERS: &[&str] = &["set-cookie"];
+
+/// A recorded request and its response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    pub url: String,
+    /// Where redirects ended, when that's not `url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Interaction {
+    /// Where the response came from (after redirects)
+    #[must_use]
+    pub fn final_url(&self) -> &str {
+        self.final_url.as_deref().unwrap_or(&self.url)
+    }
+
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Interaction for a response to `method` `url`
+fn recorded(
+    method: &Method,
+    url: &Url,
+    response_url: &Url,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: String,
+) -> Interaction {
+    Interaction {
+        method: method.to_string(),
+        url: url.to_string(),
+        final_url: (response_url != url).then(|| response_url.to_string()),
+        status: status.as_u16(),
+        headers: headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                    "[redacted]".to_string()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).into_owned()
+                };
+                (name.to_string(), value)
+            })
+            .collect(),
+        body,
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// Recorded traffic of one run
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    interactions: Mutex<Vec<Interaction>>,
+    /// Times each interaction was replayed
+    replayed: Mutex<Vec<usize>>,
+}
+
+impl Cassette {
+    /// Record into `path`, replacing what's there
+    #[must_use]
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            mode: Mode::Record,
+            interactions: Mutex::new(Vec::new()),
+            replayed: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Replay the cassette at `path`
+    pub fn replay(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read cassette {}", path.display()))?;
+        let file: CassetteFile = serde_json::from_slice(&data)
+            .with_context(|| format!("Invalid cassette {}", path.display()))?;
+        let count = file.interactions.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            mode: Mode::Replay,
+            interactions: Mutex::new(file.interactions),
+            replayed: Mutex::new(vec![0; count]),
+        })
+    }
+
+    #[must_use]
+    pub fn is_replay(&self) -> bool {
+        self.mode == Mode::Replay
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Interactions recorded or loaded so far
+    #[must_use]
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.interactions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Send `request`, or answer it from the cassette when replaying
+    pub async fn send(&self, request: reqwest::RequestBuilder) -> Result<Interaction> {
+        let (client, request) = request.build_split();
+        let request = request?;
+        let (method, url) = (request.method().clone(), request.url().clone());
+        if self.is_replay() {
+            return self.lookup(&method, &url);
+        }
+        crate::policy::check_url(&url)?;
+        let response = client.execute(request).await?;
+        let (status, response_url) = (response.status(), response.url().clone());
+        let headers = response.headers().clone();
+        let body = response.text().await?;
+        self.store(recorded(
+            &method,
+            &url,
+            &response_url,
+            status,
+            &headers,
+            body,
+        ))
+    }
+
+    /// [`Self::send`] for blocking clients
+    pub fn send_blocking(&self, request: reqwest::blocking::RequestBuilder) -> Result<Interaction> {
+        let (client, request) = request.build_split();
+        let request = request?;
+        let (method, url) = (request.method().clone(), request.url().clone());
+        if self.is_replay() {
+            return self.lookup(&method, &url);
+        }
+        crate::policy::check_url(&url)?;
+        let response = client.execute(request)?;
+        let (status, response_url) = (response.status(), response.url().clone());
+        let headers = response.headers().clone();
+        let body = response.text()?;
+        self.store(recorded(
+            &method,
+            &url,
+            &response_url,
+            status,
+            &headers,
+            body,
+        ))
+    }
+
+    fn lookup(&self, method: &Method, url: &Url) -> Result<Interaction> {
+        let (method, url) = (method.as_str(), url.as_str());
+        let interactions = self
+            .interactions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut replayed = self.replayed.lock().unwrap_or_else(PoisonError::into_inner);
+        let matching: Vec<usize> = interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.method == method && i.url == url)
+            .map(|(index, _)| index)
+            .collect();
+        let index = matching
+            .iter()
+            .copied()
+            .find(|&index| replayed[index] == 0)
+            .or_else(|| matching.last().copied())
+            .with_context(|| {
+                format!(
+                    "No recorded response for {method} {url} in cassette {}",
+                    self.path.display()
+                )
+            })?;
+        replayed[index] += 1;
+        Ok(interactions[index].clone())
+    }
+
+    fn store(&self, interaction: Interaction) -> Result<Interaction> {
+        let mut interactions = self
+            .interactions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        interactions.push(interaction.clone());
+        // Saved as it grows, so a failed run still leaves what it fetched
+        let file = CassetteFile {
+            interactions: interactions.clone(),
+        };
+        crate::state::write_atomic(&self.path, &serde_json::to_vec_pretty(&file)?)
+            .with_context(|| format!("Failed to write cassette {}", self.path.display()))?;
+        Ok(interaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serves `/page` (counting hits in its body) and redirects `/old` there
+    async fn server() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut hits = 0;
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let response = if request.starts_with("GET /old ") {
+                    "HTTP/1.1 302 Found\r\nLocation: /page\r\nContent-Length: 0\r\n\r\n".to_string()
+                } else {
+                    hits += 1;
+                    let body = format!("hit {hits}");
+                    format!(
+                        "HTTP/1.1 200 OK\r\nSet-Cookie: sid=secret\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let base = server().await;
+        let path = std::env::temp_dir().join(format!("nab-cassette-{}.json", std::process::id()));
+        let client = reqwest::Client::builder().http1_only().build().unwrap();
+
+        let recorder = Cassette::record(&path);
+        let first = recorder
+            .send(client.get(format!("{base}/old")))
+            .await
+            .unwrap();
+        assert_eq!(first.final_url(), format!("{base}/page"));
+        assert_eq!(first.body, "hit 1");
+        recorder
+            .send(client.get(format!("{base}/page")))
+            .await
+            .unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains("secret"));
+
+        let player = Cassette::replay(&path).unwrap();
+        let replayed = player
+            .send(client.get(format!("{base}/old")))
+            .await
+            .unwrap();
+        assert_eq!(replayed, first);
+        // Repeats replay in order, then stay on the last recording
+        for _ in 0..2 {
+            let page = player.send(client.get(format!("{base}/page"))).await;
+            assert_eq!(page.unwrap().body, "hit 2");
+        }
+
+        let err = player
+            .send(client.get(format!("{base}/new")))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("No recorded response for GET"),
+            "{err}"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
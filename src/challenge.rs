@@ -0,0 +1,496 @@
+//! JS Challenge Interstitials
+//!
+//! Lighter anti-bot interstitials answer with a tiny page whose inline script
+//! computes a value (usually arithmetic), stores it in a cookie, and reloads.
+//! `nab fetch --solve-js-challenge` runs those scripts in the sandboxed engine
+//! and retries the request with the cookies they set:
+//! - only small pages with little text and an inline script that writes
+//!   `document.cookie` count as challenges
+//! - scripts get no network access and a fixed budget ([`BUDGET`], 16 MB heap);
+//!   `setTimeout` callbacks run right away instead of after their delay
+//! - challenges that need a real browser (form posts, CAPTCHAs, fingerprinting
+//!   probes) fail or set nothing, and the original response is kept
+
+use std::time::Duration;
+#[cfg(feature = "spa")]
+use std::time::Instant;
+
+#[cfg(feature = "spa")]
+use anyhow::{Context, Result};
+use scraper::{Html, Node};
+#[cfg(feature = "spa")]
+use tracing::debug;
+use url::Url;
+
+#[cfg(feature = "spa")]
+use crate::js_engine::JsEngine;
+#[cfg(feature = "spa")]
+use crate::sandbox::{NetworkPolicy, SandboxLimits};
+
+/// Total execution time for all challenge scripts
+pub const BUDGET: Duration = Duration::from_secs(2);
+
+/// Heap limit for challenge scripts
+#[cfg(feature = "spa")]
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Larger pages are real content, not interstitials
+const MAX_PAGE_BYTES: usize = 64 * 1024;
+
+/// More script than this is a full browser check, not an arithmetic challenge
+const MAX_SCRIPT_BYTES: usize = 32 * 1024;
+
+/// Visible text allowed on an interstitial ("Checking your browser...")
+const MAX_TEXT_CHARS: usize = 1000;
+
+/// Characters of each script shown by [`Challenge::describe`]
+const PREVIEW_CHARS: usize = 100;
+
+/// Interstitial with inline challenge scripts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    pub scripts: Vec<String>,
+}
+
+/// Cookies and navigation produced by a challenge's scripts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    /// `name=value` pairs, in the order they were set
+    pub cookies: Vec<(String, String)>,
+    /// Where the scripts tried to navigate (`location.href = ...`), if anywhere else
+    pub navigate_to: Option<Url>,
+    pub elapsed: Duration,
+}
+
+impl Solution {
+    /// Cookies as a `Cookie` header value
+    #[must_use]
+    pub fn cookie_header(&self) -> String {
+        self.cookies
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Turn `request`, a resend of the request that hit the challenge at
+    /// `page`, into the retry: cookies added, and sent where the scripts
+    /// navigated
+    ///
+    /// The target is chosen by the page, so one on another origin must pass
+    /// the domain policy and keeps only the browser's own headers
+    /// ([`is_browser_header`]): cookies, credentials, and `--add-header`
+    /// values stay with the page that asked for them.
+    pub fn prepare_retry(
+        &self,
+        request: &mut reqwest::Request,
+        page: &Url,
+    ) -> Result<(), crate::policy::PolicyViolation> {
+        let headers = request.headers_mut();
+        match &self.navigate_to {
+            Some(target) if target.origin() != page.origin() => {
+                crate::policy::check_url(target)?;
+                let all = std::mem::take(headers);
+                for name in all.keys().filter(|name| is_browser_header(name)) {
+                    for value in all.get_all(name) {
+                        headers.append(name, value.clone());
+                    }
+                }
+                *request.url_mut() = target.clone();
+            }
+            target => {
+                let mut cookies = headers
+                    .get(reqwest::header::COOKIE)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                if !cookies.is_empty() {
+                    cookies.push_str("; ");
+                }
+                cookies.push_str(&self.cookie_header());
+                if let Ok(value) = cookies.parse() {
+                    headers.insert(reqwest::header::COOKIE, value);
+                }
+                if let Some(target) = target {
+                    *request.url_mut() = target.clone();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `name` is one a browser sends on its own, from its fingerprint
+/// rather than from the user
+fn is_browser_header(name: &reqwest::header::HeaderName) -> bool {
+    use reqwest::header::{
+        ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, CONTENT_TYPE, UPGRADE_INSECURE_REQUESTS,
+        USER_AGENT,
+    };
+    [
+        USER_AGENT,
+        ACCEPT,
+        ACCEPT_LANGUAGE,
+        ACCEPT_ENCODING,
+        CONTENT_TYPE,
+        UPGRADE_INSECURE_REQUESTS,
+    ]
+    .contains(name)
+        || name.as_str().starts_with("sec-")
+}
+
+/// Find a JS challenge in `html`, if the page is one
+#[must_use]
+pub fn detect(html: &str) -> Option<Challenge> {
+    if html.len() > MAX_PAGE_BYTES {
+        return None;
+    }
+    let document = Html::parse_document(html);
+    let mut scripts = Vec::new();
+    for node in document.tree.nodes() {
+        let Node::Element(el) = node.value() else {
+            continue;
+        };
+        let inline = el.name() == "script"
+            && el.attr("src").is_none()
+            && el
+                .attr("type")
+                .is_none_or(|t| t.is_empty() || t.contains("javascript"));
+        if inline {
+            let code: String = node
+                .children()
+                .filter_map(|c| c.value().as_text().map(|t| t.to_string()))
+                .collect();
+            if !code.trim().is_empty() {
+                scripts.push(code);
+            }
+        }
+    }
+    let text_chars = visible_text_chars(&document);
+
+    let script_bytes: usize = scripts.iter().map(String::len).sum();
+    let sets_cookie = scripts.iter().any(|s| s.contains("document.cookie"));
+    (sets_cookie && script_bytes <= MAX_SCRIPT_BYTES && text_chars <= MAX_TEXT_CHARS)
+        .then_some(Challenge { scripts })
+}
+
+/// Characters of text a visitor would see (outside scripts and styles)
+pub(crate) fn visible_text_chars(document: &Html) -> usize {
+    document
+        .tree
+        .nodes()
+        .filter_map(|node| {
+            let text = node.value().as_text()?;
+            let in_code = node
+                .parent()
+                .and_then(|p| p.value().as_element().map(|e| e.name()))
+                .is_some_and(|name| matches!(name, "script" | "style" | "noscript"));
+            (!in_code).then(|| text.trim().chars().count())
+        })
+        .sum()
+}
+
+impl Challenge {
+    /// One line per script for logs: its size and how it starts
+    #[must_use]
+    pub fn describe(&self) -> Vec<String> {
+        self.scripts
+            .iter()
+            .map(|script| {
+                let code = script.split_whitespace().collect::<Vec<_>>().join(" ");
+                let preview: String = code.chars().take(PREVIEW_CHARS).collect();
+                let more = if code.chars().count() > PREVIEW_CHARS {
+                    "…"
+                } else {
+                    ""
+                };
+                format!("{} bytes: {preview}{more}", script.len())
+            })
+            .collect()
+    }
+
+    /// Run the scripts as if loaded from `page` by a browser sending `user_agent`
+    #[cfg(feature = "spa")]
+    pub fn solve(&self, page: &Url, user_agent: &str) -> Result<Solution> {
+        let engine = JsEngine::with_limits(SandboxLimits {
+            timeout: Some(BUDGET),
+            memory_limit: MEMORY_LIMIT,
+            network: NetworkPolicy::Deny,
+        })?;
+        engine.inject_minimal_dom()?;
+        engine.set_global("__nab_href", page.as_str())?;
+        engine.set_global("__nab_user_agent", user_agent)?;
+        let location = serde_json::json!({
+            "protocol": format!("{}:", page.scheme()),
+            "host": page[url::Position::BeforeHost..url::Position::AfterPort],
+            "hostname": page.host_str().unwrap_or_default(),
+            "port": page.port().map(|p| p.to_string()).unwrap_or_default(),
+            "pathname": page.path(),
+            "search": page.query().map(|q| format!("?{q}")).unwrap_or_default(),
+            "hash": page.fragment().map(|f| format!("#{f}")).unwrap_or_default(),
+            "origin": page.origin().ascii_serialization(),
+        });
+        engine.eval(&format!("var __nab_url = {location};"))?;
+        engine.eval(CHALLENGE_SHIM)?;
+
+        let start = Instant::now();
+        for (i, script) in self.scripts.iter().enumerate() {
+            debug!("Running challenge script {}:\n{script}", i + 1);
+            engine
+                .eval_sandboxed(script)
+                .with_context(|| format!("Challenge script {} failed", i + 1))?;
+        }
+        engine
+            .eval_sandboxed("__nab_challenge.runTimers()")
+            .context("Challenge timer callback failed")?;
+        let elapsed = start.elapsed();
+
+        let state: serde_json::Value =
+            serde_json::from_str(&engine.eval("JSON.stringify(__nab_challenge.state())")?)?;
+        let cookies: Vec<(String, String)> = state["cookies"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c.as_str())
+            .filter_map(|c| {
+                let pair = c.split(';').next()?.trim();
+                let (name, value) = pair.split_once('=')?;
+                Some((name.trim().to_string(), value.trim().to_string()))
+            })
+            .filter(|(name, _)| !name.is_empty())
+            .collect();
+        if cookies.is_empty() {
+            anyhow::bail!("Challenge scripts ran but set no cookies");
+        }
+        let navigate_to = state["navigation"]
+            .as_str()
+            .and_then(|target| page.join(target).ok())
+            .filter(|target| target != page && matches!(target.scheme(), "http" | "https"));
+
+        Ok(Solution {
+            cookies,
+            navigate_to,
+            elapsed,
+        })
+    }
+}
+
+/// Browser pieces challenges rely on: a recording `document.cookie`,
+/// `location` navigation, queued timers, and real `atob`/`btoa`
+#[cfg(feature = "spa")]
+const CHALLENGE_SHIM: &str = r"
+(function () {
+    var cookies = [];
+    var navigation = null;
+    var timers = [];
+    var url = __nab_url;
+
+    Object.defineProperty(document, 'cookie', {
+        get: function () {
+            return cookies.map(function (c) { return c.split(';')[0]; }).join('; ');
+        },
+        set: function (v) { cookies.push(String(v)); }
+    });
+
+    var location = {
+        protocol: url.protocol, host: url.host, hostname: url.hostname, port: url.port,
+        pathname: url.pathname, search: url.search, hash: url.hash, origin: url.origin,
+        reload: function () { if (navigation === null) navigation = __nab_href; },
+        assign: function (u) { navigation = String(u); },
+        replace: function (u) { navigation = String(u); },
+        toString: function () { return __nab_href; }
+    };
+    Object.defineProperty(location, 'href', {
+        get: function () { return __nab_href; },
+        set: function (u) { navigation = String(u); }
+    });
+    var locationProperty = {
+        get: function () { return location; },
+        set: function (u) { navigation = String(u); }
+    };
+    Object.defineProperty(globalThis, 'location', locationProperty);
+    Object.defineProperty(window, 'location', locationProperty);
+    Object.defineProperty(document, 'location', locationProperty);
+
+    var queue = function (fn) {
+        timers.push(fn);
+        return timers.length;
+    };
+    globalThis.setTimeout = window.setTimeout = queue;
+    globalThis.setInterval = window.setInterval = queue;
+    globalThis.clearTimeout = window.clearTimeout = function () {};
+    globalThis.clearInterval = window.clearInterval = function () {};
+
+    var alphabet = 'ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/';
+    globalThis.btoa = window.btoa = function (s) {
+        s = String(s);
+        var out = '';
+        for (var i = 0; i < s.length; i += 3) {
+            var n = (s.charCodeAt(i) << 16) | ((s.charCodeAt(i + 1) || 0) << 8) | (s.charCodeAt(i + 2) || 0);
+            out += alphabet[(n >> 18) & 63] + alphabet[(n >> 12) & 63]
+                + (i + 1 < s.length ? alphabet[(n >> 6) & 63] : '=')
+                + (i + 2 < s.length ? alphabet[n & 63] : '=');
+        }
+        return out;
+    };
+    globalThis.atob = window.atob = function (s) {
+        s = String(s).replace(/[^A-Za-z0-9+\/]/g, '');
+        var out = '';
+        var bits = 0;
+        var value = 0;
+        for (var i = 0; i < s.length; i++) {
+            value = (value << 6) | alphabet.indexOf(s[i]);
+            bits += 6;
+            if (bits >= 8) {
+                bits -= 8;
+                out += String.fromCharCode((value >> bits) & 255);
+            }
+        }
+        return out;
+    };
+
+    window.navigator.userAgent = __nab_user_agent;
+    globalThis.navigator = window.navigator;
+    globalThis.__nab_challenge = {
+        runTimers: function () {
+            // Timers may queue more timers; stop after a bounded number
+            for (var i = 0; i < timers.length && i < 100; i++) {
+                var fn = timers[i];
+                if (typeof fn === 'function') fn(); else (0, eval)(String(fn));
+            }
+        },
+        state: function () { return { cookies: cookies, navigation: navigation }; }
+    };
+})();
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERSTITIAL: &str = r#"<html><head><title>Just a moment...</title></head>
+        <body><p>Checking your browser before accessing the site.</p>
+        <script>
+            var a = 17 * 3 + parseInt(atob('MTA='), 10);
+            setTimeout(function () {
+                document.cookie = 'js_check=' + a + '; path=/; max-age=3600';
+                document.cookie = 'ua_len=' + navigator.userAgent.length;
+                location.href = '/?verified=' + btoa('1');
+            }, 4000);
+        </script></body></html>"#;
+
+    #[test]
+    fn test_detect() {
+        let challenge = detect(INTERSTITIAL).unwrap();
+        assert_eq!(challenge.scripts.len(), 1);
+        let described = &challenge.describe()[0];
+        assert!(described.starts_with(&format!(
+            "{} bytes: var a = 17 * 3",
+            challenge.scripts[0].len()
+        )));
+        assert!(described.ends_with('…'));
+
+        // Ordinary pages, external scripts, and JSON data aren't challenges
+        assert!(
+            detect("<html><body><p>Hello</p><script>var x = 1;</script></body></html>").is_none()
+        );
+        assert!(detect(r#"<script src="/c.js"></script><script type="application/json">{"document.cookie":1}</script>"#).is_none());
+        let article = format!(
+            "<p>{}</p><script>document.cookie = 'seen=1';</script>",
+            "Long article text. ".repeat(100)
+        );
+        assert!(detect(&article).is_none());
+    }
+
+    #[test]
+    fn test_prepare_retry_keeps_credentials_on_origin() {
+        let page = Url::parse("https://shop.example.com/item").unwrap();
+        let retry = || {
+            reqwest::Client::new()
+                .get(page.as_str())
+                .header("Cookie", "session=secret")
+                .header("Authorization", "Bearer token")
+                .header("X-API-Key", "key")
+                .header("Proxy-Authorization", "Basic cHJveHk=")
+                .header("User-Agent", "Mozilla/5.0 Test")
+                .header("Sec-Fetch-Mode", "navigate")
+                .build()
+                .unwrap()
+        };
+        let mut solution = Solution {
+            cookies: vec![("js_check".into(), "61".into())],
+            navigate_to: Some(Url::parse("https://shop.example.com/?verified=1").unwrap()),
+            elapsed: Duration::ZERO,
+        };
+
+        let mut request = retry();
+        solution.prepare_retry(&mut request, &page).unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://shop.example.com/?verified=1"
+        );
+        assert_eq!(request.headers()["cookie"], "session=secret; js_check=61");
+        assert_eq!(request.headers()["authorization"], "Bearer token");
+        assert_eq!(request.headers()["x-api-key"], "key");
+
+        solution.navigate_to = Some(Url::parse("https://attacker.example/collect").unwrap());
+        let mut request = retry();
+        solution.prepare_retry(&mut request, &page).unwrap();
+        assert_eq!(request.url().as_str(), "https://attacker.example/collect");
+        assert!(request.headers().get("cookie").is_none());
+        assert!(request.headers().get("authorization").is_none());
+        assert!(request.headers().get("x-api-key").is_none());
+        assert!(request.headers().get("proxy-authorization").is_none());
+        assert_eq!(request.headers()["user-agent"], "Mozilla/5.0 Test");
+        assert_eq!(request.headers()["sec-fetch-mode"], "navigate");
+    }
+
+    #[test]
+    #[cfg(feature = "spa")]
+    fn test_solve() {
+        let page = Url::parse("https://shop.example.com/item?id=7").unwrap();
+        let solution = detect(INTERSTITIAL)
+            .unwrap()
+            .solve(&page, "Mozilla/5.0 Test")
+            .unwrap();
+        assert_eq!(
+            solution.cookies,
+            [
+                ("js_check".to_string(), "61".to_string()),
+                ("ua_len".to_string(), "16".to_string())
+            ]
+        );
+        assert_eq!(solution.cookie_header(), "js_check=61; ua_len=16");
+        assert_eq!(
+            solution.navigate_to.unwrap().as_str(),
+            "https://shop.example.com/?verified=MQ=="
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "spa")]
+    fn test_solve_limits() {
+        let page = Url::parse("https://example.com/").unwrap();
+
+        // Endless loops hit the budget
+        let spin = Challenge {
+            scripts: vec!["while (true) {} document.cookie = 'a=1';".to_string()],
+        };
+        let start = Instant::now();
+        assert!(spin.solve(&page, "UA").is_err());
+        assert!(start.elapsed() < BUDGET * 3);
+
+        // No network from challenge scripts
+        let fetching = Challenge {
+            scripts: vec!["fetch('https://example.com/'); document.cookie = 'a=1';".to_string()],
+        };
+        assert!(fetching.solve(&page, "UA").is_err());
+
+        // Reloading without a cookie isn't a solution
+        let reload = Challenge {
+            scripts: vec!["location.reload(); var c = document.cookie;".to_string()],
+        };
+        let error = reload.solve(&page, "UA").unwrap_err();
+        assert!(error.to_string().contains("set no cookies"));
+    }
+}
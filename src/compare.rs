@@ -0,0 +1,369 @@
+//! Variant Comparison (`nab compare`)
+//!
+//! Fetches one URL under several variants at once and reports how the pages
+//! differ, instead of N separate runs and a manual diff. A variant is one of:
+//!
+//! - `geo=de`: the [`--geo`](crate::geo) region (proxy, language, time zone)
+//! - `persona=alice`: a named persona's fixed browser identity and TLS sessions
+//! - `profile=firefox`: a browser profile
+//!
+//! The report has each variant's status, final URL, word count, and product
+//! price (from the `product` extraction preset), plus the lines of its
+//! Markdown that differ from the first variant that loaded (the baseline).
+//! Lines are compared as a multiset: moved lines aren't differences, repeated
+//! ones are counted.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use serde::Serialize;
+use url::Url;
+
+use crate::fingerprint::{persona_profile, BrowserProfile, FetchContext};
+use crate::geo::Geo;
+use crate::request_options::{ProfileChoice, RequestOptions, RequestOptionsBuilder};
+use crate::{AcceleratedClient, ClientOptions, SessionStore};
+
+/// Kinds of variant, as written before the `=`
+pub const KINDS: &[&str] = &["geo", "persona", "profile"];
+
+/// One way of fetching the page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Variant {
+    Geo(String),
+    Persona(String),
+    Profile(ProfileChoice),
+}
+
+impl Variant {
+    /// Variants of `spec`: `KIND=VALUE,VALUE...`, e.g. `geo=us,de,fr`
+    pub fn parse_list(spec: &str) -> Result<Vec<Self>> {
+        let Some((kind, values)) = spec.split_once('=') else {
+            bail!("Invalid variants '{spec}', expected KIND=VALUE,VALUE... (e.g. geo=us,de)");
+        };
+        values
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| match kind.trim() {
+                "geo" => match Geo::lookup(value) {
+                    Some(geo) => Ok(Self::Geo(geo.country)),
+                    None => bail!("Unknown country '{value}' in variants '{spec}'"),
+                },
+                "persona" => Ok(Self::Persona(value.to_string())),
+                "profile" => Ok(Self::Profile(value.parse()?)),
+                other => bail!("Unknown variant kind '{other}' ({})", KINDS.join(", ")),
+            })
+            .collect()
+    }
+
+    /// Add this variant's settings to `builder`
+    #[must_use]
+    pub fn configure(&self, builder: RequestOptionsBuilder) -> RequestOptionsBuilder {
+        match self {
+            Self::Geo(country) => builder.geo(country),
+            Self::Persona(_) => builder,
+            Self::Profile(profile) => builder.profile(*profile),
+        }
+    }
+
+    /// Browser profile and client settings to fetch with
+    fn client(&self, options: &RequestOptions) -> (BrowserProfile, ClientOptions) {
+        match self {
+            Self::Persona(persona) => (
+                persona_profile(persona),
+                ClientOptions {
+                    tls: options
+                        .client
+                        .tls
+                        .clone()
+                        .with_sessions(SessionStore::for_persona(Some(persona))),
+                    ..options.client.clone()
+                },
+            ),
+            Self::Geo(_) | Self::Profile(_) => (options.browser_profile(), options.client.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Geo(country) => write!(f, "geo={}", country.to_ascii_lowercase()),
+            Self::Persona(persona) => write!(f, "persona={persona}"),
+            Self::Profile(profile) => {
+                write!(f, "profile={}", format!("{profile:?}").to_lowercase())
+            }
+        }
+    }
+}
+
+/// The page as one variant saw it
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VariantPage {
+    pub variant: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+    pub time_ms: f64,
+    pub words: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo: Option<Geo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Non-empty Markdown lines, whitespace collapsed
+    #[serde(skip)]
+    pub lines: Vec<String>,
+}
+
+/// Lines of a variant's page that differ from the baseline's
+#[derive(Debug, Clone, Serialize)]
+pub struct TextDiff {
+    pub variant: String,
+    /// Only in this variant
+    pub added: Vec<String>,
+    /// Only in the baseline
+    pub removed: Vec<String>,
+}
+
+/// Result of `nab compare`
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareReport {
+    pub url: String,
+    pub compared_at: DateTime<Utc>,
+    /// Variant the others are diffed against (the first that loaded)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<String>,
+    /// Whether every variant that loaded got the same text
+    pub identical: bool,
+    /// Whether the variants that found a price found different ones
+    pub prices_differ: bool,
+    pub variants: Vec<VariantPage>,
+    pub differences: Vec<TextDiff>,
+}
+
+/// Fetch `url` under every variant concurrently and compare the pages
+///
+/// Each variant comes with the options it was built with (see
+/// [`Variant::configure`]); cookies are sent as those options say.
+pub async fn run(url: &str, variants: Vec<(Variant, RequestOptions)>) -> CompareReport {
+    let page_url = Url::parse(url);
+    let pages = join_all(variants.iter().map(|(variant, options)| async {
+        let start = Instant::now();
+        let page = match &page_url {
+            Ok(page_url) => fetch(page_url, variant, options).await,
+            Err(e) => Err(anyhow::anyhow!("Invalid URL {url}: {e}")),
+        };
+        let mut page = page.unwrap_or_else(|e| VariantPage {
+            error: Some(format!("{:#}", options.client.explain(url, e))),
+            ..VariantPage::default()
+        });
+        page.variant = variant.to_string();
+        page.time_ms = start.elapsed().as_secs_f64() * 1000.0;
+        page.geo.clone_from(&options.geo);
+        page
+    }))
+    .await;
+    report(url, pages)
+}
+
+async fn fetch(url: &Url, variant: &Variant, options: &RequestOptions) -> Result<VariantPage> {
+    let (profile, client_options) = variant.client(options);
+    let client = AcceleratedClient::with_profile_and_options(profile.clone(), &client_options)?;
+    let mut request = client
+        .inner()
+        .get(url.clone())
+        .headers(profile.request_headers(FetchContext::Navigate, "none"));
+    let cookies = options
+        .cookies
+        .header_for(url.host_str().unwrap_or_default());
+    if !cookies.is_empty() {
+        request = request.header(reqwest::header::COOKIE, cookies);
+    }
+    request = request.headers(options.headers.clone());
+
+    let start = Instant::now();
+    let response = options.send(request).await?;
+    let response = crate::Response::read(response, None, start.elapsed(), Vec::new()).await?;
+    let is_html = response.is_html();
+    let final_url = response.url.clone();
+    let body = response.body.into_text();
+
+    let mut page = VariantPage {
+        status: Some(response.status.as_u16()),
+        final_url: Some(final_url.to_string()),
+        ..VariantPage::default()
+    };
+    let markdown = if is_html {
+        let product = crate::extract::extract(crate::Preset::Product, &body, &final_url)?;
+        page.price = product["price"].as_f64();
+        page.currency = product["currency"].as_str().map(String::from);
+        crate::page::html_to_markdown(&body)
+    } else {
+        body
+    };
+    page.lines = lines(&markdown);
+    page.words = markdown.split_whitespace().count();
+    Ok(page)
+}
+
+/// Diff `pages` (in variant order) against the first that loaded
+#[must_use]
+pub fn report(url: &str, pages: Vec<VariantPage>) -> CompareReport {
+    let loaded: Vec<&VariantPage> = pages.iter().filter(|p| p.error.is_none()).collect();
+    let baseline = loaded.first().copied();
+    let differences: Vec<TextDiff> = baseline
+        .map(|base| {
+            loaded[1..]
+                .iter()
+                .map(|page| diff_lines(&base.lines, page))
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut prices = loaded.iter().filter_map(|p| {
+        p.price
+            .map(|price| (price.to_bits(), p.currency.as_deref()))
+    });
+    let first_price = prices.next();
+    let prices_differ = prices.any(|price| Some(price) != first_price);
+
+    CompareReport {
+        url: url.to_string(),
+        compared_at: Utc::now(),
+        baseline: baseline.map(|p| p.variant.clone()),
+        identical: differences
+            .iter()
+            .all(|d| d.added.is_empty() && d.removed.is_empty()),
+        prices_differ,
+        differences,
+        variants: pages,
+    }
+}
+
+fn diff_lines(baseline: &[String], page: &VariantPage) -> TextDiff {
+    let mut unmatched: HashMap<&str, usize> = HashMap::new();
+    for line in baseline {
+        *unmatched.entry(line).or_default() += 1;
+    }
+    let mut added = Vec::new();
+    for line in &page.lines {
+        match unmatched.get_mut(line.as_str()) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => added.push(line.clone()),
+        }
+    }
+    let removed = baseline
+        .iter()
+        .filter(|line| {
+            unmatched.get_mut(line.as_str()).is_some_and(|count| {
+                let left = *count > 0;
+                *count = count.saturating_sub(1);
+                left
+            })
+        })
+        .cloned()
+        .collect();
+    TextDiff {
+        variant: page.variant.clone(),
+        added,
+        removed,
+    }
+}
+
+fn lines(markdown: &str) -> Vec<String> {
+    markdown
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(variant: &str, text: &str, price: Option<f64>) -> VariantPage {
+        VariantPage {
+            variant: variant.into(),
+            status: Some(200),
+            price,
+            currency: price.map(|_| "EUR".into()),
+            lines: lines(text),
+            ..VariantPage::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_variants() {
+        assert_eq!(
+            Variant::parse_list("geo=us, DE,uk").unwrap(),
+            [
+                Variant::Geo("US".into()),
+                Variant::Geo("DE".into()),
+                Variant::Geo("GB".into())
+            ]
+        );
+        let profiles = Variant::parse_list("profile=firefox,safari").unwrap();
+        assert_eq!(profiles[0], Variant::Profile(ProfileChoice::Firefox));
+        assert_eq!(profiles[1].to_string(), "profile=safari");
+        assert_eq!(
+            Variant::parse_list("persona=alice").unwrap()[0].to_string(),
+            "persona=alice"
+        );
+        assert!(Variant::parse_list("geo=atlantis").is_err());
+        assert!(Variant::parse_list("colour=red").is_err());
+        assert!(Variant::parse_list("us,de").is_err());
+    }
+
+    #[test]
+    fn test_report_diffs_against_first_loaded() {
+        let failed = VariantPage {
+            variant: "geo=fr".into(),
+            error: Some("timed out".into()),
+            ..VariantPage::default()
+        };
+        let report = report(
+            "https://shop.example.com/item",
+            vec![
+                failed,
+                page(
+                    "geo=us",
+                    "# Item\nPrice: 10\nShips  worldwide\nSale",
+                    Some(10.0),
+                ),
+                page(
+                    "geo=de",
+                    "# Item\nSale\nPrice: 12\nShips worldwide",
+                    Some(12.0),
+                ),
+                page(
+                    "geo=gb",
+                    "Sale\n# Item\nShips worldwide\nPrice: 10",
+                    Some(10.0),
+                ),
+            ],
+        );
+        assert_eq!(report.baseline.as_deref(), Some("geo=us"));
+        assert!(report.prices_differ);
+        assert!(!report.identical);
+        assert_eq!(report.differences.len(), 2);
+        assert_eq!(report.differences[0].variant, "geo=de");
+        assert_eq!(report.differences[0].added, ["Price: 12"]);
+        assert_eq!(report.differences[0].removed, ["Price: 10"]);
+        assert!(report.differences[1].added.is_empty());
+        assert!(report.differences[1].removed.is_empty());
+
+        let same = super::report("u", vec![page("a", "x\nx", None), page("b", "x\nx", None)]);
+        assert!(same.identical);
+        assert!(!same.prices_differ);
+    }
+}
@@ -0,0 +1,233 @@
+//! Multi-URL Document Compilation
+//!
+//! Turns a list of article URLs into one document, in input order:
+//! - Markdown: title, linked table of contents, one section per article
+//! - EPUB: one chapter per article with a navigation document
+//!
+//! Page fetching is left to the caller (so it goes through the fingerprinted
+//! client); EPUB output downloads article images with the client it is given.
+
+use anyhow::Result;
+
+use crate::epub::{embed_images, fetch_image, EpubBuilder};
+use crate::readability::{escape_xml, Article};
+
+/// One compiled article
+#[derive(Debug, Clone)]
+pub struct CompiledArticle {
+    /// Source URL
+    pub url: String,
+    /// Extracted content
+    pub article: Article,
+}
+
+impl CompiledArticle {
+    /// Article title, falling back to the URL
+    #[must_use]
+    pub fn title(&self) -> &str {
+        if self.article.title.is_empty() {
+            &self.url
+        } else {
+            &self.article.title
+        }
+    }
+}
+
+/// Output format of a compiled document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompileFormat {
+    Markdown,
+    Epub,
+}
+
+impl CompileFormat {
+    /// Pick the format from an output path (`.epub` → EPUB, else Markdown)
+    #[must_use]
+    pub fn from_path(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("epub") => Self::Epub,
+            _ => Self::Markdown,
+        }
+    }
+}
+
+/// Parse a URL list: one URL per line, blank lines and `#` comments ignored
+#[must_use]
+pub fn parse_url_list(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Compile articles into one Markdown document with a table of contents
+#[must_use]
+pub fn compile_markdown(title: &str, articles: &[CompiledArticle]) -> String {
+    let mut out = format!("# {title}\n\n## Contents\n\n");
+    for (i, a) in articles.iter().enumerate() {
+        out.push_str(&format!("{}. [{}](#article-{})\n", i + 1, a.title(), i + 1));
+    }
+
+    for (i, a) in articles.iter().enumerate() {
+        out.push_str(&format!(
+            "\n---\n\n<a id=\"article-{}\"></a>\n\n## {}\n\n",
+            i + 1,
+            a.title()
+        ));
+        if let Some(byline) = &a.article.byline {
+            out.push_str(&format!("*{byline}*  \n"));
+        }
+        out.push_str(&format!("Source: <{}>\n\n", a.url));
+
+        let demoted = Article {
+            content_html: demote_headings(&a.article.content_html, 2),
+            ..a.article.clone()
+        };
+        out.push_str(&demoted.markdown());
+        out.push('\n');
+    }
+    out
+}
+
+/// Compile articles into an EPUB book, one chapter per article
+///
+/// Images are downloaded with `client` and embedded in the book.
+pub async fn compile_epub(
+    title: &str,
+    articles: &[CompiledArticle],
+    client: &reqwest::Client,
+) -> Result<Vec<u8>> {
+    let mut book = EpubBuilder::new(title);
+    if let Some(lang) = articles.iter().find_map(|a| a.article.language.clone()) {
+        book = book.with_language(lang);
+    }
+    for a in articles {
+        let body = embed_images(client, &mut book, &chapter_body(a)).await;
+        book.add_chapter(a.title(), body);
+    }
+    book.build()
+}
+
+/// Package a single article as an EPUB with its `OpenGraph` metadata
+///
+/// The lead image (`og:image`) becomes the cover; content images are embedded.
+pub async fn article_epub(a: &CompiledArticle, client: &reqwest::Client) -> Result<Vec<u8>> {
+    let article = &a.article;
+    let mut book = EpubBuilder::new(a.title()).with_source(&a.url);
+    if let Some(author) = &article.byline {
+        book = book.with_author(author);
+    }
+    if let Some(site) = &article.site_name {
+        book = book.with_publisher(site);
+    }
+    if let Some(description) = &article.description {
+        book = book.with_description(description);
+    }
+    if let Some(lang) = &article.language {
+        book = book.with_language(lang);
+    }
+    if let Some(date) = &article.published {
+        book = book.with_date(date);
+    }
+    if let Some(image) = &article.image {
+        if let Some(path) = fetch_image(client, &mut book, image).await {
+            book.set_cover_image(path);
+        }
+    }
+
+    let body = embed_images(client, &mut book, &chapter_body(a)).await;
+    book.add_chapter(a.title(), body);
+    book.build()
+}
+
+/// XHTML body for one article chapter
+#[must_use]
+pub fn chapter_body(a: &CompiledArticle) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape_xml(a.title()));
+    if let Some(byline) = &a.article.byline {
+        body.push_str(&format!("<p class=\"byline\">{}</p>\n", escape_xml(byline)));
+    }
+    body.push_str(&format!(
+        "<p class=\"source\"><a href=\"{0}\">{0}</a></p>\n",
+        escape_xml(&a.url)
+    ));
+    body.push_str(&demote_headings(&a.article.content_html, 1));
+    body
+}
+
+/// Shift `<hN>` tags down by `levels` (capped at h6)
+fn demote_headings(html: &str, levels: u8) -> String {
+    let re = regex::Regex::new(r"<(/?)h([1-6])>").unwrap();
+    re.replace_all(html, |caps: &regex::Captures<'_>| {
+        let level: u8 = caps[2].parse().unwrap_or(6);
+        format!("<{}h{}>", &caps[1], (level + levels).min(6))
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn article(title: &str, content: &str) -> CompiledArticle {
+        CompiledArticle {
+            url: format!("https://example.com/{}", title.to_lowercase()),
+            article: Article {
+                title: title.to_string(),
+                content_html: content.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_url_list() {
+        let list = "# reading list\nhttps://a.example/1\n\n  https://b.example/2  \n#https://skip.example\n";
+        assert_eq!(
+            parse_url_list(list),
+            vec!["https://a.example/1", "https://b.example/2"]
+        );
+    }
+
+    #[test]
+    fn test_compile_markdown_order_and_toc() {
+        let articles = vec![
+            article("Second", "<p>Body two.</p>"),
+            article("First", "<h2>Part</h2><p>Body one.</p>"),
+        ];
+        let md = compile_markdown("Reading", &articles);
+
+        assert!(md.starts_with("# Reading\n"));
+        assert!(md.contains("1. [Second](#article-1)\n2. [First](#article-2)"));
+        assert!(md.find("Body two.").unwrap() < md.find("Body one.").unwrap());
+        assert!(md.contains("#### Part"));
+        assert!(md.contains("Source: <https://example.com/first>"));
+    }
+
+    #[tokio::test]
+    async fn test_compile_epub() {
+        let a = article("Only", "<h2>Part</h2><p>Text.</p>");
+        let body = chapter_body(&a);
+        assert!(body.contains("<h3>Part</h3>"));
+        assert!(body.contains("<p class=\"source\">"));
+
+        let client = reqwest::Client::new();
+        let epub = compile_epub("Reading", &[a], &client).await.unwrap();
+        assert!(epub.starts_with(&0x0403_4b50_u32.to_le_bytes()));
+        assert!(compile_epub("Reading", &[], &client).await.is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        use std::path::Path;
+        assert_eq!(
+            CompileFormat::from_path(Path::new("book.EPUB")),
+            CompileFormat::Epub
+        );
+        assert_eq!(
+            CompileFormat::from_path(Path::new("combined.md")),
+            CompileFormat::Markdown
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Cache Compression
+//!
+//! Stored responses are written as zstd frames. HTML from one site repeats
+//! the same markup, scripts, and boilerplate on every page, so a dictionary
+//! trained on a handful of a host's pages ([`Dictionaries::train`]) lets each
+//! page compress to a fraction of what it would alone.
+//!
+//! Dictionaries live next to the entries they compress, as
+//! `dictionaries/<id>.dict` with `dictionaries/hosts.json` naming each host's
+//! current one. A frame records the ID of the dictionary it was written
+//! with, so retraining a host leaves older entries readable.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::state::Versioned;
+
+/// zstd level: the library's default, fast enough for every stored page
+pub const LEVEL: i32 = zstd::DEFAULT_COMPRESSION_LEVEL;
+
+/// Largest dictionary trained (zstd's own default size)
+pub const MAX_DICTIONARY_SIZE: usize = 112_640;
+
+/// Fewest pages a dictionary is trained from
+pub const MIN_SAMPLES: usize = 8;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HostDictionaries {
+    /// Host to the ID of its current dictionary
+    hosts: BTreeMap<String, u32>,
+}
+
+impl Versioned for HostDictionaries {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+/// A trained dictionary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Trained {
+    pub host: String,
+    pub id: u32,
+    pub bytes: usize,
+    pub samples: usize,
+}
+
+/// The zstd dictionaries of one store
+#[derive(Debug)]
+pub struct Dictionaries {
+    dir: PathBuf,
+    hosts: Mutex<HostDictionaries>,
+    /// Dictionaries read so far, by ID
+    loaded: Mutex<HashMap<u32, Arc<Vec<u8>>>>,
+}
+
+impl Dictionaries {
+    /// Dictionaries in `dir` (created when the first one is trained)
+    #[must_use]
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let hosts = crate::state::load(&dir.join("hosts.json"))
+            .unwrap_or_else(|e| {
+                tracing::debug!("Ignoring dictionary index: {e:#}");
+                None
+            })
+            .unwrap_or_default();
+        Self {
+            dir,
+            hosts: Mutex::new(hosts),
+            loaded: Mutex::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Compress `data` with `host`'s dictionary, if it has one
+    pub fn compress(&self, host: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let id = self
+            .hosts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .hosts
+            .get(host)
+            .copied();
+        let compressed = match id.and_then(|id| self.load(id)) {
+            Some(dictionary) => {
+                zstd::bulk::Compressor::with_dictionary(LEVEL, &dictionary)?.compress(data)?
+            }
+            None => zstd::bulk::compress(data, LEVEL)?,
+        };
+        Ok(compressed)
+    }
+
+    /// Decompress a frame written by [`Self::compress`]
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        match zstd::zstd_safe::get_dict_id_from_frame(data) {
+            Some(id) => {
+                let Some(dictionary) = self.load(id.get()) else {
+                    bail!("Dictionary {id} is missing from {}", self.dir.display());
+                };
+                zstd::stream::read::Decoder::with_dictionary(data, &dictionary)?
+                    .read_to_end(&mut decompressed)?;
+            }
+            None => {
+                zstd::stream::read::Decoder::new(data)?.read_to_end(&mut decompressed)?;
+            }
+        }
+        Ok(decompressed)
+    }
+
+    /// Train a dictionary for `host` from `samples` (its pages) and make it
+    /// the one its pages are compressed with from now on
+    pub fn train(&self, host: &str, samples: &[Vec<u8>]) -> Result<Trained> {
+        if samples.len() < MIN_SAMPLES {
+            bail!(
+                "{host}: {} pages, a dictionary needs at least {MIN_SAMPLES}",
+                samples.len()
+            );
+        }
+        let total: usize = samples.iter().map(Vec::len).sum();
+        let dictionary = zstd::dict::from_samples(samples, MAX_DICTIONARY_SIZE.min(total / 10))
+            .with_context(|| format!("Failed to train a dictionary for {host}"))?;
+        let id = zstd::zstd_safe::get_dict_id_from_dict(&dictionary)
+            .context("Trained dictionary has no ID")?
+            .get();
+        crate::state::write_atomic(&self.dict_path(id), &dictionary)?;
+
+        let mut hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+        hosts.hosts.insert(host.to_string(), id);
+        crate::state::save(&self.dir.join("hosts.json"), &*hosts)?;
+        let bytes = dictionary.len();
+        self.loaded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id, Arc::new(dictionary));
+        Ok(Trained {
+            host: host.to_string(),
+            id,
+            bytes,
+            samples: samples.len(),
+        })
+    }
+
+    fn load(&self, id: u32) -> Option<Arc<Vec<u8>>> {
+        let mut loaded = self.loaded.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(dictionary) = loaded.get(&id) {
+            return Some(Arc::clone(dictionary));
+        }
+        let path = self.dict_path(id);
+        let dictionary = Arc::new(std::fs::read(&path).ok()?);
+        // In use: keep it from being evicted before its entries
+        crate::quota::touch(&path);
+        loaded.insert(id, Arc::clone(&dictionary));
+        Some(dictionary)
+    }
+
+    fn dict_path(&self, id: u32) -> PathBuf {
+        self.dir.join(format!("{id}.dict"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dictionary_round_trip() {
+        let dir = std::env::temp_dir().join(format!("nab-dicts-{}", std::process::id()));
+        let dictionaries = Dictionaries::open(&dir);
+        let page = |n: usize| {
+            format!(
+                "<html><head><title>Article {n}</title><link rel=stylesheet href=/site.css>\
+                 </head><body><nav><a href=/>Home</a><a href=/news>News</a></nav>\
+                 <main><h1>Story number {n}</h1><p>Body text {}</p></main>\
+                 <footer>© Example News, all rights reserved</footer></body></html>",
+                "lorem ipsum ".repeat(n % 7 + 1)
+            )
+            .into_bytes()
+        };
+
+        let plain = dictionaries.compress("news.example", &page(1)).unwrap();
+        assert_eq!(dictionaries.decompress(&plain).unwrap(), page(1));
+
+        let samples: Vec<Vec<u8>> = (0..40).map(page).collect();
+        assert!(dictionaries.train("news.example", &samples[..3]).is_err());
+        let trained = dictionaries.train("news.example", &samples).unwrap();
+        assert_eq!(trained.samples, 40);
+        let small = dictionaries.compress("news.example", &page(99)).unwrap();
+        assert!(
+            small.len() < plain.len(),
+            "{} vs {}",
+            small.len(),
+            plain.len()
+        );
+
+        // A later run reads both kinds of frames
+        let next_run = Dictionaries::open(&dir);
+        assert_eq!(next_run.decompress(&small).unwrap(), page(99));
+        assert_eq!(next_run.decompress(&plain).unwrap(), page(1));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
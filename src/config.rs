@@ -0,0 +1,367 @@
+//! User Configuration
+//!
+//! Optional settings loaded from `~/.config/nab/config.json` (or the file
+//! named by `NAB_CONFIG`). Every section and field has a default, so a
+//! missing or partial file is fine:
+//!
+//! ```json
+//! {
+//!   "summarize": {
+//!     "endpoint": "http://localhost:11434/v1",
+//!     "model": "llama3.1",
+//!     "prompt": "Summarize for a busy engineer:\n\n{content}"
+//!   },
+//!   "translate": {
+//!     "backend": "libretranslate",
+//!     "endpoint": "http://localhost:5000"
+//!   },
+//!   "fingerprint": {
+//!     "verify_endpoints": ["https://httpbin.org/headers", "https://echo.internal/headers"]
+//!   },
+//!   "oauth2": {
+//!     "reports-api": {
+//!       "token_url": "https://auth.example.com/oauth/token",
+//!       "client_id": "nab-archiver",
+//!       "client_secret_env": "REPORTS_CLIENT_SECRET",
+//!       "scope": "reports:read",
+//!       "domains": ["api.example.com"]
+//!     }
+//!   },
+//!   "domains": {
+//!     "intranet.example.com": {
+//!       "proxy": "socks5h://bastion:1080",
+//!       "cookies": "none",
+//!       "headers": {"X-Team": "search"}
+//!     },
+//!     "slow-api.example.org": {"retries": 3, "timeout_secs": 90},
+//!     "legacy.example.net": {"http": "1"},
+//!     "shop.example.com": {"pagination": {"next_selector": "nav.pager a.forward"}}
+//!   },
+//!   "proxy_pool": [
+//!     {"url": "secret:DE_PROXY", "country": "de"},
+//!     {"url": "socks5h://fr1.proxy.example:1080", "country": "fr"}
+//!   ],
+//!   "self_update": {"channel": "beta", "disabled": false},
+//!   "audit": {"hsts_include_subdomains": true, "required": ["Permissions-Policy"]},
+//!   "policy": {"allow": ["example.com", "docs.rs"], "deny": ["admin.example.com"]}
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::HeaderPolicy;
+use crate::geo::PoolProxy;
+use crate::http_client::HttpVersion;
+use crate::paginate::PaginationRule;
+use crate::policy::DomainPolicy;
+use crate::request_options::ProfileChoice;
+use crate::self_update::Channel;
+
+/// Top-level nab configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NabConfig {
+    /// `--summarize` backend and prompts
+    pub summarize: SummarizeConfig,
+    /// `--translate` backend
+    pub translate: TranslateConfig,
+    /// `nab fingerprint verify` endpoints
+    pub fingerprint: FingerprintConfig,
+    /// OAuth2 clients for `--auth oauth2:<name>`, by name
+    pub oauth2: BTreeMap<String, OAuth2Config>,
+    /// Request settings for a host and its subdomains, by host
+    pub domains: BTreeMap<String, DomainConfig>,
+    /// Proxies by egress country, for `--geo`
+    pub proxy_pool: Vec<PoolProxy>,
+    /// `nab self update` settings
+    pub self_update: SelfUpdateConfig,
+    /// `nab audit headers` policy
+    pub audit: HeaderPolicy,
+    /// Hosts nab may fetch from
+    pub policy: DomainPolicy,
+}
+
+/// Settings for `--summarize`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SummarizeConfig {
+    /// Shell command that reads the prompt on stdin and prints the summary
+    pub command: Option<String>,
+    /// OpenAI-compatible API base URL (e.g. `https://api.openai.com/v1`)
+    pub endpoint: Option<String>,
+    /// Model name sent to the endpoint
+    pub model: String,
+    /// Environment variable holding the endpoint API key
+    pub api_key_env: String,
+    /// Prompt template; `{content}` is replaced by the page Markdown and `{url}` by its URL
+    pub prompt: String,
+    /// Prompt used to merge per-chunk summaries of long documents
+    pub combine_prompt: String,
+    /// Maximum characters of Markdown sent per request
+    pub chunk_chars: usize,
+}
+
+impl Default for SummarizeConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            endpoint: None,
+            model: "gpt-4o-mini".to_string(),
+            api_key_env: "OPENAI_API_KEY".to_string(),
+            prompt: "Summarize the following web page in 3-7 concise bullet points. \
+                     Keep names, numbers, and dates exact.\n\nSource: {url}\n\n{content}"
+                .to_string(),
+            combine_prompt: "Merge these partial summaries of one web page into a single \
+                             summary of 3-7 concise bullet points.\n\nSource: {url}\n\n{content}"
+                .to_string(),
+            chunk_chars: 12_000,
+        }
+    }
+}
+
+/// Translation service used by `--translate`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslateService {
+    /// `LibreTranslate` (self-hosted or hosted)
+    #[default]
+    LibreTranslate,
+    /// `DeepL` API
+    DeepL,
+}
+
+/// Settings for `--translate`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranslateConfig {
+    /// Which API the endpoint speaks
+    pub backend: TranslateService,
+    /// API URL; required for `LibreTranslate`, defaults to the `DeepL` free API
+    pub endpoint: Option<String>,
+    /// Environment variable holding the API key
+    /// (default `LIBRETRANSLATE_API_KEY` or `DEEPL_AUTH_KEY`)
+    pub api_key_env: Option<String>,
+}
+
+/// Settings for `nab fingerprint verify`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FingerprintConfig {
+    /// Echo endpoints the profile's requests are sent to
+    pub verify_endpoints: Vec<String>,
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        Self {
+            verify_endpoints: crate::fingerprint::verify::DEFAULT_ENDPOINTS
+                .iter()
+                .map(|e| (*e).to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Settings for `nab self update`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfUpdateConfig {
+    /// Refuse to update (machines updated some other way, air-gapped hosts)
+    pub disabled: bool,
+    /// Channel followed without `--channel`
+    pub channel: Channel,
+    /// Hex Ed25519 key the release checksums must be signed with, instead of
+    /// the one compiled into release builds
+    pub public_key: Option<String>,
+    /// Release listing in GitHub's format, e.g. an internal mirror
+    pub releases_url: Option<String>,
+}
+
+/// OAuth2 grant used to obtain the first token
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuth2Grant {
+    /// `client_credentials` with the client ID and secret
+    #[default]
+    ClientCredentials,
+    /// `refresh_token` with a long-lived token from `refresh_token_env`
+    RefreshToken,
+}
+
+/// An OAuth2 client for `--auth oauth2:<name>`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    /// Token endpoint
+    pub token_url: String,
+    pub client_id: String,
+    /// Environment variable holding the client secret (public clients may omit it)
+    pub client_secret_env: Option<String>,
+    #[serde(default)]
+    pub grant: OAuth2Grant,
+    /// Environment variable holding the refresh token (`refresh_token` grant)
+    pub refresh_token_env: Option<String>,
+    /// Space-separated scopes to request
+    pub scope: Option<String>,
+    /// `audience` parameter (Auth0 and similar)
+    pub audience: Option<String>,
+    /// Hosts the token is sent to (subdomains included); required, so a
+    /// token never goes to every site a batch or crawl touches
+    pub domains: Vec<String>,
+}
+
+/// Per-site defaults for [`RequestOptions`](crate::RequestOptions); flags win
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DomainConfig {
+    pub profile: Option<ProfileChoice>,
+    /// Proxy URL (ignored when a proxy or proxy chain is given explicitly)
+    pub proxy: Option<String>,
+    /// Cookie source: auto, none, or a browser name
+    pub cookies: Option<String>,
+    pub retries: Option<u32>,
+    pub timeout_secs: Option<u64>,
+    pub max_body: Option<usize>,
+    /// Settle time for rendered pages, in milliseconds
+    pub wait_ms: Option<u64>,
+    /// HTTP version: `"1"` for origins that misbehave under HTTP/2, `"2"`, or `"auto"`
+    pub http: Option<HttpVersion>,
+    /// Extra request headers
+    pub headers: BTreeMap<String, String>,
+    /// How `--paginate` finds the next page
+    pub pagination: Option<PaginationRule>,
+}
+
+impl NabConfig {
+    /// Path of the config file (`NAB_CONFIG` overrides the default location)
+    #[must_use]
+    pub fn path() -> PathBuf {
+        std::env::var_os("NAB_CONFIG").map_or_else(
+            || crate::workspace::config_dir().join("config.json"),
+            PathBuf::from,
+        )
+    }
+
+    /// `domains` entries matching `host` (the host and its subdomains), most specific first
+    #[must_use]
+    pub fn domains_for(&self, host: &str) -> Vec<(&String, &DomainConfig)> {
+        let host = host.to_ascii_lowercase();
+        let mut matching: Vec<(&String, &DomainConfig)> = self
+            .domains
+            .iter()
+            .filter(|(domain, _)| {
+                let domain = domain.to_ascii_lowercase();
+                host == domain || host.ends_with(&format!(".{domain}"))
+            })
+            .collect();
+        matching.sort_by_key(|(domain, _)| std::cmp::Reverse(domain.len()));
+        matching
+    }
+
+    /// Pagination rule of the most specific `domains` entry matching `url` that has one
+    #[must_use]
+    pub fn pagination_for(&self, url: &url::Url) -> Option<PaginationRule> {
+        self.domains_for(url.host_str()?)
+            .into_iter()
+            .find_map(|(_, site)| site.pagination.clone())
+    }
+
+    /// HTTP version of the most specific `domains` entry matching `url` that sets one
+    #[must_use]
+    pub fn http_for(&self, url: &url::Url) -> Option<HttpVersion> {
+        self.domains_for(url.host_str()?)
+            .into_iter()
+            .find_map(|(_, site)| site.http)
+    }
+
+    /// Load the config file, falling back to defaults when it doesn't exist
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Invalid config {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config: NabConfig =
+            serde_json::from_str(r#"{"summarize": {"command": "llm -m mini"}}"#).unwrap();
+        assert_eq!(config.summarize.command.as_deref(), Some("llm -m mini"));
+        assert_eq!(config.summarize.chunk_chars, 12_000);
+        assert!(config.summarize.prompt.contains("{content}"));
+
+        let empty: NabConfig = serde_json::from_str("{}").unwrap();
+        assert!(empty.summarize.endpoint.is_none());
+        assert_eq!(empty.translate.backend, TranslateService::LibreTranslate);
+
+        let deepl: NabConfig =
+            serde_json::from_str(r#"{"translate": {"backend": "deepl"}}"#).unwrap();
+        assert_eq!(deepl.translate.backend, TranslateService::DeepL);
+
+        let oauth: NabConfig = serde_json::from_str(
+            r#"{"oauth2": {"api": {"token_url": "https://a.example/token", "client_id": "x",
+                "domains": ["a.example"]}}}"#,
+        )
+        .unwrap();
+        assert_eq!(oauth.oauth2["api"].grant, OAuth2Grant::ClientCredentials);
+        assert!(serde_json::from_str::<NabConfig>(
+            r#"{"oauth2": {"api": {"token_url": "https://a.example/token", "client_id": "x"}}}"#,
+        )
+        .is_err());
+        assert!(empty.oauth2.is_empty());
+        assert_eq!(empty.fingerprint.verify_endpoints.len(), 2);
+
+        let domains: NabConfig = serde_json::from_str(
+            r#"{"domains": {"example.com": {"profile": "firefox", "retries": 2}}}"#,
+        )
+        .unwrap();
+        let site = &domains.domains["example.com"];
+        assert_eq!(site.profile, Some(ProfileChoice::Firefox));
+        assert_eq!(site.retries, Some(2));
+        assert!(site.headers.is_empty());
+        assert!(site.pagination.is_none());
+        assert!(
+            serde_json::from_str::<NabConfig>(r#"{"domains": {"example.com": {"retry": 2}}}"#)
+                .is_err()
+        );
+
+        let paginated: NabConfig = serde_json::from_str(
+            r#"{"domains": {
+                "example.com": {"pagination": {"page_param": "p"}},
+                "shop.example.com": {"retries": 1}
+            }}"#,
+        )
+        .unwrap();
+        let shop = url::Url::parse("https://shop.example.com/list").unwrap();
+        let rule = paginated.pagination_for(&shop).unwrap();
+        assert_eq!(rule.page_param.as_deref(), Some("p"));
+        assert_eq!(
+            paginated.domains_for("shop.example.com")[0].0,
+            "shop.example.com"
+        );
+        assert!(paginated
+            .pagination_for(&url::Url::parse("https://example.org/").unwrap())
+            .is_none());
+
+        let legacy: NabConfig =
+            serde_json::from_str(r#"{"domains": {"legacy.example.net": {"http": "1"}}}"#).unwrap();
+        let page = url::Url::parse("https://www.legacy.example.net/").unwrap();
+        assert_eq!(legacy.http_for(&page), Some(HttpVersion::Http1));
+        assert_eq!(legacy.http_for(&shop), None);
+        assert!(
+            serde_json::from_str::<NabConfig>(r#"{"domains": {"a.example": {"http": "4"}}}"#)
+                .is_err()
+        );
+    }
+}
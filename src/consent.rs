@@ -0,0 +1,469 @@
+//! Cookie Consent Handling
+//!
+//! Gets GDPR consent management platforms (CMPs) out of the way so extracted
+//! content isn't mostly cookie banner:
+//! - Static mode: strips consent dialogs and consent-wall overlays from HTML
+//! - SPA mode: pre-seeds CMP consent cookies/storage and stubs CMP JS APIs
+//!   (`__tcfapi`, `OneTrust`, `Cookiebot`, ...) with an accept or reject decision
+//!
+//! Rules cover common CMPs: OneTrust, Cookiebot, Quantcast Choice, Didomi,
+//! Usercentrics, `TrustArc`, and Sourcepoint.
+
+use scraper::{Html, Selector};
+
+/// What to do about cookie consent dialogs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsentMode {
+    /// Leave consent dialogs untouched
+    #[default]
+    Ignore,
+    /// Accept all consent categories
+    Accept,
+    /// Reject everything except strictly necessary cookies
+    Reject,
+}
+
+impl ConsentMode {
+    /// Whether consent dialogs should be handled at all
+    #[must_use]
+    pub fn is_active(self) -> bool {
+        self != Self::Ignore
+    }
+
+    fn accepts(self) -> bool {
+        self == Self::Accept
+    }
+}
+
+/// Detection and removal rule for one consent management platform
+#[derive(Debug)]
+pub struct CmpRule {
+    /// Human-readable CMP name
+    pub name: &'static str,
+    /// Substrings in page HTML that reveal the CMP (script URLs, globals)
+    pub markers: &'static [&'static str],
+    /// CSS selectors for banner, dialog, and overlay elements
+    pub selectors: &'static [&'static str],
+}
+
+/// Known consent management platforms
+pub const CONSENT_RULES: &[CmpRule] = &[
+    CmpRule {
+        name: "OneTrust",
+        markers: &["cdn.cookielaw.org", "otSDKStub", "optanon", "onetrust"],
+        selectors: &[
+            "#onetrust-consent-sdk",
+            "#onetrust-banner-sdk",
+            "#onetrust-pc-sdk",
+            ".onetrust-pc-dark-filter",
+            "#optanon",
+        ],
+    },
+    CmpRule {
+        name: "Cookiebot",
+        markers: &["consent.cookiebot.com", "Cookiebot", "CybotCookiebot"],
+        selectors: &[
+            "#CybotCookiebotDialog",
+            "#CybotCookiebotDialogBodyUnderlay",
+            "#cookiebanner",
+        ],
+    },
+    CmpRule {
+        name: "Quantcast",
+        markers: &["quantcast.mgr.consensu.org", "cmp.quantcast.com", "qc-cmp2"],
+        selectors: &["#qc-cmp2-container", "#qc-cmp2-ui", ".qc-cmp2-container"],
+    },
+    CmpRule {
+        name: "Didomi",
+        markers: &["sdk.privacy-center.org", "didomi"],
+        selectors: &["#didomi-host", "#didomi-popup", "#didomi-notice"],
+    },
+    CmpRule {
+        name: "Usercentrics",
+        markers: &["app.usercentrics.eu", "usercentrics"],
+        selectors: &["#usercentrics-root", "#usercentrics-cmp-ui"],
+    },
+    CmpRule {
+        name: "TrustArc",
+        markers: &["consent.trustarc.com", "truste"],
+        selectors: &[
+            "#truste-consent-track",
+            "#consent_blackbar",
+            ".truste_overlay",
+        ],
+    },
+    CmpRule {
+        name: "Sourcepoint",
+        markers: &["sourcepoint", "sp_message_container", "_sp_"],
+        selectors: &["[id^='sp_message_container']", ".sp_veil"],
+    },
+];
+
+/// Detect which known CMPs a page uses
+#[must_use]
+pub fn detect_cmps(html: &str) -> Vec<&'static CmpRule> {
+    let lower = html.to_lowercase();
+    CONSENT_RULES
+        .iter()
+        .filter(|rule| {
+            rule.markers
+                .iter()
+                .any(|m| lower.contains(&m.to_lowercase()))
+        })
+        .collect()
+}
+
+/// Remove consent dialogs and consent-wall overlays from HTML
+///
+/// Returns the cleaned HTML and the names of the CMPs whose elements were
+/// removed. HTML without consent elements is returned unchanged.
+#[must_use]
+pub fn strip_consent_walls(html: &str) -> (String, Vec<&'static str>) {
+    let mut document = Html::parse_document(html);
+    let mut removed = Vec::new();
+    let mut node_ids = Vec::new();
+
+    for rule in CONSENT_RULES {
+        let before = node_ids.len();
+        for selector in rule.selectors {
+            let Ok(selector) = Selector::parse(selector) else {
+                continue;
+            };
+            node_ids.extend(document.select(&selector).map(|el| el.id()));
+        }
+        if node_ids.len() > before {
+            removed.push(rule.name);
+        }
+    }
+
+    if node_ids.is_empty() {
+        return (html.to_string(), removed);
+    }
+
+    for id in node_ids {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+
+    (document.html(), removed)
+}
+
+/// Cookies that record a consent decision for every known CMP
+///
+/// Returns `name=value` pairs ready to be joined into a `Cookie` header.
+#[must_use]
+pub fn consent_cookies(mode: ConsentMode) -> Vec<String> {
+    if !mode.is_active() {
+        return Vec::new();
+    }
+
+    let now = chrono::Utc::now();
+    let on = if mode.accepts() { "1" } else { "0" };
+    let yes = if mode.accepts() { "true" } else { "false" };
+
+    // OneTrust: C0001 = strictly necessary (always on), C0002-C0005 optional
+    let groups = format!("C0001:1,C0002:{on},C0003:{on},C0004:{on},C0005:{on}");
+    let onetrust = format!(
+        "isGpcEnabled=0&datestamp={}&version=202401.1.0&isIABGlobal=false&hosts=&interactionCount=1&landingPath=NotLandingPage&groups={}",
+        encode_cookie_value(&now.format("%a %b %d %Y %H:%M:%S GMT+0000").to_string()),
+        encode_cookie_value(&groups),
+    );
+
+    // Cookiebot stores a JS-object-like consent record
+    let cookiebot = format!(
+        "{{stamp:'-1',necessary:true,preferences:{yes},statistics:{yes},marketing:{yes},method:'explicit',ver:1,utc:{}}}",
+        now.timestamp_millis()
+    );
+
+    vec![
+        format!(
+            "OptanonAlertBoxClosed={}",
+            encode_cookie_value(&now.to_rfc3339())
+        ),
+        // Already a query string with encoded values, as OneTrust writes it
+        format!("OptanonConsent={onetrust}"),
+        format!("CookieConsent={}", encode_cookie_value(&cookiebot)),
+        format!(
+            "notice_behavior={}",
+            if mode.accepts() {
+                "implied,eu"
+            } else {
+                "expressed,eu"
+            }
+        ),
+        format!(
+            "notice_gdpr_prefs={}",
+            if mode.accepts() { "0,1,2:" } else { "0:" }
+        ),
+    ]
+}
+
+/// Append consent cookies to an existing `Cookie` header value
+#[must_use]
+pub fn with_consent_cookies(cookie_header: &str, mode: ConsentMode) -> String {
+    let mut parts: Vec<String> = cookie_header
+        .split(';')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(String::from)
+        .collect();
+
+    for cookie in consent_cookies(mode) {
+        let name = cookie.split('=').next().unwrap_or_default();
+        // Real browser cookies win over synthetic ones
+        if !parts.iter().any(|p| p.split('=').next() == Some(name)) {
+            parts.push(cookie);
+        }
+    }
+    parts.join("; ")
+}
+
+fn encode_cookie_value(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// JS that stubs CMP APIs with a decided consent state (SPA mode)
+///
+/// Must run after the DOM shim and before page scripts.
+#[must_use]
+pub fn consent_js_shim(mode: ConsentMode) -> String {
+    if !mode.is_active() {
+        return String::new();
+    }
+    let granted = mode.accepts();
+
+    format!(
+        r"
+        (function() {{
+            var granted = {granted};
+            var g = (typeof window !== 'undefined') ? window : globalThis;
+            // Non-enumerable so the stubs don't show up in extracted window data.
+            // The DOM shim's window is a plain object, so define on both.
+            function def(name, value) {{
+                var desc = {{ value: value, writable: true, configurable: true, enumerable: false }};
+                Object.defineProperty(globalThis, name, desc);
+                if (g !== globalThis) Object.defineProperty(g, name, desc);
+            }}
+
+            // IAB TCF v2 (Quantcast, Didomi, Sourcepoint, ...)
+            var tcData = {{
+                tcString: '',
+                gdprApplies: true,
+                eventStatus: 'useractioncomplete',
+                cmpStatus: 'loaded',
+                purpose: {{ consents: {{ 1: granted, 2: granted, 3: granted, 4: granted, 5: granted }} }},
+                vendor: {{ consents: {{}} }}
+            }};
+            var tcfapi = function(command, version, callback) {{
+                if (typeof callback !== 'function') return;
+                if (command === 'ping') callback({{ gdprApplies: true, cmpLoaded: true, cmpStatus: 'loaded' }}, true);
+                else callback(tcData, true);
+            }};
+            def('__tcfapi', tcfapi);
+            def('__cmp', tcfapi);
+
+            // OneTrust
+            var groups = granted ? ',C0001,C0002,C0003,C0004,C0005,' : ',C0001,';
+            def('OnetrustActiveGroups', groups);
+            def('OptanonActiveGroups', groups);
+            var onetrust = {{
+                IsAlertBoxClosed: function() {{ return true; }},
+                IsAlertBoxClosedAndValid: function() {{ return true; }},
+                AllowAll: function() {{}},
+                RejectAll: function() {{}},
+                Close: function() {{}},
+                ToggleInfoDisplay: function() {{}},
+                OnConsentChanged: function() {{}},
+                GetDomainData: function() {{ return {{ Groups: [] }}; }}
+            }};
+            def('OneTrust', onetrust);
+            def('Optanon', onetrust);
+
+            // Cookiebot
+            var cookiebot = {{
+                consented: granted,
+                declined: !granted,
+                hasResponse: true,
+                consent: {{ necessary: true, preferences: granted, statistics: granted, marketing: granted }},
+                show: function() {{}},
+                hide: function() {{}},
+                renew: function() {{}},
+                submitCustomConsent: function() {{}}
+            }};
+            def('Cookiebot', cookiebot);
+            def('CookieConsent', cookiebot);
+
+            // Didomi
+            def('didomiOnReady', g.didomiOnReady || []);
+            def('Didomi', {{
+                notice: {{ isVisible: function() {{ return false; }} }},
+                getUserConsentStatusForAll: function() {{
+                    return granted ? {{ purposes: {{ enabled: [] }}, vendors: {{ enabled: [] }} }}
+                                   : {{ purposes: {{ disabled: [] }}, vendors: {{ disabled: [] }} }};
+                }}
+            }});
+
+            // Usercentrics: `uc_user_interaction` only records that the banner
+            // was answered, so it's set either way; the decision is in the
+            // Google consent mode record `uc_gcm` and the `UC_UI` API
+            var ucState = granted ? 'granted' : 'denied';
+            var noop = function() {{ return Promise.resolve(); }};
+            def('UC_UI', {{
+                isInitialized: function() {{ return true; }},
+                areAllConsentsAccepted: function() {{ return granted; }},
+                getServicesBaseInfo: function() {{ return []; }},
+                acceptAllConsents: noop,
+                denyAllConsents: noop,
+                closeCMP: noop,
+                showFirstLayer: noop,
+                showSecondLayer: noop
+            }});
+            if (g.localStorage && g.localStorage.setItem) {{
+                g.localStorage.setItem('uc_user_interaction', 'true');
+                g.localStorage.setItem('uc_gcm', JSON.stringify({{
+                    adStorage: ucState,
+                    adUserData: ucState,
+                    adPersonalization: ucState,
+                    analyticsStorage: ucState
+                }}));
+            }}
+        }})();
+        "
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONETRUST_PAGE: &str = r#"
+        <html><head><script src="https://cdn.cookielaw.org/scripttemplates/otSDKStub.js"></script></head>
+        <body>
+            <article><h1>Headline</h1><p>Actual article text.</p></article>
+            <div id="onetrust-consent-sdk"><div id="onetrust-banner-sdk">We value your privacy</div></div>
+        </body></html>
+    "#;
+
+    #[test]
+    fn test_detect_cmps() {
+        let names: Vec<&str> = detect_cmps(ONETRUST_PAGE).iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["OneTrust"]);
+        assert!(detect_cmps("<html><body>plain</body></html>").is_empty());
+    }
+
+    #[test]
+    fn test_strip_consent_walls() {
+        let (html, removed) = strip_consent_walls(ONETRUST_PAGE);
+        assert_eq!(removed, vec!["OneTrust"]);
+        assert!(!html.contains("We value your privacy"));
+        assert!(html.contains("Actual article text."));
+
+        let plain = "<html><body><p>nothing to strip</p></body></html>";
+        let (unchanged, removed) = strip_consent_walls(plain);
+        assert!(removed.is_empty());
+        assert_eq!(unchanged, plain);
+    }
+
+    #[test]
+    fn test_consent_cookies() {
+        assert!(consent_cookies(ConsentMode::Ignore).is_empty());
+
+        let accept = consent_cookies(ConsentMode::Accept).join("; ");
+        assert!(accept.contains("OptanonAlertBoxClosed="));
+        assert!(accept.contains("C0002%3A1"));
+
+        let reject = consent_cookies(ConsentMode::Reject).join("; ");
+        assert!(reject.contains("C0002%3A0"));
+        assert!(reject.contains("marketing%3Afalse"));
+    }
+
+    #[test]
+    fn test_browser_cookies_take_precedence() {
+        let header = with_consent_cookies("session=abc; CookieConsent=real", ConsentMode::Accept);
+        assert!(header.starts_with("session=abc; CookieConsent=real"));
+        assert_eq!(header.matches("CookieConsent=").count(), 1);
+        assert!(header.contains("OptanonConsent="));
+    }
+
+    /// Engine with the DOM and consent shims for `mode`
+    #[cfg(feature = "spa")]
+    fn shimmed(mode: ConsentMode) -> crate::JsEngine {
+        let engine = crate::JsEngine::new().unwrap();
+        engine.inject_minimal_dom().unwrap();
+        engine.eval(&consent_js_shim(mode)).unwrap();
+        engine
+    }
+
+    #[test]
+    #[cfg(feature = "spa")]
+    fn test_js_shim() {
+        let engine = shimmed(ConsentMode::Reject);
+        assert_eq!(
+            engine
+                .eval("Object.keys(window).indexOf('OneTrust')")
+                .unwrap(),
+            "-1"
+        );
+        assert!(consent_js_shim(ConsentMode::Ignore).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "spa")]
+    fn test_js_shim_tcf() {
+        let consent = "var c; __tcfapi('getTCData', 2, function(d) { c = d; }); \
+                       c.eventStatus + ' ' + c.purpose.consents[1]";
+        for (mode, expected) in [
+            (ConsentMode::Accept, "useractioncomplete true"),
+            (ConsentMode::Reject, "useractioncomplete false"),
+        ] {
+            assert_eq!(shimmed(mode).eval(consent).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "spa")]
+    fn test_js_shim_onetrust() {
+        let state = "OnetrustActiveGroups + ' ' + OneTrust.IsAlertBoxClosed()";
+        let accepted = shimmed(ConsentMode::Accept).eval(state).unwrap();
+        assert_eq!(accepted, ",C0001,C0002,C0003,C0004,C0005, true");
+        let rejected = shimmed(ConsentMode::Reject).eval(state).unwrap();
+        assert_eq!(rejected, ",C0001, true");
+    }
+
+    #[test]
+    #[cfg(feature = "spa")]
+    fn test_js_shim_cookiebot() {
+        let state = "Cookiebot.hasResponse + ' ' + Cookiebot.consent.marketing";
+        let accepted = shimmed(ConsentMode::Accept).eval(state).unwrap();
+        assert_eq!(accepted, "true true");
+        let rejected = shimmed(ConsentMode::Reject).eval(state).unwrap();
+        assert_eq!(rejected, "true false");
+    }
+
+    #[test]
+    #[cfg(feature = "spa")]
+    fn test_js_shim_didomi() {
+        let state = "var s = Didomi.getUserConsentStatusForAll(); \
+                     Didomi.notice.isVisible() + ' ' + ('enabled' in s.purposes)";
+        let accepted = shimmed(ConsentMode::Accept).eval(state).unwrap();
+        assert_eq!(accepted, "false true");
+        let rejected = shimmed(ConsentMode::Reject).eval(state).unwrap();
+        assert_eq!(rejected, "false false");
+        // Didomi keeps its own encoded token; the shim doesn't fake one
+        let token = "window.localStorage.getItem('didomi_token')";
+        assert_eq!(shimmed(ConsentMode::Accept).eval(token).unwrap(), "null");
+    }
+
+    #[test]
+    #[cfg(feature = "spa")]
+    fn test_js_shim_usercentrics() {
+        let state = "UC_UI.areAllConsentsAccepted() + ' ' \
+                     + window.localStorage.getItem('uc_user_interaction') + ' ' \
+                     + JSON.parse(window.localStorage.getItem('uc_gcm')).analyticsStorage";
+        let accepted = shimmed(ConsentMode::Accept).eval(state).unwrap();
+        assert_eq!(accepted, "true true granted");
+        let rejected = shimmed(ConsentMode::Reject).eval(state).unwrap();
+        assert_eq!(rejected, "false true denied");
+    }
+}
@@ -0,0 +1,472 @@
+//! Content Sniffing and Handlers
+//!
+//! Servers mislabel what they send: PDFs as `text/html`, JSON as
+//! `text/plain`, feeds as `application/xml`, everything as
+//! `application/octet-stream`. [`ContentKind::detect`] decides what a body
+//! really is from its `Content-Type` and its first bytes:
+//!
+//! 1. magic bytes win: `%PDF-`, PNG, JPEG, GIF, WebP, ZIP, gzip, and WebAssembly
+//!    signatures are recognized whatever the header says; zip archives with a
+//!    `word/` or `xl/` part are Word or Excel documents
+//! 2. then the declared type, when it's specific (HTML, JSON, XML, images, ...)
+//! 3. `text/plain`, unlabeled, and unknown bodies are sniffed: an HTML doctype,
+//!    an XML prolog or feed root, JSON brackets, or else UTF-8 text
+//!
+//! Each kind then has a handler instead of the HTML pipeline: JSON is
+//! pretty-printed, RSS and Atom feeds become Markdown ([`feed_markdown`]),
+//! PDFs become Markdown ([`crate::pdf::PdfDocument`]), Word documents Markdown
+//! and Excel workbooks CSV ([`crate::office`]), and images and other binaries
+//! are saved as files ([`file_name`]).
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Serialize;
+use url::Url;
+
+/// Content types that are binary whatever the body looks like
+const BINARY_TYPES: &[&str] = &[
+    "audio/",
+    "video/",
+    "font/",
+    "application/octet-stream",
+    "application/zip",
+    "application/gzip",
+    "application/wasm",
+];
+
+/// How many leading bytes are sniffed
+const SNIFF_LEN: usize = 1024;
+
+/// What a response body turned out to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    Html,
+    Json,
+    /// RSS or Atom
+    Feed,
+    /// XML other than a feed
+    Xml,
+    Pdf,
+    /// Word document (`.docx`)
+    Docx,
+    /// Excel workbook (`.xlsx`)
+    Xlsx,
+    Image,
+    /// Plain text, CSS, JavaScript, CSV, ...
+    Text,
+    /// Anything else that isn't text
+    Binary,
+}
+
+impl ContentKind {
+    /// Classify `body`, served with `content_type` (if any)
+    #[must_use]
+    pub fn detect(content_type: Option<&str>, body: &[u8]) -> Self {
+        if let Some(kind) = magic(body) {
+            return kind;
+        }
+        let essence = content_type
+            .and_then(|ct| ct.split(';').next())
+            .map(|ct| ct.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        match essence.as_str() {
+            "" | "text/plain" | "application/x-unknown" | "unknown/unknown" => sniff(body),
+            "application/pdf" => Self::Pdf,
+            "application/rss+xml" | "application/atom+xml" | "application/rdf+xml" => Self::Feed,
+            ct if ct.starts_with("image/") => Self::Image,
+            ct if BINARY_TYPES.iter().any(|binary| ct.starts_with(binary)) => Self::Binary,
+            ct if ct.contains("html") => Self::Html,
+            ct if ct == "application/json" || ct.ends_with("+json") => Self::Json,
+            ct if ct.ends_with("/xml") || ct.ends_with("+xml") => {
+                if is_feed(&head(body)) {
+                    Self::Feed
+                } else {
+                    Self::Xml
+                }
+            }
+            ct if ct.starts_with("text/") || ct.ends_with("javascript") => Self::Text,
+            _ => sniff(body),
+        }
+    }
+
+    /// Bodies that must be kept as bytes
+    #[must_use]
+    pub fn is_binary(self) -> bool {
+        matches!(
+            self,
+            Self::Pdf | Self::Docx | Self::Xlsx | Self::Image | Self::Binary
+        )
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Json => "json",
+            Self::Feed => "feed",
+            Self::Xml => "xml",
+            Self::Pdf => "pdf",
+            Self::Docx => "docx",
+            Self::Xlsx => "xlsx",
+            Self::Image => "image",
+            Self::Text => "text",
+            Self::Binary => "binary",
+        }
+    }
+}
+
+/// Kind given away by the first bytes, for binary formats
+fn magic(body: &[u8]) -> Option<ContentKind> {
+    const IMAGES: &[&[u8]] = &[b"\x89PNG\r\n\x1a\n", b"\xff\xd8\xff", b"GIF87a", b"GIF89a"];
+    const BINARIES: &[&[u8]] = &[b"PK\x03\x04", b"\x1f\x8b", b"\0asm"];
+    let has_part = |part: &[u8]| body.windows(part.len()).any(|window| window == part);
+    if body.starts_with(b"%PDF-") {
+        Some(ContentKind::Pdf)
+    } else if body.starts_with(b"PK\x03\x04") && has_part(b"word/document.xml") {
+        Some(ContentKind::Docx)
+    } else if body.starts_with(b"PK\x03\x04") && has_part(b"xl/workbook.xml") {
+        Some(ContentKind::Xlsx)
+    } else if IMAGES.iter().any(|sig| body.starts_with(sig))
+        || (body.starts_with(b"RIFF") && body.get(8..12) == Some(b"WEBP"))
+    {
+        Some(ContentKind::Image)
+    } else if BINARIES.iter().any(|sig| body.starts_with(sig)) {
+        Some(ContentKind::Binary)
+    } else {
+        None
+    }
+}
+
+/// Kind of an unlabeled body
+fn sniff(body: &[u8]) -> ContentKind {
+    let head = head(body);
+    let start = head.trim_start();
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        ContentKind::Html
+    } else if is_feed(&head) {
+        ContentKind::Feed
+    } else if start.starts_with("<?xml") {
+        ContentKind::Xml
+    } else if start.starts_with('{') || start.starts_with('[') {
+        ContentKind::Json
+    } else if is_text(&body[..body.len().min(SNIFF_LEN)]) {
+        ContentKind::Text
+    } else {
+        ContentKind::Binary
+    }
+}
+
+/// The first [`SNIFF_LEN`] bytes, lowercased, without a byte order mark
+fn head(body: &[u8]) -> String {
+    let body = body.strip_prefix(b"\xef\xbb\xbf").unwrap_or(body);
+    String::from_utf8_lossy(&body[..body.len().min(SNIFF_LEN)]).to_lowercase()
+}
+
+fn is_feed(head: &str) -> bool {
+    ["<rss", "<feed", "<rdf:rdf"]
+        .iter()
+        .any(|root| head.contains(root))
+}
+
+/// UTF-8 (possibly cut mid-character at the end) without control bytes
+fn is_text(bytes: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    valid
+        && !bytes
+            .iter()
+            .any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c))
+}
+
+/// `text` pretty-printed, if it's JSON
+#[must_use]
+pub fn pretty_json(text: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    serde_json::to_string_pretty(&value).ok()
+}
+
+static ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<(item|entry)\b[^>]*>(.*?)</(?:item|entry)>").unwrap());
+static ATOM_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?is)<link\b([^>]*?)/?>"#).unwrap());
+static REL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?is)\brel\s*=\s*["']([^"']*)["']"#).unwrap());
+static HREF: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?is)\bhref\s*=\s*["']([^"']*)["']"#).unwrap());
+
+/// An RSS or Atom feed as Markdown: its title, then each item's linked
+/// title, date, and summary
+#[must_use]
+pub fn feed_markdown(xml: &str) -> Option<String> {
+    let first_item = ITEM.find(xml).map_or(xml.len(), |m| m.start());
+    let channel = &xml[..first_item];
+    let mut markdown = String::new();
+    if let Some(title) = element_text(channel, "title") {
+        markdown.push_str(&format!("# {title}\n\n"));
+    }
+    let mut items = 0;
+    for item in ITEM.captures_iter(xml) {
+        let body = &item[2];
+        let title = element_text(body, "title").unwrap_or_else(|| "(untitled)".to_string());
+        match item_link(body) {
+            Some(link) => markdown.push_str(&format!("## [{title}]({link})\n")),
+            None => markdown.push_str(&format!("## {title}\n")),
+        }
+        if let Some(date) = ["pubDate", "published", "updated", "dc:date"]
+            .iter()
+            .find_map(|tag| element_text(body, tag))
+        {
+            markdown.push_str(&format!("*{date}*\n"));
+        }
+        if let Some(summary) = ["description", "summary", "content"]
+            .iter()
+            .find_map(|tag| element_text(body, tag))
+        {
+            let summary = if summary.contains('<') {
+                crate::page::html_to_markdown(&summary)
+            } else {
+                summary
+            };
+            markdown.push_str(&format!("\n{}\n", summary.trim()));
+        }
+        markdown.push('\n');
+        items += 1;
+    }
+    (items > 0).then_some(markdown)
+}
+
+/// RSS `<link>URL</link>`, else Atom's alternate `<link href>`
+fn item_link(item: &str) -> Option<String> {
+    if let Some(link) = element_text(item, "link").filter(|link| !link.is_empty()) {
+        return Some(link);
+    }
+    ATOM_LINK
+        .captures_iter(item)
+        .filter(|link| {
+            REL.captures(&link[1])
+                .is_none_or(|rel| rel[1].eq_ignore_ascii_case("alternate"))
+        })
+        .find_map(|link| HREF.captures(&link[1]).map(|href| unescape(&href[1])))
+}
+
+/// Unescaped text of the first `<tag>` in `xml`, CDATA unwrapped
+fn element_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{tag}");
+    let mut from = 0;
+    let name_end = loop {
+        let at = from + xml[from..].find(&open)? + open.len();
+        match xml[at..].chars().next() {
+            Some('>' | '/') => break at,
+            Some(c) if c.is_whitespace() => break at,
+            _ => from = at,
+        }
+    };
+    let tag_end = name_end + xml[name_end..].find('>')?;
+    if xml[..tag_end].ends_with('/') {
+        return Some(String::new());
+    }
+    let content = &xml[tag_end + 1..];
+    let raw = content[..content.find(&format!("</{tag}>"))?].trim();
+    let text = match raw
+        .strip_prefix("<![CDATA[")
+        .and_then(|inner| inner.strip_suffix("]]>"))
+    {
+        Some(cdata) => cdata.to_string(),
+        None => unescape(raw),
+    };
+    Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Decode XML character references and the predefined entities
+pub(crate) fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(end) = rest[..rest.len().min(12)].find(';') else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// File name to save a download from `url` as: the last path segment, or
+/// `download` with an extension for `kind`
+#[must_use]
+pub fn file_name(url: &Url, kind: ContentKind, content_type: Option<&str>) -> String {
+    let segment = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(|segment| {
+            crate::proxy::percent_decode(segment)
+                .chars()
+                .filter(|c| !matches!(c, '/' | '\\' | ':' | '\0'))
+                .collect::<String>()
+        })
+        .filter(|name| !name.is_empty() && !name.starts_with('.'));
+    if let Some(name) = segment {
+        return name;
+    }
+    let essence = content_type
+        .and_then(|ct| ct.split(';').next())
+        .map(|ct| ct.trim().to_ascii_lowercase());
+    let extension = match (kind, essence.as_deref()) {
+        (ContentKind::Pdf, _) => "pdf",
+        (ContentKind::Docx, _) => "docx",
+        (ContentKind::Xlsx, _) => "xlsx",
+        (_, Some("image/png")) => "png",
+        (_, Some("image/jpeg")) => "jpg",
+        (_, Some("image/gif")) => "gif",
+        (_, Some("image/webp")) => "webp",
+        (_, Some("image/svg+xml")) => "svg",
+        (_, Some("application/zip")) => "zip",
+        (_, Some("application/gzip")) => "gz",
+        _ => "bin",
+    };
+    format!("download.{extension}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let detect = ContentKind::detect;
+        assert_eq!(detect(Some("text/html"), b"%PDF-1.7\n"), ContentKind::Pdf);
+        assert_eq!(
+            detect(Some("application/octet-stream"), b"\x89PNG\r\n\x1a\n"),
+            ContentKind::Image
+        );
+        assert_eq!(
+            detect(Some("application/octet-stream"), b"{}"),
+            ContentKind::Binary
+        );
+        assert_eq!(
+            detect(Some("text/plain"), b" {\"a\": 1}"),
+            ContentKind::Json
+        );
+        assert_eq!(detect(None, b"<!DOCTYPE html><p>"), ContentKind::Html);
+        assert_eq!(
+            detect(Some("text/html; charset=utf-8"), b"<p>hi"),
+            ContentKind::Html
+        );
+        assert_eq!(
+            detect(Some("application/problem+json"), b"{}"),
+            ContentKind::Json
+        );
+        assert_eq!(
+            detect(Some("text/xml"), b"<?xml version=\"1.0\"?><rss>"),
+            ContentKind::Feed
+        );
+        assert_eq!(
+            detect(Some("application/xml"), b"<sitemap/>"),
+            ContentKind::Xml
+        );
+        assert_eq!(
+            detect(None, b"<?xml version=\"1.0\"?><a/>"),
+            ContentKind::Xml
+        );
+        assert_eq!(detect(Some("text/css"), b"p {}"), ContentKind::Text);
+        assert_eq!(detect(None, "plain café".as_bytes()), ContentKind::Text);
+        assert_eq!(detect(None, b"\x00\x01\x02"), ContentKind::Binary);
+        assert_eq!(
+            detect(
+                Some("application/octet-stream"),
+                b"PK\x03\x04....[Content_Types].xml....PK\x03\x04....xl/workbook.xml"
+            ),
+            ContentKind::Xlsx
+        );
+        assert_eq!(
+            detect(Some("application/zip"), b"PK\x03\x04....data.csv"),
+            ContentKind::Binary
+        );
+        assert!(ContentKind::Pdf.is_binary());
+        assert!(!ContentKind::Feed.is_binary());
+    }
+
+    #[test]
+    fn test_feed_markdown() {
+        let rss = r#"<?xml version="1.0"?><rss version="2.0"><channel>
+            <title>Example &amp; Co</title><link>https://example.com/</link>
+            <item><title>First post</title><link>https://example.com/1</link>
+            <pubDate>Mon, 05 Oct 2026 10:00:00 GMT</pubDate>
+            <description><![CDATA[<p>Hello <b>world</b></p>]]></description></item>
+            <item><title>Second</title></item>
+            </channel></rss>"#;
+        let markdown = feed_markdown(rss).unwrap();
+        assert!(markdown.starts_with("# Example & Co\n\n## [First post](https://example.com/1)\n"));
+        assert!(markdown.contains("*Mon, 05 Oct 2026 10:00:00 GMT*"));
+        assert!(markdown.contains("Hello **world**"));
+        assert!(markdown.contains("## Second\n"));
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Atom</title>
+            <entry><title>Entry</title>
+            <link rel="self" href="https://example.com/self"/>
+            <link href="https://example.com/entry"/>
+            <updated>2026-10-05T10:00:00Z</updated><summary>Short &#8212; sweet</summary></entry>
+            </feed>"#;
+        let markdown = feed_markdown(atom).unwrap();
+        assert!(markdown.contains("## [Entry](https://example.com/entry)\n*2026-10-05T10:00:00Z*"));
+        assert!(markdown.contains("Short — sweet"));
+        assert_eq!(feed_markdown("<rss><channel></channel></rss>"), None);
+    }
+
+    #[test]
+    fn test_file_name() {
+        let url = |s: &str| Url::parse(s).unwrap();
+        assert_eq!(
+            file_name(
+                &url("https://example.com/docs/report%202026.pdf"),
+                ContentKind::Pdf,
+                None
+            ),
+            "report 2026.pdf"
+        );
+        assert_eq!(
+            file_name(
+                &url("https://example.com/img/"),
+                ContentKind::Image,
+                Some("image/png")
+            ),
+            "download.png"
+        );
+        assert_eq!(
+            file_name(&url("https://example.com/.."), ContentKind::Binary, None),
+            "download.bin"
+        );
+    }
+}
@@ -0,0 +1,604 @@
+//! Work Distribution (`nab coordinator` / `nab worker`)
+//!
+//! A coordinator holds a URL list and leases its URLs over HTTP to any
+//! number of `nab worker` processes, each fetching with its own proxy and
+//! persona and reporting every page back. The coordinator writes the
+//! results, so a job spread over several machines ends up in one place.
+//!
+//! JSON over HTTP/1.1, one request per connection:
+//!
+//! ```text
+//! POST /lease   {"worker": "w1", "max": 8}           → {"urls": [...], "done": false}
+//! POST /result  {"worker": "w1", "url": ..., "line": {...}, "markdown": ...}
+//!                                                    → {"accepted": true}
+//! GET  /status                                       → {"pending": .., "leased": .., ...}
+//! ```
+//!
+//! Workers ask for more URLs as their own slots free up, so fast workers
+//! take more of the job. A URL not reported within the lease time is leased
+//! again; a late result for it is then refused. With a token, every request
+//! needs `Authorization: Bearer TOKEN`; without one the coordinator only
+//! listens on a loopback address, since anyone who can reach it could lease
+//! URLs and post pages into the output.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::batch::host_key;
+use crate::self_update::sha256_hex;
+
+/// How often an idle worker asks for URLs
+pub const POLL: Duration = Duration::from_secs(1);
+
+/// How long a finished coordinator waits for workers to hear it is done
+const DONE_GRACE: Duration = Duration::from_secs(3);
+
+/// Largest request accepted (a result carries the page's Markdown)
+const MAX_REQUEST_BYTES: usize = 64 * 1024 * 1024;
+
+/// Largest request head accepted; the body is only read once the token checks out
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// How long a connection gets to send its request and take the answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Pause after a failed accept (e.g. out of file descriptors)
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// `POST /lease`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaseRequest {
+    pub worker: String,
+    pub max: usize,
+}
+
+/// Answer to `POST /lease`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaseResponse {
+    pub urls: Vec<String>,
+    /// Every URL is completed: the worker can stop
+    pub done: bool,
+}
+
+/// `POST /result`: a page a worker fetched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkResult {
+    pub worker: String,
+    pub url: String,
+    /// The page's result line, as `nab batch` prints it
+    pub line: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markdown: Option<String>,
+}
+
+/// Progress of a coordinator's job (`GET /status`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueStatus {
+    pub pending: usize,
+    pub leased: usize,
+    pub completed: usize,
+    /// Pages completed by each worker
+    pub workers: BTreeMap<String, usize>,
+}
+
+#[derive(Debug)]
+struct Lease {
+    worker: String,
+    host: String,
+    expires: Instant,
+}
+
+/// URLs waiting, leased, and completed
+#[derive(Debug)]
+pub struct LeaseQueue {
+    pending: VecDeque<String>,
+    leased: HashMap<String, Lease>,
+    per_host: usize,
+    lease: Duration,
+    completed: usize,
+    workers: BTreeMap<String, usize>,
+}
+
+impl LeaseQueue {
+    /// Lease `urls` at most `per_host` at a time per host, across all workers
+    #[must_use]
+    pub fn new(urls: Vec<String>, per_host: usize, lease: Duration) -> Self {
+        Self {
+            pending: urls.into(),
+            leased: HashMap::new(),
+            per_host: per_host.max(1),
+            lease,
+            completed: 0,
+            workers: BTreeMap::new(),
+        }
+    }
+
+    /// Up to `max` URLs for `worker`; expired leases are handed out first
+    pub fn lease(&mut self, worker: &str, max: usize, now: Instant) -> Vec<String> {
+        self.workers.entry(worker.to_string()).or_default();
+        let expired: Vec<String> = self
+            .leased
+            .iter()
+            .filter(|(_, lease)| lease.expires <= now)
+            .map(|(url, _)| url.clone())
+            .collect();
+        for url in expired {
+            if let Some(lease) = self.leased.remove(&url) {
+                debug!("Lease of {url} by {} expired", lease.worker);
+            }
+            self.pending.push_front(url);
+        }
+
+        let mut in_flight: HashMap<String, usize> = HashMap::new();
+        for lease in self.leased.values() {
+            *in_flight.entry(lease.host.clone()).or_default() += 1;
+        }
+        let mut urls = Vec::new();
+        let mut waiting = VecDeque::with_capacity(self.pending.len());
+        while let Some(url) = self.pending.pop_front() {
+            if urls.len() >= max {
+                waiting.push_back(url);
+                break;
+            }
+            let host = host_key(&url);
+            let running = in_flight.entry(host.clone()).or_default();
+            if *running < self.per_host {
+                *running += 1;
+                urls.push((url, host));
+            } else {
+                waiting.push_back(url);
+            }
+        }
+        waiting.append(&mut self.pending);
+        self.pending = waiting;
+
+        urls.into_iter()
+            .map(|(url, host)| {
+                self.leased.insert(
+                    url.clone(),
+                    Lease {
+                        worker: worker.to_string(),
+                        host,
+                        expires: now + self.lease,
+                    },
+                );
+                url
+            })
+            .collect()
+    }
+
+    /// Mark a leased URL completed by `worker`; false if it isn't leased
+    /// (its lease ran out and it went to another worker, or it is done)
+    pub fn complete(&mut self, worker: &str, url: &str) -> bool {
+        if self.leased.remove(url).is_none() {
+            return false;
+        }
+        self.completed += 1;
+        *self.workers.entry(worker.to_string()).or_default() += 1;
+        true
+    }
+
+    /// Nothing pending or leased
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty() && self.leased.is_empty()
+    }
+
+    #[must_use]
+    pub fn status(&self) -> QueueStatus {
+        QueueStatus {
+            pending: self.pending.len(),
+            leased: self.leased.len(),
+            completed: self.completed,
+            workers: self.workers.clone(),
+        }
+    }
+}
+
+struct Shared {
+    queue: LeaseQueue,
+    /// Workers told the job is done
+    released: BTreeSet<String>,
+    results: mpsc::UnboundedSender<WorkResult>,
+}
+
+/// A coordinator serving one job's [`LeaseQueue`]
+pub struct Coordinator {
+    listener: TcpListener,
+    shared: Arc<Mutex<Shared>>,
+    token: Option<Arc<str>>,
+    results: mpsc::UnboundedReceiver<WorkResult>,
+}
+
+impl Coordinator {
+    /// Listen on `addr`; with `token`, requests must carry it as a bearer token
+    ///
+    /// Without a token only loopback addresses are accepted.
+    pub async fn bind(addr: &str, queue: LeaseQueue, token: Option<String>) -> Result<Self> {
+        let token = token.filter(|token| !token.is_empty());
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to listen on {addr}"))?;
+        if token.is_none() && !listener.local_addr()?.ip().is_loopback() {
+            bail!(
+                "Refusing to listen on {addr} without a token: anyone who can reach it could \
+                 lease URLs and post pages. Set --token or NAB_COORDINATOR_TOKEN, or listen on 127.0.0.1"
+            );
+        }
+        let (results, received) = mpsc::unbounded_channel();
+        Ok(Self {
+            listener,
+            shared: Arc::new(Mutex::new(Shared {
+                queue,
+                released: BTreeSet::new(),
+                results,
+            })),
+            token: token.map(Into::into),
+            results: received,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    #[must_use]
+    pub fn status(&self) -> QueueStatus {
+        lock(&self.shared).queue.status()
+    }
+
+    /// Serve until every URL is completed, passing each accepted result to
+    /// `on_result`; then wait briefly for the workers to hear the job is done
+    pub async fn run(&mut self, mut on_result: impl FnMut(WorkResult)) -> Result<()> {
+        let mut finished: Option<Instant> = None;
+        loop {
+            if finished.is_none() && lock(&self.shared).queue.is_done() {
+                finished = Some(Instant::now());
+            }
+            if let Some(at) = finished {
+                let shared = lock(&self.shared);
+                let all_released = shared
+                    .queue
+                    .workers
+                    .keys()
+                    .all(|worker| shared.released.contains(worker));
+                if all_released || at.elapsed() >= DONE_GRACE {
+                    break;
+                }
+            }
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (socket, peer) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!("Coordinator failed to accept a connection: {e}");
+                            tokio::time::sleep(ACCEPT_BACKOFF).await;
+                            continue;
+                        }
+                    };
+                    let (shared, token) = (Arc::clone(&self.shared), self.token.clone());
+                    tokio::spawn(async move {
+                        let served = serve(socket, &shared, token.as_deref());
+                        match tokio::time::timeout(REQUEST_TIMEOUT, served).await {
+                            Ok(Ok(())) => {}
+                            Ok(Err(e)) => debug!("Coordinator request from {peer} failed: {e:#}"),
+                            Err(_) => debug!("Coordinator request from {peer} timed out"),
+                        }
+                    });
+                }
+                Some(result) = self.results.recv() => on_result(result),
+                () = tokio::time::sleep(POLL), if finished.is_some() => {}
+            }
+        }
+        while let Ok(result) = self.results.try_recv() {
+            on_result(result);
+        }
+        Ok(())
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> std::sync::MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+async fn serve(mut socket: TcpStream, shared: &Mutex<Shared>, token: Option<&str>) -> Result<()> {
+    let (method, path, headers, start) = read_head(&mut socket).await?;
+    let authorized = token.is_none_or(|token| {
+        headers
+            .get("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            // Digests compared, so timing says nothing about the token
+            .is_some_and(|given| sha256_hex(given.as_bytes()) == sha256_hex(token.as_bytes()))
+    });
+    let (status, reply) = if authorized {
+        let body = read_body(&mut socket, start, &headers).await?;
+        match respond(&method, &path, &body, shared) {
+            Ok(Some(reply)) => (200, reply),
+            Ok(None) => (404, serde_json::json!({"error": "not found"})),
+            Err(e) => (400, serde_json::json!({"error": format!("{e:#}")})),
+        }
+    } else {
+        (401, serde_json::json!({"error": "missing or wrong token"}))
+    };
+    let body = reply.to_string();
+    let reason = match status {
+        200 => "OK",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Bad Request",
+    };
+    let response = format!(
+        "HTTP/1.1 {status} {reason}\r\ncontent-type: application/json\r\n\
+         content-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await?;
+    Ok(())
+}
+
+fn respond(method: &str, path: &str, body: &[u8], shared: &Mutex<Shared>) -> Result<Option<Value>> {
+    let mut shared = lock(shared);
+    let reply = match (method, path) {
+        ("POST", "/lease") => {
+            let request: LeaseRequest = serde_json::from_slice(body)?;
+            let urls = shared
+                .queue
+                .lease(&request.worker, request.max, Instant::now());
+            let done = shared.queue.is_done();
+            if done {
+                shared.released.insert(request.worker);
+            }
+            serde_json::to_value(LeaseResponse { urls, done })?
+        }
+        ("POST", "/result") => {
+            let result: WorkResult = serde_json::from_slice(body)?;
+            let accepted = shared.queue.complete(&result.worker, &result.url);
+            if accepted {
+                // Sent under the lock, so run() never sees the job done
+                // before it has the last result
+                let _ = shared.results.send(result);
+            }
+            serde_json::json!({ "accepted": accepted })
+        }
+        ("GET", "/status") => serde_json::to_value(shared.queue.status())?,
+        _ => return Ok(None),
+    };
+    Ok(Some(reply))
+}
+
+/// Method, path, lower-cased headers, and the start of the body
+type RequestHead = (String, String, HashMap<String, String>, Vec<u8>);
+
+async fn read_head(socket: &mut TcpStream) -> Result<RequestHead> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 8192];
+    let head_end = loop {
+        if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        if data.len() > MAX_HEAD_BYTES {
+            bail!("Request head too large");
+        }
+        let n = socket.read(&mut buf).await?;
+        if n == 0 {
+            bail!("Connection closed mid-request");
+        }
+        data.extend_from_slice(&buf[..n]);
+    };
+    let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    let start = data.split_off(head_end + 4);
+    Ok((method, path, headers, start))
+}
+
+/// The rest of a body of `content-length` bytes that begins with `body`
+async fn read_body(
+    socket: &mut TcpStream,
+    mut body: Vec<u8>,
+    headers: &HashMap<String, String>,
+) -> Result<Vec<u8>> {
+    let mut buf = [0u8; 8192];
+    let length: usize = headers
+        .get("content-length")
+        .map(|length| length.parse())
+        .transpose()
+        .context("Bad content-length")?
+        .unwrap_or(0);
+    if length > MAX_REQUEST_BYTES {
+        bail!("Request too large");
+    }
+    while body.len() < length {
+        let n = socket.read(&mut buf).await?;
+        if n == 0 {
+            bail!("Connection closed mid-body");
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+    body.truncate(length);
+    Ok(body)
+}
+
+/// A worker's connection to its coordinator
+#[derive(Debug, Clone)]
+pub struct WorkerClient {
+    base: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl WorkerClient {
+    /// Coordinator at `base`, e.g. `http://10.0.0.5:7420`
+    pub fn new(base: &str, token: Option<String>) -> Result<Self> {
+        let base = base.trim_end_matches('/').to_string();
+        url::Url::parse(&base).with_context(|| format!("Invalid coordinator URL {base}"))?;
+        Ok(Self {
+            base,
+            token,
+            http: crate::policy::client_builder()
+                .timeout(Duration::from_secs(60))
+                .build()?,
+        })
+    }
+
+    #[must_use]
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Up to `max` URLs for `worker`
+    pub async fn lease(&self, worker: &str, max: usize) -> Result<LeaseResponse> {
+        let request = LeaseRequest {
+            worker: worker.to_string(),
+            max,
+        };
+        self.post("/lease", &request).await
+    }
+
+    /// Report a fetched page; false if the coordinator no longer wanted it
+    pub async fn report(&self, result: &WorkResult) -> Result<bool> {
+        let reply: Value = self.post("/result", result).await?;
+        Ok(reply["accepted"] == true)
+    }
+
+    async fn post<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<T> {
+        let mut request = self.http.post(format!("{}{path}", self.base)).json(body);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach coordinator {}", self.base))?;
+        let status = response.status();
+        if !status.is_success() {
+            let reply: Value = response.json().await.unwrap_or_default();
+            bail!(
+                "Coordinator answered {status}: {}",
+                reply["error"].as_str().unwrap_or_default()
+            );
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(list: &[&str]) -> Vec<String> {
+        list.iter().map(ToString::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn test_token_is_checked_before_the_body() {
+        let queue = LeaseQueue::new(urls(&["https://a.example/"]), 1, POLL);
+        let mut coordinator = Coordinator::bind("127.0.0.1:0", queue, Some("s3cret".into()))
+            .await
+            .unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let running = tokio::spawn(async move { coordinator.run(|_| {}).await });
+        let exchange = |request: Vec<u8>| async move {
+            let mut socket = TcpStream::connect(addr).await.unwrap();
+            let _ = socket.write_all(&request).await;
+            let mut answer = String::new();
+            let _ = socket.read_to_string(&mut answer).await;
+            answer
+        };
+
+        // Answered without waiting for the announced 60 MB
+        let huge = b"POST /result HTTP/1.1\r\ncontent-length: 60000000\r\n\r\n".to_vec();
+        let answer = tokio::time::timeout(Duration::from_secs(5), exchange(huge))
+            .await
+            .unwrap();
+        assert!(answer.starts_with("HTTP/1.1 401"), "{answer}");
+
+        // A head that never ends is cut off
+        let endless = [b"GET /status HTTP/1.1\r\n".as_slice(), &[b'x'; 64 * 1024]].concat();
+        assert_eq!(exchange(endless).await, "");
+
+        let authorized = b"GET /status HTTP/1.1\r\nauthorization: Bearer s3cret\r\n\r\n".to_vec();
+        assert!(exchange(authorized).await.starts_with("HTTP/1.1 200"));
+        running.abort();
+    }
+
+    #[tokio::test]
+    async fn test_bind_needs_a_token_off_loopback() {
+        let queue = || LeaseQueue::new(Vec::new(), 1, POLL);
+        let open = Coordinator::bind("0.0.0.0:0", queue(), None).await;
+        assert!(open.err().unwrap().to_string().contains("without a token"));
+        assert!(Coordinator::bind("0.0.0.0:0", queue(), Some(String::new()))
+            .await
+            .is_err());
+        assert!(
+            Coordinator::bind("0.0.0.0:0", queue(), Some("s3cret".into()))
+                .await
+                .is_ok()
+        );
+        assert!(Coordinator::bind("127.0.0.1:0", queue(), None)
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_lease_respects_per_host_limit() {
+        let mut queue = LeaseQueue::new(
+            urls(&[
+                "https://a.example/1",
+                "https://a.example/2",
+                "https://b.example/1",
+                "https://a.example/3",
+            ]),
+            1,
+            Duration::from_secs(60),
+        );
+        let now = Instant::now();
+        let leased = queue.lease("w1", 10, now);
+        assert_eq!(
+            leased,
+            urls(&["https://a.example/1", "https://b.example/1"])
+        );
+        // a.example is busy until its URL is reported
+        assert!(queue.lease("w2", 10, now).is_empty());
+        assert!(queue.complete("w1", "https://a.example/1"));
+        assert!(!queue.complete("w1", "https://a.example/1"));
+        assert_eq!(queue.lease("w2", 10, now), urls(&["https://a.example/2"]));
+        assert_eq!(queue.status().pending, 1);
+        assert_eq!(queue.status().workers["w1"], 1);
+    }
+
+    #[test]
+    fn test_expired_leases_go_to_another_worker() {
+        let mut queue = LeaseQueue::new(urls(&["https://a.example/"]), 2, Duration::from_secs(5));
+        let now = Instant::now();
+        assert_eq!(queue.lease("w1", 1, now).len(), 1);
+        assert!(queue.lease("w2", 1, now).is_empty());
+        let later = now + Duration::from_secs(6);
+        assert_eq!(queue.lease("w2", 1, later), urls(&["https://a.example/"]));
+        // w1's late result is refused; w2's counts
+        assert!(queue.complete("w2", "https://a.example/"));
+        assert!(!queue.complete("w1", "https://a.example/"));
+        assert!(queue.is_done());
+    }
+}
@@ -0,0 +1,513 @@
+//! Crawl Frontier
+//!
+//! URL frontier for `nab crawl`:
+//! - Per-host priority queues (shallow, high-scoring links first)
+//! - Politeness: a minimum delay between requests to the same host, plus
+//!   per-host and global concurrency limits
+//! - Persistence: the frontier (queued, in-progress, and seen URLs) is saved
+//!   as JSON so an interrupted crawl resumes where it stopped
+//! - Scope: `regex:`/`glob:` include and exclude filters, checked before a
+//!   URL enters the frontier, with per-filter rejection counts
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::batch::{host_key, ConcurrencyLimits};
+
+/// A URL waiting to be crawled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrontierEntry {
+    pub url: String,
+    /// Link distance from the seed URLs
+    pub depth: u32,
+    /// Link quality estimate from [`link_score`]
+    pub score: f64,
+}
+
+impl FrontierEntry {
+    /// Higher is crawled sooner: each level of depth costs one point of score
+    #[must_use]
+    pub fn priority(&self) -> f64 {
+        self.score - f64::from(self.depth)
+    }
+}
+
+impl Eq for FrontierEntry {}
+
+impl Ord for FrontierEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority()
+            .total_cmp(&other.priority())
+            // Deterministic order for equal priority: shorter, then lexically smaller URLs first
+            .then_with(|| other.url.len().cmp(&self.url.len()))
+            .then_with(|| other.url.cmp(&self.url))
+    }
+}
+
+impl PartialOrd for FrontierEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Crawl frontier with per-host queues and politeness scheduling
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Frontier {
+    /// Maximum link depth from the seeds
+    pub max_depth: u32,
+    /// Minimum time between requests to one host
+    pub politeness_ms: u64,
+    /// Stop dispatching once this many pages are completed or in progress
+    #[serde(default)]
+    pub max_pages: Option<usize>,
+    /// Pages completed so far (across resumes)
+    pub completed: usize,
+    queues: HashMap<String, BinaryHeap<FrontierEntry>>,
+    /// Popped but not yet completed; re-queued when a saved frontier is loaded
+    in_progress: HashMap<String, FrontierEntry>,
+    seen: HashSet<String>,
+    #[serde(skip)]
+    limits: ConcurrencyLimits,
+    #[serde(skip)]
+    next_allowed: HashMap<String, Instant>,
+    #[serde(skip)]
+    in_flight: HashMap<String, usize>,
+}
+
+impl crate::state::Versioned for Frontier {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+impl Frontier {
+    #[must_use]
+    pub fn new(max_depth: u32, politeness: Duration, limits: ConcurrencyLimits) -> Self {
+        Self {
+            max_depth,
+            politeness_ms: u64::try_from(politeness.as_millis()).unwrap_or(u64::MAX),
+            max_pages: None,
+            completed: 0,
+            queues: HashMap::new(),
+            in_progress: HashMap::new(),
+            seen: HashSet::new(),
+            limits,
+            next_allowed: HashMap::new(),
+            in_flight: HashMap::new(),
+        }
+    }
+
+    /// Load a saved frontier; pages that were in progress are queued again
+    pub fn load(path: &Path, limits: ConcurrencyLimits) -> Result<Self> {
+        let mut frontier: Self = crate::state::load(path)?
+            .with_context(|| format!("No crawl state at {}", path.display()))?;
+        frontier.limits = limits;
+        for (_, entry) in std::mem::take(&mut frontier.in_progress) {
+            frontier.enqueue(entry);
+        }
+        Ok(frontier)
+    }
+
+    /// Save the frontier (atomically, see [`crate::state`])
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::state::save(path, self)
+            .with_context(|| format!("Failed to save crawl state {}", path.display()))
+    }
+
+    /// Queue a URL unless it was seen before or is too deep; returns true if queued
+    pub fn push(&mut self, url: &str, depth: u32, score: f64) -> bool {
+        let url = normalize_url(url);
+        if depth > self.max_depth || !self.seen.insert(url.clone()) {
+            return false;
+        }
+        self.enqueue(FrontierEntry { url, depth, score });
+        true
+    }
+
+    fn enqueue(&mut self, entry: FrontierEntry) {
+        self.queues
+            .entry(host_key(&entry.url))
+            .or_default()
+            .push(entry);
+    }
+
+    /// Best URL whose host is past its politeness delay and under its concurrency limit
+    pub fn pop_ready(&mut self, now: Instant) -> Option<FrontierEntry> {
+        if self.in_progress.len() >= self.limits.global.max(1) || self.limit_reached() {
+            return None;
+        }
+        let host = self
+            .queues
+            .iter()
+            .filter(|(host, _)| self.host_ready(host, now))
+            .filter_map(|(host, queue)| queue.peek().map(|top| (host, top)))
+            .max_by(|a, b| a.1.cmp(b.1))
+            .map(|(host, _)| host.clone())?;
+
+        let queue = self.queues.get_mut(&host)?;
+        let entry = queue.pop()?;
+        if queue.is_empty() {
+            self.queues.remove(&host);
+        }
+        self.next_allowed.insert(
+            host.clone(),
+            now + Duration::from_millis(self.politeness_ms),
+        );
+        *self.in_flight.entry(host).or_default() += 1;
+        self.in_progress.insert(entry.url.clone(), entry.clone());
+        Some(entry)
+    }
+
+    fn host_ready(&self, host: &str, now: Instant) -> bool {
+        self.in_flight.get(host).copied().unwrap_or(0) < self.limits.per_host.max(1)
+            && self.next_allowed.get(host).is_none_or(|t| *t <= now)
+    }
+
+    /// Mark a popped URL as crawled
+    pub fn complete(&mut self, url: &str) {
+        if self.in_progress.remove(url).is_some() {
+            self.completed += 1;
+        }
+        let host = host_key(url);
+        if let Some(n) = self.in_flight.get_mut(&host) {
+            *n = n.saturating_sub(1);
+        }
+    }
+
+    /// How long until some queued host becomes ready (`None` if nothing is queued)
+    #[must_use]
+    pub fn next_wake(&self, now: Instant) -> Option<Duration> {
+        self.queues
+            .keys()
+            .map(|host| {
+                self.next_allowed
+                    .get(host)
+                    .map_or(Duration::ZERO, |t| t.saturating_duration_since(now))
+            })
+            .min()
+    }
+
+    /// URLs waiting in the queues
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.queues.values().map(BinaryHeap::len).sum()
+    }
+
+    /// URLs popped but not completed
+    #[must_use]
+    pub fn in_progress(&self) -> usize {
+        self.in_progress.len()
+    }
+
+    /// Distinct URLs ever queued
+    #[must_use]
+    pub fn seen(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Nothing queued and nothing in progress
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.queues.is_empty() && self.in_progress.is_empty()
+    }
+
+    /// `max_pages` completed or in progress
+    #[must_use]
+    pub fn limit_reached(&self) -> bool {
+        self.max_pages
+            .is_some_and(|max| self.completed + self.in_progress.len() >= max)
+    }
+}
+
+/// URL pattern from the command line: `regex:PATTERN` or `glob:PATTERN` (bare = glob)
+#[derive(Debug, Clone)]
+pub struct UrlFilter {
+    spec: String,
+    regex: Regex,
+}
+
+impl UrlFilter {
+    /// The filter as given (`regex:...` / `glob:...`)
+    #[must_use]
+    pub fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    #[must_use]
+    pub fn matches(&self, url: &str) -> bool {
+        self.regex.is_match(url)
+    }
+}
+
+impl FromStr for UrlFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let regex = if let Some(pattern) = spec.strip_prefix("regex:") {
+            Regex::new(pattern).with_context(|| format!("Invalid regex filter '{pattern}'"))?
+        } else {
+            let pattern = spec.strip_prefix("glob:").unwrap_or(spec);
+            Regex::new(&glob_to_regex(pattern))?
+        };
+        Ok(Self {
+            spec: spec.to_string(),
+            regex,
+        })
+    }
+}
+
+/// Anchored regex for a glob: `*` matches anything (including `/`), `?` one character
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    out.push('$');
+    out
+}
+
+/// Which discovered URLs may enter the frontier
+///
+/// Excludes always win. With include filters, a URL must match one of them;
+/// without, it must be on one of the seed hosts.
+#[derive(Debug, Clone, Default)]
+pub struct CrawlScope {
+    include: Vec<UrlFilter>,
+    exclude: Vec<UrlFilter>,
+    seed_hosts: HashSet<String>,
+    rejected: HashMap<String, usize>,
+}
+
+/// Scope summary for the crawl manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct ScopeReport {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Seed hosts (the scope when no include filters are given)
+    pub seed_hosts: Vec<String>,
+    /// Rejected URL count per filter (`off-site` / `not-included` for scope misses,
+    /// `policy` for hosts the domain policy refuses)
+    pub rejected: HashMap<String, usize>,
+}
+
+impl CrawlScope {
+    #[must_use]
+    pub fn new(seeds: &[String], include: Vec<UrlFilter>, exclude: Vec<UrlFilter>) -> Self {
+        Self {
+            include,
+            exclude,
+            seed_hosts: seeds.iter().map(|s| host_key(s)).collect(),
+            rejected: HashMap::new(),
+        }
+    }
+
+    /// Check a URL, counting the reason if it's rejected
+    pub fn allows(&mut self, url: &str) -> bool {
+        let reason = if !crate::policy::allows(url) {
+            "policy".to_string()
+        } else if let Some(filter) = self.exclude.iter().find(|f| f.matches(url)) {
+            filter.spec.clone()
+        } else if self.include.is_empty() {
+            if self.seed_hosts.contains(&host_key(url)) {
+                return true;
+            }
+            "off-site".to_string()
+        } else if self.include.iter().any(|f| f.matches(url)) {
+            return true;
+        } else {
+            "not-included".to_string()
+        };
+        *self.rejected.entry(reason).or_default() += 1;
+        false
+    }
+
+    #[must_use]
+    pub fn report(&self) -> ScopeReport {
+        let mut seed_hosts: Vec<String> = self.seed_hosts.iter().cloned().collect();
+        seed_hosts.sort();
+        ScopeReport {
+            include: self.include.iter().map(|f| f.spec.clone()).collect(),
+            exclude: self.exclude.iter().map(|f| f.spec.clone()).collect(),
+            seed_hosts,
+            rejected: self.rejected.clone(),
+        }
+    }
+}
+
+/// Drop the fragment so `page#a` and `page#b` count as one URL
+#[must_use]
+pub fn normalize_url(url: &str) -> String {
+    url::Url::parse(url).map_or_else(
+        |_| url.to_string(),
+        |mut u| {
+            u.set_fragment(None);
+            u.to_string()
+        },
+    )
+}
+
+/// Score a discovered link (about 0.0-1.5, higher is more likely useful content)
+///
+/// Prefers descriptive anchor text and short, query-free paths; penalizes
+/// pagination, sorting, and other parameterized URLs.
+#[must_use]
+pub fn link_score(url: &url::Url, anchor_text: &str) -> f64 {
+    let mut score = 1.0;
+
+    let words = anchor_text.split_whitespace().count();
+    if words >= 3 {
+        score += 0.3;
+    } else if words == 0 {
+        score -= 0.2;
+    }
+
+    if let Some(query) = url.query() {
+        score -= 0.3 + 0.1 * query.matches('&').count() as f64;
+    }
+    let segments = url
+        .path_segments()
+        .map_or(0, |s| s.filter(|s| !s.is_empty()).count());
+    if segments > 3 {
+        score -= 0.1 * (segments - 3) as f64;
+    }
+    let path = url.path().to_lowercase();
+    if ["/page/", "/tag/", "/sort", "/print", "/share"]
+        .iter()
+        .any(|p| path.contains(p))
+    {
+        score -= 0.4;
+    }
+    (score * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frontier(politeness_ms: u64) -> Frontier {
+        Frontier::new(
+            2,
+            Duration::from_millis(politeness_ms),
+            ConcurrencyLimits::new(1, 8),
+        )
+    }
+
+    #[test]
+    fn test_priority_and_dedup() {
+        let mut f = frontier(0);
+        assert!(f.push("https://a.example/", 0, 1.0));
+        assert!(!f.push("https://a.example/#top", 0, 1.0));
+        assert!(f.push("https://b.example/deep", 1, 1.0));
+        assert!(f.push("https://b.example/great", 1, 1.5));
+        assert!(!f.push("https://b.example/too-deep", 3, 1.0));
+
+        let now = Instant::now();
+        assert_eq!(f.pop_ready(now).unwrap().url, "https://a.example/");
+        // b.example's best entry wins; its second entry waits for the per-host limit
+        assert_eq!(f.pop_ready(now).unwrap().url, "https://b.example/great");
+        assert!(f.pop_ready(now).is_none());
+
+        f.complete("https://b.example/great");
+        assert_eq!(f.pop_ready(now).unwrap().url, "https://b.example/deep");
+        assert_eq!(f.completed, 1);
+        assert_eq!(f.in_progress(), 2);
+    }
+
+    #[test]
+    fn test_politeness_delay() {
+        let mut f = frontier(1_000);
+        f.push("https://a.example/1", 0, 1.0);
+        f.push("https://a.example/2", 0, 1.0);
+
+        let now = Instant::now();
+        let first = f.pop_ready(now).unwrap();
+        f.complete(&first.url);
+        // Same host is blocked until the delay passes
+        assert!(f.pop_ready(now).is_none());
+        let wait = f.next_wake(now).unwrap();
+        assert!(wait > Duration::from_millis(900));
+        assert!(f.pop_ready(now + wait).is_some());
+    }
+
+    #[test]
+    fn test_save_and_resume() {
+        let dir = std::env::temp_dir().join(format!("nab-frontier-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+
+        let mut f = frontier(0);
+        f.push("https://a.example/1", 0, 1.0);
+        f.push("https://a.example/2", 1, 1.0);
+        let popped = f.pop_ready(Instant::now()).unwrap();
+        f.save(&path).unwrap();
+
+        let mut resumed = Frontier::load(&path, ConcurrencyLimits::new(1, 8)).unwrap();
+        // The interrupted page is queued again; seen URLs stay deduplicated
+        assert_eq!(resumed.queued(), 2);
+        assert_eq!(resumed.in_progress(), 0);
+        assert!(!resumed.push(&popped.url, 0, 1.0));
+        assert_eq!(resumed.pop_ready(Instant::now()).unwrap().url, popped.url);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_pages() {
+        let mut f = frontier(0);
+        f.max_pages = Some(1);
+        f.push("https://a.example/1", 0, 1.0);
+        f.push("https://b.example/1", 0, 1.0);
+        let first = f.pop_ready(Instant::now()).unwrap();
+        assert!(f.pop_ready(Instant::now()).is_none());
+        f.complete(&first.url);
+        assert!(f.limit_reached());
+        assert_eq!(f.queued(), 1);
+    }
+
+    #[test]
+    fn test_scope_filters() {
+        let seeds = vec!["https://docs.example.com/".to_string()];
+        let filter = |s: &str| s.parse::<UrlFilter>().unwrap();
+
+        let mut scope = CrawlScope::new(&seeds, Vec::new(), vec![filter("glob:*/tag/*")]);
+        assert!(scope.allows("https://docs.example.com/guide"));
+        assert!(!scope.allows("https://docs.example.com/tag/rust"));
+        assert!(!scope.allows("https://other.example/"));
+
+        let mut scope = CrawlScope::new(
+            &seeds,
+            vec![
+                filter(r"regex:^https://docs\.example\.com/"),
+                filter("blog.example.com/*"),
+            ],
+            vec![filter(r"regex:[?&]page=")],
+        );
+        assert!(scope.allows("https://docs.example.com/a"));
+        assert!(!scope.allows("https://docs.example.com/a?page=2"));
+        assert!(!scope.allows("https://cdn.example.com/x.js"));
+        // Bare patterns are globs and anchored
+        assert!(!scope.allows("https://blog.example.com/post"));
+
+        let report = scope.report();
+        assert_eq!(report.rejected["not-included"], 2);
+        assert_eq!(report.rejected[r"regex:[?&]page="], 1);
+        assert!("regex:(".parse::<UrlFilter>().is_err());
+    }
+
+    #[test]
+    fn test_link_score() {
+        let article = url::Url::parse("https://example.com/blog/post").unwrap();
+        let listing = url::Url::parse("https://example.com/blog/page/2?sort=new&x=1").unwrap();
+        assert!(link_score(&article, "How we cut latency in half") > link_score(&listing, "2"));
+        assert_eq!(link_score(&article, "Post"), 1.0);
+    }
+}
@@ -0,0 +1,695 @@
+//! cURL Commands
+//!
+//! `nab curl "<command>"` runs a pasted curl command (a browser's "Copy as
+//! cURL", or a line from an existing script) through nab's client: its
+//! method, headers, cookies, body, credentials, and connection flags become
+//! the equivalent `nab fetch` options. The other way round, `nab fetch
+//! --emit-curl` prints the request nab would send as a curl command.
+//!
+//! As in curl, redirects are only followed with `-L`, and `-d` sends a form
+//! unless a `Content-Type` header says otherwise. Cookie jars, uploads
+//! (`-F`, `-T`), and output options aren't supported.
+
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::http_client::HttpVersion;
+
+/// Options that change nothing nab does (progress, verbosity, protocol hints)
+const IGNORED_FLAGS: &[&str] = &[
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-v",
+    "--verbose",
+    "-i",
+    "--include",
+    "-f",
+    "--fail",
+    "--fail-with-body",
+    "-g",
+    "--globoff",
+    "-N",
+    "--no-buffer",
+    "-#",
+    "--progress-bar",
+    "--compressed",
+    "--http2",
+    "--http3",
+    "--path-as-is",
+    "--anyauth",
+    "--basic",
+    "--digest",
+    "--ntlm",
+];
+
+/// Ignored options that take a value
+const IGNORED_WITH_VALUE: &[&str] = &[
+    "--connect-timeout",
+    "--max-redirs",
+    "-c",
+    "--cookie-jar",
+    "-w",
+    "--write-out",
+];
+
+/// A curl request, as parsed from a command or built from a nab request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CurlCommand {
+    pub url: String,
+    /// Explicit method; `None` means GET, or POST when there's a body
+    pub method: Option<String>,
+    /// Headers in order, cookies included
+    pub headers: Vec<(String, String)>,
+    pub data: Option<String>,
+    /// `USER:PASSWORD`
+    pub user: Option<String>,
+    pub insecure: bool,
+    pub proxy: Option<String>,
+    pub follow_redirects: bool,
+    pub max_time: Option<Duration>,
+    pub retries: Option<u32>,
+    pub cert: Option<String>,
+    pub key: Option<String>,
+    pub cacert: Option<String>,
+    /// `--http1.1` or `--http2-prior-knowledge`
+    pub http: Option<HttpVersion>,
+}
+
+impl CurlCommand {
+    /// Parse a curl command line (POSIX shell quoting, `$'...'` included)
+    pub fn parse(command: &str) -> Result<Self> {
+        let words = shell_words(command)?;
+        let mut args = words.into_iter().peekable();
+        if args
+            .peek()
+            .is_some_and(|w| w == "curl" || w.ends_with("/curl"))
+        {
+            args.next();
+        }
+
+        let mut curl = Self::default();
+        let mut data: Vec<String> = Vec::new();
+        let mut json = false;
+        let mut head = false;
+        let mut get = false;
+        while let Some(arg) = args.next() {
+            let (option, attached) = split_option(&arg);
+            let Some(option) = option else {
+                if curl.url.is_empty() {
+                    curl.url = arg;
+                    continue;
+                }
+                anyhow::bail!("Only one URL is supported, got {} and {arg}", curl.url);
+            };
+            if let (false, Some(bundled)) = (takes_value(option), &attached) {
+                // Bundled short flags: -sSL
+                curl.flag(option, &mut head, &mut get)?;
+                for c in bundled.chars() {
+                    let flag = format!("-{c}");
+                    if takes_value(&flag) {
+                        anyhow::bail!("Give {flag} separately, not bundled in {arg}");
+                    }
+                    curl.flag(&flag, &mut head, &mut get)?;
+                }
+                continue;
+            }
+            if !takes_value(option) {
+                curl.flag(option, &mut head, &mut get)?;
+                continue;
+            }
+            let value = match attached {
+                Some(value) => value,
+                None => args
+                    .next()
+                    .with_context(|| format!("curl option {option} needs a value"))?,
+            };
+            match option {
+                "-X" | "--request" => curl.method = Some(value.to_uppercase()),
+                "-H" | "--header" => curl.header(&value),
+                "-b" | "--cookie" => {
+                    if !value.contains('=') {
+                        anyhow::bail!("Cookie files aren't supported (-b {value})");
+                    }
+                    curl.headers.push(("Cookie".to_string(), value));
+                }
+                "-A" | "--user-agent" => curl.headers.push(("User-Agent".to_string(), value)),
+                "-e" | "--referer" => curl.headers.push(("Referer".to_string(), value)),
+                "-u" | "--user" => curl.user = Some(value),
+                "-x" | "--proxy" => curl.proxy = Some(value),
+                "-m" | "--max-time" => {
+                    let secs: f64 = value
+                        .parse()
+                        .with_context(|| format!("Invalid --max-time {value}"))?;
+                    curl.max_time = Some(Duration::try_from_secs_f64(secs)?);
+                }
+                "--retry" => {
+                    curl.retries = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid --retry {value}"))?,
+                    );
+                }
+                "-E" | "--cert" => curl.cert = Some(value),
+                "--key" => curl.key = Some(value),
+                "--cacert" => curl.cacert = Some(value),
+                "--url" => curl.url = value,
+                "-d" | "--data" | "--data-ascii" => {
+                    data.push(read_data(&value)?.replace(['\r', '\n'], ""));
+                }
+                "--data-binary" => data.push(read_data(&value)?),
+                "--data-raw" => data.push(value),
+                "--data-urlencode" => data.push(urlencode_data(&value)?),
+                "--json" => {
+                    json = true;
+                    // --json parts are joined as they are, not with '&'
+                    let part = read_data(&value)?;
+                    match data.last_mut() {
+                        Some(last) => last.push_str(&part),
+                        None => data.push(part),
+                    }
+                }
+                _ if IGNORED_WITH_VALUE.contains(&option) => {}
+                _ => anyhow::bail!("Unsupported curl option {option}"),
+            }
+        }
+        if curl.url.is_empty() {
+            anyhow::bail!("No URL in the curl command");
+        }
+
+        let body = (!data.is_empty()).then(|| data.join("&"));
+        if get {
+            if let Some(query) = body {
+                let separator = if curl.url.contains('?') { '&' } else { '?' };
+                curl.url = format!("{}{separator}{query}", curl.url);
+            }
+        } else if let Some(body) = body {
+            if json {
+                curl.default_header("Content-Type", "application/json");
+                curl.default_header("Accept", "application/json");
+            } else {
+                curl.default_header("Content-Type", "application/x-www-form-urlencoded");
+            }
+            curl.data = Some(body);
+        }
+        if head && curl.method.is_none() {
+            curl.method = Some("HEAD".to_string());
+        }
+        Ok(curl)
+    }
+
+    fn flag(&mut self, flag: &str, head: &mut bool, get: &mut bool) -> Result<()> {
+        match flag {
+            "-L" | "--location" => self.follow_redirects = true,
+            "-k" | "--insecure" => self.insecure = true,
+            "-I" | "--head" => *head = true,
+            "-G" | "--get" => *get = true,
+            "--http1.1" => self.http = Some(HttpVersion::Http1),
+            "--http2-prior-knowledge" => self.http = Some(HttpVersion::Http2),
+            _ if IGNORED_FLAGS.contains(&flag) => {}
+            _ => anyhow::bail!("Unsupported curl option {flag}"),
+        }
+        Ok(())
+    }
+
+    /// `-H` value: `Name: value`, `Name;` for an empty value, `Name:` to drop a header
+    fn header(&mut self, header: &str) {
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if !value.is_empty() {
+                self.headers
+                    .push((name.trim().to_string(), value.to_string()));
+            }
+        } else if let Some(name) = header.strip_suffix(';') {
+            self.headers.push((name.trim().to_string(), String::new()));
+        }
+    }
+
+    fn default_header(&mut self, name: &str, value: &str) {
+        if !self.has_header(name) {
+            self.headers.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    fn has_header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+
+    /// Arguments for `nab fetch` sending the same request
+    ///
+    /// Browser cookies are off: the command carries the cookies it means to send.
+    #[must_use]
+    pub fn fetch_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "fetch".to_string(),
+            self.url.clone(),
+            "--cookies".to_string(),
+            "none".to_string(),
+        ];
+        let method = self
+            .method
+            .clone()
+            .unwrap_or_else(|| if self.data.is_some() { "POST" } else { "GET" }.to_string());
+        if method != "GET" {
+            args.extend(["-X".to_string(), method]);
+        }
+        for (name, value) in &self.headers {
+            args.extend(["--add-header".to_string(), format!("{name}: {value}")]);
+        }
+        if let Some(data) = &self.data {
+            args.extend(["-d".to_string(), data.clone()]);
+        }
+        if let Some(user) = &self.user {
+            args.extend(["-u".to_string(), user.clone()]);
+        }
+        if self.insecure {
+            args.push("--insecure".to_string());
+        }
+        if let Some(proxy) = &self.proxy {
+            args.extend(["--proxy".to_string(), proxy.clone()]);
+        }
+        if !self.follow_redirects {
+            args.push("--no-redirect".to_string());
+        }
+        if let Some(max_time) = self.max_time {
+            let secs = max_time.as_secs() + u64::from(max_time.subsec_nanos() > 0);
+            args.extend(["--timeout".to_string(), secs.max(1).to_string()]);
+        }
+        if let Some(retries) = self.retries {
+            args.extend(["--retries".to_string(), retries.to_string()]);
+        }
+        for (flag, path) in [
+            ("--cert", &self.cert),
+            ("--key", &self.key),
+            ("--cacert", &self.cacert),
+        ] {
+            if let Some(path) = path {
+                args.extend([flag.to_string(), path.clone()]);
+            }
+        }
+        if let Some(http) = self.http {
+            args.extend(["--http".to_string(), http.to_string()]);
+        }
+        args
+    }
+
+    /// This command's connection settings with `request`'s URL, method,
+    /// headers (over the client's `defaults`), and body
+    #[must_use]
+    pub fn with_request(
+        mut self,
+        request: &reqwest::Request,
+        defaults: &reqwest::header::HeaderMap,
+    ) -> Self {
+        let mut headers = defaults.clone();
+        headers.extend(request.headers().clone());
+        self.url = request.url().to_string();
+        self.headers = headers
+            .iter()
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                (name.as_str().to_string(), value)
+            })
+            .collect();
+        self.data = request
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .map(|body| String::from_utf8_lossy(body).into_owned());
+        self.method = match request.method().as_str() {
+            "GET" => None,
+            "POST" if self.data.is_some() => None,
+            method => Some(method.to_string()),
+        };
+        self
+    }
+}
+
+impl fmt::Display for CurlCommand {
+    /// One option per line, quoted for a POSIX shell
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![format!("curl {}", quote(&self.url))];
+        match self.method.as_deref() {
+            Some("HEAD") => parts.push("-I".to_string()),
+            Some(method) => parts.push(format!("-X {method}")),
+            None => {}
+        }
+        for (name, value) in &self.headers {
+            parts.push(if value.is_empty() {
+                format!("-H {}", quote(&format!("{name};")))
+            } else {
+                format!("-H {}", quote(&format!("{name}: {value}")))
+            });
+        }
+        if let Some(data) = &self.data {
+            parts.push(format!("--data-raw {}", quote(data)));
+        }
+        if let Some(user) = &self.user {
+            parts.push(format!("-u {} --anyauth", quote(user)));
+        }
+        if self.insecure {
+            parts.push("-k".to_string());
+        }
+        if let Some(proxy) = &self.proxy {
+            parts.push(format!("-x {}", quote(proxy)));
+        }
+        if self.follow_redirects {
+            parts.push("-L".to_string());
+        }
+        if let Some(max_time) = self.max_time {
+            parts.push(format!("--max-time {}", max_time.as_secs_f64()));
+        }
+        if let Some(retries) = self.retries.filter(|&r| r > 0) {
+            parts.push(format!("--retry {retries}"));
+        }
+        for (flag, path) in [
+            ("--cert", &self.cert),
+            ("--key", &self.key),
+            ("--cacert", &self.cacert),
+        ] {
+            if let Some(path) = path {
+                parts.push(format!("{flag} {}", quote(path)));
+            }
+        }
+        match self.http {
+            Some(HttpVersion::Http1) => parts.push("--http1.1".to_string()),
+            Some(HttpVersion::Http2) => parts.push("--http2-prior-knowledge".to_string()),
+            Some(HttpVersion::Http3) => parts.push("--http3".to_string()),
+            Some(HttpVersion::Auto) | None => {}
+        }
+        if self.has_header("Accept-Encoding") {
+            parts.push("--compressed".to_string());
+        }
+        f.write_str(&parts.join(" \\\n  "))
+    }
+}
+
+/// Option name and attached value: `-XPOST` → (`-X`, `POST`); `None` for a non-option
+fn split_option(arg: &str) -> (Option<&str>, Option<String>) {
+    if arg.starts_with("--") || arg.len() < 2 || !arg.starts_with('-') {
+        return (arg.starts_with("--").then_some(arg), None);
+    }
+    let (option, rest) = arg.split_at(2);
+    (Some(option), (!rest.is_empty()).then(|| rest.to_string()))
+}
+
+fn takes_value(option: &str) -> bool {
+    matches!(
+        option,
+        "-X" | "--request"
+            | "-H"
+            | "--header"
+            | "-b"
+            | "--cookie"
+            | "-A"
+            | "--user-agent"
+            | "-e"
+            | "--referer"
+            | "-u"
+            | "--user"
+            | "-x"
+            | "--proxy"
+            | "-m"
+            | "--max-time"
+            | "--retry"
+            | "-E"
+            | "--cert"
+            | "--key"
+            | "--cacert"
+            | "--url"
+            | "-d"
+            | "--data"
+            | "--data-ascii"
+            | "--data-binary"
+            | "--data-raw"
+            | "--data-urlencode"
+            | "--json"
+            | "-F"
+            | "--form"
+            | "-T"
+            | "--upload-file"
+            | "-o"
+            | "--output"
+    ) || IGNORED_WITH_VALUE.contains(&option)
+}
+
+/// `@file` reads the file (`-` for stdin), anything else is the data itself
+fn read_data(value: &str) -> Result<String> {
+    match value.strip_prefix('@') {
+        Some("-") => Ok(std::io::read_to_string(std::io::stdin())?),
+        Some(path) => {
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))
+        }
+        None => Ok(value.to_string()),
+    }
+}
+
+/// `--data-urlencode`: `content`, `=content`, `name=content`, `@file`, or `name@file`
+fn urlencode_data(value: &str) -> Result<String> {
+    let encode =
+        |text: &str| url::form_urlencoded::byte_serialize(text.as_bytes()).collect::<String>();
+    if let Some(content) = value.strip_prefix('=') {
+        return Ok(encode(content));
+    }
+    if let Some((name, content)) = value.split_once('=') {
+        return Ok(format!("{name}={}", encode(content)));
+    }
+    if let Some((name, path)) = value.split_once('@') {
+        let content = read_data(&format!("@{path}"))?;
+        return Ok(if name.is_empty() {
+            encode(&content)
+        } else {
+            format!("{name}={}", encode(&content))
+        });
+    }
+    Ok(encode(value))
+}
+
+/// Quote `text` for a POSIX shell
+fn quote(text: &str) -> String {
+    if !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c))
+    {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Split a command line into words like a POSIX shell, without expansions
+pub fn shell_words(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\\' => match chars.next() {
+                // Line continuation
+                Some('\n') => {}
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some(escaped) => {
+                    word.push(escaped);
+                    in_word = true;
+                }
+                None => word.push('\\'),
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => anyhow::bail!("Unterminated ' quote"),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => anyhow::bail!("Unterminated \" quote"),
+                        },
+                        Some(c) => word.push(c),
+                        None => anyhow::bail!("Unterminated \" quote"),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                in_word = true;
+                ansi_c_quoted(&mut chars, &mut word)?;
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Body of a `$'...'` string, as browsers write bodies with quotes or newlines
+fn ansi_c_quoted(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    word: &mut String,
+) -> Result<()> {
+    loop {
+        match chars.next() {
+            Some('\'') => return Ok(()),
+            Some('\\') => {
+                let escaped = chars.next().context("Unterminated $' quote")?;
+                match escaped {
+                    'n' => word.push('\n'),
+                    't' => word.push('\t'),
+                    'r' => word.push('\r'),
+                    '0' => word.push('\0'),
+                    'x' | 'u' | 'U' => {
+                        let digits = match escaped {
+                            'x' => 2,
+                            'u' => 4,
+                            _ => 8,
+                        };
+                        let mut hex = String::new();
+                        while hex.len() < digits
+                            && chars.peek().is_some_and(char::is_ascii_hexdigit)
+                        {
+                            hex.extend(chars.next());
+                        }
+                        let code = u32::from_str_radix(&hex, 16)
+                            .with_context(|| format!("Invalid \\{escaped} escape"))?;
+                        word.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    other => word.push(other),
+                }
+            }
+            Some(c) => word.push(c),
+            None => anyhow::bail!("Unterminated $' quote"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_browser_copy() {
+        let command = r#"curl 'https://shop.example.com/api/cart?x=1' \
+  -H 'accept: application/json' \
+  -H 'content-type: application/json' \
+  -b 'session=abc; theme=dark' \
+  --data-raw $'{"note":"it\'s here\\n"}' \
+  --compressed"#;
+        let curl = CurlCommand::parse(command).unwrap();
+        assert_eq!(curl.url, "https://shop.example.com/api/cart?x=1");
+        assert_eq!(curl.data.as_deref(), Some("{\"note\":\"it's here\\n\"}"));
+        assert_eq!(
+            curl.headers,
+            vec![
+                ("accept".to_string(), "application/json".to_string()),
+                ("content-type".to_string(), "application/json".to_string()),
+                ("Cookie".to_string(), "session=abc; theme=dark".to_string()),
+            ]
+        );
+        assert!(!curl.follow_redirects);
+
+        let args = curl.fetch_args();
+        assert_eq!(args[..4], ["fetch", curl.url.as_str(), "--cookies", "none"]);
+        let joined = args.join(" ");
+        assert!(joined.contains("-X POST"), "{joined}");
+        assert!(
+            joined.contains("--add-header Cookie: session=abc"),
+            "{joined}"
+        );
+        assert!(joined.ends_with("--no-redirect"), "{joined}");
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let curl = CurlCommand::parse(
+            "curl -sSLk -XPUT -u ada:pw -d a=1 -d 'b=2 3' --max-time 2.5 --http1.1 http://x.test/",
+        )
+        .unwrap();
+        assert_eq!(curl.method.as_deref(), Some("PUT"));
+        assert_eq!(curl.http, Some(HttpVersion::Http1));
+        assert!(curl
+            .fetch_args()
+            .ends_with(&["--http".to_string(), "1".to_string()]));
+        assert_eq!(curl.data.as_deref(), Some("a=1&b=2 3"));
+        assert_eq!(curl.user.as_deref(), Some("ada:pw"));
+        assert!(curl.follow_redirects && curl.insecure);
+        assert_eq!(curl.max_time, Some(Duration::from_millis(2500)));
+        assert!(curl.headers.contains(&(
+            "Content-Type".to_string(),
+            "application/x-www-form-urlencoded".to_string()
+        )));
+
+        let get =
+            CurlCommand::parse("curl -G --data-urlencode 'q=a b&c' http://x.test/s?l=en").unwrap();
+        assert_eq!(get.url, "http://x.test/s?l=en&q=a+b%26c");
+        assert_eq!(get.data, None);
+        assert_eq!(
+            get.fetch_args()[..2],
+            ["fetch", "http://x.test/s?l=en&q=a+b%26c"]
+        );
+
+        assert!(CurlCommand::parse("curl -F file=@a.txt http://x.test/").is_err());
+        assert!(CurlCommand::parse("curl -b cookies.txt http://x.test/").is_err());
+        assert!(CurlCommand::parse("curl -s").is_err());
+        assert!(CurlCommand::parse("curl 'http://x.test/").is_err());
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let curl = CurlCommand {
+            url: "https://api.example.com/items".to_string(),
+            method: Some("PATCH".to_string()),
+            headers: vec![
+                ("accept-encoding".to_string(), "gzip, br".to_string()),
+                ("x-note".to_string(), "it's".to_string()),
+            ],
+            data: Some("{\"a\": 1}".to_string()),
+            insecure: true,
+            follow_redirects: true,
+            max_time: Some(Duration::from_secs(10)),
+            ..CurlCommand::default()
+        };
+        let text = curl.to_string();
+        assert!(text.starts_with("curl https://api.example.com/items \\\n  -X PATCH"));
+        assert!(text.contains(r"-H 'x-note: it'\''s'"), "{text}");
+        assert!(text.ends_with("--compressed"), "{text}");
+
+        let parsed = CurlCommand::parse(&text).unwrap();
+        assert_eq!(parsed.url, curl.url);
+        assert_eq!(parsed.method, curl.method);
+        assert_eq!(parsed.data, curl.data);
+        assert_eq!(parsed.headers[..2], curl.headers[..]);
+        assert_eq!(parsed.max_time, curl.max_time);
+        assert!(parsed.insecure && parsed.follow_redirects);
+    }
+}
@@ -0,0 +1,115 @@
+//! Command Deadline (`--deadline`)
+//!
+//! `nab --deadline 10m crawl ...` bounds a whole command, on top of each
+//! request's own timeout, so a scheduled job never overruns its window:
+//!
+//! - `crawl` and `batch` stop starting requests at the deadline, save their
+//!   progress (the crawl `--state` and `--manifest`, the batch `--resume-job`
+//!   manifest), and print their summary
+//! - `stream` records until the deadline at the latest
+//! - any command still running [`GRACE`] after the deadline is stopped
+//!
+//! A command cut short exits with status [`EXIT_CODE`], like `timeout(1)`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+
+/// Time past the deadline a command gets to save its state before it's stopped
+pub const GRACE: Duration = Duration::from_secs(10);
+
+/// Exit status of a command cut short by its deadline
+pub const EXIT_CODE: i32 = 124;
+
+/// When the command must end
+static DEADLINE: OnceLock<Instant> = OnceLock::new();
+
+/// Whether a command stopped early because the deadline passed
+static CUT_SHORT: AtomicBool = AtomicBool::new(false);
+
+/// Parse `90`, `90s`, `10m`, `1h30m`, or `500ms`
+pub fn parse(spec: &str) -> Result<Duration> {
+    let spec = spec.trim().to_ascii_lowercase();
+    let mut total = Duration::ZERO;
+    let mut rest = spec.as_str();
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let Ok(number) = rest[..digits].parse::<u64>() else {
+            bail!("Invalid duration '{spec}', expected e.g. 90s, 10m, or 1h30m");
+        };
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        total += match &rest[..unit_len] {
+            "ms" => Duration::from_millis(number),
+            "s" | "" => Duration::from_secs(number),
+            "m" => Duration::from_secs(number * 60),
+            "h" => Duration::from_secs(number * 3600),
+            _ => bail!("Invalid duration '{spec}', expected e.g. 90s, 10m, or 1h30m"),
+        };
+        rest = &rest[unit_len..];
+    }
+    if total.is_zero() {
+        bail!("The deadline must be longer than zero");
+    }
+    Ok(total)
+}
+
+/// End the command `after` from now (the first call wins)
+pub fn set(after: Duration) {
+    let _ = DEADLINE.set(Instant::now() + after);
+}
+
+/// The deadline, if one is set
+#[must_use]
+pub fn get() -> Option<Instant> {
+    DEADLINE.get().copied()
+}
+
+/// Time left until the deadline (zero once it has passed)
+#[must_use]
+pub fn remaining() -> Option<Duration> {
+    get().map(|at| at.saturating_duration_since(Instant::now()))
+}
+
+/// Resolves when the deadline passes (never, without one)
+///
+/// Marks the command as [cut short](cut_short): call this for work that
+/// stops early because of it.
+pub async fn reached() {
+    match get() {
+        Some(at) => {
+            tokio::time::sleep_until(at.into()).await;
+            CUT_SHORT.store(true, Ordering::Relaxed);
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Whether work stopped early at the deadline
+#[must_use]
+pub fn cut_short() -> bool {
+    CUT_SHORT.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse("2m500ms").unwrap(), Duration::from_millis(120_500));
+        for bad in ["", "0", "soon", "10x", "m"] {
+            assert!(parse(bad).is_err(), "{bad:?}");
+        }
+    }
+}
@@ -0,0 +1,602 @@
+//! EPUB3 Packaging
+//!
+//! Builds EPUB3 books from XHTML chapters:
+//! - `mimetype` first and uncompressed, as the OCF spec requires
+//! - Package document with Dublin Core metadata and `dcterms:modified`
+//! - Navigation document (table of contents)
+//! - Shared stylesheet and binary resources (images)
+//!
+//! Entries are written with the zip STORE method, so no compression library
+//! is needed; article images are already compressed anyway.
+//!
+//! Readers don't load remote images, so [`embed_images`] downloads them into
+//! the book and drops any that can't be fetched.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::readability::escape_xml;
+
+/// Default stylesheet for chapters
+const STYLESHEET: &str = "body { font-family: serif; line-height: 1.5; margin: 0 5%; }
+h1, h2, h3, h4 { font-family: sans-serif; line-height: 1.2; }
+h1 { font-size: 1.6em; margin: 1em 0 0.3em; }
+p.byline, p.source { color: #555; font-size: 0.9em; margin: 0; }
+p.source a { color: #555; }
+img { max-width: 100%; height: auto; }
+figure { margin: 1em 0; }
+figcaption { font-size: 0.85em; color: #555; }
+blockquote { margin: 1em 2em; font-style: italic; }
+pre { white-space: pre-wrap; font-size: 0.85em; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #999; padding: 0.2em 0.4em; }
+";
+
+struct Chapter {
+    title: String,
+    body: String,
+}
+
+struct Resource {
+    path: String,
+    media_type: String,
+    data: Vec<u8>,
+}
+
+/// EPUB3 book builder
+pub struct EpubBuilder {
+    title: String,
+    author: Option<String>,
+    publisher: Option<String>,
+    description: Option<String>,
+    language: String,
+    source: Option<String>,
+    date: Option<String>,
+    cover_image: Option<String>,
+    chapters: Vec<Chapter>,
+    resources: Vec<Resource>,
+}
+
+impl EpubBuilder {
+    /// Start a book with the given title
+    #[must_use]
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            author: None,
+            publisher: None,
+            description: None,
+            language: "en".to_string(),
+            source: None,
+            date: None,
+            cover_image: None,
+            chapters: Vec::new(),
+            resources: Vec::new(),
+        }
+    }
+
+    /// Set the author (`dc:creator`)
+    #[must_use]
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Set the publisher (`dc:publisher`)
+    #[must_use]
+    pub fn with_publisher(mut self, publisher: impl Into<String>) -> Self {
+        self.publisher = Some(publisher.into());
+        self
+    }
+
+    /// Set the description (`dc:description`)
+    #[must_use]
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the language tag (`dc:language`, default `en`)
+    #[must_use]
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = language.into();
+        self
+    }
+
+    /// Set the source URL (`dc:source`)
+    #[must_use]
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Set the publication date (`dc:date`)
+    #[must_use]
+    pub fn with_date(mut self, date: impl Into<String>) -> Self {
+        self.date = Some(date.into());
+        self
+    }
+
+    /// Add a chapter; `body` is an XHTML fragment placed inside `<body>`
+    pub fn add_chapter(&mut self, title: impl Into<String>, body: impl Into<String>) {
+        self.chapters.push(Chapter {
+            title: title.into(),
+            body: body.into(),
+        });
+    }
+
+    /// Add a binary resource at `path` (relative to the chapters)
+    pub fn add_resource(
+        &mut self,
+        path: impl Into<String>,
+        media_type: impl Into<String>,
+        data: Vec<u8>,
+    ) {
+        self.resources.push(Resource {
+            path: path.into(),
+            media_type: media_type.into(),
+            data,
+        });
+    }
+
+    /// Mark a previously added resource as the cover image
+    pub fn set_cover_image(&mut self, path: impl Into<String>) {
+        self.cover_image = Some(path.into());
+    }
+
+    /// Number of chapters added so far
+    #[must_use]
+    pub fn chapter_count(&self) -> usize {
+        self.chapters.len()
+    }
+
+    /// Number of resources (images) added so far
+    #[must_use]
+    pub fn resource_count(&self) -> usize {
+        self.resources.len()
+    }
+
+    /// Package the book as EPUB bytes
+    pub fn build(&self) -> Result<Vec<u8>> {
+        if self.chapters.is_empty() {
+            anyhow::bail!("EPUB needs at least one chapter");
+        }
+
+        let mut zip = ZipWriter::default();
+        zip.add("mimetype", b"application/epub+zip");
+        zip.add(
+            "META-INF/container.xml",
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#,
+        );
+        zip.add("OEBPS/content.opf", self.package_document().as_bytes());
+        zip.add("OEBPS/nav.xhtml", self.nav_document().as_bytes());
+        zip.add("OEBPS/style.css", STYLESHEET.as_bytes());
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            zip.add(
+                &format!("OEBPS/{}", chapter_file(i)),
+                self.chapter_document(chapter).as_bytes(),
+            );
+        }
+        for resource in &self.resources {
+            zip.add(&format!("OEBPS/{}", resource.path), &resource.data);
+        }
+        Ok(zip.finish())
+    }
+
+    fn package_document(&self) -> String {
+        let mut metadata = format!(
+            "    <dc:identifier id=\"bookid\">urn:uuid:{}</dc:identifier>\n    <dc:title>{}</dc:title>\n    <dc:language>{}</dc:language>\n    <meta property=\"dcterms:modified\">{}</meta>\n",
+            uuid::Uuid::new_v4(),
+            escape_xml(&self.title),
+            escape_xml(&self.language),
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+        );
+        for (element, value) in [
+            ("dc:creator", &self.author),
+            ("dc:publisher", &self.publisher),
+            ("dc:description", &self.description),
+            ("dc:source", &self.source),
+            ("dc:date", &self.date),
+        ] {
+            if let Some(value) = value {
+                metadata.push_str(&format!(
+                    "    <{element}>{}</{element}>\n",
+                    escape_xml(value)
+                ));
+            }
+        }
+        if self.cover_image.is_some() {
+            metadata.push_str("    <meta name=\"cover\" content=\"cover-image\"/>\n");
+        }
+
+        let mut manifest = String::from(
+            "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n    <item id=\"css\" href=\"style.css\" media-type=\"text/css\"/>\n",
+        );
+        let mut spine = String::new();
+        for i in 0..self.chapters.len() {
+            manifest.push_str(&format!(
+                "    <item id=\"chapter-{i}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+                chapter_file(i)
+            ));
+            spine.push_str(&format!("    <itemref idref=\"chapter-{i}\"/>\n"));
+        }
+        for (i, resource) in self.resources.iter().enumerate() {
+            let is_cover = self.cover_image.as_deref() == Some(resource.path.as_str());
+            manifest.push_str(&format!(
+                "    <item id=\"{}\" href=\"{}\" media-type=\"{}\"{}/>\n",
+                if is_cover {
+                    "cover-image".to_string()
+                } else {
+                    format!("res-{i}")
+                },
+                escape_xml(&resource.path),
+                escape_xml(&resource.media_type),
+                if is_cover {
+                    " properties=\"cover-image\""
+                } else {
+                    ""
+                },
+            ));
+        }
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"bookid\">
+  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">
+{metadata}  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"
+        )
+    }
+
+    fn nav_document(&self) -> String {
+        let items: String = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                format!(
+                    "      <li><a href=\"{}\">{}</a></li>\n",
+                    chapter_file(i),
+                    escape_xml(&c.title)
+                )
+            })
+            .collect();
+        self.xhtml(
+            "Contents",
+            &format!(
+                "  <nav epub:type=\"toc\" id=\"toc\">\n    <h1>Contents</h1>\n    <ol>\n{items}    </ol>\n  </nav>\n"
+            ),
+        )
+    }
+
+    fn chapter_document(&self, chapter: &Chapter) -> String {
+        self.xhtml(&chapter.title, &chapter.body)
+    }
+
+    fn xhtml(&self, title: &str, body: &str) -> String {
+        let lang = escape_xml(&self.language);
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE html>
+<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" lang=\"{lang}\" xml:lang=\"{lang}\">
+<head>
+  <title>{}</title>
+  <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>
+</head>
+<body>
+{body}
+</body>
+</html>
+",
+            escape_xml(title)
+        )
+    }
+}
+
+/// Largest image embedded in a book
+const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Image `src` values in an XHTML fragment, in document order
+#[must_use]
+pub fn image_sources(xhtml: &str) -> Vec<String> {
+    let re = regex::Regex::new(r#"<img src="([^"]*)""#).unwrap();
+    let mut sources: Vec<String> = Vec::new();
+    for caps in re.captures_iter(xhtml) {
+        let src = unescape_attr(&caps[1]);
+        if !sources.contains(&src) {
+            sources.push(src);
+        }
+    }
+    sources
+}
+
+/// Point images at local copies; images missing from `local` are removed
+#[must_use]
+pub fn rewrite_images(xhtml: &str, local: &HashMap<String, String>) -> String {
+    let re = regex::Regex::new(r#"<img src="([^"]*)"([^>]*)/>"#).unwrap();
+    re.replace_all(xhtml, |caps: &regex::Captures<'_>| {
+        local
+            .get(&unescape_attr(&caps[1]))
+            .map(|path| format!("<img src=\"{}\"{}/>", escape_xml(path), &caps[2]))
+            .unwrap_or_default()
+    })
+    .into_owned()
+}
+
+/// Download the images of an XHTML fragment into `book`
+///
+/// Returns the fragment with image sources rewritten to the embedded copies.
+/// Images that fail to download or aren't EPUB image types are dropped.
+pub async fn embed_images(client: &reqwest::Client, book: &mut EpubBuilder, xhtml: &str) -> String {
+    let mut local = HashMap::new();
+    for src in image_sources(xhtml) {
+        if let Some(path) = fetch_image(client, book, &src).await {
+            local.insert(src, path);
+        }
+    }
+    rewrite_images(xhtml, &local)
+}
+
+/// Download one image into `book`, returning its path inside the book
+pub async fn fetch_image(
+    client: &reqwest::Client,
+    book: &mut EpubBuilder,
+    src: &str,
+) -> Option<String> {
+    if !src.starts_with("http://") && !src.starts_with("https://") {
+        return None;
+    }
+    let response = crate::policy::send(client.get(src)).await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| {
+            ct.split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+        });
+    let media_type = content_type
+        .filter(|ct| image_extension(ct).is_some())
+        .or_else(|| media_type_from_url(src))?;
+    let data = response.bytes().await.ok()?;
+    if data.is_empty() || data.len() > MAX_IMAGE_BYTES {
+        return None;
+    }
+
+    let path = format!(
+        "images/img-{:03}.{}",
+        book.resource_count() + 1,
+        image_extension(&media_type)?
+    );
+    book.add_resource(path.clone(), media_type, data.to_vec());
+    Some(path)
+}
+
+/// File extension for EPUB core image media types
+fn image_extension(media_type: &str) -> Option<&'static str> {
+    match media_type {
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/png" => Some("png"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "image/svg+xml" => Some("svg"),
+        _ => None,
+    }
+}
+
+fn media_type_from_url(src: &str) -> Option<String> {
+    let path = url::Url::parse(src).ok()?.path().to_lowercase();
+    let ext = path.rsplit('.').next()?;
+    let media_type = match ext {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    };
+    Some(media_type.to_string())
+}
+
+fn unescape_attr(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&#160;", "\u{a0}")
+        .replace("&amp;", "&")
+}
+
+fn chapter_file(index: usize) -> String {
+    format!("chapter-{:03}.xhtml", index + 1)
+}
+
+/// Minimal zip writer (STORE method only)
+#[derive(Default)]
+pub(crate) struct ZipWriter {
+    data: Vec<u8>,
+    central: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipWriter {
+    /// DOS date/time for 1980-01-01 00:00 (entries carry no real timestamps)
+    const DOS_TIME: u16 = 0;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    pub(crate) fn add(&mut self, name: &str, contents: &[u8]) {
+        let crc = crc32fast::hash(contents);
+        #[allow(clippy::cast_possible_truncation)]
+        let (offset, size, name_len) = (
+            self.data.len() as u32,
+            contents.len() as u32,
+            name.len() as u16,
+        );
+
+        // Local file header
+        self.data.extend_from_slice(&0x0403_4b50_u32.to_le_bytes());
+        self.write_entry_fields(true, crc, size, name_len);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(contents);
+
+        // Central directory record
+        self.central
+            .extend_from_slice(&0x0201_4b50_u32.to_le_bytes());
+        self.central.extend_from_slice(&20_u16.to_le_bytes()); // version made by
+        self.write_entry_fields(false, crc, size, name_len);
+        self.central.extend_from_slice(&0_u16.to_le_bytes()); // comment length
+        self.central.extend_from_slice(&0_u16.to_le_bytes()); // disk number
+        self.central.extend_from_slice(&0_u16.to_le_bytes()); // internal attributes
+        self.central.extend_from_slice(&0_u32.to_le_bytes()); // external attributes
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+
+        self.entries += 1;
+    }
+
+    /// Fields shared by local headers and central directory records
+    fn write_entry_fields(&mut self, local: bool, crc: u32, size: u32, name_len: u16) {
+        let buf = if local {
+            &mut self.data
+        } else {
+            &mut self.central
+        };
+        buf.extend_from_slice(&20_u16.to_le_bytes()); // version needed
+        buf.extend_from_slice(&0x0800_u16.to_le_bytes()); // flags: UTF-8 names
+        buf.extend_from_slice(&0_u16.to_le_bytes()); // method: STORE
+        buf.extend_from_slice(&Self::DOS_TIME.to_le_bytes());
+        buf.extend_from_slice(&Self::DOS_DATE.to_le_bytes());
+        buf.extend_from_slice(&crc.to_le_bytes());
+        buf.extend_from_slice(&size.to_le_bytes()); // compressed size
+        buf.extend_from_slice(&size.to_le_bytes()); // uncompressed size
+        buf.extend_from_slice(&name_len.to_le_bytes());
+        buf.extend_from_slice(&0_u16.to_le_bytes()); // extra field length
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        #[allow(clippy::cast_possible_truncation)]
+        let (central_offset, central_size) = (self.data.len() as u32, self.central.len() as u32);
+        self.data.append(&mut self.central);
+
+        // End of central directory
+        self.data.extend_from_slice(&0x0605_4b50_u32.to_le_bytes());
+        self.data.extend_from_slice(&0_u16.to_le_bytes()); // this disk
+        self.data.extend_from_slice(&0_u16.to_le_bytes()); // central directory disk
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&central_size.to_le_bytes());
+        self.data.extend_from_slice(&central_offset.to_le_bytes());
+        self.data.extend_from_slice(&0_u16.to_le_bytes()); // comment length
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_names(zip: &[u8]) -> Vec<String> {
+        // Walk local file headers (STORE: data follows the name directly)
+        let mut names = Vec::new();
+        let mut pos = 0;
+        while zip[pos..].starts_with(&0x0403_4b50_u32.to_le_bytes()) {
+            let u16_at = |p: usize| u16::from_le_bytes([zip[p], zip[p + 1]]) as usize;
+            let size = u32::from_le_bytes(zip[pos + 18..pos + 22].try_into().unwrap()) as usize;
+            let name_len = u16_at(pos + 26);
+            names.push(String::from_utf8(zip[pos + 30..pos + 30 + name_len].to_vec()).unwrap());
+            pos += 30 + name_len + size;
+        }
+        names
+    }
+
+    #[test]
+    fn test_epub_layout() {
+        let mut book = EpubBuilder::new("Collected <Articles>").with_author("Jane Writer");
+        book.add_chapter("One", "<h1>One</h1><p>First.</p>");
+        book.add_chapter("Two & Three", "<p>Second.</p>");
+        book.add_resource("images/a.png", "image/png", vec![0x89, b'P', b'N', b'G']);
+        book.set_cover_image("images/a.png");
+        let epub = book.build().unwrap();
+
+        assert_eq!(
+            entry_names(&epub),
+            vec![
+                "mimetype",
+                "META-INF/container.xml",
+                "OEBPS/content.opf",
+                "OEBPS/nav.xhtml",
+                "OEBPS/style.css",
+                "OEBPS/chapter-001.xhtml",
+                "OEBPS/chapter-002.xhtml",
+                "OEBPS/images/a.png",
+            ]
+        );
+        // mimetype must be the first entry, stored, with no extra field
+        assert_eq!(&epub[30..38], b"mimetype");
+        assert_eq!(&epub[38..58], b"application/epub+zip");
+
+        let opf = book.package_document();
+        assert!(opf.contains("<dc:title>Collected &lt;Articles&gt;</dc:title>"));
+        assert!(opf.contains("<dc:creator>Jane Writer</dc:creator>"));
+        assert!(opf.contains("properties=\"cover-image\""));
+        assert!(book.nav_document().contains("Two &amp; Three"));
+    }
+
+    #[test]
+    fn test_rewrite_images() {
+        let xhtml = r#"<p>A</p><img src="https://cdn.example/a.png?w=1&amp;h=2" alt="Chart"/><img src="https://cdn.example/b.tiff" alt=""/>"#;
+        let sources = image_sources(xhtml);
+        assert_eq!(
+            sources,
+            vec![
+                "https://cdn.example/a.png?w=1&h=2",
+                "https://cdn.example/b.tiff"
+            ]
+        );
+
+        let local = HashMap::from([(sources[0].clone(), "images/img-001.png".to_string())]);
+        assert_eq!(
+            rewrite_images(xhtml, &local),
+            r#"<p>A</p><img src="images/img-001.png" alt="Chart"/>"#
+        );
+        assert_eq!(
+            media_type_from_url("https://cdn.example/photo.JPG").as_deref(),
+            Some("image/jpeg")
+        );
+        assert_eq!(media_type_from_url("https://cdn.example/b.tiff"), None);
+    }
+
+    #[test]
+    fn test_empty_book_fails() {
+        assert!(EpubBuilder::new("Empty").build().is_err());
+    }
+
+    #[test]
+    fn test_zip_directory() {
+        let mut zip = ZipWriter::default();
+        zip.add("a.txt", b"hello");
+        let bytes = zip.finish();
+        let eocd = &bytes[bytes.len() - 22..];
+        assert_eq!(&eocd[..4], &0x0605_4b50_u32.to_le_bytes());
+        assert_eq!(u16::from_le_bytes([eocd[10], eocd[11]]), 1);
+        assert_eq!(crc32fast::hash(b"hello"), 0x3610_a686);
+    }
+}
@@ -0,0 +1,398 @@
+//! Job Estimates (`--dry-run`)
+//!
+//! `nab batch --dry-run` and `nab crawl --dry-run` size a job before it runs:
+//! how many pages, roughly how many bytes, and how long it takes under the
+//! configured concurrency limits, politeness delay, and `--human-timing`.
+//! No page bodies are downloaded:
+//! - a crawl's frontier is expanded from what's already known: a saved
+//!   `--state`, each seed site's sitemaps (found through `robots.txt`), and
+//!   the links of pages in the `--revisit` cache
+//! - a few pages per host get a `HEAD` request ([`SAMPLES_PER_HOST`]) for
+//!   their `Content-Length` and response time
+//!
+//! Pages whose links aren't known can't be expanded, so a crawl estimate is a
+//! lower bound when [`Estimate::unexplored`] isn't zero.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::batch::{host_key, ConcurrencyLimits};
+use crate::quota::format_size;
+
+/// `HEAD` requests per host
+pub const SAMPLES_PER_HOST: usize = 3;
+
+/// Most sitemap files read per crawl (sitemap indexes can nest)
+pub const MAX_SITEMAPS: usize = 50;
+
+/// Response time assumed when no sample answered
+const DEFAULT_LATENCY: Duration = Duration::from_millis(500);
+
+/// Sitemap URLs listed in a `robots.txt`
+#[must_use]
+pub fn robots_sitemaps(robots: &str) -> Vec<String> {
+    robots
+        .lines()
+        .filter_map(|line| {
+            let (field, value) = line.split_once(':')?;
+            field
+                .trim()
+                .eq_ignore_ascii_case("sitemap")
+                .then(|| value.trim().to_string())
+        })
+        .filter(|url| !url.is_empty())
+        .collect()
+}
+
+/// A parsed sitemap: pages, or more sitemaps for a sitemap index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sitemap {
+    pub pages: Vec<String>,
+    pub sitemaps: Vec<String>,
+}
+
+impl Sitemap {
+    /// Parse a sitemap file, gunzipping `.xml.gz` ones
+    pub fn from_bytes(body: &[u8]) -> Result<Self> {
+        if body.starts_with(&[0x1f, 0x8b]) {
+            let mut xml = String::new();
+            flate2::read::GzDecoder::new(body)
+                .read_to_string(&mut xml)
+                .context("Invalid gzipped sitemap")?;
+            Ok(Self::parse(&xml))
+        } else {
+            Ok(Self::parse(&String::from_utf8_lossy(body)))
+        }
+    }
+
+    /// Parse a `<urlset>` or `<sitemapindex>` document
+    #[must_use]
+    pub fn parse(xml: &str) -> Self {
+        static LOC: OnceLock<Regex> = OnceLock::new();
+        let loc = LOC.get_or_init(|| Regex::new(r"(?s)<loc>\s*(.*?)\s*</loc>").unwrap());
+        let locs = loc
+            .captures_iter(xml)
+            .map(|c| unescape_xml(&c[1]))
+            .collect();
+        if xml.contains("<sitemapindex") {
+            Self {
+                pages: Vec::new(),
+                sitemaps: locs,
+            }
+        } else {
+            Self {
+                pages: locs,
+                sitemaps: Vec::new(),
+            }
+        }
+    }
+}
+
+fn unescape_xml(text: &str) -> String {
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+        .unwrap_or(text);
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// What's known about one host's pages so far
+#[derive(Debug, Clone, Default)]
+struct HostTally {
+    pages: usize,
+    cached: usize,
+    cached_bytes: u64,
+    samples: usize,
+    latency: Duration,
+    sized: usize,
+    sized_bytes: u64,
+}
+
+impl HostTally {
+    fn bytes_per_page(&self) -> Option<u64> {
+        if self.sized > 0 {
+            Some(self.sized_bytes / self.sized as u64)
+        } else if self.cached > 0 {
+            Some(self.cached_bytes / self.cached as u64)
+        } else {
+            None
+        }
+    }
+
+    fn latency(&self) -> Option<Duration> {
+        (self.samples > 0).then(|| self.latency / u32::try_from(self.samples).unwrap_or(u32::MAX))
+    }
+}
+
+/// Adds up a planned job's pages and samples into an [`Estimate`]
+#[derive(Debug, Clone)]
+pub struct Estimator {
+    limits: ConcurrencyLimits,
+    interval: Duration,
+    hosts: BTreeMap<String, HostTally>,
+    cached: HashSet<String>,
+}
+
+impl Estimator {
+    /// A job run under `limits`, starting requests to one host at most every
+    /// `interval` (politeness delay or human timing)
+    #[must_use]
+    pub fn new(limits: ConcurrencyLimits, interval: Duration) -> Self {
+        Self {
+            limits,
+            interval,
+            hosts: BTreeMap::new(),
+            cached: HashSet::new(),
+        }
+    }
+
+    /// A page the job fetches; `cached` is the size of a copy already on disk
+    pub fn page(&mut self, url: &str, cached: Option<usize>) {
+        let host = self.hosts.entry(host_key(url)).or_default();
+        host.pages += 1;
+        if let Some(size) = cached {
+            host.cached += 1;
+            host.cached_bytes += size as u64;
+            self.cached.insert(url.to_string());
+        }
+    }
+
+    /// A `HEAD` sample of `url`: how long it took and its `Content-Length`
+    /// (a cached copy's size counts instead, if there is one)
+    pub fn sample(&mut self, url: &str, latency: Duration, size: Option<u64>) {
+        let host = self.hosts.entry(host_key(url)).or_default();
+        host.samples += 1;
+        host.latency += latency;
+        if let Some(size) = size.filter(|_| !self.cached.contains(url)) {
+            host.sized += 1;
+            host.sized_bytes += size;
+        }
+    }
+
+    /// Totals and per-host figures; hosts without samples get the average of
+    /// the others
+    #[must_use]
+    pub fn finish(&self) -> Estimate {
+        let average = |values: Vec<(u64, usize)>| {
+            let (sum, n) = values
+                .into_iter()
+                .fold((0, 0), |(sum, n), (v, count)| (sum + v, n + count));
+            (n > 0).then(|| sum / n as u64)
+        };
+        let fallback_size = average(
+            self.hosts
+                .values()
+                .filter_map(|h| h.bytes_per_page().map(|b| (b * h.pages as u64, h.pages)))
+                .collect(),
+        );
+        let fallback_latency = average(
+            self.hosts
+                .values()
+                .filter(|h| h.samples > 0)
+                .map(|h| (h.latency.as_millis() as u64, h.samples))
+                .collect(),
+        )
+        .map_or(DEFAULT_LATENCY, Duration::from_millis);
+
+        let per_host = u32::try_from(self.limits.per_host).unwrap_or(u32::MAX);
+        let mut hosts = BTreeMap::new();
+        let mut busy = Duration::ZERO;
+        for (name, tally) in &self.hosts {
+            let latency = tally.latency().unwrap_or(fallback_latency);
+            let bytes_per_page = tally.bytes_per_page().or(fallback_size);
+            let unsized_pages = tally.pages.saturating_sub(tally.cached + tally.sized) as u64;
+            let bytes =
+                bytes_per_page.map(|b| tally.cached_bytes + tally.sized_bytes + unsized_pages * b);
+            // A host starts a request every interval, or as fast as its
+            // concurrency allows
+            let spacing = self.interval.max(latency / per_host);
+            let pages = u32::try_from(tally.pages).unwrap_or(u32::MAX);
+            hosts.insert(
+                name.clone(),
+                HostEstimate {
+                    pages: tally.pages,
+                    cached: tally.cached,
+                    sampled: tally.samples,
+                    bytes,
+                    latency_ms: latency.as_millis() as u64,
+                    seconds: (spacing * pages.saturating_sub(1) + latency).as_secs_f64(),
+                },
+            );
+            busy += latency * pages;
+        }
+
+        let global = u32::try_from(self.limits.global).unwrap_or(u32::MAX);
+        let slowest_host = hosts.values().map(|h| h.seconds).fold(0.0, f64::max);
+        Estimate {
+            pages: hosts.values().map(|h| h.pages).sum(),
+            bytes: hosts.values().filter_map(|h| h.bytes).sum(),
+            unknown_size: hosts
+                .values()
+                .filter(|h| h.bytes.is_none())
+                .map(|h| h.pages)
+                .sum(),
+            seconds: slowest_host.max((busy / global).as_secs_f64()),
+            sitemap_pages: 0,
+            unexplored: 0,
+            hosts,
+        }
+    }
+}
+
+/// One host's share of a job
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HostEstimate {
+    pub pages: usize,
+    /// Pages with a copy in the `--revisit` cache
+    pub cached: usize,
+    /// Pages sampled with `HEAD`
+    pub sampled: usize,
+    /// `None` when no page of any host had a known size
+    pub bytes: Option<u64>,
+    /// Average response time (measured, or assumed)
+    pub latency_ms: u64,
+    /// Time to fetch this host's pages on its own
+    pub seconds: f64,
+}
+
+/// The size of a job
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Estimate {
+    pub pages: usize,
+    /// Expected bytes of the pages with a known or estimated size
+    pub bytes: u64,
+    /// Pages left out of `bytes`
+    pub unknown_size: usize,
+    /// Expected runtime
+    pub seconds: f64,
+    /// Pages found only through sitemaps
+    pub sitemap_pages: usize,
+    /// Planned pages within the depth limit whose links aren't known
+    pub unexplored: usize,
+    pub hosts: BTreeMap<String, HostEstimate>,
+}
+
+impl Estimate {
+    /// Human-readable report, one line per host after the totals when there
+    /// are several
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "🧮 Dry run: {} pages on {} hosts, ~{}{}, ~{} at the current limits",
+            self.pages,
+            self.hosts.len(),
+            format_size(self.bytes),
+            if self.unknown_size > 0 {
+                format!(" (+{} pages of unknown size)", self.unknown_size)
+            } else {
+                String::new()
+            },
+            format_duration(self.seconds)
+        );
+        if self.sitemap_pages > 0 {
+            out.push_str(&format!(
+                "\n   🗺️  {} pages from sitemaps",
+                self.sitemap_pages
+            ));
+        }
+        if self.unexplored > 0 {
+            out.push_str(&format!(
+                "\n   ⚠️  {} pages have links that aren't known yet; the crawl may find more",
+                self.unexplored
+            ));
+        }
+        if self.hosts.len() > 1 {
+            for (host, estimate) in &self.hosts {
+                out.push_str(&format!(
+                    "\n   {host}: {} pages, ~{}, ~{} ({} ms per request)",
+                    estimate.pages,
+                    estimate
+                        .bytes
+                        .map_or_else(|| "? bytes".to_string(), format_size),
+                    format_duration(estimate.seconds),
+                    estimate.latency_ms
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// `4000.0` → `1h 6m`
+#[must_use]
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sitemaps() {
+        let robots = "User-agent: *\nDisallow: /private\nSitemap: https://a.test/sitemap_index.xml\nsitemap:https://a.test/news.xml\n";
+        assert_eq!(
+            robots_sitemaps(robots),
+            [
+                "https://a.test/sitemap_index.xml",
+                "https://a.test/news.xml"
+            ]
+        );
+
+        let index = Sitemap::parse(
+            r#"<?xml version="1.0"?><sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+            <sitemap><loc>https://a.test/pages.xml</loc></sitemap></sitemapindex>"#,
+        );
+        assert_eq!(index.sitemaps, ["https://a.test/pages.xml"]);
+        let urlset = Sitemap::parse(
+            "<urlset><url><loc> https://a.test/?a=1&amp;b=2 </loc><lastmod>2026-01-01</lastmod></url>\
+             <url><loc><![CDATA[https://a.test/about]]></loc></url></urlset>",
+        );
+        assert_eq!(
+            urlset.pages,
+            ["https://a.test/?a=1&b=2", "https://a.test/about"]
+        );
+    }
+
+    #[test]
+    fn test_estimate_runtime_and_bytes() {
+        let mut estimator = Estimator::new(ConcurrencyLimits::new(2, 16), Duration::from_secs(1));
+        for i in 0..10 {
+            estimator.page(&format!("https://a.test/{i}"), None);
+        }
+        estimator.page("https://b.test/", Some(3000));
+        estimator.page("https://b.test/new", None);
+        estimator.sample("https://a.test/0", Duration::from_millis(200), Some(10_000));
+        estimator.sample("https://a.test/1", Duration::from_millis(400), Some(20_000));
+        estimator.sample("https://b.test/", Duration::from_millis(100), Some(99_999));
+
+        let estimate = estimator.finish();
+        assert_eq!(estimate.pages, 12);
+        let a = &estimate.hosts["a.test"];
+        assert_eq!((a.bytes, a.latency_ms), (Some(150_000), 300));
+        // Politeness dominates: nine 1s gaps, then the last response
+        assert!((a.seconds - 9.3).abs() < 1e-9, "{}", a.seconds);
+        // b.test's only sized page is its cached copy, which sizes the other
+        let b = &estimate.hosts["b.test"];
+        assert_eq!((b.bytes, b.latency_ms), (Some(6000), 100));
+        assert_eq!(estimate.bytes, 156_000);
+        assert!((estimate.seconds - 9.3).abs() < 1e-9);
+        assert!(estimate.render().contains("12 pages on 2 hosts, ~156.0 KB"));
+        assert_eq!(format_duration(4000.0), "1h 6m");
+    }
+}
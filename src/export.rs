@@ -0,0 +1,285 @@
+//! Crawl Export
+//!
+//! `nab export` turns a crawl (its manifest or JSON lines) into one row per
+//! page for analytics tools: `url`, `timestamp`, `status`, `title`, `text`,
+//! and the rest of the page's line as a `metadata` JSON string. A Parquet
+//! file loads straight into DuckDB (`SELECT * FROM 'crawl.parquet'`), Spark,
+//! or pandas.
+//!
+//! A page's text is its saved Markdown (`crawl -o`) or, with the crawl's
+//! `--store`, its stored body converted the same way.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::store::BodyStore;
+
+/// Rows per Parquet row group
+#[cfg(feature = "parquet")]
+const ROW_GROUP: usize = 1024;
+
+/// One exported page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportRow {
+    pub url: String,
+    /// When the page was fetched (the crawl's start for older crawls)
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Missing for pages that failed
+    pub status: Option<u16>,
+    pub title: Option<String>,
+    pub text: Option<String>,
+    /// The page's other fields, as JSON
+    pub metadata: String,
+}
+
+/// A crawl's pages, from its manifest or JSON lines
+#[derive(Debug, Default)]
+pub struct Crawl {
+    pub started_at: Option<DateTime<Utc>>,
+    pub pages: Vec<Value>,
+}
+
+impl Crawl {
+    /// Pages of a crawl manifest (`{"pages": [...]}`) or JSON lines
+    pub fn parse(text: &str) -> Result<Self> {
+        if let Ok(manifest) = serde_json::from_str::<Value>(text) {
+            if let Some(pages) = manifest["pages"].as_array() {
+                return Ok(Self {
+                    started_at: manifest["started_at"]
+                        .as_str()
+                        .and_then(|at| at.parse().ok()),
+                    pages: pages.clone(),
+                });
+            }
+        }
+        let pages = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.trim_start().starts_with('{'))
+            .map(|(n, line)| {
+                serde_json::from_str::<Value>(line)
+                    .with_context(|| format!("Line {} isn't JSON", n + 1))
+            })
+            // Other JSON lines, like a trailing {"stats": ...}, aren't pages
+            .filter(|page| page.as_ref().map_or(true, |page| page["url"].is_string()))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            started_at: None,
+            pages,
+        })
+    }
+
+    /// One row per page, with text from saved files or `store`
+    pub fn rows<'a>(
+        &'a self,
+        store: Option<&'a BodyStore>,
+    ) -> impl Iterator<Item = ExportRow> + 'a {
+        self.pages
+            .iter()
+            .map(move |page| row(page, self.started_at, store))
+    }
+}
+
+fn row(page: &Value, started_at: Option<DateTime<Utc>>, store: Option<&BodyStore>) -> ExportRow {
+    let (title, text) = page_text(page, store).unzip();
+    let mut metadata = page.clone();
+    if let Value::Object(fields) = &mut metadata {
+        for key in ["url", "status", "fetched_at"] {
+            fields.remove(key);
+        }
+    }
+    ExportRow {
+        url: page["url"].as_str().unwrap_or_default().to_string(),
+        timestamp: page["fetched_at"]
+            .as_str()
+            .and_then(|at| at.parse().ok())
+            .or(started_at),
+        status: page["status"].as_u64().and_then(|s| u16::try_from(s).ok()),
+        title: title.flatten(),
+        text,
+        metadata: metadata.to_string(),
+    }
+}
+
+/// `(title, text)` from the page's Markdown file, else its stored body
+fn page_text(page: &Value, store: Option<&BodyStore>) -> Option<(Option<String>, String)> {
+    if let Some(markdown) = page["file"]
+        .as_str()
+        .and_then(|file| std::fs::read_to_string(file).ok())
+    {
+        return Some((markdown_title(&markdown), markdown));
+    }
+    let body = store?.get(page["sha256"].as_str()?).ok()?;
+    let body = String::from_utf8_lossy(&body);
+    if !body.trim_start().starts_with('<') {
+        return Some((None, body.into_owned()));
+    }
+    let title = crate::page::select_text(&body, "title")
+        .ok()
+        .and_then(|titles| titles.into_iter().next());
+    Some((title, crate::page::html_to_markdown(&body)))
+}
+
+/// First level-1 heading: `# Title`, or `Title` underlined with `=`
+fn markdown_title(markdown: &str) -> Option<String> {
+    let lines: Vec<&str> = markdown.lines().collect();
+    lines.iter().enumerate().find_map(|(n, line)| {
+        if let Some(title) = line.strip_prefix("# ") {
+            return Some(title.trim().to_string());
+        }
+        let underline = lines.get(n + 1)?.trim();
+        (!line.trim().is_empty() && !underline.is_empty() && underline.chars().all(|c| c == '='))
+            .then(|| line.trim().to_string())
+    })
+}
+
+/// Write `rows` as JSON lines; the number written
+pub fn write_jsonl(rows: impl Iterator<Item = ExportRow>, mut out: impl Write) -> Result<usize> {
+    let mut count = 0;
+    for row in rows {
+        serde_json::to_writer(&mut out, &row)?;
+        out.write_all(b"\n")?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+/// Write `rows` as a zstd-compressed Parquet file; the number written
+#[cfg(feature = "parquet")]
+pub fn write_parquet(
+    rows: impl Iterator<Item = ExportRow>,
+    out: impl Write + Send,
+) -> Result<usize> {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, RecordBatch, StringArray, TimestampMillisecondArray, UInt16Array};
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::{Compression, ZstdLevel};
+    use parquet::file::properties::WriterProperties;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("url", DataType::Utf8, false),
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            true,
+        ),
+        Field::new("status", DataType::UInt16, true),
+        Field::new("title", DataType::Utf8, true),
+        Field::new("text", DataType::Utf8, true),
+        Field::new("metadata", DataType::Utf8, false),
+    ]));
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .set_max_row_group_size(ROW_GROUP)
+        .build();
+    let mut writer = ArrowWriter::try_new(out, Arc::clone(&schema), Some(properties))?;
+
+    let mut count = 0;
+    let mut rows = rows.peekable();
+    while rows.peek().is_some() {
+        let group: Vec<ExportRow> = rows.by_ref().take(ROW_GROUP).collect();
+        let timestamps: TimestampMillisecondArray = group
+            .iter()
+            .map(|row| row.timestamp.map(|at| at.timestamp_millis()))
+            .collect();
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(
+                group.iter().map(|row| row.url.as_str()),
+            )),
+            Arc::new(timestamps.with_timezone("UTC")),
+            Arc::new(group.iter().map(|row| row.status).collect::<UInt16Array>()),
+            Arc::new(
+                group
+                    .iter()
+                    .map(|row| row.title.as_deref())
+                    .collect::<StringArray>(),
+            ),
+            Arc::new(
+                group
+                    .iter()
+                    .map(|row| row.text.as_deref())
+                    .collect::<StringArray>(),
+            ),
+            Arc::new(StringArray::from_iter_values(
+                group.iter().map(|row| row.metadata.as_str()),
+            )),
+        ];
+        writer.write(&RecordBatch::try_new(Arc::clone(&schema), columns)?)?;
+        count += group.len();
+    }
+    writer.close()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_from_manifest() {
+        let dir = std::env::temp_dir().join(format!("nab-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.md");
+        std::fs::write(&file, "Page A\n======\n\nSome text\n\n# Not the title").unwrap();
+        let manifest = serde_json::json!({
+            "started_at": "2026-01-02T03:04:05+00:00",
+            "pages": [
+                {"url": "https://example.com/a", "status": 200, "links": 3,
+                 "file": file.display().to_string()},
+                {"url": "https://example.com/b", "error": "timed out"},
+            ],
+        });
+        let crawl = Crawl::parse(&manifest.to_string()).unwrap();
+        let rows: Vec<ExportRow> = crawl.rows(None).collect();
+        assert_eq!(rows[0].title.as_deref(), Some("Page A"));
+        assert_eq!(rows[0].status, Some(200));
+        assert_eq!(rows[0].timestamp, crawl.started_at);
+        assert!(rows[0].metadata.contains(r#""links":3"#));
+        assert_eq!((rows[1].status, rows[1].text.as_deref()), (None, None));
+
+        let lines = "{\"url\":\"https://example.com/c\",\"fetched_at\":\"2026-02-01T00:00:00Z\"}\n\
+                     {\"stats\":{\"pages\":1}}\n";
+        let crawl = Crawl::parse(lines).unwrap();
+        assert_eq!(crawl.pages.len(), 1);
+        let row = crawl.rows(None).next().unwrap();
+        assert_eq!(
+            row.timestamp.unwrap().to_rfc3339(),
+            "2026-02-01T00:00:00+00:00"
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_round_trip() {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let lines: String = (0..1500)
+            .map(|n| format!("{{\"url\":\"https://example.com/{n}\",\"status\":200}}\n"))
+            .collect();
+        let crawl = Crawl::parse(&lines).unwrap();
+        let mut parquet = Vec::new();
+        assert_eq!(write_parquet(crawl.rows(None), &mut parquet).unwrap(), 1500);
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(parquet))
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1500);
+        let urls = batches[0]
+            .column_by_name("url")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow_array::StringArray>()
+            .unwrap();
+        assert_eq!(urls.value(1), "https://example.com/1");
+    }
+}
@@ -18,32 +18,89 @@ use anyhow::Result;
 use reqwest::blocking::Client;
 use rquickjs::{Context, Function};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::cassette::Cassette;
+use crate::http_client::ClientOptions;
+use crate::sandbox::{NetworkPolicy, SandboxViolation, ViolationLog};
+
+/// Redirects a page fetch may follow
+const MAX_REDIRECTS: usize = 10;
 
 /// HTTP client wrapper for fetch bridge
 #[derive(Clone)]
 pub struct FetchClient {
     client: Client,
+    /// Certificate, proxy, ... of `client`
+    options: ClientOptions,
     cookie_header: String,
     base_url: String,
     /// Log of all fetched URLs (for debugging/discovery)
     fetch_log: Arc<Mutex<Vec<String>>>,
+    /// Which URLs page scripts may fetch
+    policy: NetworkPolicy,
+    /// Where blocked fetches are reported
+    violations: ViolationLog,
+    /// Records or replays page fetches
+    cassette: Option<Arc<Cassette>>,
 }
 
 impl FetchClient {
     /// Create a new fetch client with optional cookies and base URL
     #[must_use]
     pub fn new(cookies: Option<String>, base_url: Option<String>) -> Self {
+        let base_url = base_url.unwrap_or_default();
+        let policy = NetworkPolicy::Allow;
+        let violations = ViolationLog::default();
+        let options = ClientOptions::default();
         Self {
-            client: Client::builder()
-                .user_agent("nab/1.0")
-                .build()
-                .unwrap(),
+            client: page_client(&options, &policy, &base_url, &violations).unwrap(),
+            options,
             cookie_header: cookies.unwrap_or_default(),
-            base_url: base_url.unwrap_or_default(),
+            base_url,
             fetch_log: Arc::new(Mutex::new(Vec::new())),
+            policy,
+            violations,
+            cassette: None,
         }
     }
 
+    /// Restrict page fetches (and their redirects) to `policy`, reporting
+    /// refusals to `violations`
+    pub fn with_network_policy(
+        mut self,
+        policy: NetworkPolicy,
+        violations: ViolationLog,
+    ) -> Result<Self> {
+        self.policy = policy;
+        self.violations = violations;
+        self.rebuild_client()
+    }
+
+    /// Use `options` (client certificate, proxy, ...) for page fetches
+    pub fn with_options(mut self, options: &ClientOptions) -> Result<Self> {
+        self.options = options.clone();
+        self.rebuild_client()
+    }
+
+    /// A new client for changed options or policy
+    fn rebuild_client(mut self) -> Result<Self> {
+        self.client = page_client(
+            &self.options,
+            &self.policy,
+            &self.base_url,
+            &self.violations,
+        )?;
+        Ok(self)
+    }
+
+    /// Record page fetches into `cassette`, or answer them from it
+    #[must_use]
+    pub fn with_cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
     /// Get the list of all fetched URLs
     #[must_use]
     pub fn get_fetch_log(&self) -> Vec<String> {
@@ -62,6 +119,14 @@ impl FetchClient {
             url
         };
 
+        if !self.policy.permits(&full_url, &self.base_url) {
+            self.violations.record(SandboxViolation::Network {
+                url: full_url.clone(),
+                policy: self.policy.name().to_string(),
+            });
+            anyhow::bail!("fetch blocked by {} policy: {full_url}", self.policy.name());
+        }
+
         // Log the fetch for discovery
         if let Ok(mut log) = self.fetch_log.lock() {
             log.push(full_url.clone());
@@ -74,6 +139,10 @@ impl FetchClient {
             request = request.header("Cookie", &self.cookie_header);
         }
 
+        if let Some(cassette) = &self.cassette {
+            return Ok(cassette.send_blocking(request)?.body);
+        }
+
         // Execute request (blocking)
         let response = request.send()?;
         let body = response.text()?;
@@ -82,6 +151,39 @@ impl FetchClient {
     }
 }
 
+/// Blocking client for page fetches
+///
+/// Every redirect hop is checked against `policy` like the fetch itself, so
+/// a permitted URL can't bounce the request elsewhere.
+fn page_client(
+    options: &ClientOptions,
+    policy: &NetworkPolicy,
+    base_url: &str,
+    violations: &ViolationLog,
+) -> Result<Client> {
+    let (policy, base_url, violations) = (policy.clone(), base_url.to_string(), violations.clone());
+    let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+        let hop = attempt.url().to_string();
+        if policy.permits(&hop, &base_url) {
+            return crate::policy::follow(attempt, MAX_REDIRECTS);
+        }
+        violations.record(SandboxViolation::Network {
+            url: hop.clone(),
+            policy: policy.name().to_string(),
+        });
+        attempt.error(format!(
+            "redirect blocked by {} policy: {hop}",
+            policy.name()
+        ))
+    });
+    let builder = crate::policy::blocking_client_builder()
+        .user_agent("nab/1.0")
+        .redirect(redirect)
+        // Never let a page fetch hang the JS engine
+        .timeout(Duration::from_secs(15));
+    Ok(options.apply_blocking(builder)?.build()?)
+}
+
 /// Inject `fetch()` global into `QuickJS` context
 /// This creates a synchronous `fetch()` that blocks on HTTP requests
 pub fn inject_fetch_sync(ctx: &Context, client: FetchClient) -> Result<()> {
@@ -237,3 +339,67 @@ pub fn inject_fetch_sync(ctx: &Context, client: FetchClient) -> Result<()> {
         Ok(())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocked_fetch_is_reported() {
+        let log = ViolationLog::default();
+        let client = FetchClient::new(None, Some("https://example.com".to_string()))
+            .with_network_policy(NetworkPolicy::SameOrigin, log.clone())
+            .unwrap();
+
+        let err = client
+            .fetch_sync("https://tracker.example.net/pixel".to_string())
+            .unwrap_err();
+        assert!(err.to_string().contains("same-origin"));
+        assert!(client.get_fetch_log().is_empty());
+        assert_eq!(
+            log.snapshot(),
+            vec![SandboxViolation::Network {
+                url: "https://tracker.example.net/pixel".to_string(),
+                policy: "same-origin".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_redirect_out_of_policy_is_blocked() {
+        use std::io::{Read, Write};
+
+        // Any connection would wait in the backlog, so accept() tells if one came
+        let elsewhere = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        elsewhere.set_nonblocking(true).unwrap();
+        let target = format!("http://{}/secret", elsewhere.local_addr().unwrap());
+
+        // The page's own origin, redirecting /api to another origin
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let location = target.clone();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.read(&mut [0u8; 4096]);
+                let response = format!(
+                    "HTTP/1.1 302 Found\r\nLocation: {location}\r\nContent-Length: 0\r\n\r\n"
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        let log = ViolationLog::default();
+        let client = FetchClient::new(None, Some(base_url))
+            .with_network_policy(NetworkPolicy::SameOrigin, log.clone())
+            .unwrap();
+        assert!(client.fetch_sync("/api".to_string()).is_err());
+        assert!(elsewhere.accept().is_err(), "the redirect was followed");
+        assert_eq!(
+            log.snapshot(),
+            vec![SandboxViolation::Network {
+                url: target,
+                policy: "same-origin".to_string(),
+            }]
+        );
+    }
+}
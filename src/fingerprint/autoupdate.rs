@@ -3,7 +3,7 @@
 
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const UPDATE_THRESHOLD_DAYS: i64 = 14; // Chrome releases every 4 weeks, check every 2 weeks
 const SAFARI_STALE_THRESHOLD_DAYS: i64 = 180; // Safari updates quarterly
@@ -19,11 +19,25 @@ pub struct BrowserVersions {
 
 impl BrowserVersions {
     /// Load versions from cache or fetch updates if stale
+    ///
+    /// Without the `fingerprint-autoupdate` feature this never touches the
+    /// network: the cached versions are used however old, else the defaults.
     #[must_use]
     pub fn load_or_update() -> Self {
         let config_path = Self::config_path();
+        if !cfg!(feature = "fingerprint-autoupdate") {
+            return Self::load_from_file(&config_path).unwrap_or_default();
+        }
+
+        if let Ok(config) = Self::load_from_file(&config_path) {
+            if !config.is_stale() {
+                return config;
+            }
+        }
 
-        // Try to load existing config
+        // Refresh under the lock, so concurrent nab processes fetch once and
+        // those that waited pick up the new file
+        let _lock = crate::state::lock(&config_path, crate::state::LOCK_WAIT).ok();
         if let Ok(config) = Self::load_from_file(&config_path) {
             // Check if stale (>14 days old to match Chrome release cycle)
             if config.is_stale() {
@@ -193,7 +207,10 @@ impl BrowserVersions {
                 std::thread::sleep(std::time::Duration::from_millis(delay_ms));
             }
 
-            match reqwest::blocking::get(url) {
+            let response = crate::policy::blocking_client_builder()
+                .build()
+                .and_then(|client| client.get(url).send());
+            match response {
                 Ok(resp) => match resp.error_for_status() {
                     Ok(resp) => match resp.json::<serde_json::Value>() {
                         Ok(json) => return Ok(json),
@@ -243,28 +260,23 @@ impl BrowserVersions {
     }
 
     fn config_path() -> PathBuf {
-        dirs::config_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("nab")
-            .join("versions.json")
+        crate::workspace::config_dir().join("versions.json")
     }
 
-    fn load_from_file(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = std::fs::read_to_string(path)?;
-        let config: BrowserVersions = serde_json::from_str(&content)?;
-        Ok(config)
+    fn load_from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(crate::state::load(path)?.ok_or("No cached browser versions")?)
     }
 
-    fn save_to_file(&self, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        let content = serde_json::to_string_pretty(self)?;
-        std::fs::write(path, content)?;
+    fn save_to_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        crate::state::save(path, self)?;
         Ok(())
     }
 }
 
+impl crate::state::Versioned for BrowserVersions {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
 impl Default for BrowserVersions {
     fn default() -> Self {
         let now = Utc::now();
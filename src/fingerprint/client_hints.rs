@@ -0,0 +1,201 @@
+//! User-Agent Client Hints
+//!
+//! Chromium sends the low-entropy hints (`Sec-CH-UA`, `Sec-CH-UA-Mobile`,
+//! `Sec-CH-UA-Platform`) with every request, and the high-entropy ones (full
+//! version, platform version, architecture, model, ...) only to origins that
+//! ask for them with `Accept-CH`. When a response's `Critical-CH` names an
+//! accepted hint the request didn't carry, the browser retries once with it.
+//!
+//! Values are generated together with the profile, so they agree with its
+//! User-Agent and `Sec-CH-UA*` headers.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use super::{BrowserProfile, Platform};
+
+/// Hints sent without being asked for
+pub const LOW_ENTROPY: &[&str] = &["sec-ch-ua", "sec-ch-ua-mobile", "sec-ch-ua-platform"];
+
+/// High-entropy hint values of a Chromium profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientHints {
+    /// Brands with full versions, in `Sec-CH-UA` order
+    pub full_version_list: String,
+    pub full_version: String,
+    pub platform_version: String,
+    pub arch: String,
+    pub bitness: String,
+    /// Empty on desktop
+    pub model: String,
+    pub wow64: bool,
+}
+
+impl ClientHints {
+    /// Hints for a Chromium build on `platform`; `brands` are (brand, full version)
+    pub(super) fn generate<R: Rng + ?Sized>(
+        rng: &mut R,
+        platform: Platform,
+        brands: &[(String, String)],
+        full_version: &str,
+    ) -> Self {
+        let (versions, arches): (&[&str], &[&str]) = match platform {
+            // Windows 10 reports 0-10, Windows 11 13 and up
+            Platform::Windows => (&["10.0.0", "15.0.0", "19.0.0"], &["x86"]),
+            Platform::MacOS => (&["14.6.1", "15.1.0", "15.3.2"], &["arm", "arm", "x86"]),
+            Platform::Linux => (&["6.5.0", "6.8.0", "6.11.0"], &["x86"]),
+        };
+        Self {
+            full_version_list: brands
+                .iter()
+                .map(|(brand, version)| format!("\"{brand}\";v=\"{version}\""))
+                .collect::<Vec<_>>()
+                .join(", "),
+            full_version: full_version.to_string(),
+            platform_version: (*versions.choose(rng).unwrap()).to_string(),
+            arch: (*arches.choose(rng).unwrap()).to_string(),
+            bitness: "64".to_string(),
+            model: String::new(),
+            wow64: false,
+        }
+    }
+}
+
+/// Hints named in a response's `Accept-CH` or `Critical-CH` header, lowercased
+#[must_use]
+pub fn requested(headers: &HeaderMap, header: &str) -> Vec<String> {
+    let mut hints: Vec<String> = Vec::new();
+    for value in headers
+        .get_all(header)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+    {
+        for hint in value.split(',').map(|h| h.trim().to_ascii_lowercase()) {
+            if !hint.is_empty() && !hints.contains(&hint) {
+                hints.push(hint);
+            }
+        }
+    }
+    hints
+}
+
+impl BrowserProfile {
+    /// Value this profile sends for a client hint, `None` if it doesn't send it
+    #[must_use]
+    pub fn client_hint(&self, name: &str) -> Option<String> {
+        let hints = self.client_hints.as_ref()?;
+        let quoted = |value: &str| format!("\"{value}\"");
+        let value = match name.to_ascii_lowercase().as_str() {
+            "sec-ch-ua" => self.sec_ch_ua.clone(),
+            "sec-ch-ua-mobile" => self.sec_ch_ua_mobile.clone(),
+            "sec-ch-ua-platform" => self.sec_ch_ua_platform.clone(),
+            "sec-ch-ua-full-version-list" => hints.full_version_list.clone(),
+            "sec-ch-ua-full-version" => quoted(&hints.full_version),
+            "sec-ch-ua-platform-version" => quoted(&hints.platform_version),
+            "sec-ch-ua-arch" => quoted(&hints.arch),
+            "sec-ch-ua-bitness" => quoted(&hints.bitness),
+            "sec-ch-ua-model" => quoted(&hints.model),
+            "sec-ch-ua-wow64" => if hints.wow64 { "?1" } else { "?0" }.to_string(),
+            "sec-ch-ua-form-factors" => quoted("Desktop"),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Headers for the high-entropy hints among `names` this profile sends
+    #[must_use]
+    pub fn client_hint_headers(&self, names: &[String]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for name in names {
+            if LOW_ENTROPY.contains(&name.as_str()) {
+                continue;
+            }
+            let Some(value) = self.client_hint(name) else {
+                continue;
+            };
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        headers
+    }
+
+    /// Hints to retry a request with, when its response marks a hint that
+    /// wasn't among `sent` as critical (`Critical-CH`)
+    ///
+    /// Like Chromium, the retry carries every accepted hint the profile has.
+    #[must_use]
+    pub fn critical_hints_retry(
+        &self,
+        response: &HeaderMap,
+        sent: &[String],
+    ) -> Option<Vec<String>> {
+        let accepted: Vec<String> = requested(response, "accept-ch")
+            .into_iter()
+            .filter(|hint| self.client_hint(hint).is_some())
+            .collect();
+        let missing = requested(response, "critical-ch").into_iter().any(|hint| {
+            accepted.contains(&hint)
+                && !sent.contains(&hint)
+                && !LOW_ENTROPY.contains(&hint.as_str())
+        });
+        missing.then_some(accepted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::{chrome_profile, firefox_profile};
+
+    fn response(accept: &str, critical: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-ch", HeaderValue::from_str(accept).unwrap());
+        headers.insert("critical-ch", HeaderValue::from_str(critical).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_hints_match_profile() {
+        let profile = chrome_profile();
+        let full_list = profile.client_hint("Sec-CH-UA-Full-Version-List").unwrap();
+        // Same brands, in the same order, as the low-entropy list
+        let brands = |list: &str| {
+            list.split(", ")
+                .map(|b| b.split(';').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(brands(&full_list), brands(&profile.sec_ch_ua));
+        let full_version = profile.client_hint("sec-ch-ua-full-version").unwrap();
+        assert!(profile.user_agent.contains(full_version.trim_matches('"')));
+        assert_eq!(profile.client_hint("sec-ch-ua-model").unwrap(), "\"\"");
+        assert_eq!(profile.client_hint("device-memory"), None);
+        assert_eq!(firefox_profile().client_hint("sec-ch-ua-arch"), None);
+    }
+
+    #[test]
+    fn test_critical_retry() {
+        let profile = chrome_profile();
+        let headers = response(
+            "Sec-CH-UA-Arch, Sec-CH-UA-Platform-Version, Viewport-Width",
+            "Sec-CH-UA-Platform-Version",
+        );
+        let hints = profile.critical_hints_retry(&headers, &[]).unwrap();
+        assert_eq!(hints, ["sec-ch-ua-arch", "sec-ch-ua-platform-version"]);
+        let retry = profile.client_hint_headers(&hints);
+        assert_eq!(retry.len(), 2);
+        assert!(retry.contains_key("sec-ch-ua-platform-version"));
+
+        // Already sent, only low-entropy, or not accepted: no retry
+        assert_eq!(profile.critical_hints_retry(&headers, &hints), None);
+        let low = response("Sec-CH-UA-Platform", "Sec-CH-UA-Platform");
+        assert_eq!(profile.critical_hints_retry(&low, &[]), None);
+        let unaccepted = response("Sec-CH-UA-Arch", "Sec-CH-UA-Model");
+        assert_eq!(profile.critical_hints_retry(&unaccepted, &[]), None);
+        assert_eq!(firefox_profile().critical_hints_retry(&headers, &[]), None);
+    }
+}
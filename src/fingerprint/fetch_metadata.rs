@@ -0,0 +1,111 @@
+//! Fetch Metadata by Request Context
+//!
+//! Browsers describe every request with `Sec-Fetch-Dest`, `Sec-Fetch-Mode`,
+//! and `Sec-Fetch-Site`, plus `Sec-Fetch-User` and `Upgrade-Insecure-Requests`
+//! on navigations the user started. A page load, a script's `fetch()`, and an
+//! image differ in all of them, and navigation headers on an API call are an
+//! easy bot tell, so [`NAVIGATION_ONLY`] headers are never set client-wide.
+//! Safari doesn't send `Sec-Fetch-User`.
+
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, UPGRADE_INSECURE_REQUESTS};
+
+use super::BrowserProfile;
+
+/// Headers only sent with page navigations
+pub const NAVIGATION_ONLY: &[&str] = &["sec-fetch-user", "upgrade-insecure-requests"];
+
+/// What a request is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchContext {
+    /// Top-level page load the user started (link, address bar, bookmark)
+    Navigate,
+    /// `fetch()` or XHR from a page, e.g. a replayed API call
+    Api,
+    /// Resource a page loads, by `Sec-Fetch-Dest` (`image`, `style`, `font`, ...)
+    Subresource(&'static str),
+}
+
+impl BrowserProfile {
+    /// `Sec-Fetch-*` headers for `context`, with `site` as `Sec-Fetch-Site`
+    ///
+    /// Navigations also get `Upgrade-Insecure-Requests`, API calls the
+    /// `Accept: */*` of `fetch()`.
+    #[must_use]
+    pub fn fetch_metadata(&self, context: FetchContext, site: &'static str) -> HeaderMap {
+        let (dest, mode) = match context {
+            FetchContext::Navigate => ("document", "navigate"),
+            FetchContext::Api => ("empty", "cors"),
+            FetchContext::Subresource(dest @ "font") => (dest, "cors"),
+            FetchContext::Subresource(dest) => (dest, "no-cors"),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("Sec-Fetch-Site", HeaderValue::from_static(site));
+        headers.insert("Sec-Fetch-Mode", HeaderValue::from_static(mode));
+        headers.insert("Sec-Fetch-Dest", HeaderValue::from_static(dest));
+        match context {
+            FetchContext::Navigate => {
+                if !self.sec_fetch_user.is_empty() {
+                    if let Ok(user) = HeaderValue::from_str(&self.sec_fetch_user) {
+                        headers.insert("Sec-Fetch-User", user);
+                    }
+                }
+                headers.insert(UPGRADE_INSECURE_REQUESTS, HeaderValue::from_static("1"));
+            }
+            FetchContext::Api => {
+                headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+            }
+            FetchContext::Subresource(_) => {}
+        }
+        headers
+    }
+
+    /// The profile's headers ([`Self::to_headers`]) for a request in `context`
+    #[must_use]
+    pub fn request_headers(&self, context: FetchContext, site: &'static str) -> HeaderMap {
+        let mut headers = self.client_headers();
+        headers.extend(self.fetch_metadata(context, site));
+        headers
+    }
+
+    /// Headers to set on a whole client: [`Self::to_headers`] without the
+    /// navigation-only ones, which couldn't be left out per request
+    #[must_use]
+    pub fn client_headers(&self) -> HeaderMap {
+        let mut headers = self.to_headers();
+        for name in NAVIGATION_ONLY {
+            headers.remove(*name);
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::{chrome_profile, safari_profile};
+
+    #[test]
+    fn test_contexts() {
+        let chrome = chrome_profile();
+        let page = chrome.request_headers(FetchContext::Navigate, "none");
+        assert_eq!(page["Sec-Fetch-Mode"], "navigate");
+        assert_eq!(page["Sec-Fetch-User"], "?1");
+        assert_eq!(page[UPGRADE_INSECURE_REQUESTS], "1");
+
+        let api = chrome.request_headers(FetchContext::Api, "same-origin");
+        assert_eq!(api["Sec-Fetch-Dest"], "empty");
+        assert_eq!(api["Sec-Fetch-Mode"], "cors");
+        assert_eq!(api["Sec-Fetch-Site"], "same-origin");
+        assert_eq!(api[ACCEPT], "*/*");
+        assert!(!api.contains_key("Sec-Fetch-User"));
+        assert!(!api.contains_key(UPGRADE_INSECURE_REQUESTS));
+
+        let font = chrome.fetch_metadata(FetchContext::Subresource("font"), "cross-site");
+        assert_eq!(font["Sec-Fetch-Mode"], "cors");
+        assert!(!chrome.client_headers().contains_key("Sec-Fetch-User"));
+
+        let safari = safari_profile().request_headers(FetchContext::Navigate, "none");
+        assert!(!safari.contains_key("Sec-Fetch-User"));
+        assert_eq!(safari["Sec-Fetch-Dest"], "document");
+    }
+}
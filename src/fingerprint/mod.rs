@@ -2,14 +2,27 @@
 //!
 //! Generates realistic browser fingerprints to avoid detection.
 //! Based on real browser statistics and anti-fingerprinting research.
+//!
+//! Choices are random by default. After [`seed`] (`--seed`), every profile
+//! comes from one seeded generator, so a run can be reproduced exactly (given
+//! the same browser version data).
 
 pub mod autoupdate;
+pub mod client_hints;
+pub mod fetch_metadata;
+pub mod verify;
+
+pub use fetch_metadata::FetchContext;
+
+use std::sync::{Mutex, PoisonError};
 
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::{Rng, RngCore, SeedableRng};
 use reqwest::header::{
     HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, USER_AGENT,
 };
+use sha2::{Digest, Sha256};
 
 // Load versions once on first use (auto-updates if stale)
 static BROWSER_VERSIONS: std::sync::LazyLock<autoupdate::BrowserVersions> =
@@ -25,12 +38,32 @@ pub struct BrowserProfile {
     pub sec_ch_ua: String,
     pub sec_ch_ua_mobile: String,
     pub sec_ch_ua_platform: String,
+    /// High-entropy client hints, sent when a site asks (Chromium only)
+    pub client_hints: Option<client_hints::ClientHints>,
     pub sec_fetch_dest: String,
     pub sec_fetch_mode: String,
     pub sec_fetch_site: String,
     pub sec_fetch_user: String,
 }
 
+/// Generator behind all profile choices once [`seed`] was called
+static SEEDED_RNG: Mutex<Option<StdRng>> = Mutex::new(None);
+
+/// Make profile generation deterministic: after `seed(n)`, the sequence of
+/// generated profiles is the same in every run
+pub fn seed(seed: u64) {
+    *SEEDED_RNG.lock().unwrap_or_else(PoisonError::into_inner) = Some(StdRng::seed_from_u64(seed));
+}
+
+/// Run `f` with the seeded generator, or the thread's random one
+fn with_rng<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    let mut seeded = SEEDED_RNG.lock().unwrap_or_else(PoisonError::into_inner);
+    match seeded.as_mut() {
+        Some(rng) => f(rng),
+        None => f(&mut rand::thread_rng()),
+    }
+}
+
 // Browser versions now loaded dynamically via BROWSER_VERSIONS lazy static
 // Auto-updates from official APIs when >30 days old
 
@@ -43,8 +76,7 @@ pub enum Platform {
 }
 
 impl Platform {
-    fn random() -> Self {
-        let mut rng = rand::thread_rng();
+    fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
         // Realistic distribution: Windows 65%, macOS 20%, Linux 15%
         let roll: f32 = rng.gen();
         if roll < 0.65 {
@@ -76,9 +108,12 @@ impl Platform {
 /// Generate a realistic Chrome browser profile
 #[must_use]
 pub fn chrome_profile() -> BrowserProfile {
-    let mut rng = rand::thread_rng();
-    let platform = Platform::random();
-    let (major, full) = BROWSER_VERSIONS.chrome.choose(&mut rng).unwrap();
+    with_rng(|rng| chrome_with(rng))
+}
+
+fn chrome_with<R: Rng + ?Sized>(rng: &mut R) -> BrowserProfile {
+    let platform = Platform::random(rng);
+    let (_, full) = BROWSER_VERSIONS.chrome.choose(rng).unwrap();
 
     let user_agent = format!(
         "Mozilla/5.0 ({}) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/{} Safari/537.36",
@@ -88,19 +123,29 @@ pub fn chrome_profile() -> BrowserProfile {
 
     // Realistic Sec-CH-UA with brand ordering variation
     let brands = [
-        format!("\"Google Chrome\";v=\"{major}\""),
-        format!("\"Chromium\";v=\"{major}\""),
-        "\"Not_A Brand\";v=\"24\"".to_string(),
+        ("Google Chrome".to_string(), full.clone()),
+        ("Chromium".to_string(), full.clone()),
+        ("Not_A Brand".to_string(), "24.0.0.0".to_string()),
     ];
+    let sec_ch_ua = brands
+        .iter()
+        .map(|(brand, version)| {
+            let major = version.split('.').next().unwrap_or_default();
+            format!("\"{brand}\";v=\"{major}\"")
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let client_hints = client_hints::ClientHints::generate(rng, platform, &brands, full);
 
     BrowserProfile {
         user_agent,
         accept: "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7".to_string(),
-        accept_language: random_accept_language(),
+        accept_language: random_accept_language(rng),
         accept_encoding: "gzip, deflate, br, zstd".to_string(),
-        sec_ch_ua: brands.join(", "),
+        sec_ch_ua,
         sec_ch_ua_mobile: "?0".to_string(),
         sec_ch_ua_platform: platform.sec_ch_platform().to_string(),
+        client_hints: Some(client_hints),
         sec_fetch_dest: "document".to_string(),
         sec_fetch_mode: "navigate".to_string(),
         sec_fetch_site: "none".to_string(),
@@ -111,9 +156,12 @@ pub fn chrome_profile() -> BrowserProfile {
 /// Generate a realistic Firefox browser profile
 #[must_use]
 pub fn firefox_profile() -> BrowserProfile {
-    let mut rng = rand::thread_rng();
-    let platform = Platform::random();
-    let version = BROWSER_VERSIONS.firefox.choose(&mut rng).unwrap();
+    with_rng(|rng| firefox_with(rng))
+}
+
+fn firefox_with<R: Rng + ?Sized>(rng: &mut R) -> BrowserProfile {
+    let platform = Platform::random(rng);
+    let version = BROWSER_VERSIONS.firefox.choose(rng).unwrap();
 
     let user_agent = format!(
         "Mozilla/5.0 ({}; rv:{}) Gecko/20100101 Firefox/{}",
@@ -127,12 +175,13 @@ pub fn firefox_profile() -> BrowserProfile {
         accept:
             "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8"
                 .to_string(),
-        accept_language: random_accept_language(),
+        accept_language: random_accept_language(rng),
         accept_encoding: "gzip, deflate, br, zstd".to_string(),
         // Firefox doesn't send Sec-CH-UA headers
         sec_ch_ua: String::new(),
         sec_ch_ua_mobile: String::new(),
         sec_ch_ua_platform: String::new(),
+        client_hints: None,
         sec_fetch_dest: "document".to_string(),
         sec_fetch_mode: "navigate".to_string(),
         sec_fetch_site: "none".to_string(),
@@ -143,8 +192,11 @@ pub fn firefox_profile() -> BrowserProfile {
 /// Generate a realistic Safari browser profile
 #[must_use]
 pub fn safari_profile() -> BrowserProfile {
-    let mut rng = rand::thread_rng();
-    let (version, webkit) = BROWSER_VERSIONS.safari.choose(&mut rng).unwrap();
+    with_rng(|rng| safari_with(rng))
+}
+
+fn safari_with<R: Rng + ?Sized>(rng: &mut R) -> BrowserProfile {
+    let (version, webkit) = BROWSER_VERSIONS.safari.choose(rng).unwrap();
 
     // Safari only runs on macOS/iOS - always use macOS for desktop
     let user_agent = format!(
@@ -154,37 +206,51 @@ pub fn safari_profile() -> BrowserProfile {
     BrowserProfile {
         user_agent,
         accept: "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8".to_string(),
-        accept_language: random_accept_language(),
+        accept_language: random_accept_language(rng),
         accept_encoding: "gzip, deflate, br".to_string(), // Safari doesn't support zstd yet
         // Safari doesn't send Sec-CH-UA headers
         sec_ch_ua: String::new(),
         sec_ch_ua_mobile: String::new(),
         sec_ch_ua_platform: String::new(),
+        client_hints: None,
         sec_fetch_dest: "document".to_string(),
         sec_fetch_mode: "navigate".to_string(),
         sec_fetch_site: "none".to_string(),
-        sec_fetch_user: "?1".to_string(),
+        // Safari doesn't send Sec-Fetch-User
+        sec_fetch_user: String::new(),
     }
 }
 
 /// Generate a random browser profile (weighted by market share)
 #[must_use]
 pub fn random_profile() -> BrowserProfile {
-    let mut rng = rand::thread_rng();
+    with_rng(|rng| random_with(rng))
+}
+
+fn random_with<R: Rng + ?Sized>(rng: &mut R) -> BrowserProfile {
     // Realistic distribution: Chrome 65%, Safari 20%, Firefox 10%, Edge 5%
     let roll: f32 = rng.gen();
     if roll < 0.65 {
-        chrome_profile()
+        chrome_with(rng)
     } else if roll < 0.85 {
-        safari_profile()
+        safari_with(rng)
     } else {
-        firefox_profile()
+        firefox_with(rng)
     }
 }
 
+/// Profile of a named persona: the same browser identity on every run
+/// (given the same browser version data)
+#[must_use]
+pub fn persona_profile(name: &str) -> BrowserProfile {
+    let digest = Sha256::digest(name.as_bytes());
+    let mut seed = [0; 8];
+    seed.copy_from_slice(&digest[..8]);
+    random_with(&mut StdRng::seed_from_u64(u64::from_le_bytes(seed)))
+}
+
 /// Generate random Accept-Language header
-fn random_accept_language() -> String {
-    let mut rng = rand::thread_rng();
+fn random_accept_language<R: Rng + ?Sized>(rng: &mut R) -> String {
     let languages = [
         "en-US,en;q=0.9",
         "en-GB,en;q=0.9",
@@ -194,7 +260,7 @@ fn random_accept_language() -> String {
         "en-US,en;q=0.9,ja;q=0.8",
         "fi-FI,fi;q=0.9,en;q=0.8",
     ];
-    (*languages.choose(&mut rng).unwrap()).to_string()
+    (*languages.choose(rng).unwrap()).to_string()
 }
 
 impl BrowserProfile {
@@ -239,10 +305,12 @@ impl BrowserProfile {
             "Sec-Fetch-Site",
             HeaderValue::from_str(&self.sec_fetch_site).unwrap(),
         );
-        headers.insert(
-            "Sec-Fetch-User",
-            HeaderValue::from_str(&self.sec_fetch_user).unwrap(),
-        );
+        if !self.sec_fetch_user.is_empty() {
+            headers.insert(
+                "Sec-Fetch-User",
+                HeaderValue::from_str(&self.sec_fetch_user).unwrap(),
+            );
+        }
 
         // Additional headers that real browsers send
         headers.insert("Upgrade-Insecure-Requests", HeaderValue::from_static("1"));
@@ -277,6 +345,29 @@ mod tests {
         assert!(profile.user_agent.contains("Macintosh"));
     }
 
+    #[test]
+    fn test_seeded_profiles_repeat() {
+        let generate = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..8)
+                .map(|_| {
+                    let profile = random_with(&mut rng);
+                    format!("{} | {}", profile.user_agent, profile.accept_language)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(generate(42), generate(42));
+        assert_ne!(generate(42), generate(43));
+    }
+
+    #[test]
+    fn test_persona_profile_is_stable() {
+        assert_eq!(
+            persona_profile("alice").user_agent,
+            persona_profile("alice").user_agent
+        );
+    }
+
     #[test]
     fn test_headers_conversion() {
         let profile = random_profile();
@@ -0,0 +1,309 @@
+//! Fingerprint Verification (`nab fingerprint verify`)
+//!
+//! Sends a profile's requests to echo endpoints and compares what arrived
+//! with what was meant to be sent: header values, header order, the HTTP
+//! version, and headers added on the way (proxies adding `Via` or
+//! `X-Forwarded-For`). Endpoints reporting the TLS handshake (`ja3`, `ja4`)
+//! have those shown too; nab's TLS stack is rustls, so there is no intended
+//! browser TLS fingerprint to compare them with.
+//!
+//! Understood echo formats:
+//! - `{"headers": {"Name": "value", ...}}` (httpbin and compatibles)
+//! - `{"headers": ["Name: value", ...]}` or `[["Name", "value"], ...]`
+//! - `tls.peet.ws/api/all`: `http1.headers`, HTTP/2 `HEADERS` frames, `tls.*`
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::BrowserProfile;
+
+/// Echo endpoints used when none are configured
+pub const DEFAULT_ENDPOINTS: &[&str] =
+    &["https://httpbin.org/headers", "https://tls.peet.ws/api/all"];
+
+/// Headers proxies and gateways add, which browsers never send
+const LEAKY_HEADERS: &[&str] = &[
+    "via",
+    "x-forwarded-for",
+    "forwarded",
+    "x-real-ip",
+    "x-proxy-id",
+];
+
+/// What an echo endpoint saw
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Observation {
+    /// Headers as received; in wire order when `ordered`
+    pub headers: Vec<(String, String)>,
+    pub ordered: bool,
+    /// `HTTP/1.1`, `h2`, ... when reported
+    pub http_version: Option<String>,
+    /// TLS and HTTP/2 fingerprints reported by the endpoint (`ja3_hash`, `ja4`, ...)
+    pub fingerprints: Vec<(String, String)>,
+}
+
+/// Difference between the intended and the observed fingerprint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Mismatch {
+    /// Header sent with another value
+    Changed {
+        header: String,
+        expected: String,
+        observed: String,
+    },
+    /// Header that didn't arrive
+    Missing { header: String },
+    /// Header added on the way
+    Added { header: String, observed: String },
+    /// Headers arrived in another order
+    Order {
+        expected: Vec<String>,
+        observed: Vec<String>,
+    },
+    /// Browsers talk HTTP/2; the endpoint saw something else
+    HttpVersion { observed: String },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Changed {
+                header,
+                expected,
+                observed,
+            } => write!(f, "{header}: sent '{expected}', observed '{observed}'"),
+            Self::Missing { header } => write!(f, "{header}: sent but not observed"),
+            Self::Added { header, observed } => {
+                write!(f, "{header}: added on the way ('{observed}')")
+            }
+            Self::Order { expected, observed } => write!(
+                f,
+                "header order: sent {}, observed {}",
+                expected.join(", "),
+                observed.join(", ")
+            ),
+            Self::HttpVersion { observed } => {
+                write!(f, "HTTP version: browsers use HTTP/2, observed {observed}")
+            }
+        }
+    }
+}
+
+impl Observation {
+    /// Read an echo endpoint's JSON answer
+    #[must_use]
+    pub fn parse(body: &Value) -> Self {
+        let mut observation = Self::default();
+
+        // tls.peet.ws: HTTP/2 HEADERS frames or HTTP/1 header lines, in wire order
+        let frames = body["http2"]["sent_frames"].as_array();
+        let h2_headers = frames.and_then(|frames| {
+            frames
+                .iter()
+                .find(|frame| frame["frame_type"] == "HEADERS")
+                .map(|frame| &frame["headers"])
+        });
+        if let Some(lines) = h2_headers
+            .or(Some(&body["http1"]["headers"]))
+            .and_then(Value::as_array)
+        {
+            observation.headers = header_list(lines);
+            observation.ordered = true;
+        }
+        if observation.headers.is_empty() {
+            match &body["headers"] {
+                Value::Object(map) => {
+                    observation.headers = map
+                        .iter()
+                        .filter_map(|(name, value)| {
+                            Some((name.clone(), value.as_str()?.to_string()))
+                        })
+                        .collect();
+                }
+                Value::Array(lines) => {
+                    observation.headers = header_list(lines);
+                    observation.ordered = true;
+                }
+                _ => {}
+            }
+        }
+
+        observation.http_version = body["http_version"].as_str().map(String::from);
+        for (section, keys) in [
+            ("tls", &["ja3_hash", "ja4", "peetprint_hash"][..]),
+            ("http2", &["akamai_fingerprint_hash"][..]),
+        ] {
+            for key in keys {
+                if let Some(value) = body[section][key].as_str() {
+                    observation
+                        .fingerprints
+                        .push(((*key).to_string(), value.to_string()));
+                }
+            }
+        }
+        observation
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// `["Name: value", ...]` or `[["Name", "value"], ...]`, without HTTP/2 pseudo-headers
+fn header_list(lines: &[Value]) -> Vec<(String, String)> {
+    lines
+        .iter()
+        .filter_map(|line| match line {
+            Value::String(line) => line
+                .split_once(':')
+                .filter(|(name, _)| !name.is_empty())
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string())),
+            Value::Array(pair) => Some((
+                pair.first()?.as_str()?.to_string(),
+                pair.get(1)?.as_str()?.to_string(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Compare what `profile` sends with what an endpoint observed
+#[must_use]
+pub fn compare(profile: &BrowserProfile, observed: &Observation) -> Vec<Mismatch> {
+    let intended: Vec<(String, String)> = profile
+        .to_headers()
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect();
+    let mut mismatches = Vec::new();
+
+    for (name, expected) in &intended {
+        match observed.get(name) {
+            None => mismatches.push(Mismatch::Missing {
+                header: name.clone(),
+            }),
+            Some(value) if value != expected => mismatches.push(Mismatch::Changed {
+                header: name.clone(),
+                expected: expected.clone(),
+                observed: value.to_string(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (name, value) in &observed.headers {
+        if LEAKY_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            mismatches.push(Mismatch::Added {
+                header: name.clone(),
+                observed: value.clone(),
+            });
+        }
+    }
+
+    if observed.ordered {
+        let observed_order: Vec<String> = observed
+            .headers
+            .iter()
+            .map(|(name, _)| name.to_ascii_lowercase())
+            .filter(|name| intended.iter().any(|(n, _)| n == name))
+            .collect();
+        let expected_order: Vec<String> = intended
+            .iter()
+            .map(|(name, _)| name.clone())
+            .filter(|name| observed_order.contains(name))
+            .collect();
+        if observed_order != expected_order {
+            mismatches.push(Mismatch::Order {
+                expected: expected_order,
+                observed: observed_order,
+            });
+        }
+    }
+
+    // `h2`, `HTTP/2`, `HTTP/2.0`
+    if let Some(version) = observed.http_version.as_ref().filter(|v| !v.contains('2')) {
+        mismatches.push(Mismatch::HttpVersion {
+            observed: version.clone(),
+        });
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::firefox_profile;
+
+    #[test]
+    fn test_compare_httpbin() {
+        let profile = firefox_profile();
+        let mut headers = serde_json::Map::new();
+        for (name, value) in &profile.to_headers() {
+            headers.insert(name.to_string(), value.to_str().unwrap().into());
+        }
+        headers.insert("accept-language".into(), "en".into());
+        headers.remove("cache-control");
+        headers.insert("Via".into(), "1.1 squid".into());
+        let observed = Observation::parse(&serde_json::json!({ "headers": headers }));
+        assert!(!observed.ordered);
+
+        let mismatches = compare(&profile, &observed);
+        assert_eq!(mismatches.len(), 3, "{mismatches:?}");
+        assert!(
+            matches!(&mismatches[0], Mismatch::Changed { header, observed, .. }
+            if header == "accept-language" && observed == "en")
+        );
+        assert_eq!(
+            mismatches[1],
+            Mismatch::Missing {
+                header: "cache-control".into()
+            }
+        );
+        assert!(mismatches[2].to_string().contains("Via: added on the way"));
+    }
+
+    #[test]
+    fn test_compare_ordered_h2() {
+        let profile = firefox_profile();
+        let mut lines: Vec<String> = profile
+            .to_headers()
+            .iter()
+            .map(|(name, value)| format!("{name}: {}", value.to_str().unwrap()))
+            .collect();
+        lines.swap(0, 1);
+        lines.insert(0, ":method: GET".to_string());
+        let observed = Observation::parse(&serde_json::json!({
+            "http_version": "h2",
+            "http2": {"sent_frames": [{"frame_type": "SETTINGS"}, {"frame_type": "HEADERS", "headers": lines}],
+                      "akamai_fingerprint_hash": "abc"},
+            "tls": {"ja4": "t13d1312h2_x_y"}
+        }));
+        assert!(observed.ordered);
+        assert_eq!(
+            observed.fingerprints[0],
+            ("ja4".into(), "t13d1312h2_x_y".into())
+        );
+
+        let mismatches = compare(&profile, &observed);
+        assert_eq!(mismatches.len(), 1, "{mismatches:?}");
+        assert!(matches!(mismatches[0], Mismatch::Order { .. }));
+
+        let http1 = Observation {
+            http_version: Some("HTTP/1.1".into()),
+            ..observed
+        };
+        assert!(compare(&profile, &http1).contains(&Mismatch::HttpVersion {
+            observed: "HTTP/1.1".into()
+        }));
+    }
+}
@@ -0,0 +1,203 @@
+//! Geo-Targeting (`--geo`)
+//!
+//! Many shops show prices, stock, and whole pages by visitor region. `--geo de`
+//! makes a fetch look like it comes from Germany, consistently:
+//!
+//! - the exit: a proxy tagged `de` from the config's `proxy_pool`, picked at
+//!   random (an explicit `--proxy` or `--proxy-chain` is used as is)
+//! - `Accept-Language: de-DE,de;q=0.9,en;q=0.8` (an explicit
+//!   `Accept-Language` header still wins)
+//! - for rendered pages, `navigator.language(s)` and the `Intl` time zone
+//!
+//! The [`Geo`] used is recorded in `--format json` output, so captures of
+//! region variants say which region they are.
+//!
+//! ```json
+//! {
+//!   "proxy_pool": [
+//!     {"url": "secret:DE_PROXY", "country": "de"},
+//!     {"url": "socks5h://fr1.proxy.example:1080", "country": "FR"}
+//!   ]
+//! }
+//! ```
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::fingerprint::BrowserProfile;
+
+/// A proxy of the config's `proxy_pool`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolProxy {
+    /// Proxy URL; may be a `secret:NAME` reference
+    pub url: String,
+    /// ISO 3166-1 alpha-2 code of the egress country
+    pub country: String,
+}
+
+/// Country, language, and main time zone (ISO 3166-1, BCP 47, IANA)
+const REGIONS: &[(&str, &str, &str)] = &[
+    ("AE", "ar-AE", "Asia/Dubai"),
+    ("AR", "es-AR", "America/Argentina/Buenos_Aires"),
+    ("AT", "de-AT", "Europe/Vienna"),
+    ("AU", "en-AU", "Australia/Sydney"),
+    ("BE", "nl-BE", "Europe/Brussels"),
+    ("BR", "pt-BR", "America/Sao_Paulo"),
+    ("CA", "en-CA", "America/Toronto"),
+    ("CH", "de-CH", "Europe/Zurich"),
+    ("CN", "zh-CN", "Asia/Shanghai"),
+    ("CZ", "cs-CZ", "Europe/Prague"),
+    ("DE", "de-DE", "Europe/Berlin"),
+    ("DK", "da-DK", "Europe/Copenhagen"),
+    ("EE", "et-EE", "Europe/Tallinn"),
+    ("ES", "es-ES", "Europe/Madrid"),
+    ("FI", "fi-FI", "Europe/Helsinki"),
+    ("FR", "fr-FR", "Europe/Paris"),
+    ("GB", "en-GB", "Europe/London"),
+    ("GR", "el-GR", "Europe/Athens"),
+    ("HK", "zh-HK", "Asia/Hong_Kong"),
+    ("HU", "hu-HU", "Europe/Budapest"),
+    ("IE", "en-IE", "Europe/Dublin"),
+    ("IL", "he-IL", "Asia/Jerusalem"),
+    ("IN", "en-IN", "Asia/Kolkata"),
+    ("IT", "it-IT", "Europe/Rome"),
+    ("JP", "ja-JP", "Asia/Tokyo"),
+    ("KR", "ko-KR", "Asia/Seoul"),
+    ("MX", "es-MX", "America/Mexico_City"),
+    ("NL", "nl-NL", "Europe/Amsterdam"),
+    ("NO", "nb-NO", "Europe/Oslo"),
+    ("NZ", "en-NZ", "Pacific/Auckland"),
+    ("PL", "pl-PL", "Europe/Warsaw"),
+    ("PT", "pt-PT", "Europe/Lisbon"),
+    ("RO", "ro-RO", "Europe/Bucharest"),
+    ("SE", "sv-SE", "Europe/Stockholm"),
+    ("SG", "en-SG", "Asia/Singapore"),
+    ("TR", "tr-TR", "Europe/Istanbul"),
+    ("TW", "zh-TW", "Asia/Taipei"),
+    ("UA", "uk-UA", "Europe/Kyiv"),
+    ("US", "en-US", "America/New_York"),
+    ("ZA", "en-ZA", "Africa/Johannesburg"),
+];
+
+/// The region a request presents itself from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Geo {
+    /// ISO 3166-1 alpha-2 code, upper case
+    pub country: String,
+    /// BCP 47 language tag (`navigator.language`)
+    pub locale: String,
+    pub accept_language: String,
+    /// IANA time zone
+    pub timezone: String,
+    /// Proxy the requests leave through (password masked)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+}
+
+impl Geo {
+    /// The region of `country` (ISO code, any case; `uk` means `GB`)
+    #[must_use]
+    pub fn lookup(country: &str) -> Option<Self> {
+        let code = country.trim().to_ascii_uppercase();
+        let code = if code == "UK" { "GB".to_string() } else { code };
+        let &(country, locale, timezone) = REGIONS.iter().find(|(c, _, _)| *c == code)?;
+        Some(Self {
+            country: country.to_string(),
+            locale: locale.to_string(),
+            accept_language: accept_language(locale),
+            timezone: timezone.to_string(),
+            proxy: None,
+        })
+    }
+
+    /// Country codes [`Geo::lookup`] knows
+    pub fn countries() -> impl Iterator<Item = &'static str> {
+        REGIONS.iter().map(|(c, _, _)| *c)
+    }
+
+    /// A random `pool` proxy tagged with this country
+    #[must_use]
+    pub fn pick_proxy<'a>(&self, pool: &'a [PoolProxy]) -> Option<&'a PoolProxy> {
+        let tagged: Vec<&PoolProxy> = pool
+            .iter()
+            .filter(|p| Self::lookup(&p.country).is_some_and(|g| g.country == self.country))
+            .collect();
+        tagged.choose(&mut rand::thread_rng()).copied()
+    }
+
+    /// Give `profile` this region's `Accept-Language`
+    pub fn apply(&self, profile: &mut BrowserProfile) {
+        profile.accept_language.clone_from(&self.accept_language);
+    }
+
+    /// JavaScript giving a rendered page this region's language and time zone
+    #[must_use]
+    pub fn js_shim(&self) -> String {
+        let language = language(&self.locale);
+        format!(
+            r"
+            (function() {{
+                var g = (typeof window !== 'undefined') ? window : globalThis;
+                var nav = g.navigator || globalThis.navigator;
+                if (nav) {{
+                    nav.language = '{locale}';
+                    nav.languages = ['{locale}', '{language}', 'en'];
+                }}
+                if (typeof Intl !== 'undefined' && Intl.DateTimeFormat) {{
+                    var resolved = Intl.DateTimeFormat.prototype.resolvedOptions;
+                    Intl.DateTimeFormat.prototype.resolvedOptions = function() {{
+                        var options = resolved.call(this);
+                        options.timeZone = '{timezone}';
+                        options.locale = '{locale}';
+                        return options;
+                    }};
+                }}
+            }})();
+            ",
+            locale = self.locale,
+            timezone = self.timezone,
+        )
+    }
+}
+
+/// `de-DE` → `de-DE,de;q=0.9,en;q=0.8`
+fn accept_language(locale: &str) -> String {
+    match language(locale) {
+        "en" => format!("{locale},en;q=0.9"),
+        language => format!("{locale},{language};q=0.9,en;q=0.8"),
+    }
+}
+
+fn language(locale: &str) -> &str {
+    locale.split('-').next().unwrap_or(locale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_and_pool() {
+        let de = Geo::lookup("de").unwrap();
+        assert_eq!(de.locale, "de-DE");
+        assert_eq!(de.accept_language, "de-DE,de;q=0.9,en;q=0.8");
+        assert_eq!(de.timezone, "Europe/Berlin");
+        assert_eq!(Geo::lookup("uk").unwrap().accept_language, "en-GB,en;q=0.9");
+        assert!(Geo::lookup("xx").is_none());
+        assert!(de.js_shim().contains("'Europe/Berlin'"));
+
+        let pool = [
+            PoolProxy {
+                url: "http://fr.proxy:3128".into(),
+                country: "FR".into(),
+            },
+            PoolProxy {
+                url: "http://de.proxy:3128".into(),
+                country: "de".into(),
+            },
+        ];
+        assert_eq!(de.pick_proxy(&pool).unwrap().url, "http://de.proxy:3128");
+        assert!(Geo::lookup("JP").unwrap().pick_proxy(&pool).is_none());
+    }
+}
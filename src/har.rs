@@ -0,0 +1,338 @@
+//! HTTP Archive (HAR 1.2)
+//!
+//! `nab fetch --har FILE` records the page request and the subresources loaded
+//! with it. Bodies aren't stored, only their sizes, and credentials (`Cookie`,
+//! `Authorization`, ...) are replaced with `[redacted]` since cookies may come
+//! straight from the user's browser. The page entry lists the response's
+//! `Link` hints (preload, preconnect, ...) under `_linkHints`.
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+
+use crate::prefetch::EarlyHintLink;
+use crate::timing::ms;
+
+/// Headers whose values never end up in a HAR file
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "set-cookie",
+];
+
+/// HAR document (`{"log": ...}`)
+#[derive(Debug, Clone, Serialize)]
+pub struct Har {
+    log: HarLog,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct HarLog {
+    version: &'static str,
+    creator: NameVersion,
+    pages: Vec<HarPage>,
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct NameVersion {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarPage {
+    started_date_time: DateTime<Utc>,
+    id: &'static str,
+    title: String,
+    page_timings: serde_json::Value,
+}
+
+/// One request/response pair
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pageref: &'static str,
+    started_date_time: DateTime<Utc>,
+    /// Total time in milliseconds
+    time: f64,
+    request: HarRequest,
+    response: HarResponse,
+    cache: serde_json::Value,
+    timings: HarTimings,
+    /// `document`, `stylesheet`, `image`, ... (as in browser exports)
+    #[serde(rename = "_resourceType")]
+    resource_type: String,
+    /// `Link` header hints of the response
+    #[serde(rename = "_linkHints", skip_serializing_if = "Vec::is_empty")]
+    link_hints: Vec<EarlyHintLink>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    query_string: Vec<NameValue>,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarResponse {
+    status: u16,
+    status_text: String,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    content: HarContent,
+    #[serde(rename = "redirectURL")]
+    redirect_url: String,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarContent {
+    size: i64,
+    mime_type: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct HarTimings {
+    send: f64,
+    wait: f64,
+    receive: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+impl Har {
+    /// Empty archive for one page load
+    #[must_use]
+    pub fn new(page_url: &str, started: DateTime<Utc>) -> Self {
+        Self {
+            log: HarLog {
+                version: "1.2",
+                creator: NameVersion {
+                    name: "nab",
+                    version: env!("CARGO_PKG_VERSION"),
+                },
+                pages: vec![HarPage {
+                    started_date_time: started,
+                    id: "page_1",
+                    title: page_url.to_string(),
+                    page_timings: serde_json::json!({}),
+                }],
+                entries: Vec::new(),
+            },
+        }
+    }
+
+    pub fn push(&mut self, entry: HarEntry) {
+        self.log.entries.push(entry);
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> &[HarEntry] {
+        &self.log.entries
+    }
+
+    /// Write the archive as pretty-printed JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("Failed to write HAR file {}", path.display()))
+    }
+}
+
+impl HarEntry {
+    /// Entry for `request`, answered with `response` headers after `wait`
+    #[must_use]
+    pub fn new(
+        request: &reqwest::Request,
+        response: &reqwest::Response,
+        started: DateTime<Utc>,
+        wait: Duration,
+        resource_type: &str,
+    ) -> Self {
+        let http_version = format!("{:?}", response.version());
+        let content_length = response
+            .content_length()
+            .and_then(|n| i64::try_from(n).ok())
+            .unwrap_or(-1);
+        let redirect_url = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        Self {
+            pageref: "page_1",
+            started_date_time: started,
+            time: ms(wait),
+            request: HarRequest {
+                method: request.method().to_string(),
+                url: request.url().to_string(),
+                http_version: http_version.clone(),
+                cookies: Vec::new(),
+                headers: name_values(request.headers()),
+                query_string: request
+                    .url()
+                    .query_pairs()
+                    .map(|(name, value)| NameValue {
+                        name: name.into_owned(),
+                        value: value.into_owned(),
+                    })
+                    .collect(),
+                headers_size: -1,
+                body_size: request
+                    .body()
+                    .and_then(reqwest::Body::as_bytes)
+                    .map_or(0, |b| i64::try_from(b.len()).unwrap_or(-1)),
+            },
+            response: HarResponse {
+                status: response.status().as_u16(),
+                status_text: response
+                    .status()
+                    .canonical_reason()
+                    .unwrap_or_default()
+                    .to_string(),
+                http_version,
+                cookies: Vec::new(),
+                headers: name_values(response.headers()),
+                content: HarContent {
+                    size: content_length,
+                    mime_type: response
+                        .headers()
+                        .get(reqwest::header::CONTENT_TYPE)
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or("x-unknown")
+                        .to_string(),
+                },
+                redirect_url,
+                headers_size: -1,
+                body_size: content_length,
+            },
+            cache: serde_json::json!({}),
+            timings: HarTimings {
+                send: 0.0,
+                wait: ms(wait),
+                receive: 0.0,
+            },
+            resource_type: resource_type.to_string(),
+            link_hints: Vec::new(),
+        }
+    }
+
+    /// Record the downloaded body: its (decoded) size and how long it took
+    #[must_use]
+    pub fn with_body(mut self, size: usize, receive: Duration) -> Self {
+        let size = i64::try_from(size).unwrap_or(-1);
+        self.response.content.size = size;
+        if self.response.body_size < 0 {
+            self.response.body_size = size;
+        }
+        self.timings.receive = ms(receive);
+        self.time = self.timings.wait + self.timings.receive;
+        self
+    }
+
+    /// Record the response's `Link` hints
+    #[must_use]
+    pub fn with_link_hints(mut self, hints: &[EarlyHintLink]) -> Self {
+        self.link_hints = hints.to_vec();
+        self
+    }
+
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.request.url
+    }
+
+    #[must_use]
+    pub fn resource_type(&self) -> &str {
+        &self.resource_type
+    }
+}
+
+fn name_values(headers: &HeaderMap) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| NameValue {
+            name: name.to_string(),
+            value: if REDACTED_HEADERS.contains(&name.as_str()) {
+                "[redacted]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_serialization() {
+        let request = reqwest::Client::new()
+            .get("https://example.com/style.css?v=2")
+            .header("Cookie", "session=secret")
+            .header("Accept", "text/css")
+            .build()
+            .unwrap();
+        let response: reqwest::Response = http::Response::builder()
+            .status(200)
+            .header("Content-Type", "text/css")
+            .header("Set-Cookie", "id=1")
+            .header("Link", "</font.woff2>; rel=preload; as=font; crossorigin")
+            .body("body{}")
+            .unwrap()
+            .into();
+
+        let started = Utc::now();
+        let mut har = Har::new("https://example.com/", started);
+        har.push(
+            HarEntry::new(
+                &request,
+                &response,
+                started,
+                Duration::from_millis(40),
+                "stylesheet",
+            )
+            .with_body(6, Duration::from_millis(2))
+            .with_link_hints(&crate::EarlyHints::from_headers(response.headers()).links),
+        );
+        let json = serde_json::to_value(&har).unwrap();
+        let entry = &json["log"]["entries"][0];
+
+        assert_eq!(json["log"]["version"], "1.2");
+        assert_eq!(entry["pageref"], json["log"]["pages"][0]["id"]);
+        assert_eq!(entry["_resourceType"], "stylesheet");
+        assert_eq!(entry["time"], 42.0);
+        assert_eq!(entry["request"]["queryString"][0]["value"], "2");
+        assert_eq!(entry["response"]["status"], 200);
+        assert_eq!(entry["response"]["content"]["size"], 6);
+        assert_eq!(entry["response"]["content"]["mimeType"], "text/css");
+        assert_eq!(entry["_linkHints"][0]["as"], "font");
+        assert_eq!(entry["_linkHints"][0]["crossorigin"], "anonymous");
+        let text = json.to_string();
+        assert!(!text.contains("secret") && !text.contains("id=1"));
+        assert!(text.contains("[redacted]"));
+    }
+}
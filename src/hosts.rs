@@ -0,0 +1,132 @@
+//! Host Aliases (`--hosts-file`)
+//!
+//! Like `/etc/hosts`, but only for this nab run: each line maps an IP address
+//! to hostnames, so a staging server can be fetched or crawled under its
+//! production URLs. Only the address changes; the URL, `Host` header, SNI,
+//! certificate check, and cookies stay those of the production hostname.
+//!
+//! ```text
+//! # staging
+//! 10.0.4.20    www.example.com example.com
+//! 2001:db8::20 www.example.com
+//! ```
+//!
+//! Requests through a proxy are resolved by the proxy (SOCKS4 and `socks5://`
+//! excepted, which nab resolves locally), so aliases don't apply to them.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context, Result};
+
+/// Aliases for this run, set once by [`install`]
+static ALIASES: OnceLock<HashMap<String, Vec<IpAddr>>> = OnceLock::new();
+
+/// Hostname (lowercase) to addresses, in file order
+pub fn parse(text: &str) -> Result<HashMap<String, Vec<IpAddr>>> {
+    let mut aliases: HashMap<String, Vec<IpAddr>> = HashMap::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let Some(ip) = fields.next() else {
+            continue;
+        };
+        let ip: IpAddr = ip
+            .parse()
+            .with_context(|| format!("Line {}: '{ip}' isn't an IP address", n + 1))?;
+        let mut names = fields.peekable();
+        if names.peek().is_none() {
+            bail!("Line {}: no hostnames for {ip}", n + 1);
+        }
+        for name in names {
+            let addresses = aliases
+                .entry(name.trim_end_matches('.').to_lowercase())
+                .or_default();
+            if !addresses.contains(&ip) {
+                addresses.push(ip);
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+/// Read `path` and use its aliases for the rest of the run
+pub fn install(path: &Path) -> Result<()> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let aliases = parse(&text).with_context(|| format!("Invalid hosts file {}", path.display()))?;
+    let _ = ALIASES.set(aliases);
+    Ok(())
+}
+
+/// Addresses `host` is aliased to, if any
+#[must_use]
+pub fn lookup(host: &str) -> Option<&'static [IpAddr]> {
+    let host = host.trim_end_matches('.').to_lowercase();
+    ALIASES.get()?.get(&host).map(Vec::as_slice)
+}
+
+/// `host:port` addresses: its aliases, else from DNS
+pub async fn lookup_host(host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+    match lookup(host) {
+        Some(ips) => Ok(ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect()),
+        None => Ok(tokio::net::lookup_host((host, port)).await?.collect()),
+    }
+}
+
+/// Route the aliased hostnames of an async client builder
+pub fn apply(mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    for (host, addrs) in socket_addrs() {
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    builder
+}
+
+/// Route the aliased hostnames of a blocking client builder
+pub fn apply_blocking(
+    mut builder: reqwest::blocking::ClientBuilder,
+) -> reqwest::blocking::ClientBuilder {
+    for (host, addrs) in socket_addrs() {
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    builder
+}
+
+/// Port 0: reqwest connects to the URL's port
+fn socket_addrs() -> impl Iterator<Item = (&'static str, Vec<SocketAddr>)> {
+    ALIASES.get().into_iter().flatten().map(|(host, ips)| {
+        let addrs = ips.iter().map(|ip| SocketAddr::new(*ip, 0)).collect();
+        (host.as_str(), addrs)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let aliases = parse(
+            "# staging\n\
+             10.0.4.20    WWW.example.com example.com.  # web\n\
+             \n\
+             2001:db8::20 www.example.com\n\
+             10.0.4.20    www.example.com\n",
+        )
+        .unwrap();
+        assert_eq!(
+            aliases["www.example.com"],
+            [
+                "10.0.4.20".parse::<IpAddr>().unwrap(),
+                "2001:db8::20".parse().unwrap()
+            ]
+        );
+        assert_eq!(aliases["example.com"].len(), 1);
+
+        let err = parse("10.0.4 www.example.com").unwrap_err();
+        assert_eq!(err.to_string(), "Line 1: '10.0.4' isn't an IP address");
+        assert!(parse("\n10.0.4.20\n").is_err());
+    }
+}
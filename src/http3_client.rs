@@ -37,8 +37,8 @@ impl Http3Client {
     /// Check if a server advertises HTTP/3 support via Alt-Svc header
     pub async fn supports_h3(url: &str) -> bool {
         // Check Alt-Svc header via HTTP/2
-        if let Ok(client) = reqwest::Client::builder().build() {
-            if let Ok(resp) = client.head(url).send().await {
+        if let Ok(client) = crate::policy::client_builder().build() {
+            if let Ok(resp) = crate::policy::send(client.head(url)).await {
                 if let Some(alt_svc) = resp.headers().get("alt-svc") {
                     if let Ok(value) = alt_svc.to_str() {
                         return value.contains("h3");
@@ -118,12 +118,14 @@ impl Http3Client {
         let uri: http::Uri = url.parse().context("Invalid URL")?;
         let host = uri.host().context("No host in URL")?;
         let port = uri.port_u16().unwrap_or(443);
+        crate::policy::check_host(host)?;
 
         info!("HTTP/3 connecting to {}:{}", host, port);
 
         // DNS resolution
-        let addr = tokio::net::lookup_host(format!("{host}:{port}"))
+        let addr = crate::hosts::lookup_host(host, port)
             .await?
+            .into_iter()
             .next()
             .context("DNS resolution failed")?;
 
@@ -201,8 +203,8 @@ impl Http3Client {
 
     /// Check if a server advertises HTTP/3 support via Alt-Svc header
     pub async fn supports_h3(url: &str) -> bool {
-        if let Ok(client) = reqwest::Client::builder().build() {
-            if let Ok(resp) = client.head(url).send().await {
+        if let Ok(client) = crate::policy::client_builder().build() {
+            if let Ok(resp) = crate::policy::send(client.head(url)).await {
                 if let Some(alt_svc) = resp.headers().get("alt-svc") {
                     if let Ok(value) = alt_svc.to_str() {
                         return value.contains("h3");
@@ -0,0 +1,160 @@
+//! Digest access authentication (RFC 7616)
+
+use anyhow::Result;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use super::{Challenge, UserCredentials};
+
+/// Hash functions a Digest challenge can ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    fn hash_hex(self, data: &str) -> String {
+        match self {
+            Self::Md5 => hex(&Md5::digest(data.as_bytes())),
+            Self::Sha256 => hex(&Sha256::digest(data.as_bytes())),
+        }
+    }
+}
+
+/// Whether we can answer this Digest challenge
+pub(super) fn is_supported(challenge: &Challenge) -> bool {
+    parse_algorithm(challenge).is_some() && challenge.params.contains_key("nonce")
+}
+
+/// `(algorithm, session variant)` of a challenge; MD5 when unspecified
+fn parse_algorithm(challenge: &Challenge) -> Option<(Algorithm, bool)> {
+    let name = challenge
+        .params
+        .get("algorithm")
+        .map_or("MD5", String::as_str)
+        .to_ascii_uppercase();
+    let (base, session) = match name.strip_suffix("-SESS") {
+        Some(base) => (base.to_string(), true),
+        None => (name, false),
+    };
+    let algorithm = match base.as_str() {
+        "MD5" => Algorithm::Md5,
+        "SHA-256" => Algorithm::Sha256,
+        _ => return None,
+    };
+    Some((algorithm, session))
+}
+
+/// `Authorization` header answering `challenge` for `method` on `uri` (path and query)
+pub(super) fn authorization(
+    challenge: &Challenge,
+    credentials: &UserCredentials,
+    method: &str,
+    uri: &str,
+    cnonce: &str,
+) -> Result<String> {
+    let (algorithm, session) = parse_algorithm(challenge)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported Digest algorithm"))?;
+    let param = |name: &str| challenge.params.get(name).map_or("", String::as_str);
+    let (realm, nonce) = (param("realm"), param("nonce"));
+    // Only qop=auth is implemented; servers offering qop must accept it
+    let qop = challenge
+        .params
+        .get("qop")
+        .map(|q| q.split(',').map(str::trim).any(|q| q == "auth"));
+    if qop == Some(false) {
+        anyhow::bail!("Digest server requires qop=auth-int, which isn't supported");
+    }
+    let username = credentials.qualified_username();
+    let nc = "00000001";
+
+    let mut ha1 = algorithm.hash_hex(&format!("{username}:{realm}:{}", credentials.password));
+    if session {
+        ha1 = algorithm.hash_hex(&format!("{ha1}:{nonce}:{cnonce}"));
+    }
+    let ha2 = algorithm.hash_hex(&format!("{method}:{uri}"));
+    let response = if qop.is_some() {
+        algorithm.hash_hex(&format!("{ha1}:{nonce}:{nc}:{cnonce}:auth:{ha2}"))
+    } else {
+        algorithm.hash_hex(&format!("{ha1}:{nonce}:{ha2}"))
+    };
+
+    let mut header = format!(
+        r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", response="{response}""#,
+        quote(&username),
+        quote(realm),
+        quote(nonce),
+        quote(uri)
+    );
+    if let Some(name) = challenge.params.get("algorithm") {
+        header.push_str(&format!(", algorithm={name}"));
+    }
+    if qop.is_some() {
+        header.push_str(&format!(r#", qop=auth, nc={nc}, cnonce="{cnonce}""#));
+    }
+    if let Some(opaque) = challenge.params.get("opaque") {
+        header.push_str(&format!(r#", opaque="{}""#, quote(opaque)));
+    }
+    Ok(header)
+}
+
+/// Random client nonce
+pub(super) fn cnonce() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+pub(super) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Escape `"` and `\` for a quoted-string header field
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse_challenges;
+    use super::*;
+
+    #[test]
+    fn test_quoted_fields_are_escaped() {
+        let credentials: UserCredentials = r#"CORP\ada "x":secret"#.parse().unwrap();
+        let challenge = &parse_challenges(r#"Digest realm="a\"b", nonce="n""#)[0];
+        assert_eq!(challenge.params["realm"], r#"a"b"#);
+        let answer = authorization(challenge, &credentials, "GET", "/", "c").unwrap();
+        assert!(
+            answer.starts_with(r#"Digest username="CORP\\ada \"x\"", realm="a\"b", nonce="n""#),
+            "{answer}"
+        );
+    }
+
+    /// Examples from RFC 7616 §3.9.1
+    #[test]
+    fn test_rfc7616_responses() {
+        let credentials: UserCredentials = "Mufasa:Circle of Life".parse().unwrap();
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+        for (algorithm, expected) in [
+            ("MD5", "8ca523f5e9506fed4657c9700eebdbec"),
+            (
+                "SHA-256",
+                "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1",
+            ),
+        ] {
+            let header = format!(
+                r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm={algorithm}, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#
+            );
+            let challenge = &parse_challenges(&header)[0];
+            assert!(is_supported(challenge));
+            let answer =
+                authorization(challenge, &credentials, "GET", "/dir/index.html", cnonce).unwrap();
+            assert!(
+                answer.contains(&format!(r#"response="{expected}""#)),
+                "{answer}"
+            );
+            assert!(answer.contains("qop=auth, nc=00000001"));
+            assert!(answer.contains(r#"opaque="FQhe/"#));
+        }
+    }
+}
@@ -0,0 +1,498 @@
+//! HTTP Authentication (`--user`)
+//!
+//! Answers a server's `WWW-Authenticate` challenge after a 401:
+//! - `Basic`
+//! - `Digest` (RFC 7616): MD5, SHA-256, their `-sess` variants, `qop=auth`
+//! - `NTLM` and `Negotiate` via NTLMv2 (`ntlm` feature; no Kerberos)
+//!
+//! Credentials are only sent once the server has asked for them, so a
+//! `--user` left on a public URL doesn't leak.
+
+mod digest;
+#[cfg(feature = "ntlm")]
+mod ntlm;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use reqwest::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::{RequestBuilder, Response};
+use tracing::debug;
+
+use crate::RequestOptions;
+
+/// `--user` credentials; `DOMAIN\user:password` sets the NTLM domain
+#[derive(Clone, PartialEq, Eq)]
+pub struct UserCredentials {
+    pub domain: Option<String>,
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for UserCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UserCredentials")
+            .field("domain", &self.domain)
+            .field("username", &self.username)
+            .field("password", &"[redacted]")
+            .finish()
+    }
+}
+
+impl FromStr for UserCredentials {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let (user, password) = spec
+            .split_once(':')
+            .context("Expected USER:PASSWORD (or DOMAIN\\USER:PASSWORD)")?;
+        let (domain, username) = match user.split_once('\\') {
+            Some((domain, username)) => (Some(domain.to_string()), username),
+            None => (None, user),
+        };
+        if username.is_empty() {
+            anyhow::bail!("Empty user name in --user");
+        }
+        Ok(Self {
+            domain,
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+}
+
+impl UserCredentials {
+    /// `DOMAIN\user` when a domain was given, else the bare user name
+    #[must_use]
+    pub fn qualified_username(&self) -> String {
+        match &self.domain {
+            Some(domain) => format!("{domain}\\{}", self.username),
+            None => self.username.clone(),
+        }
+    }
+}
+
+/// One challenge from a `WWW-Authenticate` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    /// Lower-case scheme (`basic`, `digest`, `ntlm`, ...)
+    pub scheme: String,
+    /// `name=value` parameters, names lower-cased
+    pub params: HashMap<String, String>,
+    /// Opaque token (`NTLM <base64>`)
+    pub token: Option<String>,
+}
+
+/// Split a `WWW-Authenticate` value into its challenges
+///
+/// A header may carry several (`Negotiate, NTLM` or
+/// `Digest realm="a", qop="auth", Basic realm="a"`).
+#[must_use]
+pub fn parse_challenges(header: &str) -> Vec<Challenge> {
+    let mut challenges: Vec<Challenge> = Vec::new();
+    let mut rest = header.trim();
+    while !rest.is_empty() {
+        rest = rest.trim_start_matches([',', ' ', '\t']);
+        let end = rest.find([' ', '\t', ',', '=']).unwrap_or(rest.len());
+        let (word, after) = rest.split_at(end);
+        if word.is_empty() {
+            break;
+        }
+        let after_word = after.trim_start_matches([' ', '\t']);
+
+        // `name=value` belongs to the current challenge; anything else starts a new one
+        if after_word.starts_with('=') && !challenges.is_empty() {
+            let (value, remaining) = parse_value(after_word[1..].trim_start());
+            if let Some(current) = challenges.last_mut() {
+                current.params.insert(word.to_ascii_lowercase(), value);
+            }
+            rest = remaining;
+            continue;
+        }
+
+        let mut challenge = Challenge {
+            scheme: word.to_ascii_lowercase(),
+            params: HashMap::new(),
+            token: None,
+        };
+        rest = after;
+        // NTLM/Negotiate carry a base64 token68 that may end in '='
+        if matches!(challenge.scheme.as_str(), "ntlm" | "negotiate") {
+            let trimmed = rest.trim_start_matches([' ', '\t']);
+            let end = trimmed.find(',').unwrap_or(trimmed.len());
+            let token = trimmed[..end].trim();
+            if !token.is_empty() {
+                challenge.token = Some(token.to_string());
+            }
+            rest = &trimmed[end..];
+        }
+        challenges.push(challenge);
+    }
+    challenges
+}
+
+/// A quoted or bare parameter value and the text after it
+fn parse_value(input: &str) -> (String, &str) {
+    if let Some(quoted) = input.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    if let Some((_, escaped)) = chars.next() {
+                        value.push(escaped);
+                    }
+                }
+                '"' => return (value, &quoted[i + 1..]),
+                _ => value.push(c),
+            }
+        }
+        (value, "")
+    } else {
+        let end = input.find([',', ' ', '\t']).unwrap_or(input.len());
+        (input[..end].to_string(), &input[end..])
+    }
+}
+
+/// Resend `request` with `credentials` for the challenge in the 401 `unauthorized`
+///
+/// The answer goes through [`RequestOptions::send`], so it takes the same
+/// route (HTTP/1.1 fallback included) as the request that got the 401.
+/// When `request` was redirected, the answer goes to the URL that sent the
+/// challenge, and only if it's on `request`'s origin. Returns the original
+/// response when none of its challenges is supported.
+pub async fn authenticate(
+    options: &RequestOptions,
+    request: RequestBuilder,
+    unauthorized: Response,
+    credentials: &UserCredentials,
+) -> Result<Response> {
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    let challenger = unauthorized.url();
+    if challenger.origin() != request.url().origin() {
+        debug!(url = %challenger, "Not logging in to a redirect target on another origin");
+        return Ok(unauthorized);
+    }
+    *request.url_mut() = challenger.clone();
+    let challenges: Vec<Challenge> = unauthorized
+        .headers()
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(parse_challenges)
+        .collect();
+    let find = |scheme: &str| challenges.iter().find(|c| c.scheme == scheme);
+
+    #[cfg(feature = "ntlm")]
+    if let Some(challenge) = find("ntlm").or_else(|| find("negotiate")) {
+        debug!(scheme = %challenge.scheme, "Answering NTLM challenge");
+        return authenticate_ntlm(&challenge.scheme, request, credentials).await;
+    }
+
+    let header = if let Some(challenge) = find("digest").filter(|c| digest::is_supported(c)) {
+        debug!("Answering Digest challenge");
+        let uri = match request.url().query() {
+            Some(query) => format!("{}?{query}", request.url().path()),
+            None => request.url().path().to_string(),
+        };
+        digest::authorization(
+            challenge,
+            credentials,
+            request.method().as_str(),
+            &uri,
+            &digest::cnonce(),
+        )?
+    } else if find("basic").is_some() {
+        debug!("Answering Basic challenge");
+        let pair = format!(
+            "{}:{}",
+            credentials.qualified_username(),
+            credentials.password
+        );
+        format!("Basic {}", base64_encode(pair.as_bytes()))
+    } else {
+        let offered: Vec<&str> = challenges.iter().map(|c| c.scheme.as_str()).collect();
+        debug!(?offered, "No supported authentication scheme");
+        return Ok(unauthorized);
+    };
+
+    let mut value = HeaderValue::from_str(&header)?;
+    value.set_sensitive(true);
+    request.headers_mut().insert(AUTHORIZATION, value);
+    options.resend(&client, request).await
+}
+
+/// Whether the Digest `authorization` answers `challenge` as `credentials`
+/// would (the server side of [`authenticate`], for the mock server)
+#[cfg(feature = "mock-server")]
+pub(crate) fn digest_matches(
+    challenge: &Challenge,
+    credentials: &UserCredentials,
+    method: &str,
+    authorization: &str,
+) -> bool {
+    let response = |header: &str| {
+        parse_challenges(header)
+            .into_iter()
+            .find(|c| c.scheme == "digest")
+            .map(|c| c.params)
+    };
+    let Some(answer) = response(authorization) else {
+        return false;
+    };
+    let param = |name: &str| answer.get(name).map_or("", String::as_str);
+    digest::authorization(
+        challenge,
+        credentials,
+        method,
+        param("uri"),
+        param("cnonce"),
+    )
+    .ok()
+    .and_then(|expected| response(&expected))
+    .is_some_and(|expected| expected.get("response") == answer.get("response"))
+}
+
+/// NTLM handshake: negotiate → server challenge → authenticate
+///
+/// NTLM authenticates a connection, not a request, so both legs use one
+/// dedicated HTTP/1.1 connection.
+#[cfg(feature = "ntlm")]
+async fn authenticate_ntlm(
+    scheme: &str,
+    request: reqwest::Request,
+    credentials: &UserCredentials,
+) -> Result<Response> {
+    let client = crate::policy::client_builder()
+        .http1_only()
+        .pool_max_idle_per_host(1)
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    let label = if scheme == "negotiate" {
+        "Negotiate"
+    } else {
+        "NTLM"
+    };
+
+    let mut negotiate = request
+        .try_clone()
+        .context("Request body can't be replayed for NTLM")?;
+    negotiate.headers_mut().insert(
+        AUTHORIZATION,
+        format!("{label} {}", base64_encode(&ntlm::negotiate_message())).parse()?,
+    );
+    let response = client.execute(negotiate).await?;
+    let token = response
+        .headers()
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(parse_challenges)
+        .find(|c| c.scheme == scheme)
+        .and_then(|c| c.token)
+        .context("Server sent no NTLM challenge")?;
+    let server = ntlm::ServerChallenge::parse(&base64_decode(&token)?)?;
+
+    let message = ntlm::authenticate_message(
+        &server,
+        credentials,
+        rand::random(),
+        u64::try_from(chrono::Utc::now().timestamp()).unwrap_or_default(),
+    );
+    let mut request = request;
+    let mut value = HeaderValue::from_str(&format!("{label} {}", base64_encode(&message)))?;
+    value.set_sensitive(true);
+    request.headers_mut().insert(AUTHORIZATION, value);
+    Ok(client.execute(request).await?)
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = (u32::from(chunk[0]) << 16)
+            | (u32::from(chunk.get(1).copied().unwrap_or(0)) << 8)
+            | u32::from(chunk.get(2).copied().unwrap_or(0));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(feature = "ntlm")]
+fn base64_decode(text: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().filter(|&c| c != b'=') {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .context("Invalid base64 in NTLM challenge")?;
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_challenges() {
+        let challenges = parse_challenges(
+            r#"Digest realm="intranet, HQ", qop="auth,auth-int", nonce=abc, Basic realm="HQ""#,
+        );
+        assert_eq!(challenges.len(), 2);
+        assert_eq!(challenges[0].scheme, "digest");
+        assert_eq!(challenges[0].params["realm"], "intranet, HQ");
+        assert_eq!(challenges[0].params["qop"], "auth,auth-int");
+        assert_eq!(challenges[0].params["nonce"], "abc");
+        assert_eq!(challenges[1].scheme, "basic");
+        assert_eq!(challenges[1].params["realm"], "HQ");
+
+        let challenges = parse_challenges("Negotiate, NTLM TlRMTVNTUAACAAAA==");
+        assert_eq!(challenges.len(), 2);
+        assert_eq!(challenges[0].token, None);
+        assert_eq!(challenges[1].scheme, "ntlm");
+        assert_eq!(challenges[1].token.as_deref(), Some("TlRMTVNTUAACAAAA=="));
+    }
+
+    #[test]
+    fn test_user_credentials() {
+        let plain: UserCredentials = "ada:pa:ss".parse().unwrap();
+        assert_eq!(plain.username, "ada");
+        assert_eq!(plain.password, "pa:ss");
+        assert_eq!(plain.qualified_username(), "ada");
+
+        let domain: UserCredentials = r"CORP\ada:secret".parse().unwrap();
+        assert_eq!(domain.domain.as_deref(), Some("CORP"));
+        assert_eq!(domain.qualified_username(), r"CORP\ada");
+        assert!(!format!("{domain:?}").contains("secret"));
+
+        assert!("no-password".parse::<UserCredentials>().is_err());
+        assert_eq!(base64_encode(b"ada:pa"), "YWRhOnBh");
+        assert_eq!(base64_encode(b"a"), "YQ==");
+    }
+
+    #[tokio::test]
+    async fn test_basic_challenge_round_trip() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // 401 with a Basic challenge unless the expected credentials arrive
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let head = if request.contains("authorization: basic ywrhonbh") {
+                    "200 OK"
+                } else {
+                    "401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"test\""
+                };
+                let response =
+                    format!("HTTP/1.1 {head}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{addr}/");
+        let unauthorized = client.get(&url).send().await.unwrap();
+        assert_eq!(unauthorized.status(), 401);
+
+        let credentials: UserCredentials = "ada:pa".parse().unwrap();
+        let options = RequestOptions::default();
+        let response = authenticate(&options, client.get(&url), unauthorized, &credentials)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_login_after_redirect() {
+        use std::sync::{Arc, Mutex};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // `/old` moves to `/new` (or to `elsewhere`), which asks for Basic;
+        // keeps each request line and whether it carried credentials
+        async fn server(elsewhere: Option<String>) -> (String, Arc<Mutex<Vec<String>>>) {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base = format!("http://{}", listener.local_addr().unwrap());
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let kept = seen.clone();
+            tokio::spawn(async move {
+                while let Ok((mut socket, _)) = listener.accept().await {
+                    let mut buf = vec![0u8; 4096];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                    let line = request.lines().next().unwrap_or_default().to_string();
+                    let authorized = request.contains("authorization: basic ywrhonbh");
+                    kept.lock().unwrap().push(format!("{line} {authorized}"));
+                    let head = match (line.starts_with("get /old"), &elsewhere) {
+                        (true, Some(target)) => format!("302 Found\r\nLocation: {target}/new"),
+                        (true, None) => "302 Found\r\nLocation: /new".to_string(),
+                        (false, _) if authorized => "200 OK".to_string(),
+                        (false, _) => {
+                            "401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"test\"".to_string()
+                        }
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {head}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
+            });
+            (base, seen)
+        }
+
+        let client = reqwest::Client::new();
+        let options = RequestOptions::default();
+        let credentials: UserCredentials = "ada:pa".parse().unwrap();
+
+        // Same origin: the login goes to the page that asked for it
+        let (base, seen) = server(None).await;
+        let url = format!("{base}/old");
+        let unauthorized = client.get(&url).send().await.unwrap();
+        let response = authenticate(&options, client.get(&url), unauthorized, &credentials)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            seen.lock().unwrap()[2..],
+            ["get /new http/1.1 true".to_string()]
+        );
+
+        // Another origin never sees the credentials
+        let (other, other_seen) = server(None).await;
+        let (base, seen) = server(Some(other)).await;
+        let url = format!("{base}/old");
+        let unauthorized = client.get(&url).send().await.unwrap();
+        let response = authenticate(&options, client.get(&url), unauthorized, &credentials)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(seen.lock().unwrap().len(), 1);
+        assert_eq!(
+            *other_seen.lock().unwrap(),
+            ["get /new http/1.1 false".to_string()]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! NTLMv2 messages (MS-NLMP) for `NTLM` and `Negotiate` challenges
+//!
+//! Only NTLMv2 responses are produced; servers that insist on NTLMv1 or
+//! Kerberos-only `Negotiate` will keep answering 401.
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use md4::{Digest, Md4};
+use md5::Md5;
+
+use super::UserCredentials;
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+
+const FLAGS: u32 = NEGOTIATE_UNICODE
+    | REQUEST_TARGET
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_TARGET_INFO
+    | NEGOTIATE_128
+    | NEGOTIATE_56;
+
+/// Seconds between 1601-01-01 (FILETIME epoch) and the Unix epoch
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+/// Type 1 (negotiate) message
+pub(super) fn negotiate_message() -> Vec<u8> {
+    let mut message = Vec::with_capacity(32);
+    message.extend_from_slice(SIGNATURE);
+    message.extend_from_slice(&1u32.to_le_bytes());
+    message.extend_from_slice(&FLAGS.to_le_bytes());
+    // Empty domain and workstation buffers
+    message.extend_from_slice(&[0u8; 16]);
+    message
+}
+
+/// Parts of a type 2 (challenge) message we need
+#[derive(Debug, PartialEq)]
+pub(super) struct ServerChallenge {
+    pub challenge: [u8; 8],
+    pub target_info: Vec<u8>,
+}
+
+impl ServerChallenge {
+    pub(super) fn parse(message: &[u8]) -> Result<Self> {
+        if message.len() < 32 || &message[..8] != SIGNATURE || read_u32(message, 8) != Some(2) {
+            anyhow::bail!("Not an NTLM challenge message");
+        }
+        let mut challenge = [0u8; 8];
+        challenge.copy_from_slice(&message[24..32]);
+        let target_info = if message.len() >= 48 {
+            let len = usize::from(read_u16(message, 40).unwrap_or(0));
+            let offset = read_u32(message, 44).unwrap_or(0) as usize;
+            message
+                .get(offset..offset + len)
+                .context("NTLM target info out of bounds")?
+                .to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            challenge,
+            target_info,
+        })
+    }
+}
+
+/// Type 3 (authenticate) message with NTLMv2 responses
+pub(super) fn authenticate_message(
+    server: &ServerChallenge,
+    credentials: &UserCredentials,
+    client_challenge: [u8; 8],
+    unix_time: u64,
+) -> Vec<u8> {
+    let domain = credentials.domain.as_deref().unwrap_or_default();
+    let key = ntowf_v2(&credentials.username, &credentials.password, domain);
+
+    let timestamp = (unix_time + FILETIME_UNIX_OFFSET) * 10_000_000;
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend_from_slice(&timestamp.to_le_bytes());
+    blob.extend_from_slice(&client_challenge);
+    blob.extend_from_slice(&[0; 4]);
+    blob.extend_from_slice(&server.target_info);
+    blob.extend_from_slice(&[0; 4]);
+
+    let mut nt_response = hmac_md5(&key, &[&server.challenge[..], &blob].concat()).to_vec();
+    nt_response.extend_from_slice(&blob);
+    let mut lm_response = hmac_md5(&key, &[server.challenge, client_challenge].concat()).to_vec();
+    lm_response.extend_from_slice(&client_challenge);
+
+    let fields = [
+        lm_response,
+        nt_response,
+        utf16le(domain),
+        utf16le(&credentials.username),
+        utf16le("NAB"),
+        Vec::new(), // no session key
+    ];
+
+    let header_len = 64;
+    let mut message = Vec::new();
+    message.extend_from_slice(SIGNATURE);
+    message.extend_from_slice(&3u32.to_le_bytes());
+    let mut offset = header_len;
+    for field in &fields {
+        let len = u16::try_from(field.len()).unwrap_or(u16::MAX);
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += field.len();
+    }
+    message.extend_from_slice(&FLAGS.to_le_bytes());
+    for field in fields {
+        message.extend(field);
+    }
+    message
+}
+
+/// NTOWFv2: HMAC-MD5 keyed by the NT hash over `UPPER(user) + domain`
+fn ntowf_v2(username: &str, password: &str, domain: &str) -> [u8; 16] {
+    let nt_hash = md4(&utf16le(password));
+    hmac_md5(
+        &nt_hash,
+        &utf16le(&format!("{}{domain}", username.to_uppercase())),
+    )
+}
+
+fn utf16le(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn hmac_md5(key: &[u8], data: &[u8]) -> [u8; 16] {
+    let mut mac = Hmac::<Md5>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// MD4, needed for the NT password hash
+fn md4(data: &[u8]) -> [u8; 16] {
+    Md4::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::digest::hex;
+    use super::*;
+
+    /// Values from MS-NLMP §4.2.4
+    #[test]
+    fn test_ntlmv2_vectors() {
+        assert_eq!(
+            hex(&ntowf_v2("User", "Password", "Domain")),
+            "0c868a403bfd7a93a3001ef22ef02e3f"
+        );
+
+        let mut challenge_message = negotiate_message();
+        challenge_message[8] = 2;
+        challenge_message.resize(48, 0);
+        challenge_message[24..32].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        challenge_message.extend_from_slice(&[2, 0, 0, 0]);
+        challenge_message[40] = 4;
+        challenge_message[44] = 48;
+        let server = ServerChallenge::parse(&challenge_message).unwrap();
+        assert_eq!(server.challenge, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(server.target_info, vec![2, 0, 0, 0]);
+
+        let credentials: UserCredentials = "Domain\\User:Password".parse().unwrap();
+        let message = authenticate_message(&server, &credentials, [0xaa; 8], 0);
+        assert_eq!(&message[..8], SIGNATURE);
+        assert_eq!(read_u32(&message, 8), Some(3));
+        // NT response: 16-byte proof + 28-byte blob header + target info + 4 zero bytes
+        assert_eq!(read_u16(&message, 20), Some(16 + 28 + 4 + 4));
+        assert!(ServerChallenge::parse(&message).is_err());
+    }
+}
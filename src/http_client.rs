@@ -7,21 +7,283 @@
 //! - DNS caching + Happy Eyeballs (IPv4/IPv6 racing)
 //! - Connection pooling with keep-alive
 //! - Realistic browser fingerprinting
+//! - Client certificates for mutual TLS (via [`TlsOptions`])
+//! - HTTP and SOCKS proxies, including chains (via [`ClientOptions`])
+//! - HTTP version selection per run or per site (via [`HttpVersion`])
 
-use std::sync::Arc;
-use std::time::Duration;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use reqwest::header::{self, HeaderMap, HeaderValue};
 use reqwest::{Client, Response};
 use tokio::sync::RwLock;
 use tracing::{debug, info, instrument};
 
-use crate::fingerprint::{random_profile, BrowserProfile};
+use crate::fingerprint::{random_profile, BrowserProfile, FetchContext};
+use crate::proxy::ProxyConfig;
+use crate::request_options::RequestOptions;
+use crate::timing::RedirectLog;
+use crate::tls::TlsOptions;
+
+/// `--http` for this run, set once by [`force_http_version`]
+static FORCED_HTTP: OnceLock<HttpVersion> = OnceLock::new();
+
+/// HTTP version a client speaks (`--http 1|2|3|auto`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum HttpVersion {
+    /// Whatever TLS ALPN negotiates (HTTP/1.1 for plain `http://`)
+    #[serde(rename = "auto")]
+    Auto,
+    /// HTTP/1.1 only, Title-Case headers, lenient about malformed response
+    /// headers: for legacy origins that misbehave under HTTP/2
+    #[serde(rename = "1")]
+    Http1,
+    /// HTTP/2 without negotiating (prior knowledge)
+    #[serde(rename = "2")]
+    Http2,
+    /// HTTP/3 over QUIC; nab's request clients can't speak it yet
+    #[serde(rename = "3")]
+    Http3,
+}
+
+impl FromStr for HttpVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "1" | "1.1" => Ok(Self::Http1),
+            "2" => Ok(Self::Http2),
+            "3" => Ok(Self::Http3),
+            _ => bail!("Unknown HTTP version '{s}' (1, 2, 3, or auto)"),
+        }
+    }
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Http1 => "1",
+            Self::Http2 => "2",
+            Self::Http3 => "3",
+        })
+    }
+}
+
+impl HttpVersion {
+    fn unsupported(self) -> Result<()> {
+        if self == Self::Http3 {
+            bail!("--http 3: nab's request client doesn't speak HTTP/3 yet; use 1, 2, or auto");
+        }
+        Ok(())
+    }
+
+    fn apply(self, builder: reqwest::ClientBuilder) -> Result<reqwest::ClientBuilder> {
+        self.unsupported()?;
+        Ok(match self {
+            Self::Http1 => builder
+                .http1_only()
+                .http1_title_case_headers()
+                .http1_allow_obsolete_multiline_headers_in_responses(true)
+                .http1_ignore_invalid_headers_in_responses(true),
+            Self::Http2 => builder.http2_prior_knowledge(),
+            Self::Auto | Self::Http3 => builder,
+        })
+    }
+
+    fn apply_blocking(
+        self,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> Result<reqwest::blocking::ClientBuilder> {
+        self.unsupported()?;
+        Ok(match self {
+            Self::Http1 => builder
+                .http1_only()
+                .http1_title_case_headers()
+                .http1_allow_obsolete_multiline_headers_in_responses(true)
+                .http1_ignore_invalid_headers_in_responses(true),
+            Self::Http2 => builder.http2_prior_knowledge(),
+            Self::Auto | Self::Http3 => builder,
+        })
+    }
+}
+
+/// Speak `version` in every client of this run, whatever the options or
+/// config say (`--http`)
+pub fn force_http_version(version: HttpVersion) {
+    let _ = FORCED_HTTP.set(version);
+}
+
+/// Connection settings shared by every client of a command
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    pub tls: TlsOptions,
+    pub proxy: Option<ProxyConfig>,
+    /// Whole-request timeout, replacing the default 30s
+    pub timeout: Option<Duration>,
+    /// HTTP version; `None` leaves it to the client (see [`Self::http_version`])
+    pub http: Option<HttpVersion>,
+}
+
+impl ClientOptions {
+    /// [`Self::http`], unless [`force_http_version`] overrides it
+    #[must_use]
+    pub fn http_version(&self) -> Option<HttpVersion> {
+        FORCED_HTTP.get().copied().or(self.http)
+    }
+
+    /// Whether requests leave through a proxy: [`Self::proxy`] (`--proxy`,
+    /// `--proxy-chain`, `--geo`) or one reqwest takes from the environment
+    #[must_use]
+    pub fn is_proxied(&self) -> bool {
+        self.proxy.is_some()
+            || ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"]
+                .iter()
+                .any(|name| {
+                    std::env::var_os(name)
+                        .or_else(|| std::env::var_os(name.to_ascii_lowercase()))
+                        .is_some_and(|value| !value.is_empty())
+                })
+    }
+
+    /// Apply TLS, proxy, and HTTP version settings to an async client
+    /// builder from [`crate::policy::client_builder`]
+    pub fn apply(&self, builder: reqwest::ClientBuilder) -> Result<reqwest::ClientBuilder> {
+        let http = self.http_version();
+        let builder = match http {
+            Some(HttpVersion::Http1) => self.tls.apply_http1(builder)?,
+            _ => self.tls.apply(builder)?,
+        };
+        let mut builder = builder;
+        if let Some(http) = http {
+            builder = http.apply(builder)?;
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        Ok(match &self.proxy {
+            Some(proxy) => builder.proxy(proxy.reqwest_proxy()?),
+            None => builder,
+        })
+    }
+
+    /// [`Self::apply`] for a blocking client builder
+    pub fn apply_blocking(
+        &self,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> Result<reqwest::blocking::ClientBuilder> {
+        let http = self.http_version();
+        let builder = match http {
+            Some(HttpVersion::Http1) => self.tls.apply_blocking_http1(builder)?,
+            _ => self.tls.apply_blocking(builder)?,
+        };
+        let mut builder = builder;
+        if let Some(http) = http {
+            builder = http.apply_blocking(builder)?;
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        Ok(match &self.proxy {
+            Some(proxy) => builder.proxy(proxy.reqwest_proxy()?),
+            None => builder,
+        })
+    }
+
+    /// Add proxy chain details, if any, to an error from fetching `url`
+    #[must_use]
+    pub fn explain(&self, url: &str, error: anyhow::Error) -> anyhow::Error {
+        match &self.proxy {
+            Some(proxy) => proxy.explain(url, error),
+            None => error,
+        }
+    }
+}
+
+/// Outcome of a conditional GET
+#[derive(Debug)]
+pub enum Conditional {
+    /// Server answered 304; the cached copy is still current
+    NotModified,
+    /// Resource changed (or the server ignored the validators)
+    Modified(Response),
+}
+
+/// Cache validators of a response (`ETag`, `Last-Modified`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Read validators from response headers
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name: header::HeaderName| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        Self {
+            etag: get(header::ETAG),
+            last_modified: get(header::LAST_MODIFIED),
+        }
+    }
+
+    /// `If-None-Match` / `If-Modified-Since` request headers for these validators
+    ///
+    /// `last_modified` may be an HTTP date or RFC 3339 (converted to an HTTP date).
+    #[must_use]
+    pub fn request_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = self
+            .etag
+            .as_deref()
+            .and_then(|e| HeaderValue::from_str(e).ok())
+        {
+            headers.insert(header::IF_NONE_MATCH, value);
+        }
+        if let Some(value) = self
+            .last_modified
+            .as_deref()
+            .and_then(|d| HeaderValue::from_str(&http_date(d)).ok())
+        {
+            headers.insert(header::IF_MODIFIED_SINCE, value);
+        }
+        headers
+    }
+
+    /// True if neither validator is set
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// Normalize a date to HTTP-date format (RFC 3339 input is converted, anything else kept)
+fn http_date(value: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(value).map_or_else(
+        |_| value.to_string(),
+        |d| {
+            d.with_timezone(&chrono::Utc)
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string()
+        },
+    )
+}
 
 /// HTTP client with all acceleration features
 pub struct AcceleratedClient {
     client: Client,
     profile: Arc<RwLock<BrowserProfile>>,
+    /// Set by [`Self::with_redirect_log`]; fills [`Response::redirects`](crate::Response::redirects)
+    redirect_log: Option<RedirectLog>,
 }
 
 impl AcceleratedClient {
@@ -32,14 +294,73 @@ impl AcceleratedClient {
 
     /// Create client with specific browser profile
     pub fn with_profile(profile: BrowserProfile) -> Result<Self> {
-        let headers = profile.to_headers();
+        Self::build(
+            profile,
+            crate::policy::redirects(10),
+            &ClientOptions::default(),
+        )
+    }
+
+    /// Create client with TLS and proxy settings
+    pub fn with_options(options: &ClientOptions) -> Result<Self> {
+        Self::build(random_profile(), crate::policy::redirects(10), options)
+    }
+
+    /// Create client with a specific browser profile and TLS and proxy settings
+    pub fn with_profile_and_options(
+        profile: BrowserProfile,
+        options: &ClientOptions,
+    ) -> Result<Self> {
+        Self::build(profile, crate::policy::redirects(10), options)
+    }
+
+    /// Create client that records each redirect hop in `log` (for timing breakdowns)
+    pub fn with_redirect_log(log: &RedirectLog, options: &ClientOptions) -> Result<Self> {
+        let mut client = Self::build(random_profile(), log.policy(10), options)?;
+        client.redirect_log = Some(log.clone());
+        Ok(client)
+    }
 
-        let client = Client::builder()
+    /// Create client with the profile, redirect limit, timeout, TLS, and proxy of `request`
+    pub fn for_request(request: &RequestOptions) -> Result<Self> {
+        let profile = request.browser_profile();
+        match request.max_redirects {
+            0 => Self::build_no_redirect(profile, &request.client),
+            max => Self::build(profile, crate::policy::redirects(max), &request.client),
+        }
+    }
+
+    /// Like [`Self::for_request`], recording each redirect hop in `log`
+    pub fn for_request_with_redirect_log(
+        request: &RequestOptions,
+        log: &RedirectLog,
+    ) -> Result<Self> {
+        let profile = request.browser_profile();
+        let mut client = match request.max_redirects {
+            0 => Self::build_no_redirect(profile, &request.client)?,
+            max => Self::build(profile, log.policy(max), &request.client)?,
+        };
+        client.redirect_log = Some(log.clone());
+        Ok(client)
+    }
+
+    fn build(
+        profile: BrowserProfile,
+        redirect: reqwest::redirect::Policy,
+        options: &ClientOptions,
+    ) -> Result<Self> {
+        let headers = profile.client_headers();
+
+        let mut builder = crate::policy::client_builder();
+        // HTTP/2 multiplexing (100 streams per connection), unless the
+        // options pick a version
+        if options.http_version().is_none() {
+            builder = builder.http2_prior_knowledge();
+        }
+        let builder = builder
             // ═══════════════════════════════════════════════════════════════
             // CONNECTION ACCELERATION
             // ═══════════════════════════════════════════════════════════════
-            // HTTP/2: Multiplexing - 100 streams per connection
-            .http2_prior_knowledge()
             // Keep connections alive for reuse
             .pool_max_idle_per_host(10)
             .pool_idle_timeout(Duration::from_secs(90))
@@ -82,25 +403,29 @@ impl AcceleratedClient {
             // ═══════════════════════════════════════════════════════════════
             // REDIRECTS
             // ═══════════════════════════════════════════════════════════════
-            .redirect(reqwest::redirect::Policy::limited(10))
+            .redirect(redirect)
+            // Browsers keep the original Referer on redirects; reqwest would
+            // send the redirecting URL instead
+            .referer(false)
             // ═══════════════════════════════════════════════════════════════
             // COOKIES
             // ═══════════════════════════════════════════════════════════════
-            .cookie_store(true)
-            .build()?;
+            .cookie_store(true);
+        let client = options.apply(builder)?.build()?;
 
         Ok(Self {
             client,
             profile: Arc::new(RwLock::new(profile)),
+            redirect_log: None,
         })
     }
 
     /// Create client that tries HTTP/2 with fallback to HTTP/1.1
     pub fn new_adaptive() -> Result<Self> {
         let profile = random_profile();
-        let headers = profile.to_headers();
+        let headers = profile.client_headers();
 
-        let client = Client::builder()
+        let builder = crate::policy::client_builder()
             // Don't assume HTTP/2 - let server negotiate
             .http2_adaptive_window(true)
             .pool_max_idle_per_host(10)
@@ -115,22 +440,31 @@ impl AcceleratedClient {
             .default_headers(headers)
             .connect_timeout(Duration::from_secs(10))
             .timeout(Duration::from_secs(30))
-            .redirect(reqwest::redirect::Policy::limited(10))
-            .cookie_store(true)
-            .build()?;
+            .referer(false)
+            .cookie_store(true);
+        let client = builder.build()?;
 
         Ok(Self {
             client,
             profile: Arc::new(RwLock::new(profile)),
+            redirect_log: None,
         })
     }
 
     /// Create client that doesn't follow redirects (for auth flows)
     pub fn new_no_redirect() -> Result<Self> {
-        let profile = random_profile();
-        let headers = profile.to_headers();
+        Self::new_no_redirect_with_options(&ClientOptions::default())
+    }
+
+    /// Like [`Self::new_no_redirect`], with TLS and proxy settings
+    pub fn new_no_redirect_with_options(options: &ClientOptions) -> Result<Self> {
+        Self::build_no_redirect(random_profile(), options)
+    }
 
-        let client = Client::builder()
+    fn build_no_redirect(profile: BrowserProfile, options: &ClientOptions) -> Result<Self> {
+        let headers = profile.client_headers();
+
+        let builder = crate::policy::client_builder()
             .http2_adaptive_window(true)
             .pool_max_idle_per_host(10)
             .pool_idle_timeout(Duration::from_secs(90))
@@ -146,12 +480,13 @@ impl AcceleratedClient {
             .timeout(Duration::from_secs(30))
             // No redirects - capture 302 responses directly
             .redirect(reqwest::redirect::Policy::none())
-            .cookie_store(true)
-            .build()?;
+            .cookie_store(true);
+        let client = options.apply(builder)?.build()?;
 
         Ok(Self {
             client,
             profile: Arc::new(RwLock::new(profile)),
+            redirect_log: None,
         })
     }
 
@@ -159,7 +494,13 @@ impl AcceleratedClient {
     #[instrument(skip(self), fields(url = %url))]
     pub async fn fetch(&self, url: &str) -> Result<Response> {
         debug!("Fetching with acceleration");
-        let response = self.client.get(url).send().await?;
+        let headers = self
+            .profile
+            .read()
+            .await
+            .request_headers(FetchContext::Navigate, "none");
+        crate::policy::check(url)?;
+        let response = self.client.get(url).headers(headers).send().await?;
 
         info!(
             status = %response.status(),
@@ -171,6 +512,61 @@ impl AcceleratedClient {
         Ok(response)
     }
 
+    /// Conditional GET: sends `If-None-Match` / `If-Modified-Since` from the given validators
+    ///
+    /// Returns [`Conditional::NotModified`] on 304 so callers can keep their cached copy.
+    pub async fn get_if_modified(
+        &self,
+        url: &str,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+    ) -> Result<Conditional> {
+        let validators = Validators {
+            etag: etag.map(String::from),
+            last_modified: last_modified.map(String::from),
+        };
+        crate::policy::check(url)?;
+        let headers = self
+            .profile
+            .read()
+            .await
+            .request_headers(FetchContext::Navigate, "none");
+        let response = self
+            .client
+            .get(url)
+            .headers(headers)
+            .headers(validators.request_headers())
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            debug!("Not modified");
+            return Ok(Conditional::NotModified);
+        }
+        Ok(Conditional::Modified(response))
+    }
+
+    /// Fetch `url` into a [`crate::Response`]: HTML as Markdown, JSON parsed
+    ///
+    /// Redirect hops are recorded for clients built with
+    /// [`Self::with_redirect_log`].
+    pub async fn get(&self, url: &str) -> Result<crate::Response> {
+        if let Some(log) = &self.redirect_log {
+            log.start();
+        }
+        let start = Instant::now();
+        let response = self.fetch(url).await?;
+        let ttfb = start.elapsed();
+        let redirects = self
+            .redirect_log
+            .as_ref()
+            .map(RedirectLog::hops)
+            .unwrap_or_default();
+        Ok(crate::Response::read(response, None, ttfb, redirects)
+            .await?
+            .parsed())
+    }
+
     /// Fetch and return body as string
     pub async fn fetch_text(&self, url: &str) -> Result<String> {
         let response = self.fetch(url).await?;
@@ -209,6 +605,30 @@ impl Default for AcceleratedClient {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_validator_headers() {
+        let validators = Validators {
+            etag: Some("\"abc123\"".into()),
+            last_modified: Some("2026-01-02T03:04:05Z".into()),
+        };
+        let headers = validators.request_headers();
+        assert_eq!(headers[header::IF_NONE_MATCH], "\"abc123\"");
+        assert_eq!(
+            headers[header::IF_MODIFIED_SINCE],
+            "Fri, 02 Jan 2026 03:04:05 GMT"
+        );
+
+        let http = http_date("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(http, "Wed, 21 Oct 2015 07:28:00 GMT");
+        assert!(Validators::default().request_headers().is_empty());
+
+        let mut response_headers = HeaderMap::new();
+        response_headers.insert(header::ETAG, HeaderValue::from_static("W/\"v1\""));
+        let read = Validators::from_headers(&response_headers);
+        assert_eq!(read.etag.as_deref(), Some("W/\"v1\""));
+        assert_eq!(read.last_modified, None);
+    }
+
     #[tokio::test]
     async fn test_fetch_example() {
         let client = AcceleratedClient::new().unwrap();
@@ -0,0 +1,157 @@
+//! Resumable Jobs (`--resume-job`)
+//!
+//! A job manifest records which URLs of a long `nab batch` run are done, so
+//! running the same job again after an interruption (Ctrl-C, a crash, a lost
+//! SSH session) skips them and fetches only the rest. A URL is recorded once
+//! its output is complete: its Markdown file renamed into place, or its result
+//! line printed. Failed fetches, 429s, and 5xx responses aren't recorded and
+//! are tried again.
+//!
+//! The manifest is saved every [`SAVE_EVERY`] completions and when the job
+//! ends, atomically (see [`crate::state`]), so an interrupted job loses at
+//! most the last few records; those URLs are fetched again and their files
+//! overwritten in one step. One process holds a manifest at a time.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::state::{StateLock, Versioned};
+
+/// Save the manifest every this many completions
+pub const SAVE_EVERY: usize = 25;
+
+/// A finished URL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Completed {
+    pub status: u16,
+    /// Output file, if the page was saved to one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    pub completed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    completed: BTreeMap<String, Completed>,
+}
+
+impl Versioned for Manifest {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+/// The completed URLs of a job, saved to a manifest file
+#[derive(Debug)]
+pub struct JobManifest {
+    path: PathBuf,
+    manifest: Manifest,
+    unsaved: usize,
+    _lock: StateLock,
+}
+
+impl JobManifest {
+    /// Open the manifest at `path`, empty if it doesn't exist yet
+    ///
+    /// Fails if another nab process is running the job.
+    pub fn open(path: &Path) -> Result<Self> {
+        let lock = crate::state::lock(path, Duration::ZERO)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            manifest: crate::state::load(path)?.unwrap_or_default(),
+            unsaved: 0,
+            _lock: lock,
+        })
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether `url` was completed by this or an earlier run
+    #[must_use]
+    pub fn is_completed(&self, url: &str) -> bool {
+        self.manifest.completed.contains_key(url)
+    }
+
+    /// Number of completed URLs
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.manifest.completed.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.manifest.completed.is_empty()
+    }
+
+    /// Record `url` as done, unless its `status` is worth retrying
+    pub fn complete(&mut self, url: &str, status: u16, file: Option<String>) -> Result<()> {
+        if status == 429 || status >= 500 {
+            return Ok(());
+        }
+        self.manifest.completed.insert(
+            url.to_string(),
+            Completed {
+                status,
+                file,
+                completed_at: Utc::now(),
+            },
+        );
+        self.unsaved += 1;
+        if self.unsaved >= SAVE_EVERY {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Write the manifest now
+    pub fn save(&mut self) -> Result<()> {
+        crate::state::save(&self.path, &self.manifest)?;
+        self.unsaved = 0;
+        Ok(())
+    }
+}
+
+impl Drop for JobManifest {
+    fn drop(&mut self) {
+        // Keep the records of a job that ended early
+        if self.unsaved > 0 {
+            if let Err(e) = self.save() {
+                tracing::warn!("Failed to save job manifest: {e:#}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_survives_runs() {
+        let dir = std::env::temp_dir().join(format!("nab-job-{}", std::process::id()));
+        let path = dir.join("job.json");
+
+        let mut job = JobManifest::open(&path).unwrap();
+        assert!(job.is_empty());
+        assert!(JobManifest::open(&path).is_err(), "one process per job");
+        job.complete("https://a.example/", 200, Some("a.md".into()))
+            .unwrap();
+        job.complete("https://b.example/", 503, None).unwrap();
+        job.complete("https://c.example/", 404, None).unwrap();
+        drop(job);
+
+        let again = JobManifest::open(&path).unwrap();
+        assert_eq!(again.len(), 2);
+        assert!(again.is_completed("https://a.example/"));
+        assert!(!again.is_completed("https://b.example/"));
+        assert!(again.is_completed("https://c.example/"));
+        drop(again);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,115 @@
+//! jq Filters
+//!
+//! `--jq '.products[] | {name, price}'` runs a jq program over a command's
+//! JSON output in-process (via [jaq](https://github.com/01mf02/jaq), with the
+//! jq standard library), so scripts on minimal containers don't need a `jq`
+//! binary to pick fields out of `extract`, `spa`, or `fetch --format json`.
+
+use anyhow::Result;
+use jaq_core::load::{Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, Native, RcIter};
+use jaq_json::Val;
+use serde_json::Value;
+
+/// A compiled jq program
+pub struct JqFilter {
+    filter: jaq_core::Filter<Native<Val>>,
+}
+
+impl std::fmt::Debug for JqFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JqFilter").finish_non_exhaustive()
+    }
+}
+
+impl JqFilter {
+    /// Compile a jq program
+    pub fn parse(code: &str) -> Result<Self> {
+        let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+        let arena = Arena::default();
+        let modules = loader
+            .load(&arena, File { code, path: () })
+            .map_err(|errors| {
+                let reasons: Vec<String> = errors
+                    .into_iter()
+                    .flat_map(|(_, error)| load_error(error))
+                    .collect();
+                anyhow::anyhow!("Invalid jq filter: {}", reasons.join("; "))
+            })?;
+        let filter = Compiler::default()
+            .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+            .compile(modules)
+            .map_err(|errors| {
+                let reasons: Vec<String> = errors
+                    .into_iter()
+                    .flat_map(|(_, undefined)| undefined)
+                    .map(|(name, kind)| format!("undefined {} {name}", kind.as_str()))
+                    .collect();
+                anyhow::anyhow!("Invalid jq filter: {}", reasons.join("; "))
+            })?;
+        Ok(Self { filter })
+    }
+
+    /// Run the program on `input`, collecting every output
+    pub fn run(&self, input: Value) -> Result<Vec<Value>> {
+        let inputs = RcIter::new(core::iter::empty());
+        self.filter
+            .run((Ctx::new([], &inputs), Val::from(input)))
+            .map(|out| out.map(Value::from).map_err(|e| anyhow::anyhow!("jq: {e}")))
+            .collect()
+    }
+}
+
+/// Readable reasons for a lex or parse error
+fn load_error(error: jaq_core::load::Error<&str>) -> Vec<String> {
+    use jaq_core::load::Error;
+    let near = |at: &str| at.chars().take(20).collect::<String>();
+    match error {
+        Error::Io(errors) => errors.into_iter().map(|(_, e)| e).collect(),
+        Error::Lex(errors) => errors
+            .into_iter()
+            .map(|(expect, at)| format!("expected {} near `{}`", expect.as_str(), near(at)))
+            .collect(),
+        Error::Parse(errors) => errors
+            .into_iter()
+            .map(|(expect, at)| format!("expected {} near `{}`", expect.as_str(), near(at)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_filter_outputs() {
+        let data = json!({"products": [
+            {"name": "Mug", "price": 12, "sku": "m-1"},
+            {"name": "Cap", "price": 20, "sku": "c-1"},
+        ]});
+        let filter = JqFilter::parse(".products[] | {name, price}").unwrap();
+        assert_eq!(
+            filter.run(data.clone()).unwrap(),
+            vec![
+                json!({"name": "Mug", "price": 12}),
+                json!({"name": "Cap", "price": 20})
+            ]
+        );
+
+        // Standard library definitions are available
+        let total = JqFilter::parse("[.products[].price] | add").unwrap();
+        assert_eq!(total.run(data.clone()).unwrap(), vec![json!(32)]);
+        let cheap = JqFilter::parse("[.products[] | select(.price < 15) | .sku]").unwrap();
+        assert_eq!(cheap.run(data).unwrap(), vec![json!(["m-1"])]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(JqFilter::parse(".products[").is_err());
+        let undefined = JqFilter::parse("nosuchfn").unwrap_err();
+        assert!(undefined.to_string().contains("nosuchfn"), "{undefined}");
+        let runtime = JqFilter::parse(".[0]").unwrap();
+        assert!(runtime.run(json!({"a": 1})).is_err());
+    }
+}
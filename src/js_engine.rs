@@ -3,31 +3,124 @@
 //! Provides minimal JavaScript execution for SPA support.
 //! Uses `QuickJS` via rquickjs bindings (ES2020, ~1MB).
 
+use std::sync::{Arc, Mutex};
+
 use anyhow::Result;
-use rquickjs::{Context, Function, Runtime, Type};
+use rquickjs::{Context, Ctx, Function, Runtime, Type};
 use tracing::debug;
 
+use crate::sandbox::{ExecBudget, SandboxLimits, SandboxViolation, ViolationLog};
+
 /// Minimal JavaScript engine for executing scripts
 pub struct JsEngine {
     /// Runtime must be kept alive for Context lifetime - not directly used after initialization
     #[allow(dead_code)]
     runtime: Runtime,
     context: Context,
+    limits: SandboxLimits,
+    budget: Arc<Mutex<ExecBudget>>,
+    violations: ViolationLog,
 }
 
 impl JsEngine {
-    /// Create a new JavaScript engine
+    /// Create a new JavaScript engine with default sandbox limits
     pub fn new() -> Result<Self> {
+        Self::with_limits(SandboxLimits::default())
+    }
+
+    /// Create a JavaScript engine with explicit sandbox limits
+    pub fn with_limits(limits: SandboxLimits) -> Result<Self> {
         let runtime = Runtime::new()?;
         let context = Context::full(&runtime)?;
 
-        // Set memory limit to 32MB (reasonable for web scraping)
-        runtime.set_memory_limit(32 * 1024 * 1024);
+        // Heap limit (32MB by default, reasonable for web scraping)
+        runtime.set_memory_limit(limits.memory_limit);
 
         // Set max stack size
         runtime.set_max_stack_size(1024 * 1024);
 
-        Ok(Self { runtime, context })
+        // Interrupt page scripts once the execution budget is spent
+        let budget = Arc::new(Mutex::new(ExecBudget::new(limits.timeout)));
+        let handler_budget = Arc::clone(&budget);
+        runtime.set_interrupt_handler(Some(Box::new(move || {
+            handler_budget.lock().is_ok_and(|b| b.exhausted())
+        })));
+
+        Ok(Self {
+            runtime,
+            context,
+            limits,
+            budget,
+            violations: ViolationLog::default(),
+        })
+    }
+
+    /// Execute untrusted page JavaScript under the sandbox time budget
+    ///
+    /// Running out of time or memory is recorded as a violation (see
+    /// [`JsEngine::violations`]) and returned as an error.
+    pub fn eval_sandboxed(&self, code: &str) -> Result<String> {
+        if let Ok(mut budget) = self.budget.lock() {
+            budget.start();
+        }
+        let result = self.eval(code);
+        let exhausted = self.budget.lock().is_ok_and(|mut b| {
+            let exhausted = b.exhausted();
+            b.stop();
+            exhausted
+        });
+
+        if let Err(e) = &result {
+            if exhausted {
+                let limit_ms = self
+                    .limits
+                    .timeout
+                    .map_or(0, |t| u64::try_from(t.as_millis()).unwrap_or(u64::MAX));
+                self.violations
+                    .record(SandboxViolation::Timeout { limit_ms });
+            } else if e.to_string().contains("out of memory") {
+                self.violations.record(SandboxViolation::Memory {
+                    limit_bytes: self.limits.memory_limit,
+                });
+            }
+        }
+        result
+    }
+
+    /// Run nab's own code over page state (e.g. `JSON.stringify(window)`)
+    ///
+    /// Such code runs page getters and `toJSON`, so it gets the sandbox
+    /// limits too, with a fresh time budget rather than what the page
+    /// scripts left over.
+    pub fn eval_page_state(&self, code: &str) -> Result<String> {
+        let page_budget = self
+            .budget
+            .lock()
+            .map(|mut b| std::mem::replace(&mut *b, ExecBudget::new(self.limits.timeout)))
+            .ok();
+        let result = self.eval_sandboxed(code);
+        if let (Some(page_budget), Ok(mut budget)) = (page_budget, self.budget.lock()) {
+            *budget = page_budget;
+        }
+        result
+    }
+
+    /// Sandbox limits this engine was created with
+    #[must_use]
+    pub fn limits(&self) -> &SandboxLimits {
+        &self.limits
+    }
+
+    /// Shared violation log (pass to [`crate::FetchClient`] to log blocked fetches)
+    #[must_use]
+    pub fn violation_log(&self) -> ViolationLog {
+        self.violations.clone()
+    }
+
+    /// All sandbox violations recorded so far
+    #[must_use]
+    pub fn violations(&self) -> Vec<SandboxViolation> {
+        self.violations.snapshot()
     }
 
     /// Execute JavaScript code and return the result as a string
@@ -35,7 +128,7 @@ impl JsEngine {
         debug!("Evaluating JS: {} chars", code.len());
 
         self.context.with(|ctx| {
-            let result: rquickjs::Value = ctx.eval(code)?;
+            let result: rquickjs::Value = ctx.eval(code).map_err(|e| js_error(&ctx, e))?;
 
             // Convert result to string based on type
             let result_str = match result.type_of() {
@@ -226,6 +319,20 @@ impl JsEngine {
     }
 }
 
+/// Turn a `QuickJS` error into an error carrying the thrown exception's message
+fn js_error(ctx: &Ctx<'_>, err: rquickjs::Error) -> anyhow::Error {
+    if !err.is_exception() {
+        return err.into();
+    }
+    let thrown = ctx.catch();
+    let message = thrown
+        .as_exception()
+        .and_then(rquickjs::Exception::message)
+        .or_else(|| thrown.as_string().and_then(|s| s.to_string().ok()))
+        .unwrap_or_else(|| err.to_string());
+    anyhow::anyhow!(message)
+}
+
 impl Default for JsEngine {
     fn default() -> Self {
         Self::new().expect("Failed to create JS engine")
@@ -351,6 +458,51 @@ mod tests {
         assert_eq!(result, "4");
     }
 
+    #[test]
+    fn test_sandbox_timeout() {
+        let engine = JsEngine::with_limits(SandboxLimits {
+            timeout: Some(std::time::Duration::from_millis(50)),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(engine.eval_sandboxed("while (true) {}").is_err());
+        assert_eq!(
+            engine.violations(),
+            vec![SandboxViolation::Timeout { limit_ms: 50 }]
+        );
+
+        // Trusted extraction code still runs after the page budget is spent
+        assert_eq!(engine.eval("1 + 1").unwrap(), "2");
+        assert_eq!(engine.eval_page_state("1 + 1").unwrap(), "2");
+
+        // ...but page getters it runs into are interrupted
+        engine
+            .eval("Object.defineProperty(globalThis, 'trap', { get() { while (true) {} }, enumerable: true })")
+            .unwrap();
+        assert!(engine
+            .eval_page_state("JSON.stringify(globalThis)")
+            .is_err());
+        assert_eq!(engine.violations().len(), 2);
+    }
+
+    #[test]
+    fn test_sandbox_memory() {
+        let engine = JsEngine::with_limits(SandboxLimits {
+            memory_limit: 4 * 1024 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let result =
+            engine.eval_sandboxed("var a = []; while (true) { a.push('x'.repeat(1024)); }");
+        assert!(result.is_err());
+        assert!(matches!(
+            engine.violations().as_slice(),
+            [SandboxViolation::Memory { .. }]
+        ));
+    }
+
     #[test]
     fn test_async_await() {
         let engine = JsEngine::new().unwrap();
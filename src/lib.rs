@@ -21,24 +21,102 @@
 //! }
 //! ```
 
+#[cfg(feature = "analyze")]
 pub mod analyze;
+#[cfg(feature = "analyze")]
 pub mod annotate;
+pub mod a11y;
 pub mod api_discovery;
+pub mod audit;
 pub mod auth;
+pub mod batch;
 pub mod browser_detect;
+pub mod canonical;
+pub mod captcha;
+pub mod cassette;
+pub mod challenge;
+pub mod compare;
+pub mod compile;
+pub mod compress;
+pub mod config;
+pub mod consent;
+pub mod content;
+pub mod coordinator;
+pub mod crawl;
+pub mod curl;
+pub mod deadline;
+pub mod epub;
+pub mod estimate;
+pub mod export;
+#[cfg(feature = "spa")]
 pub mod fetch_bridge;
 pub mod fingerprint;
+pub mod geo;
+pub mod har;
+pub mod hosts;
 pub mod http3_client;
+pub mod http_auth;
 pub mod http_client;
+pub mod jq;
+pub mod job;
+#[cfg(feature = "spa")]
 pub mod js_engine;
+pub mod link_graph;
+pub mod linkcheck;
+pub mod login;
+pub mod media;
 pub mod mfa;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+pub mod navigation;
+pub mod paginate;
+pub mod oauth2;
+pub mod office;
+pub mod pacing;
+pub mod paywall;
+pub mod pdf;
+pub mod plugin;
+pub mod policy;
 pub mod prefetch;
+pub mod protocol_cache;
+pub mod proxy;
+pub mod proxy_check;
+pub mod prune;
+pub mod publish;
+pub mod quota;
+#[cfg(feature = "redis")]
+pub mod redis_frontier;
+pub mod request_options;
+pub mod response;
+pub mod revisit;
+pub mod sandbox;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod secrets;
+pub mod self_update;
+pub mod shutdown;
+pub mod state;
+pub mod store;
+#[cfg(feature = "stream")]
 pub mod stream;
+pub mod subresource;
+pub mod summarize;
+pub mod timing;
+pub mod tls;
+pub mod tls_audit;
+pub mod tls_session;
+pub mod traffic;
+pub mod translate;
+#[cfg(feature = "wasm")]
+pub mod wasm_bridge;
 pub mod websocket;
+pub mod workspace;
 
+#[cfg(feature = "analyze")]
 pub use analyze::{
     AnalysisOutput, AnalysisPipeline, AnalysisSegment, PipelineConfig as AnalysisPipelineConfig,
 };
+#[cfg(feature = "analyze")]
 pub use annotate::{
     AnalysisConfig as AnnotateAnalysisConfig, AnalysisOverlay, AnnotationPipeline, AssGenerator,
     Compositor, CompositorConfig, OverlayPosition, OverlayTrack,
@@ -51,18 +129,56 @@ pub use auth::{
     OtpRetriever, OtpSource,
 };
 pub use browser_detect::{detect_default_browser, BrowserType};
+pub use captcha::{CaptchaChallenge, CaptchaKind, CaptchaSolution, CaptchaSolver};
+pub use cassette::{Cassette, Interaction};
+pub use compile::{article_epub, compile_epub, compile_markdown, CompiledArticle};
+pub use config::NabConfig;
+pub use consent::{detect_cmps, strip_consent_walls, ConsentMode};
+pub use content::ContentKind;
+pub use epub::EpubBuilder;
+pub use extract::{ArticleData, JobPosting, Listing, Preset, Product};
+#[cfg(feature = "spa")]
 pub use fetch_bridge::{inject_fetch_sync, FetchClient};
 pub use fingerprint::{
-    chrome_profile, firefox_profile, random_profile, safari_profile, BrowserProfile,
+    chrome_profile, firefox_profile, persona_profile, random_profile, safari_profile,
+    BrowserProfile, FetchContext,
 };
+pub use geo::Geo;
+pub use har::{Har, HarEntry};
 pub use http3_client::Http3Client;
 #[cfg(feature = "http3")]
 pub use http3_client::Http3Response;
-pub use http_client::AcceleratedClient;
+pub use http_auth::UserCredentials;
+pub use http_client::{AcceleratedClient, ClientOptions, Conditional, HttpVersion, Validators};
+#[cfg(feature = "spa")]
 pub use js_engine::JsEngine;
+pub use language::{detect_language, DetectedLanguage};
+pub use login::{AuthProvider, LoginRecipe, Session, SessionAuth};
+pub use markdown::MarkdownStream;
 pub use mfa::{detect_mfa_type, MfaHandler, MfaResult, MfaType, NotificationConfig};
+pub use nab_core::{extract, language, markdown, page, readability};
+pub use navigation::{Navigator, RefererPolicy};
+pub use paywall::{detect_gate, CrawlerIdentity, GateKind, GateReport};
 pub use prefetch::{extract_link_hints, EarlyHintLink, EarlyHints, PrefetchManager};
+pub use proxy::{ProxyChain, ProxyConfig, ProxyHop};
+pub use readability::{extract_article, Article};
+pub use request_options::{
+    BrowserCookies, OptionsError, ProfileChoice, RequestOptions, RequestOptionsBuilder,
+};
+pub use response::{Body, Response};
+pub use revisit::{CachedPage, RevisitCache};
+pub use sandbox::{NetworkPolicy, SandboxLimits, SandboxViolation, ViolationLog};
+pub use secrets::SecretStore;
+#[cfg(feature = "stream")]
 pub use stream::{StreamBackend, StreamInfo, StreamProvider};
+pub use subresource::{LoadedResource, ResourceKind, WarmPlan};
+pub use summarize::{summarize, SummarizeBackend};
+pub use timing::{RedirectHop, RedirectLog, Timings};
+pub use tls::{ClientCert, TlsOptions};
+pub use tls_session::SessionStore;
+pub use translate::{translate_markdown, TranslateBackend};
+#[cfg(feature = "wasm")]
+pub use wasm_bridge::{inject_wasm_sync, WasmBridge, WasmConfig};
 pub use websocket::{JsonRpcWebSocket, WebSocket, WebSocketMessage};
 
 /// Version of nab
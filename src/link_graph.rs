@@ -0,0 +1,265 @@
+//! Crawl Link Graph (`nab crawl --graph`)
+//!
+//! The pages a crawl fetched and the links between them, for Gephi,
+//! Graphviz, or scripts (orphan pages are crawled nodes nothing links to).
+//! Every link found becomes an edge, so pages outside the crawl's scope or
+//! depth appear as nodes without a status. The file's extension picks the
+//! format: `.dot`/`.gv`, `.graphml`, or `.json` (nodes and edges lists).
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::crawl::normalize_url;
+
+/// A URL in the graph
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Node {
+    /// Response status; `None` for pages that weren't fetched or failed
+    pub status: Option<u16>,
+    /// Link depth from the seeds, for crawled pages
+    pub depth: Option<u32>,
+    /// Whether the crawl fetched (or tried to fetch) the page
+    pub crawled: bool,
+}
+
+/// A link, with the anchor text it first appeared with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    pub text: String,
+}
+
+/// File format for [`LinkGraph::write`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    Json,
+}
+
+impl GraphFormat {
+    /// Format for a file name's extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("dot" | "gv") => Ok(Self::Dot),
+            Some("graphml") => Ok(Self::GraphMl),
+            Some("json") => Ok(Self::Json),
+            _ => bail!(
+                "Can't tell the graph format of {}: use .dot, .gv, .graphml, or .json",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Link graph of a crawl
+#[derive(Debug, Clone, Default)]
+pub struct LinkGraph {
+    nodes: BTreeMap<String, Node>,
+    edges: BTreeMap<(String, String), String>,
+}
+
+impl LinkGraph {
+    /// A page the crawl fetched, `status` `None` if the request failed
+    pub fn page(&mut self, url: &str, status: Option<u16>, depth: u32) {
+        let node = self.nodes.entry(url.to_string()).or_default();
+        node.status = status;
+        node.depth = Some(depth);
+        node.crawled = true;
+    }
+
+    /// A link from `source` to `target` (each pair once)
+    pub fn link(&mut self, source: &str, target: &str, text: &str) {
+        let target = normalize_url(target);
+        self.nodes.entry(source.to_string()).or_default();
+        self.nodes.entry(target.clone()).or_default();
+        self.edges
+            .entry((source.to_string(), target))
+            .or_insert_with(|| text.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[must_use]
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Crawled pages that no other page links to
+    #[must_use]
+    pub fn orphans(&self) -> Vec<&str> {
+        let linked: BTreeSet<&str> = self
+            .edges
+            .keys()
+            .filter(|(source, target)| source != target)
+            .map(|(_, target)| target.as_str())
+            .collect();
+        self.nodes
+            .iter()
+            .filter(|(url, node)| node.crawled && !linked.contains(url.as_str()))
+            .map(|(url, _)| url.as_str())
+            .collect()
+    }
+
+    fn edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.edges.iter().map(|((source, target), text)| Edge {
+            source: source.clone(),
+            target: target.clone(),
+            text: text.clone(),
+        })
+    }
+
+    /// Graphviz DOT; statuses and depths are node attributes
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph crawl {\n");
+        for (url, node) in &self.nodes {
+            let _ = write!(out, "  {}", dot_quote(url));
+            let mut attrs = vec![format!("crawled={}", node.crawled)];
+            if let Some(status) = node.status {
+                attrs.push(format!("status={status}"));
+            }
+            if let Some(depth) = node.depth {
+                attrs.push(format!("depth={depth}"));
+            }
+            let _ = writeln!(out, " [{}];", attrs.join(", "));
+        }
+        for edge in self.edges() {
+            let _ = writeln!(
+                out,
+                "  {} -> {} [label={}];",
+                dot_quote(&edge.source),
+                dot_quote(&edge.target),
+                dot_quote(&edge.text)
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// GraphML with `status`, `depth`, and `crawled` node data and `text` edge data
+    #[must_use]
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"status\" for=\"node\" attr.name=\"status\" attr.type=\"int\"/>\n",
+            "  <key id=\"depth\" for=\"node\" attr.name=\"depth\" attr.type=\"int\"/>\n",
+            "  <key id=\"crawled\" for=\"node\" attr.name=\"crawled\" attr.type=\"boolean\"/>\n",
+            "  <key id=\"text\" for=\"edge\" attr.name=\"text\" attr.type=\"string\"/>\n",
+            "  <graph id=\"crawl\" edgedefault=\"directed\">\n",
+        ));
+        for (url, node) in &self.nodes {
+            let _ = writeln!(out, "    <node id=\"{}\">", xml_escape(url));
+            if let Some(status) = node.status {
+                let _ = writeln!(out, "      <data key=\"status\">{status}</data>");
+            }
+            if let Some(depth) = node.depth {
+                let _ = writeln!(out, "      <data key=\"depth\">{depth}</data>");
+            }
+            let _ = writeln!(out, "      <data key=\"crawled\">{}</data>", node.crawled);
+            out.push_str("    </node>\n");
+        }
+        for edge in self.edges() {
+            let _ = writeln!(
+                out,
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"text\">{}</data></edge>",
+                xml_escape(&edge.source),
+                xml_escape(&edge.target),
+                xml_escape(&edge.text)
+            );
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    /// `{"nodes": [{"id": URL, ...}], "edges": [{"source", "target", "text"}]}`
+    #[must_use]
+    pub fn to_json(&self) -> serde_json::Value {
+        let nodes: Vec<serde_json::Value> = self
+            .nodes
+            .iter()
+            .map(|(url, node)| {
+                let mut value = serde_json::to_value(node).unwrap_or_default();
+                value["id"] = url.as_str().into();
+                value
+            })
+            .collect();
+        let edges: Vec<Edge> = self.edges().collect();
+        serde_json::json!({ "nodes": nodes, "edges": edges })
+    }
+
+    /// Write the graph to `path` (atomically) in the format its extension names
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let data = match GraphFormat::from_path(path)? {
+            GraphFormat::Dot => self.to_dot().into_bytes(),
+            GraphFormat::GraphMl => self.to_graphml().into_bytes(),
+            GraphFormat::Json => serde_json::to_vec_pretty(&self.to_json())?,
+        };
+        crate::state::write_atomic(path, &data)
+    }
+}
+
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_formats() {
+        let mut graph = LinkGraph::default();
+        graph.page("https://a.test/", Some(200), 0);
+        graph.link(
+            "https://a.test/",
+            "https://a.test/docs#intro",
+            " Read\n the \"docs\" ",
+        );
+        graph.link("https://a.test/", "https://a.test/docs", "again");
+        graph.link("https://a.test/", "https://b.test/?q=1&r=2", "<b>");
+        graph.page("https://a.test/docs", Some(404), 1);
+        graph.page("https://a.test/lonely", None, 1);
+
+        assert_eq!((graph.node_count(), graph.edge_count()), (4, 2));
+        assert_eq!(
+            graph.orphans(),
+            ["https://a.test/", "https://a.test/lonely"]
+        );
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("  \"https://a.test/docs\" [crawled=true, status=404, depth=1];"));
+        assert!(dot.contains(
+            "  \"https://a.test/\" -> \"https://a.test/docs\" [label=\"Read the \\\"docs\\\"\"];"
+        ));
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<edge source=\"https://a.test/\" target=\"https://b.test/?q=1&amp;r=2\"><data key=\"text\">&lt;b&gt;</data></edge>"));
+        let json = graph.to_json();
+        assert_eq!(json["nodes"][1]["id"], "https://a.test/docs");
+        assert_eq!(json["nodes"][1]["status"], 404);
+        assert_eq!(json["edges"][0]["text"], "Read the \"docs\"");
+
+        assert!(GraphFormat::from_path(Path::new("site.GraphML")).is_ok());
+        assert!(GraphFormat::from_path(Path::new("site.txt")).is_err());
+    }
+}
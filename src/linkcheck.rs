@@ -0,0 +1,341 @@
+//! Link Checking (`nab linkcheck`)
+//!
+//! Crawls a site like `nab crawl` and checks every link its pages contain,
+//! internal and outbound, once each:
+//! - links share one [`Frontier`](crate::crawl::Frontier), so every host,
+//!   outbound ones included, gets the crawl's politeness delay and
+//!   concurrency limits
+//! - pages in scope and within the depth limit are fetched with GET and their
+//!   links queued; everything else gets HEAD, retried as GET when the server
+//!   answers HEAD with an error
+//! - redirects are followed hop by hop (at most [`MAX_REDIRECTS`]) so the
+//!   report shows the whole chain
+//!
+//! The report groups broken links, timeouts, errors, and redirects by the
+//! pages linking to them.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use reqwest::header::{CONTENT_TYPE, LOCATION};
+use reqwest::{Client, Method, Response};
+use serde::Serialize;
+use url::Url;
+
+use crate::crawl::normalize_url;
+use crate::page::extract_links;
+
+/// Longest redirect chain followed
+pub const MAX_REDIRECTS: usize = 10;
+
+/// How a link fared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    Redirected,
+    Broken,
+    Timeout,
+    Error,
+}
+
+impl Outcome {
+    /// Broken, timed out, or failed
+    #[must_use]
+    pub fn is_failure(self) -> bool {
+        matches!(self, Self::Broken | Self::Timeout | Self::Error)
+    }
+}
+
+/// Result of checking one link
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkCheck {
+    pub url: String,
+    pub outcome: Outcome,
+    /// Status of the last response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Each URL redirected to, in order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub time_ms: f64,
+}
+
+impl LinkCheck {
+    /// One-line description, e.g. `404` or `https://example.com/new → 200`
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut summary = self.redirects.join(" → ");
+        if let Some(status) = self.status {
+            if !summary.is_empty() {
+                summary.push_str(" → ");
+            }
+            summary.push_str(&status.to_string());
+        }
+        if let Some(error) = &self.error {
+            if !summary.is_empty() {
+                summary.push_str(": ");
+            }
+            summary.push_str(error);
+        }
+        summary
+    }
+}
+
+/// Check `url` with a client that doesn't follow redirects
+///
+/// With `crawl`, the page is fetched with GET and, if it's HTML, its absolute
+/// HTTP(S) links are returned as `(anchor text, URL)`.
+pub async fn check(
+    client: &Client,
+    url: &str,
+    crawl: bool,
+) -> (LinkCheck, Option<Vec<(String, Url)>>) {
+    let start = Instant::now();
+    let mut redirects = Vec::new();
+    let result = follow(client, url, crawl, &mut redirects).await;
+    let mut check = LinkCheck {
+        url: url.to_string(),
+        outcome: Outcome::Ok,
+        status: None,
+        redirects,
+        error: None,
+        time_ms: 0.0,
+    };
+    let links = match result {
+        Ok((response, final_url)) => {
+            let status = response.status();
+            check.status = Some(status.as_u16());
+            check.outcome = if status.is_client_error() || status.is_server_error() {
+                Outcome::Broken
+            } else if check.redirects.is_empty() {
+                Outcome::Ok
+            } else {
+                Outcome::Redirected
+            };
+            let is_html = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|ct| ct.contains("html"));
+            if crawl && is_html && status.is_success() {
+                match response.text().await {
+                    Ok(body) => Some(page_links(&body, &final_url)),
+                    Err(e) => {
+                        check.outcome = Outcome::Error;
+                        check.error = Some(e.to_string());
+                        None
+                    }
+                }
+            } else {
+                None
+            }
+        }
+        Err(e) => {
+            let timed_out = e
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(reqwest::Error::is_timeout);
+            check.outcome = if timed_out {
+                Outcome::Timeout
+            } else {
+                Outcome::Error
+            };
+            check.error = Some(format!("{e:#}"));
+            None
+        }
+    };
+    check.time_ms = crate::timing::ms(start.elapsed());
+    (check, links)
+}
+
+/// Request `url`, following redirects into `redirects`; the last response and its URL
+async fn follow(
+    client: &Client,
+    url: &str,
+    crawl: bool,
+    redirects: &mut Vec<String>,
+) -> Result<(Response, Url)> {
+    let mut current = Url::parse(url).with_context(|| format!("Invalid URL '{url}'"))?;
+    loop {
+        let response = request(client, &current, crawl).await?;
+        if !response.status().is_redirection() {
+            return Ok((response, current));
+        }
+        let Some(location) = response.headers().get(LOCATION) else {
+            return Ok((response, current));
+        };
+        let location = location.to_str().context("Unreadable Location header")?;
+        let next = current
+            .join(location)
+            .with_context(|| format!("Invalid redirect to '{location}'"))?;
+        if redirects.len() == MAX_REDIRECTS {
+            bail!("More than {MAX_REDIRECTS} redirects");
+        }
+        redirects.push(next.to_string());
+        current = next;
+    }
+}
+
+/// GET for crawled pages; HEAD otherwise, then GET if HEAD fails
+async fn request(client: &Client, url: &Url, crawl: bool) -> Result<Response> {
+    crate::policy::check_url(url)?;
+    if !crawl {
+        let head = client.request(Method::HEAD, url.clone()).send().await?;
+        if !(head.status().is_client_error() || head.status().is_server_error()) {
+            return Ok(head);
+        }
+    }
+    Ok(client.get(url.clone()).send().await?)
+}
+
+fn page_links(body: &str, base: &Url) -> Vec<(String, Url)> {
+    extract_links(body)
+        .into_iter()
+        .filter_map(|(text, href)| Some((text, base.join(&href).ok()?)))
+        .filter(|(_, link)| matches!(link.scheme(), "http" | "https"))
+        .collect()
+}
+
+/// Problem links of one page
+#[derive(Debug, Clone, Serialize)]
+pub struct PageProblems {
+    /// The linking page (`None` for problems with the seeds themselves)
+    pub page: Option<String>,
+    pub links: Vec<LinkCheck>,
+}
+
+/// Which pages link where, and how each link fared
+#[derive(Debug, Default)]
+pub struct LinkIndex {
+    sources: HashMap<String, BTreeSet<String>>,
+    checks: HashMap<String, LinkCheck>,
+}
+
+impl LinkIndex {
+    /// Note that `page` links to `link`
+    pub fn found(&mut self, page: &str, link: &str) {
+        self.sources
+            .entry(normalize_url(link))
+            .or_default()
+            .insert(page.to_string());
+    }
+
+    /// Record a link's check
+    pub fn checked(&mut self, check: LinkCheck) {
+        self.checks.insert(normalize_url(&check.url), check);
+    }
+
+    /// Pages linking to `link`
+    #[must_use]
+    pub fn sources(&self, link: &str) -> usize {
+        self.sources
+            .get(&normalize_url(link))
+            .map_or(0, BTreeSet::len)
+    }
+
+    /// Links checked
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.checks.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+
+    /// Number of links per outcome
+    #[must_use]
+    pub fn counts(&self) -> BTreeMap<Outcome, usize> {
+        let mut counts = BTreeMap::new();
+        for check in self.checks.values() {
+            *counts.entry(check.outcome).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Failed links, and redirected ones unless `ignore_redirects`, by linking page
+    #[must_use]
+    pub fn problems(&self, ignore_redirects: bool) -> Vec<PageProblems> {
+        let mut pages: BTreeMap<Option<&str>, Vec<LinkCheck>> = BTreeMap::new();
+        for (url, check) in &self.checks {
+            let reported = check.outcome.is_failure()
+                || (check.outcome == Outcome::Redirected && !ignore_redirects);
+            if !reported {
+                continue;
+            }
+            match self.sources.get(url) {
+                Some(sources) => {
+                    for page in sources {
+                        pages.entry(Some(page)).or_default().push(check.clone());
+                    }
+                }
+                None => pages.entry(None).or_default().push(check.clone()),
+            }
+        }
+        pages
+            .into_iter()
+            .map(|(page, mut links)| {
+                links.sort_by(|a, b| b.outcome.cmp(&a.outcome).then_with(|| a.url.cmp(&b.url)));
+                PageProblems {
+                    page: page.map(String::from),
+                    links,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(url: &str, outcome: Outcome, status: Option<u16>) -> LinkCheck {
+        LinkCheck {
+            url: url.to_string(),
+            outcome,
+            status,
+            redirects: Vec::new(),
+            error: None,
+            time_ms: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_problems_by_page() {
+        let mut index = LinkIndex::default();
+        index.checked(check("https://a.example/", Outcome::Ok, Some(200)));
+        index.found("https://a.example/", "https://a.example/gone#top");
+        index.found("https://a.example/about", "https://a.example/gone");
+        index.found("https://a.example/about", "https://b.example/moved");
+        index.checked(check("https://a.example/gone", Outcome::Broken, Some(404)));
+        let mut moved = check("https://b.example/moved", Outcome::Redirected, Some(200));
+        moved.redirects = vec!["https://b.example/new".into()];
+        assert_eq!(moved.summary(), "https://b.example/new → 200");
+        index.checked(moved);
+        index.checked(check("https://c.example/", Outcome::Timeout, None));
+
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.sources("https://a.example/gone"), 2);
+        assert_eq!(index.counts()[&Outcome::Broken], 1);
+
+        let problems = index.problems(false);
+        let pages: Vec<_> = problems.iter().map(|p| p.page.as_deref()).collect();
+        assert_eq!(
+            pages,
+            [
+                None,
+                Some("https://a.example/"),
+                Some("https://a.example/about")
+            ]
+        );
+        let about: Vec<_> = problems[2].links.iter().map(|l| l.outcome).collect();
+        assert_eq!(about, [Outcome::Broken, Outcome::Redirected]);
+
+        let problems = index.problems(true);
+        assert_eq!(problems[2].links.len(), 1);
+    }
+}
@@ -0,0 +1,628 @@
+//! Scripted Logins
+//!
+//! `nab login <recipe>` runs a site recipe and stores the resulting session
+//! (cookies and auth headers) in the [`SecretStore`]; later commands use it
+//! with `--auth <name>`. Recipes are JSON files, given as a path or as a name
+//! under `~/.config/nab/recipes/<name>.json`:
+//!
+//! ```json
+//! {
+//!   "name": "intranet",
+//!   "login_url": "https://intranet.example.com/login",
+//!   "username_env": "INTRANET_USER",
+//!   "password_env": "INTRANET_PASSWORD",
+//!   "flow": {
+//!     "type": "form",
+//!     "fields": { "email": "{username}", "password": "{password}" }
+//!   },
+//!   "expect_cookie": "sessionid"
+//! }
+//! ```
+//!
+//! API logins post a JSON body and read a token out of the response:
+//!
+//! ```json
+//! "flow": {
+//!   "type": "api",
+//!   "url": "https://api.example.com/v1/auth",
+//!   "body": { "user": "{username}", "pass": "{password}" },
+//!   "token_path": "data.access_token"
+//! }
+//! ```
+//!
+//! Credentials come from the named environment variables, falling back to
+//! 1Password. `{totp}` in a template is filled from 1Password.
+//!
+//! `--auth oauth2:<client>` uses an OAuth2 client from the config file
+//! instead (see [`crate::oauth2`]); [`AuthProvider`] covers both.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, COOKIE};
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::auth::OnePasswordAuth;
+use crate::fingerprint::random_profile;
+use crate::oauth2::OAuth2Auth;
+use crate::secrets::SecretStore;
+
+/// A site login recipe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginRecipe {
+    /// Session name (default: the recipe file name)
+    #[serde(default)]
+    pub name: String,
+    /// Page the login starts from; its host scopes the stored session
+    pub login_url: String,
+    /// Environment variable holding the username
+    pub username_env: Option<String>,
+    /// Environment variable holding the password
+    pub password_env: Option<String>,
+    /// How to log in
+    pub flow: LoginFlow,
+    /// Fail unless the login sets this cookie
+    pub expect_cookie: Option<String>,
+}
+
+/// Login mechanism of a recipe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LoginFlow {
+    /// Fill and submit the HTML login form on `login_url`
+    Form {
+        /// CSS selector of the form (default: the first form with a password field)
+        form_selector: Option<String>,
+        /// Field values; hidden inputs (CSRF tokens etc.) are kept unless overridden
+        fields: BTreeMap<String, String>,
+    },
+    /// Send credentials to a JSON API and keep the returned token
+    Api {
+        /// Endpoint to call
+        url: String,
+        /// HTTP method
+        #[serde(default = "default_api_method")]
+        method: String,
+        /// JSON body; string values are templated
+        #[serde(default)]
+        body: serde_json::Value,
+        /// Dot path of the token in the response (e.g. `data.access_token`)
+        token_path: Option<String>,
+        /// Header the token is sent in
+        #[serde(default = "default_token_header")]
+        token_header: String,
+        /// Prefix before the token in that header
+        #[serde(default = "default_token_prefix")]
+        token_prefix: String,
+    },
+}
+
+fn default_api_method() -> String {
+    "POST".to_string()
+}
+
+fn default_token_header() -> String {
+    "Authorization".to_string()
+}
+
+fn default_token_prefix() -> String {
+    "Bearer ".to_string()
+}
+
+/// Stored result of a login
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub name: String,
+    /// Recipe that created the session, re-run to refresh it
+    pub recipe: PathBuf,
+    /// Host the session belongs to (subdomains included)
+    pub domain: String,
+    /// `Cookie` header value (may be empty)
+    pub cookies: String,
+    /// Extra request headers such as `Authorization`
+    pub headers: Vec<(String, String)>,
+    /// RFC 3339 login time
+    pub created_at: String,
+}
+
+impl Session {
+    /// Load a stored session by name
+    pub fn load(store: &SecretStore, name: &str) -> Result<Self> {
+        let value = store.get(&session_key(name))?.with_context(|| {
+            format!("No saved session '{name}'. Run `nab login <recipe>` first")
+        })?;
+        serde_json::from_str(&value).with_context(|| format!("Corrupt session '{name}'"))
+    }
+
+    /// Save under the session's name
+    pub fn save(&self, store: &SecretStore) -> Result<()> {
+        store.set(&session_key(&self.name), &serde_json::to_string(self)?)
+    }
+
+    /// Whether requests to `url` should carry this session
+    #[must_use]
+    pub fn applies_to(&self, url: &str) -> bool {
+        url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_lowercase))
+            .is_some_and(|host| {
+                host == self.domain
+                    || host
+                        .strip_suffix(&self.domain)
+                        .is_some_and(|sub| sub.ends_with('.'))
+            })
+    }
+
+    /// `Cookie` and auth headers for a request to `url` (empty for other sites)
+    pub fn header_map(&self, url: &str) -> Result<HeaderMap> {
+        let mut map = HeaderMap::new();
+        if !self.applies_to(url) {
+            return Ok(map);
+        }
+        if !self.cookies.is_empty() {
+            map.insert(COOKIE, HeaderValue::from_str(&self.cookies)?);
+        }
+        for (name, value) in &self.headers {
+            map.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        Ok(map)
+    }
+}
+
+fn session_key(name: &str) -> String {
+    format!("session:{name}")
+}
+
+impl LoginRecipe {
+    /// Load a recipe from a path, or by name from `~/.config/nab/recipes/`
+    pub fn load(recipe: &str) -> Result<(Self, PathBuf)> {
+        let direct = PathBuf::from(recipe);
+        let path = if direct.exists() {
+            direct
+        } else {
+            recipes_dir().join(format!("{recipe}.json"))
+        };
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Login recipe not found: {}", path.display()))?;
+        let mut parsed: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid login recipe {}", path.display()))?;
+        if parsed.name.is_empty() {
+            parsed.name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        // Absolute, so a stored session can re-run it from any directory
+        let path = path.canonicalize().unwrap_or(path);
+        Ok((parsed, path))
+    }
+
+    /// Run the login and return the resulting session (not yet saved)
+    pub async fn run(&self, recipe_path: &Path) -> Result<Session> {
+        let login_url = url::Url::parse(&self.login_url).context("Invalid login_url")?;
+        let domain = login_url
+            .host_str()
+            .context("login_url has no host")?
+            .to_lowercase();
+        let credentials = self.credentials()?;
+
+        let jar = Arc::new(Jar::default());
+        let client = crate::policy::client_builder()
+            .cookie_provider(Arc::clone(&jar))
+            .default_headers(random_profile().to_headers())
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        let mut headers = Vec::new();
+        match &self.flow {
+            LoginFlow::Form {
+                form_selector,
+                fields,
+            } => {
+                let page = client.get(login_url.as_str()).send().await?;
+                let page_url = page.url().clone();
+                let html = page.text().await?;
+                let form = LoginForm::parse(&html, &page_url, form_selector.as_deref())?;
+
+                let mut values = form.hidden;
+                for (name, template) in fields {
+                    values.insert(name.clone(), credentials.fill(template));
+                }
+                let request = if form.method.eq_ignore_ascii_case("get") {
+                    client.get(form.action).query(&values)
+                } else {
+                    client.post(form.action).form(&values)
+                };
+                let response = request.send().await?;
+                if !response.status().is_success() && !response.status().is_redirection() {
+                    anyhow::bail!("Login form rejected: HTTP {}", response.status());
+                }
+            }
+            LoginFlow::Api {
+                url,
+                method,
+                body,
+                token_path,
+                token_header,
+                token_prefix,
+            } => {
+                let method = reqwest::Method::from_bytes(method.to_uppercase().as_bytes())
+                    .context("Invalid API method")?;
+                let response = client
+                    .request(method, url)
+                    .json(&credentials.fill_json(body))
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    anyhow::bail!("Login API rejected: HTTP {}", response.status());
+                }
+                if let Some(path) = token_path {
+                    let json: serde_json::Value = response
+                        .json()
+                        .await
+                        .context("Login API didn't return JSON")?;
+                    let token = json_path(&json, path)
+                        .with_context(|| format!("No token at '{path}' in login response"))?;
+                    headers.push((token_header.clone(), format!("{token_prefix}{token}")));
+                }
+            }
+        }
+
+        let origin = login_url.join("/")?;
+        let cookies = jar
+            .cookies(&origin)
+            .and_then(|v| v.to_str().ok().map(String::from))
+            .unwrap_or_default();
+        if let Some(expected) = &self.expect_cookie {
+            let found = cookies
+                .split("; ")
+                .any(|c| c.split('=').next() == Some(expected.as_str()));
+            if !found {
+                anyhow::bail!("Login failed: cookie '{expected}' was not set");
+            }
+        }
+        if cookies.is_empty() && headers.is_empty() {
+            anyhow::bail!("Login produced no cookies or tokens");
+        }
+
+        Ok(Session {
+            name: self.name.clone(),
+            recipe: recipe_path.to_path_buf(),
+            domain,
+            cookies,
+            headers,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Username/password from the recipe's env vars, else 1Password
+    fn credentials(&self) -> Result<Credentials> {
+        let from_env = |var: &Option<String>| var.as_ref().and_then(|v| std::env::var(v).ok());
+        let mut credentials = Credentials {
+            username: from_env(&self.username_env),
+            password: from_env(&self.password_env),
+            totp: None,
+        };
+        let needs_totp = match &self.flow {
+            LoginFlow::Form { fields, .. } => fields.values().any(|v| v.contains("{totp}")),
+            LoginFlow::Api { body, .. } => body.to_string().contains("{totp}"),
+        };
+
+        if (credentials.password.is_none() || needs_totp) && OnePasswordAuth::is_available() {
+            if let Some(cred) =
+                OnePasswordAuth::new(None).get_credential_for_url(&self.login_url)?
+            {
+                credentials.username = credentials.username.or(cred.username);
+                credentials.password = credentials.password.or(cred.password);
+                credentials.totp = cred.totp;
+            }
+        }
+        if credentials.password.is_none() {
+            anyhow::bail!(
+                "No password for '{}': set {} or add it to 1Password",
+                self.name,
+                self.password_env.as_deref().unwrap_or("password_env")
+            );
+        }
+        Ok(credentials)
+    }
+}
+
+/// Directory searched for recipes given by name
+#[must_use]
+pub fn recipes_dir() -> PathBuf {
+    crate::workspace::config_dir().join("recipes")
+}
+
+/// Values substituted into recipe templates
+#[derive(Debug, Default)]
+struct Credentials {
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+}
+
+impl Credentials {
+    fn fill(&self, template: &str) -> String {
+        template
+            .replace("{username}", self.username.as_deref().unwrap_or_default())
+            .replace("{password}", self.password.as_deref().unwrap_or_default())
+            .replace("{totp}", self.totp.as_deref().unwrap_or_default())
+    }
+
+    fn fill_json(&self, value: &serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+        match value {
+            Value::String(s) => Value::String(self.fill(s)),
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.fill_json(v)).collect()),
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| (k.clone(), self.fill_json(v)))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+/// The login form found on a page
+#[derive(Debug)]
+struct LoginForm {
+    action: url::Url,
+    method: String,
+    /// Hidden inputs, submitted as-is
+    hidden: BTreeMap<String, String>,
+}
+
+impl LoginForm {
+    fn parse(html: &str, page_url: &url::Url, selector: Option<&str>) -> Result<Self> {
+        let document = Html::parse_document(html);
+        let forms = Selector::parse(selector.unwrap_or("form"))
+            .map_err(|e| anyhow::anyhow!("Invalid form selector: {e}"))?;
+        let password = Selector::parse("input[type=password]").expect("valid selector");
+        let hidden = Selector::parse("input[type=hidden][name]").expect("valid selector");
+
+        let mut candidates = document.select(&forms);
+        let form = if selector.is_some() {
+            candidates.next()
+        } else {
+            candidates.find(|f| f.select(&password).next().is_some())
+        }
+        .context("Login form not found on the login page")?;
+
+        let action = match form.value().attr("action").filter(|a| !a.is_empty()) {
+            Some(action) => page_url.join(action)?,
+            None => page_url.clone(),
+        };
+        let hidden = form
+            .select(&hidden)
+            .filter_map(|input| {
+                let name = input.value().attr("name")?;
+                let value = input.value().attr("value").unwrap_or_default();
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+
+        Ok(Self {
+            action,
+            method: form.value().attr("method").unwrap_or("post").to_string(),
+            hidden,
+        })
+    }
+}
+
+/// Value at a dot path (`data.items.0.token`) as a string
+fn json_path(value: &serde_json::Value, path: &str) -> Option<String> {
+    let pointer = format!("/{}", path.replace('.', "/"));
+    match value.pointer(&pointer)? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+/// A session shared by concurrent requests that can re-login when it expires
+///
+/// When several requests hit 401 at once, only the first re-runs the recipe;
+/// the others wait and reuse the fresh session.
+#[derive(Debug)]
+pub struct SessionAuth {
+    name: String,
+    store: SecretStore,
+    state: Mutex<(u64, Session)>,
+}
+
+impl SessionAuth {
+    /// Load the named session from `store`
+    pub fn load(store: SecretStore, name: &str) -> Result<Self> {
+        let session = Session::load(&store, name)?;
+        Ok(Self {
+            name: name.to_string(),
+            store,
+            state: Mutex::new((0, session)),
+        })
+    }
+
+    /// Current session and its generation (pass the generation to [`Self::refresh`])
+    pub async fn current(&self) -> (u64, Session) {
+        self.state.lock().await.clone()
+    }
+
+    /// Re-run the login unless another caller already refreshed past `generation`
+    pub async fn refresh(&self, generation: u64) -> Result<()> {
+        let mut state = self.state.lock().await;
+        if state.0 != generation {
+            return Ok(());
+        }
+        let (mut recipe, path) = LoginRecipe::load(&state.1.recipe.to_string_lossy())?;
+        recipe.name.clone_from(&self.name);
+        let session = recipe.run(&path).await?;
+        session.save(&self.store)?;
+        *state = (generation + 1, session);
+        Ok(())
+    }
+}
+
+/// Credentials behind `--auth`: a saved login session or `oauth2:<client>`
+#[derive(Debug)]
+pub enum AuthProvider {
+    Session(SessionAuth),
+    OAuth2(OAuth2Auth),
+}
+
+impl AuthProvider {
+    /// Resolve an `--auth` value
+    pub fn load(spec: &str, store: SecretStore) -> Result<Self> {
+        match spec.strip_prefix("oauth2:") {
+            Some(client) => Ok(Self::OAuth2(OAuth2Auth::from_config(client, store)?)),
+            None => Ok(Self::Session(SessionAuth::load(store, spec)?)),
+        }
+    }
+
+    /// Session or client name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Session(auth) => &auth.name,
+            Self::OAuth2(auth) => auth.name(),
+        }
+    }
+
+    /// Headers for a request to `url`, with the credential generation for [`Self::refresh`]
+    pub async fn headers(&self, url: &str) -> Result<(u64, HeaderMap)> {
+        match self {
+            Self::Session(auth) => {
+                let (generation, session) = auth.current().await;
+                Ok((generation, session.header_map(url)?))
+            }
+            Self::OAuth2(auth) if !auth.applies_to(url) => {
+                Ok((auth.generation().await, HeaderMap::new()))
+            }
+            Self::OAuth2(auth) => {
+                let (generation, token) = auth.token().await?;
+                let mut map = HeaderMap::new();
+                map.insert(
+                    reqwest::header::AUTHORIZATION,
+                    HeaderValue::from_str(&token.header_value())?,
+                );
+                Ok((generation, map))
+            }
+        }
+    }
+
+    /// Renew credentials the server rejected (re-login or new token)
+    pub async fn refresh(&self, generation: u64) -> Result<()> {
+        match self {
+            Self::Session(auth) => auth.refresh(generation).await,
+            Self::OAuth2(auth) => auth.refresh(generation).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recipe_parsing() {
+        let recipe: LoginRecipe = serde_json::from_str(
+            r#"{
+                "login_url": "https://example.com/login",
+                "password_env": "EXAMPLE_PASSWORD",
+                "flow": {"type": "api", "url": "https://example.com/api/token",
+                         "body": {"user": "{username}"}, "token_path": "data.token"}
+            }"#,
+        )
+        .unwrap();
+        match recipe.flow {
+            LoginFlow::Api {
+                method,
+                token_header,
+                token_prefix,
+                ..
+            } => {
+                assert_eq!(method, "POST");
+                assert_eq!(token_header, "Authorization");
+                assert_eq!(token_prefix, "Bearer ");
+            }
+            LoginFlow::Form { .. } => panic!("expected api flow"),
+        }
+
+        let credentials = Credentials {
+            username: Some("ada".into()),
+            password: Some("s3cret".into()),
+            totp: Some("123456".into()),
+        };
+        assert_eq!(
+            credentials.fill("{username}:{password}:{totp}"),
+            "ada:s3cret:123456"
+        );
+        assert_eq!(
+            credentials.fill_json(&serde_json::json!({"a": ["{username}", 1]})),
+            serde_json::json!({"a": ["ada", 1]})
+        );
+        assert_eq!(
+            json_path(&serde_json::json!({"data": {"token": "t0k"}}), "data.token").as_deref(),
+            Some("t0k")
+        );
+    }
+
+    #[test]
+    fn test_login_form_parsing() {
+        let html = r#"
+            <form action="/search"><input name="q"></form>
+            <form action="/session" method="POST">
+              <input type="hidden" name="csrf" value="abc123">
+              <input name="email"><input type="password" name="password">
+            </form>"#;
+        let page = url::Url::parse("https://example.com/login").unwrap();
+        let form = LoginForm::parse(html, &page, None).unwrap();
+        assert_eq!(form.action.as_str(), "https://example.com/session");
+        assert_eq!(form.method, "POST");
+        assert_eq!(form.hidden.get("csrf").map(String::as_str), Some("abc123"));
+
+        let search = LoginForm::parse(html, &page, Some("form[action='/search']")).unwrap();
+        assert_eq!(search.action.as_str(), "https://example.com/search");
+        assert!(LoginForm::parse("<p>no form</p>", &page, None).is_err());
+    }
+
+    #[test]
+    fn test_session_scope() {
+        let session = Session {
+            name: "example".into(),
+            recipe: PathBuf::from("example.json"),
+            domain: "example.com".into(),
+            cookies: "sid=1".into(),
+            headers: vec![("Authorization".into(), "Bearer x".into())],
+            created_at: String::new(),
+        };
+        assert!(session.applies_to("https://example.com/a"));
+        assert!(session.applies_to("https://api.example.com/a"));
+        assert!(!session.applies_to("https://notexample.com/"));
+        assert!(!session.applies_to("https://example.org/"));
+        let headers = session.header_map("https://api.example.com/").unwrap();
+        assert_eq!(headers["cookie"], "sid=1");
+        assert_eq!(headers["authorization"], "Bearer x");
+        assert!(session
+            .header_map("https://example.org/")
+            .unwrap()
+            .is_empty());
+
+        let dir = std::env::temp_dir().join(format!("nab-login-{}", std::process::id()));
+        let store = SecretStore::File(dir.clone());
+        session.save(&store).unwrap();
+        assert_eq!(Session::load(&store, "example").unwrap(), session);
+        assert!(Session::load(&store, "missing").is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(dir.with_extension("key"));
+    }
+}
@@ -2,32 +2,75 @@
 //!
 //! Designed for LLM consumption: minimal tokens, maximum information.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand, ValueEnum};
+#[cfg(feature = "spa")]
 use scraper::{Html, Selector};
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
+use nab::page::{extract_links, html_to_markdown, markdown_lines};
+#[cfg(feature = "spa")]
+use nab::{inject_fetch_sync, ApiDiscovery, FetchClient, JsEngine, NetworkPolicy, SandboxLimits};
 use nab::{
-    inject_fetch_sync, AcceleratedClient, ApiDiscovery, CookieSource, FetchClient, JsEngine,
-    OnePasswordAuth, OtpRetriever,
+    AcceleratedClient, ConsentMode, CookieSource, CrawlerIdentity, GateReport, OnePasswordAuth,
+    OtpRetriever,
 };
 
 #[derive(Parser)]
 #[command(name = "nab")]
 #[command(about = "Token-optimized HTTP client with SPA extraction")]
 #[command(version)]
+// Later flags win, so `nab curl` options can override the curl command's
+#[command(args_override_self = true)]
 struct Cli {
     /// Enable verbose debug logging
     #[arg(short, long, global = true)]
     verbose: bool,
 
+    /// Seed browser fingerprint generation so runs are reproducible (default: random)
+    #[arg(long, global = true, value_name = "U64")]
+    seed: Option<u64>,
+
+    /// Filter JSON output through a jq program, e.g. '.products[] | {name, price}'
+    #[arg(long, global = true, value_name = "FILTER")]
+    jq: Option<String>,
+
+    /// Keep config, sessions, secrets, and caches in this workspace [env: NAB_WORKSPACE]
+    #[arg(long, global = true, value_name = "NAME")]
+    workspace: Option<String>,
+
+    /// End the whole command after this long, e.g. 90s, 10m, 1h30m; crawl and batch save their progress
+    #[arg(long, global = true, value_name = "DURATION")]
+    deadline: Option<String>,
+
+    /// Cap nab's cache directory, e.g. 2GB; least recently used files are evicted first
+    #[arg(long, global = true, value_name = "SIZE", value_parser = nab::quota::parse_size)]
+    cache_max_size: Option<u64>,
+
+    /// Print transfer statistics when the command ends: bytes, requests by status, cache savings, time
+    #[arg(long, global = true)]
+    stats: bool,
+
+    /// Resolve hostnames from this /etc/hosts-style file for the whole run (IP HOST...)
+    #[arg(long, global = true, value_name = "FILE")]
+    hosts_file: Option<PathBuf>,
+
+    /// Speak this HTTP version for the whole run: 1, 2, 3, or auto (default: per config domain, else auto)
+    #[arg(long, global = true, value_name = "VERSION")]
+    http: Option<nab::HttpVersion>,
+
+    /// Don't read or update the per-host protocol memory (HTTP/1.x-only hosts, Alt-Svc)
+    #[arg(long, global = true)]
+    no_protocol_cache: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -41,6 +84,8 @@ enum OutputFormat {
     Compact,
     /// JSON output
     Json,
+    /// EPUB e-book of the extracted article (saved to --output or <title>.epub)
+    Epub,
 }
 
 #[derive(Clone, Copy, Default, ValueEnum)]
@@ -52,6 +97,8 @@ enum AnalyzeOutputFormat {
     Markdown,
     /// SRT subtitle format
     Srt,
+    /// FFmetadata chapters (with --chapters)
+    Ffmetadata,
 }
 
 #[derive(Clone, Copy, Debug, Default, ValueEnum)]
@@ -65,6 +112,411 @@ enum OverlayStyleArg {
     Debug,
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ContainerArg {
+    /// MP4, with +faststart for files (fragmented on a pipe)
+    Mp4,
+    /// Matroska, takes any codec
+    Mkv,
+    /// MPEG-TS
+    Ts,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LoudnessArg {
+    /// EBU R128 broadcast (-23 LUFS)
+    Ebu,
+    /// Podcast platforms (-16 LUFS)
+    Podcast,
+    /// Video/music streaming services (-14 LUFS)
+    Streaming,
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum ConsentArg {
+    #[default]
+    /// Leave cookie consent dialogs alone
+    Ignore,
+    /// Accept all cookie categories
+    Accept,
+    /// Reject all but strictly necessary cookies
+    Reject,
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum SecFetchArg {
+    #[default]
+    /// api for requests with a body, a non-GET method, or a JSON Accept; navigate otherwise
+    Auto,
+    /// Page load the user started
+    Navigate,
+    /// A page script's fetch() or XHR
+    Api,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PresetArg {
+    /// name, price, currency, availability, sku, images
+    Product,
+    /// title, authors, dates, canonical URL, Markdown body, word count, language
+    Article,
+    /// title, company, locations, remote, employment type, salary, dates, description
+    Job,
+    /// real estate: title, price, sale or rent, address, rooms, floor area, images
+    Listing,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ProfileArg {
+    Chrome,
+    Firefox,
+    Safari,
+    /// Weighted by market share
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum CrawlerArg {
+    /// Google search crawler
+    Googlebot,
+    /// Bing search crawler
+    Bingbot,
+}
+
+impl From<ProfileArg> for nab::ProfileChoice {
+    fn from(arg: ProfileArg) -> Self {
+        match arg {
+            ProfileArg::Chrome => nab::ProfileChoice::Chrome,
+            ProfileArg::Firefox => nab::ProfileChoice::Firefox,
+            ProfileArg::Safari => nab::ProfileChoice::Safari,
+            ProfileArg::Random => nab::ProfileChoice::Random,
+        }
+    }
+}
+
+impl From<CrawlerArg> for CrawlerIdentity {
+    fn from(arg: CrawlerArg) -> Self {
+        match arg {
+            CrawlerArg::Googlebot => CrawlerIdentity::Googlebot,
+            CrawlerArg::Bingbot => CrawlerIdentity::Bingbot,
+        }
+    }
+}
+
+impl From<PresetArg> for nab::Preset {
+    fn from(arg: PresetArg) -> Self {
+        match arg {
+            PresetArg::Product => nab::Preset::Product,
+            PresetArg::Article => nab::Preset::Article,
+            PresetArg::Job => nab::Preset::Job,
+            PresetArg::Listing => nab::Preset::Listing,
+        }
+    }
+}
+
+impl From<ConsentArg> for ConsentMode {
+    fn from(arg: ConsentArg) -> Self {
+        match arg {
+            ConsentArg::Ignore => ConsentMode::Ignore,
+            ConsentArg::Accept => ConsentMode::Accept,
+            ConsentArg::Reject => ConsentMode::Reject,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum FingerprintAction {
+    /// Send a profile's requests to echo endpoints and report what arrived differently
+    Verify {
+        /// Profile to verify
+        #[arg(short, long, default_value = "random")]
+        profile: ProfileArg,
+
+        /// Echo endpoint (repeatable; default: fingerprint.verify_endpoints from the config)
+        #[arg(long, value_name = "URL", action = clap::ArgAction::Append)]
+        endpoint: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretsAction {
+    /// Store a secret (read from stdin when VALUE is omitted, keeping it out of shell history)
+    Set {
+        /// Secret name, e.g. corp-proxy (use it as secret:corp-proxy)
+        name: String,
+
+        /// Secret value
+        value: Option<String>,
+    },
+    /// Print a secret
+    Get {
+        /// Secret name
+        name: String,
+    },
+    /// Delete a secret
+    Rm {
+        /// Secret name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProxyAction {
+    /// Test proxies for connectivity, latency, egress IP, anonymity, and anti-bot challenges
+    Check {
+        /// Proxy list: one URL (or secret:NAME) per line, # comments
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Write the scored JSON report here instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Proxies checked at once
+        #[arg(long, default_value = "8")]
+        concurrency: usize,
+
+        /// Per-request timeout in seconds
+        #[arg(long, default_value = "15")]
+        timeout: u64,
+
+        /// Anti-bot endpoint to test (repeatable, replaces the defaults)
+        #[arg(long = "endpoint", value_name = "URL")]
+        endpoints: Vec<String>,
+
+        /// Service answering with the caller's IP
+        #[arg(long, value_name = "URL", default_value = nab::proxy_check::DEFAULT_IP_URL)]
+        ip_url: String,
+
+        /// Service echoing request headers as JSON (for the anonymity level)
+        #[arg(long, value_name = "URL", default_value = nab::proxy_check::DEFAULT_HEADERS_URL)]
+        headers_url: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum WorkspaceAction {
+    /// List workspaces
+    List,
+    /// Print a workspace's directory (default: the current one)
+    Path {
+        /// Workspace name
+        name: Option<String>,
+    },
+    /// Zip a workspace's files, e.g. to hand them over at the end of an engagement
+    Archive {
+        /// Workspace name
+        name: String,
+
+        /// Archive file [default: NAME.zip]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Delete a workspace and everything in it
+    Rm {
+        /// Workspace name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Size of the cache, by directory
+    Stats,
+    /// Delete everything in the cache
+    Clear,
+    /// Rewrite revisit pages with zstd, optionally training per-host dictionaries
+    Compress {
+        /// Only this --revisit persona's pages [default: every persona]
+        #[arg(long)]
+        persona: Option<String>,
+
+        /// Train a dictionary for each host with enough pages first
+        #[arg(long)]
+        train: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum StoreAction {
+    /// Remove blobs no run manifest refers to
+    Gc {
+        /// Store directory
+        dir: PathBuf,
+
+        /// First delete all but this many newest run manifests
+        #[arg(long, value_name = "N")]
+        keep_runs: Option<usize>,
+
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the newest stored body of a URL
+    Cat {
+        /// Store directory
+        dir: PathBuf,
+
+        url: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SelfAction {
+    /// Install the newest release for this platform after verifying its checksum
+    Update {
+        /// Release channel: stable or beta [default: self_update.channel from the config]
+        #[arg(long)]
+        channel: Option<nab::self_update::Channel>,
+
+        /// Only report whether an update is available
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum ReportFormat {
+    #[default]
+    /// Markdown table per URL
+    Markdown,
+    /// JSON array of reports
+    Json,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum ExportFormat {
+    #[default]
+    /// Columnar file for DuckDB, Spark, or pandas
+    Parquet,
+    /// One JSON object per row
+    Jsonl,
+}
+
+#[derive(Subcommand)]
+enum AuditAction {
+    /// Grade security headers (HSTS, CSP, framing, cookie flags...) against a policy
+    Headers {
+        /// URLs to audit
+        #[arg(required = true)]
+        urls: Vec<String>,
+
+        /// Policy JSON file [default: the audit section of the config, else built-in]
+        #[arg(long, value_name = "FILE")]
+        policy: Option<PathBuf>,
+
+        /// Report format
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: ReportFormat,
+
+        /// Fail unless every URL grades at least this (A-F)
+        #[arg(long, value_name = "GRADE")]
+        min_grade: Option<nab::audit::Grade>,
+
+        /// Browser profile to fetch as
+        #[arg(long, value_enum)]
+        profile: Option<ProfileArg>,
+
+        /// Cookie source: none (default, a first visit), auto, or a browser name
+        #[arg(long, default_value = "none")]
+        cookies: String,
+
+        /// Retries per URL after a connection error, 429, 502, 503, or 504
+        #[arg(long)]
+        retries: Option<u32>,
+
+        /// Request timeout per URL, in seconds
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check pages for missing alt text, heading order, landmarks, labels, and contrast
+    A11y {
+        /// URLs to audit
+        #[arg(required = true)]
+        urls: Vec<String>,
+
+        /// Fail if any page has more errors than this (0 for none)
+        #[arg(long, value_name = "N")]
+        max_errors: Option<usize>,
+
+        /// Browser profile to fetch as
+        #[arg(long, value_enum)]
+        profile: Option<ProfileArg>,
+
+        /// Cookie source: none (default, a first visit), auto, or a browser name
+        #[arg(long, default_value = "none")]
+        cookies: String,
+
+        /// Retries per URL after a connection error, 429, 502, 503, or 504
+        #[arg(long)]
+        retries: Option<u32>,
+
+        /// Request timeout per URL, in seconds
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+
+        /// Write the JSON report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Report a server's certificate chain, expiry, protocol, cipher, and OCSP stapling
+    Tls {
+        /// Hosts as HOST, HOST:PORT, or a URL (port 443 unless given)
+        #[arg(required = true)]
+        hosts: Vec<String>,
+
+        /// Fail if a certificate doesn't validate or expires within this many days
+        #[arg(long, value_name = "DAYS")]
+        min_days: Option<i64>,
+
+        /// Also trust the CAs in this PEM file (added to the system roots)
+        #[arg(long, value_name = "FILE")]
+        cacert: Option<PathBuf>,
+
+        /// Connect through this proxy (http, socks5, or socks5h)
+        #[arg(long, value_name = "URL")]
+        proxy: Option<String>,
+
+        /// Hop through these proxies in order, e.g. socks5h://bastion:1080,http://egress:3128
+        #[arg(long, value_name = "URL,URL...", conflicts_with = "proxy")]
+        proxy_chain: Option<String>,
+
+        /// Connect through a `proxy_pool` proxy for this country (ISO code, e.g. de)
+        #[arg(long, value_name = "COUNTRY")]
+        geo: Option<String>,
+
+        /// Connect and handshake timeout per host, in seconds
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+
+        /// Write the JSON report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Group crawled pages into canonical clusters and check hreflang reciprocity
+    Canonical {
+        /// Crawl manifest, or the crawl's JSON lines (- for stdin)
+        input: String,
+
+        /// Fail if there are more issues than this (0 for none)
+        #[arg(long, value_name = "N")]
+        max_issues: Option<usize>,
+
+        /// Write the JSON report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+// Parsed once per run; boxing Fetch's many flags wouldn't buy anything
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Fetch a URL (token-optimized output available)
@@ -80,7 +532,7 @@ enum Commands {
         #[arg(short, long)]
         body: bool,
 
-        /// Output format: full, compact, json
+        /// Output format: full, compact, json, epub
         #[arg(short, long, default_value = "full")]
         format: OutputFormat,
 
@@ -88,9 +540,13 @@ enum Commands {
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Use cookies from browser (auto, brave, chrome, firefox, safari, edge). Use 'none' to disable.
-        #[arg(short, long, default_value = "auto")]
-        cookies: String,
+        /// Use cookies from browser (auto, brave, chrome, firefox, safari, edge). Use 'none' to disable. [default: auto]
+        #[arg(short, long)]
+        cookies: Option<String>,
+
+        /// Browser fingerprint to present [default: random]
+        #[arg(long)]
+        profile: Option<ProfileArg>,
 
         /// Use 1Password credentials for this URL
         #[arg(long = "1password", visible_alias = "op")]
@@ -100,13 +556,37 @@ enum Commands {
         #[arg(long)]
         raw_html: bool,
 
-        /// Extract links only
+        /// Extract links only; with --format json, a link report: anchor text, rel, and
+        /// nofollow/sponsored/ugc flags
         #[arg(short, long)]
         links: bool,
 
-        /// Maximum body chars to display (0=unlimited)
-        #[arg(long, default_value = "0")]
-        max_body: usize,
+        /// Show PDFs as one Markdown section per page (## Page N) instead of running text
+        #[arg(long)]
+        pdf_pages: bool,
+
+        /// For images: add format, size, EXIF/XMP metadata, and a perceptual hash (dHash)
+        #[arg(long)]
+        image_meta: bool,
+
+        /// Maximum body chars to display (0=unlimited) [default: 0]
+        #[arg(long)]
+        max_body: Option<usize>,
+
+        /// Retry connection errors, timeouts, 429, 502, 503, and 504 this many times [default: 0]
+        #[arg(long, value_name = "N")]
+        retries: Option<u32>,
+
+        /// Give up on a request after this many seconds [default: 30]
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+
+        /// Print Markdown as the page downloads (skips the gate, CAPTCHA, and language checks)
+        #[arg(
+            long,
+            conflicts_with_all = ["raw_html", "links", "output", "har", "warm_resources", "gated_retry", "summarize", "translate"]
+        )]
+        stream: bool,
 
         /// Add custom request headers (can be repeated: --add-header "Accept: application/json")
         #[arg(long = "add-header", action = clap::ArgAction::Append)]
@@ -116,10 +596,41 @@ enum Commands {
         #[arg(long)]
         auto_referer: bool,
 
+        /// Navigate from: auto (search engine, or the previous page on the site), none, or a URL
+        #[arg(long, value_name = "auto|none|URL", conflicts_with = "auto_referer")]
+        referer: Option<String>,
+
+        /// Sec-Fetch-* headers as for a page load or an API call (override values with --add-header)
+        #[arg(long, default_value = "auto")]
+        sec_fetch: SecFetchArg,
+
         /// Warmup URL to fetch first (establishes session state for APIs)
         #[arg(long)]
         warmup_url: Option<String>,
 
+        /// Also load the favicon and first stylesheet like a browser, or the given kinds (favicon,css,js,img,preload)
+        #[arg(
+            long,
+            value_name = "KINDS",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = nab::subresource::DEFAULT_KINDS
+        )]
+        warm_resources: Option<String>,
+
+        /// Subresources of each kind to load with --warm-resources
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 1,
+            requires = "warm_resources"
+        )]
+        warm_count: usize,
+
+        /// Save an HTTP Archive (HAR 1.2) of the page and its loaded subresources
+        #[arg(long, value_name = "FILE")]
+        har: Option<PathBuf>,
+
         /// HTTP method (GET, POST, PUT, DELETE, PATCH)
         #[arg(short = 'X', long, default_value = "GET")]
         method: String,
@@ -135,9 +646,122 @@ enum Commands {
         /// Don't follow redirects (capture 302 response directly)
         #[arg(long)]
         no_redirect: bool,
+
+        /// Print the request as a curl command instead of sending it
+        #[arg(long, conflicts_with_all = ["paginate", "stream"])]
+        emit_curl: bool,
+
+        /// Cookie consent banners: accept, reject, or ignore (strips consent walls unless ignore)
+        #[arg(long, default_value = "ignore")]
+        consent: ConsentArg,
+
+        /// Re-fetch paywalled pages as a search crawler (only for sites that serve crawlers full text)
+        #[arg(long, value_name = "CRAWLER")]
+        gated_retry: Option<CrawlerArg>,
+
+        /// Run simple JS challenge interstitials in the sandbox and retry with the cookies they set
+        #[arg(long)]
+        solve_js_challenge: bool,
+
+        /// Hand CAPTCHA walls to an external solver: command:<program> or webhook:<url>
+        #[arg(long, value_name = "SOLVER")]
+        captcha_solver: Option<String>,
+
+        /// Summarize the page with the command or endpoint from the config file
+        #[arg(long)]
+        summarize: bool,
+
+        /// Translate the page Markdown into LANG (e.g. en) with the config file's backend
+        #[arg(long, value_name = "LANG", conflicts_with_all = ["raw_html", "links"])]
+        translate: Option<String>,
+
+        /// Transform the page with a Rhai script's on_response/on_markdown hooks
+        #[arg(long, value_name = "FILE", conflicts_with = "stream")]
+        script: Option<PathBuf>,
+
+        /// Follow rel=next and ?page=N links, printing every page's Markdown as one document
+        #[arg(long, conflicts_with_all = ["stream", "raw_html", "links", "har"])]
+        paginate: bool,
+
+        /// Stop --paginate after this many pages
+        #[arg(long, value_name = "N", default_value_t = nab::paginate::DEFAULT_MAX_PAGES, requires = "paginate")]
+        max_pages: usize,
+
+        /// Conditional fetch: send If-None-Match with this ETag (304 means unchanged)
+        #[arg(long)]
+        etag: Option<String>,
+
+        /// Conditional fetch: send If-Modified-Since (HTTP date or RFC 3339)
+        #[arg(long, value_name = "DATE")]
+        if_modified_since: Option<String>,
+
+        /// Authenticate with a session saved by `nab login`, or oauth2:CLIENT from the config
+        #[arg(long, value_name = "NAME")]
+        auth: Option<String>,
+
+        /// Answer Basic/Digest (and NTLM with the ntlm feature) challenges as USER:PASSWORD
+        #[arg(
+            short = 'u',
+            long,
+            value_name = "USER:PASSWORD",
+            conflicts_with = "auth"
+        )]
+        user: Option<nab::UserCredentials>,
+
+        /// Client certificate for mutual TLS: PEM (cert and key) or a PKCS#12 .p12/.pfx bundle
+        #[arg(long, value_name = "FILE")]
+        cert: Option<PathBuf>,
+
+        /// Private key for --cert, if it's in a separate PEM file
+        #[arg(long, value_name = "FILE", requires = "cert")]
+        key: Option<PathBuf>,
+
+        /// Password of a PKCS#12 bundle or an encrypted --key
+        #[arg(long, value_name = "PASSWORD", requires = "cert")]
+        cert_password: Option<String>,
+
+        /// Also trust the CA certificates in this PEM file (for private CAs)
+        #[arg(long, value_name = "FILE")]
+        cacert: Option<PathBuf>,
+
+        /// Don't verify TLS certificates (pins are still checked). Never use on untrusted networks
+        #[arg(long)]
+        insecure: bool,
+
+        /// Only connect if the certificate chain has this public key (sha256//BASE64, repeatable)
+        #[arg(long, value_name = "sha256//HASH", action = clap::ArgAction::Append)]
+        pin: Vec<String>,
+
+        /// Send requests through this proxy (http, https, socks5, or socks5h for proxy-side DNS)
+        #[arg(long, value_name = "URL")]
+        proxy: Option<String>,
+
+        /// Hop through these proxies in order, e.g. socks5h://bastion:1080,http://egress:3128
+        #[arg(long, value_name = "URL,URL...", conflicts_with = "proxy")]
+        proxy_chain: Option<String>,
+
+        /// Fetch as seen from this country (ISO code, e.g. de): a `proxy_pool` proxy
+        /// tagged with it (unless --proxy is given) and a matching Accept-Language
+        #[arg(long, value_name = "COUNTRY")]
+        geo: Option<String>,
+    },
+
+    /// Run a curl command (e.g. a browser's "Copy as cURL") with nab's client
+    Curl {
+        /// The whole curl command, quoted
+        command: String,
+
+        /// More `nab fetch` options, e.g. --format json or --emit-curl
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "FETCH OPTIONS"
+        )]
+        fetch_args: Vec<String>,
     },
 
     /// Extract data from JavaScript-heavy SPA pages
+    #[cfg(feature = "spa")]
     Spa {
         /// URL to extract data from
         url: String,
@@ -178,101 +802,659 @@ enum Commands {
         #[arg(long)]
         minify: bool,
 
-        /// Limit arrays to first N items
+        /// Limit arrays to N items (the first and last, around a __truncated count)
         #[arg(long)]
         max_array: Option<usize>,
 
-        /// Limit nesting depth
+        /// Limit nesting depth (deeper objects and arrays become a __truncated count)
         #[arg(long)]
         max_depth: Option<usize>,
 
-        /// Force HTTP/1.1 (for servers with HTTP/2 issues)
+        /// Drop volatile keys so runs diff cleanly: names, globs (*At), paths (props.**.etag), timestamps, tracking
+        #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+        ignore_keys: Vec<String>,
+
+        /// Keep these keys whole despite the limits: names, globs, or paths (props.pageProps.product)
+        #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+        keep_paths: Vec<String>,
+
+        /// Tighten the limits until the (minified) JSON fits in this many bytes
+        #[arg(long, value_name = "BYTES")]
+        max_bytes: Option<usize>,
+
+        /// Force HTTP/1.1 (for servers with HTTP/2 issues); same as --http 1
         #[arg(long)]
         http1: bool,
-    },
 
-    /// Benchmark fetching multiple URLs
-    Bench {
-        /// URLs to benchmark (comma-separated)
-        urls: String,
+        /// Disable WebAssembly support in the page JS environment
+        #[arg(long)]
+        no_wasm: bool,
 
-        /// Number of iterations per URL
-        #[arg(short, long, default_value = "5")]
-        iterations: usize,
-    },
+        /// Time limit in milliseconds for each WebAssembly call
+        #[arg(long, default_value = "1000")]
+        wasm_timeout: u64,
 
-    /// Test browser fingerprint spoofing
-    Fingerprint {
-        /// Number of profiles to generate
-        #[arg(short, long, default_value = "3")]
-        count: usize,
-    },
+        /// Total page JS execution budget in milliseconds (0 = unlimited)
+        #[arg(long, default_value = "30000")]
+        js_timeout: u64,
 
-    /// Test 1Password integration
-    Auth {
-        /// URL to find credentials for
-        url: String,
-    },
+        /// Page JS heap limit in MB
+        #[arg(long, default_value = "32")]
+        js_memory: usize,
 
-    /// Run all validation tests against real websites
-    Validate,
+        /// Network policy for page fetch() calls: allow, deny, same-origin, allowlist
+        #[arg(long, default_value = "allow")]
+        js_network: String,
 
-    /// Get OTP code from all available sources
-    Otp {
-        /// Domain or URL to get OTP for
-        domain: String,
-    },
+        /// Host allowed by the allowlist network policy (can be repeated)
+        #[arg(long = "js-allow", action = clap::ArgAction::Append)]
+        js_allow: Vec<String>,
 
-    /// Stream media from various providers
-    Stream {
-        /// Provider or URL (yle, youtube, or direct URL)
-        source: String,
+        /// Cookie consent banners: accept, reject, or ignore (pre-answers the page's consent dialog)
+        #[arg(long, default_value = "ignore")]
+        consent: ConsentArg,
 
-        /// Program/video ID or URL
-        id: String,
+        /// Client certificate for mutual TLS: PEM (cert and key) or a PKCS#12 .p12/.pfx bundle
+        #[arg(long, value_name = "FILE")]
+        cert: Option<PathBuf>,
 
-        /// Output destination (- for stdout, path for file)
-        #[arg(short, long, default_value = "-")]
-        output: String,
+        /// Private key for --cert, if it's in a separate PEM file
+        #[arg(long, value_name = "FILE", requires = "cert")]
+        key: Option<PathBuf>,
 
-        /// Quality: best, worst, or height (720, 1080)
-        #[arg(short, long, default_value = "best")]
-        quality: String,
+        /// Password of a PKCS#12 bundle or an encrypted --key
+        #[arg(long, value_name = "PASSWORD", requires = "cert")]
+        cert_password: Option<String>,
 
-        /// Force native backend
-        #[arg(long)]
-        native: bool,
+        /// Also trust the CA certificates in this PEM file (for private CAs)
+        #[arg(long, value_name = "FILE")]
+        cacert: Option<PathBuf>,
 
-        /// Force ffmpeg backend
+        /// Don't verify TLS certificates (pins are still checked). Never use on untrusted networks
         #[arg(long)]
-        ffmpeg: bool,
+        insecure: bool,
 
-        /// Show stream info only (no download)
-        #[arg(long)]
-        info: bool,
+        /// Only connect if the certificate chain has this public key (sha256//BASE64, repeatable)
+        #[arg(long, value_name = "sha256//HASH", action = clap::ArgAction::Append)]
+        pin: Vec<String>,
 
-        /// List episodes (for series URLs)
-        #[arg(long)]
-        list: bool,
+        /// Send requests through this proxy (http, https, socks5, or socks5h for proxy-side DNS)
+        #[arg(long, value_name = "URL")]
+        proxy: Option<String>,
+
+        /// Hop through these proxies in order, e.g. socks5h://bastion:1080,http://egress:3128
+        #[arg(long, value_name = "URL,URL...", conflicts_with = "proxy")]
+        proxy_chain: Option<String>,
+
+        /// Render as seen from this country (ISO code, e.g. de): a `proxy_pool` proxy
+        /// tagged with it, and a matching Accept-Language, navigator.language, and time zone
+        #[arg(long, value_name = "COUNTRY")]
+        geo: Option<String>,
+
+        /// Save every response the extraction needs to this cassette file
+        #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+        record: Option<PathBuf>,
+
+        /// Answer requests from a --record cassette instead of the network
+        #[arg(long, value_name = "FILE")]
+        replay: Option<PathBuf>,
+    },
+
+    /// Extract normalized structured data (JSON) with a preset
+    Extract {
+        /// URL to extract from
+        url: String,
+
+        /// What to extract
+        #[arg(short, long)]
+        preset: PresetArg,
 
         /// Use cookies from browser (auto, brave, chrome, firefox, safari, edge). Use 'none' to disable.
         #[arg(short, long, default_value = "auto")]
         cookies: String,
 
-        /// Duration limit for live streams (e.g., "1h", "30m")
+        /// Follow rel=next and ?page=N links and print the items of every page as one array
         #[arg(long)]
-        duration: Option<String>,
+        paginate: bool,
 
-        /// ffmpeg output options (e.g., "-c:v libx265")
-        #[arg(long = "ffmpeg-opts")]
-        ffmpeg_opts: Option<String>,
+        /// Stop --paginate after this many pages
+        #[arg(long, value_name = "N", default_value_t = nab::paginate::DEFAULT_MAX_PAGES, requires = "paginate")]
+        max_pages: usize,
 
-        /// Pipe output to media player (vlc, mpv, etc.)
-        #[arg(long)]
-        player: Option<String>,
+        /// Save every response the extraction needs to this cassette file
+        #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+        record: Option<PathBuf>,
+
+        /// Answer requests from a --record cassette instead of the network
+        #[arg(long, value_name = "FILE")]
+        replay: Option<PathBuf>,
+    },
+
+    /// Fetch a URL under several geos, personas, or profiles at once and report the differences
+    Compare {
+        /// URL to compare
+        url: String,
+
+        /// Variants as KIND=VALUE,VALUE... with KIND geo, persona, or profile (repeatable),
+        /// e.g. geo=us,de,fr
+        #[arg(long, value_name = "KIND=VALUES", required = true, action = clap::ArgAction::Append)]
+        variants: Vec<String>,
+
+        /// Cookie source: none (default, so only the variants differ), auto, or a browser name
+        #[arg(long, default_value = "none")]
+        cookies: String,
+
+        /// Retries per variant after a connection error, 429, 502, 503, or 504
+        #[arg(long)]
+        retries: Option<u32>,
+
+        /// Request timeout per variant, in seconds
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+
+        /// Write the JSON report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Compile multiple URLs into one Markdown or EPUB document
+    Compile {
+        /// File with one URL per line ('-' for stdin, '#' starts a comment)
+        input: String,
+
+        /// Output file (.epub for EPUB, anything else for Markdown)
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Document title (defaults to the output file name)
+        #[arg(short, long)]
+        title: Option<String>,
+
+        /// Number of URLs fetched in parallel
+        #[arg(long, default_value = "4")]
+        concurrency: usize,
+    },
+
+    /// Fetch many URLs concurrently, interleaving hosts (one JSON line per URL)
+    Batch {
+        /// File with one URL per line ('-' for stdin, '#' starts a comment)
+        input: String,
+
+        /// Directory to save each page as Markdown
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+
+        /// Maximum simultaneous requests to any one host
+        #[arg(long, default_value = "2")]
+        per_host_concurrency: usize,
+
+        /// Maximum simultaneous requests overall
+        #[arg(long, default_value = "16")]
+        global_concurrency: usize,
+
+        /// Threads converting pages to Markdown for --output-dir (0 = one per CPU core)
+        #[arg(long, default_value = "0", requires = "output_dir")]
+        parse_threads: usize,
+
+        /// Record completed URLs in this manifest and skip the ones an earlier run completed
+        #[arg(long, value_name = "FILE")]
+        resume_job: Option<PathBuf>,
+
+        /// Stop with an error once the output directory holds this much, e.g. 10GB
+        #[arg(long, value_name = "SIZE", value_parser = nab::quota::parse_size, requires = "output_dir")]
+        output_max_size: Option<u64>,
+
+        /// Pace each host like a person browsing: short bursts, then reading pauses around SECS
+        #[arg(long, value_name = "SECS", num_args = 0..=1, default_missing_value = "4")]
+        human_timing: Option<f64>,
+
+        /// Navigate from: auto (search engine, then the previous page on the same site), none, or a URL
+        #[arg(long, value_name = "auto|none|URL")]
+        referer: Option<String>,
+
+        /// Session saved by `nab login` or oauth2:CLIENT; renewed when a request gets 401
+        #[arg(long, value_name = "NAME")]
+        auth: Option<String>,
+
+        /// Answer Basic/Digest (and NTLM with the ntlm feature) challenges as USER:PASSWORD
+        #[arg(
+            short = 'u',
+            long,
+            value_name = "USER:PASSWORD",
+            conflicts_with = "auth"
+        )]
+        user: Option<nab::UserCredentials>,
+
+        /// Client certificate for mutual TLS: PEM (cert and key) or a PKCS#12 .p12/.pfx bundle
+        #[arg(long, value_name = "FILE")]
+        cert: Option<PathBuf>,
+
+        /// Private key for --cert, if it's in a separate PEM file
+        #[arg(long, value_name = "FILE", requires = "cert")]
+        key: Option<PathBuf>,
+
+        /// Password of a PKCS#12 bundle or an encrypted --key
+        #[arg(long, value_name = "PASSWORD", requires = "cert")]
+        cert_password: Option<String>,
+
+        /// Also trust the CA certificates in this PEM file (for private CAs)
+        #[arg(long, value_name = "FILE")]
+        cacert: Option<PathBuf>,
+
+        /// Don't verify TLS certificates (pins are still checked). Never use on untrusted networks
+        #[arg(long)]
+        insecure: bool,
+
+        /// Only connect if the certificate chain has this public key (sha256//BASE64, repeatable)
+        #[arg(long, value_name = "sha256//HASH", action = clap::ArgAction::Append)]
+        pin: Vec<String>,
+
+        /// Send requests through this proxy (http, https, socks5, or socks5h for proxy-side DNS)
+        #[arg(long, value_name = "URL")]
+        proxy: Option<String>,
+
+        /// Hop through these proxies in order, e.g. socks5h://bastion:1080,http://egress:3128
+        #[arg(long, value_name = "URL,URL...", conflicts_with = "proxy")]
+        proxy_chain: Option<String>,
+
+        /// Add each page's links to its line: anchor text, rel, and nofollow/sponsored/ugc flags
+        #[arg(long)]
+        links: bool,
+
+        /// Also send each page (its line and Markdown) to nats://HOST/SUBJECT or
+        /// kafka://BROKERS/TOPIC, keyed by canonical URL
+        #[arg(long, value_name = "URL")]
+        publish: Option<nab::publish::PublishTarget>,
+
+        /// Estimate bytes and runtime from HEAD samples instead of fetching the pages
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Hand out a URL list to `nab worker`s over HTTP and collect their pages (JSON lines)
+    Coordinator {
+        /// File with one URL per line ('-' for stdin, '#' starts a comment)
+        input: String,
+
+        /// Address to listen on; other than loopback only with a token
+        #[arg(long, default_value = "127.0.0.1:7420")]
+        listen: String,
+
+        /// Maximum simultaneous requests to any one host, across all workers
+        #[arg(long, default_value = "2")]
+        per_host_concurrency: usize,
+
+        /// Lease a URL again if its worker hasn't reported it in this many seconds
+        #[arg(long, default_value = "300")]
+        lease_secs: u64,
+
+        /// Require workers to send this bearer token (default: $NAB_COORDINATOR_TOKEN)
+        #[arg(long)]
+        token: Option<String>,
+
+        /// Directory to save each page as Markdown
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+
+        /// Record completed URLs in this manifest and skip the ones an earlier run completed
+        #[arg(long, value_name = "FILE")]
+        resume_job: Option<PathBuf>,
+    },
+
+    /// Fetch URLs leased from a `nab coordinator` until its job is done
+    Worker {
+        /// Coordinator URL, e.g. http://10.0.0.5:7420
+        coordinator: String,
+
+        /// Name reported to the coordinator (default: HOSTNAME-PID)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Pages fetched at a time
+        #[arg(long, default_value = "8")]
+        concurrency: usize,
+
+        /// Bearer token the coordinator requires (default: $NAB_COORDINATOR_TOKEN)
+        #[arg(long)]
+        token: Option<String>,
+
+        /// Fetch as this persona: its browser identity and TLS sessions
+        #[arg(long, value_name = "NAME")]
+        persona: Option<String>,
+
+        /// Send requests through this proxy (http, https, socks5, or socks5h for proxy-side DNS)
+        #[arg(long, value_name = "URL")]
+        proxy: Option<String>,
+
+        /// Hop through these proxies in order, e.g. socks5h://bastion:1080,http://egress:3128
+        #[arg(long, value_name = "URL,URL...", conflicts_with = "proxy")]
+        proxy_chain: Option<String>,
+    },
+
+    /// Crawl from seed URLs, staying on their hosts (one JSON line per page)
+    Crawl {
+        /// Seed URLs
+        #[arg(required = true)]
+        seeds: Vec<String>,
+
+        /// Maximum link depth from the seeds
+        #[arg(long, default_value = "2")]
+        max_depth: u32,
+
+        /// Minimum delay between requests to the same host (ms)
+        #[arg(long, default_value = "1000")]
+        delay_ms: u64,
+
+        /// Pace each host like a person browsing instead of --delay-ms: bursts, then pauses around SECS
+        #[arg(long, value_name = "SECS", num_args = 0..=1, default_missing_value = "4")]
+        human_timing: Option<f64>,
+
+        /// Maximum simultaneous requests to any one host
+        #[arg(long, default_value = "2")]
+        per_host_concurrency: usize,
+
+        /// Maximum simultaneous requests overall
+        #[arg(long, default_value = "16")]
+        global_concurrency: usize,
+
+        /// Directory to save each page as Markdown
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+
+        /// Frontier state file; saved periodically and on Ctrl-C, resumed if it exists
+        #[arg(long)]
+        state: Option<PathBuf>,
+
+        /// Share the frontier through Redis with every crawler of the same --crawl-name,
+        /// e.g. redis://HOST:6379/0
+        #[arg(long, value_name = "URL", conflicts_with_all = ["state", "dry_run"])]
+        frontier_redis: Option<String>,
+
+        /// Name of the shared crawl (default: the first seed's host)
+        #[arg(long, value_name = "NAME", requires = "frontier_redis")]
+        crawl_name: Option<String>,
+
+        /// Only follow matching URLs instead of the seed hosts (regex:PATTERN or glob:PATTERN)
+        #[arg(long, value_name = "FILTER", action = clap::ArgAction::Append)]
+        include: Vec<nab::crawl::UrlFilter>,
+
+        /// Never follow matching URLs (regex:PATTERN or glob:PATTERN)
+        #[arg(long, value_name = "FILTER", action = clap::ArgAction::Append)]
+        exclude: Vec<nab::crawl::UrlFilter>,
+
+        /// Stop after this many pages
+        #[arg(long)]
+        max_pages: Option<usize>,
+
+        /// Crawl manifest path (default: OUTPUT_DIR/manifest.json when -o is given)
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+
+        /// Come back as this persona: same identity, cached validators, 304s for unchanged pages
+        #[arg(long, value_name = "PERSONA")]
+        revisit: Option<String>,
+
+        /// Keep page bodies in this content-addressed store, each distinct body once
+        #[arg(long, value_name = "DIR")]
+        store: Option<PathBuf>,
+
+        /// Stop with an error once the output directory holds this much, e.g. 10GB
+        #[arg(long, value_name = "SIZE", value_parser = nab::quota::parse_size, requires = "output_dir")]
+        output_max_size: Option<u64>,
+
+        /// Save the images of crawled pages to OUTPUT_DIR/images and list them in the manifest
+        #[arg(long, requires = "output_dir")]
+        download_images: bool,
+
+        /// With --download-images: add EXIF/XMP metadata and a perceptual hash (dHash) per image
+        #[arg(long, requires = "download_images")]
+        image_meta: bool,
+
+        /// Don't save pages marked noindex (robots meta tag or X-Robots-Tag) to the output directory
+        #[arg(long, requires = "output_dir")]
+        honor_noindex: bool,
+
+        /// Write the link graph (pages by status and depth, links with anchor text) to FILE:
+        /// .dot, .graphml, or .json
+        #[arg(long, value_name = "FILE")]
+        graph: Option<PathBuf>,
+
+        /// Also send each page (its line and Markdown) to nats://HOST/SUBJECT or
+        /// kafka://BROKERS/TOPIC, keyed by canonical URL
+        #[arg(long, value_name = "URL")]
+        publish: Option<nab::publish::PublishTarget>,
+
+        /// Estimate pages, bytes, and runtime from --state, sitemaps, and the --revisit
+        /// cache instead of crawling
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Crawl a site and report its broken links, redirects, and timeouts by page
+    Linkcheck {
+        /// Seed URLs
+        #[arg(required = true)]
+        seeds: Vec<String>,
+
+        /// Link depth of the pages crawled from the seeds (their links are checked too)
+        #[arg(long, default_value = "3")]
+        depth: u32,
+
+        /// Minimum delay between requests to the same host (ms)
+        #[arg(long, default_value = "500")]
+        delay_ms: u64,
+
+        /// Maximum simultaneous requests to any one host
+        #[arg(long, default_value = "2")]
+        per_host_concurrency: usize,
+
+        /// Maximum simultaneous requests overall
+        #[arg(long, default_value = "16")]
+        global_concurrency: usize,
+
+        /// Only crawl matching URLs instead of the seed hosts (regex:PATTERN or glob:PATTERN)
+        #[arg(long, value_name = "FILTER", action = clap::ArgAction::Append)]
+        include: Vec<nab::crawl::UrlFilter>,
+
+        /// Never crawl matching URLs; links to them are still checked
+        #[arg(long, value_name = "FILTER", action = clap::ArgAction::Append)]
+        exclude: Vec<nab::crawl::UrlFilter>,
+
+        /// Time allowed per request, in seconds
+        #[arg(long, value_name = "SECS", default_value = "10")]
+        timeout: u64,
+
+        /// Leave links that only redirect out of the report
+        #[arg(long)]
+        ignore_redirects: bool,
+
+        /// Write the JSON report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Benchmark fetching multiple URLs
+    Bench {
+        /// URLs to benchmark (comma-separated)
+        urls: String,
+
+        /// Number of iterations per URL
+        #[arg(short, long, default_value = "5")]
+        iterations: usize,
+    },
+
+    /// Test browser fingerprint spoofing
+    Fingerprint {
+        /// Number of profiles to generate
+        #[arg(short, long, default_value = "3")]
+        count: usize,
+
+        #[command(subcommand)]
+        action: Option<FingerprintAction>,
+    },
+
+    /// Test 1Password integration
+    Auth {
+        /// URL to find credentials for
+        url: String,
+    },
+
+    /// Log in with a site recipe and save the session for --auth
+    Login {
+        /// Recipe file, or a name under ~/.config/nab/recipes/
+        recipe: String,
+
+        /// Session name (default: the recipe's name)
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Manage secrets in the OS keychain or the encrypted file store
+    Secrets {
+        #[command(subcommand)]
+        action: SecretsAction,
+    },
+
+    /// Check proxies before using them
+    Proxy {
+        #[command(subcommand)]
+        action: ProxyAction,
+    },
+
+    /// Manage workspaces (separate config, sessions, and caches per client)
+    Workspace {
+        #[command(subcommand)]
+        action: WorkspaceAction,
+    },
+
+    /// Audit a site's security headers, TLS setup, and accessibility
+    Audit {
+        #[command(subcommand)]
+        action: AuditAction,
+    },
+
+    /// Show or empty nab's cache (revisit pages, TLS session hints)
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
+    /// Read from or clean up a `crawl --store` body store
+    Store {
+        #[command(subcommand)]
+        action: StoreAction,
+    },
+
+    /// Convert a crawl into rows (url, timestamp, status, title, text, metadata) for analytics
+    Export {
+        /// Crawl manifest, or the crawl's JSON lines (- for stdin)
+        input: String,
+
+        /// Output file (- for stdout, JSON lines only)
+        output: String,
+
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+
+        /// Take page text from this `crawl --store` when the crawl saved no Markdown
+        #[arg(long, value_name = "DIR")]
+        store: Option<PathBuf>,
+    },
+
+    /// Manage the nab binary itself
+    #[command(name = "self")]
+    SelfManage {
+        #[command(subcommand)]
+        action: SelfAction,
+    },
+
+    /// Run all validation tests against real websites
+    Validate,
+
+    /// Get OTP code from all available sources
+    Otp {
+        /// Domain or URL to get OTP for
+        domain: String,
+    },
+
+    /// Stream media from various providers
+    #[cfg(feature = "stream")]
+    Stream {
+        /// Provider or URL (yle, youtube, or direct URL)
+        source: String,
+
+        /// Program/video ID or URL
+        id: String,
+
+        /// Output destination (- for stdout, path for file)
+        #[arg(short, long, default_value = "-")]
+        output: String,
+
+        /// Quality: best, worst, height (720, 1080), or auto (highest the measured bandwidth sustains; native HLS)
+        #[arg(short, long, visible_alias = "video", default_value = "best")]
+        quality: String,
+
+        /// Mux these audio languages as separate tagged tracks (e.g. en,de), via ffmpeg
+        #[arg(
+            long,
+            value_name = "LANGS",
+            value_delimiter = ',',
+            conflicts_with = "native"
+        )]
+        audio_lang: Vec<String>,
+
+        /// Output container (default: from the output file name); mkv when a codec doesn't fit
+        #[arg(long, value_name = "FORMAT")]
+        container: Option<ContainerArg>,
+
+        /// Force native backend
+        #[arg(long)]
+        native: bool,
+
+        /// Force ffmpeg backend
+        #[arg(long)]
+        ffmpeg: bool,
+
+        /// Show stream info only (no download)
+        #[arg(long)]
+        info: bool,
+
+        /// List episodes (for series URLs)
+        #[arg(long)]
+        list: bool,
+
+        /// Use cookies from browser (auto, brave, chrome, firefox, safari, edge). Use 'none' to disable.
+        #[arg(short, long, default_value = "auto")]
+        cookies: String,
+
+        /// Duration limit for live streams (e.g., "1h", "30m")
+        #[arg(long)]
+        duration: Option<String>,
+
+        /// ffmpeg output options (e.g., "-c:v libx265")
+        #[arg(long = "ffmpeg-opts")]
+        ffmpeg_opts: Option<String>,
+
+        /// Pipe output to media player (vlc, mpv, etc.)
+        #[arg(long)]
+        player: Option<String>,
+
+        /// Extra player arguments (e.g. "--volume=50 --fs")
+        #[arg(
+            long,
+            value_name = "ARGS",
+            requires = "player",
+            allow_hyphen_values = true
+        )]
+        player_args: Option<String>,
+
+        /// Preallocate and memory-map the output file, writing VOD segments in place as they finish
+        #[arg(long, conflicts_with_all = ["ffmpeg", "player", "ffmpeg_opts", "audio_lang"])]
+        mmap: bool,
     },
 
     /// Analyze video with multimodal pipeline (transcription + vision)
+    #[cfg(feature = "analyze")]
     Analyze {
         /// Video file or URL to analyze
         video: String,
@@ -300,9 +1482,31 @@ enum Commands {
         /// Claude API key for vision analysis (or `ANTHROPIC_API_KEY` env)
         #[arg(long)]
         api_key: Option<String>,
+
+        /// Check a `nab stream` capture (MPEG-TS) for lost packets, missing frames, A/V desync, and bitrate anomalies instead (JSON)
+        #[arg(long, conflicts_with_all = ["audio_only", "diarize", "dgx", "api_key"])]
+        integrity: bool,
+
+        /// Detect chapters (container markers, else silences and scene cuts) instead, as JSON or `--format ffmetadata` for `nab annotate --chapters`
+        #[arg(long, conflicts_with_all = ["audio_only", "diarize", "dgx", "api_key", "integrity"])]
+        chapters: bool,
+
+        /// Write thumbnail sprite sheets and a WebVTT storyboard (seek-bar previews) to DIR instead
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["audio_only", "diarize", "dgx", "api_key", "integrity", "chapters"])]
+        storyboard: Option<PathBuf>,
+
+        /// Seconds between storyboard thumbnails
+        #[arg(
+            long,
+            value_name = "SECS",
+            default_value = "10",
+            requires = "storyboard"
+        )]
+        storyboard_interval: f64,
     },
 
     /// Add overlays to video (subtitles, speaker labels, analysis)
+    #[cfg(feature = "analyze")]
     Annotate {
         /// Input video file
         video: String,
@@ -329,15 +1533,78 @@ enum Commands {
         /// Use hardware acceleration (`VideoToolbox` on macOS)
         #[arg(long)]
         hwaccel: bool,
+
+        /// Embed chapters from `nab analyze --chapters` (JSON or FFmetadata)
+        #[arg(long, value_name = "FILE")]
+        chapters: Option<PathBuf>,
+
+        /// Normalize audio loudness (re-encodes the audio)
+        #[arg(long, value_name = "TARGET")]
+        normalize_loudness: Option<LoudnessArg>,
+
+        /// Also write the audio alone, e.g. a podcast episode (codec from the extension: m4a, mp3, opus, flac, wav)
+        #[arg(long, value_name = "FILE")]
+        extract_audio: Option<PathBuf>,
     },
+
+    /// Serve canned responses from a fixtures directory (tests, offline demos)
+    #[cfg(feature = "mock-server")]
+    #[command(hide = true)]
+    MockServer {
+        /// Fixture files, plus an optional mock.json of routes
+        #[arg(long, value_name = "DIR")]
+        fixtures: PathBuf,
+
+        /// Address to listen on; port 0 picks a free one
+        #[arg(long, default_value = "127.0.0.1:0")]
+        listen: String,
+
+        /// Refuse HTTP/2, like a legacy server
+        #[arg(long)]
+        http1_only: bool,
+    },
+
+    /// List nab-<name> plugins found on PATH (run them as `nab <name>`)
+    Plugins,
+
+    /// Any other command runs the nab-<name> plugin from PATH
+    #[command(external_subcommand)]
+    External(Vec<String>),
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+
+    // `nab curl` is `nab fetch` with the options the curl command translates to
+    if let Commands::Curl {
+        command,
+        fetch_args,
+    } = &cli.command
+    {
+        let args = std::iter::once("nab".to_string())
+            .chain(nab::curl::CurlCommand::parse(command)?.fetch_args())
+            .chain(fetch_args.iter().cloned());
+        let fetch = Cli::try_parse_from(args).unwrap_or_else(|e| e.exit());
+        cli.verbose |= fetch.verbose;
+        cli.seed = cli.seed.or(fetch.seed);
+        cli.jq = cli.jq.take().or(fetch.jq);
+        cli.workspace = cli.workspace.take().or(fetch.workspace);
+        cli.deadline = cli.deadline.take().or(fetch.deadline);
+        cli.cache_max_size = cli.cache_max_size.or(fetch.cache_max_size);
+        cli.stats |= fetch.stats;
+        cli.hosts_file = cli.hosts_file.take().or(fetch.hosts_file);
+        cli.http = cli.http.or(fetch.http);
+        cli.no_protocol_cache |= fetch.no_protocol_cache;
+        cli.command = fetch.command;
+    }
 
     // Initialize logging based on --verbose flag
-    let log_level = if cli.verbose { Level::DEBUG } else { Level::INFO };
+    let log_level = if cli.verbose {
+        Level::DEBUG
+    } else {
+        Level::INFO
+    };
 
     FmtSubscriber::builder()
         .with_max_level(log_level)
@@ -345,6 +1612,64 @@ async fn main() -> Result<()> {
         .compact()
         .init();
 
+    if let Some(seed) = cli.seed {
+        nab::fingerprint::seed(seed);
+    }
+    if let Some(code) = &cli.jq {
+        let _ = JQ.set(nab::jq::JqFilter::parse(code)?);
+    }
+    let workspace = cli
+        .workspace
+        .clone()
+        .or_else(|| std::env::var(nab::workspace::WORKSPACE_ENV).ok())
+        .filter(|name| !name.is_empty());
+    if let Some(name) = &workspace {
+        nab::workspace::select(name)?;
+    }
+    if let Some(max) = cli.cache_max_size {
+        nab::quota::set_cache_limit(max);
+        trim_cache();
+    }
+    if let Some(path) = &cli.hosts_file {
+        nab::hosts::install(path)?;
+    }
+    if let Some(version) = cli.http {
+        nab::http_client::force_http_version(version);
+    }
+    if cli.no_protocol_cache {
+        nab::protocol_cache::disable();
+    }
+    nab::policy::install(nab::policy::load()?);
+    nab::traffic::start();
+    // Commands printing JSON lines end them with the statistics
+    let json_lines = matches!(cli.command, Commands::Batch { .. } | Commands::Crawl { .. });
+    // Commands with work in flight stop cleanly on Ctrl-C/SIGTERM and save their progress
+    #[cfg(feature = "stream")]
+    let streaming = matches!(cli.command, Commands::Stream { .. });
+    #[cfg(not(feature = "stream"))]
+    let streaming = false;
+    if streaming
+        || matches!(
+            cli.command,
+            Commands::Crawl { .. }
+                | Commands::Batch { .. }
+                | Commands::Coordinator { .. }
+                | Commands::Worker { .. }
+                | Commands::Linkcheck { .. }
+        )
+    {
+        nab::shutdown::install();
+    }
+    if let Some(spec) = cli.deadline.clone() {
+        let after = nab::deadline::parse(&spec)?;
+        nab::deadline::set(after);
+        // Whatever can't stop cleanly at the deadline is stopped after a grace period
+        tokio::spawn(async move {
+            tokio::time::sleep(after + nab::deadline::GRACE).await;
+            eprintln!("⏰ Deadline {spec} passed, stopping");
+            std::process::exit(nab::deadline::EXIT_CODE);
+        });
+    }
 
     match cli.command {
         Commands::Fetch {
@@ -354,39 +1679,163 @@ async fn main() -> Result<()> {
             format,
             output,
             cookies,
+            profile,
             use_1password,
             raw_html,
             links,
+            pdf_pages,
+            image_meta,
             max_body,
+            retries,
+            timeout,
+            stream,
             add_headers,
             auto_referer,
+            referer,
+            sec_fetch,
             warmup_url,
+            warm_resources,
+            warm_count,
+            har,
             method,
             data,
             capture_cookies,
             no_redirect,
+            emit_curl,
+            consent,
+            gated_retry,
+            solve_js_challenge,
+            captcha_solver,
+            summarize,
+            translate,
+            script,
+            paginate,
+            max_pages,
+            etag,
+            if_modified_since,
+            auth,
+            user,
+            cert,
+            key,
+            cert_password,
+            cacert,
+            insecure,
+            pin,
+            proxy,
+            proxy_chain,
+            geo,
         } => {
+            // Connection flags of --emit-curl; the request itself is added when it's built
+            let curl = emit_curl.then(|| nab::curl::CurlCommand {
+                user: user.as_ref().map(|u| match &u.domain {
+                    Some(domain) => format!("{domain}\\{}:{}", u.username, u.password),
+                    None => format!("{}:{}", u.username, u.password),
+                }),
+                insecure,
+                proxy: proxy.clone(),
+                cert: cert.as_ref().map(|p| p.display().to_string()),
+                key: key.as_ref().map(|p| p.display().to_string()),
+                cacert: cacert.as_ref().map(|p| p.display().to_string()),
+                ..nab::curl::CurlCommand::default()
+            });
+            let mut options = request_options(
+                cert,
+                key,
+                cert_password,
+                cacert,
+                insecure,
+                pin,
+                proxy,
+                proxy_chain,
+            );
+            if let Some(cookies) = cookies {
+                options = options.cookies(cookies);
+            }
+            if let Some(profile) = profile {
+                options = options.profile(profile.into());
+            }
+            if let Some(chars) = max_body {
+                options = options.max_body(chars);
+            }
+            if let Some(retries) = retries {
+                options = options.retries(retries);
+            }
+            if let Some(secs) = timeout {
+                options = options.timeout(std::time::Duration::from_secs(secs));
+            }
+            for header in add_headers {
+                options = options.header(header);
+            }
+            if no_redirect {
+                options = options.max_redirects(0);
+            }
+            if let Some(country) = geo {
+                options = options.geo(country);
+            }
+            let config = nab::config::NabConfig::load()?;
+            let options = announce(options.domain_config(&url, &config).build()?);
+            if paginate {
+                let rule = config.pagination_for(&url::Url::parse(&url)?);
+                let pagination = nab::paginate::Pagination::new(rule, max_pages);
+                return cmd_fetch_pages(&url, &options, pagination, format, output)
+                    .await
+                    .map_err(|e| options.client.explain(&url, e));
+            }
+            let warm = warm_resources
+                .map(|kinds| nab::WarmPlan::parse(&kinds, warm_count))
+                .transpose()?;
+            let captcha_solver = captcha_solver
+                .as_deref()
+                .map(nab::captcha::solver_from_spec)
+                .transpose()?;
+            let navigator = referer.as_deref().map(navigator).transpose()?;
             cmd_fetch(
                 &url,
                 headers,
                 body,
                 format,
                 output,
-                &cookies,
                 use_1password,
                 raw_html,
                 links,
-                max_body,
-                &add_headers,
+                pdf_pages,
+                image_meta,
+                stream,
                 auto_referer,
+                navigator.as_ref(),
+                sec_fetch,
                 warmup_url.as_deref(),
+                warm.as_ref(),
+                har.as_deref(),
                 &method,
                 data.as_deref(),
                 capture_cookies,
-                no_redirect,
+                consent.into(),
+                gated_retry.map(Into::into),
+                solve_js_challenge,
+                captcha_solver.as_deref(),
+                summarize,
+                translate.as_deref(),
+                script.as_deref(),
+                nab::Validators {
+                    etag,
+                    last_modified: if_modified_since,
+                },
+                auth.as_deref(),
+                user.as_ref(),
+                curl.map(|curl| nab::curl::CurlCommand {
+                    follow_redirects: options.max_redirects > 0,
+                    max_time: options.client.timeout,
+                    retries: Some(options.retries),
+                    http: options.client.http_version(),
+                    ..curl
+                }),
+                &options,
             )
-            .await?;
+            .await
+            .map_err(|e| options.client.explain(&url, e))?;
         }
+        #[cfg(feature = "spa")]
         Commands::Spa {
             url,
             cookies,
@@ -400,8 +1849,62 @@ async fn main() -> Result<()> {
             minify,
             max_array,
             max_depth,
+            ignore_keys,
+            keep_paths,
+            max_bytes,
             http1,
+            no_wasm,
+            wasm_timeout,
+            js_timeout,
+            js_memory,
+            js_network,
+            js_allow,
+            consent,
+            cert,
+            key,
+            cert_password,
+            cacert,
+            insecure,
+            pin,
+            proxy,
+            proxy_chain,
+            geo,
+            record,
+            replay,
         } => {
+            let mut options = request_options(
+                cert,
+                key,
+                cert_password,
+                cacert,
+                insecure,
+                pin,
+                proxy,
+                proxy_chain,
+            );
+            if let Some(country) = geo {
+                options = options.geo(country);
+            }
+            if http1 {
+                options = options.http(nab::HttpVersion::Http1);
+            }
+            let options = announce(
+                options
+                    .domain_config(&url, &nab::config::NabConfig::load()?)
+                    .build()?,
+            );
+            let cassette = cassette(record, replay.as_deref())?;
+            // A replay must not depend on the browser's cookies
+            let cookies = if cassette.as_ref().is_some_and(|c| c.is_replay()) {
+                "none".to_string()
+            } else {
+                cookies
+            };
+            let limits = SandboxLimits {
+                timeout: (js_timeout > 0).then(|| std::time::Duration::from_millis(js_timeout)),
+                memory_limit: js_memory * 1024 * 1024,
+                network: NetworkPolicy::parse(&js_network, &js_allow)?,
+            };
             cmd_spa(
                 &url,
                 &cookies,
@@ -413,32 +1916,448 @@ async fn main() -> Result<()> {
                 extract.as_deref(),
                 summary,
                 minify,
-                max_array,
-                max_depth,
-                http1,
+                &nab::prune::Pruner {
+                    ignore: nab::prune::KeyFilter::new(&ignore_keys),
+                    keep: nab::prune::KeyFilter::new(&keep_paths),
+                    max_array,
+                    max_depth,
+                    max_bytes,
+                },
+                no_wasm,
+                wasm_timeout,
+                limits,
+                consent.into(),
+                &options.client,
+                options.geo.as_ref(),
+                cassette,
             )
-            .await?;
+            .await
+            .map_err(|e| options.client.explain(&url, e))?;
         }
-        Commands::Bench { urls, iterations } => {
-            cmd_bench(&urls, iterations).await?;
+        Commands::Extract {
+            url,
+            preset,
+            cookies,
+            paginate,
+            max_pages,
+            record,
+            replay,
+        } => {
+            let cassette = cassette(record, replay.as_deref())?;
+            let pagination = if paginate {
+                let rule = nab::config::NabConfig::load()?.pagination_for(&url::Url::parse(&url)?);
+                Some(nab::paginate::Pagination::new(rule, max_pages))
+            } else {
+                None
+            };
+            cmd_extract(&url, preset.into(), &cookies, cassette, pagination).await?;
         }
-        Commands::Fingerprint { count } => {
-            cmd_fingerprint(count);
+        Commands::Compare {
+            url,
+            variants,
+            cookies,
+            retries,
+            timeout,
+            output,
+        } => {
+            cmd_compare(
+                &url,
+                &variants,
+                &cookies,
+                retries,
+                timeout,
+                output.as_deref(),
+            )
+            .await?;
+        }
+        Commands::Compile {
+            input,
+            output,
+            title,
+            concurrency,
+        } => {
+            cmd_compile(&input, &output, title.as_deref(), concurrency).await?;
+        }
+        Commands::Batch {
+            input,
+            output_dir,
+            per_host_concurrency,
+            global_concurrency,
+            parse_threads,
+            resume_job,
+            output_max_size,
+            human_timing,
+            referer,
+            auth,
+            user,
+            cert,
+            key,
+            cert_password,
+            cacert,
+            insecure,
+            pin,
+            proxy,
+            proxy_chain,
+            links,
+            publish,
+            dry_run,
+        } => {
+            // Many hosts share these clients, so the config's domains don't apply
+            let options = announce(
+                request_options(
+                    cert,
+                    key,
+                    cert_password,
+                    cacert,
+                    insecure,
+                    pin,
+                    proxy,
+                    proxy_chain,
+                )
+                .build()?,
+            )
+            .client;
+            let limits =
+                nab::batch::ConcurrencyLimits::new(per_host_concurrency, global_concurrency);
+            let navigator = referer.as_deref().map(navigator).transpose()?;
+            let pacer = human_pacer(human_timing, cli.seed)?;
+            if dry_run {
+                cmd_batch_estimate(
+                    &input,
+                    limits,
+                    resume_job.as_deref(),
+                    pacer.as_ref(),
+                    &options,
+                )
+                .await?;
+            } else {
+                start_publishing(publish).await?;
+                let fetched = cmd_batch(
+                    &input,
+                    output_dir.as_deref(),
+                    limits,
+                    parse_threads,
+                    resume_job.as_deref(),
+                    output_max_size,
+                    pacer.as_ref(),
+                    navigator.as_ref(),
+                    auth.as_deref(),
+                    user.as_ref(),
+                    &options,
+                    links,
+                )
+                .await;
+                finish_publishing().await?;
+                fetched?;
+            }
+        }
+        Commands::Coordinator {
+            input,
+            listen,
+            per_host_concurrency,
+            lease_secs,
+            token,
+            output_dir,
+            resume_job,
+        } => {
+            let token = token.or_else(coordinator_token);
+            let queue_settings = (
+                per_host_concurrency,
+                std::time::Duration::from_secs(lease_secs),
+            );
+            cmd_coordinator(
+                &input,
+                &listen,
+                queue_settings,
+                token,
+                output_dir.as_deref(),
+                resume_job.as_deref(),
+            )
+            .await?;
+        }
+        Commands::Worker {
+            coordinator,
+            name,
+            concurrency,
+            token,
+            persona,
+            proxy,
+            proxy_chain,
+        } => {
+            let mut options = announce(
+                request_options(None, None, None, None, false, Vec::new(), proxy, proxy_chain)
+                    .build()?,
+            )
+            .client;
+            if persona.is_some() {
+                options.tls = persona_client_options(persona.as_deref()).tls;
+            }
+            let token = token.or_else(coordinator_token);
+            let worker = nab::coordinator::WorkerClient::new(&coordinator, token)?;
+            let name = name.unwrap_or_else(|| {
+                let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "worker".into());
+                format!("{host}-{}", std::process::id())
+            });
+            cmd_worker(&worker, &name, concurrency, persona.as_deref(), &options).await?;
+        }
+        Commands::Crawl {
+            seeds,
+            max_depth,
+            delay_ms,
+            human_timing,
+            per_host_concurrency,
+            global_concurrency,
+            output_dir,
+            state,
+            frontier_redis,
+            crawl_name,
+            include,
+            exclude,
+            max_pages,
+            manifest,
+            revisit,
+            store,
+            output_max_size,
+            download_images,
+            image_meta,
+            honor_noindex,
+            graph,
+            publish,
+            dry_run,
+        } => {
+            if let Some(path) = &graph {
+                nab::link_graph::GraphFormat::from_path(path)?;
+            }
+            let scope = nab::crawl::CrawlScope::new(&seeds, include, exclude);
+            let manifest =
+                manifest.or_else(|| output_dir.as_ref().map(|d| d.join("manifest.json")));
+            let pacer = human_pacer(human_timing, cli.seed)?;
+            // Human timing does the per-host spacing instead of the fixed delay
+            let delay_ms = if pacer.is_some() { 0 } else { delay_ms };
+            let limits =
+                nab::batch::ConcurrencyLimits::new(per_host_concurrency, global_concurrency);
+            if dry_run {
+                cmd_crawl_estimate(
+                    &seeds,
+                    max_depth,
+                    max_pages,
+                    std::time::Duration::from_millis(delay_ms),
+                    pacer.as_ref(),
+                    limits,
+                    scope,
+                    state.as_deref(),
+                    revisit.as_deref(),
+                )
+                .await?;
+            } else {
+                start_publishing(publish).await?;
+                let shared = frontier_redis.map(|url| {
+                    let name = crawl_name.unwrap_or_else(|| {
+                        seeds.first().map(|seed| nab::batch::host_key(seed)).unwrap_or_default()
+                    });
+                    (url, name)
+                });
+                let crawled = cmd_crawl(
+                    &seeds,
+                    max_depth,
+                    max_pages,
+                    std::time::Duration::from_millis(delay_ms),
+                    pacer.as_ref(),
+                    limits,
+                    scope,
+                    output_dir.as_deref(),
+                    output_max_size,
+                    state.as_deref(),
+                    shared.as_ref().map(|(url, name)| (url.as_str(), name.as_str())),
+                    manifest.as_deref(),
+                    revisit.as_deref(),
+                    store.as_deref(),
+                    download_images
+                        .then(|| CrawlImages::new(image_meta))
+                        .as_ref(),
+                    honor_noindex,
+                    graph.as_deref(),
+                )
+                .await;
+                finish_publishing().await?;
+                crawled?;
+            }
+        }
+        Commands::Linkcheck {
+            seeds,
+            depth,
+            delay_ms,
+            per_host_concurrency,
+            global_concurrency,
+            include,
+            exclude,
+            timeout,
+            ignore_redirects,
+            output,
+        } => {
+            cmd_linkcheck(
+                &seeds,
+                depth,
+                std::time::Duration::from_millis(delay_ms),
+                nab::batch::ConcurrencyLimits::new(per_host_concurrency, global_concurrency),
+                nab::crawl::CrawlScope::new(&seeds, include, exclude),
+                std::time::Duration::from_secs(timeout),
+                ignore_redirects,
+                output.as_deref(),
+            )
+            .await?;
+        }
+        Commands::Curl { .. } => unreachable!("nab curl runs as nab fetch"),
+        Commands::Bench { urls, iterations } => {
+            cmd_bench(&urls, iterations).await?;
+        }
+        Commands::Fingerprint {
+            action: Some(FingerprintAction::Verify { profile, endpoint }),
+            ..
+        } => {
+            cmd_fingerprint_verify(profile, endpoint).await?;
+        }
+        Commands::Fingerprint {
+            count,
+            action: None,
+        } => {
+            cmd_fingerprint(count, cli.seed);
         }
         Commands::Auth { url } => {
             cmd_auth(&url)?;
         }
+        Commands::Login { recipe, name } => {
+            cmd_login(&recipe, name.as_deref()).await?;
+        }
+        Commands::Secrets { action } => {
+            cmd_secrets(action)?;
+        }
+        Commands::Proxy { action } => {
+            cmd_proxy(action).await?;
+        }
+        Commands::Workspace { action } => {
+            cmd_workspace(action)?;
+        }
+        Commands::Audit {
+            action:
+                AuditAction::Headers {
+                    urls,
+                    policy,
+                    format,
+                    min_grade,
+                    profile,
+                    cookies,
+                    retries,
+                    timeout,
+                    output,
+                },
+        } => {
+            cmd_audit_headers(
+                &urls,
+                policy.as_deref(),
+                format,
+                min_grade,
+                profile,
+                &cookies,
+                retries,
+                timeout,
+                output.as_deref(),
+            )
+            .await?;
+        }
+        Commands::Audit {
+            action:
+                AuditAction::A11y {
+                    urls,
+                    max_errors,
+                    profile,
+                    cookies,
+                    retries,
+                    timeout,
+                    output,
+                },
+        } => {
+            cmd_audit_a11y(
+                &urls,
+                max_errors,
+                profile,
+                &cookies,
+                retries,
+                timeout,
+                output.as_deref(),
+            )
+            .await?;
+        }
+        Commands::Audit {
+            action:
+                AuditAction::Tls {
+                    hosts,
+                    min_days,
+                    cacert,
+                    proxy,
+                    proxy_chain,
+                    geo,
+                    timeout,
+                    output,
+                },
+        } => {
+            let mut options = request_options(
+                None,
+                None,
+                None,
+                cacert,
+                false,
+                Vec::new(),
+                proxy,
+                proxy_chain,
+            );
+            if let Some(geo) = geo {
+                options = options.geo(geo);
+            }
+            if let Some(secs) = timeout {
+                options = options.timeout(std::time::Duration::from_secs(secs));
+            }
+            cmd_audit_tls(&hosts, options, min_days, output.as_deref()).await?;
+        }
+        Commands::Audit {
+            action:
+                AuditAction::Canonical {
+                    input,
+                    max_issues,
+                    output,
+                },
+        } => cmd_audit_canonical(&input, max_issues, output.as_deref())?,
+        Commands::Cache { action } => {
+            cmd_cache(&action)?;
+        }
+        Commands::Store { action } => {
+            cmd_store(&action)?;
+        }
+        Commands::Export {
+            input,
+            output,
+            format,
+            store,
+        } => cmd_export(&input, &output, format, store.as_deref())?,
+        Commands::SelfManage {
+            action: SelfAction::Update { channel, check },
+        } => {
+            cmd_self_update(channel, check).await?;
+        }
         Commands::Validate => {
             cmd_validate().await?;
         }
         Commands::Otp { domain } => {
             cmd_otp(&domain)?;
         }
+        #[cfg(feature = "stream")]
         Commands::Stream {
             source,
             id,
             output,
             quality,
+            audio_lang,
+            container,
             native,
             ffmpeg,
             info,
@@ -447,12 +2366,16 @@ async fn main() -> Result<()> {
             duration,
             ffmpeg_opts,
             player,
+            player_args,
+            mmap,
         } => {
             cmd_stream(
                 &source,
                 &id,
                 &output,
                 &quality,
+                &audio_lang,
+                container,
                 native,
                 ffmpeg,
                 info,
@@ -461,9 +2384,40 @@ async fn main() -> Result<()> {
                 duration.as_deref(),
                 ffmpeg_opts.as_deref(),
                 player.as_deref(),
+                player_args.as_deref(),
+                mmap,
             )
             .await?;
         }
+        #[cfg(feature = "analyze")]
+        Commands::Analyze {
+            video,
+            output,
+            integrity: true,
+            ..
+        } => {
+            cmd_integrity(&video, output.as_deref())?;
+        }
+        #[cfg(feature = "analyze")]
+        Commands::Analyze {
+            video,
+            format,
+            output,
+            chapters: true,
+            ..
+        } => {
+            cmd_chapters(&video, format, output.as_deref()).await?;
+        }
+        #[cfg(feature = "analyze")]
+        Commands::Analyze {
+            video,
+            storyboard: Some(dir),
+            storyboard_interval,
+            ..
+        } => {
+            cmd_storyboard(&video, &dir, storyboard_interval).await?;
+        }
+        #[cfg(feature = "analyze")]
         Commands::Analyze {
             video,
             audio_only,
@@ -472,6 +2426,10 @@ async fn main() -> Result<()> {
             output,
             dgx,
             api_key,
+            integrity: false,
+            chapters: false,
+            storyboard: None,
+            storyboard_interval: _,
         } => {
             cmd_analyze(
                 &video,
@@ -484,6 +2442,7 @@ async fn main() -> Result<()> {
             )
             .await?;
         }
+        #[cfg(feature = "analyze")]
         Commands::Annotate {
             video,
             output,
@@ -492,6 +2451,9 @@ async fn main() -> Result<()> {
             analysis,
             style,
             hwaccel,
+            chapters,
+            normalize_loudness,
+            extract_audio,
         } => {
             cmd_annotate(
                 &video,
@@ -501,14 +2463,158 @@ async fn main() -> Result<()> {
                 analysis,
                 style,
                 hwaccel,
+                chapters.as_deref(),
+                normalize_loudness,
+                extract_audio.as_deref(),
             )
             .await?;
         }
+        #[cfg(feature = "mock-server")]
+        Commands::MockServer {
+            fixtures,
+            listen,
+            http1_only,
+        } => {
+            cmd_mock_server(&fixtures, &listen, http1_only).await?;
+        }
+        Commands::Plugins => {
+            cmd_plugins();
+        }
+        Commands::External(args) => {
+            let globals = nab::plugin::Globals {
+                verbose: cli.verbose,
+                seed: cli.seed,
+            };
+            cmd_plugin(&args, globals).await?;
+        }
+    }
+
+    if cli.stats && !nab::traffic::is_empty() {
+        let summary = nab::traffic::summary();
+        eprintln!("{}", summary.render());
+        if json_lines {
+            print_json_line(&serde_json::json!({ "stats": summary }));
+        }
+    }
+    trim_cache();
+    if nab::shutdown::is_requested() {
+        std::process::exit(nab::shutdown::EXIT_CODE);
+    }
+    if nab::deadline::cut_short() {
+        std::process::exit(nab::deadline::EXIT_CODE);
+    }
+    Ok(())
+}
+
+/// Evict from the cache down to `--cache-max-size`
+fn trim_cache() {
+    match nab::quota::trim_cache() {
+        Ok(evicted) if evicted.files > 0 => tracing::debug!(
+            "Evicted {} cache files ({})",
+            evicted.files,
+            nab::quota::format_size(evicted.bytes)
+        ),
+        Ok(_) => {}
+        Err(e) => eprintln!("⚠️  {e:#}"),
     }
+}
+
+fn cmd_plugins() {
+    let plugins = nab::plugin::discover();
+    if plugins.is_empty() {
+        println!("No plugins found (executables named nab-<name> on PATH)");
+    }
+    for (name, path) in plugins {
+        println!("{name:<16} {}", path.display());
+    }
+}
 
+/// `nab <name> args...` for a name that isn't built in
+async fn cmd_plugin(args: &[String], globals: nab::plugin::Globals) -> Result<()> {
+    let Some((name, args)) = args.split_first() else {
+        anyhow::bail!("No plugin name");
+    };
+    let Some(program) = nab::plugin::find(name) else {
+        anyhow::bail!(
+            "unrecognized subcommand '{name}' (no {}{name} on PATH; see `nab --help` and `nab plugins`)",
+            nab::plugin::PREFIX
+        );
+    };
+    let status = nab::plugin::run(&program, args, globals).await?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
     Ok(())
 }
 
+/// Options builder from the TLS (`--cert`, `--key`, `--cert-password`, `--cacert`,
+/// `--insecure`, `--pin`) and proxy (`--proxy`, `--proxy-chain`) flags
+#[allow(clippy::too_many_arguments)]
+fn request_options(
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+    password: Option<String>,
+    cacert: Option<PathBuf>,
+    insecure: bool,
+    pins: Vec<String>,
+    proxy: Option<String>,
+    proxy_chain: Option<String>,
+) -> nab::RequestOptionsBuilder {
+    let mut options = nab::RequestOptions::builder().insecure(insecure);
+    if let Some(cert) = cert {
+        options = options.cert(cert);
+    }
+    if let Some(key) = key {
+        options = options.key(key);
+    }
+    if let Some(password) = password {
+        options = options.cert_password(password);
+    }
+    if let Some(cacert) = cacert {
+        options = options.cacert(cacert);
+    }
+    for pin in pins {
+        options = options.pin(pin);
+    }
+    if let Some(proxy) = proxy {
+        options = options.proxy(proxy);
+    }
+    if let Some(chain) = proxy_chain {
+        options = options.proxy_chain(chain);
+    }
+    options
+}
+
+/// Warn about --insecure and show the proxy chain and geo of built options
+fn announce(options: nab::RequestOptions) -> nab::RequestOptions {
+    let tls = &options.client.tls;
+    if tls.is_insecure() {
+        eprintln!("⚠️  --insecure: TLS certificates are NOT verified; anyone on the network path can read and alter this traffic");
+        if !tls.is_pinned() {
+            eprintln!("   Pin the server key with --pin sha256//... to keep MITM protection");
+        }
+    }
+    if let Some(nab::ProxyConfig::Chain(chain)) = &options.client.proxy {
+        let hops: Vec<String> = chain.hops().iter().map(ToString::to_string).collect();
+        eprintln!("🔀 Proxy chain: {}", hops.join(" → "));
+    }
+    if let Some(geo) = &options.geo {
+        eprintln!(
+            "🌍 Geo {}: {}, {} via {}",
+            geo.country,
+            geo.locale,
+            geo.timezone,
+            geo.proxy.as_deref().unwrap_or("no proxy")
+        );
+    }
+    options
+}
+
+/// Navigation chain for a `--referer` value
+fn navigator(referer: &str) -> Result<nab::Navigator> {
+    Ok(nab::Navigator::new(referer.parse()?))
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn cmd_fetch(
     url: &str,
@@ -516,25 +2622,82 @@ async fn cmd_fetch(
     show_body: bool,
     format: OutputFormat,
     output_file: Option<PathBuf>,
-    cookies: &str,
     use_1password: bool,
     raw_html: bool,
     links: bool,
-    max_body: usize,
-    custom_headers: &[String],
+    pdf_pages: bool,
+    image_meta: bool,
+    stream: bool,
     auto_referer: bool,
+    navigator: Option<&nab::Navigator>,
+    sec_fetch: SecFetchArg,
     warmup_url: Option<&str>,
+    warm: Option<&nab::WarmPlan>,
+    har_file: Option<&std::path::Path>,
     method: &str,
     data: Option<&str>,
     capture_cookies: bool,
-    no_redirect: bool,
+    consent: ConsentMode,
+    gated_retry: Option<CrawlerIdentity>,
+    solve_js_challenge: bool,
+    captcha_solver: Option<&dyn nab::CaptchaSolver>,
+    summarize: bool,
+    translate: Option<&str>,
+    script: Option<&std::path::Path>,
+    validators: nab::Validators,
+    auth: Option<&str>,
+    user: Option<&nab::UserCredentials>,
+    emit_curl: Option<nab::curl::CurlCommand>,
+    options: &nab::RequestOptions,
 ) -> Result<()> {
-    // Create client - with or without redirect following
-    let client = if no_redirect {
-        AcceleratedClient::new_no_redirect()?
+    let max_body = options.max_body;
+    if stream && !matches!(format, OutputFormat::Full) {
+        anyhow::bail!("--stream works with --format full");
+    }
+    if stream && consent.is_active() {
+        anyhow::bail!("--stream can't strip consent walls, that needs the whole page");
+    }
+    if solve_js_challenge && !cfg!(feature = "spa") {
+        anyhow::bail!("--solve-js-challenge needs nab built with the `spa` feature");
+    }
+    if JQ.get().is_some() && !matches!(format, OutputFormat::Json) {
+        anyhow::bail!("--jq works with --format json");
+    }
+    if script.is_some() && !cfg!(feature = "script") {
+        anyhow::bail!("--script needs nab built with the `script` feature");
+    }
+    #[cfg(feature = "script")]
+    let script = script.map(nab::script::PageScript::load).transpose()?;
+
+    // Fail before fetching if summarization isn't configured
+    let summarize_config = if summarize {
+        let config = nab::config::NabConfig::load()?.summarize;
+        nab::summarize::SummarizeBackend::from_config(&config)?;
+        Some(config)
     } else {
-        AcceleratedClient::new()?
+        None
+    };
+    // Same for translation
+    let translation = match translate {
+        Some(target) => {
+            let config = nab::config::NabConfig::load()?.translate;
+            nab::translate::TranslateBackend::from_config(&config)?;
+            Some((target, config))
+        }
+        None => None,
     };
+    // And for --auth (a saved login, or an OAuth2 token fetched now)
+    let auth = auth
+        .map(|spec| nab::AuthProvider::load(spec, nab::SecretStore::detect()))
+        .transpose()?;
+    let (auth_generation, mut auth_headers) = match &auth {
+        Some(provider) => provider.headers(url).await?,
+        None => (0, reqwest::header::HeaderMap::new()),
+    };
+
+    // Create client - with or without redirect following
+    let redirects = nab::RedirectLog::new();
+    let client = AcceleratedClient::for_request_with_redirect_log(options, &redirects)?;
     let profile = client.profile().await;
 
     // Extract domain from URL
@@ -545,31 +2708,28 @@ async fn cmd_fetch(
 
     // Get cookies (auto-detect by default, unless "none")
     let mut cookie_header = String::new();
-    let browser_name = if cookies.to_lowercase() == "none" {
-        None
-    } else if cookies.to_lowercase() == "auto" {
-        // Auto-detect
-        if let Ok(detected) = nab::detect_default_browser() {
-            Some(detected.as_str().to_string())
-        } else {
-            Some("chrome".to_string()) // fallback
-        }
-    } else {
-        Some(cookies.to_string())
-    };
-
-    if let Some(browser) = &browser_name {
-        let source = match browser.to_lowercase().as_str() {
-            "brave" => CookieSource::Brave,
-            "chrome" => CookieSource::Chrome,
-            "firefox" => CookieSource::Firefox,
-            "safari" => CookieSource::Safari,
-            "edge" => CookieSource::Chrome, // Edge uses Chromium cookie format
-            _ => CookieSource::Chrome,      // Default to Chrome
-        };
+    if let Some((browser, source)) = options.cookies.resolve() {
         cookie_header = source.get_cookie_header(&domain).unwrap_or_default();
         if !cookie_header.is_empty() && matches!(format, OutputFormat::Full) {
-            println!("🍪 Loading {} cookies for {domain}", browser.to_lowercase());
+            println!("🍪 Loading {browser} cookies for {domain}");
+        }
+    }
+
+    // Pre-answer cookie consent dialogs
+    if consent.is_active() {
+        cookie_header = nab::consent::with_consent_cookies(&cookie_header, consent);
+    }
+
+    // Login session cookies (--auth) go last, so renewed ones can replace them
+    let cookies_without_auth = cookie_header.clone();
+    let auth_cookies = auth_headers.remove(reqwest::header::COOKIE);
+    if let Some(cookies) = auth_cookies.as_ref().and_then(|v| v.to_str().ok()) {
+        append_cookies(&mut cookie_header, cookies);
+    }
+    let authenticated = auth_cookies.is_some() || !auth_headers.is_empty();
+    if let Some(provider) = auth.as_ref().filter(|_| authenticated) {
+        if matches!(format, OutputFormat::Full) {
+            println!("🔑 Using --auth {}", provider.name());
         }
     }
 
@@ -586,20 +2746,48 @@ async fn cmd_fetch(
         }
     }
 
+    // Nothing, not even the warmup or the timing probe, contacts a host the
+    // domain policy refuses
+    nab::policy::check(url)?;
+
     // Session warmup (for APIs that require prior page load)
-    if let Some(warmup) = warmup_url {
+    let mut accepted_hints = Vec::new();
+    if let Some(warmup) = warmup_url.filter(|_| emit_curl.is_none()) {
         if matches!(format, OutputFormat::Full) {
             println!("🔥 Warming up session: {warmup}");
         }
         let mut warmup_req = client.inner().get(warmup);
         warmup_req = warmup_req.headers(profile.to_headers());
+        if let (Some(navigator), Ok(parsed)) = (navigator, url::Url::parse(warmup)) {
+            warmup_req = warmup_req.headers(navigator.headers(&parsed));
+        }
         if !cookie_header.is_empty() {
             warmup_req = warmup_req.header("Cookie", &cookie_header);
         }
-        let _ = warmup_req.send().await; // Ignore result, just establish session
+        // Ignore the page, just establish the session and the client hints it asks for
+        if let Ok(response) = nab::policy::send(warmup_req).await {
+            if let Some(navigator) = navigator {
+                navigator.visited(response.url());
+            }
+            accepted_hints =
+                nab::fingerprint::client_hints::requested(response.headers(), "accept-ch");
+        }
     }
 
+    // DNS/connect/TLS phases are timed on a probe connection (JSON timings
+    // only). The probe connects directly, so it's skipped behind a proxy.
+    let connection = match (format, url::Url::parse(url)) {
+        (OutputFormat::Json, Ok(parsed)) if emit_curl.is_none() && !options.client.is_proxied() => {
+            let tls_config = options.client.tls.rustls_config().ok().flatten();
+            nab::timing::probe_connection(&parsed, tls_config)
+                .await
+                .ok()
+        }
+        _ => None,
+    };
+
     let start = Instant::now();
+    let started_at = chrono::Utc::now();
 
     // Build request based on HTTP method
     let mut request = match method.to_uppercase().as_str() {
@@ -613,24 +2801,47 @@ async fn cmd_fetch(
 
     // Add request body for methods that support it
     if let Some(body_data) = data {
+        nab::traffic::sent(url, body_data.len());
         request = request.body(body_data.to_owned());
         // Default to JSON content type if not specified
-        if !custom_headers
-            .iter()
-            .any(|h| h.to_lowercase().starts_with("content-type"))
-        {
+        if !options.headers.contains_key(reqwest::header::CONTENT_TYPE) {
             request = request.header("Content-Type", "application/json");
         }
     }
 
-    // Add fingerprint headers
-    request = request.headers(profile.to_headers());
+    // Add fingerprint headers for a page load or an API call, with the client
+    // hints the warmup page asked for
+    let api = match sec_fetch {
+        SecFetchArg::Navigate => false,
+        SecFetchArg::Api => true,
+        SecFetchArg::Auto => {
+            data.is_some()
+                || !matches!(method.to_uppercase().as_str(), "GET" | "HEAD")
+                || options
+                    .headers
+                    .get_all(reqwest::header::ACCEPT)
+                    .iter()
+                    .any(|v| v.to_str().is_ok_and(|v| v.to_lowercase().contains("json")))
+        }
+    };
+    request = request.headers(if api {
+        profile.request_headers(nab::FetchContext::Api, "same-origin")
+    } else {
+        profile.request_headers(nab::FetchContext::Navigate, "none")
+    });
+    request = request.headers(profile.client_hint_headers(&accepted_hints));
+
+    // Conditional fetch validators (--etag / --if-modified-since)
+    request = request.headers(validators.request_headers());
 
     // Add cookies if present
     if !cookie_header.is_empty() {
         request = request.header("Cookie", &cookie_header);
     }
 
+    // --auth headers, e.g. a bearer token
+    request = request.headers(auth_headers);
+
     // Add auto-referer if requested (domain origin)
     if auto_referer {
         if let Ok(parsed) = url::Url::parse(url) {
@@ -639,15 +2850,125 @@ async fn cmd_fetch(
         }
     }
 
-    // Add custom headers (--add-header "Name: Value")
-    for header_str in custom_headers {
-        let parts: Vec<&str> = header_str.splitn(2, ':').collect();
-        if parts.len() == 2 {
-            request = request.header(parts[0].trim(), parts[1].trim());
+    // Navigation chain (--referer)
+    if let (Some(navigator), Ok(parsed)) = (navigator, url::Url::parse(url)) {
+        request = request.headers(navigator.headers(&parsed));
+    }
+
+    // Add custom headers (--add-header "Name: Value" and the config's domains)
+    request = request.headers(options.headers.clone());
+
+    // --emit-curl: the request as it would go out, with the client's own headers
+    if let Some(curl) = emit_curl {
+        let built = request.build()?;
+        println!("{}", curl.with_request(&built, &profile.client_headers()));
+        return Ok(());
+    }
+
+    // Kept to resend once with renewed credentials or a --user challenge answer
+    let retry = if authenticated || user.is_some() {
+        request.try_clone()
+    } else {
+        None
+    };
+
+    // Resent with the cookies a JS challenge sets (--solve-js-challenge)
+    #[cfg(feature = "spa")]
+    let challenge_retry = if solve_js_challenge {
+        request.try_clone()
+    } else {
+        None
+    };
+
+    // Resent with the cookies a CAPTCHA solver returns (--captcha-solver)
+    let captcha_retry = captcha_solver.and_then(|_| request.try_clone());
+
+    // Resent with the high-entropy client hints a response marks as critical
+    let hints_retry = profile
+        .client_hints
+        .as_ref()
+        .and_then(|_| request.try_clone());
+
+    // Copy of the page request for --har
+    let har_request = har_file
+        .and_then(|_| request.try_clone())
+        .and_then(|r| r.build().ok());
+
+    redirects.start();
+    let mut response = options.send(request).await?;
+
+    if let Some(retry) = hints_retry {
+        if let Some(hints) = profile.critical_hints_retry(response.headers(), &accepted_hints) {
+            if matches!(format, OutputFormat::Full) {
+                println!(
+                    "🧬 Server requires client hints, retrying with {}",
+                    hints.join(", ")
+                );
+            }
+            redirects.start();
+            response = options
+                .send(retry.headers(profile.client_hint_headers(&hints)))
+                .await?;
+        }
+    }
+
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        match (retry, &auth, user) {
+            (Some(retry), Some(provider), _) if authenticated => {
+                if matches!(format, OutputFormat::Full) {
+                    println!(
+                        "🔑 Credentials rejected (401), renewing {}",
+                        provider.name()
+                    );
+                }
+                provider.refresh(auth_generation).await?;
+                let (_, mut fresh) = provider.headers(url).await?;
+                if let Some(cookies) = fresh.remove(reqwest::header::COOKIE) {
+                    let mut merged = cookies_without_auth;
+                    append_cookies(&mut merged, cookies.to_str()?);
+                    fresh.insert(reqwest::header::COOKIE, merged.parse()?);
+                }
+                redirects.start();
+                response = options.send(retry.headers(fresh)).await?;
+            }
+            (Some(retry), None, Some(credentials)) => {
+                if matches!(format, OutputFormat::Full) {
+                    println!(
+                        "🔐 Server requires login, retrying as {}",
+                        credentials.username
+                    );
+                }
+                redirects.start();
+                response =
+                    nab::http_auth::authenticate(options, retry, response, credentials).await?;
+            }
+            _ => {}
         }
     }
 
-    let response = request.send().await?;
+    #[cfg(feature = "spa")]
+    if let Some(retry) = challenge_retry {
+        response = solve_challenge(
+            &client,
+            options,
+            response,
+            retry,
+            &mut cookie_header,
+            &profile.user_agent,
+        )
+        .await?;
+    }
+    if let (Some(solver), Some(retry)) = (captcha_solver, captcha_retry) {
+        response = solve_captcha(
+            &client,
+            options,
+            response,
+            retry,
+            solver,
+            &mut cookie_header,
+        )
+        .await?;
+    }
 
     let elapsed = start.elapsed();
     let status = response.status();
@@ -672,32 +2993,319 @@ async fn cmd_fetch(
         }
     }
 
-    // Output based on format
-    match format {
-        OutputFormat::Compact => {
-            // Minimal: STATUS SIZE TIME
-            let body_text = response.text().await?;
-            let body_len = body_text.len();
-            println!(
-                "{} {}B {:.0}ms",
-                status.as_u16(),
-                body_len,
-                elapsed.as_secs_f64() * 1000.0
-            );
+    let not_modified = status == reqwest::StatusCode::NOT_MODIFIED;
+    let response_validators = nab::Validators::from_headers(response.headers());
 
-            if show_body || output_file.is_some() || markdown || links {
-                output_body(&body_text, output_file, markdown, links, max_body)?;
-            }
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("html"));
+
+    // Markdown as it downloads (--stream)
+    if stream && is_html && !not_modified {
+        println!("🌐 Fetching: {url}");
+        println!("\n📊 Response:");
+        println!("   Status: {status}");
+        println!("   Version: {version:?}");
+        println!("   Time: {:.2}ms", elapsed.as_secs_f64() * 1000.0);
+        println!();
+        let size = stream_markdown(response, max_body).await?;
+        println!("\n📄 Body: {size} bytes");
+        return Ok(());
+    }
+
+    let summary_file = output_file.clone();
+
+    let mut har = har_file.map(|_| nab::Har::new(url, started_at));
+    // Link: rel=preload/preconnect hints (the 103's hints are repeated here)
+    let link_hints = nab::EarlyHints::from_headers(response.headers());
+    let har_entry = har_request.as_ref().map(|r| {
+        nab::HarEntry::new(r, &response, started_at, elapsed, "document")
+            .with_link_hints(&link_hints.links)
+    });
+    let download_start = Instant::now();
+    let page = nab::Response::read(response, connection, elapsed, redirects.hops()).await?;
+    let download = download_start.elapsed();
+    // What the body really is picks its handler; only HTML goes on to Markdown
+    let kind = page.kind();
+    let is_html = kind == nab::ContentKind::Html;
+    let nab::Response {
+        url: page_url,
+        headers: response_headers,
+        mut timings,
+        body,
+        ..
+    } = page;
+    let body_size = body.len();
+    let (text, binary, pdf) = match body {
+        nab::Body::Bytes(bytes) if bytes.is_empty() => (String::new(), None, None),
+        nab::Body::Bytes(bytes) if kind == nab::ContentKind::Pdf && output_file.is_none() => {
+            let document = nab::pdf::PdfDocument::parse(&bytes)
+                .map_err(|e| anyhow::anyhow!("{e}; save the PDF with -o FILE"))?;
+            (document.to_markdown(pdf_pages), None, Some(document))
         }
-        OutputFormat::Json => {
-            let body_text = response.text().await?;
-            let output = serde_json::json!({
-                "status": status.as_u16(),
-                "size": body_text.len(),
-                "time_ms": elapsed.as_secs_f64() * 1000.0,
-                "url": url,
+        nab::Body::Bytes(bytes) if kind == nab::ContentKind::Docx && output_file.is_none() => {
+            let markdown = nab::office::docx_markdown(&bytes)
+                .map_err(|e| anyhow::anyhow!("{e}; save the document with -o FILE"))?;
+            (markdown, None, None)
+        }
+        nab::Body::Bytes(bytes) if kind == nab::ContentKind::Xlsx && output_file.is_none() => {
+            let sheets = nab::office::xlsx_sheets(&bytes)
+                .map_err(|e| anyhow::anyhow!("{e}; save the workbook with -o FILE"))?;
+            (nab::office::sheets_text(&sheets), None, None)
+        }
+        nab::Body::Bytes(bytes) => (String::new(), Some(bytes), None),
+        body => (body.into_text(), None, None),
+    };
+    #[cfg(feature = "script")]
+    let text = match script {
+        Some(script) => {
+            let meta = nab::script::PageMeta {
+                url: url.to_string(),
+                final_url: page_url.to_string(),
+                status: status.as_u16(),
+                content_type: response_headers
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(ToString::to_string),
+                is_html,
+            };
+            let text = script.on_response(text, &meta)?;
+            let _ = PAGE_SCRIPT.set((script, meta));
+            text
+        }
+        None => text,
+    };
+    if let (Some(har), Some(entry)) = (har.as_mut(), har_entry) {
+        har.push(entry.with_body(body_size, download));
+    }
+
+    // Never hand back a CAPTCHA wall as if it were the page
+    let captcha = is_html
+        .then(|| nab::captcha::detect(&text, &page_url))
+        .flatten();
+    if let Some(captcha) = &captcha {
+        eprintln!(
+            "🧩 Page is blocked by {} (site key {}){}",
+            captcha.kind.name(),
+            captcha.site_key.as_deref().unwrap_or("unknown"),
+            if captcha_solver.is_none() {
+                "; plug in a solver with --captcha-solver command:<program> or webhook:<url>"
+            } else {
+                ""
+            }
+        );
+    }
+
+    // Load subresources like a browser would (--warm-resources)
+    let resources = match warm {
+        Some(plan) if is_html && !not_modified => {
+            let mut hints = link_hints.links.clone();
+            if plan.preload {
+                hints.extend(nab::extract_link_hints(&text));
+            }
+            let selected = plan.select_hinted(&text, &page_url, &hints);
+            nab::subresource::load(client.inner(), &page_url, &selected, &cookie_header).await
+        }
+        _ => Vec::new(),
+    };
+    if let Some(har) = har.as_mut() {
+        for entry in resources.iter().filter_map(|r| r.har.clone()) {
+            har.push(entry);
+        }
+    }
+    if let (Some(path), Some(har)) = (har_file, &har) {
+        har.save(path)?;
+        eprintln!(
+            "🗂️  HAR saved: {} ({} entries)",
+            path.display(),
+            har.entries().len()
+        );
+    }
+
+    // Images and other binaries (and documents with -o) are saved, not printed
+    if let Some(bytes) = binary {
+        let content_type = response_headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok());
+        let path = output_file
+            .unwrap_or_else(|| unused_path(nab::content::file_name(&page_url, kind, content_type)));
+        std::fs::write(&path, &bytes)?;
+        let image = (image_meta && kind == nab::ContentKind::Image)
+            .then(|| nab::media::ImageMeta::inspect(&bytes))
+            .flatten();
+        match format {
+            OutputFormat::Json => {
+                let mut output = serde_json::json!({
+                    "status": status.as_u16(),
+                    "size": bytes.len(),
+                    "time_ms": elapsed.as_secs_f64() * 1000.0,
+                    "url": url,
+                    "kind": kind,
+                    "content_type": content_type,
+                    "saved_to": path.display().to_string(),
+                });
+                if let Some(image) = &image {
+                    output["image"] = serde_json::to_value(image)?;
+                }
+                print_json(&output, false)?;
+            }
+            OutputFormat::Compact => println!(
+                "{} {}B {:.0}ms saved:{}{}",
+                status.as_u16(),
+                bytes.len(),
+                elapsed.as_secs_f64() * 1000.0,
+                path.display(),
+                image
+                    .as_ref()
+                    .and_then(|image| image.phash.as_ref())
+                    .map(|phash| format!(" phash:{phash}"))
+                    .unwrap_or_default()
+            ),
+            OutputFormat::Full | OutputFormat::Epub => {
+                println!(
+                    "💾 Saved {} ({}, {} bytes) to {}",
+                    kind.name(),
+                    content_type.unwrap_or("no content type"),
+                    bytes.len(),
+                    path.display()
+                );
+                if let Some(image) = &image {
+                    print_image_meta(image);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Output based on format
+    match format {
+        OutputFormat::Epub => {
+            let body_text = strip_consent(text, consent, format);
+            let base = url::Url::parse(url).ok();
+            let article = nab::CompiledArticle {
+                url: url.to_string(),
+                article: nab::extract_article(&body_text, base.as_ref()),
+            };
+            let path = output_file.unwrap_or_else(|| epub_file_name(article.title()));
+            let epub = nab::article_epub(&article, client.inner()).await?;
+            std::fs::write(&path, &epub)?;
+            println!(
+                "📕 Saved EPUB: {} ({} bytes, {}ms)",
+                path.display(),
+                epub.len(),
+                elapsed.as_millis()
+            );
+        }
+        OutputFormat::Compact => {
+            // Minimal: STATUS SIZE TIME [gated:KIND] [captcha:KIND]
+            let (body_text, gate, _) = check_gate(&client, url, text, is_html, gated_retry).await?;
+            let body_text = strip_consent(body_text, consent, format);
+            let body_len = body_text.len();
+            println!(
+                "{} {}B {:.0}ms{}{}",
+                status.as_u16(),
+                body_len,
+                elapsed.as_secs_f64() * 1000.0,
+                gate.kind
+                    .map(|k| format!(" gated:{}", k.name()))
+                    .unwrap_or_default(),
+                captcha
+                    .as_ref()
+                    .map(|c| format!(" captcha:{}", c.kind.name()))
+                    .unwrap_or_default()
+            );
+
+            if let Some((target, config)) = &translation {
+                let translated = translate_page(&body_text, is_html, target, config).await?;
+                output_body(&translated, output_file, false, false, max_body)?;
+            } else if show_body || output_file.is_some() || markdown || links {
+                let shown = present(&body_text, kind, raw_html);
+                output_body(&shown, output_file, markdown && is_html, links, max_body)?;
+            }
+            if let Some(config) = &summarize_config {
+                let summary =
+                    summarize_page(&body_text, url, is_html, config, summary_file.as_deref())
+                        .await?;
+                println!("\n{summary}");
+            }
+            if let Some(fields) = script_fields() {
+                println!("\n🧾 Fields: {}", serde_json::Value::from(fields));
+            }
+        }
+        OutputFormat::Json => {
+            let (body_text, gate, unlocked_by) =
+                check_gate(&client, url, text, is_html, gated_retry).await?;
+            let body_text = strip_consent(body_text, consent, format);
+
+            let parse_start = Instant::now();
+            let page_md = is_html.then(|| page_markdown(&body_text, true));
+            if is_html {
+                timings.add_parse(parse_start.elapsed());
+            }
+
+            let mut output = serde_json::json!({
+                "status": status.as_u16(),
+                "size": body_text.len(),
+                "time_ms": elapsed.as_secs_f64() * 1000.0,
+                "url": url,
+                "kind": kind,
+                "content_gated": gate.content_gated(),
+                "timings": timings,
             });
-            println!("{}", serde_json::to_string(&output)?);
+            if let Some(geo) = &options.geo {
+                output["geo"] = serde_json::to_value(geo)?;
+            }
+            if gate.content_gated() {
+                output["gate"] = serde_json::to_value(&gate)?;
+            }
+            if let Some(crawler) = unlocked_by {
+                output["unlocked_by"] = crawler.name().into();
+            }
+            if not_modified {
+                output["not_modified"] = true.into();
+            }
+            if let Some(etag) = &response_validators.etag {
+                output["etag"] = etag.as_str().into();
+            }
+            if let Some(last_modified) = &response_validators.last_modified {
+                output["last_modified"] = last_modified.as_str().into();
+            }
+            if !link_hints.is_empty() {
+                output["link_hints"] = serde_json::to_value(&link_hints.links)?;
+            }
+            if links && is_html {
+                output["links"] =
+                    serde_json::to_value(nab::page::link_report(&body_text, &page_url))?;
+            }
+            if !resources.is_empty() {
+                output["resources"] = serde_json::to_value(&resources)?;
+            }
+            if let Some(captcha) = &captcha {
+                output["captcha"] = serde_json::to_value(captcha)?;
+            }
+            if let Some(md) = &page_md {
+                output["language"] = serde_json::to_value(nab::detect_language(md))?;
+            }
+            if let Some(pdf) = &pdf {
+                output["pdf"] = serde_json::to_value(pdf)?;
+            }
+            if let Some((target, config)) = &translation {
+                output["translation"] = serde_json::json!({
+                    "target": target,
+                    "markdown": translate_page(&body_text, is_html, target, config).await?,
+                });
+            }
+            if let Some(config) = &summarize_config {
+                output["summary"] = summarize_page(&body_text, url, is_html, config, None)
+                    .await?
+                    .into();
+            }
+            if let Some(fields) = script_fields() {
+                output["fields"] = fields.into();
+            }
+            print_json(&output, false)?;
 
             if let Some(path) = output_file {
                 let mut file = File::create(&path)?;
@@ -712,10 +3320,9 @@ async fn cmd_fetch(
                 println!(
                     "🍪 Loaded {} cookies from {}",
                     cookie_header.matches('=').count(),
-                    if cookies == "auto" {
-                        "browser (auto-detected)"
-                    } else {
-                        cookies
+                    match &options.cookies {
+                        nab::BrowserCookies::Browser(name) => name.as_str(),
+                        _ => "browser (auto-detected)",
                     }
                 );
             }
@@ -724,26 +3331,516 @@ async fn cmd_fetch(
             println!("   Status: {status}");
             println!("   Version: {version:?}");
             println!("   Time: {:.2}ms", elapsed.as_secs_f64() * 1000.0);
+            if !link_hints.is_empty() {
+                let hints: Vec<String> = link_hints
+                    .links
+                    .iter()
+                    .map(|l| format!("{} {}", l.rel, l.url))
+                    .collect();
+                println!("   Link hints: {}", hints.join(", "));
+            }
 
             if show_headers {
                 println!("\n📋 Headers:");
-                for (name, value) in response.headers() {
+                for (name, value) in &response_headers {
                     println!("   {}: {}", name, value.to_str().unwrap_or("<binary>"));
                 }
             }
 
-            let body_text = response.text().await?;
+            if not_modified {
+                println!("\n✅ Not modified (cached copy is current)");
+                return Ok(());
+            }
+
+            let (body_text, gate, unlocked_by) =
+                check_gate(&client, url, text, is_html, gated_retry).await?;
+            if let Some(kind) = gate.kind {
+                println!(
+                    "\n🔒 Content gated: {}{}",
+                    kind.name(),
+                    gate.vendor.map(|v| format!(" ({v})")).unwrap_or_default()
+                );
+                for signal in &gate.signals {
+                    println!("   {signal}");
+                }
+                match (gated_retry, unlocked_by) {
+                    (_, Some(crawler)) => {
+                        println!("🤖 Retried as {}: full content served", crawler.name());
+                    }
+                    (Some(crawler), None) => {
+                        println!("🤖 Retried as {}: still gated", crawler.name());
+                    }
+                    (None, None) => {}
+                }
+            }
+            let body_text = strip_consent(body_text, consent, format);
             println!("\n📄 Body: {} bytes", body_text.len());
+            if let Some(pdf) = &pdf {
+                print_pdf_summary(pdf);
+            }
+            for resource in &resources {
+                match (resource.status, &resource.error) {
+                    (Some(status), _) => println!(
+                        "🧩 {} {}: {status} ({} bytes)",
+                        resource.kind, resource.url, resource.size
+                    ),
+                    (None, Some(error)) => {
+                        println!("🧩 {} {}: failed ({error})", resource.kind, resource.url);
+                    }
+                    (None, None) => {}
+                }
+            }
+            if let Some(lang) = is_html
+                .then(|| nab::detect_language(&page_markdown(&body_text, true)))
+                .flatten()
+            {
+                println!(
+                    "🗣️  Language: {} ({}, {:.2})",
+                    lang.name, lang.code, lang.confidence
+                );
+            }
+            if let Some((target, _)) = &translation {
+                println!("🌍 Translating to {target}");
+            }
 
-            if show_body || output_file.is_some() || markdown || links {
-                output_body(&body_text, output_file, markdown, links, max_body)?;
+            if let Some((target, config)) = &translation {
+                let translated = translate_page(&body_text, is_html, target, config).await?;
+                output_body(&translated, output_file, false, false, max_body)?;
+            } else if show_body || output_file.is_some() || markdown || links {
+                let shown = present(&body_text, kind, raw_html);
+                output_body(&shown, output_file, markdown && is_html, links, max_body)?;
             }
+            if let Some(config) = &summarize_config {
+                let summary =
+                    summarize_page(&body_text, url, is_html, config, summary_file.as_deref())
+                        .await?;
+                println!("\n📝 Summary:\n{summary}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Summarize a fetched page, appending the summary to `append_to` if given
+async fn summarize_page(
+    body: &str,
+    url: &str,
+    is_html: bool,
+    config: &nab::config::SummarizeConfig,
+    append_to: Option<&std::path::Path>,
+) -> Result<String> {
+    let content = page_markdown(body, is_html);
+    let summary = nab::summarize::summarize(&content, url, config).await?;
+    if let Some(path) = append_to {
+        let mut file = std::fs::OpenOptions::new().append(true).open(path)?;
+        write!(file, "\n\n## Summary\n\n{summary}\n")?;
+    }
+    Ok(summary)
+}
+
+/// Translate a fetched page's Markdown into `target`
+async fn translate_page(
+    body: &str,
+    is_html: bool,
+    target: &str,
+    config: &nab::config::TranslateConfig,
+) -> Result<String> {
+    nab::translate::translate_markdown(&page_markdown(body, is_html), target, config).await
+}
+
+/// Append `name=value` pairs to a `Cookie` header value
+fn append_cookies(header: &mut String, cookies: &str) {
+    if !header.is_empty() {
+        header.push_str("; ");
+    }
+    header.push_str(cookies);
+}
+
+/// `--jq` program applied to JSON output
+static JQ: std::sync::OnceLock<nab::jq::JqFilter> = std::sync::OnceLock::new();
+
+/// Print JSON output, through the `--jq` program if one was given
+fn print_json(value: &serde_json::Value, pretty: bool) -> Result<()> {
+    let print = |value: &serde_json::Value| -> Result<()> {
+        if pretty {
+            println!("{}", serde_json::to_string_pretty(value)?);
+        } else {
+            println!("{value}");
         }
+        Ok(())
+    };
+    match JQ.get() {
+        Some(filter) => filter.run(value.clone())?.iter().try_for_each(print),
+        None => print(value),
+    }
+}
+
+/// Print one line of JSON-lines output; a `--jq` error skips the line with a warning
+fn print_json_line(line: &serde_json::Value) {
+    if let Err(e) = print_json(line, false) {
+        eprintln!("⚠️  {e:#}");
+    }
+}
+
+/// `--publish` sink of a crawl or batch
+static PUBLISHER: std::sync::OnceLock<nab::publish::Publisher> = std::sync::OnceLock::new();
+
+/// Connect the `--publish` sink, if one was given
+async fn start_publishing(target: Option<nab::publish::PublishTarget>) -> Result<()> {
+    if let Some(target) = target {
+        let publisher = nab::publish::Publisher::connect(target).await?;
+        eprintln!("📡 Publishing pages to {}", publisher.target());
+        let _ = PUBLISHER.set(publisher);
+    }
+    Ok(())
+}
+
+/// Queue a fetched page for `--publish`; its Markdown is only made when publishing
+fn publish_page(line: &serde_json::Value, markdown: impl FnOnce() -> String) {
+    let fetched = line["status"]
+        .as_u64()
+        .is_some_and(|status| (200..300).contains(&status) || status == 304);
+    if let Some(publisher) = PUBLISHER.get().filter(|_| fetched) {
+        publisher.publish(line, &markdown());
     }
+}
 
+/// Wait until the broker has every page queued for `--publish`
+async fn finish_publishing() -> Result<()> {
+    if let Some(publisher) = PUBLISHER.get() {
+        let sent = publisher.finish().await?;
+        eprintln!("📡 Published {sent} pages to {}", publisher.target());
+    }
     Ok(())
 }
 
+/// `--script` hooks and the page they run on (`nab fetch` handles one page)
+#[cfg(feature = "script")]
+static PAGE_SCRIPT: std::sync::OnceLock<(nab::script::PageScript, nab::script::PageMeta)> =
+    std::sync::OnceLock::new();
+
+/// HTML to Markdown, run through the `--script` `on_markdown` hook if any
+fn to_markdown(html: &str) -> String {
+    let md = html_to_markdown(html);
+    #[cfg(feature = "script")]
+    if let Some((script, meta)) = PAGE_SCRIPT.get() {
+        return match script.on_markdown(md.clone(), meta) {
+            Ok(transformed) => transformed,
+            Err(e) => {
+                eprintln!("⚠️  Script: {e:#}");
+                md
+            }
+        };
+    }
+    md
+}
+
+/// Fields the `--script` hooks added, if any
+fn script_fields() -> Option<serde_json::Map<String, serde_json::Value>> {
+    #[cfg(feature = "script")]
+    if let Some((script, _)) = PAGE_SCRIPT.get() {
+        return Some(script.fields()).filter(|fields| !fields.is_empty());
+    }
+    None
+}
+
+/// A PDF's page count and metadata, for `nab fetch`'s full output
+fn print_pdf_summary(pdf: &nab::pdf::PdfDocument) {
+    let images: usize = pdf.pages.iter().map(|page| page.images).sum();
+    println!("📑 PDF: {} pages, {images} images", pdf.pages.len());
+    for (label, value) in [
+        ("Title", &pdf.title),
+        ("Author", &pdf.author),
+        ("Subject", &pdf.subject),
+        ("Created", &pdf.created),
+        ("Producer", &pdf.producer),
+    ] {
+        if let Some(value) = value {
+            println!("   {label}: {value}");
+        }
+    }
+    if !pdf.has_text() {
+        println!("   No extractable text (scanned pages or fonts without a text encoding)");
+    }
+}
+
+/// Image format, size, hash, and EXIF/XMP tags for `--image-meta`
+fn print_image_meta(image: &nab::media::ImageMeta) {
+    println!(
+        "🖼️  {} {}×{}{}",
+        image.format.to_uppercase(),
+        image.width,
+        image.height,
+        image
+            .phash
+            .as_ref()
+            .map(|phash| format!(", dHash {phash}"))
+            .unwrap_or_default()
+    );
+    for (name, value) in image.exif.iter().chain(&image.xmp) {
+        println!("   {name}: {value}");
+    }
+}
+
+/// A non-HTML body as `nab fetch` shows it: JSON pretty-printed and feeds as
+/// Markdown (unless `raw`), everything else as-is
+fn present(body: &str, kind: nab::ContentKind, raw: bool) -> std::borrow::Cow<'_, str> {
+    let converted = match kind {
+        _ if raw => None,
+        nab::ContentKind::Json => nab::content::pretty_json(body),
+        nab::ContentKind::Feed => nab::content::feed_markdown(body),
+        _ => None,
+    };
+    converted.map_or(std::borrow::Cow::Borrowed(body), std::borrow::Cow::Owned)
+}
+
+/// `name` in the working directory, numbered (`name-1.ext`, ...) if taken
+fn unused_path(name: String) -> PathBuf {
+    let path = PathBuf::from(&name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+        _ => (name.as_str(), String::new()),
+    };
+    (1..)
+        .map(|n| PathBuf::from(format!("{stem}-{n}{extension}")))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(path)
+}
+
+/// Markdown for HTML pages, the body as-is otherwise
+fn page_markdown(body: &str, is_html: bool) -> String {
+    if is_html {
+        to_markdown(body)
+    } else {
+        body.to_string()
+    }
+}
+
+/// Default EPUB file name derived from an article title
+fn epub_file_name(title: &str) -> PathBuf {
+    let slug: String = title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .take(8)
+        .collect::<Vec<_>>()
+        .join("-");
+    PathBuf::from(format!(
+        "{}.epub",
+        if slug.is_empty() { "article" } else { &slug }
+    ))
+}
+
+/// Check a fetched page for paywalls and login walls
+///
+/// With `retry`, a paywalled page is fetched again as that crawler; the
+/// crawler's copy is used only if it is no longer gated.
+async fn check_gate(
+    client: &AcceleratedClient,
+    url: &str,
+    body: String,
+    is_html: bool,
+    retry: Option<CrawlerIdentity>,
+) -> Result<(String, GateReport, Option<CrawlerIdentity>)> {
+    let gate = if is_html {
+        nab::paywall::detect_gate(&body)
+    } else {
+        GateReport::default()
+    };
+
+    if let (Some(crawler), Some(nab::GateKind::Paywall)) = (retry, gate.kind) {
+        let mut request = client.inner().get(url);
+        for (name, value) in crawler.headers() {
+            request = request.header(name, value);
+        }
+        if let Ok(response) = nab::policy::send(request).await {
+            if response.status().is_success() {
+                let retried = response.text().await?;
+                if !nab::paywall::detect_gate(&retried).content_gated() {
+                    return Ok((retried, gate, Some(crawler)));
+                }
+            }
+        }
+    }
+
+    Ok((body, gate, None))
+}
+
+/// Run a JS challenge interstitial, if `response` is one, and resend `retry` with its cookies
+///
+/// Everything executed is logged to stderr. When the page isn't a challenge
+/// or solving fails, the original response is returned.
+#[cfg(feature = "spa")]
+async fn solve_challenge(
+    client: &AcceleratedClient,
+    options: &nab::RequestOptions,
+    response: reqwest::Response,
+    retry: reqwest::RequestBuilder,
+    cookie_header: &mut String,
+    user_agent: &str,
+) -> Result<reqwest::Response> {
+    let (response, html) = buffer_html(response).await?;
+    let Some(html) = html else {
+        return Ok(response);
+    };
+    let Some(challenge) = nab::challenge::detect(&html) else {
+        return Ok(response);
+    };
+    let page_url = response.url().clone();
+
+    eprintln!(
+        "🧮 JS challenge at {page_url}: running {} inline script(s) in the sandbox (no network, {}s budget)",
+        challenge.scripts.len(),
+        nab::challenge::BUDGET.as_secs()
+    );
+    for (i, script) in challenge.describe().iter().enumerate() {
+        eprintln!("   #{} {script}", i + 1);
+    }
+    let solution = match challenge.solve(&page_url, user_agent) {
+        Ok(solution) => solution,
+        Err(e) => {
+            eprintln!("⚠️  JS challenge not solved: {e:#}");
+            return Ok(response);
+        }
+    };
+    eprintln!(
+        "🍪 Challenge set {} in {}ms",
+        solution.cookie_header(),
+        solution.elapsed.as_millis()
+    );
+
+    let mut request = retry.build()?;
+    solution.prepare_retry(&mut request, &page_url)?;
+    eprintln!("🔁 Retrying {}", request.url());
+    append_cookies(cookie_header, &solution.cookie_header());
+    options.resend(client.inner(), request).await
+}
+
+/// Hand a CAPTCHA wall in `response`, if it is one, to `solver` and submit its answer
+///
+/// Tokens go through the widget's form; cookies are added and `retry` resent.
+/// When the page has no CAPTCHA or solving fails, the original response is returned.
+async fn solve_captcha(
+    client: &AcceleratedClient,
+    options: &nab::RequestOptions,
+    response: reqwest::Response,
+    retry: reqwest::RequestBuilder,
+    solver: &dyn nab::CaptchaSolver,
+    cookie_header: &mut String,
+) -> Result<reqwest::Response> {
+    let (response, html) = buffer_html(response).await?;
+    let Some(html) = html else {
+        return Ok(response);
+    };
+    let Some(challenge) = nab::captcha::detect(&html, response.url()) else {
+        return Ok(response);
+    };
+
+    eprintln!(
+        "🧩 {} at {} (site key {}), asking solver {}",
+        challenge.kind.name(),
+        challenge.page_url,
+        challenge.site_key.as_deref().unwrap_or("unknown"),
+        solver.name()
+    );
+    let solution = match solver.solve(&challenge).await {
+        Ok(solution) => solution,
+        Err(e) => {
+            eprintln!("⚠️  CAPTCHA not solved: {e:#}");
+            return Ok(response);
+        }
+    };
+
+    if let Some(cookies) = solution.cookies.as_deref().filter(|c| !c.is_empty()) {
+        append_cookies(cookie_header, cookies);
+    }
+    let submission = if solution.token.is_empty() {
+        None
+    } else {
+        challenge.submission(client.inner(), &solution.token)
+    };
+    let request = match submission {
+        Some(request) => {
+            eprintln!(
+                "📨 Submitting {} token to the page's form",
+                challenge.kind.name()
+            );
+            request
+        }
+        None if solution.cookies.is_some() => {
+            eprintln!("🔁 Retrying {} with solver cookies", challenge.page_url);
+            retry
+        }
+        None => {
+            eprintln!("⚠️  Solver returned a token, but the page has no form to submit it with");
+            return Ok(response);
+        }
+    };
+    let mut request = request.build()?;
+    if !cookie_header.is_empty() {
+        request
+            .headers_mut()
+            .insert(reqwest::header::COOKIE, cookie_header.parse()?);
+    }
+    options.resend(client.inner(), request).await
+}
+
+/// Read an HTML response's body early (e.g. to look for challenges)
+///
+/// Returns an equivalent response to keep working with, plus the HTML; other
+/// responses pass through untouched.
+async fn buffer_html(response: reqwest::Response) -> Result<(reqwest::Response, Option<String>)> {
+    use reqwest::ResponseBuilderExt;
+
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("html"));
+    if !is_html {
+        return Ok((response, None));
+    }
+
+    let mut rebuilt = http::Response::builder()
+        .status(response.status())
+        .version(response.version())
+        .url(response.url().clone());
+    if let Some(headers) = rebuilt.headers_mut() {
+        *headers = response.headers().clone();
+        // The body below is already decoded
+        headers.remove(reqwest::header::CONTENT_ENCODING);
+        headers.remove(reqwest::header::CONTENT_LENGTH);
+    }
+    let body = response.bytes().await?;
+    let html = String::from_utf8_lossy(&body).into_owned();
+    Ok((rebuilt.body(body)?.into(), Some(html)))
+}
+
+/// Remove cookie consent dialogs and consent walls from a fetched page
+fn strip_consent(body: String, consent: ConsentMode, format: OutputFormat) -> String {
+    if !consent.is_active() {
+        return body;
+    }
+    let (cleaned, removed) = nab::consent::strip_consent_walls(&body);
+    if !removed.is_empty() && matches!(format, OutputFormat::Full) {
+        println!("🍪 Removed consent banner: {}", removed.join(", "));
+    }
+    cleaned
+}
+
 fn output_body(
     body: &str,
     output_file: Option<PathBuf>,
@@ -755,7 +3852,7 @@ fn output_body(
     if let Some(path) = output_file {
         let mut file = File::create(&path)?;
         if markdown {
-            let md = html_to_markdown(body);
+            let md = to_markdown(body);
             file.write_all(md.as_bytes())?;
         } else {
             file.write_all(body.as_bytes())?;
@@ -780,7 +3877,7 @@ fn output_body(
 
     // Convert to markdown if requested
     let output = if markdown {
-        html_to_markdown(body)
+        to_markdown(body)
     } else {
         body.to_string()
     };
@@ -801,65 +3898,34 @@ fn output_body(
     Ok(())
 }
 
-fn html_to_markdown(html: &str) -> String {
-    // Use html2md for conversion
-    let md = html2md::parse_html(html);
-
-    // Post-process: remove excessive whitespace and clutter
-    let lines: Vec<&str> = md
-        .lines()
-        .map(str::trim)
-        .filter(|l| !l.is_empty())
-        .filter(|l| !is_boilerplate(l))
-        .collect();
-
-    lines.join("\n")
-}
-
-fn is_boilerplate(line: &str) -> bool {
-    // Preserve markdown links - never filter lines containing link syntax
-    if line.contains("](") {
-        return false;
-    }
-
-    let lower = line.to_lowercase();
-    // Skip common navigation/boilerplate patterns
-    lower.contains("skip to content")
-        || lower.contains("cookie")
-        || lower.contains("privacy policy")
-        || lower.contains("terms of service")
-        || lower.starts_with("©")
-        || lower.starts_with("copyright")
-        || (lower.len() < 3 && !lower.chars().any(char::is_alphanumeric))
-}
-
-fn extract_links(html: &str) -> Vec<(String, String)> {
-    let document = Html::parse_document(html);
-    let selector = Selector::parse("a[href]").unwrap();
-
-    let mut links = Vec::new();
-    let mut seen = HashSet::new();
-
-    for element in document.select(&selector) {
-        if let Some(href) = element.value().attr("href") {
-            // Skip anchors, javascript, and duplicates
-            if href.starts_with('#') || href.starts_with("javascript:") || seen.contains(href) {
-                continue;
+/// Print a page's Markdown as its body arrives, returning the body size
+///
+/// Lines are filtered like [`html_to_markdown`]; with `max_body` the download
+/// stops once that much Markdown is out.
+async fn stream_markdown(mut response: reqwest::Response, max_body: usize) -> Result<usize> {
+    let mut converter = nab::MarkdownStream::new();
+    let mut stdout = std::io::stdout().lock();
+    let (mut size, mut written) = (0, 0);
+    let mut print = |markdown: &str, stdout: &mut std::io::StdoutLock| -> Result<bool> {
+        for line in markdown_lines(markdown) {
+            if max_body > 0 && written + line.len() > max_body {
+                writeln!(stdout, "\n... [truncated at {max_body} bytes]")?;
+                return Ok(false);
             }
-            seen.insert(href.to_string());
-
-            let text = element
-                .text()
-                .collect::<Vec<_>>()
-                .join(" ")
-                .trim()
-                .to_string();
-
-            links.push((text, href.to_string()));
+            writeln!(stdout, "{line}")?;
+            written += line.len() + 1;
+        }
+        stdout.flush()?;
+        Ok(true)
+    };
+    while let Some(chunk) = response.chunk().await? {
+        size += chunk.len();
+        if !print(&converter.push(&chunk), &mut stdout)? {
+            return Ok(size);
         }
     }
-
-    links
+    print(&converter.finish(), &mut stdout)?;
+    Ok(size)
 }
 
 fn truncate_text(text: &str, max: usize) -> String {
@@ -871,6 +3937,7 @@ fn truncate_text(text: &str, max: usize) -> String {
 }
 
 #[allow(clippy::too_many_arguments)]
+#[cfg(feature = "spa")]
 async fn cmd_spa(
     url: &str,
     cookies: &str,
@@ -882,11 +3949,20 @@ async fn cmd_spa(
     extract_path: Option<&str>,
     summary: bool,
     minify: bool,
-    max_array: Option<usize>,
-    max_depth: Option<usize>,
-    _http1: bool,
+    pruner: &nab::prune::Pruner,
+    no_wasm: bool,
+    wasm_timeout_ms: u64,
+    limits: SandboxLimits,
+    consent: ConsentMode,
+    options: &nab::ClientOptions,
+    geo: Option<&nab::Geo>,
+    cassette: Option<Arc<nab::Cassette>>,
 ) -> Result<()> {
-    let client = AcceleratedClient::new()?;
+    let mut profile = nab::random_profile();
+    if let Some(geo) = geo {
+        geo.apply(&mut profile);
+    }
+    let client = AcceleratedClient::with_profile_and_options(profile, options)?;
 
     // Extract domain from URL
     let domain = url::Url::parse(url)
@@ -924,22 +4000,33 @@ async fn cmd_spa(
         }
     }
 
+    // Pre-answer cookie consent dialogs
+    if consent.is_active() {
+        cookie_header = nab::consent::with_consent_cookies(&cookie_header, consent);
+    }
+
     let profile = client.profile().await;
     let start = Instant::now();
 
-    let response = if cookie_header.is_empty() {
-        client.fetch(url).await?
+    let html = if let Some(cassette) = &cassette {
+        let mut request = client.inner().get(url).headers(profile.to_headers());
+        if !cookie_header.is_empty() {
+            request = request.header("Cookie", &cookie_header);
+        }
+        cassette.send(request).await?.body
     } else {
-        client
-            .inner()
-            .get(url)
-            .header("Cookie", &cookie_header)
-            .headers(profile.to_headers())
-            .send()
-            .await?
+        let response = if cookie_header.is_empty() {
+            client.fetch(url).await?
+        } else {
+            let request = client
+                .inner()
+                .get(url)
+                .header("Cookie", &cookie_header)
+                .headers(profile.to_headers());
+            nab::policy::send(request).await?
+        };
+        response.text().await?
     };
-
-    let html = response.text().await?;
     let elapsed = start.elapsed();
 
     println!("🕸️  Extracting SPA data from: {url}");
@@ -1005,19 +4092,22 @@ async fn cmd_spa(
 
             // Fetch the endpoint with cookies (ignore errors, continue to next endpoint)
             let fetch_result = async {
-                let resp = if cookie_header.is_empty() {
-                    client.fetch(&endpoint_url).await?
-                } else {
-                    client
-                        .inner()
-                        .get(&endpoint_url)
-                        .header("Cookie", &cookie_header)
-                        .headers(profile.to_headers())
-                        .send()
-                        .await?
+                // Called like the page's scripts would
+                let site = match (url::Url::parse(url), url::Url::parse(&endpoint_url)) {
+                    (Ok(page), Ok(endpoint)) => nab::navigation::fetch_site(&page, &endpoint),
+                    _ => "same-origin",
+                };
+                let mut request = client
+                    .inner()
+                    .get(&endpoint_url)
+                    .headers(profile.request_headers(nab::FetchContext::Api, site));
+                if !cookie_header.is_empty() {
+                    request = request.header("Cookie", &cookie_header);
+                }
+                let text = match &cassette {
+                    Some(cassette) => cassette.send(request).await?.body,
+                    None => nab::policy::send(request).await?.text().await?,
                 };
-
-                let text = resp.text().await?;
                 let data = serde_json::from_str::<serde_json::Value>(&text)?;
 
                 // Check if it looks like useful data (not just error messages)
@@ -1035,15 +4125,7 @@ async fn cmd_spa(
                     elapsed.as_secs_f64() * 1000.0
                 );
                 println!("\n✅ API endpoint {endpoint_url} returned data:");
-                output_spa_data(
-                    &data,
-                    output,
-                    extract_path,
-                    summary,
-                    minify,
-                    max_array,
-                    max_depth,
-                )?;
+                output_spa_data(&data, output, extract_path, summary, minify, pruner)?;
                 found_data = true;
                 break;
             }
@@ -1059,15 +4141,7 @@ async fn cmd_spa(
                 elapsed.as_secs_f64() * 1000.0
             );
             println!("\n✅ __NEXT_DATA__ found:");
-            output_spa_data(
-                &data,
-                output,
-                extract_path,
-                summary,
-                minify,
-                max_array,
-                max_depth,
-            )?;
+            output_spa_data(&data, output, extract_path, summary, minify, pruner)?;
             found_data = true;
         }
     }
@@ -1081,15 +4155,7 @@ async fn cmd_spa(
             );
         }
         println!("\n✅ __INITIAL_STATE__ found:");
-        output_spa_data(
-            &data,
-            output,
-            extract_path,
-            summary,
-            minify,
-            max_array,
-            max_depth,
-        )?;
+        output_spa_data(&data, output, extract_path, summary, minify, pruner)?;
         found_data = true;
     }
 
@@ -1102,15 +4168,7 @@ async fn cmd_spa(
             );
         }
         println!("\n✅ __NUXT__ found:");
-        output_spa_data(
-            &data,
-            output,
-            extract_path,
-            summary,
-            minify,
-            max_array,
-            max_depth,
-        )?;
+        output_spa_data(&data, output, extract_path, summary, minify, pruner)?;
         found_data = true;
     }
 
@@ -1123,15 +4181,7 @@ async fn cmd_spa(
             );
         }
         println!("\n✅ __PRELOADED_STATE__ found:");
-        output_spa_data(
-            &data,
-            output,
-            extract_path,
-            summary,
-            minify,
-            max_array,
-            max_depth,
-        )?;
+        output_spa_data(&data, output, extract_path, summary, minify, pruner)?;
         found_data = true;
     }
 
@@ -1144,9 +4194,16 @@ async fn cmd_spa(
             .map(|u| u.origin().unicode_serialization())
             .unwrap_or_default();
 
-        // Create JS engine with fetch() bridge
-        let js_engine = JsEngine::new()?;
+        // Create sandboxed JS engine with fetch() bridge
+        let network_policy = limits.network.clone();
+        let js_engine = JsEngine::with_limits(limits)?;
         js_engine.inject_minimal_dom()?;
+        if let Some(geo) = geo {
+            js_engine.eval(&geo.js_shim())?;
+        }
+        if consent.is_active() {
+            js_engine.eval(&nab::consent::consent_js_shim(consent))?;
+        }
 
         // Create fetch client with cookies
         let fetch_client = FetchClient::new(
@@ -1160,12 +4217,33 @@ async fn cmd_spa(
             } else {
                 Some(base_url.clone())
             },
-        );
+        )
+        .with_network_policy(network_policy, js_engine.violation_log())?
+        .with_options(options)?;
+        let fetch_client = match &cassette {
+            Some(cassette) => fetch_client.with_cassette(Arc::clone(cassette)),
+            None => fetch_client,
+        };
 
         // Inject fetch() bridge into JS context (clone so we can access the log later)
         let fetch_client_clone = fetch_client.clone();
         inject_fetch_sync(js_engine.context(), fetch_client_clone)?;
 
+        // Inject WebAssembly shim (pages that sign API requests in WASM)
+        #[cfg(feature = "wasm")]
+        let wasm_bridge = if no_wasm {
+            None
+        } else {
+            let bridge = nab::WasmBridge::new(nab::WasmConfig {
+                call_timeout: std::time::Duration::from_millis(wasm_timeout_ms),
+                ..Default::default()
+            })?;
+            nab::inject_wasm_sync(js_engine.context(), bridge.clone())?;
+            Some(bridge)
+        };
+        #[cfg(not(feature = "wasm"))]
+        let _ = (no_wasm, wasm_timeout_ms);
+
         // Set window.location
         js_engine.set_global("__PAGE_URL__", url)?;
         js_engine.eval(&format!(
@@ -1193,7 +4271,7 @@ async fn cmd_spa(
             }
 
             // Execute script (ignore errors - some scripts may fail without full browser API)
-            if let Err(e) = js_engine.eval(&script_content) {
+            if let Err(e) = js_engine.eval_sandboxed(&script_content) {
                 if show_console {
                     println!("⚠️  Script execution error: {e}");
                 }
@@ -1211,7 +4289,8 @@ async fn cmd_spa(
         }
 
         // Try to extract data from window object
-        // Check common SPA data locations in the JS runtime
+        // Check common SPA data locations in the JS runtime; stringifying
+        // runs page getters and toJSON, so it's sandboxed too
         let patterns_to_check = vec![
             ("window.__NEXT_DATA__", "__NEXT_DATA__"),
             ("window.__INITIAL_STATE__", "__INITIAL_STATE__"),
@@ -1220,19 +4299,13 @@ async fn cmd_spa(
         ];
 
         for (js_path, name) in patterns_to_check {
-            if let Ok(json_str) = js_engine.eval(&format!("JSON.stringify({js_path} || null)")) {
+            if let Ok(json_str) =
+                js_engine.eval_page_state(&format!("JSON.stringify({js_path} || null)"))
+            {
                 if json_str != "null" {
                     if let Ok(data) = serde_json::from_str::<serde_json::Value>(&json_str) {
                         println!("\n✅ {name} found via JavaScript execution:");
-                        output_spa_data(
-                            &data,
-                            output,
-                            extract_path,
-                            summary,
-                            minify,
-                            max_array,
-                            max_depth,
-                        )?;
+                        output_spa_data(&data, output, extract_path, summary, minify, pruner)?;
                         found_data = true;
                         break;
                     }
@@ -1242,7 +4315,7 @@ async fn cmd_spa(
 
         // If still no data, try extracting entire window object
         if !found_data {
-            if let Ok(window_json) = js_engine.eval("JSON.stringify(window)") {
+            if let Ok(window_json) = js_engine.eval_page_state("JSON.stringify(window)") {
                 if let Ok(window_data) = serde_json::from_str::<serde_json::Value>(&window_json) {
                     // Filter out DOM and built-in objects
                     if let Some(obj) = window_data.as_object() {
@@ -1265,15 +4338,7 @@ async fn cmd_spa(
                         if !clean_data.is_empty() {
                             println!("\n✅ Extracted window data via JavaScript:");
                             let data = serde_json::Value::Object(clean_data);
-                            output_spa_data(
-                                &data,
-                                output,
-                                extract_path,
-                                summary,
-                                minify,
-                                max_array,
-                                max_depth,
-                            )?;
+                            output_spa_data(&data, output, extract_path, summary, minify, pruner)?;
                             found_data = true;
                         }
                     }
@@ -1281,6 +4346,17 @@ async fn cmd_spa(
             }
         }
 
+        #[cfg(feature = "wasm")]
+        if let Some(bridge) = &wasm_bridge {
+            if show_console && bridge.module_count() > 0 {
+                println!(
+                    "\n🧩 WebAssembly: {} modules compiled, {} instances",
+                    bridge.module_count(),
+                    bridge.instance_count()
+                );
+            }
+        }
+
         // Log any fetch() calls made during JavaScript execution
         let fetched_urls = fetch_client.get_fetch_log();
         if !fetched_urls.is_empty() {
@@ -1290,6 +4366,8 @@ async fn cmd_spa(
             }
         }
 
+        print_sandbox_report(&js_engine, output);
+
         if !found_data {
             println!("\n❌ No SPA data found even after JavaScript execution");
             println!("   HTML size: {} bytes", html.len());
@@ -1304,6 +4382,34 @@ async fn cmd_spa(
     Ok(())
 }
 
+/// Report sandbox limits page JS ran into (JSON metadata line for `-o json`)
+#[cfg(feature = "spa")]
+fn print_sandbox_report(js_engine: &JsEngine, output: &str) {
+    let violations = js_engine.violations();
+    if violations.is_empty() {
+        return;
+    }
+
+    if output == "json" {
+        let limits = js_engine.limits();
+        let report = serde_json::json!({
+            "sandbox": {
+                "timeout_ms": limits.timeout.map(|t| t.as_millis()),
+                "memory_limit_bytes": limits.memory_limit,
+                "network_policy": limits.network.name(),
+                "violations": violations,
+            }
+        });
+        println!("{report}");
+    } else {
+        println!("\n🛡️  Sandbox violations ({}):", violations.len());
+        for violation in &violations {
+            println!("   • {violation}");
+        }
+    }
+}
+
+#[cfg(feature = "spa")]
 fn extract_script_json(html: &str, var_name: &str) -> Option<serde_json::Value> {
     // Pattern: window.__VAR__ = {...} or <script id="__VAR__">...</script>
     let document = Html::parse_document(html);
@@ -1350,6 +4456,7 @@ fn extract_script_json(html: &str, var_name: &str) -> Option<serde_json::Value>
     None
 }
 
+#[cfg(feature = "spa")]
 fn extract_json_object(s: &str) -> Option<&str> {
     let first_char = s.chars().next()?;
     let (open, close) = match first_char {
@@ -1386,14 +4493,14 @@ fn extract_json_object(s: &str) -> Option<&str> {
     None
 }
 
+#[cfg(feature = "spa")]
 fn output_spa_data(
     data: &serde_json::Value,
     output: &str,
     extract_path: Option<&str>,
     summary: bool,
     minify: bool,
-    max_array: Option<usize>,
-    max_depth: Option<usize>,
+    pruner: &nab::prune::Pruner,
 ) -> Result<()> {
     // Extract specific path if requested
     let target = if let Some(path) = extract_path {
@@ -1402,84 +4509,29 @@ fn output_spa_data(
         for part in parts {
             current = current.get(part).unwrap_or(&serde_json::Value::Null);
         }
-        current.clone()
-    } else {
-        data.clone()
-    };
-
-    // Apply transformations
-    let transformed = if max_array.is_some() || max_depth.is_some() {
-        transform_json(
-            &target,
-            max_array.unwrap_or(usize::MAX),
-            max_depth.unwrap_or(usize::MAX),
-            0,
-        )
+        current
     } else {
-        target
+        data
     };
+    let transformed = pruner.prune(target);
 
     // Output
+    if summary && JQ.get().is_some() {
+        anyhow::bail!("--jq works on the data, not --summary");
+    }
     if summary {
         println!("   {} bytes", serde_json::to_string(&transformed)?.len());
         print_structure(&transformed, 3, 0);
     } else if output == "json" || minify {
-        if minify {
-            println!("{}", serde_json::to_string(&transformed)?);
-        } else {
-            println!("{}", serde_json::to_string_pretty(&transformed)?);
-        }
+        print_json(&transformed, !minify)?;
     } else {
-        println!("{}", serde_json::to_string_pretty(&transformed)?);
+        print_json(&transformed, true)?;
     }
 
     Ok(())
 }
 
-fn transform_json(
-    value: &serde_json::Value,
-    max_array: usize,
-    max_depth: usize,
-    depth: usize,
-) -> serde_json::Value {
-    if depth >= max_depth {
-        return serde_json::Value::String("[depth limit]".to_string());
-    }
-
-    match value {
-        serde_json::Value::Array(arr) => {
-            let limited: Vec<serde_json::Value> = arr
-                .iter()
-                .take(max_array)
-                .map(|v| transform_json(v, max_array, max_depth, depth + 1))
-                .collect();
-            if arr.len() > max_array {
-                let mut result = limited;
-                result.push(serde_json::Value::String(format!(
-                    "... +{} more",
-                    arr.len() - max_array
-                )));
-                serde_json::Value::Array(result)
-            } else {
-                serde_json::Value::Array(limited)
-            }
-        }
-        serde_json::Value::Object(obj) => {
-            let transformed: serde_json::Map<String, serde_json::Value> = obj
-                .iter()
-                .map(|(k, v)| {
-                    (
-                        k.clone(),
-                        transform_json(v, max_array, max_depth, depth + 1),
-                    )
-                })
-                .collect();
-            serde_json::Value::Object(transformed)
-        }
-        _ => value.clone(),
-    }
-}
-
+#[cfg(feature = "spa")]
 fn print_structure(value: &serde_json::Value, max_depth: usize, depth: usize) {
     let indent = "  ".repeat(depth);
 
@@ -1512,12 +4564,2105 @@ fn print_structure(value: &serde_json::Value, max_depth: usize, depth: usize) {
                 }
             }
         }
-        serde_json::Value::Array(arr) if !arr.is_empty() => {
-            println!("{indent}[0]:");
-            print_structure(&arr[0], max_depth, depth + 1);
+        serde_json::Value::Array(arr) if !arr.is_empty() => {
+            println!("{indent}[0]:");
+            print_structure(&arr[0], max_depth, depth + 1);
+        }
+        _ => {}
+    }
+}
+
+async fn cmd_extract(
+    url: &str,
+    preset: nab::Preset,
+    cookies: &str,
+    cassette: Option<Arc<nab::Cassette>>,
+    pagination: Option<nab::paginate::Pagination>,
+) -> Result<()> {
+    let page_url = url::Url::parse(url)?;
+    let client = AcceleratedClient::new()?;
+    let cassette = cassette.as_deref();
+
+    let Some(mut pagination) = pagination else {
+        let (final_url, html) = fetch_extract_page(&client, &page_url, cookies, cassette).await?;
+        let data = nab::extract::extract(preset, &html, &final_url)?;
+        return print_json(&data, true);
+    };
+
+    // Every page's item, in page order; the first page must have one
+    let mut items = Vec::new();
+    let mut next = Some(page_url);
+    while let Some(page_url) = next.take() {
+        let page = fetch_extract_page(&client, &page_url, cookies, cassette)
+            .await
+            .and_then(|(final_url, html)| {
+                let data = nab::extract::extract(preset, &html, &final_url)?;
+                Ok((final_url, html, data))
+            });
+        let (final_url, html, data) = match page {
+            Ok(page) => page,
+            Err(e) if items.is_empty() => return Err(e),
+            Err(e) => {
+                eprintln!("⚠️  Stopping at {page_url}: {e:#}");
+                break;
+            }
+        };
+        eprintln!("📄 Page {}: {final_url}", items.len() + 1);
+        items.push(data);
+        next = pagination.next(&html, &final_url);
+    }
+    print_json(&serde_json::Value::Array(items), true)
+}
+
+/// `nab compare`: fetch `url` under every variant and report the differences
+async fn cmd_compare(
+    url: &str,
+    specs: &[String],
+    cookies: &str,
+    retries: Option<u32>,
+    timeout: Option<u64>,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    use nab::compare::Variant;
+
+    let mut variants = Vec::new();
+    for spec in specs {
+        variants.extend(Variant::parse_list(spec)?);
+    }
+    if variants.len() < 2 {
+        anyhow::bail!("Comparing needs at least two variants, e.g. --variants geo=us,de");
+    }
+    let config = nab::config::NabConfig::load()?;
+    let mut configured = Vec::new();
+    for variant in variants {
+        let mut options = variant.configure(nab::RequestOptions::builder().cookies(cookies));
+        if let Some(retries) = retries {
+            options = options.retries(retries);
+        }
+        if let Some(secs) = timeout {
+            options = options.timeout(std::time::Duration::from_secs(secs));
+        }
+        let options = options
+            .domain_config(url, &config)
+            .build()
+            .map_err(|e| anyhow::anyhow!("{variant}: {e}"))?;
+        configured.push((variant, options));
+    }
+
+    eprintln!("🔀 Comparing {} variants of {url}", configured.len());
+    let report = nab::compare::run(url, configured).await;
+    for page in &report.variants {
+        if let Some(error) = &page.error {
+            eprintln!("❌ {}: {error}", page.variant);
+            continue;
+        }
+        let price = match (page.price, &page.currency) {
+            (Some(price), Some(currency)) => format!(", {price} {currency}"),
+            (Some(price), None) => format!(", {price}"),
+            (None, _) => String::new(),
+        };
+        let changes = match report
+            .differences
+            .iter()
+            .find(|d| d.variant == page.variant)
+        {
+            Some(diff) => format!(", +{}/-{} lines", diff.added.len(), diff.removed.len()),
+            None => " (baseline)".to_string(),
+        };
+        eprintln!(
+            "✅ {}: {}, {} words{price}{changes}",
+            page.variant,
+            page.status.unwrap_or_default(),
+            page.words
+        );
+    }
+
+    let value = serde_json::to_value(&report)?;
+    match output {
+        Some(path) => {
+            nab::state::write_atomic(path, serde_json::to_string_pretty(&value)?.as_bytes())?;
+            eprintln!("💾 Report saved to {}", path.display());
+            Ok(())
+        }
+        None => print_json(&value, true),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn cmd_audit_headers(
+    urls: &[String],
+    policy: Option<&std::path::Path>,
+    format: ReportFormat,
+    min_grade: Option<nab::audit::Grade>,
+    profile: Option<ProfileArg>,
+    cookies: &str,
+    retries: Option<u32>,
+    timeout: Option<u64>,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let config = nab::config::NabConfig::load()?;
+    let policy: nab::audit::HeaderPolicy = match policy {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+            serde_json::from_str(&text)
+                .map_err(|e| anyhow::anyhow!("Invalid policy {}: {e}", path.display()))?
+        }
+        None => config.audit.clone(),
+    };
+    policy.validate()?;
+
+    let mut reports = Vec::new();
+    let mut failed = 0;
+    for url in urls {
+        let mut options = nab::RequestOptions::builder().cookies(cookies);
+        if let Some(profile) = profile {
+            options = options.profile(profile.into());
+        }
+        if let Some(retries) = retries {
+            options = options.retries(retries);
+        }
+        if let Some(secs) = timeout {
+            options = options.timeout(std::time::Duration::from_secs(secs));
+        }
+        let options = options.domain_config(url, &config).build()?;
+        match nab::audit::run(url, &options, &policy).await {
+            Ok(report) => {
+                eprintln!("🛡️  {url}: {} ({}/100)", report.grade, report.score);
+                reports.push(report);
+            }
+            Err(e) => {
+                eprintln!("❌ {url}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    let markdown = || {
+        reports
+            .iter()
+            .map(nab::audit::AuditReport::to_markdown)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    match (format, output) {
+        (ReportFormat::Markdown, None) => print!("{}", markdown()),
+        (ReportFormat::Json, None) => print_json(&serde_json::to_value(&reports)?, true)?,
+        (format, Some(path)) => {
+            let text = match format {
+                ReportFormat::Markdown => markdown(),
+                ReportFormat::Json => serde_json::to_string_pretty(&reports)? + "\n",
+            };
+            nab::state::write_atomic(path, text.as_bytes())?;
+            eprintln!("💾 Report saved to {}", path.display());
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} audits failed", urls.len());
+    }
+    if let Some(min) = min_grade {
+        let below: Vec<String> = reports
+            .iter()
+            .filter(|report| report.grade > min)
+            .map(|report| format!("{} ({})", report.url, report.grade))
+            .collect();
+        if !below.is_empty() {
+            anyhow::bail!("Graded below {min}: {}", below.join(", "));
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_audit_a11y(
+    urls: &[String],
+    max_errors: Option<usize>,
+    profile: Option<ProfileArg>,
+    cookies: &str,
+    retries: Option<u32>,
+    timeout: Option<u64>,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let config = nab::config::NabConfig::load()?;
+    let mut reports = Vec::new();
+    let mut failed = 0;
+    for url in urls {
+        let mut options = nab::RequestOptions::builder().cookies(cookies);
+        if let Some(profile) = profile {
+            options = options.profile(profile.into());
+        }
+        if let Some(retries) = retries {
+            options = options.retries(retries);
+        }
+        if let Some(secs) = timeout {
+            options = options.timeout(std::time::Duration::from_secs(secs));
+        }
+        let options = options.domain_config(url, &config).build()?;
+        match nab::a11y::run(url, &options).await {
+            Ok(report) => {
+                eprintln!(
+                    "♿ {url}: {} errors, {} warnings",
+                    report.errors, report.warnings
+                );
+                reports.push(report);
+            }
+            Err(e) => {
+                eprintln!("❌ {url}: {e:#}");
+                failed += 1;
+            }
+        }
+    }
+
+    let value = serde_json::to_value(&reports)?;
+    match output {
+        Some(path) => {
+            nab::state::write_atomic(path, serde_json::to_string_pretty(&value)?.as_bytes())?;
+            eprintln!("💾 Report saved to {}", path.display());
+        }
+        None => print_json(&value, true)?,
+    }
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} audits failed", urls.len());
+    }
+    if let Some(max) = max_errors {
+        let over: Vec<String> = reports
+            .iter()
+            .filter(|report| report.errors > max)
+            .map(|report| format!("{} ({} errors)", report.url, report.errors))
+            .collect();
+        if !over.is_empty() {
+            anyhow::bail!("More than {max} accessibility errors: {}", over.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn cmd_audit_canonical(
+    input: &str,
+    max_issues: Option<usize>,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let text = if input == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(input)
+            .map_err(|e| anyhow::anyhow!("Failed to read {input}: {e}"))?
+    };
+    let report = nab::canonical::analyze(&nab::canonical::read_pages(&text)?);
+    eprintln!(
+        "🔗 {} pages, {} canonical clusters, {} issues",
+        report.pages,
+        report.clusters.len(),
+        report.issues.len()
+    );
+    for issue in &report.issues {
+        eprintln!(
+            "   ⚠️  {}: {} → {}",
+            issue.page, issue.message, issue.target
+        );
+    }
+
+    let value = serde_json::to_value(&report)?;
+    match output {
+        Some(path) => {
+            nab::state::write_atomic(path, serde_json::to_string_pretty(&value)?.as_bytes())?;
+            eprintln!("💾 Report saved to {}", path.display());
+        }
+        None => print_json(&value, true)?,
+    }
+    if let Some(max) = max_issues.filter(|max| report.issues.len() > *max) {
+        anyhow::bail!(
+            "{} canonical/hreflang issues (more than {max})",
+            report.issues.len()
+        );
+    }
+    Ok(())
+}
+
+async fn cmd_audit_tls(
+    hosts: &[String],
+    options: nab::RequestOptionsBuilder,
+    min_days: Option<i64>,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let config = nab::config::NabConfig::load()?;
+    let mut reports = Vec::new();
+    let mut problems = Vec::new();
+    let mut failed = 0;
+    for spec in hosts {
+        let (host, port) = nab::tls_audit::target(spec)?;
+        let options = options
+            .clone()
+            .domain_config(&format!("https://{host}/"), &config)
+            .build()?;
+        let report = match nab::tls_audit::inspect(&host, port, &options.client).await {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("❌ {host}:{port}: {e:#}");
+                failed += 1;
+                continue;
+            }
+        };
+        let expiry = report.expires_in_days.map_or_else(
+            || "no certificate".to_string(),
+            |days| format!("expires in {days} days"),
+        );
+        match &report.verify_error {
+            None => eprintln!(
+                "🔒 {host}:{port}: {}, {expiry}, {}",
+                report.protocol.as_deref().unwrap_or("?"),
+                report.cipher.as_deref().unwrap_or("?")
+            ),
+            Some(error) => {
+                eprintln!("⚠️  {host}:{port}: invalid ({error}), {expiry}");
+                problems.push(format!("{host}:{port} invalid"));
+            }
+        }
+        if let (Some(min), Some(days)) = (min_days, report.expires_in_days) {
+            if days < min {
+                problems.push(format!("{host}:{port} expires in {days} days"));
+            }
+        }
+        reports.push(report);
+    }
+
+    let value = serde_json::to_value(&reports)?;
+    match output {
+        Some(path) => {
+            nab::state::write_atomic(path, serde_json::to_string_pretty(&value)?.as_bytes())?;
+            eprintln!("💾 Report saved to {}", path.display());
+        }
+        None => print_json(&value, true)?,
+    }
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} handshakes failed", hosts.len());
+    }
+    if min_days.is_some() && !problems.is_empty() {
+        anyhow::bail!("TLS check failed: {}", problems.join(", "));
+    }
+    Ok(())
+}
+
+/// Download a page for `nab extract`, with browser cookies unless replaying
+async fn fetch_extract_page(
+    client: &AcceleratedClient,
+    page_url: &url::Url,
+    cookies: &str,
+    cassette: Option<&nab::Cassette>,
+) -> Result<(url::Url, String)> {
+    let url = page_url.as_str();
+    let mut request = client.inner().get(page_url.clone());
+    let replaying = cassette.is_some_and(nab::Cassette::is_replay);
+    if !cookies.eq_ignore_ascii_case("none") && !replaying {
+        let source = if cookies.eq_ignore_ascii_case("auto") {
+            nab::detect_default_browser()
+                .map_or(CookieSource::Chrome, |b| cookie_source(b.as_str()))
+        } else {
+            cookie_source(cookies)
+        };
+        let cookie_header = source
+            .get_cookie_header(page_url.host_str().unwrap_or_default())
+            .unwrap_or_default();
+        if !cookie_header.is_empty() {
+            request = request.header(reqwest::header::COOKIE, cookie_header);
+        }
+    }
+
+    Ok(if let Some(cassette) = cassette {
+        let interaction = cassette.send(request).await?;
+        if !interaction.is_success() {
+            anyhow::bail!("{url} answered {}", interaction.status);
+        }
+        (url::Url::parse(interaction.final_url())?, interaction.body)
+    } else {
+        let response = nab::policy::send(request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("{url} answered {status}");
+        }
+        (response.url().clone(), response.text().await?)
+    })
+}
+
+/// `nab fetch --paginate`: every page's Markdown in one document (or a JSON array)
+async fn cmd_fetch_pages(
+    url: &str,
+    options: &nab::RequestOptions,
+    mut pagination: nab::paginate::Pagination,
+    format: OutputFormat,
+    output_file: Option<PathBuf>,
+) -> Result<()> {
+    if matches!(format, OutputFormat::Epub) {
+        anyhow::bail!("--paginate works with --format full, compact, or json");
+    }
+    let mut pages: Vec<(url::Url, u16, String)> = Vec::new();
+    let mut next = Some(url::Url::parse(url)?);
+    while let Some(page_url) = next.take() {
+        let page = match options.fetch(page_url.as_str()).await {
+            Ok(page) if page.status.is_success() => page,
+            Ok(page) if pages.is_empty() => anyhow::bail!("{page_url} answered {}", page.status),
+            Err(e) if pages.is_empty() => return Err(e),
+            Ok(page) => {
+                eprintln!("⚠️  Stopping at {page_url}: it answered {}", page.status);
+                break;
+            }
+            Err(e) => {
+                eprintln!("⚠️  Stopping at {page_url}: {e:#}");
+                break;
+            }
+        };
+        let (final_url, status, is_html) = (page.url.clone(), page.status.as_u16(), page.is_html());
+        let body = page.body.into_text();
+        eprintln!("📄 Page {}: {final_url}", pages.len() + 1);
+        next = pagination.next(&body, &final_url);
+        pages.push((final_url, status, page_markdown(&body, is_html)));
+    }
+
+    let count = pages.len();
+    let document = if matches!(format, OutputFormat::Json) {
+        let pages: Vec<serde_json::Value> = pages
+            .into_iter()
+            .map(|(url, status, markdown)| {
+                serde_json::json!({"url": url.as_str(), "status": status, "markdown": markdown})
+            })
+            .collect();
+        if output_file.is_none() {
+            return print_json(&serde_json::Value::Array(pages), false);
+        }
+        serde_json::to_string(&pages)?
+    } else {
+        pages
+            .iter()
+            .enumerate()
+            .map(|(i, (url, _, markdown))| {
+                format!(
+                    "## Page {}\n\nSource: <{url}>\n\n{}\n",
+                    i + 1,
+                    markdown.trim()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n---\n\n")
+    };
+    match output_file {
+        Some(path) => {
+            std::fs::write(&path, &document)?;
+            eprintln!("💾 Saved {count} pages to {}", path.display());
+        }
+        None => print!("{document}"),
+    }
+    Ok(())
+}
+
+/// Cassette for `--record FILE` / `--replay FILE`
+fn cassette(
+    record: Option<PathBuf>,
+    replay: Option<&std::path::Path>,
+) -> Result<Option<Arc<nab::Cassette>>> {
+    Ok(match (record, replay) {
+        (Some(path), _) => Some(Arc::new(nab::Cassette::record(path))),
+        (None, Some(path)) => Some(Arc::new(nab::Cassette::replay(path)?)),
+        (None, None) => None,
+    })
+}
+
+/// Cookie store of a browser named on the command line (Edge reads like Chrome)
+fn cookie_source(browser: &str) -> CookieSource {
+    match browser.to_lowercase().as_str() {
+        "brave" => CookieSource::Brave,
+        "firefox" => CookieSource::Firefox,
+        "safari" => CookieSource::Safari,
+        _ => CookieSource::Chrome,
+    }
+}
+
+async fn cmd_compile(
+    input: &str,
+    output: &std::path::Path,
+    title: Option<&str>,
+    concurrency: usize,
+) -> Result<()> {
+    use futures::StreamExt;
+    use nab::compile::{compile_epub, compile_markdown, CompileFormat};
+
+    let urls = read_url_list(input)?;
+
+    let client = AcceleratedClient::new()?;
+    println!("📚 Compiling {} URLs", urls.len());
+
+    // buffered() keeps results in input order
+    let pages: Vec<(String, Result<String>)> = futures::stream::iter(urls)
+        .map(|url| {
+            let client = &client;
+            async move {
+                let page = client.fetch_text(&url).await;
+                (url, page)
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut articles = Vec::with_capacity(pages.len());
+    for (url, page) in pages {
+        match page {
+            Ok(html) => {
+                let base = url::Url::parse(&url).ok();
+                let article = nab::readability::extract_article(&html, base.as_ref());
+                let compiled = nab::compile::CompiledArticle { url, article };
+                println!("   ✅ {}", compiled.title());
+                articles.push(compiled);
+            }
+            Err(e) => println!("   ❌ {url}: {e}"),
+        }
+    }
+    if articles.is_empty() {
+        anyhow::bail!("None of the URLs could be fetched");
+    }
+
+    let title = title.map_or_else(
+        || {
+            output
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Compiled articles")
+                .to_string()
+        },
+        String::from,
+    );
+
+    match CompileFormat::from_path(output) {
+        CompileFormat::Markdown => {
+            std::fs::write(output, compile_markdown(&title, &articles))?;
+        }
+        CompileFormat::Epub => {
+            std::fs::write(
+                output,
+                compile_epub(&title, &articles, client.inner()).await?,
+            )?;
+        }
+    }
+    println!(
+        "💾 Saved {} articles to {}",
+        articles.len(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+/// Read a URL list file ('-' for stdin), failing if it has no URLs
+fn read_url_list(input: &str) -> Result<Vec<String>> {
+    let list = if input == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(input)?
+    };
+    let urls = nab::compile::parse_url_list(&list);
+    if urls.is_empty() {
+        anyhow::bail!("No URLs found in {input}");
+    }
+    Ok(urls)
+}
+
+/// Client settings resuming the TLS sessions of `persona` (`None`: the default one)
+fn persona_client_options(persona: Option<&str>) -> nab::ClientOptions {
+    nab::ClientOptions {
+        tls: nab::TlsOptions::default().with_sessions(nab::SessionStore::for_persona(persona)),
+        ..nab::ClientOptions::default()
+    }
+}
+
+/// Pacer for `--human-timing SECS`
+fn human_pacer(secs: Option<f64>, seed: Option<u64>) -> Result<Option<nab::pacing::Pacer>> {
+    let Some(secs) = secs else {
+        return Ok(None);
+    };
+    if !secs.is_finite() || secs <= 0.0 {
+        anyhow::bail!("--human-timing takes a positive number of seconds, not {secs}");
+    }
+    let timing = nab::pacing::HumanTiming::with_median(std::time::Duration::from_secs_f64(secs));
+    Ok(Some(nab::pacing::Pacer::new(timing, seed)))
+}
+
+/// Distinct URLs of a batch `input`, less those `job` completed in an earlier run
+fn pending_batch_urls(input: &str, job: Option<&nab::job::JobManifest>) -> Result<Vec<String>> {
+    let mut urls = read_url_list(input)?;
+    let mut seen = HashSet::new();
+    urls.retain(|u| seen.insert(u.clone()));
+
+    if let Some(job) = job {
+        let before = urls.len();
+        urls.retain(|url| !job.is_completed(url));
+        if urls.len() < before {
+            eprintln!(
+                "⏭️  Skipping {} URLs completed in an earlier run ({})",
+                before - urls.len(),
+                job.path().display()
+            );
+        }
+    }
+    Ok(urls)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn cmd_batch(
+    input: &str,
+    output_dir: Option<&std::path::Path>,
+    limits: nab::batch::ConcurrencyLimits,
+    parse_threads: usize,
+    resume_job: Option<&std::path::Path>,
+    output_max_size: Option<u64>,
+    pacer: Option<&nab::pacing::Pacer>,
+    navigator: Option<&nab::Navigator>,
+    auth: Option<&str>,
+    user: Option<&nab::UserCredentials>,
+    options: &nab::ClientOptions,
+    links: bool,
+) -> Result<()> {
+    let job = resume_job.map(nab::job::JobManifest::open).transpose()?;
+    let urls = pending_batch_urls(input, job.as_ref())?;
+    let job = job.map(|job| Arc::new(std::sync::Mutex::new(job)));
+    let quota = output_dir
+        .zip(output_max_size)
+        .map(|(dir, max)| Arc::new(nab::quota::OutputQuota::new(dir, max)));
+
+    // Markdown files are written by the parse pool, which prints their lines
+    let parse_pool = match output_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            let (job, quota) = (job.clone(), quota.clone());
+            Some(nab::batch::ParsePool::new(parse_threads, move |page| {
+                save_batch_page(page, job.as_deref(), quota.as_deref())
+            })?)
+        }
+        None => None,
+    };
+
+    let auth = auth
+        .map(|spec| nab::AuthProvider::load(spec, nab::SecretStore::detect()))
+        .transpose()?;
+    let clients = SiteClients::new(nab::random_profile(), options)?;
+    let total = urls.len();
+    let start = Instant::now();
+    eprintln!(
+        "📦 Fetching {total} URLs ({} per host, {} total{})",
+        limits.per_host,
+        limits.global,
+        parse_pool
+            .as_ref()
+            .map(|pool| format!(", {} parse threads", pool.threads()))
+            .unwrap_or_default()
+    );
+
+    let mut fetched = 0;
+    let scheduled = nab::batch::run_scheduled(
+        urls,
+        limits,
+        |url| {
+            let client = clients.for_url(&url);
+            let requests = &clients.requests;
+            let (auth, parse_pool) = (auth.as_ref(), parse_pool.as_ref());
+            async move {
+                if let Some(pacer) = pacer {
+                    pacer.wait(&url).await;
+                }
+                let page =
+                    fetch_batch_page(client, requests, &url, navigator, auth, user, links).await?;
+                match (parse_pool, output_dir) {
+                    (Some(pool), Some(dir)) => {
+                        let path = dir.join(nab::batch::url_file_name(&url));
+                        pool.submit((page, path)).await?;
+                        Ok(None)
+                    }
+                    _ => {
+                        publish_page(&page.line, || page_markdown(&page.body, page.is_html));
+                        Ok(Some(page.line))
+                    }
+                }
+            }
+        },
+        |url, result| match result {
+            Ok(Some(line)) => {
+                fetched += 1;
+                print_json_line(&line);
+                complete_batch_url(job.as_deref(), &url, &line);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                nab::traffic::error(&url);
+                print_json_line(
+                    &serde_json::json!({"url": url, "error": options.explain(&url, e).to_string()}),
+                );
+            }
+        },
+    );
+    // When stopped no more URLs are started; pages already fetched are still saved
+    let stopped = tokio::select! {
+        () = scheduled => None,
+        () = nab::shutdown::requested() => Some("⏸️  Interrupted"),
+        () = nab::deadline::reached() => Some("⏰ Deadline reached"),
+        () = output_exceeded(quota.as_deref()) => Some("🛑 Output limit reached"),
+    };
+    if let Some(pool) = parse_pool {
+        fetched += pool.finish().await?;
+    }
+    if let Some(job) = &job {
+        let mut job = job
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        job.save()?;
+        eprintln!(
+            "📋 Job manifest: {} ({} URLs completed)",
+            job.path().display(),
+            job.len()
+        );
+    }
+
+    if let Some(why) = stopped {
+        eprintln!(
+            "{why}; fetched {fetched}/{total} in {:.1}s{}",
+            start.elapsed().as_secs_f64(),
+            resume_job
+                .map(|path| format!(", resume with --resume-job {}", path.display()))
+                .unwrap_or_default()
+        );
+    } else {
+        eprintln!(
+            "✅ Fetched {fetched}/{total} in {:.1}s",
+            start.elapsed().as_secs_f64()
+        );
+    }
+    match quota.filter(|quota| quota.is_exceeded()) {
+        Some(quota) => anyhow::bail!(quota.error()),
+        None => Ok(()),
+    }
+}
+
+/// `--token` of `nab coordinator` and `nab worker` from the environment
+fn coordinator_token() -> Option<String> {
+    std::env::var("NAB_COORDINATOR_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+}
+
+/// `nab coordinator`: lease `input`'s URLs to workers until all are reported
+async fn cmd_coordinator(
+    input: &str,
+    listen: &str,
+    (per_host, lease): (usize, std::time::Duration),
+    token: Option<String>,
+    output_dir: Option<&std::path::Path>,
+    resume_job: Option<&std::path::Path>,
+) -> Result<()> {
+    let job = resume_job.map(nab::job::JobManifest::open).transpose()?;
+    let urls = pending_batch_urls(input, job.as_ref())?;
+    let job = job.map(std::sync::Mutex::new);
+    if let Some(dir) = output_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let total = urls.len();
+    let queue = nab::coordinator::LeaseQueue::new(urls, per_host, lease);
+    let mut coordinator = nab::coordinator::Coordinator::bind(listen, queue, token).await?;
+    let addr = coordinator.local_addr()?;
+    eprintln!("🧭 Coordinating {total} URLs on http://{addr} ({per_host} per host)");
+    let start = Instant::now();
+
+    let served = coordinator.run(|mut result| {
+        if let (Some(dir), Some(markdown)) = (output_dir, &result.markdown) {
+            let path = dir.join(nab::batch::url_file_name(&result.url));
+            match nab::state::write_atomic(&path, markdown.as_bytes()) {
+                Ok(()) => result.line["file"] = path.display().to_string().into(),
+                Err(e) => {
+                    result.line =
+                        serde_json::json!({"url": result.url, "error": format!("{e:#}")});
+                }
+            }
+        }
+        result.line["worker"] = result.worker.into();
+        print_json_line(&result.line);
+        complete_batch_url(job.as_ref(), &result.url, &result.line);
+    });
+    let stopped = tokio::select! {
+        served = served => {
+            served?;
+            None
+        }
+        () = nab::shutdown::requested() => Some("⏸️  Interrupted"),
+        () = nab::deadline::reached() => Some("⏰ Deadline reached"),
+    };
+    if let Some(job) = &job {
+        let mut job = job
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        job.save()?;
+        eprintln!(
+            "📋 Job manifest: {} ({} URLs completed)",
+            job.path().display(),
+            job.len()
+        );
+    }
+
+    let status = coordinator.status();
+    let workers: Vec<String> = status
+        .workers
+        .iter()
+        .map(|(worker, pages)| format!("{worker}: {pages}"))
+        .collect();
+    let summary = format!(
+        "{}/{total} in {:.1}s ({})",
+        status.completed,
+        start.elapsed().as_secs_f64(),
+        workers.join(", ")
+    );
+    match stopped {
+        Some(why) => eprintln!("{why}; completed {summary}"),
+        None => eprintln!("✅ Completed {summary}"),
+    }
+    Ok(())
+}
+
+/// `nab worker`: fetch leased URLs, `concurrency` at a time, and report each page
+async fn cmd_worker(
+    coordinator: &nab::coordinator::WorkerClient,
+    name: &str,
+    concurrency: usize,
+    persona: Option<&str>,
+    options: &nab::ClientOptions,
+) -> Result<()> {
+    use futures::stream::{FuturesUnordered, StreamExt};
+
+    let concurrency = concurrency.max(1);
+    let profile = persona.map_or_else(nab::random_profile, nab::persona_profile);
+    let clients = SiteClients::new(profile, options)?;
+    eprintln!(
+        "👷 Worker {name} fetching for {} ({concurrency} at a time)",
+        coordinator.base()
+    );
+    let start = Instant::now();
+    let mut running = FuturesUnordered::new();
+    let (mut done, mut reported) = (false, 0);
+
+    loop {
+        if !done && running.len() < concurrency {
+            let leased = coordinator.lease(name, concurrency - running.len()).await?;
+            done = leased.done;
+            for url in leased.urls {
+                let (client, requests) = (clients.for_url(&url), &clients.requests);
+                running.push(async move {
+                    let page =
+                        fetch_batch_page(client, requests, &url, None, None, None, false).await;
+                    (url, page)
+                });
+            }
+        }
+        if done && running.is_empty() {
+            break;
+        }
+        tokio::select! {
+            Some((url, page)) = running.next() => {
+                let (line, markdown) = match page {
+                    Ok(page) => {
+                        let markdown = page_markdown(&page.body, page.is_html);
+                        (page.line, Some(markdown))
+                    }
+                    Err(e) => {
+                        nab::traffic::error(&url);
+                        let error = options.explain(&url, e).to_string();
+                        (serde_json::json!({"url": url, "error": error}), None)
+                    }
+                };
+                let result = nab::coordinator::WorkResult {
+                    worker: name.to_string(),
+                    url,
+                    line,
+                    markdown,
+                };
+                if coordinator.report(&result).await? {
+                    reported += 1;
+                }
+            }
+            () = tokio::time::sleep(nab::coordinator::POLL) => {}
+            () = nab::shutdown::requested() => {
+                // Unreported URLs are leased to another worker when their leases run out
+                eprintln!("⏸️  Interrupted; {} leased pages left unfinished", running.len());
+                break;
+            }
+        }
+    }
+    eprintln!(
+        "✅ Worker {name}: {reported} pages in {:.1}s",
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+
+/// `nab batch --dry-run`: size the job from a few `HEAD` requests per host
+async fn cmd_batch_estimate(
+    input: &str,
+    limits: nab::batch::ConcurrencyLimits,
+    resume_job: Option<&std::path::Path>,
+    pacer: Option<&nab::pacing::Pacer>,
+    options: &nab::ClientOptions,
+) -> Result<()> {
+    let job = resume_job.map(nab::job::JobManifest::open).transpose()?;
+    let urls = pending_batch_urls(input, job.as_ref())?;
+    let interval = pacer.map_or(std::time::Duration::ZERO, |p| p.timing().mean_interval());
+    let mut estimator = nab::estimate::Estimator::new(limits, interval);
+    for url in &urls {
+        estimator.page(url, None);
+    }
+    let clients = SiteClients::new(nab::random_profile(), options)?;
+    sample_pages(&clients, &mut estimator, &urls, limits).await;
+    report_estimate(&estimator.finish());
+    Ok(())
+}
+
+/// `HEAD` the first few pages of each host into `estimator`
+///
+/// Sizes are asked for uncompressed, the size pages are saved at.
+async fn sample_pages(
+    clients: &SiteClients,
+    estimator: &mut nab::estimate::Estimator,
+    urls: &[String],
+    limits: nab::batch::ConcurrencyLimits,
+) {
+    let mut per_host: HashMap<String, usize> = HashMap::new();
+    let samples: Vec<String> = urls
+        .iter()
+        .filter(|url| {
+            let count = per_host.entry(nab::batch::host_key(url)).or_default();
+            *count += 1;
+            *count <= nab::estimate::SAMPLES_PER_HOST
+        })
+        .cloned()
+        .collect();
+    eprintln!("🔎 Sampling {} pages with HEAD", samples.len());
+    nab::batch::run_scheduled(
+        samples,
+        limits,
+        |url| async move {
+            let client = clients.for_url(&url);
+            let headers = client.profile().await.to_headers();
+            let start = Instant::now();
+            let request = client
+                .inner()
+                .head(&url)
+                .headers(headers)
+                .header(reqwest::header::ACCEPT_ENCODING, "identity");
+            let response = nab::policy::send(request).await;
+            (start.elapsed(), response)
+        },
+        |url, (elapsed, response)| match response {
+            Ok(response) => {
+                let status = response.status();
+                nab::traffic::response(&url, status.as_u16(), 0, None);
+                let size = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_LENGTH)
+                    .and_then(|v| v.to_str().ok()?.parse().ok())
+                    .filter(|_| status.is_success());
+                estimator.sample(&url, elapsed, size);
+            }
+            Err(_) => nab::traffic::error(&url),
+        },
+    )
+    .await;
+}
+
+/// Print a dry run's estimate: a JSON line on stdout, the summary on stderr
+fn report_estimate(estimate: &nab::estimate::Estimate) {
+    print_json_line(&serde_json::json!({ "estimate": estimate }));
+    eprintln!("{}", estimate.render());
+}
+
+/// A fetched batch page: its JSON result line and body
+struct BatchPage {
+    line: serde_json::Value,
+    body: String,
+    is_html: bool,
+}
+
+/// Write a page's Markdown to `path` and print its result line (runs on the parse pool)
+///
+/// The file is replaced in one step, so an interrupted job never leaves half a page.
+fn save_batch_page(
+    (mut page, path): (BatchPage, PathBuf),
+    job: Option<&std::sync::Mutex<nab::job::JobManifest>>,
+    quota: Option<&nab::quota::OutputQuota>,
+) -> bool {
+    let markdown = page_markdown(&page.body, page.is_html);
+    let written = quota
+        .map_or(Ok(()), |quota| quota.reserve(&path, markdown.len() as u64))
+        .and_then(|()| nab::state::write_atomic(&path, markdown.as_bytes()));
+    let line = match written {
+        Ok(()) => {
+            page.line["file"] = path.display().to_string().into();
+            page.line
+        }
+        Err(e) => serde_json::json!({
+            "url": page.line["url"],
+            "error": format!("{e:#}"),
+        }),
+    };
+    print_json_line(&line);
+    if let Some(url) = line["url"].as_str() {
+        complete_batch_url(job, url, &line);
+    }
+    publish_page(&line, || markdown);
+    line.get("error").is_none()
+}
+
+/// Resolves once `quota` refuses a write (never, without one)
+async fn output_exceeded(quota: Option<&nab::quota::OutputQuota>) {
+    match quota {
+        Some(quota) => quota.exceeded().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Record a batch URL whose result `line` has no error in the `--resume-job` manifest
+fn complete_batch_url(
+    job: Option<&std::sync::Mutex<nab::job::JobManifest>>,
+    url: &str,
+    line: &serde_json::Value,
+) {
+    let (Some(job), Some(status)) = (job, line["status"].as_u64()) else {
+        return;
+    };
+    if line.get("error").is_some() {
+        return;
+    }
+    let file = line["file"].as_str().map(String::from);
+    let mut job = job
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    if let Err(e) = job.complete(url, u16::try_from(status).unwrap_or(u16::MAX), file) {
+        eprintln!("⚠️  {e:#}");
+    }
+}
+
+/// Fetch one batch URL; returns its JSON result line and body
+///
+/// With `--auth`, a 401 renews the credentials (re-login or new token) once and retries;
+/// with `--user`, a 401 is answered with the server's auth scheme. With `--referer`,
+/// each page continues its site's navigation chain. With `--links`, an HTML
+/// page's line lists its links.
+async fn fetch_batch_page(
+    client: &AcceleratedClient,
+    options: &nab::RequestOptions,
+    url: &str,
+    navigator: Option<&nab::Navigator>,
+    auth: Option<&nab::AuthProvider>,
+    user: Option<&nab::UserCredentials>,
+    links: bool,
+) -> Result<BatchPage> {
+    nab::policy::check(url)?;
+    let start = Instant::now();
+    let mut navigation = client
+        .profile()
+        .await
+        .request_headers(nab::FetchContext::Navigate, "none");
+    if let (Some(navigator), Ok(parsed)) = (navigator, url::Url::parse(url)) {
+        navigation.extend(navigator.headers(&parsed));
+    }
+    let get = || client.inner().get(url).headers(navigation.clone());
+    let response = match auth {
+        Some(provider) => {
+            let (generation, headers) = provider.headers(url).await?;
+            let authenticated = !headers.is_empty();
+            let response = options.send(get().headers(headers)).await?;
+            if authenticated && response.status() == reqwest::StatusCode::UNAUTHORIZED {
+                eprintln!(
+                    "🔑 {} rejected (401), renewing credentials",
+                    provider.name()
+                );
+                provider.refresh(generation).await?;
+                let (_, fresh) = provider.headers(url).await?;
+                options.send(get().headers(fresh)).await?
+            } else {
+                response
+            }
+        }
+        None => {
+            let response = options.send(get()).await?;
+            match user {
+                Some(credentials) if response.status() == reqwest::StatusCode::UNAUTHORIZED => {
+                    nab::http_auth::authenticate(options, get(), response, credentials).await?
+                }
+                _ => response,
+            }
+        }
+    };
+    if let Some(navigator) = navigator {
+        navigator.visited(response.url());
+    }
+    let status = response.status().as_u16();
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("html"));
+    let declared = response.content_length();
+    let final_url = response.url().clone();
+    let body = response.text().await?;
+    nab::traffic::response(url, status, body.len(), declared);
+
+    let mut line = serde_json::json!({
+        "url": url,
+        "status": status,
+        "size": body.len(),
+        "time_ms": start.elapsed().as_secs_f64() * 1000.0,
+    });
+    if links && is_html {
+        line["links"] = serde_json::to_value(nab::page::link_report(&body, &final_url))?;
+    }
+    Ok(BatchPage {
+        line,
+        body,
+        is_html,
+    })
+}
+
+/// Save the crawl frontier every this many pages
+const CRAWL_SAVE_EVERY: usize = 25;
+
+#[allow(clippy::too_many_arguments)]
+async fn cmd_crawl(
+    seeds: &[String],
+    max_depth: u32,
+    max_pages: Option<usize>,
+    delay: std::time::Duration,
+    pacer: Option<&nab::pacing::Pacer>,
+    limits: nab::batch::ConcurrencyLimits,
+    mut scope: nab::crawl::CrawlScope,
+    output_dir: Option<&std::path::Path>,
+    output_max_size: Option<u64>,
+    state: Option<&std::path::Path>,
+    shared: Option<(&str, &str)>,
+    manifest: Option<&std::path::Path>,
+    revisit: Option<&str>,
+    store: Option<&std::path::Path>,
+    images: Option<&CrawlImages>,
+    honor_noindex: bool,
+    graph: Option<&std::path::Path>,
+) -> Result<()> {
+    use futures::stream::{FuturesUnordered, StreamExt};
+    use nab::crawl::{link_score, Frontier};
+
+    // One crawl per state file; a second would overwrite the first's progress
+    let _state_lock = state
+        .map(|path| nab::state::lock(path, std::time::Duration::ZERO))
+        .transpose()?;
+    let mut frontier = match (shared, state) {
+        (Some((url, name)), _) => {
+            CrawlFrontier::shared(url, name, max_depth, delay, limits).await?
+        }
+        (None, Some(path)) if path.exists() => {
+            let mut frontier = Frontier::load(path, limits)?;
+            frontier.max_depth = max_depth;
+            frontier.politeness_ms = u64::try_from(delay.as_millis()).unwrap_or(u64::MAX);
+            eprintln!(
+                "♻️  Resuming crawl: {} done, {} queued",
+                frontier.completed,
+                frontier.queued()
+            );
+            CrawlFrontier::Local(frontier)
+        }
+        _ => CrawlFrontier::Local(Frontier::new(max_depth, delay, limits)),
+    };
+    frontier.set_max_pages(max_pages);
+
+    for seed in seeds {
+        frontier.push(seed, 0, 1.0).await?;
+    }
+    if let Some(dir) = output_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let quota = output_dir
+        .zip(output_max_size)
+        .map(|(dir, max)| nab::quota::OutputQuota::new(dir, max));
+
+    let (clients, cache) = crawl_client(revisit)?;
+    let store = store.map(nab::store::BodyStore::open).transpose()?;
+    let start = Instant::now();
+    let started_at = chrono::Utc::now();
+    let mut pages = Vec::new();
+    let mut running = FuturesUnordered::new();
+    let mut unsaved = 0;
+    let mut link_graph = nab::link_graph::LinkGraph::default();
+    let interrupted = nab::shutdown::requested();
+    tokio::pin!(interrupted);
+    let deadline = nab::deadline::reached();
+    tokio::pin!(deadline);
+    let mut stopped = None;
+
+    while stopped.is_none() {
+        while let Some(entry) = frontier.pop_ready().await? {
+            let client = clients.for_url(&entry.url);
+            let (cache, store, quota) = (cache.as_ref(), store.as_ref(), quota.as_ref());
+            running.push(async move {
+                if let Some(pacer) = pacer {
+                    pacer.wait(&entry.url).await;
+                }
+                let output = output_dir.map(|dir| (dir, quota));
+                let page = fetch_crawl_page(
+                    client,
+                    &entry.url,
+                    output,
+                    cache,
+                    store,
+                    images,
+                    honor_noindex,
+                )
+                .await;
+                (entry, page)
+            });
+        }
+        if running.is_empty() && frontier.is_finished().await? {
+            break;
+        }
+        let wake = frontier
+            .next_wake()
+            .unwrap_or(std::time::Duration::from_secs(1));
+
+        tokio::select! {
+            Some((entry, page)) = running.next() => {
+                let mut line = match page {
+                    Ok((mut line, links)) => {
+                        let status = line["status"].as_u64().and_then(|s| u16::try_from(s).ok());
+                        link_graph.page(&entry.url, status, entry.depth);
+                        let mut queued = 0;
+                        for (text, link) in links {
+                            if graph.is_some() {
+                                link_graph.link(&entry.url, link.as_str(), &text);
+                            }
+                            let score = link_score(&link, &text);
+                            if scope.allows(link.as_str())
+                                && frontier.push(link.as_str(), entry.depth + 1, score).await?
+                            {
+                                queued += 1;
+                            }
+                        }
+                        line["queued"] = queued.into();
+                        line
+                    }
+                    Err(e) => {
+                        nab::traffic::error(&entry.url);
+                        link_graph.page(&entry.url, None, entry.depth);
+                        serde_json::json!({"url": entry.url, "error": e.to_string()})
+                    }
+                };
+                // After its links are queued, so no crawler sharing the frontier
+                // sees it empty in between
+                frontier.complete(&entry.url).await?;
+                line["depth"] = entry.depth.into();
+                print_json_line(&line);
+                pages.push(line);
+
+                unsaved += 1;
+                if unsaved >= CRAWL_SAVE_EVERY {
+                    if let Some(path) = state {
+                        frontier.save(path)?;
+                    }
+                    if let Some(store) = &store {
+                        store.save()?;
+                    }
+                    trim_cache();
+                    unsaved = 0;
+                }
+            }
+            () = tokio::time::sleep(wake) => {}
+            () = &mut interrupted => stopped = Some("⏸️  Interrupted"),
+            () = &mut deadline => stopped = Some("⏰ Deadline reached"),
+            () = output_exceeded(quota.as_ref()) => stopped = Some("🛑 Output limit reached"),
+        }
+    }
+
+    if honor_noindex {
+        let noindex = pages.iter().filter(|page| page["noindex"] == true).count();
+        if noindex > 0 {
+            eprintln!("🚫 {noindex} noindex pages not saved");
+        }
+    }
+    if let Some(path) = graph {
+        link_graph.write(path)?;
+        eprintln!(
+            "🕸️  Link graph: {} ({} pages, {} links, {} orphans)",
+            path.display(),
+            link_graph.node_count(),
+            link_graph.edge_count(),
+            link_graph.orphans().len()
+        );
+    }
+
+    if let Some(store) = &store {
+        store.save()?;
+    }
+
+    // Stopped early: keep the progress so far
+    if let Some(why) = stopped {
+        match state {
+            Some(path) => {
+                frontier.save(path)?;
+                eprintln!("{why}; resume with --state {}", path.display());
+            }
+            None => eprintln!("{why}"),
+        }
+        if let Some(path) = manifest {
+            let report = crawl_manifest(seeds, &frontier, &scope, started_at, pages, false);
+            nab::state::write_atomic(path, &serde_json::to_vec_pretty(&report)?)?;
+            eprintln!("📋 Partial manifest: {}", path.display());
+        }
+        eprintln!(
+            "⏹️  Crawled {} pages in {:.1}s before stopping",
+            frontier.completed(),
+            start.elapsed().as_secs_f64()
+        );
+        if let Some(quota) = quota.as_ref().filter(|quota| quota.is_exceeded()) {
+            anyhow::bail!(quota.error());
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = state {
+        let _ = std::fs::remove_file(path);
+    }
+    if let Some(path) = manifest {
+        let report = crawl_manifest(seeds, &frontier, &scope, started_at, pages, true);
+        nab::state::write_atomic(path, &serde_json::to_vec_pretty(&report)?)?;
+        eprintln!("📋 Manifest: {}", path.display());
+    }
+    eprintln!(
+        "✅ Crawled {} pages in {:.1}s",
+        frontier.completed(),
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// `nab crawl`'s frontier: its own, or one shared through Redis
+/// (`--frontier-redis`) with every crawler of the same crawl name
+enum CrawlFrontier {
+    Local(nab::crawl::Frontier),
+    #[cfg(feature = "redis")]
+    Shared(nab::redis_frontier::RedisFrontier),
+}
+
+impl CrawlFrontier {
+    #[allow(clippy::unused_async)]
+    async fn shared(
+        url: &str,
+        name: &str,
+        max_depth: u32,
+        delay: std::time::Duration,
+        limits: nab::batch::ConcurrencyLimits,
+    ) -> Result<Self> {
+        #[cfg(feature = "redis")]
+        {
+            let mut frontier =
+                nab::redis_frontier::RedisFrontier::connect(url, name, max_depth, delay, limits)
+                    .await?;
+            let status = frontier.status().await?;
+            eprintln!(
+                "🔗 Joined crawl {name}: {} done, {} queued, {} in progress",
+                status.completed, status.queued, status.in_progress
+            );
+            Ok(Self::Shared(frontier))
+        }
+        #[cfg(not(feature = "redis"))]
+        {
+            let _ = (url, name, max_depth, delay, limits);
+            anyhow::bail!("This nab was built without --frontier-redis (feature \"redis\")")
+        }
+    }
+
+    fn set_max_pages(&mut self, max_pages: Option<usize>) {
+        match self {
+            Self::Local(frontier) => frontier.max_pages = max_pages,
+            #[cfg(feature = "redis")]
+            Self::Shared(frontier) => frontier.max_pages = max_pages,
+        }
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn push(&mut self, url: &str, depth: u32, score: f64) -> Result<bool> {
+        match self {
+            Self::Local(frontier) => Ok(frontier.push(url, depth, score)),
+            #[cfg(feature = "redis")]
+            Self::Shared(frontier) => frontier.push(url, depth, score).await,
+        }
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn pop_ready(&mut self) -> Result<Option<nab::crawl::FrontierEntry>> {
+        match self {
+            Self::Local(frontier) => Ok(frontier.pop_ready(Instant::now())),
+            #[cfg(feature = "redis")]
+            Self::Shared(frontier) => frontier.pop_ready().await,
+        }
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn complete(&mut self, url: &str) -> Result<()> {
+        match self {
+            Self::Local(frontier) => {
+                frontier.complete(url);
+                Ok(())
+            }
+            #[cfg(feature = "redis")]
+            Self::Shared(frontier) => frontier.complete(url).await,
+        }
+    }
+
+    /// Nothing left to crawl, or the page limit reached
+    #[allow(clippy::unused_async)]
+    async fn is_finished(&mut self) -> Result<bool> {
+        match self {
+            Self::Local(frontier) => Ok(frontier.is_done() || frontier.limit_reached()),
+            #[cfg(feature = "redis")]
+            Self::Shared(frontier) => frontier.is_finished().await,
+        }
+    }
+
+    /// When a URL may be ready; a shared frontier is polled for other crawlers' links
+    fn next_wake(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::Local(frontier) => frontier.next_wake(Instant::now()),
+            #[cfg(feature = "redis")]
+            Self::Shared(_) => Some(nab::redis_frontier::POLL),
+        }
+    }
+
+    /// Pages this crawler completed
+    fn completed(&self) -> usize {
+        match self {
+            Self::Local(frontier) => frontier.completed,
+            #[cfg(feature = "redis")]
+            Self::Shared(frontier) => frontier.completed,
+        }
+    }
+
+    fn queued(&self) -> usize {
+        match self {
+            Self::Local(frontier) => frontier.queued(),
+            #[cfg(feature = "redis")]
+            Self::Shared(frontier) => frontier.queued(),
+        }
+    }
+
+    fn max_depth(&self) -> u32 {
+        match self {
+            Self::Local(frontier) => frontier.max_depth,
+            #[cfg(feature = "redis")]
+            Self::Shared(frontier) => frontier.max_depth,
+        }
+    }
+
+    fn max_pages(&self) -> Option<usize> {
+        match self {
+            Self::Local(frontier) => frontier.max_pages,
+            #[cfg(feature = "redis")]
+            Self::Shared(frontier) => frontier.max_pages,
+        }
+    }
+
+    /// Save to `--state` (which a shared crawl can't have)
+    fn save(&self, path: &std::path::Path) -> Result<()> {
+        match self {
+            Self::Local(frontier) => frontier.save(path),
+            #[cfg(feature = "redis")]
+            Self::Shared(_) => Ok(()),
+        }
+    }
+}
+
+/// The crawl's clients, as the `--revisit` persona with its cache if there is one
+fn crawl_client(revisit: Option<&str>) -> Result<(SiteClients, Option<nab::RevisitCache>)> {
+    let cache = revisit.map(nab::RevisitCache::open).transpose()?;
+    if let (Some(persona), Some(cache)) = (revisit, &cache) {
+        eprintln!("🔁 Revisiting as {persona} ({})", cache.dir().display());
+    }
+    let profile = revisit.map_or_else(nab::random_profile, nab::persona_profile);
+    let clients = SiteClients::new(profile, &persona_client_options(revisit))?;
+    Ok((clients, cache))
+}
+
+/// Clients of a multi-site command: one per HTTP version the config's
+/// `domains` or the protocol memory pick, all with the same browser identity
+struct SiteClients {
+    default: AcceleratedClient,
+    by_version: HashMap<nab::HttpVersion, AcceleratedClient>,
+    config: nab::config::NabConfig,
+    /// How requests of these clients are sent (HTTP/1.1 fallback included)
+    requests: nab::RequestOptions,
+}
+
+impl SiteClients {
+    fn new(profile: nab::BrowserProfile, options: &nab::ClientOptions) -> Result<Self> {
+        let config = nab::config::NabConfig::load()?;
+        let mut versions: HashSet<nab::HttpVersion> =
+            config.domains.values().filter_map(|site| site.http).collect();
+        if nab::protocol_cache::global().has_version(nab::HttpVersion::Http1) {
+            versions.insert(nab::HttpVersion::Http1);
+        }
+        let by_version = versions
+            .into_iter()
+            .map(|version| {
+                let options = nab::ClientOptions {
+                    http: Some(version),
+                    ..options.clone()
+                };
+                let client = AcceleratedClient::with_profile_and_options(profile.clone(), &options)?;
+                Ok((version, client))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            default: AcceleratedClient::with_profile_and_options(profile, options)?,
+            by_version,
+            config,
+            requests: nab::RequestOptions {
+                client: options.clone(),
+                ..nab::RequestOptions::default()
+            },
+        })
+    }
+
+    /// Client for `url`'s domain
+    fn for_url(&self, url: &str) -> &AcceleratedClient {
+        url::Url::parse(url)
+            .ok()
+            .and_then(|url| {
+                self.config
+                    .http_for(&url)
+                    .or_else(|| nab::protocol_cache::global().preferred(&url))
+            })
+            .and_then(|version| self.by_version.get(&version))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// `nab crawl --dry-run`: plan the crawl without fetching pages, then size it
+/// from a few `HEAD` requests per host
+///
+/// The frontier starts from `--state` and the seeds, takes in the seed sites'
+/// sitemaps (as links from the seeds), and grows from the links of pages in
+/// the `--revisit` cache, with the crawl's depth, scope, and page limits.
+#[allow(clippy::too_many_arguments)]
+async fn cmd_crawl_estimate(
+    seeds: &[String],
+    max_depth: u32,
+    max_pages: Option<usize>,
+    delay: std::time::Duration,
+    pacer: Option<&nab::pacing::Pacer>,
+    limits: nab::batch::ConcurrencyLimits,
+    mut scope: nab::crawl::CrawlScope,
+    state: Option<&std::path::Path>,
+    revisit: Option<&str>,
+) -> Result<()> {
+    use nab::crawl::{link_score, Frontier};
+
+    // Nothing is fetched while planning, so nothing needs pacing
+    let unlimited = nab::batch::ConcurrencyLimits::new(usize::MAX, usize::MAX);
+    let mut frontier = match state {
+        Some(path) if path.exists() => {
+            let mut frontier = Frontier::load(path, unlimited)?;
+            frontier.max_depth = max_depth;
+            frontier.politeness_ms = 0;
+            eprintln!(
+                "♻️  Resuming crawl: {} done, {} queued",
+                frontier.completed,
+                frontier.queued()
+            );
+            frontier
+        }
+        _ => Frontier::new(max_depth, std::time::Duration::ZERO, unlimited),
+    };
+    frontier.max_pages = max_pages;
+    for seed in seeds {
+        frontier.push(seed, 0, 1.0);
+    }
+
+    let (clients, cache) = crawl_client(revisit)?;
+    let mut sitemap_pages = 0;
+    if max_depth > 0 {
+        for page in sitemap_pages_of(&clients, seeds).await {
+            let Ok(link) = url::Url::parse(&page) else {
+                continue;
+            };
+            if scope.allows(link.as_str()) && frontier.push(link.as_str(), 1, link_score(&link, ""))
+            {
+                sitemap_pages += 1;
+            }
+        }
+    }
+
+    let mut planned = Vec::new();
+    let mut unexplored = 0;
+    while let Some(entry) = frontier.pop_ready(Instant::now()) {
+        frontier.complete(&entry.url);
+        let cached = cache.as_ref().and_then(|cache| cache.get(&entry.url));
+        match (&cached, url::Url::parse(&entry.url)) {
+            (Some(page), Ok(base)) => {
+                let is_html = page
+                    .content_type
+                    .as_deref()
+                    .is_some_and(|ct| ct.contains("html"));
+                let links = if is_html {
+                    page_links(&page.body, &base)
+                } else {
+                    Vec::new()
+                };
+                for (text, link) in links {
+                    if scope.allows(link.as_str()) {
+                        frontier.push(link.as_str(), entry.depth + 1, link_score(&link, &text));
+                    }
+                }
+            }
+            _ if entry.depth < max_depth => unexplored += 1,
+            _ => {}
+        }
+        planned.push((entry.url, cached.map(|page| page.body.len())));
+    }
+
+    let interval = pacer.map_or(delay, |p| p.timing().mean_interval());
+    let mut estimator = nab::estimate::Estimator::new(limits, interval);
+    for (url, cached) in &planned {
+        estimator.page(url, *cached);
+    }
+    let urls: Vec<String> = planned.into_iter().map(|(url, _)| url).collect();
+    sample_pages(&clients, &mut estimator, &urls, limits).await;
+    let mut estimate = estimator.finish();
+    estimate.sitemap_pages = sitemap_pages;
+    estimate.unexplored = unexplored;
+    report_estimate(&estimate);
+    Ok(())
+}
+
+/// Page URLs in the sitemaps of the seeds' sites: those their `robots.txt`
+/// lists, else `/sitemap.xml` (at most [`nab::estimate::MAX_SITEMAPS`] files)
+async fn sitemap_pages_of(clients: &SiteClients, seeds: &[String]) -> Vec<String> {
+    use nab::estimate::{robots_sitemaps, Sitemap, MAX_SITEMAPS};
+
+    let origins: std::collections::BTreeSet<String> = seeds
+        .iter()
+        .filter_map(|seed| url::Url::parse(seed).ok())
+        .map(|seed| seed.origin().ascii_serialization())
+        .collect();
+    // Relative locations are taken from the file they're listed in
+    let resolve = |base: &str, urls: Vec<String>| -> Vec<String> {
+        let base = url::Url::parse(base).ok();
+        urls.into_iter()
+            .filter_map(|url| Some(base.as_ref()?.join(&url).ok()?.to_string()))
+            .collect()
+    };
+    let mut queue = Vec::new();
+    for origin in origins {
+        let robots = format!("{origin}/robots.txt");
+        let listed = fetch_metadata(clients.for_url(&robots), &robots)
+            .await
+            .map(|body| robots_sitemaps(&String::from_utf8_lossy(&body)))
+            .unwrap_or_default();
+        if listed.is_empty() {
+            queue.push(format!("{origin}/sitemap.xml"));
+        } else {
+            queue.extend(resolve(&robots, listed));
+        }
+    }
+
+    let mut read = HashSet::new();
+    let mut pages = Vec::new();
+    while let Some(url) = queue.pop() {
+        if read.len() >= MAX_SITEMAPS {
+            eprintln!("⚠️  Read {MAX_SITEMAPS} sitemaps, skipping the rest");
+            break;
+        }
+        if !read.insert(url.clone()) {
+            continue;
+        }
+        let Some(body) = fetch_metadata(clients.for_url(&url), &url).await else {
+            continue;
+        };
+        match Sitemap::from_bytes(&body) {
+            Ok(sitemap) => {
+                queue.extend(resolve(&url, sitemap.sitemaps));
+                pages.extend(resolve(&url, sitemap.pages));
+            }
+            Err(e) => eprintln!("⚠️  Sitemap {url}: {e:#}"),
+        }
+    }
+    if !pages.is_empty() {
+        eprintln!("🗺️  {} pages in {} sitemaps", pages.len(), read.len());
+    }
+    pages
+}
+
+/// Body of a successful response for a site file like `robots.txt`
+async fn fetch_metadata(client: &AcceleratedClient, url: &str) -> Option<Vec<u8>> {
+    let headers = client.profile().await.to_headers();
+    let request = client.inner().get(url).headers(headers);
+    let Ok(response) = nab::policy::send(request).await else {
+        nab::traffic::error(url);
+        return None;
+    };
+    let (status, declared) = (response.status(), response.content_length());
+    let body = response.bytes().await.ok()?;
+    nab::traffic::response(url, status.as_u16(), body.len(), declared);
+    status.is_success().then(|| body.to_vec())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn cmd_linkcheck(
+    seeds: &[String],
+    depth: u32,
+    delay: std::time::Duration,
+    limits: nab::batch::ConcurrencyLimits,
+    mut scope: nab::crawl::CrawlScope,
+    timeout: std::time::Duration,
+    ignore_redirects: bool,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    use futures::stream::{FuturesUnordered, StreamExt};
+    use nab::crawl::{link_score, Frontier};
+    use nab::linkcheck::{LinkIndex, Outcome};
+
+    // One level past the crawled pages, so their links are checked as well
+    let mut frontier = Frontier::new(depth + 1, delay, limits);
+    for seed in seeds {
+        frontier.push(seed, 0, 1.0);
+    }
+    let client = AcceleratedClient::new_no_redirect_with_options(&nab::ClientOptions {
+        timeout: Some(timeout),
+        ..persona_client_options(None)
+    })?;
+
+    let start = Instant::now();
+    let started_at = chrono::Utc::now();
+    let mut index = LinkIndex::default();
+    let mut crawled = 0;
+    let mut running = FuturesUnordered::new();
+    let interrupted = nab::shutdown::requested();
+    tokio::pin!(interrupted);
+    let deadline = nab::deadline::reached();
+    tokio::pin!(deadline);
+    let mut stopped = None;
+
+    while stopped.is_none() {
+        while let Some(entry) = frontier.pop_ready(Instant::now()) {
+            let crawl = entry.depth <= depth && scope.allows(&entry.url);
+            let client = client.inner();
+            running.push(async move {
+                let (check, links) = nab::linkcheck::check(client, &entry.url, crawl).await;
+                (entry, check, links)
+            });
+        }
+        if running.is_empty() && frontier.is_done() {
+            break;
+        }
+        let wake = frontier
+            .next_wake(Instant::now())
+            .unwrap_or(std::time::Duration::from_secs(1));
+
+        tokio::select! {
+            Some((entry, check, links)) = running.next() => {
+                frontier.complete(&entry.url);
+                if let Some(links) = links {
+                    crawled += 1;
+                    for (text, link) in links {
+                        index.found(&entry.url, link.as_str());
+                        frontier.push(link.as_str(), entry.depth + 1, link_score(&link, &text));
+                    }
+                }
+                let icon = match check.outcome {
+                    Outcome::Ok => None,
+                    Outcome::Redirected if ignore_redirects => None,
+                    Outcome::Redirected => Some("↪️ "),
+                    Outcome::Broken => Some("❌"),
+                    Outcome::Timeout => Some("⏱️ "),
+                    Outcome::Error => Some("⚠️ "),
+                };
+                if let Some(icon) = icon {
+                    eprintln!("{icon} {}: {}", check.url, check.summary());
+                }
+                index.checked(check);
+            }
+            () = tokio::time::sleep(wake) => {}
+            () = &mut interrupted => stopped = Some("⏸️  Interrupted"),
+            () = &mut deadline => stopped = Some("⏰ Deadline reached"),
+        }
+    }
+    if let Some(why) = stopped {
+        eprintln!("{why}; reporting the links checked so far");
+    }
+
+    let counts = index.counts();
+    let count = |outcome| counts.get(&outcome).copied().unwrap_or(0);
+    let failed = count(Outcome::Broken) + count(Outcome::Timeout) + count(Outcome::Error);
+    let report = serde_json::json!({
+        "seeds": seeds,
+        "started_at": started_at.to_rfc3339(),
+        "finished_at": chrono::Utc::now().to_rfc3339(),
+        "complete": stopped.is_none(),
+        "depth": depth,
+        "pages_crawled": crawled,
+        "links_checked": index.len(),
+        "summary": counts,
+        "scope": scope.report(),
+        "pages": index.problems(ignore_redirects),
+    });
+    match output {
+        Some(path) => {
+            nab::state::write_atomic(path, &serde_json::to_vec_pretty(&report)?)?;
+            eprintln!("📋 Report: {}", path.display());
+        }
+        None => print_json(&report, true)?,
+    }
+    eprintln!(
+        "{} Checked {} links on {crawled} pages in {:.1}s: {} broken, {} timed out, {} failed, {} redirected",
+        if failed == 0 { "✅" } else { "❌" },
+        index.len(),
+        start.elapsed().as_secs_f64(),
+        count(Outcome::Broken),
+        count(Outcome::Timeout),
+        count(Outcome::Error),
+        count(Outcome::Redirected),
+    );
+    if failed > 0 {
+        anyhow::bail!("{failed} links are broken or unreachable");
+    }
+    Ok(())
+}
+
+/// Crawl manifest: settings, scope filters with rejection counts, and crawled pages
+fn crawl_manifest(
+    seeds: &[String],
+    frontier: &CrawlFrontier,
+    scope: &nab::crawl::CrawlScope,
+    started_at: chrono::DateTime<chrono::Utc>,
+    pages: Vec<serde_json::Value>,
+    complete: bool,
+) -> serde_json::Value {
+    serde_json::json!({
+        "seeds": seeds,
+        "started_at": started_at.to_rfc3339(),
+        "finished_at": chrono::Utc::now().to_rfc3339(),
+        "complete": complete,
+        "max_depth": frontier.max_depth(),
+        "max_pages": frontier.max_pages(),
+        "scope": scope.report(),
+        "queued": frontier.queued(),
+        "pages": pages,
+    })
+}
+
+/// `nab crawl --download-images`: images saved so far (each once per crawl)
+struct CrawlImages {
+    saved: std::sync::Mutex<std::collections::HashSet<String>>,
+    meta: bool,
+}
+
+impl CrawlImages {
+    fn new(meta: bool) -> Self {
+        Self {
+            saved: std::sync::Mutex::default(),
+            meta,
+        }
+    }
+
+    /// Save the page's images not saved before to `dir/images`; one manifest entry each
+    async fn download(
+        &self,
+        client: &AcceleratedClient,
+        urls: Vec<url::Url>,
+        dir: &std::path::Path,
+        quota: Option<&nab::quota::OutputQuota>,
+    ) -> Result<Vec<serde_json::Value>> {
+        let dir = dir.join("images");
+        let mut entries = Vec::new();
+        for url in urls {
+            let url = url.to_string();
+            if !self.saved.lock().unwrap().insert(url.clone()) {
+                continue;
+            }
+            let response = match client.fetch(&url).await {
+                Ok(response) if response.status().is_success() => response,
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    nab::traffic::response(&url, status, 0, None);
+                    entries.push(serde_json::json!({"url": url, "status": status}));
+                    continue;
+                }
+                Err(e) => {
+                    nab::traffic::error(&url);
+                    entries.push(serde_json::json!({"url": url, "error": e.to_string()}));
+                    continue;
+                }
+            };
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+            let (status, declared) = (response.status().as_u16(), response.content_length());
+            let bytes = response.bytes().await?;
+            nab::traffic::response(&url, status, bytes.len(), declared);
+            let name = nab::batch::url_file_name(&url);
+            let path = dir.join(name.trim_end_matches(".md"));
+            if let Some(quota) = quota {
+                quota.reserve(&path, bytes.len() as u64)?;
+            }
+            std::fs::create_dir_all(&dir)?;
+            nab::state::write_atomic(&path, &bytes)?;
+            let mut entry = serde_json::json!({
+                "url": url,
+                "file": path.display().to_string(),
+                "size": bytes.len(),
+                "content_type": content_type,
+            });
+            if let Some(image) = self
+                .meta
+                .then(|| nab::media::ImageMeta::inspect(&bytes))
+                .flatten()
+            {
+                entry["image"] = serde_json::to_value(image)?;
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/// Fetch one crawl page: its JSON result line and the absolute links it contains
+///
+/// With a `--revisit` cache, a page seen before is revalidated; on 304 its
+/// links come from the cached copy. With a `--store`, the body is kept there
+/// and the line gets its `sha256`.
+async fn fetch_crawl_page(
+    client: &AcceleratedClient,
+    url: &str,
+    output: Option<(&std::path::Path, Option<&nab::quota::OutputQuota>)>,
+    cache: Option<&nab::RevisitCache>,
+    store: Option<&nab::store::BodyStore>,
+    images: Option<&CrawlImages>,
+    honor_noindex: bool,
+) -> Result<(serde_json::Value, Vec<(String, url::Url)>)> {
+    let cached = cache.and_then(|c| c.get(url));
+    let response = match &cached {
+        Some(page) => {
+            let validators = &page.validators;
+            let etag = validators.etag.as_deref();
+            match client
+                .get_if_modified(url, etag, validators.last_modified.as_deref())
+                .await?
+            {
+                nab::Conditional::NotModified => None,
+                nab::Conditional::Modified(response) => Some(response),
+            }
         }
-        _ => {}
+        None => Some(client.fetch(url).await?),
+    };
+    let (status, final_url, content_type, robots, body) = match (response, cached) {
+        (Some(response), _) => {
+            let status = response.status().as_u16();
+            let final_url = response.url().clone();
+            let headers = response.headers().clone();
+            let declared = response.content_length();
+            let body = response.text().await?;
+            nab::traffic::response(url, status, body.len(), declared);
+            if let Some(cache) = cache {
+                match nab::CachedPage::from_response(url, &headers, &body) {
+                    Some(page) if status == 200 => cache.store(&page)?,
+                    _ => cache.remove(url),
+                }
+            }
+            let content_type = headers
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+            let robots = nab::revisit::robots_header(&headers);
+            (status, final_url, content_type, robots, body)
+        }
+        (None, Some(page)) => {
+            nab::traffic::response(url, 304, 0, None);
+            nab::traffic::cache_hit(url, page.body.len());
+            let final_url = url::Url::parse(url)?;
+            (304, final_url, page.content_type, page.robots, page.body)
+        }
+        (None, None) => unreachable!("only cached pages are revalidated"),
+    };
+    let is_html = content_type.is_some_and(|ct| ct.contains("html"));
+
+    let links = if is_html {
+        page_links(&body, &final_url)
+    } else {
+        Vec::new()
+    };
+
+    let mut line = serde_json::json!({
+        "url": url,
+        "status": status,
+        "size": body.len(),
+        "links": links.len(),
+        "fetched_at": chrono::Utc::now().to_rfc3339(),
+    });
+    // canonical, hreflang, noindex, and nofollow, for `nab audit canonical`
+    let signals = if is_html {
+        nab::canonical::PageSignals::from_html(&body, &final_url, robots.as_deref())
+    } else {
+        nab::canonical::PageSignals::from_robots_header(robots.as_deref())
+    };
+    let noindex = signals.noindex;
+    if let serde_json::Value::Object(signals) = serde_json::to_value(signals)? {
+        line.as_object_mut().expect("object").extend(signals);
+    }
+    let stored = matches!(status, 200..=299 | 304) && !(honor_noindex && noindex);
+    if let Some(store) = store.filter(|_| stored) {
+        line["sha256"] = store.put(url, body.as_bytes())?.into();
+    }
+    let mut markdown = None;
+    if let Some((dir, quota)) = output.filter(|_| !(honor_noindex && noindex)) {
+        let path = dir.join(nab::batch::url_file_name(url));
+        let text = page_markdown(&body, is_html);
+        if let Some(quota) = quota {
+            quota.reserve(&path, text.len() as u64)?;
+        }
+        nab::state::write_atomic(&path, text.as_bytes())?;
+        markdown = Some(text);
+        line["file"] = path.display().to_string().into();
+        if let Some(images) = images.filter(|_| is_html) {
+            let urls = nab::media::image_urls(&body, &final_url);
+            line["images"] = images.download(client, urls, dir, quota).await?.into();
+        }
+    }
+    if !(honor_noindex && noindex) {
+        publish_page(&line, || {
+            markdown.unwrap_or_else(|| page_markdown(&body, is_html))
+        });
+    }
+    Ok((line, links))
+}
+
+/// `(text, absolute URL)` of a page's http(s) links
+fn page_links(html: &str, base: &url::Url) -> Vec<(String, url::Url)> {
+    extract_links(html)
+        .into_iter()
+        .filter_map(|(text, href)| Some((text, base.join(&href).ok()?)))
+        .filter(|(_, link)| matches!(link.scheme(), "http" | "https"))
+        .collect()
+}
+
+/// Serve `fixtures` until killed; the first stdout line has the base URL
+#[cfg(feature = "mock-server")]
+async fn cmd_mock_server(fixtures: &std::path::Path, listen: &str, http1_only: bool) -> Result<()> {
+    let fixtures = nab::mock_server::Fixtures::load(fixtures)?;
+    let mut server = nab::mock_server::MockServer::bind(fixtures, listen).await?;
+    if http1_only {
+        server = server.http1_only();
     }
+    println!("Listening on http://{}", server.local_addr()?);
+    std::io::stdout().flush()?;
+    server.run().await
 }
 
 async fn cmd_bench(urls: &str, iterations: usize) -> Result<()> {
@@ -1557,8 +6702,11 @@ async fn cmd_bench(urls: &str, iterations: usize) -> Result<()> {
     Ok(())
 }
 
-fn cmd_fingerprint(count: usize) {
-    println!("🎭 Generating {count} browser fingerprints:\n");
+fn cmd_fingerprint(count: usize, seed: Option<u64>) {
+    match seed {
+        Some(seed) => println!("🎭 Generating {count} browser fingerprints (seed {seed}):\n"),
+        None => println!("🎭 Generating {count} browser fingerprints:\n"),
+    }
 
     for i in 0..count {
         let profile = nab::random_profile();
@@ -1568,10 +6716,390 @@ fn cmd_fingerprint(count: usize) {
         if !profile.sec_ch_ua.is_empty() {
             println!("   Sec-CH-UA: {}", profile.sec_ch_ua);
         }
+        if let Some(hints) = &profile.client_hints {
+            println!(
+                "   Client hints: {} {}-bit, platform {}",
+                hints.arch, hints.bitness, hints.platform_version
+            );
+        }
         println!();
     }
 }
 
+async fn cmd_fingerprint_verify(profile: ProfileArg, endpoints: Vec<String>) -> Result<()> {
+    let profile = nab::ProfileChoice::from(profile).profile();
+    let endpoints = if endpoints.is_empty() {
+        nab::config::NabConfig::load()?.fingerprint.verify_endpoints
+    } else {
+        endpoints
+    };
+    let client = AcceleratedClient::with_profile(profile.clone())?;
+
+    println!("🎭 Verifying {}\n", profile.user_agent);
+    let mut total = 0;
+    for endpoint in &endpoints {
+        let request = client.inner().get(endpoint).headers(profile.to_headers());
+        let body: serde_json::Value = match nab::policy::send(request).await {
+            Ok(response) => match response.json().await {
+                Ok(body) => body,
+                Err(e) => {
+                    println!("⚠️  {endpoint}: not a JSON echo ({e})");
+                    continue;
+                }
+            },
+            Err(e) => {
+                println!("⚠️  {endpoint}: {e}");
+                continue;
+            }
+        };
+        let observed = nab::fingerprint::verify::Observation::parse(&body);
+        if observed.headers.is_empty() {
+            println!("⚠️  {endpoint}: no headers in the echo");
+            continue;
+        }
+        let mismatches = nab::fingerprint::verify::compare(&profile, &observed);
+        if mismatches.is_empty() {
+            println!("✅ {endpoint}: matches");
+        } else {
+            println!("❌ {endpoint}: {} mismatch(es)", mismatches.len());
+            for mismatch in &mismatches {
+                println!("   {mismatch}");
+            }
+        }
+        for (name, value) in &observed.fingerprints {
+            println!("   {name}: {value}");
+        }
+        total += mismatches.len();
+    }
+
+    if total > 0 {
+        anyhow::bail!("{total} fingerprint mismatch(es)");
+    }
+    Ok(())
+}
+
+async fn cmd_login(recipe: &str, name: Option<&str>) -> Result<()> {
+    let (mut login, path) = nab::LoginRecipe::load(recipe)?;
+    if let Some(name) = name {
+        login.name = name.to_string();
+    }
+
+    println!("🔐 Logging in to {} ({})", login.login_url, login.name);
+    let session = login.run(&path).await?;
+    let store = nab::SecretStore::detect();
+    session.save(&store)?;
+
+    let cookies = session
+        .cookies
+        .split("; ")
+        .filter(|c| !c.is_empty())
+        .count();
+    println!(
+        "✅ Saved session '{}' for {} in {} ({cookies} cookies, {} headers)",
+        session.name,
+        session.domain,
+        store.name(),
+        session.headers.len()
+    );
+    println!("   Use it with: nab fetch --auth {} <URL>", session.name);
+    Ok(())
+}
+
+fn cmd_secrets(action: SecretsAction) -> Result<()> {
+    let store = nab::SecretStore::detect();
+    match action {
+        SecretsAction::Set { name, value } => {
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    let value = std::io::read_to_string(std::io::stdin())?;
+                    value.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            if value.is_empty() {
+                anyhow::bail!("Refusing to store an empty secret");
+            }
+            store.set(&name, &value)?;
+            eprintln!("✅ Saved '{name}' in the {}", store.name());
+        }
+        SecretsAction::Get { name } => match store.get(&name)? {
+            Some(value) => println!("{value}"),
+            None => anyhow::bail!("No secret '{name}' in the {}", store.name()),
+        },
+        SecretsAction::Rm { name } => {
+            if !store.delete(&name)? {
+                anyhow::bail!("No secret '{name}' in the {}", store.name());
+            }
+            eprintln!("🗑️  Removed '{name}' from the {}", store.name());
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_proxy(action: ProxyAction) -> Result<()> {
+    let ProxyAction::Check {
+        file,
+        output,
+        concurrency,
+        timeout,
+        endpoints,
+        ip_url,
+        headers_url,
+    } = action;
+    let list = std::fs::read_to_string(&file)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", file.display()))?;
+    let specs = nab::proxy_check::parse_list(&list);
+    if specs.is_empty() {
+        anyhow::bail!("No proxies in {}", file.display());
+    }
+    let mut options = nab::proxy_check::CheckOptions {
+        ip_url,
+        headers_url,
+        timeout: std::time::Duration::from_secs(timeout),
+        concurrency,
+        ..nab::proxy_check::CheckOptions::default()
+    };
+    if !endpoints.is_empty() {
+        options.challenge_urls = endpoints;
+    }
+
+    eprintln!("🔎 Checking {} proxies...", specs.len());
+    let report = nab::proxy_check::check_all(&specs, &options, |proxy| match &proxy.error {
+        Some(error) => eprintln!("❌ {}: {error}", proxy.proxy),
+        None => eprintln!(
+            "✅ {}: score {} ({:?}, {:.0}ms, egress {})",
+            proxy.proxy,
+            proxy.score,
+            proxy.anonymity,
+            proxy.latency_ms.unwrap_or_default(),
+            proxy.egress_ip.as_deref().unwrap_or("?")
+        ),
+    })
+    .await?;
+
+    let json = serde_json::to_vec_pretty(&report)?;
+    match output {
+        Some(path) => {
+            nab::state::write_atomic(&path, &json)?;
+            eprintln!("📄 Report written to {}", path.display());
+        }
+        None => println!("{}", String::from_utf8_lossy(&json)),
+    }
+    Ok(())
+}
+
+fn cmd_workspace(action: WorkspaceAction) -> Result<()> {
+    use nab::workspace;
+    match action {
+        WorkspaceAction::List => {
+            let current = workspace::current();
+            for name in workspace::list()? {
+                let marker = if current.as_deref() == Some(name.as_str()) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{marker} {name}");
+            }
+        }
+        WorkspaceAction::Path { name } => match name {
+            Some(name) => println!("{}", workspace::path(&name)?.display()),
+            None => println!("{}", workspace::config_dir().display()),
+        },
+        WorkspaceAction::Archive { name, output } => {
+            let (zip, files) = workspace::archive(&name)?;
+            let output = output.unwrap_or_else(|| PathBuf::from(format!("{name}.zip")));
+            std::fs::write(&output, zip)?;
+            eprintln!(
+                "📦 Archived workspace '{name}' ({files} files) to {}",
+                output.display()
+            );
+        }
+        WorkspaceAction::Rm { name } => {
+            if !workspace::remove(&name)? {
+                anyhow::bail!("No workspace '{name}'");
+            }
+            eprintln!("🗑️  Removed workspace '{name}'");
+        }
+    }
+    Ok(())
+}
+
+fn cmd_cache(action: &CacheAction) -> Result<()> {
+    use nab::quota::{format_size, Usage};
+    let dir = nab::workspace::cache_dir();
+    match action {
+        CacheAction::Stats => {
+            println!("📁 {}", dir.display());
+            let mut parts: Vec<(String, Usage)> = std::fs::read_dir(&dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    (name, nab::quota::usage(&entry.path()))
+                })
+                .collect();
+            parts.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, usage) in &parts {
+                let name = if usage.files == 1 && dir.join(name).is_file() {
+                    name.clone()
+                } else {
+                    format!("{name}/")
+                };
+                println!(
+                    "   {name:<24} {:>10}  {} files",
+                    format_size(usage.bytes),
+                    usage.files
+                );
+            }
+            let total = nab::quota::usage(&dir);
+            println!(
+                "   {:<24} {:>10}  {} files",
+                "total",
+                format_size(total.bytes),
+                total.files
+            );
+        }
+        CacheAction::Clear => {
+            let cleared = nab::quota::clear(&dir)?;
+            eprintln!(
+                "🗑️  Cleared {} files ({}) from {}",
+                cleared.files,
+                format_size(cleared.bytes),
+                dir.display()
+            );
+        }
+        CacheAction::Compress { persona, train } => {
+            let personas = match persona {
+                Some(persona) => vec![persona.clone()],
+                None => {
+                    let mut names: Vec<String> = std::fs::read_dir(dir.join("revisit"))
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .filter(|entry| entry.path().is_dir())
+                        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                        .collect();
+                    names.sort();
+                    names
+                }
+            };
+            if personas.is_empty() {
+                eprintln!("📭 No revisit pages in {}", dir.display());
+            }
+            for persona in &personas {
+                let report = nab::RevisitCache::open(persona)?.compress_all(*train)?;
+                println!(
+                    "🗜️  {persona}: {} pages, {} → {}",
+                    report.entries,
+                    format_size(report.bytes_before),
+                    format_size(report.bytes_after)
+                );
+                for trained in &report.dictionaries {
+                    println!(
+                        "   📖 {}: {} dictionary from {} pages",
+                        trained.host,
+                        format_size(trained.bytes as u64),
+                        trained.samples
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An existing `crawl --store` (opening creates one; don't for a mistyped path)
+fn open_store(dir: &std::path::Path) -> Result<nab::store::BodyStore> {
+    if !dir.join("blobs").is_dir() {
+        anyhow::bail!("{} is not a crawl --store directory", dir.display());
+    }
+    nab::store::BodyStore::open(dir)
+}
+
+fn cmd_store(action: &StoreAction) -> Result<()> {
+    let (StoreAction::Gc { dir, .. } | StoreAction::Cat { dir, .. }) = action;
+    let store = open_store(dir)?;
+    match action {
+        StoreAction::Gc {
+            keep_runs, dry_run, ..
+        } => {
+            let report = store.gc(*keep_runs, *dry_run)?;
+            let verb = if *dry_run { "Would remove" } else { "Removed" };
+            eprintln!(
+                "🧹 {verb} {} runs and {} blobs ({}); {} blobs kept",
+                report.runs_removed,
+                report.blobs_removed,
+                nab::quota::format_size(report.bytes_removed),
+                report.blobs_kept
+            );
+        }
+        StoreAction::Cat { dir, url } => {
+            let Some(hash) = store.lookup(url)? else {
+                anyhow::bail!("{url} is not in {}", dir.display());
+            };
+            std::io::stdout().write_all(&store.get(&hash)?)?;
+        }
+    }
+    Ok(())
+}
+
+fn cmd_export(
+    input: &str,
+    output: &str,
+    format: ExportFormat,
+    store: Option<&std::path::Path>,
+) -> Result<()> {
+    let text = if input == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(input)
+            .map_err(|e| anyhow::anyhow!("Failed to read {input}: {e}"))?
+    };
+    let crawl = nab::export::Crawl::parse(&text)?;
+    let store = store.map(open_store).transpose()?;
+    let rows = crawl.rows(store.as_ref());
+    let count = match (format, output) {
+        (ExportFormat::Jsonl, "-") => nab::export::write_jsonl(rows, std::io::stdout().lock())?,
+        (ExportFormat::Jsonl, path) => {
+            nab::export::write_jsonl(rows, std::io::BufWriter::new(std::fs::File::create(path)?))?
+        }
+        (ExportFormat::Parquet, "-") => anyhow::bail!("Parquet needs an output file, not stdout"),
+        #[cfg(feature = "parquet")]
+        (ExportFormat::Parquet, path) => {
+            nab::export::write_parquet(rows, std::fs::File::create(path)?)?
+        }
+        #[cfg(not(feature = "parquet"))]
+        (ExportFormat::Parquet, _) => {
+            anyhow::bail!("This nab was built without Parquet support (feature \"parquet\")")
+        }
+    };
+    eprintln!("📦 Exported {count} pages to {output}");
+    Ok(())
+}
+
+async fn cmd_self_update(channel: Option<nab::self_update::Channel>, check: bool) -> Result<()> {
+    let config = nab::config::NabConfig::load()?.self_update;
+    let channel = channel.unwrap_or(config.channel);
+    let updater = nab::self_update::Updater::new(config)?;
+    let current = env!("CARGO_PKG_VERSION");
+    let Some(update) = updater.check(channel, current).await? else {
+        println!("✅ nab {current} is the newest {channel} release");
+        return Ok(());
+    };
+    let version = update.release.version();
+    if check {
+        println!("⬆️  nab {version} is available ({channel}, installed: {current})");
+        return Ok(());
+    }
+    let path = nab::self_update::current_executable()?;
+    eprintln!("⬇️  Downloading {} ({version})", update.asset.name);
+    updater.install(&update, &path).await?;
+    println!("✅ Updated nab {current} → {version} ({})", path.display());
+    Ok(())
+}
+
 fn cmd_auth(url: &str) -> Result<()> {
     if !OnePasswordAuth::is_available() {
         println!("❌ 1Password CLI not available or not authenticated");
@@ -1743,11 +7271,14 @@ fn cmd_otp(domain: &str) -> Result<()> {
 }
 
 #[allow(clippy::too_many_arguments)]
+#[cfg(feature = "stream")]
 async fn cmd_stream(
     source: &str,
     id: &str,
     output: &str,
     quality: &str,
+    audio_lang: &[String],
+    container: Option<ContainerArg>,
     force_native: bool,
     force_ffmpeg: bool,
     info_only: bool,
@@ -1756,22 +7287,37 @@ async fn cmd_stream(
     duration: Option<&str>,
     ffmpeg_opts: Option<&str>,
     player: Option<&str>,
+    player_args: Option<&str>,
+    mmap: bool,
 ) -> Result<()> {
     use nab::stream::{
         backend::StreamConfig,
         backends::{FfmpegBackend, NativeHlsBackend},
+        container::Container,
+        player::Player,
         providers::{GenericHlsProvider, YleProvider},
         StreamBackend, StreamProvider, StreamQuality,
     };
     use nab::CookieSource;
     use std::collections::HashMap;
-    use std::process::Stdio;
     use tokio::io::{stdout, AsyncWriteExt};
 
+    if mmap && output == "-" {
+        anyhow::bail!("--mmap writes to a file, pass one with --output");
+    }
+
+    // Record until the deadline at the latest
+    let duration = duration.map(parse_duration).transpose()?;
+    let duration = match nab::deadline::remaining() {
+        Some(left) => Some(duration.unwrap_or(u64::MAX).min(left.as_secs().max(1))),
+        None => duration,
+    };
+
     // Parse quality
     let stream_quality = match quality.to_lowercase().as_str() {
         "best" => StreamQuality::Best,
         "worst" => StreamQuality::Worst,
+        "auto" => StreamQuality::Auto,
         q => q
             .parse::<u32>()
             .map(StreamQuality::Specific)
@@ -1936,6 +7482,7 @@ async fn cmd_stream(
         } else {
             Some(cookies.to_string())
         },
+        audio_languages: audio_lang.to_vec(),
     };
 
     // For Yle, get fresh manifest URL via yle-dl (Akamai tokens expire quickly)
@@ -1960,17 +7507,54 @@ async fn cmd_stream(
     let is_dash = manifest_url.contains(".mpd");
     let is_encrypted = false; // Would need manifest parsing to detect
 
-    let use_ffmpeg = force_ffmpeg || is_dash || is_encrypted || ffmpeg_opts.is_some();
+    let player_extra_args: Vec<String> = player_args
+        .map(|args| args.split_whitespace().map(String::from).collect())
+        .unwrap_or_default();
+
+    // Output container: --container, else the output file's extension
+    let to_file = output != "-" && player.is_none();
+    let mut output_path = std::path::PathBuf::from(output);
+    let container = match container {
+        Some(ContainerArg::Mp4) => Some(Container::Mp4),
+        Some(ContainerArg::Mkv) => Some(Container::Mkv),
+        Some(ContainerArg::Ts) => Some(Container::Ts),
+        None if to_file => Container::from_extension(&output_path),
+        None => None,
+    };
+
+    let use_ffmpeg =
+        force_ffmpeg || is_dash || is_encrypted || ffmpeg_opts.is_some() || !audio_lang.is_empty();
     let use_native = force_native && !is_dash && !is_encrypted;
 
     if use_ffmpeg && !use_native {
         eprintln!("🔧 Backend: ffmpeg");
+        if !audio_lang.is_empty() {
+            eprintln!("   🔊 Audio tracks: {}", audio_lang.join(", "));
+        }
+        if config.quality == StreamQuality::Auto {
+            eprintln!(
+                "   ⚠️  --quality auto needs the native backend; ffmpeg takes the best variant"
+            );
+        }
         let mut backend = FfmpegBackend::new()?;
 
         if let Some(opts) = ffmpeg_opts {
             backend = backend.with_transcode_opts(opts);
         }
 
+        if let Some(requested) = container {
+            // Transcoded streams get codecs that fit whatever was asked for
+            let container = if ffmpeg_opts.is_some() {
+                requested
+            } else {
+                compatible_container(requested, manifest_url, &config.headers).await
+            };
+            if container != requested && to_file {
+                output_path.set_extension(container.extension());
+            }
+            backend = backend.with_container(container);
+        }
+
         // Check ffmpeg availability
         if !backend.check_available().await {
             anyhow::bail!("ffmpeg not found in PATH. Install ffmpeg or use --native.");
@@ -1985,51 +7569,40 @@ async fn cmd_stream(
         };
 
         if let Some(player_cmd) = player {
-            // Stream to media player
+            // Stream to media player, remuxed by ffmpeg
             eprintln!("🎬 Piping to: {player_cmd}");
-            let player_args = get_player_stdin_args(player_cmd);
-            let mut child = tokio::process::Command::new(player_cmd)
-                .args(&player_args)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::inherit())
-                .stderr(Stdio::inherit())
-                .spawn()
-                .map_err(|e| anyhow::anyhow!("Failed to spawn {player_cmd}: {e}"))?;
-
-            let mut stdin = child
-                .stdin
-                .take()
-                .ok_or_else(|| anyhow::anyhow!("Failed to get stdin for {player_cmd}"))?;
-
-            if let Some(dur_str) = duration {
-                let secs = parse_duration(dur_str)?;
-                backend
-                    .stream_with_duration(
-                        manifest_url,
-                        &config,
-                        &mut stdin,
-                        secs,
-                        Some(Box::new(progress_cb)),
-                    )
-                    .await?;
-            } else {
-                backend
-                    .stream_to(
-                        manifest_url,
-                        &config,
-                        &mut stdin,
-                        Some(Box::new(progress_cb)),
-                    )
-                    .await?;
+            let (mut player, mut input) = Player::spawn(player_cmd, &player_extra_args)?;
+            let streamed = async {
+                if let Some(secs) = duration {
+                    backend
+                        .stream_with_duration(
+                            manifest_url,
+                            &config,
+                            &mut input,
+                            secs,
+                            Some(Box::new(progress_cb)),
+                        )
+                        .await
+                } else {
+                    backend
+                        .stream_to(
+                            manifest_url,
+                            &config,
+                            &mut input,
+                            Some(Box::new(progress_cb)),
+                        )
+                        .await
+                }
+            };
+            tokio::select! {
+                result = streamed => played(result, player_cmd)?,
+                status = player.exited() => stopped_by_player(player_cmd, status?),
             }
-
-            drop(stdin); // Close stdin to signal EOF
-            child.wait().await?;
+            player.finish(input).await?;
         } else if output == "-" {
             // Stream to stdout
             let mut stdout = stdout();
-            if let Some(dur_str) = duration {
-                let secs = parse_duration(dur_str)?;
+            if let Some(secs) = duration {
                 backend
                     .stream_with_duration(
                         manifest_url,
@@ -2052,25 +7625,44 @@ async fn cmd_stream(
             stdout.flush().await?;
         } else {
             // Stream to file
-            let path = std::path::Path::new(output);
-            let duration_parsed = duration.map(parse_duration).transpose()?;
             backend
                 .stream_to_file(
                     manifest_url,
                     &config,
-                    path,
+                    &output_path,
                     Some(Box::new(progress_cb)),
-                    duration_parsed,
+                    duration,
                 )
                 .await?;
         }
     } else {
         eprintln!("🔧 Backend: native");
-        let backend = NativeHlsBackend::new()?;
+        let site_config = nab::config::NabConfig::load()?;
+        let options = nab::ClientOptions {
+            http: url::Url::parse(manifest_url)
+                .ok()
+                .and_then(|url| site_config.http_for(&url)),
+            ..nab::ClientOptions::default()
+        };
+        let backend = NativeHlsBackend::with_options(&options)?
+            .with_mmap_output(mmap)
+            .with_quality_log(Box::new(|s: nab::stream::backend::QualitySwitch| {
+                let from = s.from.map(|h| format!("{h}p → ")).unwrap_or_default();
+                eprintln!(
+                    "\n   📶 {from}{}p @ {:.1} Mbps (measured {:.1} Mbps, {:.0}s)",
+                    s.to,
+                    s.bandwidth as f64 / 1e6,
+                    s.throughput_bps as f64 / 1e6,
+                    s.elapsed_seconds
+                );
+            }));
 
         if !backend.can_handle(manifest_url, is_encrypted) {
             anyhow::bail!("Native backend cannot handle this stream. Try --ffmpeg.");
         }
+        if !to_file && container.is_some_and(|c| c != Container::Ts) {
+            eprintln!("   ⚠️  The native backend pipes MPEG-TS; use --ffmpeg for --container");
+        }
 
         let progress_cb = |p: nab::stream::backend::StreamProgress| {
             let total = p
@@ -2089,54 +7681,56 @@ async fn cmd_stream(
         if let Some(player_cmd) = player {
             // Stream to media player
             eprintln!("🎬 Piping to: {player_cmd}");
-            let player_args = get_player_stdin_args(player_cmd);
-            let mut child = tokio::process::Command::new(player_cmd)
-                .args(&player_args)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::inherit())
-                .stderr(Stdio::inherit())
-                .spawn()
-                .map_err(|e| anyhow::anyhow!("Failed to spawn {player_cmd}: {e}"))?;
-
-            let mut stdin = child
-                .stdin
-                .take()
-                .ok_or_else(|| anyhow::anyhow!("Failed to get stdin for {player_cmd}"))?;
-
-            backend
-                .stream_to(
+            let (mut player, mut input) = Player::spawn(player_cmd, &player_extra_args)?;
+            tokio::select! {
+                result = backend.stream_to(
                     manifest_url,
                     &config,
-                    &mut stdin,
+                    &mut input,
                     Some(Box::new(progress_cb)),
-                )
-                .await?;
-
-            drop(stdin); // Close stdin to signal EOF
-            child.wait().await?;
+                ) => played(result, player_cmd)?,
+                status = player.exited() => stopped_by_player(player_cmd, status?),
+                () = nab::deadline::reached() => eprintln!("\n⏰ Deadline reached"),
+            }
+            player.finish(input).await?;
         } else if output == "-" {
             let mut stdout = stdout();
-            backend
-                .stream_to(
+            tokio::select! {
+                result = backend.stream_to(
                     manifest_url,
                     &config,
                     &mut stdout,
                     Some(Box::new(progress_cb)),
-                )
-                .await?;
+                ) => result?,
+                () = nab::deadline::reached() => eprintln!("\n⏰ Deadline reached"),
+            }
             stdout.flush().await?;
         } else {
-            let path = std::path::Path::new(output);
-            let duration_parsed = duration.map(parse_duration).transpose()?;
+            // Segments are MPEG-TS; other containers are remuxed from a temporary capture
+            let remux = container.filter(|c| *c != Container::Ts);
+            let download = match remux {
+                Some(_) => output_path.with_extension("part.ts"),
+                None => output_path.clone(),
+            };
             backend
                 .stream_to_file(
                     manifest_url,
                     &config,
-                    path,
+                    &download,
                     Some(Box::new(progress_cb)),
-                    duration_parsed,
+                    duration,
                 )
                 .await?;
+            if let Some(requested) = remux {
+                let source = download.to_string_lossy();
+                let container = compatible_container(requested, &source, &HashMap::new()).await;
+                if container != requested {
+                    output_path.set_extension(container.extension());
+                }
+                eprintln!("\n📦 Remuxing to {}", output_path.display());
+                nab::stream::container::remux("ffmpeg", &download, &output_path, container).await?;
+                std::fs::remove_file(&download)?;
+            }
         }
     }
 
@@ -2144,19 +7738,48 @@ async fn cmd_stream(
     Ok(())
 }
 
-/// Get arguments for media players to read from stdin
-fn get_player_stdin_args(player: &str) -> Vec<&'static str> {
-    match player {
-        "vlc" => vec!["-", "--intf", "dummy", "--play-and-exit"],
-        "mpv" => vec!["-"],
-        "ffplay" => vec!["-i", "-"],
-        "mplayer" => vec!["-"],
-        "iina" => vec!["--stdin"],
-        _ => vec!["-"], // Default: most players accept - for stdin
+/// `requested`, or Matroska when `source` has a codec that can't go in it
+#[cfg(feature = "stream")]
+async fn compatible_container(
+    requested: nab::stream::container::Container,
+    source: &str,
+    headers: &std::collections::HashMap<String, String>,
+) -> nab::stream::container::Container {
+    // Without ffprobe there's nothing to check against
+    let Ok(streams) = nab::stream::tracks::probe("ffprobe", source, headers).await else {
+        return requested;
+    };
+    let (container, unsupported) = requested.compatible(&streams);
+    if let Some(codec) = unsupported {
+        eprintln!(
+            "   ⚠️  {codec} can't go in {}; writing {} instead",
+            requested.extension(),
+            container.extension()
+        );
+    }
+    container
+}
+
+/// The end of a stream piped into a player; the player quitting first isn't an error
+#[cfg(feature = "stream")]
+fn played(result: Result<()>, player: &str) -> Result<()> {
+    match result {
+        Err(e) if nab::stream::player::is_closed(&e) => {
+            eprintln!("\n🎬 {player} closed, download stopped");
+            Ok(())
+        }
+        result => result,
     }
 }
 
+/// The player exited while the stream was still downloading
+#[cfg(feature = "stream")]
+fn stopped_by_player(player: &str, status: std::process::ExitStatus) {
+    eprintln!("\n🎬 {player} exited ({status}), download stopped");
+}
+
 /// Parse duration string like "1h", "30m", "1h30m", "90" (seconds)
+#[cfg(feature = "stream")]
 fn parse_duration(s: &str) -> Result<u64> {
     let s = s.trim().to_lowercase();
 
@@ -2195,6 +7818,107 @@ fn parse_duration(s: &str) -> Result<u64> {
     Ok(total_secs)
 }
 
+/// `nab analyze --integrity`: report what's damaged in a capture, by byte range and time
+#[cfg(feature = "analyze")]
+fn cmd_integrity(video: &str, output: Option<&std::path::Path>) -> Result<()> {
+    if video.starts_with("http://") || video.starts_with("https://") {
+        anyhow::bail!("--integrity checks a local capture; save it with nab stream first");
+    }
+    let file =
+        std::fs::File::open(video).map_err(|e| anyhow::anyhow!("Failed to open {video}: {e}"))?;
+    let report = nab::analyze::integrity::check(std::io::BufReader::new(file))?;
+    let json = serde_json::to_string_pretty(&report)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, &json)?;
+            eprintln!("📄 Saved to: {}", path.display());
+        }
+        None => println!("{json}"),
+    }
+
+    let streams: Vec<String> = report
+        .streams
+        .iter()
+        .map(|s| format!("{} {}", s.kind, s.codec))
+        .collect();
+    eprintln!(
+        "{} {:.1}s, {} packets ({}): {} issues",
+        if report.is_clean() { "✅" } else { "❌" },
+        report.duration,
+        report.packets,
+        streams.join(", "),
+        report.issues.len()
+    );
+    if !report.is_clean() {
+        anyhow::bail!("{} integrity problems in {video}", report.issues.len());
+    }
+    Ok(())
+}
+
+/// `nab analyze --chapters`: chapter markers as JSON or an FFmetadata file
+#[cfg(feature = "analyze")]
+async fn cmd_chapters(
+    video: &str,
+    format: AnalyzeOutputFormat,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    use nab::analyze::chapters::{to_ffmetadata, ChapterDetector};
+
+    if video.starts_with("http://") || video.starts_with("https://") {
+        anyhow::bail!("--chapters reads a local file; save it with nab stream first");
+    }
+    let chapters = ChapterDetector::default()
+        .detect(std::path::Path::new(video))
+        .await?;
+    let out = match format {
+        AnalyzeOutputFormat::Json => serde_json::to_string_pretty(&chapters)?,
+        AnalyzeOutputFormat::Ffmetadata => to_ffmetadata(&chapters),
+        AnalyzeOutputFormat::Markdown | AnalyzeOutputFormat::Srt => {
+            anyhow::bail!("--chapters writes json or ffmetadata")
+        }
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(path, &out)?;
+            eprintln!("📄 Saved to: {}", path.display());
+        }
+        None => println!("{out}"),
+    }
+    let source = chapters
+        .first()
+        .map_or("none".into(), |c| format!("{:?}", c.source).to_lowercase());
+    eprintln!("📑 {} chapters (from {source})", chapters.len());
+    Ok(())
+}
+
+/// `nab analyze --storyboard`: sprite sheets plus a WebVTT storyboard for seek previews
+#[cfg(feature = "analyze")]
+async fn cmd_storyboard(video: &str, dir: &std::path::Path, interval: f64) -> Result<()> {
+    use nab::analyze::storyboard::StoryboardConfig;
+
+    if video.starts_with("http://") || video.starts_with("https://") {
+        anyhow::bail!("--storyboard reads a local file; save it with nab stream first");
+    }
+    if interval <= 0.0 {
+        anyhow::bail!("--storyboard-interval must be positive");
+    }
+    let config = StoryboardConfig {
+        interval,
+        ..Default::default()
+    };
+    let storyboard = config.generate(std::path::Path::new(video), dir).await?;
+    eprintln!(
+        "🎞️  {} thumbnails ({}x{}) in {} sprite sheets",
+        storyboard.thumbnails,
+        storyboard.thumbnail_width,
+        storyboard.thumbnail_height,
+        storyboard.sprites.len()
+    );
+    eprintln!("📄 Saved to: {}", storyboard.vtt.display());
+    Ok(())
+}
+
+#[cfg(feature = "analyze")]
 async fn cmd_analyze(
     video: &str,
     audio_only: bool,
@@ -2279,6 +8003,9 @@ async fn cmd_analyze(
         AnalyzeOutputFormat::Json => ReportFormat::Json,
         AnalyzeOutputFormat::Markdown => ReportFormat::Markdown,
         AnalyzeOutputFormat::Srt => ReportFormat::Srt,
+        AnalyzeOutputFormat::Ffmetadata => {
+            anyhow::bail!("--format ffmetadata is for --chapters")
+        }
     };
 
     let report = AnalysisReport::generate(&analysis, report_format)?;
@@ -2312,6 +8039,8 @@ async fn cmd_analyze(
     Ok(())
 }
 
+#[cfg(feature = "analyze")]
+#[allow(clippy::too_many_arguments)]
 async fn cmd_annotate(
     video: &str,
     output: &str,
@@ -2320,8 +8049,11 @@ async fn cmd_annotate(
     analysis: bool,
     style: OverlayStyleArg,
     hwaccel: bool,
+    chapters: Option<&std::path::Path>,
+    loudness: Option<LoudnessArg>,
+    extract_audio: Option<&std::path::Path>,
 ) -> Result<()> {
-    use nab::annotate::{AnalysisConfig, AnnotationPipeline, PipelineConfig};
+    use nab::annotate::{AnalysisConfig, AnnotationPipeline, LoudnessTarget, PipelineConfig};
 
     eprintln!("🎬 Annotating: {video}");
     eprintln!("   Output: {output}");
@@ -2367,10 +8099,49 @@ async fn cmd_annotate(
         }
     }
 
+    // Chapters from `nab analyze --chapters`; JSON is converted to FFmetadata first
+    if let Some(path) = chapters {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+        let ffmetadata = if text.starts_with(";FFMETADATA1") {
+            path.to_path_buf()
+        } else {
+            let parsed: Vec<nab::analyze::chapters::Chapter> = serde_json::from_str(&text)
+                .map_err(|e| anyhow::anyhow!("{} is not chapters JSON: {e}", path.display()))?;
+            std::fs::create_dir_all(&config.temp_dir)?;
+            let converted = config.temp_dir.join("chapters.ffmeta");
+            std::fs::write(&converted, nab::analyze::chapters::to_ffmetadata(&parsed))?;
+            converted
+        };
+        config.compositor = config.compositor.with_chapters(ffmetadata);
+        eprintln!("   Chapters: {}", path.display());
+    }
+
+    if let Some(loudness) = loudness {
+        let target = match loudness {
+            LoudnessArg::Ebu => LoudnessTarget::Ebu,
+            LoudnessArg::Podcast => LoudnessTarget::Podcast,
+            LoudnessArg::Streaming => LoudnessTarget::Streaming,
+        };
+        config.compositor = config.compositor.with_loudness(target);
+        eprintln!("   Loudness: {loudness:?}");
+    }
+
+    if let Some(path) = extract_audio {
+        config.compositor = config.compositor.with_audio_output(path);
+        eprintln!("   Audio: {}", path.display());
+    }
+
     eprintln!("   Style: {style:?}");
 
     // Create and run pipeline
-    let pipeline = AnnotationPipeline::new(config)?;
+    let pipeline = AnnotationPipeline::new(config)?.with_progress(Box::new(|p| {
+        let speed = p.speed.map(|s| format!(", {s:.1}x")).unwrap_or_default();
+        match p.percent() {
+            Some(percent) => eprint!("\r   Compositing: {percent:.0}%{speed}   "),
+            None => eprint!("\r   Compositing: {:.0}s{speed}   ", p.time_seconds),
+        }
+    }));
 
     let start = std::time::Instant::now();
     let result = pipeline.process_file(video, output).await?;
@@ -2381,6 +8152,9 @@ async fn cmd_annotate(
     if let Some(ref path) = result.output_path {
         eprintln!("   Output: {}", path.display());
     }
+    if let Some(path) = extract_audio {
+        eprintln!("   Audio: {}", path.display());
+    }
 
     eprintln!("   Subtitles: {} entries", result.subtitle_count);
     eprintln!("   Speakers detected: {}", result.speakers.len());
@@ -0,0 +1,1067 @@
+//! Image Metadata
+//!
+//! What `--image-meta` records about a downloaded image, for deduplication
+//! and provenance checks on scraped media:
+//! - Format and pixel size (PNG, JPEG, GIF, WebP)
+//! - EXIF tags from JPEG `APP1`, PNG `eXIf`, and WebP `EXIF` chunks, with GPS
+//!   positions as decimal degrees
+//! - XMP properties from the embedded `x:xmpmeta` packet
+//! - A 64-bit difference hash (dHash) of the picture: near-duplicates (resized,
+//!   recompressed) differ in a few bits, see [`hamming`]
+//!
+//! Pixels are decoded just far enough to hash: PNG fully, baseline JPEG down
+//! to its 1/8-scale DC image. Progressive JPEGs, GIFs, and WebPs get no hash.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::Serialize;
+use url::Url;
+
+/// Decompressed PNG data is capped here; bigger images aren't hashed
+const MAX_PIXEL_BYTES: u64 = 256 * 1024 * 1024;
+
+/// What an image says about itself
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImageMeta {
+    /// `png`, `jpeg`, `gif`, or `webp`
+    pub format: &'static str,
+    pub width: u32,
+    pub height: u32,
+    /// EXIF tags by name (`Make`, `DateTimeOriginal`, `GPSLatitude`, ...)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub exif: BTreeMap<String, String>,
+    /// XMP properties by qualified name (`dc:creator`, `xmp:CreatorTool`, ...)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub xmp: BTreeMap<String, String>,
+    /// Difference hash as 16 hex digits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phash: Option<String>,
+}
+
+impl ImageMeta {
+    /// Inspect an image; `None` if it isn't a PNG, JPEG, GIF, or WebP
+    #[must_use]
+    pub fn inspect(bytes: &[u8]) -> Option<Self> {
+        let mut meta = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            png(bytes)?
+        } else if bytes.starts_with(b"\xff\xd8\xff") {
+            jpeg(bytes)?
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Self::new("gif", u16_le(bytes, 6)?.into(), u16_le(bytes, 8)?.into())
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+            webp(bytes)?
+        } else {
+            return None;
+        };
+        meta.xmp = xmp_properties(bytes);
+        Some(meta)
+    }
+
+    fn new(format: &'static str, width: u32, height: u32) -> Self {
+        Self {
+            format,
+            width,
+            height,
+            exif: BTreeMap::new(),
+            xmp: BTreeMap::new(),
+            phash: None,
+        }
+    }
+}
+
+/// Bits that differ between two hashes from [`ImageMeta::phash`]; up to
+/// about 10 of 64 is usually the same picture
+#[must_use]
+pub fn hamming(a: &str, b: &str) -> Option<u32> {
+    let a = u64::from_str_radix(a, 16).ok()?;
+    let b = u64::from_str_radix(b, 16).ok()?;
+    Some((a ^ b).count_ones())
+}
+
+/// Absolute `http(s)` URLs of a page's `<img src>`s, in order, without repeats
+#[must_use]
+pub fn image_urls(html: &str, base: &Url) -> Vec<Url> {
+    let selector = Selector::parse("img[src]").expect("valid selector");
+    let mut urls: Vec<Url> = Vec::new();
+    for img in Html::parse_document(html).select(&selector) {
+        let Some(url) = img
+            .value()
+            .attr("src")
+            .and_then(|src| base.join(src.trim()).ok())
+        else {
+            continue;
+        };
+        if matches!(url.scheme(), "http" | "https") && !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// dHash of a grayscale image: shrink to 9×8, one bit per left-brighter-than-right pair
+fn dhash(luma: &[f32], width: usize, height: usize) -> Option<String> {
+    if width == 0 || height == 0 || luma.len() < width * height {
+        return None;
+    }
+    // Box-average the cells; tiny images repeat pixels instead
+    let cell = |cx: usize, cy: usize| {
+        let (x0, y0) = (cx * width / 9, cy * height / 8);
+        let x1 = ((cx + 1) * width / 9).max(x0 + 1);
+        let y1 = ((cy + 1) * height / 8).max(y0 + 1);
+        let mut sum = 0.0;
+        for y in y0..y1 {
+            sum += luma[y * width + x0..y * width + x1].iter().sum::<f32>();
+        }
+        sum / ((x1 - x0) * (y1 - y0)) as f32
+    };
+    let mut hash = 0u64;
+    for cy in 0..8 {
+        let row: Vec<f32> = (0..9).map(|cx| cell(cx, cy)).collect();
+        for pair in row.windows(2) {
+            hash = (hash << 1) | u64::from(pair[0] > pair[1]);
+        }
+    }
+    Some(format!("{hash:016x}"))
+}
+
+fn u16_le(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u16_be(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+// ---------------------------------------------------------------------------
+// PNG
+
+fn png(bytes: &[u8]) -> Option<ImageMeta> {
+    let mut at = 8;
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut idat = Vec::new();
+    let mut exif = BTreeMap::new();
+    while let Some(len) = u32_be(bytes, at) {
+        let (Some(kind), Some(data)) = (
+            bytes.get(at + 4..at + 8),
+            bytes.get(at + 8..at + 8 + len as usize),
+        ) else {
+            break;
+        };
+        match kind {
+            b"IHDR" if data.len() >= 13 => header = Some(data),
+            b"PLTE" => palette = data,
+            b"IDAT" => idat.extend_from_slice(data),
+            b"eXIf" => exif = tiff_tags(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        at += 12 + len as usize;
+    }
+    let header = header?;
+    let mut meta = ImageMeta::new("png", u32_be(header, 0)?, u32_be(header, 4)?);
+    meta.exif = exif;
+    meta.phash = png_luma(header, palette, &idat)
+        .and_then(|luma| dhash(&luma, meta.width as usize, meta.height as usize));
+    Some(meta)
+}
+
+/// Grayscale pixels of a non-interlaced PNG, composited onto white
+fn png_luma(header: &[u8], palette: &[u8], idat: &[u8]) -> Option<Vec<f32>> {
+    let (width, height) = (u32_be(header, 0)? as usize, u32_be(header, 4)? as usize);
+    let (depth, color, interlace) = (usize::from(header[8]), header[9], header[12]);
+    let channels = match color {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return None,
+    };
+    if interlace != 0 || !matches!(depth, 1 | 2 | 4 | 8 | 16) {
+        return None;
+    }
+    let stride = (width * channels * depth).div_ceil(8);
+    let size = (stride as u64 + 1) * height as u64;
+    if size > MAX_PIXEL_BYTES {
+        return None;
+    }
+    let mut raw = Vec::with_capacity(size as usize);
+    flate2::read::ZlibDecoder::new(idat)
+        .take(size)
+        .read_to_end(&mut raw)
+        .ok()?;
+    if raw.len() < size as usize {
+        return None;
+    }
+
+    // Undo the per-row filters in place
+    let bpp = (channels * depth).div_ceil(8);
+    let mut rows = vec![0u8; stride * height];
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (done, rest) = rows.split_at_mut(y * stride);
+        let prior = done
+            .get(done.len().saturating_sub(stride)..)
+            .filter(|_| y > 0);
+        let row = &mut rest[..stride];
+        for x in 0..stride {
+            let a = if x >= bpp { row[x - bpp] } else { 0 };
+            let b = prior.map_or(0, |p| p[x]);
+            let c = if x >= bpp {
+                prior.map_or(0, |p| p[x - bpp])
+            } else {
+                0
+            };
+            row[x] = line[x].wrapping_add(match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return None,
+            });
+        }
+    }
+
+    let max = ((1u32 << depth) - 1) as f32;
+    let sample = |row: &[u8], i: usize| -> f32 {
+        match depth {
+            16 => f32::from(u16::from_be_bytes([row[i * 2], row[i * 2 + 1]])) * 255.0 / max,
+            8 => f32::from(row[i]),
+            _ => {
+                let bit = i * depth;
+                let value = (row[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1);
+                if color == 3 {
+                    f32::from(value)
+                } else {
+                    f32::from(value) * 255.0 / max
+                }
+            }
+        }
+    };
+    let mut luma = Vec::with_capacity(width * height);
+    for row in rows.chunks_exact(stride) {
+        for x in 0..width {
+            let s = |c: usize| sample(row, x * channels + c);
+            let (gray, alpha) = match color {
+                0 => (s(0), 255.0),
+                2 => (rgb_luma(s(0), s(1), s(2)), 255.0),
+                3 => {
+                    let i = s(0) as usize * 3;
+                    let rgb = palette.get(i..i + 3)?;
+                    let rgb = rgb.iter().map(|&v| f32::from(v)).collect::<Vec<_>>();
+                    (rgb_luma(rgb[0], rgb[1], rgb[2]), 255.0)
+                }
+                4 => (s(0), s(1)),
+                _ => (rgb_luma(s(0), s(1), s(2)), s(3)),
+            };
+            luma.push((gray * alpha + 255.0 * (255.0 - alpha)) / 255.0);
+        }
+    }
+    Some(luma)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = (
+        (p - i16::from(a)).abs(),
+        (p - i16::from(b)).abs(),
+        (p - i16::from(c)).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn rgb_luma(r: f32, g: f32, b: f32) -> f32 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+// ---------------------------------------------------------------------------
+// JPEG
+
+/// Huffman table in the canonical form of JPEG Annex F.2.2.3
+#[derive(Clone, Default)]
+struct Huffman {
+    max_code: [i32; 17],
+    min_code: [i32; 17],
+    offset: [usize; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], values: &[u8]) -> Self {
+        let mut table = Self {
+            max_code: [-1; 17],
+            values: values.to_vec(),
+            ..Self::default()
+        };
+        let (mut code, mut k) = (0i32, 0usize);
+        for len in 1..=16 {
+            let n = usize::from(counts[len - 1]);
+            table.min_code[len] = code;
+            table.offset[len] = k;
+            if n > 0 {
+                table.max_code[len] = code + n as i32 - 1;
+            }
+            code = (code + n as i32) << 1;
+            k += n;
+        }
+        table
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Option<u8> {
+        let mut code = 0i32;
+        for len in 1..=16 {
+            code = (code << 1) | bits.bit() as i32;
+            if code <= self.max_code[len] {
+                let i = self.offset[len] + (code - self.min_code[len]) as usize;
+                return self.values.get(i).copied();
+            }
+        }
+        None
+    }
+}
+
+/// Entropy-coded data, with `FF 00` unstuffed; reads zeros at a marker
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    byte: u8,
+    left: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> u32 {
+        if self.left == 0 {
+            self.byte = match self.data.get(self.pos..self.pos + 2) {
+                Some([0xff, 0]) => {
+                    self.pos += 2;
+                    0xff
+                }
+                Some([0xff, _]) => 0,
+                _ => {
+                    self.pos += 1;
+                    self.data.get(self.pos - 1).copied().unwrap_or(0)
+                }
+            };
+            self.left = 8;
+        }
+        self.left -= 1;
+        u32::from(self.byte >> self.left) & 1
+    }
+
+    fn bits(&mut self, n: u8) -> u32 {
+        (0..n).fold(0, |v, _| (v << 1) | self.bit())
+    }
+
+    /// Skip to the byte after the next `RSTn` marker
+    fn restart(&mut self) {
+        self.left = 0;
+        if let Some([0xff, 0xd0..=0xd7]) = self.data.get(self.pos..self.pos + 2) {
+            self.pos += 2;
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+}
+
+fn jpeg(bytes: &[u8]) -> Option<ImageMeta> {
+    let mut meta = None;
+    let mut components: Vec<Component> = Vec::new();
+    let mut baseline = false;
+    let mut quant = [0u16; 4];
+    let mut dc_tables: [Huffman; 4] = Default::default();
+    let mut ac_tables: [Huffman; 4] = Default::default();
+    let mut restart_interval = 0;
+    let mut exif = BTreeMap::new();
+    let mut at = 2;
+    loop {
+        // Markers may be padded with extra FFs
+        while bytes.get(at) == Some(&0xff) && bytes.get(at + 1) == Some(&0xff) {
+            at += 1;
+        }
+        if bytes.get(at) != Some(&0xff) {
+            break;
+        }
+        let marker = *bytes.get(at + 1)?;
+        if marker == 0xd9 {
+            break;
+        }
+        let len = usize::from(u16_be(bytes, at + 2)?);
+        let data = bytes.get(at + 4..(at + 2 + len).max(at + 4))?;
+        at += 2 + len;
+        match marker {
+            0xe1 if data.starts_with(b"Exif\0\0") => exif = tiff_tags(&data[6..]),
+            0xdb => {
+                let mut i = 0;
+                while let Some(&pq) = data.get(i) {
+                    let wide = pq >> 4 != 0;
+                    quant[usize::from(pq & 3)] = if wide {
+                        u16_be(data, i + 1)?
+                    } else {
+                        u16::from(*data.get(i + 1)?)
+                    };
+                    i += if wide { 129 } else { 65 };
+                }
+            }
+            0xc4 => {
+                let mut i = 0;
+                while let Some(&tc) = data.get(i) {
+                    let counts = data.get(i + 1..i + 17)?;
+                    let n: usize = counts.iter().map(|&c| usize::from(c)).sum();
+                    let table = Huffman::new(counts, data.get(i + 17..i + 17 + n)?);
+                    let slot = usize::from(tc & 3);
+                    if tc >> 4 == 0 {
+                        dc_tables[slot] = table;
+                    } else {
+                        ac_tables[slot] = table;
+                    }
+                    i += 17 + n;
+                }
+            }
+            0xdd => restart_interval = usize::from(u16_be(data, 0)?),
+            // Start of frame; all but the DHT/JPG/DAC markers in C0..CF
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                let height = u16_be(data, 1)?;
+                let width = u16_be(data, 3)?;
+                meta = Some(ImageMeta::new("jpeg", width.into(), height.into()));
+                baseline = matches!(marker, 0xc0 | 0xc1) && data[0] == 8;
+                components = (0..usize::from(*data.get(5)?))
+                    .map(|i| {
+                        let c = data.get(6 + i * 3..9 + i * 3)?;
+                        Some(Component {
+                            id: c[0],
+                            h: usize::from(c[1] >> 4).max(1),
+                            v: usize::from(c[1] & 15).max(1),
+                            quant: usize::from(c[2] & 3),
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+            }
+            0xda => {
+                let mut meta = meta?;
+                meta.exif = exif;
+                if baseline {
+                    let scan = Scan {
+                        components: &components,
+                        header: data,
+                        quant: &quant,
+                        dc_tables: &dc_tables,
+                        ac_tables: &ac_tables,
+                        restart_interval,
+                    };
+                    meta.phash = scan.luma(meta.width as usize, meta.height as usize, &bytes[at..]);
+                }
+                return Some(meta);
+            }
+            _ => {}
+        }
+    }
+    meta.map(|mut meta| {
+        meta.exif = exif;
+        meta
+    })
+}
+
+/// The first scan of a baseline JPEG
+struct Scan<'a> {
+    components: &'a [Component],
+    header: &'a [u8],
+    quant: &'a [u16; 4],
+    dc_tables: &'a [Huffman; 4],
+    ac_tables: &'a [Huffman; 4],
+    restart_interval: usize,
+}
+
+impl Scan<'_> {
+    /// dHash of the luma DC image (one value per 8×8 block)
+    fn luma(&self, width: usize, height: usize, data: &[u8]) -> Option<String> {
+        let luma = self.components.first()?;
+        let h_max = self.components.iter().map(|c| c.h).max()?;
+        let v_max = self.components.iter().map(|c| c.v).max()?;
+        // Components in this scan with their DC/AC table slots
+        let scanned: Vec<(usize, usize, usize)> = (0..usize::from(*self.header.first()?))
+            .map(|i| {
+                let id = *self.header.get(1 + i * 2)?;
+                let tables = *self.header.get(2 + i * 2)?;
+                let index = self.components.iter().position(|c| c.id == id)?;
+                Some((index, usize::from(tables >> 4), usize::from(tables & 3)))
+            })
+            .collect::<Option<_>>()?;
+        if !scanned.iter().any(|&(index, ..)| index == 0) {
+            return None;
+        }
+
+        // Blocks of the luma plane that cover the picture
+        let plane_w = (width * luma.h).div_ceil(h_max).div_ceil(8);
+        let plane_h = (height * luma.v).div_ceil(v_max).div_ceil(8);
+        let (mcus_x, mcus_y, layout) = if scanned.len() == 1 {
+            (plane_w, plane_h, vec![(0, 1, 1)])
+        } else {
+            let layout = scanned.iter().map(|&(i, ..)| {
+                let c = self.components[i];
+                (i, c.h, c.v)
+            });
+            (
+                width.div_ceil(8 * h_max),
+                height.div_ceil(8 * v_max),
+                layout.collect(),
+            )
+        };
+        let &(_, luma_h, luma_v) = layout.iter().find(|&&(index, ..)| index == 0)?;
+        let (grid_w, grid_h) = (mcus_x * luma_h, mcus_y * luma_v);
+        if grid_w.saturating_mul(grid_h) > 1 << 24 {
+            return None;
+        }
+        let mut grid = vec![0f32; grid_w * grid_h];
+        let scale = f32::from(self.quant[luma.quant]) / 8.0;
+
+        let mut bits = Bits {
+            data,
+            pos: 0,
+            byte: 0,
+            left: 0,
+        };
+        let mut predictors = vec![0i32; self.components.len()];
+        for mcu in 0..mcus_x * mcus_y {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+                bits.restart();
+                predictors.fill(0);
+            }
+            let (mx, my) = (mcu % mcus_x, mcu / mcus_x);
+            for (&(index, dc, ac), &(_, h, v)) in scanned.iter().zip(&layout) {
+                for block in 0..h * v {
+                    let value = self.block(&mut bits, dc, ac)?;
+                    predictors[index] += value;
+                    if index == 0 {
+                        let x = mx * h + block % h;
+                        let y = my * v + block / h;
+                        grid[y * grid_w + x] = predictors[0] as f32 * scale + 128.0;
+                    }
+                }
+            }
+        }
+
+        // Drop the padding blocks past the picture's edge
+        let (w, h) = (plane_w.min(grid_w), plane_h.min(grid_h));
+        let cropped: Vec<f32> = grid
+            .chunks_exact(grid_w)
+            .take(h)
+            .flat_map(|row| row[..w].iter().copied())
+            .collect();
+        dhash(&cropped, w, h)
+    }
+
+    /// Decode one block, returning its DC difference and skipping the AC terms
+    fn block(&self, bits: &mut Bits, dc: usize, ac: usize) -> Option<i32> {
+        let size = self.dc_tables[dc].decode(bits)?;
+        let diff = extend(bits.bits(size), size);
+        let mut k = 1;
+        while k < 64 {
+            let rs = self.ac_tables[ac].decode(bits)?;
+            let (run, size) = (rs >> 4, rs & 15);
+            if size == 0 {
+                if run != 15 {
+                    break;
+                }
+                k += 16;
+                continue;
+            }
+            bits.bits(size);
+            k += usize::from(run) + 1;
+        }
+        Some(diff)
+    }
+}
+
+/// Sign-extend a JPEG magnitude category value
+fn extend(value: u32, size: u8) -> i32 {
+    if size == 0 {
+        return 0;
+    }
+    let value = value as i32;
+    if value < 1 << (size - 1) {
+        value - (1 << size) + 1
+    } else {
+        value
+    }
+}
+
+// ---------------------------------------------------------------------------
+// WebP
+
+fn webp(bytes: &[u8]) -> Option<ImageMeta> {
+    let mut meta = None;
+    let mut exif = BTreeMap::new();
+    let mut at = 12;
+    while let Some(kind) = bytes.get(at..at + 4) {
+        let len = u32::from_le_bytes(bytes.get(at + 4..at + 8)?.try_into().ok()?) as usize;
+        let data = bytes.get(at + 8..(at + 8 + len).min(bytes.len()))?;
+        match kind {
+            b"VP8X" if data.len() >= 10 => {
+                let size =
+                    |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], 0]) + 1;
+                meta = Some(ImageMeta::new("webp", size(4), size(7)));
+            }
+            b"VP8 " if meta.is_none() && data.len() >= 10 => {
+                let width = u16_le(data, 6)? & 0x3fff;
+                let height = u16_le(data, 8)? & 0x3fff;
+                meta = Some(ImageMeta::new("webp", width.into(), height.into()));
+            }
+            b"VP8L" if meta.is_none() && data.len() >= 5 => {
+                let packed = u32::from_le_bytes(data[1..5].try_into().ok()?);
+                let (width, height) = ((packed & 0x3fff) + 1, ((packed >> 14) & 0x3fff) + 1);
+                meta = Some(ImageMeta::new("webp", width, height));
+            }
+            b"EXIF" => exif = tiff_tags(data.strip_prefix(b"Exif\0\0").unwrap_or(data)),
+            _ => {}
+        }
+        at += 8 + len + len % 2;
+    }
+    meta.map(|mut meta| {
+        meta.exif = exif;
+        meta
+    })
+}
+
+// ---------------------------------------------------------------------------
+// EXIF
+
+/// Tags worth keeping, by IFD: 0 = main, 1 = Exif, 2 = GPS (handled apart)
+const TAGS: &[(u8, u16, &str)] = &[
+    (0, 0x010e, "ImageDescription"),
+    (0, 0x010f, "Make"),
+    (0, 0x0110, "Model"),
+    (0, 0x0112, "Orientation"),
+    (0, 0x0131, "Software"),
+    (0, 0x0132, "DateTime"),
+    (0, 0x013b, "Artist"),
+    (0, 0x8298, "Copyright"),
+    (1, 0x829a, "ExposureTime"),
+    (1, 0x829d, "FNumber"),
+    (1, 0x8827, "ISO"),
+    (1, 0x9003, "DateTimeOriginal"),
+    (1, 0x9004, "DateTimeDigitized"),
+    (1, 0x920a, "FocalLength"),
+    (1, 0xa420, "ImageUniqueID"),
+    (1, 0xa430, "CameraOwnerName"),
+    (1, 0xa431, "BodySerialNumber"),
+    (1, 0xa434, "LensModel"),
+];
+
+/// A TIFF structure (the EXIF payload) with its byte order
+struct Tiff<'a> {
+    data: &'a [u8],
+    little: bool,
+}
+
+/// One IFD entry's value
+enum Value {
+    Text(String),
+    Numbers(Vec<f64>),
+}
+
+impl Tiff<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let b: [u8; 2] = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let b: [u8; 4] = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    /// Entries of the IFD at `offset` as (tag, value)
+    fn ifd(&self, offset: usize) -> Vec<(u16, Value)> {
+        let count = self.u16(offset).unwrap_or(0);
+        (0..usize::from(count))
+            .filter_map(|i| {
+                let entry = offset + 2 + i * 12;
+                Some((self.u16(entry)?, self.value(entry)?))
+            })
+            .collect()
+    }
+
+    fn value(&self, entry: usize) -> Option<Value> {
+        let kind = self.u16(entry + 2)?;
+        let count = self.u32(entry + 4)? as usize;
+        let size = match kind {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 => 4,
+            5 | 10 => 8,
+            _ => return None,
+        };
+        let len = count.checked_mul(size)?;
+        let at = if len <= 4 {
+            entry + 8
+        } else {
+            self.u32(entry + 8)? as usize
+        };
+        let raw = self.data.get(at..at.checked_add(len)?)?;
+        Some(match kind {
+            2 => Value::Text(
+                String::from_utf8_lossy(raw)
+                    .trim_end_matches('\0')
+                    .trim()
+                    .to_string(),
+            ),
+            1 | 7 => Value::Numbers(raw.iter().map(|&b| f64::from(b)).collect()),
+            6 => Value::Numbers(raw.iter().map(|&b| f64::from(b as i8)).collect()),
+            3 | 8 => Value::Numbers(
+                (0..count)
+                    .map(|i| {
+                        let v = self.u16(at + i * 2).unwrap_or(0);
+                        if kind == 8 {
+                            f64::from(v as i16)
+                        } else {
+                            f64::from(v)
+                        }
+                    })
+                    .collect(),
+            ),
+            4 | 9 => Value::Numbers(
+                (0..count)
+                    .map(|i| {
+                        let v = self.u32(at + i * 4).unwrap_or(0);
+                        if kind == 9 {
+                            f64::from(v as i32)
+                        } else {
+                            f64::from(v)
+                        }
+                    })
+                    .collect(),
+            ),
+            _ => Value::Numbers(
+                (0..count)
+                    .map(|i| {
+                        let (n, d) = (self.u32(at + i * 8)?, self.u32(at + i * 8 + 4)?);
+                        let (n, d) = if kind == 10 {
+                            (f64::from(n as i32), f64::from(d as i32))
+                        } else {
+                            (f64::from(n), f64::from(d))
+                        };
+                        Some(if d == 0.0 { 0.0 } else { n / d })
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+        })
+    }
+}
+
+/// Named tags of an EXIF payload (a TIFF header and its IFDs)
+fn tiff_tags(data: &[u8]) -> BTreeMap<String, String> {
+    let little = match data.get(..4) {
+        Some(b"II*\0") => true,
+        Some(b"MM\0*") => false,
+        _ => return BTreeMap::new(),
+    };
+    let tiff = Tiff { data, little };
+    let mut tags = BTreeMap::new();
+    let Some(ifd0) = tiff.u32(4) else {
+        return tags;
+    };
+    let main = tiff.ifd(ifd0 as usize);
+    let pointer = |tag: u16| {
+        main.iter().find_map(|(t, v)| match v {
+            Value::Numbers(n) if *t == tag => n.first().map(|&o| o as usize),
+            _ => None,
+        })
+    };
+    let (exif, gps) = (pointer(0x8769), pointer(0x8825));
+
+    let exif = exif.map(|offset| tiff.ifd(offset)).unwrap_or_default();
+    for (ifd, entries) in [(0, &main), (1, &exif)] {
+        for (tag, value) in entries {
+            let Some(&(_, _, name)) = TAGS.iter().find(|(i, t, _)| *i == ifd && t == tag) else {
+                continue;
+            };
+            let text = match value {
+                Value::Text(text) if !text.is_empty() => text.clone(),
+                Value::Numbers(n)
+                    if !n.is_empty() && name == "ExposureTime" && n[0] < 1.0 && n[0] > 0.0 =>
+                {
+                    format!("1/{}", (1.0 / n[0]).round())
+                }
+                Value::Numbers(n) if !n.is_empty() => number(n[0]),
+                _ => continue,
+            };
+            tags.insert(name.to_string(), text);
+        }
+    }
+
+    if let Some(offset) = gps {
+        let entries = tiff.ifd(offset);
+        let get = |tag: u16| entries.iter().find(|(t, _)| *t == tag).map(|(_, v)| v);
+        let coordinate = |reference: u16, value: u16, negative: &str| {
+            let Some(Value::Numbers(dms)) = get(value) else {
+                return None;
+            };
+            let degrees = dms
+                .iter()
+                .zip([1.0, 60.0, 3600.0])
+                .map(|(v, d)| v / d)
+                .sum::<f64>();
+            let sign = match get(reference) {
+                Some(Value::Text(r)) if r == negative => -1.0,
+                _ => 1.0,
+            };
+            Some(number((sign * degrees * 1e6).round() / 1e6))
+        };
+        if let Some(lat) = coordinate(1, 2, "S") {
+            tags.insert("GPSLatitude".to_string(), lat);
+        }
+        if let Some(lon) = coordinate(3, 4, "W") {
+            tags.insert("GPSLongitude".to_string(), lon);
+        }
+        if let Some(Value::Numbers(alt)) = get(6) {
+            let below = matches!(get(5), Some(Value::Numbers(r)) if r.first() == Some(&1.0));
+            if let Some(&alt) = alt.first() {
+                tags.insert(
+                    "GPSAltitude".to_string(),
+                    number(if below { -alt } else { alt }),
+                );
+            }
+        }
+    }
+    tags
+}
+
+/// Shortest decimal for a tag value (`2.8`, `250`)
+fn number(value: f64) -> String {
+    let text = format!("{value:.6}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// ---------------------------------------------------------------------------
+// XMP
+
+static XMP_ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\s([A-Za-z][\w.-]*:[\w.-]+)="([^"]*)""#).unwrap());
+static XMP_LIST: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?s)<([A-Za-z][\w.-]*:[\w.-]+)[^>]*>\s*<rdf:(?:Alt|Seq|Bag)>(.*?)</rdf:(?:Alt|Seq|Bag)>",
+    )
+    .unwrap()
+});
+static XMP_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<rdf:li[^>]*>([^<]*)</rdf:li>").unwrap());
+static XMP_SIMPLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<([A-Za-z][\w.-]*:[\w.-]+)(?:\s[^>]*)?>([^<]+)</([A-Za-z][\w.-]*:[\w.-]+)>")
+        .unwrap()
+});
+
+/// Properties of the first XMP packet: `rdf:Description` attributes, simple
+/// elements, and lists (items joined with `; `)
+fn xmp_properties(bytes: &[u8]) -> BTreeMap<String, String> {
+    let mut properties = BTreeMap::new();
+    let Some(start) = find(bytes, b"<x:xmpmeta") else {
+        return properties;
+    };
+    let Some(len) = find(&bytes[start..], b"</x:xmpmeta>") else {
+        return properties;
+    };
+    let packet = String::from_utf8_lossy(&bytes[start..start + len]);
+    let keep = |name: &str| {
+        !["xmlns:", "rdf:", "x:", "xml:"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    };
+    let mut add = |name: &str, value: &str| {
+        let value = crate::content::unescape(value.trim());
+        if keep(name) && !value.is_empty() {
+            properties.entry(name.to_string()).or_insert(value);
+        }
+    };
+
+    for description in packet.split("<rdf:Description").skip(1) {
+        let tag = description.split('>').next().unwrap_or_default();
+        for caps in XMP_ATTRIBUTE.captures_iter(tag) {
+            add(&caps[1], &caps[2]);
+        }
+    }
+    for caps in XMP_LIST.captures_iter(&packet) {
+        let items: Vec<&str> = XMP_ITEM
+            .captures_iter(&caps[2])
+            .filter_map(|item| Some(item.get(1)?.as_str().trim()))
+            .filter(|item| !item.is_empty())
+            .collect();
+        add(&caps[1], &items.join("; "));
+    }
+    for caps in XMP_SIMPLE.captures_iter(&packet) {
+        if caps[1] == caps[3] {
+            add(&caps[1], &caps[2]);
+        }
+    }
+    properties
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// An 8-bit grayscale PNG with the given rows and chunks before IDAT
+    fn png_file(rows: &[Vec<u8>], extra: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let chunk = |out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]| {
+            out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            out.extend_from_slice(kind);
+            out.extend_from_slice(data);
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(kind);
+            crc.update(data);
+            out.extend_from_slice(&crc.finalize().to_be_bytes());
+        };
+        let mut header = Vec::new();
+        header.extend_from_slice(&(rows[0].len() as u32).to_be_bytes());
+        header.extend_from_slice(&(rows.len() as u32).to_be_bytes());
+        header.extend_from_slice(&[8, 0, 0, 0, 0]);
+        // Alternate Sub and Up filters so unfiltering is exercised
+        let mut raw = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            if y % 2 == 0 {
+                raw.push(1);
+                raw.extend(
+                    row.iter()
+                        .enumerate()
+                        .map(|(x, &v)| v.wrapping_sub(if x > 0 { row[x - 1] } else { 0 })),
+                );
+            } else {
+                raw.push(2);
+                raw.extend(
+                    row.iter()
+                        .zip(&rows[y - 1])
+                        .map(|(&v, &up)| v.wrapping_sub(up)),
+                );
+            }
+        }
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(&raw).unwrap();
+
+        let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut out, b"IHDR", &header);
+        for (kind, data) in extra {
+            chunk(&mut out, kind, data);
+        }
+        chunk(&mut out, b"IDAT", &zlib.finish().unwrap());
+        chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    #[test]
+    fn test_png_exif_xmp_and_hash() {
+        // Little-endian TIFF: IFD0 with Make and a GPS pointer; GPS south latitude
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        let entry = |tag: u16, kind: u16, count: u32, value: u32| {
+            [
+                tag.to_le_bytes().as_slice(),
+                &kind.to_le_bytes(),
+                &count.to_le_bytes(),
+                &value.to_le_bytes(),
+            ]
+            .concat()
+        };
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        tiff.extend(entry(0x010f, 2, 4, u32::from_le_bytes(*b"Nab\0")));
+        tiff.extend(entry(0x8825, 4, 1, 38));
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        tiff.extend(entry(1, 2, 2, u32::from_le_bytes(*b"S\0\0\0")));
+        tiff.extend(entry(2, 5, 3, 68));
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        for (n, d) in [(33u32, 1u32), (51, 1), (2160, 100)] {
+            tiff.extend_from_slice(&n.to_le_bytes());
+            tiff.extend_from_slice(&d.to_le_bytes());
+        }
+        let xmp = b"<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF><rdf:Description rdf:about=\"\" \
+                    xmp:CreatorTool=\"Tests\"><dc:creator><rdf:Seq><rdf:li>Ann</rdf:li>\
+                    <rdf:li>Bo</rdf:li></rdf:Seq></dc:creator><photoshop:City>Turku</photoshop:City>\
+                    </rdf:Description></rdf:RDF></x:xmpmeta>";
+        let mut itxt = b"XML:com.adobe.xmp\0\0\0\0\0".to_vec();
+        itxt.extend_from_slice(xmp);
+
+        // Brightness falls left to right: every bit of the hash is set
+        let rows: Vec<Vec<u8>> = (0..16)
+            .map(|y| (0..18).map(|x| 250 - x * 12 - y).collect())
+            .collect();
+        let meta =
+            ImageMeta::inspect(&png_file(&rows, &[(b"eXIf", &tiff), (b"iTXt", &itxt)])).unwrap();
+        assert_eq!((meta.format, meta.width, meta.height), ("png", 18, 16));
+        assert_eq!(meta.exif["Make"], "Nab");
+        assert_eq!(meta.exif["GPSLatitude"], "-33.856");
+        assert_eq!(meta.xmp["xmp:CreatorTool"], "Tests");
+        assert_eq!(meta.xmp["dc:creator"], "Ann; Bo");
+        assert_eq!(meta.xmp["photoshop:City"], "Turku");
+        assert_eq!(meta.phash.as_deref(), Some("ffffffffffffffff"));
+
+        // The same picture, brighter and at a different size, hashes the same
+        let bigger: Vec<Vec<u8>> = (0..32)
+            .map(|y| (0..36).map(|x| 255 - x * 6 - y / 2).collect())
+            .collect();
+        let other = ImageMeta::inspect(&png_file(&bigger, &[])).unwrap();
+        assert!(other.exif.is_empty() && other.xmp.is_empty());
+        assert_eq!(
+            hamming(
+                meta.phash.as_deref().unwrap(),
+                other.phash.as_deref().unwrap()
+            ),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_dimensions_only() {
+        let gif = b"GIF89a\x40\x01\xf0\x00\x80\x00\x00";
+        let meta = ImageMeta::inspect(gif).unwrap();
+        assert_eq!(
+            (meta.format, meta.width, meta.height, meta.phash),
+            ("gif", 320, 240, None)
+        );
+        assert!(ImageMeta::inspect(b"<svg/>").is_none());
+
+        let base = Url::parse("https://example.com/gallery/").unwrap();
+        let html = r#"<img src="a.jpg"><img src="/b.png"><img src="a.jpg"><img src="data:image/gif;base64,R0lG">"#;
+        let urls: Vec<String> = image_urls(html, &base).iter().map(Url::to_string).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/gallery/a.jpg",
+                "https://example.com/b.png"
+            ]
+        );
+        assert_eq!(hamming("00000000000000ff", "000000000000000f"), Some(4));
+    }
+}
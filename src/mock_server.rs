@@ -0,0 +1,579 @@
+//! Mock HTTP Server (`nab mock-server`)
+//!
+//! Serves canned responses from a fixtures directory, so the CLI's network
+//! paths can be tested without real sites and demoed offline. Built with the
+//! `mock-server` feature; the command is hidden from `--help`.
+//!
+//! Files are served by path (`/` and directories map to `index.html`). An
+//! optional `mock.json` in the directory adds routes on top:
+//!
+//! ```json
+//! {
+//!   "latency_ms": 10,
+//!   "routes": {
+//!     "/old": { "status": 301, "headers": { "Location": "/" } },
+//!     "/slow": { "file": "index.html", "latency_ms": 800 },
+//!     "/gz": { "file": "index.html", "encoding": "gzip" },
+//!     "/members": {
+//!       "file": "article.html",
+//!       "challenge": { "file": "challenge.html", "cookie": "passed=42" }
+//!     },
+//!     "/intranet": {
+//!       "file": "article.html",
+//!       "auth": { "scheme": "digest", "user": "ada", "password": "pa" }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! - `encoding: gzip` compresses the body when the request accepts gzip
+//! - `challenge` answers with the challenge page (503 unless `status` is
+//!   given) until a request carries `cookie`
+//! - `auth` answers 401 with a `Basic` or `Digest` challenge until a request
+//!   logs in as `user`
+//!
+//! No TLS: HTTP/1.1 (one request per connection) and HTTP/2 with prior
+//! knowledge (h2c), which is what nab's client speaks to `http://` URLs.
+//! [`MockServer::http1_only`] refuses HTTP/2, like many legacy servers.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write as _;
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::debug;
+
+use crate::http_auth::{self, parse_challenges, UserCredentials};
+
+/// Route file in the fixtures directory
+pub const CONFIG_FILE: &str = "mock.json";
+
+/// Requests with larger heads are refused
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+/// Nonce of every Digest challenge
+const DIGEST_NONCE: &str = "6d6f636b2d6e6f6e6365";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct MockConfig {
+    #[serde(default)]
+    latency_ms: u64,
+    #[serde(default)]
+    routes: HashMap<String, Route>,
+}
+
+/// A canned response in `mock.json`
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Route {
+    status: Option<u16>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Body from a fixture file
+    file: Option<String>,
+    /// Inline body
+    body: Option<String>,
+    latency_ms: Option<u64>,
+    encoding: Option<String>,
+    challenge: Option<ChallengeRoute>,
+    auth: Option<AuthRoute>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChallengeRoute {
+    file: String,
+    /// `name=value` the challenge sets once passed
+    cookie: String,
+    status: Option<u16>,
+}
+
+/// Login a route asks for (`scheme`: `basic` or `digest`)
+#[derive(Debug, Clone, Deserialize)]
+struct AuthRoute {
+    scheme: String,
+    user: String,
+    password: String,
+}
+
+impl AuthRoute {
+    /// `WWW-Authenticate` challenge of the route
+    fn challenge(&self) -> String {
+        if self.scheme.eq_ignore_ascii_case("digest") {
+            format!(r#"Digest realm="mock", qop="auth", nonce="{DIGEST_NONCE}""#)
+        } else {
+            r#"Basic realm="mock""#.to_string()
+        }
+    }
+
+    /// Whether `request` logs in as the route's user
+    fn accepts(&self, request: &MockRequest) -> bool {
+        let Some(authorization) = request.header("authorization") else {
+            return false;
+        };
+        let credentials = UserCredentials {
+            domain: None,
+            username: self.user.clone(),
+            password: self.password.clone(),
+        };
+        if self.scheme.eq_ignore_ascii_case("digest") {
+            let challenge = parse_challenges(&self.challenge()).remove(0);
+            http_auth::digest_matches(&challenge, &credentials, &request.method, authorization)
+        } else {
+            let pair = format!("{}:{}", self.user, self.password);
+            authorization == format!("Basic {}", http_auth::base64_encode(pair.as_bytes()))
+        }
+    }
+}
+
+/// Request line and headers of a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockRequest {
+    pub method: String,
+    /// Path without the query
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl MockRequest {
+    fn from_http<T>(request: &http::Request<T>) -> Self {
+        Self {
+            method: request.method().to_string(),
+            path: request.uri().path().to_string(),
+            headers: request
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Parse a request head (up to the blank line)
+    pub fn parse(head: &str) -> Result<Self> {
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+        let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+            anyhow::bail!("Malformed request line");
+        };
+        let path = target.split(['?', '#']).next().unwrap_or("/").to_string();
+        let headers = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        Ok(Self {
+            method: method.to_string(),
+            path,
+            headers,
+        })
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn has_cookie(&self, cookie: &str) -> bool {
+        self.headers
+            .iter()
+            .filter(|(name, _)| name == "cookie")
+            .flat_map(|(_, value)| value.split(';'))
+            .any(|pair| pair.trim() == cookie)
+    }
+}
+
+/// A response to send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub latency: Duration,
+}
+
+impl MockResponse {
+    fn new(status: u16, content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body,
+            latency: Duration::ZERO,
+        }
+    }
+
+    fn not_found() -> Self {
+        Self::new(404, "text/plain", b"Not Found".to_vec())
+    }
+
+    /// Status line, headers, and (unless `head_only`) body
+    fn to_bytes(&self, head_only: bool) -> Vec<u8> {
+        let reason = reqwest::StatusCode::from_u16(self.status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .unwrap_or("Unknown");
+        let mut out = format!("HTTP/1.1 {} {reason}\r\n", self.status);
+        for (name, value) in &self.headers {
+            out.push_str(&format!("{name}: {value}\r\n"));
+        }
+        out.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        let mut bytes = out.into_bytes();
+        if !head_only {
+            bytes.extend_from_slice(&self.body);
+        }
+        bytes
+    }
+}
+
+/// A fixtures directory and its routes
+#[derive(Debug, Clone)]
+pub struct Fixtures {
+    dir: PathBuf,
+    config: MockConfig,
+}
+
+impl Fixtures {
+    /// Load `dir` and its `mock.json`, if any
+    pub fn load(dir: &Path) -> Result<Self> {
+        if !dir.is_dir() {
+            anyhow::bail!("Fixtures directory {} not found", dir.display());
+        }
+        let config_path = dir.join(CONFIG_FILE);
+        let config = if config_path.exists() {
+            let data = std::fs::read(&config_path)?;
+            serde_json::from_slice(&data)
+                .with_context(|| format!("Invalid {}", config_path.display()))?
+        } else {
+            MockConfig::default()
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            config,
+        })
+    }
+
+    /// Response to `request`
+    #[must_use]
+    pub fn respond(&self, request: &MockRequest) -> MockResponse {
+        let default_latency = Duration::from_millis(self.config.latency_ms);
+        let Some(route) = self.config.routes.get(&request.path) else {
+            let mut response = self
+                .file(&request.path)
+                .unwrap_or_else(MockResponse::not_found);
+            response.latency = default_latency;
+            return response;
+        };
+
+        let mut response = match (&route.auth, &route.challenge) {
+            (Some(auth), _) if !auth.accepts(request) => {
+                let mut response = MockResponse::new(401, "text/plain", b"Unauthorized".to_vec());
+                response
+                    .headers
+                    .push(("WWW-Authenticate".to_string(), auth.challenge()));
+                response
+            }
+            (_, Some(challenge)) if !request.has_cookie(&challenge.cookie) => self
+                .file(&challenge.file)
+                .map(|mut page| {
+                    page.status = challenge.status.unwrap_or(503);
+                    page
+                })
+                .unwrap_or_else(MockResponse::not_found),
+            _ => {
+                let mut response = match (&route.file, &route.body) {
+                    (Some(file), _) => self.file(file).unwrap_or_else(MockResponse::not_found),
+                    (None, Some(body)) => {
+                        MockResponse::new(200, "text/html; charset=utf-8", body.clone().into())
+                    }
+                    (None, None) => MockResponse::new(200, "text/plain", Vec::new()),
+                };
+                if let Some(status) = route.status {
+                    response.status = status;
+                }
+                for (name, value) in &route.headers {
+                    response
+                        .headers
+                        .retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+                    response.headers.push((name.clone(), value.clone()));
+                }
+                response
+            }
+        };
+
+        let accepts_gzip = request
+            .header("accept-encoding")
+            .is_some_and(|v| v.to_ascii_lowercase().contains("gzip"));
+        if route.encoding.as_deref() == Some("gzip") && accepts_gzip {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            if encoder.write_all(&response.body).is_ok() {
+                if let Ok(body) = encoder.finish() {
+                    response.body = body;
+                    response
+                        .headers
+                        .push(("Content-Encoding".to_string(), "gzip".to_string()));
+                    response
+                        .headers
+                        .push(("Vary".to_string(), "Accept-Encoding".to_string()));
+                }
+            }
+        }
+        response.latency = route
+            .latency_ms
+            .map_or(default_latency, Duration::from_millis);
+        response
+    }
+
+    /// Fixture file at `path` (relative to the directory), never outside it
+    fn file(&self, path: &str) -> Option<MockResponse> {
+        let relative = Path::new(path.trim_start_matches('/'));
+        if relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return None;
+        }
+        let mut full = self.dir.join(relative);
+        if full.is_dir() {
+            full = full.join("index.html");
+        }
+        let body = std::fs::read(&full).ok()?;
+        Some(MockResponse::new(200, content_type(&full), body))
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+    {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "json" => "application/json",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "m3u8" => "application/vnd.apple.mpegurl",
+        "ts" => "video/mp2t",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Server for a fixtures directory
+pub struct MockServer {
+    listener: TcpListener,
+    fixtures: Arc<Fixtures>,
+    http1_only: bool,
+}
+
+impl MockServer {
+    /// Listen on `addr` (port 0 picks a free port)
+    pub async fn bind(fixtures: Fixtures, addr: &str) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to listen on {addr}"))?;
+        Ok(Self {
+            listener,
+            fixtures: Arc::new(fixtures),
+            http1_only: false,
+        })
+    }
+
+    /// Answer HTTP/2 connections with `400 Bad Request`
+    #[must_use]
+    pub fn http1_only(mut self) -> Self {
+        self.http1_only = true;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve until the process ends
+    pub async fn run(self) -> Result<()> {
+        loop {
+            let (socket, peer) = self.listener.accept().await?;
+            let fixtures = Arc::clone(&self.fixtures);
+            let http1_only = self.http1_only;
+            tokio::spawn(async move {
+                if let Err(e) = serve(socket, &fixtures, http1_only).await {
+                    debug!("Mock request from {peer} failed: {e:#}");
+                }
+            });
+        }
+    }
+}
+
+async fn serve(mut socket: TcpStream, fixtures: &Arc<Fixtures>, http1_only: bool) -> Result<()> {
+    // HTTP/2 connections open with `PRI * HTTP/2.0`
+    let mut preface = [0u8; 3];
+    let mut peeked = 0;
+    while peeked < preface.len() {
+        peeked = socket.peek(&mut preface).await?;
+        if peeked == 0 {
+            return Ok(());
+        }
+    }
+    if &preface == b"PRI" && http1_only {
+        let refusal = MockResponse::new(400, "text/plain", Vec::new());
+        socket.write_all(&refusal.to_bytes(false)).await?;
+        socket.shutdown().await?;
+        return Ok(());
+    }
+    if &preface == b"PRI" {
+        return serve_h2(socket, fixtures).await;
+    }
+
+    let mut head = Vec::new();
+    let mut buf = [0u8; 4096];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = socket.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        head.extend_from_slice(&buf[..n]);
+        if head.len() > MAX_HEAD_BYTES {
+            anyhow::bail!("Request head too large");
+        }
+    }
+    let request = MockRequest::parse(&String::from_utf8_lossy(&head))?;
+    let response = fixtures.respond(&request);
+    debug!("{} {} -> {}", request.method, request.path, response.status);
+    tokio::time::sleep(response.latency).await;
+    socket
+        .write_all(&response.to_bytes(request.method == "HEAD"))
+        .await?;
+    socket.shutdown().await?;
+    Ok(())
+}
+
+async fn serve_h2(socket: TcpStream, fixtures: &Arc<Fixtures>) -> Result<()> {
+    let mut connection = h2::server::handshake(socket).await?;
+    while let Some(stream) = connection.accept().await {
+        let (request, mut respond) = stream?;
+        let fixtures = Arc::clone(fixtures);
+        tokio::spawn(async move {
+            let request = MockRequest::from_http(&request);
+            let response = fixtures.respond(&request);
+            debug!(
+                "{} {} -> {} (h2)",
+                request.method, request.path, response.status
+            );
+            tokio::time::sleep(response.latency).await;
+
+            let mut head = http::Response::builder().status(response.status);
+            for (name, value) in &response.headers {
+                head = head.header(name.as_str(), value.as_str());
+            }
+            let head = head.header("content-length", response.body.len());
+            let head_only = request.method == "HEAD" || response.body.is_empty();
+            let Ok(head) = head.body(()) else {
+                return;
+            };
+            if let Ok(mut send) = respond.send_response(head, head_only) {
+                if !head_only {
+                    let _ = send.send_data(response.body.into(), true);
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> (PathBuf, Fixtures) {
+        let dir = std::env::temp_dir().join(format!("nab-mock-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        std::fs::write(dir.join("index.html"), "<h1>Home</h1>").unwrap();
+        std::fs::write(dir.join("docs/index.html"), "<h1>Docs</h1>").unwrap();
+        std::fs::write(
+            dir.join("wall.html"),
+            "<script>document.cookie='ok=1'</script>",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(CONFIG_FILE),
+            r#"{"latency_ms": 5, "routes": {
+                "/old": {"status": 301, "headers": {"Location": "/"}},
+                "/gz": {"file": "index.html", "encoding": "gzip", "latency_ms": 50},
+                "/members": {"body": "<p>Members</p>", "challenge": {"file": "wall.html", "cookie": "ok=1"}},
+                "/staff": {"body": "<p>Staff</p>", "auth": {"scheme": "basic", "user": "ada", "password": "pa"}}
+            }}"#,
+        )
+        .unwrap();
+        let fixtures = Fixtures::load(&dir).unwrap();
+        (dir, fixtures)
+    }
+
+    fn get(path: &str, extra: &str) -> MockRequest {
+        MockRequest::parse(&format!("GET {path} HTTP/1.1\r\nHost: x\r\n{extra}\r\n")).unwrap()
+    }
+
+    #[test]
+    fn test_respond() {
+        let (dir, fixtures) = fixtures();
+        let home = fixtures.respond(&get("/?q=1", ""));
+        assert_eq!(
+            (home.status, home.body.as_slice()),
+            (200, &b"<h1>Home</h1>"[..])
+        );
+        assert_eq!(home.latency, Duration::from_millis(5));
+        assert_eq!(fixtures.respond(&get("/docs", "")).body, b"<h1>Docs</h1>");
+        assert_eq!(fixtures.respond(&get("/missing", "")).status, 404);
+        assert_eq!(fixtures.respond(&get("/../etc/passwd", "")).status, 404);
+
+        let moved = fixtures.respond(&get("/old", ""));
+        assert_eq!(moved.status, 301);
+        assert!(moved.headers.contains(&("Location".into(), "/".into())));
+
+        let plain = fixtures.respond(&get("/gz", ""));
+        assert_eq!(plain.body, b"<h1>Home</h1>");
+        let gzipped = fixtures.respond(&get("/gz", "Accept-Encoding: gzip, br\r\n"));
+        assert!(gzipped
+            .headers
+            .contains(&("Content-Encoding".into(), "gzip".into())));
+        assert_eq!(&gzipped.body[..2], &[0x1f, 0x8b]);
+        assert_eq!(gzipped.latency, Duration::from_millis(50));
+
+        let wall = fixtures.respond(&get("/members", "Cookie: a=b\r\n"));
+        assert_eq!(wall.status, 503);
+        let passed = fixtures.respond(&get("/members", "Cookie: a=b; ok=1\r\n"));
+        assert_eq!(
+            (passed.status, passed.body.as_slice()),
+            (200, &b"<p>Members</p>"[..])
+        );
+
+        let login = fixtures.respond(&get("/staff", ""));
+        assert_eq!(login.status, 401);
+        assert!(login
+            .headers
+            .contains(&("WWW-Authenticate".into(), r#"Basic realm="mock""#.into())));
+        let wrong = fixtures.respond(&get("/staff", "Authorization: Basic YWRhOng=\r\n"));
+        assert_eq!(wrong.status, 401);
+        let staff = fixtures.respond(&get("/staff", "Authorization: Basic YWRhOnBh\r\n"));
+        assert_eq!(staff.body, b"<p>Staff</p>");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
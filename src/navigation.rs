@@ -0,0 +1,190 @@
+//! Navigation Chain Emulation (`--referer`)
+//!
+//! A browser's first page on a site usually comes from a search engine, and
+//! the pages after it from links on the site. `--referer auto` reproduces
+//! that: the first request to a site carries Google's referer and
+//! `Sec-Fetch-Site: cross-site`, and each later one (e.g. in a batch) the
+//! page fetched before it on the same site. `--referer none` is a typed-in
+//! address (`Sec-Fetch-Site: none`), and `--referer <url>` a click on `url`.
+//!
+//! Referers follow the default `strict-origin-when-cross-origin` policy.
+//! Redirects keep the headers of the first request, as the client doesn't
+//! replace the Referer with the redirecting URL, and a site's chain continues
+//! from the URL the redirects ended at.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{bail, Result};
+use reqwest::header::{HeaderMap, HeaderValue, REFERER};
+use url::Url;
+
+/// Where a site's first page in `auto` mode is reached from
+const SEARCH_REFERER: &str = "https://www.google.com/";
+
+/// `--referer` value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefererPolicy {
+    /// Search engine first, then the previous page on the same site
+    Auto,
+    /// No referer, as for a typed-in address or a bookmark
+    None,
+    /// Always this page
+    Url(Url),
+}
+
+impl FromStr for RefererPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "none" => Ok(Self::None),
+            _ => match Url::parse(s) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(Self::Url(url)),
+                _ => bail!("Invalid referer '{s}': expected auto, none, or an http(s) URL"),
+            },
+        }
+    }
+}
+
+/// Referer and `Sec-Fetch-Site` for a run's page navigations
+#[derive(Debug)]
+pub struct Navigator {
+    policy: RefererPolicy,
+    /// Last page reached on each site
+    visited: Mutex<HashMap<String, Url>>,
+}
+
+impl Navigator {
+    #[must_use]
+    pub fn new(policy: RefererPolicy) -> Self {
+        Self {
+            policy,
+            visited: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Headers for navigating to `url`
+    #[must_use]
+    pub fn headers(&self, url: &Url) -> HeaderMap {
+        let from = match &self.policy {
+            RefererPolicy::None => None,
+            RefererPolicy::Url(from) => Some(from.clone()),
+            RefererPolicy::Auto => Some(
+                self.visited
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .get(&site_key(url))
+                    .cloned()
+                    .unwrap_or_else(|| Url::parse(SEARCH_REFERER).expect("valid URL")),
+            ),
+        };
+
+        let mut headers = HeaderMap::new();
+        let site = match &from {
+            Some(from) => {
+                if let Some(value) = referer(from, url).and_then(|r| HeaderValue::from_str(&r).ok())
+                {
+                    headers.insert(REFERER, value);
+                }
+                fetch_site(from, url)
+            }
+            None => "none",
+        };
+        headers.insert("Sec-Fetch-Site", HeaderValue::from_static(site));
+        headers
+    }
+
+    /// Continue `url`'s site chain from `url` (the final URL after redirects)
+    pub fn visited(&self, url: &Url) {
+        self.visited
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(site_key(url), url.clone());
+    }
+}
+
+/// `Sec-Fetch-Site` of a request from `from` to `to`
+#[must_use]
+pub fn fetch_site(from: &Url, to: &Url) -> &'static str {
+    if from.origin() == to.origin() {
+        "same-origin"
+    } else if site_key(from) == site_key(to) {
+        "same-site"
+    } else {
+        "cross-site"
+    }
+}
+
+/// Referer sent from `from` to `to` under `strict-origin-when-cross-origin`
+pub(crate) fn referer(from: &Url, to: &Url) -> Option<String> {
+    if from.scheme() == "https" && to.scheme() != "https" {
+        return None;
+    }
+    if from.origin() == to.origin() {
+        let mut full = from.clone();
+        full.set_fragment(None);
+        let _ = full.set_username("");
+        let _ = full.set_password(None);
+        Some(full.to_string())
+    } else {
+        Some(format!("{}/", from.origin().ascii_serialization()))
+    }
+}
+
+/// Scheme and registrable domain (last two host labels, a rough stand-in)
+fn site_key(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    let labels: Vec<&str> = host.rsplitn(3, '.').collect();
+    let domain = match labels.as_slice() {
+        [tld, domain, ..] => format!("{domain}.{tld}"),
+        _ => host.to_string(),
+    };
+    format!("{}://{domain}", url.scheme())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_auto_chain() {
+        let navigator = Navigator::new(RefererPolicy::Auto);
+        let first = navigator.headers(&url("https://shop.example.com/"));
+        assert_eq!(first[REFERER], "https://www.google.com/");
+        assert_eq!(first["Sec-Fetch-Site"], "cross-site");
+
+        // Redirected to www: the chain continues from there
+        navigator.visited(&url("https://www.example.com/#top"));
+        let same_origin = navigator.headers(&url("https://www.example.com/p/1"));
+        assert_eq!(same_origin[REFERER], "https://www.example.com/");
+        assert_eq!(same_origin["Sec-Fetch-Site"], "same-origin");
+        let same_site = navigator.headers(&url("https://img.example.com/a"));
+        assert_eq!(same_site[REFERER], "https://www.example.com/");
+        assert_eq!(same_site["Sec-Fetch-Site"], "same-site");
+
+        let other = navigator.headers(&url("https://other.example.org/"));
+        assert_eq!(other[REFERER], "https://www.google.com/");
+    }
+
+    #[test]
+    fn test_fixed_and_none() {
+        let navigator: Navigator = Navigator::new("https://news.example/a?b=1".parse().unwrap());
+        let headers = navigator.headers(&url("https://news.example/c"));
+        assert_eq!(headers[REFERER], "https://news.example/a?b=1");
+        let downgrade = navigator.headers(&url("http://news.example/c"));
+        assert!(!downgrade.contains_key(REFERER));
+        assert_eq!(downgrade["Sec-Fetch-Site"], "cross-site");
+
+        let none = Navigator::new(RefererPolicy::None).headers(&url("https://news.example/"));
+        assert!(!none.contains_key(REFERER));
+        assert_eq!(none["Sec-Fetch-Site"], "none");
+        assert!("ftp://x".parse::<RefererPolicy>().is_err());
+    }
+}
@@ -0,0 +1,407 @@
+//! OAuth2 Bearer Tokens
+//!
+//! Token handling for `--auth oauth2:<name>`, using a client from the config
+//! file's `oauth2` section:
+//! - first token via `client_credentials` or a configured refresh token
+//! - cached in the [`SecretStore`] with its expiry, reused across runs
+//! - renewed shortly before expiry, or when a request comes back 401,
+//!   preferring the `refresh_token` grant when the server issued one
+//! - only sent to the client's `domains` (and their subdomains)
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::config::{OAuth2Config, OAuth2Grant};
+use crate::secrets::SecretStore;
+
+/// Renew tokens this many seconds before they expire
+const EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// A cached access token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedToken {
+    pub access_token: String,
+    /// Scheme for the `Authorization` header (usually `Bearer`)
+    pub token_type: String,
+    /// Unix time the token expires; `None` if the server didn't say
+    pub expires_at: Option<i64>,
+    pub refresh_token: Option<String>,
+}
+
+impl CachedToken {
+    /// Whether the token can still be used at `now` (unix seconds)
+    #[must_use]
+    pub fn is_fresh(&self, now: i64) -> bool {
+        self.expires_at
+            .is_none_or(|expires| now + EXPIRY_MARGIN_SECS < expires)
+    }
+
+    /// `Authorization` header value
+    #[must_use]
+    pub fn header_value(&self) -> String {
+        // Servers return "bearer" as often as "Bearer"
+        if self.token_type.eq_ignore_ascii_case("bearer") {
+            format!("Bearer {}", self.access_token)
+        } else {
+            format!("{} {}", self.token_type, self.access_token)
+        }
+    }
+}
+
+/// Token endpoint response (RFC 6749 §5.1)
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default = "default_token_type")]
+    token_type: String,
+    expires_in: Option<i64>,
+    refresh_token: Option<String>,
+}
+
+fn default_token_type() -> String {
+    "Bearer".to_string()
+}
+
+/// An OAuth2 client with a cached, shared token
+///
+/// Concurrent callers share one token; when several see a 401 at once only
+/// the first fetches a new one.
+#[derive(Debug)]
+pub struct OAuth2Auth {
+    name: String,
+    config: OAuth2Config,
+    store: SecretStore,
+    http: reqwest::Client,
+    /// Token generation and the current token
+    state: Mutex<(u64, Option<CachedToken>)>,
+}
+
+impl OAuth2Auth {
+    /// Client `name` from `config`, picking up any token cached in `store`
+    pub fn new(name: &str, config: OAuth2Config, store: SecretStore) -> Result<Self> {
+        if config.domains.is_empty() {
+            anyhow::bail!("OAuth2 client '{name}' has no \"domains\" to send its token to");
+        }
+        let cached = store
+            .get(&cache_key(name))?
+            .and_then(|json| serde_json::from_str(&json).ok());
+        Ok(Self {
+            name: name.to_string(),
+            config,
+            store,
+            http: crate::policy::client_builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            state: Mutex::new((0, cached)),
+        })
+    }
+
+    /// Load client `name` from the config file
+    pub fn from_config(name: &str, store: SecretStore) -> Result<Self> {
+        let config = crate::config::NabConfig::load()?
+            .oauth2
+            .remove(name)
+            .with_context(|| {
+                format!(
+                    "No OAuth2 client '{name}'. Add it under \"oauth2\" in {}",
+                    crate::config::NabConfig::path().display()
+                )
+            })?;
+        Self::new(name, config, store)
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether requests to `url` should carry the token
+    #[must_use]
+    pub fn applies_to(&self, url: &str) -> bool {
+        let Some(host) = url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_lowercase))
+        else {
+            return false;
+        };
+        self.config.domains.iter().any(|domain| {
+            let domain = domain.trim().trim_end_matches('.').to_lowercase();
+            host == domain
+                || host
+                    .strip_suffix(&domain)
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+    }
+
+    /// Current token generation, without fetching a token
+    pub async fn generation(&self) -> u64 {
+        self.state.lock().await.0
+    }
+
+    /// A usable token and its generation, fetching one if needed
+    pub async fn token(&self) -> Result<(u64, CachedToken)> {
+        let mut state = self.state.lock().await;
+        let now = chrono::Utc::now().timestamp();
+        if let Some(token) = state.1.as_ref().filter(|t| t.is_fresh(now)) {
+            return Ok((state.0, token.clone()));
+        }
+        let token = self.request_token(state.1.as_ref()).await?;
+        *state = (state.0 + 1, Some(token.clone()));
+        Ok((state.0, token))
+    }
+
+    /// Replace a token the server rejected, unless another caller already did
+    pub async fn refresh(&self, generation: u64) -> Result<()> {
+        let mut state = self.state.lock().await;
+        if state.0 != generation {
+            return Ok(());
+        }
+        let token = self.request_token(state.1.as_ref()).await?;
+        *state = (generation + 1, Some(token));
+        Ok(())
+    }
+
+    /// Get a new token from the endpoint and cache it
+    async fn request_token(&self, previous: Option<&CachedToken>) -> Result<CachedToken> {
+        // Prefer the refresh token the server handed out last time
+        let issued = previous.and_then(|t| t.refresh_token.clone());
+        let token = match issued {
+            Some(refresh) => match self.grant_refresh_token(&refresh).await {
+                Ok(token) => token,
+                Err(e) => {
+                    debug!("Refresh token rejected ({e}), starting over");
+                    self.initial_grant().await?
+                }
+            },
+            None => self.initial_grant().await?,
+        };
+        self.store
+            .set(&cache_key(&self.name), &serde_json::to_string(&token)?)?;
+        Ok(token)
+    }
+
+    async fn initial_grant(&self) -> Result<CachedToken> {
+        match self.config.grant {
+            OAuth2Grant::ClientCredentials => {
+                self.grant(vec![("grant_type", "client_credentials".to_string())], None)
+                    .await
+            }
+            OAuth2Grant::RefreshToken => {
+                let var = self
+                    .config
+                    .refresh_token_env
+                    .as_deref()
+                    .context("refresh_token grant needs refresh_token_env")?;
+                let refresh = std::env::var(var).with_context(|| format!("{var} is not set"))?;
+                self.grant_refresh_token(&refresh).await
+            }
+        }
+    }
+
+    async fn grant_refresh_token(&self, refresh: &str) -> Result<CachedToken> {
+        self.grant(
+            vec![
+                ("grant_type", "refresh_token".to_string()),
+                ("refresh_token", refresh.to_string()),
+            ],
+            Some(refresh),
+        )
+        .await
+    }
+
+    /// POST a token request; `refresh` is kept if the server doesn't rotate it
+    async fn grant(
+        &self,
+        mut params: Vec<(&str, String)>,
+        refresh: Option<&str>,
+    ) -> Result<CachedToken> {
+        params.push(("client_id", self.config.client_id.clone()));
+        if let Some(var) = &self.config.client_secret_env {
+            let secret = std::env::var(var).with_context(|| format!("{var} is not set"))?;
+            params.push(("client_secret", secret));
+        }
+        if let Some(scope) = &self.config.scope {
+            params.push(("scope", scope.clone()));
+        }
+        if let Some(audience) = &self.config.audience {
+            params.push(("audience", audience.clone()));
+        }
+
+        let response = self
+            .http
+            .post(&self.config.token_url)
+            .form(&params)
+            .send()
+            .await
+            .context("Token request failed")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "Token endpoint returned HTTP {status}: {}",
+                body.chars().take(200).collect::<String>()
+            );
+        }
+        let parsed: TokenResponse = response
+            .json()
+            .await
+            .context("Invalid token endpoint response")?;
+        debug!(client = %self.name, expires_in = ?parsed.expires_in, "Got OAuth2 token");
+
+        Ok(CachedToken {
+            access_token: parsed.access_token,
+            token_type: parsed.token_type,
+            expires_at: parsed
+                .expires_in
+                .map(|secs| chrono::Utc::now().timestamp() + secs),
+            refresh_token: parsed.refresh_token.or_else(|| refresh.map(String::from)),
+        })
+    }
+}
+
+fn cache_key(name: &str) -> String {
+    format!("oauth2:{name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Token endpoint issuing `t1`, `t2`, ... and recording request bodies
+    async fn token_server(bodies: Arc<std::sync::Mutex<Vec<String>>>) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let issued = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 8192];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let body = request.split("\r\n\r\n").nth(1).unwrap_or("").to_string();
+                bodies.lock().unwrap().push(body);
+                let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+                let json = format!(
+                    r#"{{"access_token":"t{n}","token_type":"bearer","expires_in":3600,"refresh_token":"r{n}"}}"#
+                );
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{json}",
+                    json.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_token_cache_and_refresh() {
+        let bodies = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let addr = token_server(Arc::clone(&bodies)).await;
+        let dir = std::env::temp_dir().join(format!("nab-oauth2-{}", std::process::id()));
+        let store = SecretStore::File(dir.clone());
+        let config = OAuth2Config {
+            token_url: format!("http://{addr}/token"),
+            client_id: "nab".into(),
+            client_secret_env: None,
+            grant: OAuth2Grant::ClientCredentials,
+            refresh_token_env: None,
+            scope: Some("read".into()),
+            audience: None,
+            domains: vec!["api.example.com".into()],
+        };
+
+        let auth = OAuth2Auth::new("test", config.clone(), store.clone()).unwrap();
+        let (generation, token) = auth.token().await.unwrap();
+        assert_eq!(token.header_value(), "Bearer t1");
+        assert_eq!(auth.token().await.unwrap().1.access_token, "t1");
+
+        auth.refresh(generation).await.unwrap();
+        // A stale generation doesn't trigger another request
+        auth.refresh(generation).await.unwrap();
+        assert_eq!(auth.token().await.unwrap().1.access_token, "t2");
+
+        let bodies = bodies.lock().unwrap().clone();
+        assert_eq!(bodies.len(), 2);
+        assert!(bodies[0].contains("grant_type=client_credentials"));
+        assert!(bodies[0].contains("scope=read"));
+        assert!(bodies[1].contains("grant_type=refresh_token"));
+        assert!(bodies[1].contains("refresh_token=r1"));
+
+        // A new client reuses the cached token
+        let reloaded = OAuth2Auth::new("test", config, store).unwrap();
+        assert_eq!(reloaded.token().await.unwrap().1.access_token, "t2");
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(dir.with_extension("key"));
+    }
+
+    #[tokio::test]
+    async fn test_token_only_sent_to_its_domains() {
+        let bodies = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let addr = token_server(Arc::clone(&bodies)).await;
+        let dir = std::env::temp_dir().join(format!("nab-oauth2-scope-{}", std::process::id()));
+        let store = SecretStore::File(dir.clone());
+        let config = OAuth2Config {
+            token_url: format!("http://{addr}/token"),
+            client_id: "nab".into(),
+            client_secret_env: None,
+            grant: OAuth2Grant::ClientCredentials,
+            refresh_token_env: None,
+            scope: None,
+            audience: None,
+            domains: vec!["api.example.com".into()],
+        };
+        let provider = crate::login::AuthProvider::OAuth2(
+            OAuth2Auth::new("scoped", config.clone(), store.clone()).unwrap(),
+        );
+
+        let (_, headers) = provider.headers("https://tracker.example/").await.unwrap();
+        assert!(headers.is_empty());
+        let (_, headers) = provider
+            .headers("https://notapi.example.com/")
+            .await
+            .unwrap();
+        assert!(headers.is_empty());
+        assert!(
+            bodies.lock().unwrap().is_empty(),
+            "no token fetched for other hosts"
+        );
+        let (_, headers) = provider
+            .headers("https://v2.api.example.com/x")
+            .await
+            .unwrap();
+        assert_eq!(headers[reqwest::header::AUTHORIZATION], "Bearer t1");
+
+        let unscoped = OAuth2Config {
+            domains: Vec::new(),
+            ..config
+        };
+        assert!(OAuth2Auth::new("unscoped", unscoped, store).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(dir.with_extension("key"));
+    }
+
+    #[test]
+    fn test_token_freshness() {
+        let token = CachedToken {
+            access_token: "abc".into(),
+            token_type: "MAC".into(),
+            expires_at: Some(1_000),
+            refresh_token: None,
+        };
+        assert!(token.is_fresh(900));
+        assert!(!token.is_fresh(950));
+        assert_eq!(token.header_value(), "MAC abc");
+        let forever = CachedToken {
+            expires_at: None,
+            ..token
+        };
+        assert!(forever.is_fresh(i64::MAX));
+    }
+}
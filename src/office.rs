@@ -0,0 +1,756 @@
+//! Office Document Extraction
+//!
+//! Word (`.docx`) and Excel (`.xlsx`) files are zip archives of XML parts.
+//! Document-heavy sites (government publications, investor relations) serve
+//! a lot of them, so `nab fetch` reads them instead of saving opaque bytes:
+//!
+//! - [`docx_markdown`]: `word/document.xml` as Markdown: headings from the
+//!   `Title` and `Heading N` styles, list items, bold and italic runs,
+//!   hyperlinks (through the document's relationships), and tables
+//! - [`xlsx_sheets`]: each worksheet's cells, with shared and inline strings
+//!   resolved, written out by [`Sheet::to_csv`]
+//!
+//! Cells hold what Excel stored, not what it displays: dates stay serial
+//! numbers and formulas give their cached results.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::content::unescape;
+
+/// Largest zip entry inflated, against zip bombs
+const MAX_ENTRY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Columns an Excel worksheet can have
+const MAX_COLUMNS: usize = 16_384;
+
+/// A Word document's text as Markdown
+pub fn docx_markdown(docx: &[u8]) -> Result<String> {
+    let zip = Zip::open(docx)?;
+    let document = zip
+        .read("word/document.xml")
+        .ok_or_else(|| anyhow!("Not a Word document (no word/document.xml)"))?;
+    let links = zip
+        .read("word/_rels/document.xml.rels")
+        .map(|rels| relationships(&rels))
+        .unwrap_or_default();
+
+    let mut doc = Docx::default();
+    for event in Events::new(&document) {
+        doc.event(event, &links);
+    }
+    let mut markdown = doc.blocks.join("\n\n");
+    markdown.push('\n');
+    Ok(markdown)
+}
+
+/// A worksheet's name and rows
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sheet {
+    pub name: String,
+    /// Rows as stored (blank rows skipped), padded to the widest row
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Sheet {
+    /// The rows as CSV (RFC 4180)
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for row in &self.rows {
+            let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+}
+
+/// Sheets as `nab fetch` prints them: one sheet's CSV as is, several each
+/// under a `## Name` heading
+#[must_use]
+pub fn sheets_text(sheets: &[Sheet]) -> String {
+    match sheets {
+        [sheet] => sheet.to_csv(),
+        _ => sheets
+            .iter()
+            .map(|sheet| format!("## {}\n\n{}", sheet.name, sheet.to_csv()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// An Excel workbook's worksheets, in workbook order
+pub fn xlsx_sheets(xlsx: &[u8]) -> Result<Vec<Sheet>> {
+    let zip = Zip::open(xlsx)?;
+    let workbook = zip
+        .read("xl/workbook.xml")
+        .ok_or_else(|| anyhow!("Not an Excel workbook (no xl/workbook.xml)"))?;
+    let targets = zip
+        .read("xl/_rels/workbook.xml.rels")
+        .map(|rels| relationships(&rels))
+        .unwrap_or_default();
+    let shared = zip
+        .read("xl/sharedStrings.xml")
+        .map(|xml| shared_strings(&xml))
+        .unwrap_or_default();
+
+    let mut sheets = Vec::new();
+    for event in Events::new(&workbook) {
+        let Event::Open {
+            name: "sheet", tag, ..
+        } = event
+        else {
+            continue;
+        };
+        let (Some(name), Some(id)) = (attribute(tag, "name"), attribute(tag, "r:id")) else {
+            continue;
+        };
+        let Some(target) = targets.get(&id) else {
+            continue;
+        };
+        // Targets are relative to xl/, or absolute within the package
+        let path = match target.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None => format!("xl/{target}"),
+        };
+        if let Some(xml) = zip.read(&path) {
+            sheets.push(Sheet {
+                name,
+                rows: worksheet_rows(&xml, &shared),
+            });
+        }
+    }
+    if sheets.is_empty() {
+        bail!("No worksheets found in the workbook");
+    }
+    Ok(sheets)
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// `Id` to `Target` of a relationships part
+fn relationships(xml: &str) -> HashMap<String, String> {
+    Events::new(xml)
+        .filter_map(|event| match event {
+            Event::Open {
+                name: "Relationship",
+                tag,
+                ..
+            } => Some((attribute(tag, "Id")?, attribute(tag, "Target")?)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The shared string table, rich text runs joined
+fn shared_strings(xml: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut current: Option<String> = None;
+    let mut in_text = false;
+    // Phonetic guides (`rPh`) repeat the text in another script
+    let mut phonetic = 0;
+    for event in Events::new(xml) {
+        match event {
+            Event::Open { name: "si", .. } => current = Some(String::new()),
+            Event::Close("si") => strings.extend(current.take()),
+            Event::Open {
+                name: "rPh",
+                empty: false,
+                ..
+            } => phonetic += 1,
+            Event::Close("rPh") => phonetic -= 1,
+            Event::Open {
+                name: "t", empty, ..
+            } => in_text = !empty,
+            Event::Close("t") => in_text = false,
+            Event::Text(text) if in_text && phonetic == 0 => {
+                if let Some(current) = &mut current {
+                    current.push_str(&unescape(text));
+                }
+            }
+            _ => {}
+        }
+    }
+    strings
+}
+
+/// A worksheet's cells as rows
+fn worksheet_rows(xml: &str, shared: &[String]) -> Vec<Vec<String>> {
+    let mut cells: BTreeMap<usize, BTreeMap<usize, String>> = BTreeMap::new();
+    let mut row = 0;
+    let mut column = 0;
+    let mut kind = String::new();
+    let mut value: Option<String> = None;
+    let mut in_value = false;
+    for event in Events::new(xml) {
+        match event {
+            Event::Open {
+                name: "row", tag, ..
+            } => {
+                row = attribute(tag, "r")
+                    .and_then(|r| r.parse().ok())
+                    .unwrap_or(row + 1);
+                column = 0;
+            }
+            Event::Open {
+                name: "c",
+                tag,
+                empty,
+            } => {
+                column = attribute(tag, "r")
+                    .and_then(|r| cell_column(&r))
+                    .unwrap_or(column + 1);
+                kind = attribute(tag, "t").unwrap_or_default();
+                value = (!empty).then(String::new);
+            }
+            Event::Open {
+                name: "v" | "t",
+                empty: false,
+                ..
+            } => in_value = true,
+            Event::Close("v" | "t") => in_value = false,
+            Event::Text(text) if in_value => {
+                if let Some(value) = &mut value {
+                    value.push_str(&unescape(text));
+                }
+            }
+            Event::Close("c") => {
+                let Some(raw) = value.take() else {
+                    continue;
+                };
+                let text = match kind.as_str() {
+                    "s" => raw
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| shared.get(i).cloned())
+                        .unwrap_or_default(),
+                    "b" => if raw.trim() == "1" { "TRUE" } else { "FALSE" }.to_string(),
+                    _ => raw,
+                };
+                if !text.is_empty() && column <= MAX_COLUMNS {
+                    cells.entry(row).or_default().insert(column, text);
+                }
+            }
+            _ => {}
+        }
+    }
+    let width = cells
+        .values()
+        .filter_map(|row| row.keys().next_back())
+        .max()
+        .copied()
+        .unwrap_or(0);
+    cells
+        .into_values()
+        .map(|mut row| {
+            (1..=width)
+                .map(|column| row.remove(&column).unwrap_or_default())
+                .collect()
+        })
+        .collect()
+}
+
+/// 1-based column of a cell reference like `AB12`
+fn cell_column(reference: &str) -> Option<usize> {
+    let letters: Vec<u8> = reference
+        .bytes()
+        .take_while(u8::is_ascii_alphabetic)
+        .collect();
+    if letters.is_empty() || letters.len() > 3 {
+        return None;
+    }
+    Some(letters.iter().fold(0, |column, letter| {
+        column * 26 + usize::from(letter.to_ascii_uppercase() - b'A' + 1)
+    }))
+}
+
+/// A run of text and its formatting
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    text: String,
+    bold: bool,
+    italic: bool,
+    link: Option<String>,
+}
+
+/// Markdown built up from `word/document.xml` events
+#[derive(Default)]
+struct Docx {
+    blocks: Vec<String>,
+    /// Spans of the paragraph being read
+    spans: Vec<Span>,
+    /// `Title` is level 1, `Heading N` level N
+    heading: Option<usize>,
+    list: bool,
+    bold: bool,
+    italic: bool,
+    link: Option<String>,
+    in_text: bool,
+    /// Open tables (nested ones are flattened into their cell)
+    tables: usize,
+    rows: Vec<Vec<String>>,
+}
+
+impl Docx {
+    fn event(&mut self, event: Event<'_>, links: &HashMap<String, String>) {
+        match event {
+            Event::Open { name: "p", .. } => {
+                self.spans.clear();
+                self.heading = None;
+                self.list = false;
+            }
+            Event::Close("p") => self.paragraph(),
+            Event::Open {
+                name: "pStyle",
+                tag,
+                ..
+            } => {
+                let style = attribute(tag, "w:val").unwrap_or_default().to_lowercase();
+                let style = style.replace(' ', "");
+                if style == "title" {
+                    self.heading = Some(1);
+                } else if let Some(level) = style.strip_prefix("heading") {
+                    self.heading = level.parse::<usize>().ok().map(|level| level.clamp(1, 6));
+                } else if style.starts_with("list") {
+                    self.list = true;
+                }
+            }
+            Event::Open { name: "numPr", .. } => self.list = true,
+            Event::Open { name: "r", .. } => {
+                self.bold = false;
+                self.italic = false;
+            }
+            Event::Open { name: "b", tag, .. } => self.bold = toggled_on(tag),
+            Event::Open { name: "i", tag, .. } => self.italic = toggled_on(tag),
+            Event::Open {
+                name: "hyperlink",
+                tag,
+                empty: false,
+            } => {
+                self.link = attribute(tag, "r:id")
+                    .and_then(|id| links.get(&id).cloned())
+                    .or_else(|| attribute(tag, "w:anchor").map(|anchor| format!("#{anchor}")));
+            }
+            Event::Close("hyperlink") => self.link = None,
+            Event::Open {
+                name: "t", empty, ..
+            } => self.in_text = !empty,
+            Event::Close("t") => self.in_text = false,
+            Event::Text(text) if self.in_text => self.push(&unescape(text)),
+            Event::Open { name: "tab", .. } => self.push(" "),
+            Event::Open {
+                name: "br" | "cr", ..
+            } => self.push("\n"),
+            Event::Open { name: "tbl", .. } => {
+                self.tables += 1;
+                if self.tables == 1 {
+                    self.rows.clear();
+                }
+            }
+            Event::Close("tbl") => {
+                self.tables = self.tables.saturating_sub(1);
+                if self.tables == 0 {
+                    let rows = std::mem::take(&mut self.rows);
+                    if let Some(table) = markdown_table(&rows) {
+                        self.blocks.push(table);
+                    }
+                }
+            }
+            Event::Open { name: "tr", .. } if self.tables == 1 => self.rows.push(Vec::new()),
+            Event::Open { name: "tc", .. } if self.tables == 1 => {
+                if let Some(row) = self.rows.last_mut() {
+                    row.push(String::new());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        let (bold, italic, link) = (self.bold, self.italic, self.link.clone());
+        match self.spans.last_mut() {
+            Some(last) if (last.bold, last.italic, &last.link) == (bold, italic, &link) => {
+                last.text.push_str(text);
+            }
+            _ => self.spans.push(Span {
+                text: text.to_string(),
+                bold,
+                italic,
+                link,
+            }),
+        }
+    }
+
+    /// Finish the paragraph: into the open table cell, or as a block
+    fn paragraph(&mut self) {
+        let text: String = self.spans.drain(..).map(|span| span.markdown()).collect();
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        if self.tables > 0 {
+            if let Some(cell) = self.rows.last_mut().and_then(|row| row.last_mut()) {
+                if !cell.is_empty() {
+                    cell.push(' ');
+                }
+                cell.push_str(&text.replace('\n', " "));
+            }
+            return;
+        }
+        let block = match (self.heading, self.list) {
+            (Some(level), _) => format!("{} {}", "#".repeat(level), text.replace('\n', " ")),
+            (None, true) => format!("- {text}"),
+            (None, false) => text.to_string(),
+        };
+        // List items stay together as one list
+        match self.blocks.last_mut() {
+            Some(last) if self.list && self.heading.is_none() && last.starts_with("- ") => {
+                last.push('\n');
+                last.push_str(&block);
+            }
+            _ => self.blocks.push(block),
+        }
+    }
+}
+
+impl Span {
+    fn markdown(&self) -> String {
+        let mut text = self.text.clone();
+        for (on, marker) in [(self.italic, "*"), (self.bold, "**")] {
+            if on {
+                text = emphasize(&text, marker);
+            }
+        }
+        match &self.link {
+            Some(url) if !text.trim().is_empty() => format!("[{}]({url})", text.trim()),
+            _ => text,
+        }
+    }
+}
+
+/// `text` wrapped in `marker`, keeping surrounding whitespace outside it
+fn emphasize(text: &str, marker: &str) -> String {
+    let inner = text.trim();
+    if inner.is_empty() {
+        return text.to_string();
+    }
+    let start = text.len() - text.trim_start().len();
+    let end = start + inner.len();
+    format!("{}{marker}{inner}{marker}{}", &text[..start], &text[end..])
+}
+
+/// A Word toggle property (`<w:b/>`, `<w:b w:val="0"/>`) is on
+fn toggled_on(tag: &str) -> bool {
+    !matches!(
+        attribute(tag, "w:val").as_deref(),
+        Some("0" | "false" | "off" | "none")
+    )
+}
+
+/// Rows as a Markdown table, the first row as its header
+fn markdown_table(rows: &[Vec<String>]) -> Option<String> {
+    let width = rows.iter().map(Vec::len).max().filter(|&w| w > 0)?;
+    let line = |row: &[String]| {
+        let cells: Vec<String> = (0..width)
+            .map(|i| {
+                row.get(i)
+                    .map_or(String::new(), |cell| cell.replace('|', "\\|"))
+            })
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut table = vec![line(&rows[0]), format!("|{}", " --- |".repeat(width))];
+    table.extend(rows[1..].iter().map(|row| line(row)));
+    Some(table.join("\n"))
+}
+
+// ─── XML ────────────────────────────────────────────────────────────────────
+
+/// An XML event; names are local (namespace prefix dropped)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event<'a> {
+    /// A start tag; `tag` is its raw text, for [`attribute`], and `empty`
+    /// whether it closes itself
+    Open {
+        name: &'a str,
+        tag: &'a str,
+        empty: bool,
+    },
+    Close(&'a str),
+    /// Character data, still escaped
+    Text(&'a str),
+}
+
+/// The events of an XML document, skipping the prolog, comments, and
+/// processing instructions
+struct Events<'a> {
+    xml: &'a str,
+    pos: usize,
+}
+
+impl<'a> Events<'a> {
+    fn new(xml: &'a str) -> Self {
+        Self { xml, pos: 0 }
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        loop {
+            let rest = &self.xml[self.pos..];
+            if rest.is_empty() {
+                return None;
+            }
+            if !rest.starts_with('<') {
+                let end = rest.find('<').unwrap_or(rest.len());
+                self.pos += end;
+                return Some(Event::Text(&rest[..end]));
+            }
+            let (skip_to, close) = if rest.starts_with("<!--") {
+                (rest.find("-->").map(|i| i + 3), None)
+            } else if rest.starts_with("<![CDATA[") {
+                // Only markup-free text comes as CDATA in these parts
+                let end = rest.find("]]>").unwrap_or(rest.len());
+                self.pos += end.saturating_add(3).min(rest.len());
+                return Some(Event::Text(&rest[9.min(end)..end]));
+            } else if rest.starts_with("<?") || rest.starts_with("<!") {
+                (rest.find('>').map(|i| i + 1), None)
+            } else {
+                (rest.find('>').map(|i| i + 1), Some(()))
+            };
+            let Some(end) = skip_to else {
+                self.pos = self.xml.len();
+                return None;
+            };
+            self.pos += end;
+            if close.is_none() {
+                continue;
+            }
+            let tag = &rest[1..end - 1];
+            if let Some(name) = tag.strip_prefix('/') {
+                return Some(Event::Close(local_name(name.trim())));
+            }
+            let empty = tag.ends_with('/');
+            let tag = tag.strip_suffix('/').unwrap_or(tag);
+            let name = tag.split(|c: char| c.is_whitespace()).next().unwrap_or("");
+            return Some(Event::Open {
+                name: local_name(name),
+                tag,
+                empty,
+            });
+        }
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, local)| local)
+}
+
+/// Unescaped value of attribute `name` in a start tag's text
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(at) = rest.find(name) {
+        let before = rest[..at].chars().next_back();
+        let after = rest[at + name.len()..].trim_start();
+        rest = &rest[at + name.len()..];
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=').map(str::trim_start) else {
+            continue;
+        };
+        let quote = value.chars().next().filter(|q| matches!(q, '"' | '\''))?;
+        let value = &value[1..];
+        return Some(unescape(&value[..value.find(quote)?]));
+    }
+    None
+}
+
+// ─── Zip ────────────────────────────────────────────────────────────────────
+
+/// A zip archive's entries, by name
+struct Zip<'a> {
+    bytes: &'a [u8],
+    entries: HashMap<String, Entry>,
+}
+
+struct Entry {
+    method: u16,
+    compressed_size: usize,
+    header_offset: usize,
+}
+
+impl<'a> Zip<'a> {
+    /// Index the central directory
+    fn open(bytes: &'a [u8]) -> Result<Self> {
+        // The end of central directory record, before an up to 64 KiB comment
+        let search_from = bytes.len().saturating_sub(22 + 0xffff);
+        let end = bytes[search_from..]
+            .windows(4)
+            .rposition(|window| window == b"PK\x05\x06")
+            .map(|i| search_from + i)
+            .ok_or_else(|| anyhow!("Not a zip archive"))?;
+        let count = usize::from(u16_at(bytes, end + 10)?);
+        let mut pos = usize::try_from(u32_at(bytes, end + 16)?)?;
+
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            if bytes.get(pos..pos + 4) != Some(b"PK\x01\x02") {
+                bail!("Corrupt zip central directory");
+            }
+            let name_len = usize::from(u16_at(bytes, pos + 28)?);
+            let extra_len = usize::from(u16_at(bytes, pos + 30)?);
+            let comment_len = usize::from(u16_at(bytes, pos + 32)?);
+            let name = bytes
+                .get(pos + 46..pos + 46 + name_len)
+                .ok_or_else(|| anyhow!("Corrupt zip central directory"))?;
+            entries.insert(
+                String::from_utf8_lossy(name).into_owned(),
+                Entry {
+                    method: u16_at(bytes, pos + 10)?,
+                    compressed_size: usize::try_from(u32_at(bytes, pos + 20)?)?,
+                    header_offset: usize::try_from(u32_at(bytes, pos + 42)?)?,
+                },
+            );
+            pos += 46 + name_len + extra_len + comment_len;
+        }
+        Ok(Self { bytes, entries })
+    }
+
+    /// Entry `name` as text, if it's there and readable
+    fn read(&self, name: &str) -> Option<String> {
+        let entry = self.entries.get(name)?;
+        let header = entry.header_offset;
+        if self.bytes.get(header..header + 4)? != b"PK\x03\x04" {
+            return None;
+        }
+        let name_len = usize::from(u16_at(self.bytes, header + 26).ok()?);
+        let extra_len = usize::from(u16_at(self.bytes, header + 28).ok()?);
+        let start = header + 30 + name_len + extra_len;
+        let data = self
+            .bytes
+            .get(start..start.checked_add(entry.compressed_size)?)?;
+        let mut out = Vec::new();
+        match entry.method {
+            0 => out.extend_from_slice(data),
+            8 => {
+                flate2::read::DeflateDecoder::new(data)
+                    .take(MAX_ENTRY_SIZE)
+                    .read_to_end(&mut out)
+                    .ok()?;
+            }
+            _ => return None,
+        }
+        let text = String::from_utf8_lossy(&out);
+        Some(text.strip_prefix('\u{feff}').unwrap_or(&text).to_string())
+    }
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Result<u16> {
+    let field = bytes
+        .get(at..at + 2)
+        .ok_or_else(|| anyhow!("Truncated zip archive"))?;
+    Ok(u16::from_le_bytes([field[0], field[1]]))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32> {
+    let field = bytes
+        .get(at..at + 4)
+        .ok_or_else(|| anyhow!("Truncated zip archive"))?;
+    Ok(u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epub::ZipWriter;
+
+    fn zip(parts: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = ZipWriter::default();
+        for (name, contents) in parts {
+            zip.add(name, contents.as_bytes());
+        }
+        zip.finish()
+    }
+
+    #[test]
+    fn test_docx_markdown() {
+        let document = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+<w:p><w:pPr><w:pStyle w:val="Title"/></w:pPr><w:r><w:t>Annual Report</w:t></w:r></w:p>
+<w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Results</w:t></w:r></w:p>
+<w:p><w:r><w:t xml:space="preserve">Revenue was </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>up 12% </w:t></w:r><w:r><w:rPr><w:b w:val="0"/><w:i/></w:rPr><w:t>year on year</w:t></w:r><w:r><w:t>; see </w:t></w:r><w:hyperlink r:id="rId5"><w:r><w:t>the filing</w:t></w:r></w:hyperlink><w:r><w:t>.</w:t></w:r></w:p>
+<w:p><w:pPr><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:t>Costs &amp; risks</w:t></w:r></w:p>
+<w:p><w:pPr><w:pStyle w:val="ListParagraph"/></w:pPr><w:r><w:t>Outlook</w:t></w:r></w:p>
+<w:tbl><w:tr><w:tc><w:p><w:r><w:t>Quarter</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>Sales</w:t></w:r></w:p></w:tc></w:tr>
+<w:tr><w:tc><w:p><w:r><w:t>Q1</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>1|2</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+<w:p/></w:body></w:document>"#;
+        let rels = r#"<Relationships><Relationship Id="rId5" Type="hyperlink" Target="https://example.com/filing?a=1&amp;b=2" TargetMode="External"/></Relationships>"#;
+        let docx = zip(&[
+            ("[Content_Types].xml", "<Types/>"),
+            ("word/document.xml", document),
+            ("word/_rels/document.xml.rels", rels),
+        ]);
+        assert_eq!(
+            docx_markdown(&docx).unwrap(),
+            "# Annual Report\n\n## Results\n\n\
+             Revenue was **up 12%** *year on year*; see \
+             [the filing](https://example.com/filing?a=1&b=2).\n\n\
+             - Costs & risks\n- Outlook\n\n\
+             | Quarter | Sales |\n| --- | --- |\n| Q1 | 1\\|2 |\n"
+        );
+        assert!(docx_markdown(&zip(&[("xl/workbook.xml", "<workbook/>")])).is_err());
+        assert!(docx_markdown(b"PK\x03\x04 not really").is_err());
+    }
+
+    #[test]
+    fn test_xlsx_sheets() {
+        let workbook = r#"<workbook xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>
+            <sheet name="Summary" sheetId="1" r:id="rId1"/><sheet name="Data &amp; notes" sheetId="2" r:id="rId2"/>
+            </sheets></workbook>"#;
+        let rels = r#"<Relationships>
+            <Relationship Id="rId1" Target="worksheets/sheet1.xml"/>
+            <Relationship Id="rId2" Target="/xl/worksheets/sheet2.xml"/></Relationships>"#;
+        let shared = r#"<sst><si><t>Name</t></si><si><r><t>Total</t></r><r><t xml:space="preserve">, net</t></r><rPh><t>ソウケイ</t></rPh></si><si><t>Widget "A"</t></si></sst>"#;
+        let sheet1 = r#"<worksheet><sheetData>
+            <row r="1"><c r="A1" t="s"><v>0</v></c><c r="C1" t="s"><v>1</v></c></row>
+            <row r="2"><c r="A2" t="s"><v>2</v></c><c r="B2" t="b"><v>1</v></c><c r="C2"><f>SUM(D2:D9)</f><v>1234.5</v></c></row>
+            <row r="4"><c r="A4" t="inlineStr"><is><t>line
+break</t></is></c><c r="B4" s="1"/></row>
+            </sheetData></worksheet>"#;
+        let sheet2 = r#"<x:worksheet xmlns:x="main"><x:sheetData><x:row><x:c t="str"><x:v>only</x:v></x:c></x:row></x:sheetData></x:worksheet>"#;
+        let xlsx = zip(&[
+            ("xl/workbook.xml", workbook),
+            ("xl/_rels/workbook.xml.rels", rels),
+            ("xl/sharedStrings.xml", shared),
+            ("xl/worksheets/sheet1.xml", sheet1),
+            ("xl/worksheets/sheet2.xml", sheet2),
+        ]);
+        let sheets = xlsx_sheets(&xlsx).unwrap();
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(sheets[0].name, "Summary");
+        assert_eq!(
+            sheets[0].to_csv(),
+            "Name,,\"Total, net\"\r\n\"Widget \"\"A\"\"\",TRUE,1234.5\r\n\"line\nbreak\",,\r\n"
+        );
+        assert_eq!(sheets[1].name, "Data & notes");
+        assert_eq!(sheets[1].rows, vec![vec!["only".to_string()]]);
+        assert!(sheets_text(&sheets).starts_with("## Summary\n\nName,,"));
+        assert!(sheets_text(&sheets).contains("\n## Data & notes\n\nonly\r\n"));
+        assert_eq!(sheets_text(&sheets[1..]), "only\r\n");
+        assert_eq!(cell_column("AB12"), Some(28));
+    }
+}
@@ -0,0 +1,225 @@
+//! Human Timing
+//!
+//! `--human-timing` spaces a long batch or crawl the way a person browses
+//! instead of at a fixed interval: a few requests in quick succession (a
+//! page and what it pulls in), then a pause to read. Reading pauses follow
+//! a log-normal distribution (mostly near the median, sometimes much
+//! longer), and now and then a much longer break is taken.
+//!
+//! Each host is paced on its own; different hosts don't wait for each other.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::time::Instant;
+
+use crate::batch::host_key;
+
+/// Shape of the pauses between requests to one host
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HumanTiming {
+    /// Median reading pause
+    pub think_median: Duration,
+    /// Spread of reading pauses (σ of the underlying normal distribution)
+    pub think_sigma: f64,
+    /// Requests in a burst, inclusive range
+    pub burst: (u32, u32),
+    /// Gap between requests within a burst, inclusive range
+    pub burst_gap: (Duration, Duration),
+    /// Chance that a reading pause is followed by a long break
+    pub break_chance: f64,
+    /// Length of a long break, inclusive range
+    pub break_length: (Duration, Duration),
+}
+
+impl Default for HumanTiming {
+    fn default() -> Self {
+        Self::with_median(Duration::from_secs(4))
+    }
+}
+
+impl HumanTiming {
+    /// Default shape around a median reading pause
+    #[must_use]
+    pub fn with_median(think_median: Duration) -> Self {
+        Self {
+            think_median,
+            think_sigma: 0.6,
+            burst: (1, 4),
+            burst_gap: (Duration::from_millis(80), Duration::from_millis(600)),
+            break_chance: 0.04,
+            break_length: (think_median * 5, think_median * 15),
+        }
+    }
+
+    /// Average time between request starts over a long run: a burst's gaps,
+    /// a reading pause (the log-normal mean), and the odd break, per request
+    #[must_use]
+    pub fn mean_interval(&self) -> Duration {
+        let middle = |(low, high): (Duration, Duration)| (low + high).as_secs_f64() / 2.0;
+        let burst = f64::from(self.burst.0 + self.burst.1) / 2.0;
+        let think = self.think_median.as_secs_f64() * (self.think_sigma.powi(2) / 2.0).exp();
+        let cycle = (burst - 1.0).max(0.0) * middle(self.burst_gap)
+            + think
+            + self.break_chance * middle(self.break_length);
+        Duration::from_secs_f64(cycle / burst.max(1.0))
+    }
+}
+
+#[derive(Debug)]
+struct HostPace {
+    /// Earliest start of the host's next request
+    next: Instant,
+    /// Requests left in the current burst
+    burst_left: u32,
+}
+
+/// Hands out request start times per host
+#[derive(Debug)]
+pub struct Pacer {
+    timing: HumanTiming,
+    rng: Mutex<StdRng>,
+    hosts: Mutex<HashMap<String, HostPace>>,
+}
+
+impl Pacer {
+    /// A pacer; `seed` makes the pauses reproducible
+    #[must_use]
+    pub fn new(timing: HumanTiming, seed: Option<u64>) -> Self {
+        Self {
+            timing,
+            rng: Mutex::new(seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The shape of the pauses
+    #[must_use]
+    pub fn timing(&self) -> HumanTiming {
+        self.timing
+    }
+
+    /// Wait until a request to `url` may start
+    pub async fn wait(&self, url: &str) {
+        tokio::time::sleep_until(self.schedule(url, Instant::now())).await;
+    }
+
+    /// Reserve the next start time for a request to `url`, asked at `now`
+    pub fn schedule(&self, url: &str, now: Instant) -> Instant {
+        let mut rng = self.rng.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+        let pace = hosts.entry(host_key(url)).or_insert_with(|| HostPace {
+            next: now,
+            burst_left: rng.gen_range(self.timing.burst.0..=self.timing.burst.1),
+        });
+        let start = pace.next.max(now);
+        let gap = if pace.burst_left > 1 {
+            pace.burst_left -= 1;
+            between(&mut *rng, self.timing.burst_gap)
+        } else {
+            pace.burst_left = rng.gen_range(self.timing.burst.0..=self.timing.burst.1);
+            self.think_time(&mut *rng)
+        };
+        pace.next = start + gap;
+        start
+    }
+
+    fn think_time(&self, rng: &mut impl Rng) -> Duration {
+        // Box-Muller: a standard normal sample from two uniform ones
+        let (u1, u2): (f64, f64) = (rng.gen_range(f64::EPSILON..1.0), rng.gen());
+        let normal = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+        let think = self
+            .timing
+            .think_median
+            .mul_f64((self.timing.think_sigma * normal).exp());
+        if rng.gen_bool(self.timing.break_chance.clamp(0.0, 1.0)) {
+            think + between(rng, self.timing.break_length)
+        } else {
+            think
+        }
+    }
+}
+
+/// Uniform duration in an inclusive range
+fn between(rng: &mut impl Rng, (low, high): (Duration, Duration)) -> Duration {
+    if high <= low {
+        low
+    } else {
+        rng.gen_range(low..=high)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bursts_then_pauses() {
+        let timing = HumanTiming {
+            burst: (3, 3),
+            break_chance: 0.0,
+            ..HumanTiming::with_median(Duration::from_secs(10))
+        };
+        let pacer = Pacer::new(timing, Some(7));
+        let now = Instant::now();
+        let starts: Vec<Instant> = (0..6)
+            .map(|_| pacer.schedule("https://a.example/page", now))
+            .collect();
+        let gaps: Vec<Duration> = starts.windows(2).map(|w| w[1] - w[0]).collect();
+
+        assert_eq!(starts[0], now, "first request goes immediately");
+        for gap in [gaps[0], gaps[1], gaps[3], gaps[4]] {
+            assert!(gap <= Duration::from_millis(600), "burst gap {gap:?}");
+        }
+        assert!(
+            gaps[2] > Duration::from_secs(1),
+            "reading pause {:?}",
+            gaps[2]
+        );
+
+        // Other hosts are paced separately
+        assert_eq!(pacer.schedule("https://b.example/", now), now);
+    }
+
+    #[test]
+    fn test_mean_interval() {
+        let timing = HumanTiming {
+            think_sigma: 0.0,
+            burst: (2, 2),
+            burst_gap: (Duration::from_secs(1), Duration::from_secs(1)),
+            break_chance: 0.5,
+            break_length: (Duration::from_secs(10), Duration::from_secs(10)),
+            ..HumanTiming::with_median(Duration::from_secs(4))
+        };
+        // Per burst of two: a 1s gap, a 4s pause, half of a 10s break
+        assert_eq!(timing.mean_interval(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_think_time_distribution() {
+        let timing = HumanTiming {
+            break_chance: 0.0,
+            ..HumanTiming::with_median(Duration::from_secs(4))
+        };
+        let pacer = Pacer::new(timing, Some(1));
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut samples: Vec<Duration> = (0..2001).map(|_| pacer.think_time(&mut rng)).collect();
+        samples.sort();
+        let median = samples[1000];
+        assert!(
+            median > Duration::from_secs(3) && median < Duration::from_secs(5),
+            "median {median:?}"
+        );
+        assert!(samples[2000] > Duration::from_secs(10), "long tail");
+
+        let breaks = HumanTiming {
+            break_chance: 1.0,
+            ..timing
+        };
+        let pacer = Pacer::new(breaks, Some(1));
+        assert!(pacer.think_time(&mut rng) >= Duration::from_secs(20));
+    }
+}
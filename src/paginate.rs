@@ -0,0 +1,226 @@
+//! Pagination
+//!
+//! `--paginate` follows a listing or a split article across its pages. The
+//! page after the current one is, in order of preference:
+//!
+//! 1. the site's rule from the config file (see [`PaginationRule`])
+//! 2. a `<link rel="next">` or `<a rel="next">`
+//! 3. a link to the same path with the `page`, `p`, or `pg` query number one
+//!    higher (`?page=3` → `?page=4`; a page without the parameter is page 1)
+//!
+//! Rules go in the `domains` section of the config file:
+//!
+//! ```json
+//! "domains": {
+//!   "shop.example.com": {"pagination": {"next_selector": "nav.pager a.forward"}},
+//!   "api.example.org": {"pagination": {"page_param": "offset", "step": 50}}
+//! }
+//! ```
+
+use std::collections::HashSet;
+
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Pages followed when `--max-pages` isn't given
+pub const DEFAULT_MAX_PAGES: usize = 20;
+
+/// Query parameters recognized as page numbers
+const PAGE_PARAMS: &[&str] = &["page", "p", "pg"];
+
+/// How a site links its pages
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaginationRule {
+    /// CSS selector of the next-page link
+    pub next_selector: Option<String>,
+    /// Query parameter counting pages (or items), advanced without needing a link
+    pub page_param: Option<String>,
+    /// Amount `page_param` advances by (default 1; the page size for offsets)
+    pub step: Option<u64>,
+}
+
+/// Tracks the pages of one `--paginate` run
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    rule: Option<PaginationRule>,
+    max_pages: usize,
+    visited: HashSet<String>,
+}
+
+impl Pagination {
+    #[must_use]
+    pub fn new(rule: Option<PaginationRule>, max_pages: usize) -> Self {
+        Self {
+            rule,
+            max_pages,
+            visited: HashSet::new(),
+        }
+    }
+
+    /// The page to fetch after `html` (served from `url`), or `None` when done
+    ///
+    /// Stops at `max_pages` and at links back to a page already seen.
+    pub fn next(&mut self, html: &str, url: &Url) -> Option<Url> {
+        self.visited.insert(url.to_string());
+        if self.visited.len() >= self.max_pages {
+            return None;
+        }
+        next_page(html, url, self.rule.as_ref())
+            .filter(|next| !self.visited.contains(next.as_str()))
+    }
+}
+
+/// URL of the page after `html`, if it links one
+#[must_use]
+pub fn next_page(html: &str, url: &Url, rule: Option<&PaginationRule>) -> Option<Url> {
+    let document = Html::parse_document(html);
+    if let Some(rule) = rule {
+        if let Some(selector) = &rule.next_selector {
+            let selector = Selector::parse(selector).ok()?;
+            return document.select(&selector).find_map(|link| href(link, url));
+        }
+        if let Some(param) = &rule.page_param {
+            return Some(advance(url, param, rule.step.unwrap_or(1)));
+        }
+    }
+
+    let rel_next = Selector::parse("link[rel][href], a[rel][href]").expect("valid selector");
+    let next = document
+        .select(&rel_next)
+        .filter(|link| {
+            link.value().attr("rel").is_some_and(|rel| {
+                rel.split_ascii_whitespace()
+                    .any(|r| r.eq_ignore_ascii_case("next"))
+            })
+        })
+        .find_map(|link| href(link, url));
+    if next.is_some() {
+        return next;
+    }
+
+    let anchors = Selector::parse("a[href]").expect("valid selector");
+    let links: Vec<Url> = document
+        .select(&anchors)
+        .filter_map(|link| href(link, url))
+        .collect();
+    PAGE_PARAMS.iter().find_map(|param| {
+        let candidate = advance(url, param, 1);
+        let number = query_number(&candidate, param);
+        links
+            .iter()
+            .find(|link| link.path() == url.path() && query_number(link, param) == number)
+            .cloned()
+    })
+}
+
+/// Absolute URL of a link's `href`
+fn href(link: scraper::ElementRef<'_>, base: &Url) -> Option<Url> {
+    let href = link.value().attr("href")?.trim();
+    if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+        return None;
+    }
+    base.join(href).ok()
+}
+
+/// Numeric value of query parameter `param`
+fn query_number(url: &Url, param: &str) -> Option<u64> {
+    url.query_pairs()
+        .find(|(key, _)| key == param)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// `url` with `param` advanced by `step` (a missing page number counts as page 1, a missing offset as 0)
+fn advance(url: &Url, param: &str, step: u64) -> Url {
+    let current = query_number(url, param).unwrap_or(u64::from(step == 1));
+    let next = (current + step).to_string();
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| key != param)
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    pairs.push((param.to_string(), next));
+    let mut advanced = url.clone();
+    advanced.query_pairs_mut().clear().extend_pairs(pairs);
+    advanced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_next_page_detection() {
+        let base = url("https://shop.example.com/kettles?page=2");
+        let rel = r#"<link rel="prev" href="?page=1"><link rel="next" href="/kettles?page=3">"#;
+        assert_eq!(
+            next_page(rel, &base, None),
+            Some(url("https://shop.example.com/kettles?page=3"))
+        );
+
+        let numbered =
+            r#"<a href="/kettles?page=1">1</a> <a href="/kettles?sort=new&page=3">3</a>"#;
+        assert_eq!(
+            next_page(numbered, &base, None),
+            Some(url("https://shop.example.com/kettles?sort=new&page=3"))
+        );
+
+        // Page 1 usually has no page parameter
+        let first = url("https://blog.example.com/archive");
+        let links = r#"<a href="/archive?p=2">Older</a>"#;
+        assert_eq!(
+            next_page(links, &first, None),
+            Some(url("https://blog.example.com/archive?p=2"))
+        );
+
+        assert_eq!(next_page("<a href='/about'>About</a>", &base, None), None);
+    }
+
+    #[test]
+    fn test_rules() {
+        let base = url("https://shop.example.com/kettles");
+        let html = r#"<a rel="next" href="/wrong"></a><nav class="pager"><a class="fwd" href="/kettles/2">›</a></nav>"#;
+        let selector = PaginationRule {
+            next_selector: Some("nav.pager a.fwd".to_string()),
+            ..PaginationRule::default()
+        };
+        assert_eq!(
+            next_page(html, &base, Some(&selector)),
+            Some(url("https://shop.example.com/kettles/2"))
+        );
+
+        let offsets = PaginationRule {
+            page_param: Some("offset".to_string()),
+            step: Some(50),
+            ..PaginationRule::default()
+        };
+        let second = next_page("", &base, Some(&offsets)).unwrap();
+        assert_eq!(
+            second.as_str(),
+            "https://shop.example.com/kettles?offset=50"
+        );
+        assert_eq!(
+            next_page("", &second, Some(&offsets)).unwrap().as_str(),
+            "https://shop.example.com/kettles?offset=100"
+        );
+    }
+
+    #[test]
+    fn test_pagination_stops() {
+        let page1 = url("https://example.com/list");
+        let page2 = url("https://example.com/list?page=2");
+        let links = r#"<a href="/list?page=2">2</a>"#;
+        let mut pagination = Pagination::new(None, 2);
+        assert_eq!(pagination.next(links, &page1), Some(page2.clone()));
+        assert_eq!(pagination.next(links, &page2), None, "max pages");
+
+        let mut cycle = Pagination::new(None, 10);
+        let back = r#"<link rel="next" href="/list">"#;
+        assert_eq!(cycle.next(back, &page1), None, "already visited");
+    }
+}
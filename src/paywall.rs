@@ -0,0 +1,400 @@
+//! Paywall and Login-Wall Detection
+//!
+//! Recognizes pages whose real content is gated, so callers can tell a short
+//! teaser apart from a short article:
+//! - Structured data: `isAccessibleForFree: false` in JSON-LD,
+//!   `article:content_tier` meta
+//! - Vendor overlays: Piano/Tinypass, Poool, Zephr, Pelcro, `LaterPay`,
+//!   Memberful, Leaky Paywall, Steady
+//! - Heuristics: truncated article text next to a subscribe or sign-in prompt
+//!
+//! An explicit [`CrawlerIdentity`] retry is available for sites configured to
+//! serve full text to search crawlers. Many sites verify crawlers by reverse
+//! DNS, so the retry is opt-in and its result is re-checked.
+
+use scraper::{Html, Selector};
+use serde::Serialize;
+
+/// Kind of gate in front of the content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GateKind {
+    /// Subscription or metered paywall
+    Paywall,
+    /// Content requires signing in
+    LoginWall,
+}
+
+impl GateKind {
+    /// Short name for reporting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Paywall => "paywall",
+            Self::LoginWall => "login_wall",
+        }
+    }
+}
+
+/// Result of gate detection for one page
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GateReport {
+    /// Kind of gate (None when not gated)
+    pub kind: Option<GateKind>,
+    /// Paywall vendor, when recognized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<&'static str>,
+    /// Evidence that led to the verdict
+    pub signals: Vec<String>,
+}
+
+impl GateReport {
+    /// Whether the page's content appears gated
+    #[must_use]
+    pub fn content_gated(&self) -> bool {
+        self.kind.is_some()
+    }
+}
+
+/// Paywall vendor fingerprint
+struct Vendor {
+    name: &'static str,
+    /// Substrings in page HTML (script hosts, globals)
+    markers: &'static [&'static str],
+    /// Elements that only exist while the paywall is shown
+    overlays: &'static [&'static str],
+}
+
+const VENDORS: &[Vendor] = &[
+    Vendor {
+        name: "Piano",
+        markers: &["tinypass.com", "piano.io", "tp.push(", "tinypass"],
+        overlays: &[
+            ".tp-modal",
+            ".tp-backdrop",
+            ".tp-container-inner",
+            "#piano-inline",
+        ],
+    },
+    Vendor {
+        name: "Poool",
+        markers: &["poool.fr", "poool-widget"],
+        overlays: &["#poool-widget", "[data-poool]"],
+    },
+    Vendor {
+        name: "Zephr",
+        markers: &["zephr"],
+        overlays: &["[data-zephr-feature]", "#zephr-overlay"],
+    },
+    Vendor {
+        name: "Pelcro",
+        markers: &["pelcro"],
+        overlays: &["#pelcro-app", ".pelcro-paywall"],
+    },
+    Vendor {
+        name: "LaterPay",
+        markers: &["laterpay"],
+        overlays: &[".lp_paywall", "[data-lp-paywall]"],
+    },
+    Vendor {
+        name: "Memberful",
+        markers: &["memberful"],
+        overlays: &[".memberful-paywall"],
+    },
+    Vendor {
+        name: "Leaky Paywall",
+        markers: &["leaky-paywall", "leaky_paywall"],
+        overlays: &[".leaky_paywall_message_wrap", "#leaky_paywall_message"],
+    },
+    Vendor {
+        name: "Steady",
+        markers: &["steadyhq.com"],
+        overlays: &["#steady-paywall", ".steady-paywall-container"],
+    },
+];
+
+/// Generic paywall containers used by in-house implementations
+const GENERIC_OVERLAYS: &[&str] = &[
+    "[class*='paywall']",
+    "[id*='paywall']",
+    "[class*='regwall']",
+    "[class*='subscribe-wall']",
+    "[class*='subscription-wall']",
+];
+
+const SUBSCRIBE_PROMPTS: &[&str] = &[
+    "subscribe to continue",
+    "subscribe to read",
+    "to continue reading",
+    "continue reading with",
+    "already a subscriber",
+    "become a member to read",
+    "this article is for subscribers",
+    "this content is for subscribers",
+    "subscribers only",
+    "you've reached your limit",
+    "you have reached your limit",
+    "free articles remaining",
+];
+
+const LOGIN_PROMPTS: &[&str] = &[
+    "sign in to continue",
+    "log in to continue",
+    "login to continue",
+    "sign in to read",
+    "log in to read",
+    "you must be logged in",
+    "please log in to view",
+    "sign in to view",
+];
+
+/// Visible article text below this many characters counts as truncated
+const TRUNCATED_CHARS: usize = 1500;
+
+/// Detect paywalls and login walls in an HTML page
+#[must_use]
+pub fn detect_gate(html: &str) -> GateReport {
+    let document = Html::parse_document(html);
+    let lower = html.to_lowercase();
+    let mut signals = Vec::new();
+    let mut paywall = false;
+
+    if json_ld_not_free(&document) {
+        signals.push("json-ld isAccessibleForFree=false".to_string());
+        paywall = true;
+    }
+
+    if let Some(tier) = select_first_attr(
+        &document,
+        "meta[property='article:content_tier']",
+        "content",
+    ) {
+        let tier = tier.to_lowercase();
+        if tier == "locked" || tier == "metered" {
+            signals.push(format!("article:content_tier={tier}"));
+            paywall = true;
+        }
+    }
+
+    let mut vendor = None;
+    for v in VENDORS {
+        if let Some(selector) = v.overlays.iter().find(|s| has_match(&document, s)) {
+            signals.push(format!("{} overlay {selector}", v.name));
+            vendor = Some(v.name);
+            paywall = true;
+            break;
+        }
+    }
+    if vendor.is_none() {
+        vendor = VENDORS
+            .iter()
+            .find(|v| v.markers.iter().any(|m| lower.contains(m)))
+            .map(|v| v.name);
+    }
+
+    let article_chars = article_text_len(&document);
+    let truncated = article_chars < TRUNCATED_CHARS;
+
+    let generic_overlay = GENERIC_OVERLAYS.iter().find(|s| has_match(&document, s));
+    let subscribe_prompt = SUBSCRIBE_PROMPTS.iter().find(|p| lower.contains(*p));
+    if let Some(prompt) = subscribe_prompt {
+        if generic_overlay.is_some() || truncated {
+            signals.push(format!("subscribe prompt \"{prompt}\""));
+            if let Some(selector) = generic_overlay {
+                signals.push(format!("paywall element {selector}"));
+            }
+            paywall = true;
+        }
+    }
+
+    let mut login_wall = false;
+    if !paywall {
+        if let Some(prompt) = LOGIN_PROMPTS.iter().find(|p| lower.contains(*p)) {
+            let password_form = has_match(&document, "input[type='password']");
+            if password_form || truncated {
+                signals.push(format!("login prompt \"{prompt}\""));
+                if password_form {
+                    signals.push("password field".to_string());
+                }
+                login_wall = true;
+            }
+        }
+    }
+
+    let kind = if paywall {
+        Some(GateKind::Paywall)
+    } else if login_wall {
+        Some(GateKind::LoginWall)
+    } else {
+        None
+    };
+    if kind.is_some() && truncated {
+        signals.push(format!("article text {article_chars} chars"));
+    }
+
+    GateReport {
+        kind,
+        vendor: if paywall { vendor } else { None },
+        signals: if kind.is_some() { signals } else { Vec::new() },
+    }
+}
+
+/// Whether any JSON-LD block marks the page as not free
+fn json_ld_not_free(document: &Html) -> bool {
+    let selector = Selector::parse("script[type='application/ld+json']").unwrap();
+    document.select(&selector).any(|script| {
+        let text: String = script.text().collect();
+        serde_json::from_str::<serde_json::Value>(text.trim()).is_ok_and(|v| not_free(&v))
+    })
+}
+
+fn not_free(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Object(map) => {
+            let flagged = map.get("isAccessibleForFree").is_some_and(|v| match v {
+                serde_json::Value::Bool(b) => !b,
+                serde_json::Value::String(s) => s.eq_ignore_ascii_case("false"),
+                _ => false,
+            });
+            flagged || map.values().any(not_free)
+        }
+        serde_json::Value::Array(items) => items.iter().any(not_free),
+        _ => false,
+    }
+}
+
+/// Visible text length of the main article body
+fn article_text_len(document: &Html) -> usize {
+    for selector in ["article p", "main p", "p"] {
+        let selector = Selector::parse(selector).unwrap();
+        let len: usize = document
+            .select(&selector)
+            .map(|p| p.text().map(str::trim).map(str::len).sum::<usize>())
+            .sum();
+        if len > 0 {
+            return len;
+        }
+    }
+    0
+}
+
+fn has_match(document: &Html, selector: &str) -> bool {
+    Selector::parse(selector).is_ok_and(|s| document.select(&s).next().is_some())
+}
+
+fn select_first_attr(document: &Html, selector: &str, attr: &str) -> Option<String> {
+    let selector = Selector::parse(selector).ok()?;
+    document
+        .select(&selector)
+        .find_map(|el| el.value().attr(attr).map(String::from))
+}
+
+/// Search-crawler identity for an explicit gated-content retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlerIdentity {
+    Googlebot,
+    Bingbot,
+}
+
+impl CrawlerIdentity {
+    /// Request headers the crawler sends
+    #[must_use]
+    pub fn headers(self) -> Vec<(&'static str, &'static str)> {
+        match self {
+            Self::Googlebot => vec![
+                (
+                    "User-Agent",
+                    "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+                ),
+                ("From", "googlebot(at)googlebot.com"),
+                ("Referer", "https://www.google.com/"),
+                (
+                    "Accept",
+                    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                ),
+            ],
+            Self::Bingbot => vec![
+                (
+                    "User-Agent",
+                    "Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)",
+                ),
+                ("From", "bingbot(at)microsoft.com"),
+                ("Referer", "https://www.bing.com/"),
+                (
+                    "Accept",
+                    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                ),
+            ],
+        }
+    }
+
+    /// Short name for reporting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Googlebot => "googlebot",
+            Self::Bingbot => "bingbot",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_ld_paywall() {
+        let html = r#"<html><head>
+            <script type="application/ld+json">
+            {"@context":"https://schema.org","@graph":[{"@type":"NewsArticle",
+              "isAccessibleForFree":"False",
+              "hasPart":{"@type":"WebPageElement","isAccessibleForFree":false,"cssSelector":".paywalled"}}]}
+            </script>
+            <script src="https://cdn.tinypass.com/api/tinypass.min.js"></script>
+            </head><body><article><p>Teaser paragraph.</p></article></body></html>"#;
+
+        let report = detect_gate(html);
+        assert!(report.content_gated());
+        assert_eq!(report.kind, Some(GateKind::Paywall));
+        assert_eq!(report.vendor, Some("Piano"));
+        assert!(report.signals[0].contains("isAccessibleForFree"));
+    }
+
+    #[test]
+    fn test_vendor_overlay_and_prompt() {
+        let overlay = r#"<html><body><article><p>Intro.</p></article>
+            <div class="tp-modal">Already a subscriber? Sign in</div></body></html>"#;
+        let report = detect_gate(overlay);
+        assert_eq!(report.vendor, Some("Piano"));
+        assert!(report.content_gated());
+
+        let in_house = r#"<html><body><article><p>Intro.</p></article>
+            <div class="article-paywall">Subscribe to continue reading.</div></body></html>"#;
+        let report = detect_gate(in_house);
+        assert_eq!(report.kind, Some(GateKind::Paywall));
+        assert_eq!(report.vendor, None);
+    }
+
+    #[test]
+    fn test_login_wall() {
+        let html = r#"<html><body><p>Sign in to continue.</p>
+            <form><input type="email"><input type="password"></form></body></html>"#;
+        let report = detect_gate(html);
+        assert_eq!(report.kind, Some(GateKind::LoginWall));
+        assert!(report.signals.iter().any(|s| s == "password field"));
+    }
+
+    #[test]
+    fn test_free_article_not_gated() {
+        let body = "Plenty of free text. ".repeat(200);
+        let html = format!(
+            r#"<html><head><script src="https://cdn.tinypass.com/x.js"></script>
+            <script type="application/ld+json">{{"@type":"NewsArticle","isAccessibleForFree":true}}</script>
+            </head><body><article><p>{body}</p><p>Subscribe to read more stories like this.</p></article></body></html>"#
+        );
+        let report = detect_gate(&html);
+        assert!(!report.content_gated());
+        assert!(report.signals.is_empty());
+        assert_eq!(report.vendor, None);
+    }
+}
@@ -0,0 +1,1588 @@
+//! PDF Text and Metadata Extraction
+//!
+//! [`PdfDocument::parse`] reads a PDF without rendering it:
+//!
+//! - metadata from the document information dictionary (title, author,
+//!   subject, keywords, creator, producer, and dates)
+//! - the pages in page tree order, through their content streams, form
+//!   XObjects, and objects packed in object streams
+//! - text decoded through each font's `ToUnicode` map, else as WinAnsi
+//! - layout: text runs are placed by their text and graphics matrices,
+//!   grouped into lines by baseline and into paragraphs by line spacing;
+//!   larger type becomes headings and hyphenated line breaks are rejoined
+//! - images drawn on a page become placeholders
+//!
+//! Out of reach: encrypted documents, stream filters other than
+//! `FlateDecode`, composite fonts without a `ToUnicode` map, and scanned
+//! pages, which have no text at all.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::ops::Range;
+use std::rc::Rc;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+/// Kerning (in thousandths of an em) wide enough to read as a space in `TJ`
+const WORD_GAP: f64 = 200.0;
+
+/// Deepest form XObject nesting followed
+const MAX_FORM_DEPTH: usize = 8;
+
+/// Deepest array and dictionary nesting parsed
+const MAX_NESTING: usize = 64;
+
+/// Type this much larger than the body text is a heading
+const HEADING_SCALE: f64 = 1.25;
+
+/// A PDF's metadata and pages
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PdfDocument {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<String>,
+    /// Application the document was written in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creator: Option<String>,
+    /// Application that wrote the PDF
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub producer: Option<String>,
+    /// Creation date, ISO 8601 to the precision the PDF gives
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    pub pages: Vec<PdfPage>,
+}
+
+/// One page's content
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PdfPage {
+    /// 1-based page number
+    pub number: usize,
+    /// The page as Markdown, headings at level 2
+    pub markdown: String,
+    /// Images drawn on the page
+    pub images: usize,
+    #[serde(skip)]
+    blocks: Vec<Block>,
+}
+
+/// A laid-out piece of a page
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Heading(String),
+    Paragraph(String),
+    Image(String),
+}
+
+impl PdfDocument {
+    /// Parse `pdf`'s metadata and page text
+    pub fn parse(pdf: &[u8]) -> Result<Self> {
+        if !pdf.starts_with(b"%PDF-") {
+            bail!("Not a PDF");
+        }
+        let file = File::parse(pdf);
+        if file.trailer("Encrypt").is_some() {
+            bail!("Encrypted PDFs aren't supported");
+        }
+        let pages = file.pages();
+        if pages.is_empty() {
+            bail!("No pages found in the PDF");
+        }
+
+        let info = file
+            .trailer("Info")
+            .and_then(|info| file.resolve(info).as_dict())
+            .cloned()
+            .unwrap_or_default();
+        let text = |key: &str| {
+            file.resolve(info.get(key)?)
+                .as_string()
+                .map(|s| decode_text_string(s).trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let date = |key: &str| text(key).map(|raw| pdf_date(&raw));
+
+        let mut fonts = FontCache::default();
+        let pages = pages
+            .iter()
+            .enumerate()
+            .map(|(i, (page, resources))| {
+                let mut runs = Vec::new();
+                let content = file.page_content(page);
+                let mut interpreter = Interpreter {
+                    file: &file,
+                    fonts: &mut fonts,
+                    runs: &mut runs,
+                };
+                interpreter.run(&content, resources, Matrix::IDENTITY, 0);
+                let blocks = layout(&runs);
+                PdfPage {
+                    number: i + 1,
+                    markdown: render(&blocks, 2),
+                    images: blocks
+                        .iter()
+                        .filter(|block| matches!(block, Block::Image(_)))
+                        .count(),
+                    blocks,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            title: text("Title"),
+            author: text("Author"),
+            subject: text("Subject"),
+            keywords: text("Keywords"),
+            creator: text("Creator"),
+            producer: text("Producer"),
+            created: date("CreationDate"),
+            modified: date("ModDate"),
+            pages,
+        })
+    }
+
+    /// Whether any page has text
+    #[must_use]
+    pub fn has_text(&self) -> bool {
+        self.pages
+            .iter()
+            .flat_map(|page| &page.blocks)
+            .any(|block| !matches!(block, Block::Image(_)))
+    }
+
+    /// The document as Markdown under its title: with `per_page`, a
+    /// `## Page N` section per page, else paragraphs run on across page breaks
+    #[must_use]
+    pub fn to_markdown(&self, per_page: bool) -> String {
+        let mut markdown = String::new();
+        if let Some(title) = &self.title {
+            markdown.push_str(&format!("# {title}\n\n"));
+        }
+        if per_page {
+            for page in &self.pages {
+                markdown.push_str(&format!("## Page {}\n\n", page.number));
+                markdown.push_str(&render(&page.blocks, 3));
+                markdown.push_str("\n\n");
+            }
+        } else {
+            let mut blocks: Vec<Block> = Vec::new();
+            for block in self
+                .pages
+                .iter()
+                .flat_map(|page| page.blocks.iter().cloned())
+            {
+                // A paragraph cut by a page break continues in lowercase
+                if let (Some(Block::Paragraph(last)), Block::Paragraph(next)) =
+                    (blocks.last_mut(), &block)
+                {
+                    let open = !last.ends_with(['.', '!', '?', ':', '"', '”', ')']);
+                    if open && next.starts_with(char::is_lowercase) {
+                        join_line(last, next);
+                        continue;
+                    }
+                }
+                blocks.push(block);
+            }
+            markdown.push_str(&render(&blocks, 2));
+        }
+        let mut markdown = markdown.trim_end().to_string();
+        markdown.push('\n');
+        markdown
+    }
+}
+
+fn render(blocks: &[Block], heading_level: usize) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::Heading(text) => format!("{} {text}", "#".repeat(heading_level)),
+            Block::Paragraph(text) => text.clone(),
+            Block::Image(label) => format!("![{label}]()"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// `D:YYYYMMDDHHmmSS+HH'mm'` as ISO 8601, keeping the parts given
+fn pdf_date(raw: &str) -> String {
+    let date = raw.strip_prefix("D:").unwrap_or(raw);
+    let digits: String = date.chars().take_while(char::is_ascii_digit).collect();
+    if digits.len() < 4 {
+        return raw.to_string();
+    }
+    let mut iso = digits[..4].to_string();
+    for (from, separator) in [(4, '-'), (6, '-'), (8, 'T'), (10, ':'), (12, ':')] {
+        let Some(part) = digits.get(from..from + 2) else {
+            return iso;
+        };
+        iso.push(separator);
+        iso.push_str(part);
+    }
+    let zone = &date[digits.len()..];
+    match zone.chars().next() {
+        Some('Z') => iso.push('Z'),
+        Some(sign @ ('+' | '-')) => {
+            let offset: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+            if offset.len() >= 4 {
+                iso.push_str(&format!("{sign}{}:{}", &offset[..2], &offset[2..4]));
+            }
+        }
+        _ => {}
+    }
+    iso
+}
+
+// ─── Objects ────────────────────────────────────────────────────────────────
+
+/// A PDF object (booleans, which text extraction never needs, read as null)
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Number(f64),
+    Name(String),
+    String(Vec<u8>),
+    Array(Vec<Value>),
+    Dict(Dict),
+    Ref(u32),
+}
+
+type Dict = HashMap<String, Value>;
+
+impl Value {
+    fn as_dict(&self) -> Option<&Dict> {
+        match self {
+            Self::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_name(&self) -> Option<&str> {
+        match self {
+            Self::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn as_string(&self) -> Option<&[u8]> {
+        match self {
+            Self::String(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+/// A text string: UTF-16 with a byte order mark, else PDFDocEncoding
+/// (read as Latin-1)
+fn decode_text_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(b"\xfe\xff") {
+        Some(utf16) => utf16_be(utf16),
+        None => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+fn utf16_be(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// A token of a PDF file or content stream
+enum Token<'a> {
+    Value(Value),
+    /// A keyword: a content stream operator, `obj`, `stream`, ...
+    Operator(&'a [u8]),
+    DictEnd,
+    ArrayEnd,
+}
+
+struct Lexer<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(bytes: &'a [u8], pos: usize) -> Self {
+        Self {
+            bytes,
+            pos,
+            depth: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while self.peek().is_some_and(|b| !matches!(b, b'\n' | b'\r')) {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// The next value, skipping keywords
+    fn value(&mut self) -> Option<Value> {
+        loop {
+            match self.token()? {
+                Token::Value(value) => return Some(value),
+                Token::DictEnd | Token::ArrayEnd => return None,
+                Token::Operator(_) => {}
+            }
+        }
+    }
+
+    fn token(&mut self) -> Option<Token<'a>> {
+        self.skip_whitespace();
+        let b = self.peek()?;
+        let next = self.bytes.get(self.pos + 1).copied();
+        if matches!((b, next), (b'[', _) | (b'<', Some(b'<'))) && self.depth >= MAX_NESTING {
+            self.pos = self.bytes.len();
+            return None;
+        }
+        Some(match (b, next) {
+            (b'(', _) => Token::Value(Value::String(self.literal())),
+            (b'<', Some(b'<')) => {
+                self.pos += 2;
+                self.depth += 1;
+                let dict = self.dict();
+                self.depth -= 1;
+                Token::Value(Value::Dict(dict))
+            }
+            (b'<', _) => Token::Value(Value::String(self.hex())),
+            (b'>', Some(b'>')) => {
+                self.pos += 2;
+                Token::DictEnd
+            }
+            (b'[', _) => {
+                self.pos += 1;
+                self.depth += 1;
+                let mut items = Vec::new();
+                loop {
+                    match self.token() {
+                        Some(Token::Value(value)) => items.push(value),
+                        Some(Token::Operator(_)) => {}
+                        Some(Token::ArrayEnd | Token::DictEnd) | None => break,
+                    }
+                }
+                self.depth -= 1;
+                Token::Value(Value::Array(items))
+            }
+            (b']', _) => {
+                self.pos += 1;
+                Token::ArrayEnd
+            }
+            (b'/', _) => {
+                self.pos += 1;
+                Token::Value(Value::Name(self.name()))
+            }
+            // Stray delimiters
+            (b'{' | b'}' | b')' | b'>', _) => {
+                self.pos += 1;
+                Token::Operator(b"")
+            }
+            _ => self.word(),
+        })
+    }
+
+    /// A number, reference, or keyword
+    fn word(&mut self) -> Token<'a> {
+        let word = self.regular();
+        if matches!(word, b"true" | b"false" | b"null") {
+            return Token::Value(Value::Null);
+        }
+        let Some(number) = std::str::from_utf8(word)
+            .ok()
+            .and_then(|n| n.parse::<f64>().ok())
+        else {
+            return Token::Operator(word);
+        };
+        // `12 0 R` is a reference
+        if word.iter().all(u8::is_ascii_digit) {
+            let start = self.pos;
+            self.skip_whitespace();
+            let generation = self.regular();
+            self.skip_whitespace();
+            if generation.iter().all(u8::is_ascii_digit) && self.regular() == b"R" {
+                if let Ok(number) = u32::try_from(number as u64) {
+                    return Token::Value(Value::Ref(number));
+                }
+            }
+            self.pos = start;
+        }
+        Token::Value(Value::Number(number))
+    }
+
+    /// Bytes up to the next whitespace or delimiter (at least one)
+    fn regular(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while self.peek().is_some_and(is_regular) {
+            self.pos += 1;
+        }
+        if self.pos == start && self.pos < self.bytes.len() {
+            self.pos += 1;
+        }
+        &self.bytes[start..self.pos]
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(is_regular) {
+            self.pos += 1;
+        }
+        let raw = &self.bytes[start..self.pos];
+        let mut name = Vec::with_capacity(raw.len());
+        let mut i = 0;
+        while i < raw.len() {
+            // `#xx` escapes a byte
+            let escaped = raw
+                .get(i + 1..i + 3)
+                .filter(|_| raw[i] == b'#')
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(byte) => {
+                    name.push(byte);
+                    i += 3;
+                }
+                None => {
+                    name.push(raw[i]);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&name).into_owned()
+    }
+
+    fn dict(&mut self) -> Dict {
+        let mut dict = Dict::new();
+        loop {
+            match self.token() {
+                Some(Token::Value(Value::Name(key))) => {
+                    let Some(value) = self.value() else {
+                        break;
+                    };
+                    dict.insert(key, value);
+                }
+                Some(Token::Value(_) | Token::Operator(_)) => {}
+                Some(Token::DictEnd | Token::ArrayEnd) | None => break,
+            }
+        }
+        dict
+    }
+
+    fn literal(&mut self) -> Vec<u8> {
+        let mut s = Vec::new();
+        let mut depth = 0;
+        self.pos += 1;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'\\' => {
+                    let Some(escaped) = self.peek() else {
+                        break;
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => s.push(b'\n'),
+                        b'r' => s.push(b'\r'),
+                        b't' => s.push(b'\t'),
+                        b'b' => s.push(0x08),
+                        b'f' => s.push(0x0c),
+                        b'0'..=b'7' => {
+                            let mut octal = u32::from(escaped - b'0');
+                            for _ in 0..2 {
+                                let Some(digit @ b'0'..=b'7') = self.peek() else {
+                                    break;
+                                };
+                                octal = octal * 8 + u32::from(digit - b'0');
+                                self.pos += 1;
+                            }
+                            s.push(u8::try_from(octal & 0xff).unwrap_or_default());
+                        }
+                        // Line continuation
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        other => s.push(other),
+                    }
+                }
+                b'(' => {
+                    depth += 1;
+                    s.push(b);
+                }
+                b')' if depth == 0 => break,
+                b')' => {
+                    depth -= 1;
+                    s.push(b);
+                }
+                _ => s.push(b),
+            }
+        }
+        s
+    }
+
+    fn hex(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut digits = Vec::new();
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            if b == b'>' {
+                break;
+            }
+            if let Some(digit) = char::from(b).to_digit(16) {
+                digits.push(u8::try_from(digit).unwrap_or_default());
+            }
+        }
+        if digits.len() % 2 == 1 {
+            digits.push(0);
+        }
+        digits
+            .chunks(2)
+            .map(|pair| (pair[0] << 4) | pair[1])
+            .collect()
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r' | 0x0c | 0)
+}
+
+/// Not whitespace or a delimiter
+fn is_regular(b: u8) -> bool {
+    !is_whitespace(b) && !b"()<>[]{}/%".contains(&b)
+}
+
+/// An indirect object: its value and, for a stream, where its data is
+struct Indirect {
+    value: Value,
+    stream: Option<Range<usize>>,
+}
+
+/// A PDF's objects and trailer entries
+struct File<'a> {
+    bytes: &'a [u8],
+    objects: HashMap<u32, Indirect>,
+    /// Trailer and cross-reference stream dictionaries, in file order
+    trailers: Vec<Dict>,
+}
+
+impl<'a> File<'a> {
+    /// Index every `N G obj` in `bytes`, without trusting the
+    /// cross-reference table; later definitions (incremental updates)
+    /// replace earlier ones
+    fn parse(bytes: &'a [u8]) -> Self {
+        let mut file = Self {
+            bytes,
+            objects: HashMap::new(),
+            trailers: Vec::new(),
+        };
+        let mut pos = 0;
+        while let Some((number, body)) = next_object(bytes, pos) {
+            let mut lexer = Lexer::new(bytes, body);
+            let value = lexer.value().unwrap_or(Value::Null);
+            lexer.skip_whitespace();
+            let mut stream = None;
+            if bytes[lexer.pos..].starts_with(b"stream") {
+                let mut start = lexer.pos + b"stream".len();
+                if bytes[start..].starts_with(b"\r\n") {
+                    start += 2;
+                } else if bytes[start..].starts_with(b"\n") || bytes[start..].starts_with(b"\r") {
+                    start += 1;
+                }
+                let end = stream_end(bytes, start, &value);
+                stream = Some(start..end);
+                lexer.pos = end;
+            }
+            pos = lexer.pos.max(body);
+            if let Some(dict) = value.as_dict() {
+                if dict.get("Type").and_then(Value::as_name) == Some("XRef") {
+                    file.trailers.push(dict.clone());
+                }
+            }
+            file.objects.insert(number, Indirect { value, stream });
+        }
+        let mut from = 0;
+        while let Some(at) = find(&bytes[from..], b"trailer").map(|i| from + i) {
+            from = at + b"trailer".len();
+            if let Some(Value::Dict(dict)) = Lexer::new(bytes, from).value() {
+                file.trailers.push(dict);
+            }
+        }
+        file.unpack_object_streams();
+        file
+    }
+
+    /// Add the objects packed in object streams, unless defined directly
+    fn unpack_object_streams(&mut self) {
+        let streams: Vec<u32> = self
+            .objects
+            .iter()
+            .filter(|(_, object)| {
+                object
+                    .value
+                    .as_dict()
+                    .and_then(|dict| dict.get("Type"))
+                    .and_then(Value::as_name)
+                    == Some("ObjStm")
+            })
+            .map(|(number, _)| *number)
+            .collect();
+        for number in streams {
+            let Some(data) = self.stream(number) else {
+                continue;
+            };
+            let dict = self.objects[&number].value.as_dict();
+            let field = |key: &str| {
+                dict.and_then(|dict| dict.get(key))
+                    .and_then(Value::as_number)
+                    .unwrap_or(0.0) as usize
+            };
+            let (count, first) = (field("N"), field("First"));
+            let mut header = Lexer::new(&data, 0);
+            let mut entries = Vec::new();
+            for _ in 0..count {
+                match (header.value(), header.value()) {
+                    (Some(Value::Number(n)), Some(Value::Number(offset))) => {
+                        entries.push((n as u32, first + offset as usize));
+                    }
+                    _ => break,
+                }
+            }
+            for (n, offset) in entries {
+                if offset >= data.len() || self.objects.contains_key(&n) {
+                    continue;
+                }
+                let value = Lexer::new(&data, offset).value().unwrap_or(Value::Null);
+                self.objects.insert(
+                    n,
+                    Indirect {
+                        value,
+                        stream: None,
+                    },
+                );
+            }
+        }
+    }
+
+    /// The last trailer's (or cross-reference stream's) entry for `key`
+    fn trailer(&self, key: &str) -> Option<&Value> {
+        self.trailers.iter().rev().find_map(|dict| dict.get(key))
+    }
+
+    /// Follow references (a few levels deep) to a direct value
+    fn resolve<'v>(&'v self, mut value: &'v Value) -> &'v Value {
+        for _ in 0..8 {
+            let Value::Ref(number) = value else {
+                return value;
+            };
+            match self.objects.get(number) {
+                Some(object) => value = &object.value,
+                None => return &Value::Null,
+            }
+        }
+        &Value::Null
+    }
+
+    /// Decoded data of stream object `number`
+    fn stream(&self, number: u32) -> Option<Vec<u8>> {
+        let object = self.objects.get(&number)?;
+        let raw = &self.bytes[object.stream.clone()?];
+        let dict = object.value.as_dict()?;
+        let filters: Vec<&str> = match dict.get("Filter").map(|f| self.resolve(f)) {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Name(name)) => vec![name.as_str()],
+            Some(Value::Array(names)) => names.iter().filter_map(Value::as_name).collect(),
+            Some(_) => return None,
+        };
+        // PNG predictors only show up on images and cross-reference streams
+        let predicted = dict
+            .get("DecodeParms")
+            .map(|parms| self.resolve(parms))
+            .and_then(Value::as_dict)
+            .and_then(|parms| parms.get("Predictor"))
+            .and_then(Value::as_number)
+            .is_some_and(|predictor| predictor > 1.0);
+        match filters.as_slice() {
+            [] => Some(raw.to_vec()),
+            ["FlateDecode" | "Fl"] if !predicted => {
+                let mut out = Vec::new();
+                // A truncated or damaged stream still yields what inflated
+                let _ = flate2::read::ZlibDecoder::new(raw).read_to_end(&mut out);
+                (!out.is_empty()).then_some(out)
+            }
+            _ => None,
+        }
+    }
+
+    /// Pages in page tree order, with their (possibly inherited) resources
+    fn pages(&self) -> Vec<(Dict, Dict)> {
+        let mut pages = Vec::new();
+        let root = self
+            .trailer("Root")
+            .and_then(|root| self.resolve(root).as_dict())
+            .and_then(|catalog| catalog.get("Pages"));
+        if let Some(root) = root {
+            self.walk(root, &Dict::new(), &mut HashSet::new(), &mut pages);
+        }
+        if pages.is_empty() {
+            // No usable page tree: every page object, in object order
+            let mut numbers: Vec<&u32> = self.objects.keys().collect();
+            numbers.sort_unstable();
+            for number in numbers {
+                let Some(dict) = self.objects[number].value.as_dict() else {
+                    continue;
+                };
+                if dict.get("Type").and_then(Value::as_name) == Some("Page") {
+                    pages.push((dict.clone(), self.resources(dict, &Dict::new())));
+                }
+            }
+        }
+        pages
+    }
+
+    fn walk(
+        &self,
+        node: &Value,
+        inherited: &Dict,
+        seen: &mut HashSet<u32>,
+        pages: &mut Vec<(Dict, Dict)>,
+    ) {
+        if let Value::Ref(number) = node {
+            if !seen.insert(*number) {
+                return;
+            }
+        }
+        let Some(dict) = self.resolve(node).as_dict() else {
+            return;
+        };
+        let resources = self.resources(dict, inherited);
+        match dict.get("Kids").map(|kids| self.resolve(kids)) {
+            Some(Value::Array(kids)) => {
+                for kid in kids {
+                    self.walk(kid, &resources, seen, pages);
+                }
+            }
+            _ => pages.push((dict.clone(), resources)),
+        }
+    }
+
+    /// A page tree node's resources, else those it inherits
+    fn resources(&self, dict: &Dict, inherited: &Dict) -> Dict {
+        dict.get("Resources")
+            .and_then(|resources| self.resolve(resources).as_dict())
+            .cloned()
+            .unwrap_or_else(|| inherited.clone())
+    }
+
+    /// A page's content streams, decoded and concatenated
+    fn page_content(&self, page: &Dict) -> Vec<u8> {
+        let streams = match page.get("Contents") {
+            Some(contents) => match self.resolve(contents) {
+                Value::Array(items) => items.clone(),
+                _ => vec![contents.clone()],
+            },
+            None => Vec::new(),
+        };
+        let mut content = Vec::new();
+        for stream in streams {
+            if let Some(data) = match stream {
+                Value::Ref(number) => self.stream(number),
+                _ => None,
+            } {
+                content.extend_from_slice(&data);
+                content.push(b'\n');
+            }
+        }
+        content
+    }
+}
+
+/// Number and body start of the first `N G obj` at or after `pos`
+fn next_object(bytes: &[u8], mut pos: usize) -> Option<(u32, usize)> {
+    loop {
+        let at = pos + find(&bytes[pos..], b"obj")?;
+        pos = at + b"obj".len();
+        if bytes.get(pos).is_some_and(|&b| is_regular(b)) {
+            continue;
+        }
+        // Walk back over `G` and `N`
+        let mut i = at;
+        let mut numbers = Vec::new();
+        for _ in 0..2 {
+            let end = i;
+            while i > 0 && is_whitespace(bytes[i - 1]) {
+                i -= 1;
+            }
+            let digits_end = i;
+            while i > 0 && bytes[i - 1].is_ascii_digit() {
+                i -= 1;
+            }
+            if i == digits_end || (end == digits_end && !numbers.is_empty()) {
+                break;
+            }
+            numbers.push(&bytes[i..digits_end]);
+        }
+        if i > 0 && is_regular(bytes[i - 1]) {
+            continue;
+        }
+        if let [_, number] = numbers.as_slice() {
+            if let Some(number) = std::str::from_utf8(number)
+                .ok()
+                .and_then(|n| n.parse().ok())
+            {
+                return Some((number, pos));
+            }
+        }
+    }
+}
+
+/// End of the stream data starting at `start`: by a direct `/Length` when
+/// it lands on `endstream`, else by searching for `endstream`
+fn stream_end(bytes: &[u8], start: usize, dict: &Value) -> usize {
+    let length = dict
+        .as_dict()
+        .and_then(|dict| dict.get("Length"))
+        .and_then(Value::as_number)
+        .and_then(|length| start.checked_add(length as usize));
+    if let Some(end) = length.filter(|&end| end <= bytes.len()) {
+        let mut after = end;
+        while after < bytes.len() && is_whitespace(bytes[after]) {
+            after += 1;
+        }
+        if bytes[after..].starts_with(b"endstream") {
+            return end;
+        }
+    }
+    let mut end = find(&bytes[start..], b"endstream").map_or(bytes.len(), |i| start + i);
+    if bytes[..end].ends_with(b"\r\n") {
+        end -= 2;
+    } else if bytes[..end].ends_with(b"\n") || bytes[..end].ends_with(b"\r") {
+        end -= 1;
+    }
+    end.max(start)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// ─── Fonts ──────────────────────────────────────────────────────────────────
+
+/// How a font's string bytes become text
+#[derive(Debug, Default)]
+struct Font {
+    /// `ToUnicode` map entries, by code
+    to_unicode: HashMap<u32, String>,
+    /// Bytes per code in the `ToUnicode` map
+    code_len: usize,
+    /// A composite font without a `ToUnicode` map: glyph ids only
+    opaque: bool,
+}
+
+impl Font {
+    fn load(file: &File<'_>, font: &Value) -> Self {
+        let Some(dict) = file.resolve(font).as_dict() else {
+            return Self::default();
+        };
+        let cmap = match dict.get("ToUnicode") {
+            Some(Value::Ref(number)) => file.stream(*number),
+            _ => None,
+        };
+        match cmap {
+            Some(cmap) => {
+                let (to_unicode, code_len) = parse_cmap(&cmap);
+                Self {
+                    to_unicode,
+                    code_len,
+                    opaque: false,
+                }
+            }
+            None => Self {
+                opaque: dict.get("Subtype").and_then(Value::as_name) == Some("Type0"),
+                ..Self::default()
+            },
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        if self.opaque {
+            return String::new();
+        }
+        if self.to_unicode.is_empty() {
+            if let Some(utf16) = bytes.strip_prefix(b"\xfe\xff") {
+                return utf16_be(utf16);
+            }
+            let (text, _, _) = encoding_rs::WINDOWS_1252.decode(bytes);
+            return text.chars().filter(|c| !c.is_control()).collect();
+        }
+        let mut text = String::new();
+        for code in bytes.chunks(self.code_len.max(1)) {
+            let code = code_value(code);
+            match self.to_unicode.get(&code) {
+                Some(mapped) => text.push_str(mapped),
+                // Unmapped single bytes are usually plain ASCII
+                None if self.code_len == 1 => {
+                    if let Some(c) = char::from_u32(code).filter(|c| !c.is_control()) {
+                        text.push(c);
+                    }
+                }
+                None => {}
+            }
+        }
+        text
+    }
+}
+
+/// A big-endian character code
+fn code_value(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |code, &b| (code << 8) | u32::from(b))
+}
+
+/// A `ToUnicode` CMap's entries, and its bytes per code
+fn parse_cmap(cmap: &[u8]) -> (HashMap<u32, String>, usize) {
+    let mut map = HashMap::new();
+    let mut code_len = 1;
+    let mut lexer = Lexer::new(cmap, 0);
+    let mut operands: Vec<Value> = Vec::new();
+    while let Some(token) = lexer.token() {
+        match token {
+            Token::Value(value) => operands.push(value),
+            Token::Operator(b"endcodespacerange") => {
+                if let Some(Value::String(low)) = operands.first() {
+                    code_len = low.len().clamp(1, 4);
+                }
+                operands.clear();
+            }
+            Token::Operator(b"endbfchar") => {
+                for pair in operands.chunks(2) {
+                    if let [Value::String(src), Value::String(dst)] = pair {
+                        map.insert(code_value(src), utf16_be(dst));
+                    }
+                }
+                operands.clear();
+            }
+            Token::Operator(b"endbfrange") => {
+                for range in operands.chunks(3) {
+                    let [Value::String(low), Value::String(high), dst] = range else {
+                        continue;
+                    };
+                    let (low, high) = (code_value(low), code_value(high));
+                    if high < low || high - low > 0xffff {
+                        continue;
+                    }
+                    for (i, code) in (low..=high).enumerate() {
+                        let mapped = match dst {
+                            // Consecutive codes map to consecutive characters
+                            Value::String(start) if start.len() >= 2 => {
+                                let mut units: Vec<u16> = start
+                                    .chunks_exact(2)
+                                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                                    .collect();
+                                if let Some(last) = units.last_mut() {
+                                    *last = last.wrapping_add(u16::try_from(i).unwrap_or(0));
+                                }
+                                String::from_utf16_lossy(&units)
+                            }
+                            Value::Array(items) => match items.get(i) {
+                                Some(Value::String(s)) => utf16_be(s),
+                                _ => continue,
+                            },
+                            _ => continue,
+                        };
+                        map.insert(code, mapped);
+                    }
+                }
+                operands.clear();
+            }
+            Token::Operator(_) | Token::DictEnd | Token::ArrayEnd => operands.clear(),
+        }
+    }
+    (map, code_len)
+}
+
+/// Fonts loaded so far, by object number
+#[derive(Default)]
+struct FontCache(HashMap<u32, Rc<Font>>);
+
+impl FontCache {
+    fn get(&mut self, file: &File<'_>, font: &Value) -> Rc<Font> {
+        match font {
+            Value::Ref(number) => self
+                .0
+                .entry(*number)
+                .or_insert_with(|| Rc::new(Font::load(file, font)))
+                .clone(),
+            _ => Rc::new(Font::load(file, font)),
+        }
+    }
+}
+
+// ─── Content streams ────────────────────────────────────────────────────────
+
+/// An affine transform `[a b c d e f]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Matrix([f64; 6]);
+
+impl Matrix {
+    const IDENTITY: Self = Self([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn translate(x: f64, y: f64) -> Self {
+        Self([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    /// `self`, then `next`
+    fn then(self, next: Self) -> Self {
+        let [a1, b1, c1, d1, e1, f1] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = next.0;
+        Self([
+            a1 * a2 + b1 * c2,
+            a1 * b2 + b1 * d2,
+            c1 * a2 + d1 * c2,
+            c1 * b2 + d1 * d2,
+            e1 * a2 + f1 * c2 + e2,
+            e1 * b2 + f1 * d2 + f2,
+        ])
+    }
+
+    /// The last six numbers of `operands`
+    fn from_operands(operands: &[Value]) -> Option<Self> {
+        let numbers: Vec<f64> = operands.iter().filter_map(Value::as_number).collect();
+        let [a, b, c, d, e, f] = numbers.get(numbers.len().checked_sub(6)?..)? else {
+            return None;
+        };
+        Some(Self([*a, *b, *c, *d, *e, *f]))
+    }
+}
+
+/// Something drawn on a page, in content order
+#[derive(Debug, Clone, PartialEq)]
+enum Run {
+    /// Text with its baseline origin at `(x, y)`, in `size`-point type
+    Text {
+        x: f64,
+        y: f64,
+        size: f64,
+        text: String,
+    },
+    Image(String),
+}
+
+/// Collects what content streams draw
+struct Interpreter<'f, 'a> {
+    file: &'f File<'a>,
+    fonts: &'f mut FontCache,
+    runs: &'f mut Vec<Run>,
+}
+
+/// Graphics and text state within one content stream
+struct State {
+    ctm: Matrix,
+    saved: Vec<Matrix>,
+    tm: Matrix,
+    tlm: Matrix,
+    leading: f64,
+    font: Rc<Font>,
+    font_size: f64,
+}
+
+impl State {
+    fn next_line(&mut self) {
+        self.tlm = Matrix::translate(0.0, -self.leading).then(self.tlm);
+        self.tm = self.tlm;
+    }
+}
+
+impl Interpreter<'_, '_> {
+    /// Interpret `content` drawn with `resources` under `ctm`
+    fn run(&mut self, content: &[u8], resources: &Dict, ctm: Matrix, depth: usize) {
+        let file = self.file;
+        let resource = |kind: &str, name: &str| {
+            let group = file.resolve(resources.get(kind)?).as_dict()?;
+            group.get(name).cloned()
+        };
+        let mut state = State {
+            ctm,
+            saved: Vec::new(),
+            tm: Matrix::IDENTITY,
+            tlm: Matrix::IDENTITY,
+            leading: 0.0,
+            font: Rc::default(),
+            font_size: 12.0,
+        };
+        let mut lexer = Lexer::new(content, 0);
+        let mut operands: Vec<Value> = Vec::new();
+        while let Some(token) = lexer.token() {
+            let operator = match token {
+                Token::Value(value) => {
+                    operands.push(value);
+                    continue;
+                }
+                Token::Operator(operator) => operator,
+                Token::DictEnd | Token::ArrayEnd => continue,
+            };
+            // The `i`th operand from the end
+            let number = |i: usize| {
+                operands
+                    .len()
+                    .checked_sub(i)
+                    .and_then(|at| operands[at].as_number())
+                    .unwrap_or(0.0)
+            };
+            match operator {
+                b"q" => state.saved.push(state.ctm),
+                b"Q" => state.ctm = state.saved.pop().unwrap_or(ctm),
+                b"cm" => {
+                    if let Some(m) = Matrix::from_operands(&operands) {
+                        state.ctm = m.then(state.ctm);
+                    }
+                }
+                b"BT" => {
+                    state.tm = Matrix::IDENTITY;
+                    state.tlm = Matrix::IDENTITY;
+                }
+                b"Tf" => {
+                    state.font_size = number(1);
+                    let font = operands
+                        .iter()
+                        .find_map(Value::as_name)
+                        .and_then(|name| resource("Font", name));
+                    if let Some(font) = font {
+                        state.font = self.fonts.get(file, &font);
+                    }
+                }
+                b"TL" => state.leading = number(1),
+                b"Td" | b"TD" => {
+                    let (tx, ty) = (number(2), number(1));
+                    if operator == b"TD" {
+                        state.leading = -ty;
+                    }
+                    state.tlm = Matrix::translate(tx, ty).then(state.tlm);
+                    state.tm = state.tlm;
+                }
+                b"Tm" => {
+                    if let Some(m) = Matrix::from_operands(&operands) {
+                        state.tlm = m;
+                        state.tm = m;
+                    }
+                }
+                b"T*" => state.next_line(),
+                b"Tj" | b"'" | b"\"" => {
+                    if operator != b"Tj" {
+                        state.next_line();
+                    }
+                    if let Some(Value::String(s)) = operands.last() {
+                        let text = state.font.decode(s);
+                        self.show(&mut state, text);
+                    }
+                }
+                b"TJ" => {
+                    if let Some(Value::Array(items)) = operands.last() {
+                        let mut text = String::new();
+                        for item in items {
+                            match item {
+                                Value::String(s) => text.push_str(&state.font.decode(s)),
+                                Value::Number(n) if -n > WORD_GAP && !text.ends_with(' ') => {
+                                    text.push(' ');
+                                }
+                                _ => {}
+                            }
+                        }
+                        self.show(&mut state, text);
+                    }
+                }
+                b"Do" => {
+                    let xobject = operands
+                        .last()
+                        .and_then(Value::as_name)
+                        .and_then(|name| resource("XObject", name));
+                    if let Some(Value::Ref(number)) = xobject {
+                        self.draw(number, state.ctm, depth);
+                    }
+                }
+                b"ID" => {
+                    // Inline image data runs until `EI`
+                    lexer.pos = find(&content[lexer.pos..], b"EI")
+                        .map_or(content.len(), |at| lexer.pos + at + 2);
+                }
+                _ => {}
+            }
+            operands.clear();
+        }
+    }
+
+    /// Record `text` at the current text position and move past it
+    fn show(&mut self, state: &mut State, text: String) {
+        let [_, _, c, d, x, y] = state.tm.then(state.ctm).0;
+        let size = (state.font_size * c.hypot(d)).abs();
+        // Without font metrics, advance by an average glyph width
+        let advance = text.chars().count() as f64 * state.font_size * 0.5;
+        state.tm = Matrix::translate(advance, 0.0).then(state.tm);
+        if !text.trim().is_empty() {
+            self.runs.push(Run::Text { x, y, size, text });
+        }
+    }
+
+    /// Draw XObject `number`: an image placeholder, or a form's content
+    fn draw(&mut self, number: u32, ctm: Matrix, depth: usize) {
+        let file = self.file;
+        let Some(dict) = file.objects.get(&number).and_then(|o| o.value.as_dict()) else {
+            return;
+        };
+        match dict.get("Subtype").and_then(Value::as_name) {
+            Some("Image") => {
+                let dimension = |key: &str| {
+                    dict.get(key)
+                        .and_then(|value| file.resolve(value).as_number())
+                };
+                let label = match (dimension("Width"), dimension("Height")) {
+                    (Some(width), Some(height)) => format!("Image {width}×{height}"),
+                    _ => "Image".to_string(),
+                };
+                self.runs.push(Run::Image(label));
+            }
+            Some("Form") if depth < MAX_FORM_DEPTH => {
+                let Some(content) = file.stream(number) else {
+                    return;
+                };
+                let matrix = match dict.get("Matrix") {
+                    Some(Value::Array(items)) => Matrix::from_operands(items),
+                    _ => None,
+                };
+                let resources = dict
+                    .get("Resources")
+                    .and_then(|resources| file.resolve(resources).as_dict())
+                    .cloned()
+                    .unwrap_or_default();
+                let ctm = matrix.unwrap_or(Matrix::IDENTITY).then(ctm);
+                self.run(&content, &resources, ctm, depth + 1);
+            }
+            _ => {}
+        }
+    }
+}
+
+// ─── Layout ─────────────────────────────────────────────────────────────────
+
+/// A line of text, or an image
+struct Line {
+    y: f64,
+    size: f64,
+    x_end: f64,
+    text: String,
+    image: bool,
+}
+
+/// Group a page's runs into lines, then paragraphs and headings
+fn layout(runs: &[Run]) -> Vec<Block> {
+    let lines = lines(runs);
+    let body_size = body_size(&lines);
+    // Line spacing: most gaps between lines in the same type are within
+    // paragraphs, so the lower quartile is the spacing between lines
+    let mut gaps: Vec<f64> = lines
+        .windows(2)
+        .filter(|pair| !pair[0].image && !pair[1].image && pair[0].size == pair[1].size)
+        .map(|pair| pair[0].y - pair[1].y)
+        .filter(|&gap| gap > 0.0 && gap < body_size * 3.0)
+        .collect();
+    gaps.sort_by(f64::total_cmp);
+    let spacing = gaps.get(gaps.len() / 4).copied().unwrap_or(body_size * 1.2);
+
+    let mut blocks = Vec::new();
+    let mut paragraph: Option<(String, f64)> = None;
+    let mut previous: Option<&Line> = None;
+    let flush = |paragraph: &mut Option<(String, f64)>, blocks: &mut Vec<Block>| {
+        if let Some((text, size)) = paragraph.take() {
+            if size >= body_size * HEADING_SCALE && text.chars().count() <= 200 {
+                blocks.push(Block::Heading(text));
+            } else {
+                blocks.push(Block::Paragraph(text));
+            }
+        }
+    };
+    for line in &lines {
+        if line.image {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Image(line.text.clone()));
+            previous = None;
+            continue;
+        }
+        // Close, evenly spaced lines in the same type continue a paragraph
+        let continues = previous.is_some_and(|prev| {
+            let gap = prev.y - line.y;
+            gap > 0.0
+                && gap <= spacing * 1.4 + 0.5
+                && (prev.size - line.size).abs() <= 0.15 * line.size.max(prev.size)
+                && !starts_list_item(&line.text)
+        });
+        if continues {
+            if let Some((text, _)) = &mut paragraph {
+                join_line(text, &line.text);
+            }
+        } else {
+            flush(&mut paragraph, &mut blocks);
+            paragraph = Some((line.text.clone(), line.size));
+        }
+        previous = Some(line);
+    }
+    flush(&mut paragraph, &mut blocks);
+    blocks
+}
+
+/// Runs on the same baseline joined into lines
+fn lines(runs: &[Run]) -> Vec<Line> {
+    let mut lines: Vec<Line> = Vec::new();
+    for run in runs {
+        let Run::Text { x, y, size, text } = run else {
+            if let Run::Image(label) = run {
+                lines.push(Line {
+                    y: 0.0,
+                    size: 0.0,
+                    x_end: 0.0,
+                    text: label.clone(),
+                    image: true,
+                });
+            }
+            continue;
+        };
+        let (x, y, size) = (*x, *y, *size);
+        let width = text.chars().count() as f64 * size * 0.5;
+        match lines.last_mut() {
+            Some(line) if !line.image && (line.y - y).abs() <= 0.3 * size.max(line.size) => {
+                if x - line.x_end > 0.15 * size && !line.text.ends_with(' ') {
+                    line.text.push(' ');
+                }
+                line.text.push_str(text);
+                line.x_end = line.x_end.max(x + width);
+                line.size = line.size.max(size);
+            }
+            _ => lines.push(Line {
+                y,
+                size,
+                x_end: x + width,
+                text: text.clone(),
+                image: false,
+            }),
+        }
+    }
+    for line in lines.iter_mut().filter(|line| !line.image) {
+        line.text = line.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+    lines
+}
+
+/// The type size most of a page's text is set in
+fn body_size(lines: &[Line]) -> f64 {
+    let mut chars: HashMap<u64, usize> = HashMap::new();
+    for line in lines.iter().filter(|line| !line.image) {
+        *chars.entry((line.size * 10.0).round() as u64).or_default() += line.text.len();
+    }
+    chars
+        .into_iter()
+        .max_by_key(|&(size, count)| (count, size))
+        .map_or(12.0, |(size, _)| size as f64 / 10.0)
+}
+
+fn starts_list_item(text: &str) -> bool {
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    text.starts_with(['•', '◦', '▪', '–', '-', '*'])
+        || (digits > 0 && text[digits..].starts_with(['.', ')']))
+}
+
+/// Append a wrapped line, rejoining a word hyphenated across the break
+fn join_line(paragraph: &mut String, line: &str) {
+    let hyphenated = paragraph.ends_with('-')
+        && paragraph
+            .chars()
+            .rev()
+            .nth(1)
+            .is_some_and(char::is_alphabetic)
+        && line.starts_with(char::is_lowercase);
+    if hyphenated {
+        paragraph.pop();
+    } else {
+        paragraph.push(' ');
+    }
+    paragraph.push_str(line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A PDF of `objects`, numbered from 1, with object 1 as the catalog
+    fn pdf(objects: &[Vec<u8>], info: Option<u32>) -> Vec<u8> {
+        let mut pdf = b"%PDF-1.5\n".to_vec();
+        for (n, object) in objects.iter().enumerate() {
+            pdf.extend(format!("{} 0 obj\n", n + 1).bytes());
+            pdf.extend_from_slice(object);
+            pdf.extend(b"\nendobj\n");
+        }
+        let info = info.map(|n| format!(" /Info {n} 0 R")).unwrap_or_default();
+        pdf.extend(format!("trailer\n<< /Root 1 0 R{info} >>\n%%EOF\n").bytes());
+        pdf
+    }
+
+    fn stream(dict: &str, data: &[u8]) -> Vec<u8> {
+        let mut object = format!("<< {dict} /Length {} >>\nstream\n", data.len()).into_bytes();
+        object.extend_from_slice(data);
+        object.extend(b"\nendstream");
+        object
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_parse_layout_and_metadata() {
+        let page_one = b"BT /F1 20 Tf 72 740 Td (Quarterly Report) Tj ET
+            BT /F1 11 Tf 13 TL 72 700 Td (Revenue grew in every re-) Tj T*
+            [(gion) -250 (this quarter.)] TJ 0 -30 Td (\\223Costs\\224 fell.) Tj ET
+            q 200 0 0 100 72 400 cm /Im1 Do Q
+            BT /F1 11 Tf 72 380 Td (The outlook is) Tj ET";
+        let page_two = deflate(b"BT /F2 11 Tf 72 740 Td <00480069> Tj 20 0 Td <0021> Tj ET");
+        let cmap = b"/CIDInit /ProcSet findresource begin 12 dict begin begincmap
+            1 begincodespacerange <0000> <FFFF> endcodespacerange
+            2 beginbfchar <0021> <0021> <0048> <0068> endbfchar
+            1 beginbfrange <0069> <006A> <0069> endbfrange
+            endcmap end end";
+        let objects = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            b"<< /Type /Pages /Kids [3 0 R 5 0 R] /Count 2
+               /Resources << /Font << /F1 7 0 R /F2 8 0 R >> >> >>"
+                .to_vec(),
+            b"<< /Type /Page /Parent 2 0 R /Contents 4 0 R
+               /Resources << /Font << /F1 7 0 R >> /XObject << /Im1 9 0 R >> >> >>"
+                .to_vec(),
+            stream("", page_one),
+            b"<< /Type /Page /Parent 2 0 R /Contents [6 0 R] >>".to_vec(),
+            stream("/Filter /FlateDecode", &page_two),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_vec(),
+            b"<< /Type /Font /Subtype /Type0 /BaseFont /Subset /ToUnicode 11 0 R >>".to_vec(),
+            stream("/Subtype /Image /Width 640 /Height 320", b"\xff\xd8"),
+            b"<< /Title (Q3 \\(draft\\)) /Author <FEFF004D0069006B006B006F>
+               /CreationDate (D:20261005093000+02'00') >>"
+                .to_vec(),
+            stream("", cmap),
+        ];
+        let document = PdfDocument::parse(&pdf(&objects, Some(10))).unwrap();
+
+        assert_eq!(document.title.as_deref(), Some("Q3 (draft)"));
+        assert_eq!(document.author.as_deref(), Some("Mikko"));
+        assert_eq!(
+            document.created.as_deref(),
+            Some("2026-10-05T09:30:00+02:00")
+        );
+        assert_eq!(document.pages.len(), 2);
+        assert_eq!(document.pages[0].images, 1);
+        assert_eq!(
+            document.pages[0].markdown,
+            "## Quarterly Report\n\n\
+             Revenue grew in every region this quarter.\n\n\
+             “Costs” fell.\n\n\
+             ![Image 640×320]()\n\n\
+             The outlook is"
+        );
+        assert_eq!(document.pages[1].markdown, "hi !");
+        assert!(document.has_text());
+
+        assert_eq!(
+            document.to_markdown(false),
+            "# Q3 (draft)\n\n## Quarterly Report\n\n\
+             Revenue grew in every region this quarter.\n\n\
+             “Costs” fell.\n\n![Image 640×320]()\n\nThe outlook is hi !\n"
+        );
+        let per_page = document.to_markdown(true);
+        assert!(per_page.contains("## Page 1\n\n### Quarterly Report\n\n"));
+        assert!(per_page.ends_with("## Page 2\n\nhi !\n"));
+    }
+
+    #[test]
+    fn test_parse_object_streams() {
+        let pages = "<< /Type /Pages /Kids [4 0 R] /Count 1 >>";
+        let page = "<< /Type /Page /Parent 3 0 R /Contents 5 0 R >>";
+        let header = format!("3 0 4 {} ", pages.len() + 1);
+        let packed = deflate(format!("{header}{pages} {page}").as_bytes());
+        let objects = vec![
+            b"<< /Type /Catalog /Pages 3 0 R >>".to_vec(),
+            stream(
+                &format!(
+                    "/Type /ObjStm /N 2 /First {} /Filter /FlateDecode",
+                    header.len()
+                ),
+                &packed,
+            ),
+        ];
+        let mut bytes = pdf(&objects, None);
+        let content = stream("", b"BT 10 TL 0 700 Td (Packed) Tj T* (objects) Tj ET");
+        let at = find(&bytes, b"trailer").unwrap();
+        bytes.splice(
+            at..at,
+            [b"5 0 obj\n".as_slice(), &content, b"\nendobj\n"].concat(),
+        );
+
+        let document = PdfDocument::parse(&bytes).unwrap();
+        assert_eq!(document.pages.len(), 1);
+        assert_eq!(document.pages[0].markdown, "Packed objects");
+        assert_eq!(document.title, None);
+    }
+
+    #[test]
+    fn test_parse_bogus_stream_length() {
+        // A /Length past the end of memory falls back to finding `endstream`
+        let content =
+            b"<< /Length 99999999999999999999 >>\nstream\nBT 0 700 Td (Found) Tj ET\nendstream";
+        let objects = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+            b"<< /Type /Page /Parent 2 0 R /Contents 4 0 R >>".to_vec(),
+            content.to_vec(),
+        ];
+        let document = PdfDocument::parse(&pdf(&objects, None)).unwrap();
+        assert_eq!(document.pages[0].markdown, "Found");
+    }
+
+    #[test]
+    fn test_parse_rejects() {
+        assert!(PdfDocument::parse(b"<html>").is_err());
+        let encrypted = b"%PDF-1.4\ntrailer\n<< /Root 1 0 R /Encrypt 2 0 R >>\n";
+        assert!(PdfDocument::parse(encrypted).is_err());
+        assert!(PdfDocument::parse(b"%PDF-1.4\n%%EOF\n").is_err());
+        assert_eq!(pdf_date("D:2026"), "2026");
+        assert_eq!(pdf_date("D:20261005Z"), "2026-10-05");
+    }
+}
@@ -0,0 +1,415 @@
+//! External Subcommands
+//!
+//! `nab <name> [args...]` runs `nab-<name>` from `PATH` when `<name>` isn't a
+//! built-in command, the way cargo and git do, so site-specific extractors can
+//! live outside this crate. `nab plugins` lists what's installed.
+//!
+//! A plugin gets its arguments as given, `NAB_PLUGIN_PROTOCOL` (the protocol
+//! version) and `NAB_BIN` (the running `nab`) in its environment, and its
+//! stdin and stdout are a JSON-lines pipe to `nab`. Every message is one JSON
+//! object on one line with `"nab": 1`. Lines a plugin prints without that key
+//! are passed through to `nab`'s stdout; stderr isn't touched.
+//!
+//! `nab` first writes a greeting with the global options:
+//!
+//! ```json
+//! {"nab": 1, "type": "hello", "version": "0.3.0", "verbose": false, "seed": null}
+//! ```
+//!
+//! A plugin fetches pages through `nab`'s engine (fingerprint, cookies,
+//! proxies, retries, per-domain config) by writing a request with an id of
+//! its choice. Every `options` field is optional:
+//!
+//! ```json
+//! {"nab": 1, "type": "fetch", "id": 7, "url": "https://example.com/",
+//!  "options": {"profile": "firefox", "cookies": "none", "proxy": null,
+//!              "headers": {"Accept-Language": "fi"}, "retries": 2,
+//!              "timeout_secs": 20, "max_redirects": 10, "raw": false}}
+//! ```
+//!
+//! and reads the answer with the same id from its stdin. Requests run
+//! concurrently, so answers can come back in any order:
+//!
+//! ```json
+//! {"nab": 1, "type": "response", "id": 7, "status": 200,
+//!  "url": "https://example.com/", "headers": {"content-type": ["text/html"]},
+//!  "timings": {"ttfb_ms": 81.2, "...": "..."},
+//!  "body": {"kind": "markdown", "text": "# Example Domain\n..."}}
+//! {"nab": 1, "type": "error", "id": 8, "message": "HTTP request timed out"}
+//! ```
+//!
+//! HTML bodies arrive as `markdown` (`text` with `"raw": true`), JSON as
+//! `json` with a `value`, binary content as `bytes` with `base64`, and
+//! everything else as `text`. `nab` exits with the plugin's exit code.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::config::NabConfig;
+use crate::request_options::{RequestOptions, RequestOptionsBuilder};
+use crate::response::{Body, Response};
+use crate::timing::Timings;
+
+/// Version carried in every message's `nab` field
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Executable name prefix of plugins
+pub const PREFIX: &str = "nab-";
+
+/// Global flags handed to a plugin in the greeting
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Globals {
+    pub verbose: bool,
+    pub seed: Option<u64>,
+}
+
+/// A request from a plugin
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PluginMessage {
+    /// Fetch a page and answer with a `response` or `error` for `id`
+    Fetch {
+        id: Value,
+        url: String,
+        #[serde(default)]
+        options: PluginFetchOptions,
+    },
+}
+
+impl PluginMessage {
+    /// Parse a line of plugin output; `None` for lines meant for the user
+    pub fn parse(line: &str) -> Option<Result<Self>> {
+        let value: Value = serde_json::from_str(line).ok()?;
+        let version = value.get("nab")?;
+        if version != PROTOCOL_VERSION {
+            return Some(Err(anyhow::anyhow!(
+                "Unsupported plugin protocol version {version} (nab speaks {PROTOCOL_VERSION})"
+            )));
+        }
+        Some(serde_json::from_value(value).context("Invalid plugin message"))
+    }
+}
+
+/// What a plugin can set on a fetch
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginFetchOptions {
+    /// chrome, firefox, safari, or random
+    pub profile: Option<String>,
+    /// auto, none, or a browser name
+    pub cookies: Option<String>,
+    pub proxy: Option<String>,
+    pub headers: BTreeMap<String, String>,
+    pub retries: Option<u32>,
+    pub timeout_secs: Option<u64>,
+    pub max_redirects: Option<usize>,
+    /// Keep HTML as served instead of converting it to Markdown
+    pub raw: bool,
+}
+
+impl PluginFetchOptions {
+    fn builder(&self) -> RequestOptionsBuilder {
+        let mut builder = RequestOptions::builder();
+        if let Some(profile) = &self.profile {
+            builder = builder.profile_name(profile);
+        }
+        if let Some(cookies) = &self.cookies {
+            builder = builder.cookies(cookies);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy);
+        }
+        for (name, value) in &self.headers {
+            builder = builder.header(format!("{name}: {value}"));
+        }
+        if let Some(retries) = self.retries {
+            builder = builder.retries(retries);
+        }
+        if let Some(secs) = self.timeout_secs {
+            builder = builder.timeout(Duration::from_secs(secs));
+        }
+        if let Some(max) = self.max_redirects {
+            builder = builder.max_redirects(max);
+        }
+        builder
+    }
+}
+
+/// A message to a plugin
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HostMessage {
+    Hello {
+        version: &'static str,
+        verbose: bool,
+        seed: Option<u64>,
+    },
+    Response {
+        id: Value,
+        status: u16,
+        url: String,
+        headers: BTreeMap<String, Vec<String>>,
+        timings: Box<Timings>,
+        body: WireBody,
+    },
+    Error {
+        id: Value,
+        message: String,
+    },
+}
+
+impl HostMessage {
+    /// The message as one protocol line (without the newline)
+    #[must_use]
+    pub fn to_line(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Value::Object(map) = &mut value {
+            map.insert("nab".to_string(), PROTOCOL_VERSION.into());
+        }
+        value.to_string()
+    }
+
+    fn response(id: Value, page: Response) -> Self {
+        let mut headers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (name, value) in &page.headers {
+            headers
+                .entry(name.as_str().to_string())
+                .or_default()
+                .push(String::from_utf8_lossy(value.as_bytes()).into_owned());
+        }
+        Self::Response {
+            id,
+            status: page.status.as_u16(),
+            url: page.url.to_string(),
+            headers,
+            timings: Box::new(page.timings),
+            body: page.body.into(),
+        }
+    }
+}
+
+/// [`Body`] as it travels over the pipe
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum WireBody {
+    Text { text: String },
+    Markdown { text: String },
+    Json { value: Value },
+    Bytes { base64: String },
+}
+
+impl From<Body> for WireBody {
+    fn from(body: Body) -> Self {
+        match body {
+            Body::Text(text) => Self::Text { text },
+            Body::Markdown(text) => Self::Markdown { text },
+            Body::Json(value) => Self::Json { value },
+            Body::Bytes(bytes) => Self::Bytes {
+                base64: crate::http_auth::base64_encode(&bytes),
+            },
+        }
+    }
+}
+
+/// Path of the `nab-<name>` plugin, searching `PATH` in order
+#[must_use]
+pub fn find(name: &str) -> Option<PathBuf> {
+    let dirs = std::env::var_os("PATH")?;
+    std::env::split_paths(&dirs)
+        .flat_map(|dir| candidates(&dir, name))
+        .find(|path| is_executable(path))
+}
+
+/// Installed plugins by name; on duplicate names the first on `PATH` wins
+#[must_use]
+pub fn discover() -> BTreeMap<String, PathBuf> {
+    let mut plugins = BTreeMap::new();
+    let Some(dirs) = std::env::var_os("PATH") else {
+        return plugins;
+    };
+    for dir in std::env::split_paths(&dirs) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            let Some(name) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.strip_prefix(PREFIX))
+            else {
+                continue;
+            };
+            if !name.is_empty() && is_executable(&path) {
+                plugins.entry(name.to_string()).or_insert(path);
+            }
+        }
+    }
+    plugins
+}
+
+fn candidates(dir: &Path, name: &str) -> Vec<PathBuf> {
+    let base = dir.join(format!("{PREFIX}{name}"));
+    if cfg!(windows) {
+        vec![base.with_extension("exe"), base.with_extension("cmd")]
+    } else {
+        vec![base]
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("exe") || ext.eq_ignore_ascii_case("cmd"))
+}
+
+/// Run `program` with `args`, answering its fetch requests until it exits
+pub async fn run(program: &Path, args: &[String], globals: Globals) -> Result<ExitStatus> {
+    let mut child = tokio::process::Command::new(program)
+        .args(args)
+        .env("NAB_PLUGIN_PROTOCOL", PROTOCOL_VERSION.to_string())
+        .env("NAB_BIN", std::env::current_exe()?)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Failed to start {}", program.display()))?;
+    let mut stdin = child.stdin.take().context("Plugin stdin not piped")?;
+    let stdout = child.stdout.take().context("Plugin stdout not piped")?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            // A plugin that closed its stdin just doesn't read answers
+            if stdin.write_all(format!("{line}\n").as_bytes()).await.is_err() {
+                break;
+            }
+            let _ = stdin.flush().await;
+        }
+    });
+    let hello = HostMessage::Hello {
+        version: env!("CARGO_PKG_VERSION"),
+        verbose: globals.verbose,
+        seed: globals.seed,
+    };
+    let _ = tx.send(hello.to_line());
+
+    let config = NabConfig::load()?;
+    let mut lines = BufReader::new(stdout).lines();
+    while let Some(line) = lines.next_line().await? {
+        match PluginMessage::parse(&line) {
+            None => println!("{line}"),
+            Some(Err(e)) => eprintln!("⚠️  {}: {e:#}", program.display()),
+            Some(Ok(PluginMessage::Fetch { id, url, options })) => {
+                let (tx, config) = (tx.clone(), config.clone());
+                tokio::spawn(async move {
+                    debug!("Plugin fetch {id}: {url}");
+                    let answer = match fetch(&url, &options, &config).await {
+                        Ok(page) => HostMessage::response(id, page),
+                        Err(e) => HostMessage::Error {
+                            id,
+                            message: format!("{e:#}"),
+                        },
+                    };
+                    let _ = tx.send(answer.to_line());
+                });
+            }
+        }
+    }
+    drop(tx);
+    let status = child.wait().await?;
+    writer.abort();
+    Ok(status)
+}
+
+async fn fetch(url: &str, options: &PluginFetchOptions, config: &NabConfig) -> Result<Response> {
+    let request = options.builder().domain_config(url, config).build()?;
+    let page = request.fetch(url).await?;
+    Ok(if options.raw { page } else { page.parsed() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_messages() {
+        assert!(PluginMessage::parse("plain output").is_none());
+        assert!(PluginMessage::parse(r#"{"title": "not for nab"}"#).is_none());
+        assert!(PluginMessage::parse(r#"{"nab": 2, "type": "fetch"}"#)
+            .unwrap()
+            .is_err());
+        assert!(PluginMessage::parse(r#"{"nab": 1, "type": "launch"}"#)
+            .unwrap()
+            .is_err());
+
+        let fetch = PluginMessage::parse(
+            r#"{"nab": 1, "type": "fetch", "id": "a", "url": "https://example.com/",
+                "options": {"profile": "safari", "headers": {"X-A": "1"}, "raw": true}}"#,
+        )
+        .unwrap()
+        .unwrap();
+        let PluginMessage::Fetch { id, url, options } = fetch;
+        assert_eq!(id, "a");
+        assert_eq!(url, "https://example.com/");
+        assert!(options.raw);
+        let request = options.builder().cookies("none").build().unwrap();
+        assert_eq!(request.profile, crate::ProfileChoice::Safari);
+        assert_eq!(request.headers["x-a"], "1");
+    }
+
+    #[test]
+    fn test_host_message_lines() {
+        let line = HostMessage::Error {
+            id: 3.into(),
+            message: "boom".to_string(),
+        }
+        .to_line();
+        let value: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({"nab": 1, "type": "error", "id": 3, "message": "boom"})
+        );
+
+        let body = serde_json::to_value(WireBody::from(Body::Bytes(b"ada:pa".to_vec()))).unwrap();
+        assert_eq!(body, serde_json::json!({"kind": "bytes", "base64": "YWRhOnBh"}));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover_on_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("nab-plugins-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plugin = dir.join("nab-hello");
+        std::fs::write(&plugin, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let not_executable = dir.join("nab-notes");
+        std::fs::write(&not_executable, "").unwrap();
+        std::fs::set_permissions(&not_executable, std::fs::Permissions::from_mode(0o644))
+            .unwrap();
+
+        assert_eq!(candidates(&dir, "hello"), vec![plugin.clone()]);
+        assert!(is_executable(&plugin));
+        assert!(!is_executable(&not_executable));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,295 @@
+//! Domain Policy
+//!
+//! Pins the hosts nab may fetch from, so an organization can be sure it
+//! never reaches outside an approved scope:
+//!
+//! ```json
+//! {"policy": {"allow": ["example.com", "docs.rs"], "deny": ["admin.example.com"]}}
+//! ```
+//!
+//! A pattern matches its host and every subdomain, like the config's
+//! `domains`. Deny wins over allow; with no allow list every host not
+//! denied is allowed. The policy is read from the user's config and from
+//! [`SYSTEM_PATH`]; both apply, and no command-line flag turns either off.
+//! Programs that don't [`install`] it (library, C, and Python callers) get
+//! it loaded on first use, and if it can't be read no host is allowed.
+//!
+//! It is checked where requests start (the URL a command fetches, the links
+//! a crawl queues), at every redirect hop, and at every DNS lookup. Every
+//! client nab builds starts from [`client_builder`] (or
+//! [`blocking_client_builder`]), which adds the redirect and DNS checks.
+//! URLs with an IP address never reach the DNS check, and a proxy looks up
+//! names itself, so requests are sent through [`send`] (or
+//! [`check_request`]) and raw connections (TLS audits, timing probes,
+//! HTTP/3) call [`check_host`] first. Each violation is logged and fails
+//! with a [`PolicyViolation`].
+
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Policy file that applies to every user of the machine
+pub const SYSTEM_PATH: &str = "/etc/nab/policy.json";
+
+/// Redirects followed by clients from [`client_builder`], as by reqwest
+const MAX_REDIRECTS: usize = 10;
+
+/// Policies for this run: set by [`install`], else loaded on first use
+static POLICIES: OnceLock<Result<Vec<DomainPolicy>, String>> = OnceLock::new();
+
+/// Allowed and denied host patterns
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DomainPolicy {
+    /// Only these hosts (and their subdomains), if any are given
+    pub allow: Vec<String>,
+    /// Never these hosts (and their subdomains)
+    pub deny: Vec<String>,
+}
+
+/// Why the domain policy refused a host
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PolicyViolation {
+    #[error("{host} is denied by the domain policy ({pattern})")]
+    Denied { host: String, pattern: String },
+    #[error("{host} isn't allowed by the domain policy")]
+    NotAllowed { host: String },
+    #[error("The domain policy couldn't be read, so no host is allowed: {0}")]
+    Unreadable(String),
+}
+
+impl DomainPolicy {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether `host` may be fetched from
+    pub fn check_host(&self, host: &str) -> Result<(), PolicyViolation> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if let Some(pattern) = self.deny.iter().find(|pattern| matches(pattern, host)) {
+            return Err(PolicyViolation::Denied {
+                host: host.to_string(),
+                pattern: pattern.clone(),
+            });
+        }
+        if self.allow.is_empty() || self.allow.iter().any(|pattern| matches(pattern, host)) {
+            Ok(())
+        } else {
+            Err(PolicyViolation::NotAllowed {
+                host: host.to_string(),
+            })
+        }
+    }
+}
+
+fn matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern
+        .trim()
+        .trim_start_matches("*.")
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    host == pattern || host.ends_with(&format!(".{pattern}"))
+}
+
+/// The policies of [`SYSTEM_PATH`] and the user's config, the empty ones left out
+pub fn load() -> Result<Vec<DomainPolicy>> {
+    let mut policies = Vec::new();
+    let system = Path::new(SYSTEM_PATH);
+    if system.exists() {
+        let text = std::fs::read_to_string(system)
+            .with_context(|| format!("Failed to read {}", system.display()))?;
+        policies.push(
+            serde_json::from_str(&text)
+                .with_context(|| format!("Invalid domain policy {}", system.display()))?,
+        );
+    }
+    policies.push(crate::config::NabConfig::load()?.policy);
+    policies.retain(|policy: &DomainPolicy| !policy.is_empty());
+    Ok(policies)
+}
+
+/// Enforce `policies` for the rest of the run
+pub fn install(policies: Vec<DomainPolicy>) {
+    let _ = POLICIES.set(Ok(policies));
+}
+
+fn installed() -> Result<&'static [DomainPolicy], PolicyViolation> {
+    match POLICIES.get_or_init(|| load().map_err(|e| format!("{e:#}"))) {
+        Ok(policies) => Ok(policies),
+        Err(error) => Err(PolicyViolation::Unreadable(error.clone())),
+    }
+}
+
+/// Whether a policy is in force
+#[must_use]
+pub fn is_active() -> bool {
+    !installed().is_ok_and(<[DomainPolicy]>::is_empty)
+}
+
+/// Whether every policy allows `host`, logging a refusal
+pub fn check_host(host: &str) -> Result<(), PolicyViolation> {
+    let result = installed().and_then(|policies| {
+        policies
+            .iter()
+            .try_for_each(|policy| policy.check_host(host))
+    });
+    if let Err(violation) = &result {
+        tracing::warn!(target: "nab::policy", "Blocked: {violation}");
+    }
+    result
+}
+
+/// Whether every policy allows the host of `url` (URLs without one, such
+/// as `data:`, have nothing to check)
+pub fn check(url: &str) -> Result<(), PolicyViolation> {
+    match url::Url::parse(url) {
+        Ok(url) => check_url(&url),
+        Err(_) => Ok(()),
+    }
+}
+
+pub fn check_url(url: &url::Url) -> Result<(), PolicyViolation> {
+    url.host_str().map_or(Ok(()), check_host)
+}
+
+/// `request` if the policy allows its URL
+///
+/// Doesn't clone the body, so it works for streaming requests too.
+pub fn check_request(request: reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    check_url(request.url())?;
+    Ok(reqwest::RequestBuilder::from_parts(client, request))
+}
+
+/// Send `request` if the policy allows its URL
+pub async fn send(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    Ok(check_request(request)?.send().await?)
+}
+
+/// Like [`check`], without logging (for filtering, e.g. a crawl's links)
+#[must_use]
+pub fn allows(url: &str) -> bool {
+    let Some(host) = url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+    else {
+        return true;
+    };
+    installed().is_ok_and(|policies| {
+        policies
+            .iter()
+            .all(|policy| policy.check_host(&host).is_ok())
+    })
+}
+
+/// Follow up to `max` redirects, none to a host the policy refuses
+#[must_use]
+pub fn redirects(max: usize) -> reqwest::redirect::Policy {
+    if !is_active() {
+        return reqwest::redirect::Policy::limited(max);
+    }
+    reqwest::redirect::Policy::custom(move |attempt| follow(attempt, max))
+}
+
+/// Follow `attempt` unless it's redirect number `max + 1` or goes to a host
+/// the policy refuses (the decision of [`redirects`], for custom policies)
+pub(crate) fn follow(attempt: reqwest::redirect::Attempt, max: usize) -> reqwest::redirect::Action {
+    // `previous` starts with the original URL: one more than the redirects so far
+    if let Err(violation) = check_url(attempt.url()) {
+        attempt.error(violation)
+    } else if attempt.previous().len() > max {
+        attempt.error("too many redirects")
+    } else {
+        attempt.follow()
+    }
+}
+
+/// Refuse DNS lookups of hosts the policy doesn't allow
+struct PolicyResolver;
+
+impl reqwest::dns::Resolve for PolicyResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            check_host(&host)?;
+            let addrs: Vec<_> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let addrs: reqwest::dns::Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Async client builder with the host aliases and the policy's redirect
+/// and DNS checks; every client nab builds starts here
+pub fn client_builder() -> reqwest::ClientBuilder {
+    apply(crate::hosts::apply(reqwest::Client::builder())).redirect(redirects(MAX_REDIRECTS))
+}
+
+/// [`client_builder`] for blocking clients
+pub fn blocking_client_builder() -> reqwest::blocking::ClientBuilder {
+    apply_blocking(crate::hosts::apply_blocking(
+        reqwest::blocking::Client::builder(),
+    ))
+    .redirect(redirects(MAX_REDIRECTS))
+}
+
+/// Check the hosts an async client looks up
+pub fn apply(builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    if is_active() {
+        builder.dns_resolver(Arc::new(PolicyResolver))
+    } else {
+        builder
+    }
+}
+
+/// [`apply`] for a blocking client builder
+pub fn apply_blocking(
+    builder: reqwest::blocking::ClientBuilder,
+) -> reqwest::blocking::ClientBuilder {
+    if is_active() {
+        builder.dns_resolver(Arc::new(PolicyResolver))
+    } else {
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deny_wins_over_allow() {
+        let policy: DomainPolicy = serde_json::from_str(
+            r#"{"allow": ["example.com", "*.docs.rs"], "deny": ["admin.example.com"]}"#,
+        )
+        .unwrap();
+        assert!(policy.check_host("example.com").is_ok());
+        assert!(policy.check_host("WWW.Example.com.").is_ok());
+        assert!(policy.check_host("docs.rs").is_ok());
+        assert_eq!(
+            policy.check_host("api.admin.example.com"),
+            Err(PolicyViolation::Denied {
+                host: "api.admin.example.com".into(),
+                pattern: "admin.example.com".into(),
+            })
+        );
+        assert!(matches!(
+            policy.check_host("notexample.com"),
+            Err(PolicyViolation::NotAllowed { .. })
+        ));
+
+        let deny_only = DomainPolicy {
+            allow: Vec::new(),
+            deny: vec!["tracker.example".into()],
+        };
+        assert!(deny_only.check_host("anything.example").is_ok());
+        assert!(deny_only.check_host("cdn.tracker.example").is_err());
+        assert!(serde_json::from_str::<DomainPolicy>(r#"{"allowed": []}"#).is_err());
+    }
+}
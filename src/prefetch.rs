@@ -11,6 +11,8 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use reqwest::header::{HeaderMap, LINK};
+use serde::Serialize;
 use tokio::sync::RwLock;
 use tracing::{debug, info};
 
@@ -121,22 +123,29 @@ impl Default for PrefetchManager {
 ///
 /// Early Hints allow servers to send headers before the final response,
 /// enabling preloading of resources.
-#[derive(Debug, Clone)]
+///
+/// The HTTP client consumes interim `103` responses itself and only hands
+/// over the final one. Servers repeat their hints in its `Link` header
+/// (that's usually where the 103 is generated from), so
+/// [`Self::from_headers`] reads them there.
+#[derive(Debug, Clone, Default)]
 pub struct EarlyHints {
     /// Link headers with preload hints
     pub links: Vec<EarlyHintLink>,
 }
 
 /// A single Early Hint link
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct EarlyHintLink {
     /// URL to preload
     pub url: String,
     /// Relationship (preload, preconnect, dns-prefetch, etc.)
     pub rel: String,
     /// Resource type (script, style, image, font, etc.)
+    #[serde(rename = "as", skip_serializing_if = "Option::is_none")]
     pub as_type: Option<String>,
     /// Crossorigin attribute
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub crossorigin: Option<String>,
 }
 
@@ -157,6 +166,41 @@ impl EarlyHints {
         Self { links }
     }
 
+    /// Links of a response's `Link` headers, several per header allowed
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let values: Vec<&str> = headers
+            .get_all(LINK)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect();
+        let mut links: Vec<&str> = Vec::new();
+        for value in values {
+            // Split on commas outside `<...>` and quoted parameters
+            let (mut start, mut in_url, mut quoted) = (0, false, false);
+            for (i, c) in value.char_indices() {
+                match c {
+                    '<' if !quoted => in_url = true,
+                    '>' if !quoted => in_url = false,
+                    '"' if !in_url => quoted = !quoted,
+                    ',' if !in_url && !quoted => {
+                        links.push(&value[start..i]);
+                        start = i + 1;
+                    }
+                    _ => {}
+                }
+            }
+            links.push(&value[start..]);
+        }
+        Self::parse(&links)
+    }
+
+    /// Whether there are no hints
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
     fn parse_link(header: &str) -> Option<EarlyHintLink> {
         // Parse: <url>; rel=preload; as=script; crossorigin
         let parts: Vec<&str> = header.split(';').map(str::trim).collect();
@@ -314,6 +358,35 @@ mod tests {
         assert_eq!(hints.preconnects().len(), 1);
     }
 
+    #[test]
+    fn test_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            LINK,
+            "</app.css>; rel=preload; as=style, <https://cdn.example.com/a,b.js>; rel=\"preload\"; as=script"
+                .parse()
+                .unwrap(),
+        );
+        headers.append(
+            LINK,
+            "<https://fonts.gstatic.com>; rel=preconnect"
+                .parse()
+                .unwrap(),
+        );
+        let hints = EarlyHints::from_headers(&headers);
+        assert_eq!(hints.links.len(), 3);
+        assert_eq!(hints.links[1].url, "https://cdn.example.com/a,b.js");
+        assert_eq!(hints.links[1].as_type.as_deref(), Some("script"));
+        assert_eq!(hints.preloads().len(), 2);
+        assert!(EarlyHints::from_headers(&HeaderMap::new()).is_empty());
+
+        let json = serde_json::to_value(&hints.links[0]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"url": "/app.css", "rel": "preload", "as": "style"})
+        );
+    }
+
     #[test]
     fn test_extract_link_hints() {
         let html = r#"
@@ -0,0 +1,320 @@
+//! Per-Host Protocol Memory
+//!
+//! nab opens connections with HTTP/2 straight away. A server that only
+//! speaks HTTP/1.x breaks that first exchange, and the request is sent again
+//! over HTTP/1.1; the host is then remembered, so later runs (a daily watch
+//! job, say) connect with HTTP/1.1 on the first try. Entries are per origin
+//! (scheme, host, and port). After
+//! [`REMEMBER_FOR`] the host gets another chance at HTTP/2.
+//!
+//! Each origin's `Alt-Svc` advertisements (RFC 7838: `h3=":443"; ma=86400`)
+//! are kept next to it until their `ma` runs out, for clients that can take
+//! the alternative route.
+//!
+//! The memory lives in `~/.cache/nab/protocols.json` (or the
+//! [workspace](crate::workspace)'s cache); `--no-protocol-cache` leaves it
+//! alone for a run.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::http_client::HttpVersion;
+use crate::state::Versioned;
+
+/// How long an origin is remembered as speaking only HTTP/1.x
+pub const REMEMBER_FOR: Duration = Duration::days(7);
+
+/// `ma` of an `Alt-Svc` entry that doesn't give one (RFC 7838)
+const DEFAULT_MAX_AGE: i64 = 86_400;
+
+/// Set by [`disable`] (`--no-protocol-cache`)
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// The run's memory, opened on first use
+static CACHE: LazyLock<ProtocolCache> = LazyLock::new(|| {
+    if DISABLED.load(Ordering::Relaxed) {
+        ProtocolCache::in_memory()
+    } else {
+        ProtocolCache::open(crate::workspace::cache_dir().join("protocols.json"))
+    }
+});
+
+/// Neither read nor write the memory this run
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// This run's memory (in memory only after [`disable`])
+#[must_use]
+pub fn global() -> &'static ProtocolCache {
+    &CACHE
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProtocolHints {
+    /// By origin, e.g. `https://example.com`
+    origins: BTreeMap<String, OriginProtocol>,
+}
+
+impl Versioned for ProtocolHints {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+/// What an origin was last seen speaking
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OriginProtocol {
+    /// Negotiated HTTP version (`None` until a response says)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<HttpVersion>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alt_svc: Vec<AltService>,
+    pub updated: DateTime<Utc>,
+}
+
+/// One `Alt-Svc` advertisement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AltService {
+    /// ALPN protocol ID, e.g. `h3`
+    pub protocol: String,
+    /// `host:port`, host empty for the origin's own
+    pub authority: String,
+    pub expires: DateTime<Utc>,
+}
+
+/// Entries of an `Alt-Svc` header received at `now`; empty for `clear`,
+/// `None` when nothing in it parses
+#[must_use]
+pub fn parse_alt_svc(value: &str, now: DateTime<Utc>) -> Option<Vec<AltService>> {
+    if value.trim() == "clear" {
+        return Some(Vec::new());
+    }
+    let services: Vec<AltService> = value
+        .split(',')
+        .filter_map(|entry| {
+            let mut params = entry.split(';');
+            let (protocol, authority) = params.next()?.trim().split_once('=')?;
+            let authority = authority.trim().trim_matches('"');
+            let max_age = params
+                .filter_map(|param| param.trim().strip_prefix("ma="))
+                .find_map(|secs| secs.trim().parse::<i64>().ok())
+                .unwrap_or(DEFAULT_MAX_AGE);
+            (!protocol.is_empty() && authority.contains(':')).then(|| AltService {
+                protocol: protocol.trim().to_string(),
+                authority: authority.to_string(),
+                expires: now + Duration::seconds(max_age),
+            })
+        })
+        .collect();
+    (!services.is_empty()).then_some(services)
+}
+
+/// Per-origin protocol memory, saved after each change
+#[derive(Debug)]
+pub struct ProtocolCache {
+    /// Where the memory is saved (`None`: kept in memory only)
+    path: Option<PathBuf>,
+    hints: Mutex<ProtocolHints>,
+}
+
+impl ProtocolCache {
+    /// Memory saved to `path`
+    #[must_use]
+    pub fn open(path: PathBuf) -> Self {
+        let hints = crate::state::load(&path)
+            .unwrap_or_else(|e| {
+                tracing::debug!("Ignoring protocol memory: {e:#}");
+                None
+            })
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            hints: Mutex::new(hints),
+        }
+    }
+
+    /// Memory that forgets everything at exit
+    #[must_use]
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            hints: Mutex::default(),
+        }
+    }
+
+    /// Version to connect to `url`'s origin with, when it isn't nab's
+    /// default (HTTP/2): HTTP/1.1 for one seen speaking only HTTP/1.x lately
+    #[must_use]
+    pub fn preferred(&self, url: &Url) -> Option<HttpVersion> {
+        self.origin(url)
+            .filter(|seen| Utc::now() - seen.updated < REMEMBER_FOR)
+            .and_then(|seen| seen.version)
+            .filter(|&version| version == HttpVersion::Http1)
+    }
+
+    /// Unexpired `Alt-Svc` advertisements of `url`'s origin
+    #[must_use]
+    pub fn alt_services(&self, url: &Url) -> Vec<AltService> {
+        let now = Utc::now();
+        self.origin(url)
+            .map(|seen| seen.alt_svc)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|service| service.expires > now)
+            .collect()
+    }
+
+    /// Whether any origin is remembered as speaking `version`
+    #[must_use]
+    pub fn has_version(&self, version: HttpVersion) -> bool {
+        self.lock()
+            .origins
+            .values()
+            .any(|seen| seen.version == Some(version))
+    }
+
+    /// Note that `url`'s origin answered over `version` (if known),
+    /// advertising `alt_svc` (an `Alt-Svc` header value, if any)
+    pub fn record(&self, url: &Url, version: Option<HttpVersion>, alt_svc: Option<&str>) {
+        let origin = url.origin().ascii_serialization();
+        let now = Utc::now();
+        let mut hints = self.lock();
+        let old = hints.origins.get(&origin).cloned();
+        let mut seen = old.clone().unwrap_or(OriginProtocol {
+            version: None,
+            alt_svc: Vec::new(),
+            updated: now,
+        });
+        seen.version = version.or(seen.version);
+        seen.alt_svc.retain(|service| service.expires > now);
+        if let Some(services) = alt_svc.and_then(|value| parse_alt_svc(value, now)) {
+            seen.alt_svc = services;
+        }
+        // Unchanged entries are saved again at most once a day
+        let changed = old.as_ref().is_none_or(|old| {
+            old.version != seen.version
+                || !same_routes(&old.alt_svc, &seen.alt_svc)
+                || now - old.updated >= Duration::days(1)
+        });
+        if !changed {
+            return;
+        }
+        seen.updated = now;
+        hints.origins.insert(origin, seen);
+        if let Some(path) = &self.path {
+            if let Err(e) = crate::state::save(path, &*hints) {
+                tracing::debug!("Failed to save protocol memory: {e:#}");
+            }
+        }
+    }
+
+    /// Record a response from a client that picked no version itself
+    pub fn record_response(&self, response: &reqwest::Response) {
+        let version = match response.version() {
+            reqwest::Version::HTTP_2 => Some(HttpVersion::Http2),
+            reqwest::Version::HTTP_3 => Some(HttpVersion::Http3),
+            reqwest::Version::HTTP_09 | reqwest::Version::HTTP_10 | reqwest::Version::HTTP_11 => {
+                Some(HttpVersion::Http1)
+            }
+            _ => None,
+        };
+        let alt_svc = response
+            .headers()
+            .get(reqwest::header::ALT_SVC)
+            .and_then(|v| v.to_str().ok());
+        self.record(response.url(), version, alt_svc);
+    }
+
+    fn origin(&self, url: &Url) -> Option<OriginProtocol> {
+        let origin = url.origin().ascii_serialization();
+        self.lock().origins.get(&origin).cloned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ProtocolHints> {
+        self.hints.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Whether a failed request may have met a server without HTTP/2: it broke
+/// down for a reason other than a timeout, an unknown name, or a refused
+/// connection
+#[must_use]
+pub fn may_need_http1(error: &reqwest::Error) -> bool {
+    if error.is_timeout() || !(error.is_connect() || error.is_request()) {
+        return false;
+    }
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if cause.to_string().starts_with("dns error") {
+            return false;
+        }
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            if io.kind() == std::io::ErrorKind::ConnectionRefused {
+                return false;
+            }
+        }
+        source = cause.source();
+    }
+    true
+}
+
+/// Same advertisements, whatever their expiry
+fn same_routes(a: &[AltService], b: &[AltService]) -> bool {
+    let route = |s: &AltService| (s.protocol.clone(), s.authority.clone());
+    a.iter().map(route).eq(b.iter().map(route))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_alt_svc() {
+        let now = Utc::now();
+        let services =
+            parse_alt_svc(r#"h3=":443"; ma=3600, h2="alt.example.com:443""#, now).unwrap();
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].protocol, "h3");
+        assert_eq!(services[0].authority, ":443");
+        assert_eq!(services[0].expires, now + Duration::seconds(3600));
+        assert_eq!(
+            services[1].expires,
+            now + Duration::seconds(DEFAULT_MAX_AGE)
+        );
+        assert_eq!(parse_alt_svc("clear", now), Some(Vec::new()));
+        assert_eq!(parse_alt_svc("nonsense", now), None);
+    }
+
+    #[test]
+    fn test_memory_persists() {
+        let path = std::env::temp_dir().join(format!("nab-protocols-{}.json", std::process::id()));
+        let legacy = Url::parse("https://legacy.example/page").unwrap();
+        let modern = Url::parse("https://modern.example/").unwrap();
+        let cache = ProtocolCache::open(path.clone());
+        cache.record(&legacy, Some(HttpVersion::Http1), None);
+        cache.record(&modern, Some(HttpVersion::Http2), Some(r#"h3=":443""#));
+        assert_eq!(cache.preferred(&legacy), Some(HttpVersion::Http1));
+        // HTTP/2 is the default anyway
+        assert_eq!(cache.preferred(&modern), None);
+        // Another port is another origin
+        let other_port = Url::parse("https://legacy.example:8443/").unwrap();
+        assert_eq!(cache.preferred(&other_port), None);
+
+        let next_run = ProtocolCache::open(path.clone());
+        assert_eq!(next_run.preferred(&legacy), Some(HttpVersion::Http1));
+        assert_eq!(next_run.alt_services(&modern)[0].protocol, "h3");
+        assert!(next_run.has_version(HttpVersion::Http1));
+        // A response without Alt-Svc keeps the advertisement
+        next_run.record(&modern, None, None);
+        assert_eq!(next_run.alt_services(&modern).len(), 1);
+        next_run.record(&modern, None, Some("clear"));
+        assert!(next_run.alt_services(&modern).is_empty());
+        assert_eq!(ProtocolCache::in_memory().preferred(&legacy), None);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,495 @@
+//! Proxy Pre-Check (`nab proxy check`)
+//!
+//! Tests a list of proxies before they are put to work:
+//! - connectivity and latency: a request for the egress IP through the proxy
+//! - egress IP: the address target sites see
+//! - anonymity, from the headers the proxy adds: `transparent` (our own IP is
+//!   forwarded), `anonymous` (`Via`, `X-Forwarded-For`, ... reveal a proxy),
+//!   or `elite` (nothing added)
+//! - reputation: whether anti-bot protected endpoints challenge the egress IP
+//!   right away (CAPTCHA, JS challenge, Cloudflare `cf-mitigated`, Google's
+//!   `/sorry/` page, 403/429)
+//!
+//! Each proxy gets a score from 0 (unusable) to 100:
+//! - unreachable: 0
+//! - latency: -1 per 50 ms over 200 ms, at most -30
+//! - anonymity: transparent -50, anonymous -15, unknown -5
+//! - challenges: up to -40, in proportion to the endpoints that challenged
+//!
+//! The report lists proxies best first. Keep proxy passwords in the secret
+//! store (`secret:NAME` lines) so the report's entries can be used as they are;
+//! literal passwords are masked in the report.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt};
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use url::Url;
+
+use crate::fingerprint::random_profile;
+
+/// Returns the caller's IP, as JSON `{"ip": ...}` or plain text
+pub const DEFAULT_IP_URL: &str = "https://api.ipify.org?format=json";
+
+/// Echoes the request headers as JSON `{"headers": {...}}`
+pub const DEFAULT_HEADERS_URL: &str = "https://httpbin.org/headers";
+
+/// Anti-bot protected pages that challenge poorly reputed IPs at once
+pub const DEFAULT_CHALLENGE_URLS: &[&str] = &[
+    "https://www.google.com/search?q=weather",
+    "https://nowsecure.nl/",
+    "https://www.amazon.com/",
+];
+
+/// Headers a proxy adds that reveal it
+const PROXY_HEADERS: &[&str] = &[
+    "via",
+    "x-forwarded-for",
+    "forwarded",
+    "x-real-ip",
+    "x-proxy-id",
+    "proxy-connection",
+    "client-ip",
+];
+
+/// Endpoints and limits of a check
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    pub ip_url: String,
+    pub headers_url: String,
+    pub challenge_urls: Vec<String>,
+    /// Our IP without a proxy (detects transparent proxies); looked up when `None`
+    pub direct_ip: Option<String>,
+    /// Per-request timeout
+    pub timeout: Duration,
+    /// Proxies checked at once
+    pub concurrency: usize,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            ip_url: DEFAULT_IP_URL.to_string(),
+            headers_url: DEFAULT_HEADERS_URL.to_string(),
+            challenge_urls: DEFAULT_CHALLENGE_URLS
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            direct_ip: None,
+            timeout: Duration::from_secs(15),
+            concurrency: 8,
+        }
+    }
+}
+
+/// What a proxy's headers give away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anonymity {
+    /// Our own IP is forwarded
+    Transparent,
+    /// The proxy announces itself, but not our IP
+    Anonymous,
+    /// No proxy headers
+    Elite,
+    /// The header echo failed
+    Unknown,
+}
+
+/// How an anti-bot endpoint answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Ok,
+    Captcha,
+    Challenge,
+    /// 403 or 429 without a recognizable challenge
+    Blocked,
+    Error,
+}
+
+/// One anti-bot endpoint through one proxy
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointResult {
+    pub url: String,
+    pub status: Option<u16>,
+    pub verdict: Verdict,
+}
+
+/// Check result of one proxy
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyReport {
+    /// As listed (password masked)
+    pub proxy: String,
+    pub reachable: bool,
+    pub latency_ms: Option<f64>,
+    pub egress_ip: Option<String>,
+    pub anonymity: Anonymity,
+    pub endpoints: Vec<EndpointResult>,
+    pub score: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of a whole check, proxies best first
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    pub checked_at: String,
+    pub direct_ip: Option<String>,
+    pub proxies: Vec<ProxyReport>,
+}
+
+/// Proxy specs of a list file: one per line, `#` comments and blanks skipped
+#[must_use]
+pub fn parse_list(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Check every proxy of `specs`, calling `progress` as each finishes
+pub async fn check_all(
+    specs: &[String],
+    options: &CheckOptions,
+    mut progress: impl FnMut(&ProxyReport),
+) -> Result<CheckReport> {
+    let direct_ip = match &options.direct_ip {
+        Some(ip) => Some(ip.clone()),
+        None => {
+            let client = client(None, options.timeout)?;
+            egress_ip(&client, &options.ip_url).await.ok()
+        }
+    };
+    let mut reports = Vec::with_capacity(specs.len());
+    {
+        let mut checks = stream::iter(specs)
+            .map(|spec| check(spec, options, direct_ip.as_deref()))
+            .buffer_unordered(options.concurrency.max(1));
+        while let Some(report) = checks.next().await {
+            progress(&report);
+            reports.push(report);
+        }
+    }
+    reports.sort_by(|a, b| b.score.cmp(&a.score).then(a.proxy.cmp(&b.proxy)));
+    Ok(CheckReport {
+        checked_at: chrono::Utc::now().to_rfc3339(),
+        direct_ip,
+        proxies: reports,
+    })
+}
+
+/// Check one proxy (`secret:NAME` specs are looked up in the secret store)
+pub async fn check(spec: &str, options: &CheckOptions, direct_ip: Option<&str>) -> ProxyReport {
+    let mut report = ProxyReport {
+        proxy: mask_password(spec),
+        reachable: false,
+        latency_ms: None,
+        egress_ip: None,
+        anonymity: Anonymity::Unknown,
+        endpoints: Vec::new(),
+        score: 0,
+        error: None,
+    };
+    let client =
+        match crate::secrets::resolve(spec).and_then(|url| client(Some(&url), options.timeout)) {
+            Ok(client) => client,
+            Err(e) => {
+                report.error = Some(format!("{e:#}"));
+                return report;
+            }
+        };
+
+    let start = Instant::now();
+    match egress_ip(&client, &options.ip_url).await {
+        Ok(ip) => {
+            report.reachable = true;
+            report.latency_ms = Some(crate::timing::ms(start.elapsed()));
+            report.egress_ip = Some(ip);
+        }
+        Err(e) => {
+            report.error = Some(format!("{e:#}"));
+            return report;
+        }
+    }
+    report.anonymity = match echoed_headers(&client, &options.headers_url).await {
+        Ok(headers) => anonymity(&headers, direct_ip),
+        Err(_) => Anonymity::Unknown,
+    };
+    for url in &options.challenge_urls {
+        report.endpoints.push(probe_endpoint(&client, url).await);
+    }
+    report.score = score(&report);
+    report
+}
+
+/// Browser-like client through `proxy` (direct when `None`)
+fn client(proxy: Option<&str>, timeout: Duration) -> Result<reqwest::Client> {
+    let mut builder = crate::policy::client_builder()
+        .default_headers(random_profile().client_headers())
+        .timeout(timeout)
+        .cookie_store(true);
+    builder = match proxy {
+        Some(url) => builder.proxy(
+            reqwest::Proxy::all(url)
+                .with_context(|| format!("Invalid proxy URL '{}'", mask_password(url)))?,
+        ),
+        None => builder.no_proxy(),
+    };
+    Ok(builder.build()?)
+}
+
+async fn egress_ip(client: &reqwest::Client, url: &str) -> Result<String> {
+    let body = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let ip = match serde_json::from_str::<serde_json::Value>(&body) {
+        Ok(json) => json
+            .get("ip")
+            .or_else(|| json.get("origin"))
+            .and_then(|ip| ip.as_str())
+            .map(String::from),
+        Err(_) => Some(body.trim().to_string()),
+    };
+    ip.filter(|ip| !ip.is_empty())
+        .with_context(|| format!("No IP address in the answer of {url}"))
+}
+
+/// Headers as the server received them, names lowercased
+async fn echoed_headers(client: &reqwest::Client, url: &str) -> Result<Vec<(String, String)>> {
+    let json: serde_json::Value = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let headers = json
+        .get("headers")
+        .and_then(|h| h.as_object())
+        .context("No headers in the echo")?;
+    Ok(headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_ascii_lowercase(),
+                value.as_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect())
+}
+
+/// Anonymity level from the headers a server received
+#[must_use]
+pub fn anonymity(headers: &[(String, String)], direct_ip: Option<&str>) -> Anonymity {
+    if let Some(ip) = direct_ip {
+        if headers.iter().any(|(_, value)| value.contains(ip)) {
+            return Anonymity::Transparent;
+        }
+    }
+    if headers
+        .iter()
+        .any(|(name, _)| PROXY_HEADERS.contains(&name.as_str()))
+    {
+        Anonymity::Anonymous
+    } else {
+        Anonymity::Elite
+    }
+}
+
+async fn probe_endpoint(client: &reqwest::Client, url: &str) -> EndpointResult {
+    let response = match client.get(url).send().await {
+        Ok(response) => response,
+        Err(_) => {
+            return EndpointResult {
+                url: url.to_string(),
+                status: None,
+                verdict: Verdict::Error,
+            }
+        }
+    };
+    let status = response.status().as_u16();
+    let final_url = response.url().clone();
+    let headers = response.headers().clone();
+    let body = response.text().await.unwrap_or_default();
+    EndpointResult {
+        url: url.to_string(),
+        status: Some(status),
+        verdict: verdict(status, &headers, &final_url, &body),
+    }
+}
+
+/// Whether an answer is a challenge rather than the page
+#[must_use]
+pub fn verdict(status: u16, headers: &HeaderMap, url: &Url, body: &str) -> Verdict {
+    if crate::captcha::detect(body, url).is_some() || url.path().starts_with("/sorry/") {
+        Verdict::Captcha
+    } else if headers
+        .get("cf-mitigated")
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"challenge"))
+        || crate::challenge::detect(body).is_some()
+    {
+        Verdict::Challenge
+    } else if matches!(status, 403 | 429) {
+        Verdict::Blocked
+    } else {
+        Verdict::Ok
+    }
+}
+
+/// Score from 0 to 100, see the module docs
+#[must_use]
+pub fn score(report: &ProxyReport) -> u8 {
+    if !report.reachable {
+        return 0;
+    }
+    let mut score = 100.0;
+    if let Some(latency) = report.latency_ms {
+        score -= ((latency - 200.0) / 50.0).clamp(0.0, 30.0);
+    }
+    score -= match report.anonymity {
+        Anonymity::Transparent => 50.0,
+        Anonymity::Anonymous => 15.0,
+        Anonymity::Unknown => 5.0,
+        Anonymity::Elite => 0.0,
+    };
+    if !report.endpoints.is_empty() {
+        let challenged = report
+            .endpoints
+            .iter()
+            .filter(|e| e.verdict != Verdict::Ok)
+            .count();
+        #[allow(clippy::cast_precision_loss)]
+        let share = challenged as f64 / report.endpoints.len() as f64;
+        score -= 40.0 * share;
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let score = score.round().clamp(0.0, 100.0) as u8;
+    score
+}
+
+/// `spec` with any password replaced by `***`
+pub(crate) fn mask_password(spec: &str) -> String {
+    match Url::parse(spec) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some("***"));
+            url.to_string()
+        }
+        _ => spec.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Answers every request, so it also works as an HTTP proxy for `http://` targets
+    async fn fake_proxy(forwarded_for: Option<&'static str>) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 8192];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let target = request.split_whitespace().nth(1).unwrap_or("/");
+                let path = Url::parse(target)
+                    .map_or_else(|_| target.to_string(), |u| u.path().to_string());
+                let (status, extra, body) = match path.as_str() {
+                    "/ip" => ("200 OK", "", r#"{"ip": "203.0.113.7"}"#.to_string()),
+                    "/headers" => {
+                        let forwarded = forwarded_for
+                            .map(|ip| format!(r#", "X-Forwarded-For": "{ip}""#))
+                            .unwrap_or_default();
+                        let body = format!(r#"{{"headers": {{"Host": "check.test"{forwarded}}}}}"#);
+                        ("200 OK", "", body)
+                    }
+                    "/challenged" => (
+                        "403 Forbidden",
+                        "cf-mitigated: challenge\r\n",
+                        "<html>Just a moment...</html>".to_string(),
+                    ),
+                    _ => (
+                        "200 OK",
+                        "",
+                        "<html><body><p>Welcome</p></body></html>".to_string(),
+                    ),
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\n{extra}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    fn options() -> CheckOptions {
+        CheckOptions {
+            ip_url: "http://check.test/ip".into(),
+            headers_url: "http://check.test/headers".into(),
+            challenge_urls: vec![
+                "http://check.test/page".into(),
+                "http://check.test/challenged".into(),
+            ],
+            direct_ip: Some("198.51.100.1".into()),
+            timeout: Duration::from_secs(5),
+            concurrency: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_scores_proxies() {
+        let elite = format!("http://{}", fake_proxy(None).await);
+        let transparent = format!("http://{}", fake_proxy(Some("198.51.100.1")).await);
+        let dead = "http://127.0.0.1:9".to_string();
+        let specs = vec![transparent.clone(), dead.clone(), elite.clone()];
+
+        let mut seen = 0;
+        let report = check_all(&specs, &options(), |_| seen += 1).await.unwrap();
+        assert_eq!(seen, 3);
+        let order: Vec<&str> = report.proxies.iter().map(|p| p.proxy.as_str()).collect();
+        assert_eq!(order, [elite.as_str(), transparent.as_str(), dead.as_str()]);
+
+        let best = &report.proxies[0];
+        assert_eq!(best.egress_ip.as_deref(), Some("203.0.113.7"));
+        assert_eq!(best.anonymity, Anonymity::Elite);
+        let verdicts: Vec<Verdict> = best.endpoints.iter().map(|e| e.verdict).collect();
+        assert_eq!(verdicts, [Verdict::Ok, Verdict::Challenge]);
+        assert_eq!(best.score, 80);
+
+        assert_eq!(report.proxies[1].anonymity, Anonymity::Transparent);
+        assert!(!report.proxies[2].reachable);
+        assert_eq!(report.proxies[2].score, 0);
+    }
+
+    #[test]
+    fn test_anonymity_and_list() {
+        let header = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(
+            anonymity(&[header("via", "1.1 squid")], Some("198.51.100.1")),
+            Anonymity::Anonymous
+        );
+        assert_eq!(
+            anonymity(&[header("x-real-ip", "198.51.100.1")], Some("198.51.100.1")),
+            Anonymity::Transparent
+        );
+        assert_eq!(anonymity(&[header("host", "x")], None), Anonymity::Elite);
+
+        let list = "# pool\nhttp://a:1\n\n  socks5h://u:p@b:2  \nsecret:corp\n";
+        assert_eq!(
+            parse_list(list),
+            ["http://a:1", "socks5h://u:p@b:2", "secret:corp"]
+        );
+        assert_eq!(mask_password("socks5h://u:p@b:2"), "socks5h://u:***@b:2");
+    }
+}
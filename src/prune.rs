@@ -0,0 +1,417 @@
+//! JSON Pruning
+//!
+//! Hydration state (`__NEXT_DATA__`, `__NUXT__`, ...) is often megabytes of
+//! JSON. [`Pruner`] turns it into a readable summary: long arrays keep their
+//! first and last items around a `{"__truncated": count}` marker, deeper
+//! levels collapse into the same marker, and with a byte budget the limits
+//! tighten until the output fits. Keys named in `--keep-paths` stay whole.
+//!
+//! Hydration state also carries keys that change on every load: render
+//! timestamps, request and trace IDs, analytics payloads. [`KeyFilter`]
+//! drops them so repeated `nab spa` extractions of an unchanged page print
+//! the same JSON and diff cleanly.
+//!
+//! A key rule (for `--ignore-keys` and `--keep-paths`) is one of:
+//!
+//! - a key name, matched at any depth: `sessionId`
+//! - a key glob, where `*` matches any characters: `*At`, `_*`
+//! - a dotted path from the root, where `*` matches one key or array index
+//!   and `**` any number of them: `props.pageProps.*.updatedAt`, `**.etag`
+//! - a group: `timestamps` or `tracking` (see [`TIMESTAMP_KEYS`] and
+//!   [`TRACKING_KEYS`])
+
+use serde_json::Value;
+
+/// Keys the `timestamps` group drops
+pub const TIMESTAMP_KEYS: &[&str] = &[
+    "timestamp",
+    "*Timestamp",
+    "*_timestamp",
+    "ts",
+    "*At",
+    "*_at",
+    "date",
+    "now",
+    "lastModified",
+    "buildTime",
+    "serverTime",
+    "renderTime",
+    "expires",
+    "expiry",
+];
+
+/// Keys the `tracking` group drops
+pub const TRACKING_KEYS: &[&str] = &[
+    "*racking*",
+    "*nalytics*",
+    "gtm*",
+    "utm_*",
+    "requestId",
+    "request_id",
+    "traceId",
+    "trace_id",
+    "spanId",
+    "nonce",
+    "*Nonce",
+    "csrf*",
+    "*Csrf*",
+    "buildId",
+    "deploymentId",
+    "experiments",
+];
+
+/// Key of the marker that stands in for dropped items
+pub const TRUNCATED: &str = "__truncated";
+
+/// Strings are never cut shorter than this to meet a byte budget
+const MIN_STRING_CHARS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rule {
+    /// Key glob, at any depth
+    Key(String),
+    /// Path of segment globs from the root
+    Path(Vec<String>),
+}
+
+/// Keys to drop from JSON output (`--ignore-keys`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyFilter {
+    rules: Vec<Rule>,
+}
+
+impl KeyFilter {
+    /// Build a filter from rules (names, globs, dotted paths, or groups)
+    #[must_use]
+    pub fn new<S: AsRef<str>>(rules: &[S]) -> Self {
+        let mut filter = Self::default();
+        for rule in rules.iter().map(|r| r.as_ref().trim()) {
+            match rule {
+                "" => {}
+                "timestamps" => filter.extend_keys(TIMESTAMP_KEYS),
+                "tracking" => filter.extend_keys(TRACKING_KEYS),
+                path if path.contains('.') => filter
+                    .rules
+                    .push(Rule::Path(path.split('.').map(str::to_string).collect())),
+                key => filter.rules.push(Rule::Key(key.to_string())),
+            }
+        }
+        filter
+    }
+
+    fn extend_keys(&mut self, keys: &[&str]) {
+        self.rules
+            .extend(keys.iter().map(|key| Rule::Key((*key).to_string())));
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Remove every matching key from `value`
+    pub fn apply(&self, value: &mut Value) {
+        if !self.is_empty() {
+            self.prune(value, &mut Vec::new());
+        }
+    }
+
+    fn prune(&self, value: &mut Value, path: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                map.retain(|key, _| {
+                    path.push(key.clone());
+                    let keep = !self.matches(key, path);
+                    path.pop();
+                    keep
+                });
+                for (key, child) in map.iter_mut() {
+                    path.push(key.clone());
+                    self.prune(child, path);
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                for (index, child) in items.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    self.prune(child, path);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn matches(&self, key: &str, path: &[String]) -> bool {
+        self.rules.iter().any(|rule| match rule {
+            Rule::Key(glob) => glob_matches(glob, key),
+            Rule::Path(segments) => path_matches(segments, path),
+        })
+    }
+}
+
+/// Limits that turn a large JSON payload into a readable summary
+#[derive(Debug, Clone, Default)]
+pub struct Pruner {
+    /// Keys to drop entirely
+    pub ignore: KeyFilter,
+    /// Keys whose values are kept whole, whatever the limits
+    pub keep: KeyFilter,
+    /// Longest array kept; longer ones keep their first and last items
+    pub max_array: Option<usize>,
+    /// Deepest nesting kept; deeper objects and arrays become a marker
+    pub max_depth: Option<usize>,
+    /// Output size budget in bytes; the limits tighten until the JSON fits
+    pub max_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    array: usize,
+    string: usize,
+    depth: usize,
+}
+
+impl Pruner {
+    /// The pruned copy of `value`
+    #[must_use]
+    pub fn prune(&self, value: &Value) -> Value {
+        let mut value = value.clone();
+        self.ignore.apply(&mut value);
+        let mut limits = Limits {
+            array: self.max_array.unwrap_or(usize::MAX),
+            string: usize::MAX,
+            depth: self.max_depth.unwrap_or(usize::MAX),
+        };
+        let mut pruned = self.limit(&value, limits, &mut Vec::new());
+        let Some(budget) = self.max_bytes else {
+            return pruned;
+        };
+
+        // Shorten arrays first, then long strings, then nesting
+        let (longest_array, longest_string, depth) = measure(&value);
+        limits.array = limits.array.min(longest_array);
+        limits.string = longest_string;
+        limits.depth = limits.depth.min(depth);
+        while pruned.to_string().len() > budget {
+            if limits.array > 2 {
+                limits.array /= 2;
+            } else if limits.string > MIN_STRING_CHARS {
+                limits.string = (limits.string / 2).max(MIN_STRING_CHARS);
+            } else if limits.depth > 1 {
+                limits.depth -= 1;
+            } else {
+                break;
+            }
+            pruned = self.limit(&value, limits, &mut Vec::new());
+        }
+        pruned
+    }
+
+    fn limit(&self, value: &Value, limits: Limits, path: &mut Vec<String>) -> Value {
+        match value {
+            Value::Object(map) if path.len() >= limits.depth => truncated(map.len()),
+            Value::Array(items) if path.len() >= limits.depth => truncated(items.len()),
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(key, child)| {
+                        path.push(key.clone());
+                        let child = if self.keep.matches(key, path) {
+                            child.clone()
+                        } else {
+                            self.limit(child, limits, path)
+                        };
+                        path.pop();
+                        (key.clone(), child)
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) => {
+                let (head, tail) = if items.len() > limits.array {
+                    let head = limits.array.div_ceil(2);
+                    (head, limits.array - head)
+                } else {
+                    (items.len(), 0)
+                };
+                let dropped = items.len() - head - tail;
+                let mut kept = Vec::with_capacity(head + tail + 1);
+                for (index, item) in items.iter().enumerate() {
+                    if index == head && dropped > 0 {
+                        kept.push(truncated(dropped));
+                    }
+                    if index < head || index >= items.len() - tail {
+                        path.push(index.to_string());
+                        kept.push(self.limit(item, limits, path));
+                        path.pop();
+                    }
+                }
+                Value::Array(kept)
+            }
+            Value::String(text) if text.chars().count() > limits.string => {
+                let cut: String = text.chars().take(limits.string).collect();
+                let rest = text.chars().count() - limits.string;
+                Value::String(format!("{cut}… (+{rest} chars)"))
+            }
+            _ => value.clone(),
+        }
+    }
+}
+
+/// `{"__truncated": count}`
+fn truncated(count: usize) -> Value {
+    let mut marker = serde_json::Map::new();
+    marker.insert(TRUNCATED.to_string(), count.into());
+    Value::Object(marker)
+}
+
+/// Longest array, longest string (in chars), and nesting depth of `value`
+fn measure(value: &Value) -> (usize, usize, usize) {
+    let children: Box<dyn Iterator<Item = &Value>> = match value {
+        Value::Object(map) => Box::new(map.values()),
+        Value::Array(items) => Box::new(items.iter()),
+        Value::String(text) => return (0, text.chars().count(), 0),
+        _ => return (0, 0, 0),
+    };
+    let own_len = match value {
+        Value::Array(items) => items.len(),
+        _ => 0,
+    };
+    children.fold((own_len, 0, 1), |(array, string, depth), child| {
+        let (a, s, d) = measure(child);
+        (array.max(a), string.max(s), depth.max(d + 1))
+    })
+}
+
+/// Whether `text` matches `glob`, where `*` matches any run of characters
+fn glob_matches(glob: &str, text: &str) -> bool {
+    let Some((first, rest)) = glob.split_once('*') else {
+        return glob == text;
+    };
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or_default();
+    for part in parts {
+        match remaining.find(part) {
+            Some(at) => remaining = &remaining[at + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
+}
+
+/// Whether a key path matches segment globs, with `**` spanning any number of segments
+fn path_matches(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((segment, rest)) if segment == "**" => {
+            (0..=path.len()).any(|skip| path_matches(rest, &path[skip..]))
+        }
+        Some((segment, rest)) => path
+            .split_first()
+            .is_some_and(|(key, tail)| glob_matches(segment, key) && path_matches(rest, tail)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("sessionId", "sessionId"));
+        assert!(!glob_matches("sessionId", "sessionIds"));
+        assert!(glob_matches("*At", "updatedAt"));
+        assert!(!glob_matches("*At", "format"));
+        assert!(glob_matches("_*", "_sentryTraceData"));
+        assert!(glob_matches("*racking*", "trackingPixel"));
+        assert!(glob_matches("a*b*c", "abc"));
+        assert!(!glob_matches("a*bc*c", "abc"));
+    }
+
+    #[test]
+    fn test_ignore_keys_groups_and_paths() {
+        let mut state = json!({
+            "props": {
+                "pageProps": {
+                    "product": {"name": "Kettle", "updatedAt": "2026-01-02", "etag": "x1"},
+                    "sessionId": "abc",
+                    "reviews": [{"text": "Great", "id": 1, "etag": "x2"}]
+                },
+                "buildId": "b-91",
+                "format": "full"
+            },
+            "analyticsContext": {"page": "pdp"},
+            "etag": "root"
+        });
+        KeyFilter::new(&["timestamps", "tracking", "sessionId", "props.**.etag"]).apply(&mut state);
+        assert_eq!(
+            state,
+            json!({
+                "props": {
+                    "pageProps": {
+                        "product": {"name": "Kettle"},
+                        "reviews": [{"text": "Great", "id": 1}]
+                    },
+                    "format": "full"
+                },
+                "etag": "root"
+            })
+        );
+
+        let mut indexed = json!({"items": [{"id": 1, "seen": 5}, {"id": 2, "seen": 6}]});
+        KeyFilter::new(&["items.*.seen"]).apply(&mut indexed);
+        assert_eq!(indexed, json!({"items": [{"id": 1}, {"id": 2}]}));
+        assert!(KeyFilter::new(&["", " "]).is_empty());
+    }
+
+    #[test]
+    fn test_prune_arrays_and_depth() {
+        let state = json!({
+            "items": [1, 2, 3, 4, 5, 6, 7],
+            "nested": {"a": {"b": {"c": 1}}, "list": [[1, 2], [3]]},
+            "config": {"deep": {"deeper": {"deepest": true}}}
+        });
+        let pruner = Pruner {
+            keep: KeyFilter::new(&["config"]),
+            max_array: Some(3),
+            max_depth: Some(2),
+            ..Pruner::default()
+        };
+        assert_eq!(
+            pruner.prune(&state),
+            json!({
+                "items": [1, 2, {"__truncated": 4}, 7],
+                "nested": {"a": {"__truncated": 1}, "list": {"__truncated": 2}},
+                "config": {"deep": {"deeper": {"deepest": true}}}
+            })
+        );
+
+        // No limits: unchanged
+        assert_eq!(Pruner::default().prune(&state), state);
+    }
+
+    #[test]
+    fn test_prune_to_byte_budget() {
+        let products: Vec<Value> = (0..500)
+            .map(|i| json!({"id": i, "description": "x".repeat(400)}))
+            .collect();
+        let state = json!({"products": products, "buildId": "b-1"});
+        assert!(state.to_string().len() > 200_000);
+
+        let pruner = Pruner {
+            ignore: KeyFilter::new(&["buildId"]),
+            max_bytes: Some(2_000),
+            ..Pruner::default()
+        };
+        let pruned = pruner.prune(&state);
+        assert!(pruned.to_string().len() <= 2_000, "{pruned}");
+        let kept = pruned["products"].as_array().unwrap();
+        assert_eq!(kept[0]["id"], 0);
+        assert_eq!(kept.last().unwrap()["id"], 499);
+        assert!(kept.iter().any(|item| item.get(TRUNCATED).is_some()));
+        assert!(pruned.get("buildId").is_none());
+    }
+}
@@ -0,0 +1,317 @@
+//! Publish Sinks
+//!
+//! `nab crawl --publish` and `nab batch --publish` send each page to a
+//! message broker as it is extracted, so nab can feed a real-time ingestion
+//! pipeline without intermediate files:
+//!
+//! - `nats://HOST[:PORT][,HOST...]/SUBJECT`: NATS core publish (feature
+//!   `nats`), the key in a `Nab-Key` header
+//! - `kafka://BROKER[:PORT][,BROKER...]/TOPIC`: Kafka produce (feature
+//!   `kafka`), the key as the record key
+//!
+//! A message's key is the page's canonical URL if it declares one, else its
+//! URL; its payload is the page's JSON line with its Markdown. Pages are
+//! queued and sent in the background; [`Publisher::finish`] waits until the
+//! broker has them.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Broker kind of a [`PublishTarget`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Broker {
+    Nats,
+    Kafka,
+}
+
+impl Broker {
+    fn default_port(self) -> u16 {
+        match self {
+            Self::Nats => 4222,
+            Self::Kafka => 9092,
+        }
+    }
+}
+
+/// Where `--publish` sends pages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishTarget {
+    pub broker: Broker,
+    /// `host:port` of each server
+    pub servers: Vec<String>,
+    /// NATS subject or Kafka topic
+    pub topic: String,
+}
+
+impl FromStr for PublishTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (scheme, rest) = s
+            .split_once("://")
+            .with_context(|| format!("'{s}' isn't nats://HOST/SUBJECT or kafka://BROKER/TOPIC"))?;
+        let broker = match scheme {
+            "nats" => Broker::Nats,
+            "kafka" => Broker::Kafka,
+            _ => bail!("Unknown publish scheme '{scheme}' (nats or kafka)"),
+        };
+        let (servers, topic) = rest.split_once('/').unwrap_or((rest, ""));
+        if topic.is_empty() || topic.contains('/') {
+            bail!("'{s}' needs one subject or topic after the servers");
+        }
+        let servers: Vec<String> = servers
+            .split(',')
+            .filter(|server| !server.is_empty())
+            .map(|server| {
+                if server.contains(':') {
+                    server.to_string()
+                } else {
+                    format!("{server}:{}", broker.default_port())
+                }
+            })
+            .collect();
+        if servers.is_empty() {
+            bail!("'{s}' names no servers");
+        }
+        Ok(Self {
+            broker,
+            servers,
+            topic: topic.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for PublishTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = match self.broker {
+            Broker::Nats => "nats",
+            Broker::Kafka => "kafka",
+        };
+        write!(f, "{scheme}://{}/{}", self.servers.join(","), self.topic)
+    }
+}
+
+/// A page on its way to the broker
+#[cfg_attr(not(any(feature = "nats", feature = "kafka")), allow(dead_code))]
+#[derive(Debug)]
+struct Message {
+    key: String,
+    payload: Vec<u8>,
+}
+
+/// Key and payload of a page's message
+#[must_use]
+pub fn message(line: &Value, markdown: &str) -> (String, Vec<u8>) {
+    let key = line["canonical"]
+        .as_str()
+        .or_else(|| line["url"].as_str())
+        .unwrap_or_default()
+        .to_string();
+    let mut payload = line.clone();
+    payload["markdown"] = markdown.into();
+    (key, payload.to_string().into_bytes())
+}
+
+/// A connection to the broker, sending queued pages in the background
+#[derive(Debug)]
+pub struct Publisher {
+    target: PublishTarget,
+    queue: Mutex<Option<mpsc::UnboundedSender<Message>>>,
+    sender: Mutex<Option<JoinHandle<Result<usize>>>>,
+}
+
+impl Publisher {
+    /// Connect to `target` (failing now if it can't be reached)
+    pub async fn connect(target: PublishTarget) -> Result<Self> {
+        let sink = Sink::connect(&target)
+            .await
+            .with_context(|| format!("Failed to connect to {target}"))?;
+        let (queue, mut queued) = mpsc::unbounded_channel::<Message>();
+        let sender = tokio::spawn(async move {
+            let mut sent = 0;
+            while let Some(message) = queued.recv().await {
+                sink.send(message).await?;
+                sent += 1;
+            }
+            sink.flush().await?;
+            Ok(sent)
+        });
+        Ok(Self {
+            target,
+            queue: Mutex::new(Some(queue)),
+            sender: Mutex::new(Some(sender)),
+        })
+    }
+
+    #[must_use]
+    pub fn target(&self) -> &PublishTarget {
+        &self.target
+    }
+
+    /// Queue a page: its result line and Markdown
+    pub fn publish(&self, line: &Value, markdown: &str) {
+        let (key, payload) = message(line, markdown);
+        if let Some(queue) = &*self.queue.lock().unwrap_or_else(PoisonError::into_inner) {
+            // A closed queue means sending failed; finish() reports why
+            let _ = queue.send(Message { key, payload });
+        }
+    }
+
+    /// Send what is queued and wait for the broker; the number of pages sent
+    pub async fn finish(&self) -> Result<usize> {
+        self.queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let sender = self
+            .sender
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        match sender {
+            Some(sender) => sender
+                .await?
+                .with_context(|| format!("Failed to publish to {}", self.target)),
+            None => Ok(0),
+        }
+    }
+}
+
+enum Sink {
+    #[cfg(feature = "nats")]
+    Nats {
+        client: async_nats::Client,
+        subject: String,
+    },
+    #[cfg(feature = "kafka")]
+    Kafka {
+        producer: rdkafka::producer::FutureProducer,
+        topic: String,
+    },
+}
+
+impl Sink {
+    async fn connect(target: &PublishTarget) -> Result<Self> {
+        match target.broker {
+            #[cfg(feature = "nats")]
+            Broker::Nats => {
+                let servers: Vec<String> = target
+                    .servers
+                    .iter()
+                    .map(|server| format!("nats://{server}"))
+                    .collect();
+                Ok(Self::Nats {
+                    client: async_nats::connect(servers).await?,
+                    subject: target.topic.clone(),
+                })
+            }
+            #[cfg(feature = "kafka")]
+            Broker::Kafka => {
+                use rdkafka::producer::Producer;
+
+                let producer: rdkafka::producer::FutureProducer = rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", target.servers.join(","))
+                    .set("message.timeout.ms", "30000")
+                    .create()?;
+                // Fails here rather than on the first page if no broker answers
+                producer
+                    .client()
+                    .fetch_metadata(Some(&target.topic), std::time::Duration::from_secs(10))?;
+                Ok(Self::Kafka {
+                    producer,
+                    topic: target.topic.clone(),
+                })
+            }
+            #[allow(unreachable_patterns)]
+            broker => {
+                let feature = match broker {
+                    Broker::Nats => "nats",
+                    Broker::Kafka => "kafka",
+                };
+                bail!("This nab was built without {feature} publishing (feature \"{feature}\")")
+            }
+        }
+    }
+
+    #[allow(clippy::unused_async)]
+    #[cfg_attr(not(any(feature = "nats", feature = "kafka")), allow(unused_variables))]
+    async fn send(&self, message: Message) -> Result<()> {
+        match self {
+            #[cfg(feature = "nats")]
+            Self::Nats { client, subject } => {
+                let mut headers = async_nats::HeaderMap::new();
+                headers.insert("Nab-Key", message.key.as_str());
+                client
+                    .publish_with_headers(subject.clone(), headers, message.payload.into())
+                    .await?;
+                Ok(())
+            }
+            #[cfg(feature = "kafka")]
+            Self::Kafka { producer, topic } => {
+                let record = rdkafka::producer::FutureRecord::to(topic)
+                    .key(&message.key)
+                    .payload(&message.payload);
+                producer
+                    .send(record, std::time::Duration::ZERO)
+                    .await
+                    .map_err(|(e, _)| e)?;
+                Ok(())
+            }
+            #[cfg(not(any(feature = "nats", feature = "kafka")))]
+            _ => unreachable!("connect() fails without a publishing feature"),
+        }
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn flush(&self) -> Result<()> {
+        match self {
+            #[cfg(feature = "nats")]
+            Self::Nats { client, .. } => Ok(client.flush().await?),
+            #[cfg(feature = "kafka")]
+            Self::Kafka { producer, .. } => {
+                use rdkafka::producer::Producer;
+
+                Ok(producer.flush(std::time::Duration::from_secs(30))?)
+            }
+            #[cfg(not(any(feature = "nats", feature = "kafka")))]
+            _ => unreachable!("connect() fails without a publishing feature"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        let target: PublishTarget = "kafka://b1,b2:9093/pages".parse().unwrap();
+        assert_eq!(target.broker, Broker::Kafka);
+        assert_eq!(target.servers, ["b1:9092", "b2:9093"]);
+        assert_eq!(target.topic, "pages");
+        assert_eq!(target.to_string(), "kafka://b1:9092,b2:9093/pages");
+        let target: PublishTarget = "nats://localhost/nab.pages".parse().unwrap();
+        assert_eq!(target.servers, ["localhost:4222"]);
+        assert!("nats://localhost".parse::<PublishTarget>().is_err());
+        assert!("amqp://localhost/q".parse::<PublishTarget>().is_err());
+    }
+
+    #[test]
+    fn test_message_key() {
+        let line = serde_json::json!({"url": "https://example.com/?utm=x", "status": 200});
+        let (key, payload) = message(&line, "# Hi");
+        assert_eq!(key, "https://example.com/?utm=x");
+        let payload: Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(payload["markdown"], "# Hi");
+
+        let line = serde_json::json!({"url": "https://example.com/?utm=x",
+                                      "canonical": "https://example.com/"});
+        assert_eq!(message(&line, "").0, "https://example.com/");
+    }
+}
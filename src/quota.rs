@@ -0,0 +1,309 @@
+//! Disk Quotas (`--cache-max-size`, `--output-max-size`)
+//!
+//! Unattended runs must not fill the disk:
+//!
+//! - `--cache-max-size 2GB` caps nab's [cache directory](crate::workspace::cache_dir)
+//!   (revisit pages, TLS session hints). Least recently used files go first;
+//!   a revisit hit counts as a use. The cache is trimmed when a command starts
+//!   and ends, and while a crawl saves its state.
+//! - `--output-max-size 10GB` caps what `crawl` and `batch` write to their
+//!   output directory, files already there included. At the cap the command
+//!   stops starting requests, saves its progress, and fails with an error
+//!   naming the limit.
+//!
+//! `nab cache stats` reports the cache's size and `nab cache clear` empties it.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use tokio::sync::watch;
+
+/// `--cache-max-size` of this run
+static CACHE_LIMIT: OnceLock<u64> = OnceLock::new();
+
+/// Parse `500MB`, `2GB`, `1.5GiB`, or a plain byte count
+///
+/// `KB`/`MB`/`GB`/`TB` are powers of 1000, `KiB`/`MiB`/`GiB`/`TiB` of 1024.
+pub fn parse_size(spec: &str) -> Result<u64> {
+    let spec = spec.trim();
+    let split = spec
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(spec.len());
+    let (number, unit) = spec.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("Invalid size '{spec}', expected e.g. 500MB or 2GB"))?;
+    let scale: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000_u64.pow(2),
+        "g" | "gb" => 1000_u64.pow(3),
+        "t" | "tb" => 1000_u64.pow(4),
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => bail!("Invalid size '{spec}', expected e.g. 500MB or 2GB"),
+    };
+    let bytes = (number * scale as f64).round();
+    if bytes < 1.0 {
+        bail!("The size must be at least one byte");
+    }
+    Ok(bytes as u64)
+}
+
+/// `1500000` → `1.5 MB`
+#[must_use]
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1000.0 {
+            break;
+        }
+        size /= 1000.0;
+        unit = next;
+    }
+    format!("{size:.1} {unit}")
+}
+
+/// Files and bytes in a directory tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Size of everything under `dir` (empty if it doesn't exist)
+#[must_use]
+pub fn usage(dir: &Path) -> Usage {
+    let mut usage = Usage::default();
+    for (_, bytes, _) in files(dir) {
+        usage.add(bytes);
+    }
+    usage
+}
+
+/// Delete least recently used files under `dir` until it holds at most `max` bytes
+///
+/// Returns what was deleted. Lock and temp files of running writers are left alone.
+pub fn evict(dir: &Path, max: u64) -> Result<Usage> {
+    let mut entries = files(dir);
+    let mut total: u64 = entries.iter().map(|(_, bytes, _)| bytes).sum();
+    let mut evicted = Usage::default();
+    if total <= max {
+        return Ok(evicted);
+    }
+    entries.sort_by_key(|(_, _, used)| *used);
+    for (path, bytes, _) in entries {
+        if total <= max {
+            break;
+        }
+        if is_in_use(&path) {
+            continue;
+        }
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to evict {}", path.display()))?;
+        total -= bytes;
+        evicted.add(bytes);
+    }
+    Ok(evicted)
+}
+
+/// Delete all files under `dir`, except those of running writers
+pub fn clear(dir: &Path) -> Result<Usage> {
+    evict(dir, 0)
+}
+
+/// Cap nab's cache at `max` bytes for this run
+pub fn set_cache_limit(max: u64) {
+    let _ = CACHE_LIMIT.set(max);
+}
+
+/// Evict from the cache down to its `--cache-max-size` (nothing without one)
+pub fn trim_cache() -> Result<Usage> {
+    match CACHE_LIMIT.get() {
+        Some(&max) => evict(&crate::workspace::cache_dir(), max),
+        None => Ok(Usage::default()),
+    }
+}
+
+/// Mark a cache file as just used, so it's evicted last
+pub fn touch(path: &Path) {
+    if let Ok(file) = std::fs::File::options().append(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+/// `(path, bytes, last used)` of the files under `dir`
+fn files(dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else if meta.is_file() {
+                let used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                found.push((entry.path(), meta.len(), used));
+            }
+        }
+    }
+    found
+}
+
+fn is_in_use(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "lock" || ext == "tmp")
+}
+
+/// Byte budget of a command's output directory
+#[derive(Debug)]
+pub struct OutputQuota {
+    dir: PathBuf,
+    limit: u64,
+    used: AtomicU64,
+    exceeded: watch::Sender<bool>,
+}
+
+impl OutputQuota {
+    /// Budget of `limit` bytes for `dir`, counting what's already in it
+    #[must_use]
+    pub fn new(dir: &Path, limit: u64) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            limit,
+            used: AtomicU64::new(usage(dir).bytes),
+            exceeded: watch::channel(false).0,
+        }
+    }
+
+    /// Bytes written or found so far
+    #[must_use]
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Account for writing `bytes` to `path`, or fail if that would pass the limit
+    ///
+    /// Replacing a file only counts the growth.
+    pub fn reserve(&self, path: &Path, bytes: u64) -> Result<()> {
+        let existing = std::fs::metadata(path).map_or(0, |meta| meta.len());
+        let growth = bytes.saturating_sub(existing);
+        let reserved = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used + growth).filter(|&total| total <= self.limit)
+            });
+        if reserved.is_err() {
+            self.exceeded.send_replace(true);
+            bail!(self.error());
+        }
+        Ok(())
+    }
+
+    /// Whether a write was refused
+    #[must_use]
+    pub fn is_exceeded(&self) -> bool {
+        *self.exceeded.borrow()
+    }
+
+    /// Resolves once a write is refused
+    pub async fn exceeded(&self) {
+        let _ = self
+            .exceeded
+            .subscribe()
+            .wait_for(|exceeded| *exceeded)
+            .await;
+    }
+
+    /// The error a command stopped at the limit fails with
+    #[must_use]
+    pub fn error(&self) -> String {
+        format!(
+            "Output limit of {} reached in {}; raise --output-max-size or free space",
+            format_size(self.limit),
+            self.dir.display()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_sizes() {
+        assert_eq!(parse_size("2GB").unwrap(), 2_000_000_000);
+        assert_eq!(parse_size("500 mb").unwrap(), 500_000_000);
+        assert_eq!(parse_size("1.5GiB").unwrap(), 1_610_612_736);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        for bad in ["", "0", "GB", "10 parsecs"] {
+            assert!(parse_size(bad).is_err(), "{bad:?}");
+        }
+        assert_eq!(format_size(999), "999 B");
+        assert_eq!(format_size(1_500_000), "1.5 MB");
+        assert_eq!(format_size(10_000_000_000), "10.0 GB");
+    }
+
+    #[test]
+    fn test_evict_and_output_quota() {
+        let dir = std::env::temp_dir().join(format!("nab-quota-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let old = SystemTime::now() - std::time::Duration::from_secs(3600);
+        for (name, age) in [("a", 1), ("sub/b", 2), ("c", 3)] {
+            let path = dir.join(name);
+            std::fs::write(&path, [0u8; 100]).unwrap();
+            let file = std::fs::File::options().append(true).open(&path).unwrap();
+            file.set_modified(old + std::time::Duration::from_secs(age))
+                .unwrap();
+        }
+        touch(&dir.join("a"));
+        assert_eq!(
+            usage(&dir),
+            Usage {
+                files: 3,
+                bytes: 300
+            }
+        );
+
+        // "sub/b" is the least recently used, then "c"
+        assert_eq!(evict(&dir, 250).unwrap().files, 1);
+        assert!(!dir.join("sub/b").exists());
+        assert!(dir.join("a").exists() && dir.join("c").exists());
+
+        let quota = OutputQuota::new(&dir, 300);
+        assert_eq!(quota.used(), 200);
+        quota.reserve(&dir.join("a"), 150).unwrap();
+        quota.reserve(&dir.join("d"), 50).unwrap();
+        assert!(!quota.is_exceeded());
+        assert!(quota.reserve(&dir.join("e"), 51).is_err());
+        assert!(quota.is_exceeded());
+        assert!(quota.error().contains("300 B"));
+
+        assert_eq!(clear(&dir).unwrap().files, 2);
+        assert_eq!(usage(&dir).bytes, 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
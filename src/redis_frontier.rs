@@ -0,0 +1,297 @@
+//! Shared Crawl Frontier (Redis)
+//!
+//! `nab crawl --frontier-redis redis://HOST/DB` keeps the frontier in Redis,
+//! so `nab crawl` processes on several machines work through one large site
+//! together: each URL is queued once, handed to one process, and the
+//! politeness delay and per-host concurrency limit hold across all of them,
+//! not per process.
+//!
+//! Everything lives under `nab:crawl:<name>:` (`--crawl-name`, by default the
+//! first seed's host):
+//!
+//! ```text
+//! seen            SET   every URL ever queued
+//! hosts           SET   hosts with queued URLs
+//! queue:<host>    ZSET  queued entries by priority
+//! in_progress     HASH  URL → entry handed out
+//! leases          ZSET  URL → lease expiry (ms)
+//! polite:<host>   key   set for the politeness delay after each request
+//! in_flight:<host>      requests running on the host
+//! completed             pages done
+//! ```
+//!
+//! Popping, pushing, and completing are Lua scripts, so each is atomic
+//! across processes. A page whose process died is queued again once its
+//! lease ([`LEASE`]) runs out. Scripts name their keys at run time, which
+//! standalone Redis (and Valkey) allow but Redis Cluster doesn't.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use redis::aio::MultiplexedConnection;
+use redis::Script;
+
+use crate::batch::{host_key, ConcurrencyLimits};
+use crate::crawl::{normalize_url, FrontierEntry};
+
+/// How long a process has to complete a page before it is handed out again
+pub const LEASE: Duration = Duration::from_secs(600);
+
+/// How often a process with nothing to do asks for work again
+pub const POLL: Duration = Duration::from_millis(250);
+
+const PUSH: &str = r"
+local p = ARGV[1]
+if redis.call('SADD', p .. ':seen', ARGV[2]) == 0 then return 0 end
+redis.call('ZADD', p .. ':queue:' .. ARGV[3], ARGV[4], ARGV[5])
+redis.call('SADD', p .. ':hosts', ARGV[3])
+return 1
+";
+
+const POP: &str = r"
+local p = ARGV[1]
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+for _, url in ipairs(redis.call('ZRANGEBYSCORE', p .. ':leases', '-inf', now)) do
+  local held = redis.call('HGET', p .. ':in_progress', url)
+  redis.call('ZREM', p .. ':leases', url)
+  redis.call('HDEL', p .. ':in_progress', url)
+  if held then
+    local lease = cjson.decode(held)
+    redis.call('ZADD', p .. ':queue:' .. lease.host, lease.priority, lease.entry)
+    redis.call('SADD', p .. ':hosts', lease.host)
+    redis.call('DECR', p .. ':in_flight:' .. lease.host)
+  end
+end
+local max_pages = tonumber(ARGV[4])
+if max_pages > 0 then
+  local done = tonumber(redis.call('GET', p .. ':completed') or '0')
+  if done + redis.call('HLEN', p .. ':in_progress') >= max_pages then return false end
+end
+local best_host, best_entry, best_priority
+for _, host in ipairs(redis.call('SMEMBERS', p .. ':hosts')) do
+  local busy = redis.call('EXISTS', p .. ':polite:' .. host) == 1
+    or tonumber(redis.call('GET', p .. ':in_flight:' .. host) or '0') >= tonumber(ARGV[3])
+  if not busy then
+    local top = redis.call('ZRANGE', p .. ':queue:' .. host, -1, -1, 'WITHSCORES')
+    if #top == 0 then
+      redis.call('SREM', p .. ':hosts', host)
+    elseif best_priority == nil or tonumber(top[2]) > best_priority then
+      best_host, best_entry, best_priority = host, top[1], tonumber(top[2])
+    end
+  end
+end
+if best_host == nil then return false end
+local queue = p .. ':queue:' .. best_host
+redis.call('ZREM', queue, best_entry)
+if redis.call('ZCARD', queue) == 0 then redis.call('SREM', p .. ':hosts', best_host) end
+if tonumber(ARGV[2]) > 0 then
+  redis.call('SET', p .. ':polite:' .. best_host, '1', 'PX', ARGV[2])
+end
+redis.call('INCR', p .. ':in_flight:' .. best_host)
+local url = cjson.decode(best_entry).url
+local lease = cjson.encode({host = best_host, priority = best_priority, entry = best_entry})
+redis.call('HSET', p .. ':in_progress', url, lease)
+redis.call('ZADD', p .. ':leases', now + tonumber(ARGV[5]), url)
+return best_entry
+";
+
+const COMPLETE: &str = r"
+local p = ARGV[1]
+if redis.call('HDEL', p .. ':in_progress', ARGV[2]) == 0 then return 0 end
+redis.call('ZREM', p .. ':leases', ARGV[2])
+redis.call('DECR', p .. ':in_flight:' .. ARGV[3])
+redis.call('INCR', p .. ':completed')
+return 1
+";
+
+const STATUS: &str = r"
+local p = ARGV[1]
+local queued = 0
+for _, host in ipairs(redis.call('SMEMBERS', p .. ':hosts')) do
+  queued = queued + redis.call('ZCARD', p .. ':queue:' .. host)
+end
+local done = tonumber(redis.call('GET', p .. ':completed') or '0')
+return {queued, redis.call('HLEN', p .. ':in_progress'), done}
+";
+
+/// Key prefix of the crawl `name`
+#[must_use]
+pub fn key_prefix(name: &str) -> String {
+    format!("nab:crawl:{name}")
+}
+
+/// The whole crawl's progress, across processes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SharedStatus {
+    pub queued: usize,
+    pub in_progress: usize,
+    pub completed: usize,
+}
+
+/// A crawl frontier shared through Redis
+pub struct RedisFrontier {
+    connection: MultiplexedConnection,
+    prefix: String,
+    pub max_depth: u32,
+    politeness: Duration,
+    limits: ConcurrencyLimits,
+    pub max_pages: Option<usize>,
+    /// Pages this process completed
+    pub completed: usize,
+    /// Pages this process has running
+    in_flight: usize,
+    /// As of the last [`Self::status`]
+    last_status: SharedStatus,
+}
+
+impl std::fmt::Debug for RedisFrontier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisFrontier")
+            .field("prefix", &self.prefix)
+            .field("completed", &self.completed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RedisFrontier {
+    /// Join (or start) the crawl `name` on the Redis server at `url`
+    pub async fn connect(
+        url: &str,
+        name: &str,
+        max_depth: u32,
+        politeness: Duration,
+        limits: ConcurrencyLimits,
+    ) -> Result<Self> {
+        let connection = redis::Client::open(url)
+            .with_context(|| format!("Invalid Redis URL {url}"))?
+            .get_multiplexed_async_connection()
+            .await
+            .with_context(|| format!("Failed to connect to {url}"))?;
+        Ok(Self {
+            connection,
+            prefix: key_prefix(name),
+            max_depth,
+            politeness,
+            limits,
+            max_pages: None,
+            completed: 0,
+            in_flight: 0,
+            last_status: SharedStatus::default(),
+        })
+    }
+
+    /// Queue a URL unless any process queued it before or it is too deep;
+    /// returns true if queued
+    pub async fn push(&mut self, url: &str, depth: u32, score: f64) -> Result<bool> {
+        if depth > self.max_depth {
+            return Ok(false);
+        }
+        let entry = FrontierEntry {
+            url: normalize_url(url),
+            depth,
+            score,
+        };
+        let queued: i64 = Script::new(PUSH)
+            .arg(&self.prefix)
+            .arg(&entry.url)
+            .arg(host_key(&entry.url))
+            .arg(entry.priority())
+            .arg(serde_json::to_string(&entry)?)
+            .invoke_async(&mut self.connection)
+            .await?;
+        Ok(queued == 1)
+    }
+
+    /// Best URL whose host no process has requested from within the
+    /// politeness delay and that is under the per-host limit everywhere
+    pub async fn pop_ready(&mut self) -> Result<Option<FrontierEntry>> {
+        if self.in_flight >= self.limits.global.max(1) {
+            return Ok(None);
+        }
+        let popped: Option<String> = Script::new(POP)
+            .arg(&self.prefix)
+            .arg(u64::try_from(self.politeness.as_millis()).unwrap_or(u64::MAX))
+            .arg(self.limits.per_host.max(1))
+            .arg(self.max_pages.unwrap_or(0))
+            .arg(u64::try_from(LEASE.as_millis()).unwrap_or(u64::MAX))
+            .invoke_async(&mut self.connection)
+            .await?;
+        let Some(entry) = popped else {
+            return Ok(None);
+        };
+        self.in_flight += 1;
+        Ok(Some(serde_json::from_str(&entry)?))
+    }
+
+    /// Mark a popped URL as crawled
+    pub async fn complete(&mut self, url: &str) -> Result<()> {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        let completed: i64 = Script::new(COMPLETE)
+            .arg(&self.prefix)
+            .arg(url)
+            .arg(host_key(url))
+            .invoke_async(&mut self.connection)
+            .await?;
+        // Zero if its lease ran out and another process has it now
+        if completed == 1 {
+            self.completed += 1;
+        }
+        Ok(())
+    }
+
+    /// Progress of the whole crawl
+    pub async fn status(&mut self) -> Result<SharedStatus> {
+        let (queued, in_progress, completed): (usize, usize, usize) = Script::new(STATUS)
+            .arg(&self.prefix)
+            .invoke_async(&mut self.connection)
+            .await?;
+        self.last_status = SharedStatus {
+            queued,
+            in_progress,
+            completed,
+        };
+        Ok(self.last_status)
+    }
+
+    /// URLs queued, as of the last [`Self::status`]
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.last_status.queued
+    }
+
+    /// Nothing queued or in progress anywhere, or `max_pages` reached
+    pub async fn is_finished(&mut self) -> Result<bool> {
+        let status = self.status().await?;
+        let limit_reached = self
+            .max_pages
+            .is_some_and(|max| status.completed + status.in_progress >= max);
+        Ok((status.queued == 0 && status.in_progress == 0) || limit_reached)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_prefix() {
+        assert_eq!(key_prefix("docs.example.com"), "nab:crawl:docs.example.com");
+    }
+
+    #[test]
+    fn test_entries_round_trip_through_scripts() {
+        // POP hands back the member PUSH stored, decoded by the script with cjson
+        let entry = FrontierEntry {
+            url: "https://example.com/a".into(),
+            depth: 1,
+            score: 0.5,
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert!(json.starts_with(r#"{"url":"#), "{json}");
+        assert_eq!(serde_json::from_str::<FrontierEntry>(&json).unwrap(), entry);
+        for script in [PUSH, POP, COMPLETE, STATUS] {
+            assert!(script.contains("local p = ARGV[1]"));
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Structured Responses
+//!
+//! [`Response`] is a fetched page as data: status, headers, final URL,
+//! redirect chain, timings, and a typed [`Body`]. Library callers get it from
+//! [`AcceleratedClient::get`](crate::AcceleratedClient::get); `nab fetch`
+//! reads every page into one and formats it from there.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::{StatusCode, Url, Version};
+use serde_json::Value;
+
+use crate::content::ContentKind;
+use crate::timing::{ConnectionTimings, RedirectHop, Timings};
+
+/// A response body, decoded as far as its content type allows
+#[derive(Debug, Clone, PartialEq)]
+pub enum Body {
+    /// Text as served (charset-decoded), including HTML not yet converted
+    Text(String),
+    /// Binary content (images, PDFs, archives, ...)
+    Bytes(Vec<u8>),
+    /// Parsed JSON
+    Json(Value),
+    /// HTML converted to Markdown
+    Markdown(String),
+}
+
+impl Body {
+    /// The body as text, unless it's binary or parsed JSON
+    #[must_use]
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) | Self::Markdown(text) => Some(text),
+            Self::Bytes(_) | Self::Json(_) => None,
+        }
+    }
+
+    /// Text of any body: bytes are decoded lossily, JSON is serialized
+    #[must_use]
+    pub fn into_text(self) -> String {
+        match self {
+            Self::Text(text) | Self::Markdown(text) => text,
+            Self::Bytes(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Self::Json(value) => value.to_string(),
+        }
+    }
+
+    /// Size in bytes (of the serialized form for JSON)
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Text(text) | Self::Markdown(text) => text.len(),
+            Self::Bytes(bytes) => bytes.len(),
+            Self::Json(value) => value.to_string().len(),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A fetched page
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: StatusCode,
+    pub version: Version,
+    /// Where the response came from, after redirects
+    pub url: Url,
+    pub headers: HeaderMap,
+    /// Phase timings; `timings.redirects` is the redirect chain
+    pub timings: Timings,
+    pub body: Body,
+}
+
+impl Response {
+    /// Download `response`'s body as text, or as bytes when it's binary by
+    /// its content type or first bytes ([`ContentKind::detect`])
+    ///
+    /// `ttfb` is the time until the response headers arrived, `connection` the
+    /// probed connection phases (if any), and `redirects` the hops followed.
+    pub async fn read(
+        response: reqwest::Response,
+        connection: Option<ConnectionTimings>,
+        ttfb: Duration,
+        redirects: Vec<RedirectHop>,
+    ) -> Result<Self> {
+        let (status, version) = (response.status(), response.version());
+        let (url, headers) = (response.url().clone(), response.headers().clone());
+        let content_type = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok());
+        let declared = response.content_length();
+        let start = Instant::now();
+        let bytes = response.bytes().await?;
+        crate::traffic::response(url.as_str(), status.as_u16(), bytes.len(), declared);
+        let body = if ContentKind::detect(content_type, &bytes).is_binary() {
+            Body::Bytes(bytes.to_vec())
+        } else {
+            Body::Text(decode_text(&bytes, content_type))
+        };
+        let timings = Timings::new(connection, ttfb, start.elapsed(), None, redirects);
+        Ok(Self {
+            status,
+            version,
+            url,
+            headers,
+            timings,
+            body,
+        })
+    }
+
+    /// `Content-Type` without parameters, lowercased
+    #[must_use]
+    pub fn content_type(&self) -> Option<String> {
+        let ct = self.headers.get(CONTENT_TYPE)?.to_str().ok()?;
+        Some(ct.split(';').next()?.trim().to_ascii_lowercase())
+    }
+
+    /// What the body is, by its content type and first bytes
+    #[must_use]
+    pub fn kind(&self) -> ContentKind {
+        let content_type = self.headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok());
+        match &self.body {
+            Body::Text(text) => ContentKind::detect(content_type, text.as_bytes()),
+            Body::Bytes(bytes) => ContentKind::detect(content_type, bytes),
+            Body::Json(_) => ContentKind::Json,
+            Body::Markdown(_) => ContentKind::Html,
+        }
+    }
+
+    #[must_use]
+    pub fn is_html(&self) -> bool {
+        self.kind() == ContentKind::Html
+    }
+
+    #[must_use]
+    pub fn is_json(&self) -> bool {
+        self.kind() == ContentKind::Json
+    }
+
+    /// Redirects followed on the way to [`Self::url`]
+    #[must_use]
+    pub fn redirects(&self) -> &[RedirectHop] {
+        &self.timings.redirects
+    }
+
+    /// HTML converted to [`Body::Markdown`] (timed as `parse`) and JSON
+    /// parsed into [`Body::Json`]; other bodies, and JSON that doesn't
+    /// parse, stay as they are
+    #[must_use]
+    pub fn parsed(mut self) -> Self {
+        let Body::Text(text) = &self.body else {
+            return self;
+        };
+        let kind = self.kind();
+        if kind == ContentKind::Html {
+            let start = Instant::now();
+            let markdown = crate::page::html_to_markdown(text);
+            self.timings.add_parse(start.elapsed());
+            self.body = Body::Markdown(markdown);
+        } else if kind == ContentKind::Json {
+            if let Ok(value) = serde_json::from_str(text) {
+                self.body = Body::Json(value);
+            }
+        }
+        self
+    }
+}
+
+/// `bytes` decoded with `content_type`'s charset, UTF-8 by default (as
+/// [`reqwest::Response::text`] would)
+fn decode_text(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(|ct| {
+            ct.split(';').skip(1).find_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("charset")
+                    .then(|| value.trim().trim_matches('"'))
+            })
+        })
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serves one response with `content_type` and `body` per connection
+    async fn server(content_type: &'static str, body: &'static [u8]) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(body).await;
+            }
+        });
+        format!("http://{addr}/")
+    }
+
+    async fn get(content_type: &'static str, body: &'static [u8]) -> Response {
+        let url = server(content_type, body).await;
+        let client = reqwest::Client::builder().http1_only().build().unwrap();
+        let response = client.get(&url).send().await.unwrap();
+        Response::read(response, None, Duration::from_millis(5), Vec::new())
+            .await
+            .unwrap()
+            .parsed()
+    }
+
+    #[tokio::test]
+    async fn test_body_variants() {
+        const HTML: &str = "<html><body><h2>Hi</h2><p>There</p></body></html>";
+        let page = get("text/html; charset=utf-8", HTML.as_bytes()).await;
+        assert_eq!(page.status, StatusCode::OK);
+        assert!(page.is_html());
+        assert_eq!(
+            page.body,
+            Body::Markdown(crate::page::html_to_markdown(HTML))
+        );
+        assert!(page.timings.parse_ms.is_some());
+
+        let api = get("application/problem+json", br#"{"ok": true}"#).await;
+        assert_eq!(api.body, Body::Json(serde_json::json!({"ok": true})));
+
+        let broken = get("application/json", b"{oops").await;
+        assert_eq!(broken.body.as_text(), Some("{oops"));
+
+        let image = get("image/png", b"\x89PNG\r\n").await;
+        assert_eq!(image.body, Body::Bytes(b"\x89PNG\r\n".to_vec()));
+        assert_eq!(image.body.len(), 6);
+        assert!(image.timings.parse_ms.is_none());
+
+        // Sniffed: a mislabeled PDF, unlabeled JSON, and a legacy charset
+        let pdf = get("text/html", b"%PDF-1.4\n\xe2\xe3").await;
+        assert_eq!(pdf.kind(), ContentKind::Pdf);
+        assert_eq!(pdf.body, Body::Bytes(b"%PDF-1.4\n\xe2\xe3".to_vec()));
+        let plain = get("text/plain", br#"[1, 2]"#).await;
+        assert_eq!(plain.body, Body::Json(serde_json::json!([1, 2])));
+        let latin1 = get("text/plain; charset=iso-8859-1", b"caf\xe9").await;
+        assert_eq!(latin1.body.as_text(), Some("café"));
+    }
+}
@@ -0,0 +1,310 @@
+//! Repeat-Visit Cache (`--revisit`)
+//!
+//! A browser coming back to a page sends the validators it got last time
+//! (`If-None-Match`, `If-Modified-Since`) and is answered `304 Not Modified`
+//! when nothing changed. `--revisit <persona>` does the same: each persona
+//! (one stable browser identity, see [`persona_profile`]) keeps its own cache
+//! of validators and bodies, so unchanged pages cost a 304 instead of a full
+//! download and the traffic looks like a returning visitor's.
+//!
+//! Entries live in `~/.cache/nab/revisit/<persona>/` (or the
+//! [workspace](crate::workspace)'s cache), one zstd-compressed JSON file per
+//! URL, with the persona's per-host [dictionaries](crate::compress).
+//! [`RevisitCache::compress_all`] rewrites older plain-JSON entries and
+//! trains the dictionaries.
+//!
+//! [`persona_profile`]: crate::fingerprint::persona_profile
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::compress::{Dictionaries, Trained, MIN_SAMPLES};
+use crate::http_client::Validators;
+
+/// A stored response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedPage {
+    pub url: String,
+    #[serde(flatten)]
+    pub validators: Validators,
+    pub content_type: Option<String>,
+    /// `X-Robots-Tag` directives, which a `304` doesn't repeat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub robots: Option<String>,
+    pub body: String,
+    /// RFC 3339
+    pub stored_at: String,
+}
+
+impl CachedPage {
+    /// Entry for a response, if it has validators to revisit with
+    #[must_use]
+    pub fn from_response(url: &str, headers: &HeaderMap, body: &str) -> Option<Self> {
+        let validators = Validators::from_headers(headers);
+        if validators.is_empty() {
+            return None;
+        }
+        Some(Self {
+            url: url.to_string(),
+            validators,
+            content_type: headers
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from),
+            robots: robots_header(headers),
+            body: body.to_string(),
+            stored_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+/// All `X-Robots-Tag` values of a response, comma-joined
+#[must_use]
+pub fn robots_header(headers: &HeaderMap) -> Option<String> {
+    let values: Vec<&str> = headers
+        .get_all("x-robots-tag")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+/// One persona's cache
+#[derive(Debug, Clone)]
+pub struct RevisitCache {
+    dir: PathBuf,
+    dictionaries: Arc<Dictionaries>,
+}
+
+/// What [`RevisitCache::compress_all`] did
+#[derive(Debug, Default, Serialize)]
+pub struct CompressReport {
+    pub entries: usize,
+    /// Entry bytes before and after
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub dictionaries: Vec<Trained>,
+}
+
+impl RevisitCache {
+    /// Cache of `persona` in the default location
+    pub fn open(persona: &str) -> Result<Self> {
+        if persona.is_empty()
+            || !persona
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            || persona.starts_with('.')
+        {
+            bail!("Invalid persona '{persona}': use letters, digits, '-', '_', and '.'");
+        }
+        let dir = crate::workspace::cache_dir().join("revisit").join(persona);
+        Ok(Self::at(dir))
+    }
+
+    /// Cache in `dir`
+    #[must_use]
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let dictionaries = Arc::new(Dictionaries::open(dir.join("dictionaries")));
+        Self { dir, dictionaries }
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Stored entry for `url`
+    #[must_use]
+    pub fn get(&self, url: &str) -> Option<CachedPage> {
+        let name = entry_name(url);
+        let (path, page) = [format!("{name}.json.zst"), format!("{name}.json")]
+            .into_iter()
+            .map(|file| self.dir.join(file))
+            .find_map(|path| self.read(&path).map(|page| (path, page)))?;
+        if page.url != url {
+            return None;
+        }
+        // A hit keeps the entry from being evicted early
+        crate::quota::touch(&path);
+        Some(page)
+    }
+
+    /// Store (or replace) the entry for `page.url`
+    pub fn store(&self, page: &CachedPage) -> Result<()> {
+        let host = url::Url::parse(&page.url)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_default();
+        let data = self
+            .dictionaries
+            .compress(&host, &serde_json::to_vec(page)?)?;
+        let name = entry_name(&page.url);
+        crate::state::write_atomic(&self.dir.join(format!("{name}.json.zst")), &data)?;
+        // Written before compression
+        let _ = std::fs::remove_file(self.dir.join(format!("{name}.json")));
+        Ok(())
+    }
+
+    /// Forget `url` (e.g. after it disappeared)
+    pub fn remove(&self, url: &str) {
+        let name = entry_name(url);
+        let _ = std::fs::remove_file(self.dir.join(format!("{name}.json.zst")));
+        let _ = std::fs::remove_file(self.dir.join(format!("{name}.json")));
+    }
+
+    /// Rewrite every entry compressed; with `train`, first train a
+    /// dictionary for each host with at least [`MIN_SAMPLES`] entries
+    pub fn compress_all(&self, train: bool) -> Result<CompressReport> {
+        let mut report = CompressReport::default();
+        let mut by_host: BTreeMap<String, Vec<CachedPage>> = BTreeMap::new();
+        for path in self.entry_files() {
+            let Some(page) = self.read(&path) else {
+                continue;
+            };
+            report.entries += 1;
+            report.bytes_before += std::fs::metadata(&path).map_or(0, |meta| meta.len());
+            let host = url::Url::parse(&page.url)
+                .ok()
+                .and_then(|url| url.host_str().map(String::from))
+                .unwrap_or_default();
+            by_host.entry(host).or_default().push(page);
+        }
+        for (host, pages) in &by_host {
+            if train && pages.len() >= MIN_SAMPLES {
+                let samples = pages
+                    .iter()
+                    .map(serde_json::to_vec)
+                    .collect::<serde_json::Result<Vec<_>>>()?;
+                report
+                    .dictionaries
+                    .push(self.dictionaries.train(host, &samples)?);
+            }
+            for page in pages {
+                self.store(page)?;
+            }
+        }
+        report.bytes_after = self
+            .entry_files()
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum();
+        Ok(report)
+    }
+
+    /// Entry at `path`, compressed or (written by older versions) plain
+    fn read(&self, path: &Path) -> Option<CachedPage> {
+        let data = std::fs::read(path).ok()?;
+        let data = if path.extension().is_some_and(|ext| ext == "zst") {
+            self.dictionaries.decompress(&data).ok()?
+        } else {
+            data
+        };
+        serde_json::from_slice(&data).ok()
+    }
+
+    fn entry_files(&self) -> Vec<PathBuf> {
+        std::fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                name.ends_with(".json") || name.ends_with(".json.zst")
+            })
+            .collect()
+    }
+}
+
+/// File name of `url`'s entry, without extension
+fn entry_name(url: &str) -> String {
+    let digest = Sha256::digest(url.as_bytes());
+    digest[..16].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderValue, ETAG};
+
+    #[test]
+    fn test_store_and_get() {
+        let dir = std::env::temp_dir().join(format!("nab-revisit-{}", std::process::id()));
+        let cache = RevisitCache::at(&dir);
+        let url = "https://news.example/";
+        assert_eq!(cache.get(url), None);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(CachedPage::from_response(url, &headers, "<p>hi</p>"), None);
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.append("x-robots-tag", HeaderValue::from_static("noarchive"));
+        headers.append(
+            "x-robots-tag",
+            HeaderValue::from_static("otherbot: noindex"),
+        );
+        let page = CachedPage::from_response(url, &headers, "<p>hi</p>").unwrap();
+        cache.store(&page).unwrap();
+
+        let stored = cache.get(url).unwrap();
+        assert_eq!(stored.validators.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(stored.body, "<p>hi</p>");
+        assert_eq!(
+            stored.robots.as_deref(),
+            Some("noarchive, otherbot: noindex")
+        );
+        assert_eq!(cache.get("https://news.example/other"), None);
+        cache.remove(url);
+        assert_eq!(cache.get(url), None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_compress_all() {
+        let dir = std::env::temp_dir().join(format!("nab-revisit-zst-{}", std::process::id()));
+        let cache = RevisitCache::at(&dir);
+        let page = |n: usize| CachedPage {
+            url: format!("https://blog.example/post/{n}"),
+            validators: Validators::default(),
+            content_type: Some("text/html".into()),
+            robots: None,
+            body: format!(
+                "<html><head><title>Post {n}</title></head><body><nav>Home | Archive</nav>\
+                 <article>Post {n}: {}</article><footer>Blog footer</footer></body></html>",
+                "words ".repeat(n % 5 + 1)
+            ),
+            stored_at: "2026-01-01T00:00:00+00:00".into(),
+        };
+        // Written by an older version
+        for n in 0..20 {
+            let legacy = dir.join(format!("{}.json", entry_name(&page(n).url)));
+            crate::state::write_atomic(&legacy, &serde_json::to_vec(&page(n)).unwrap()).unwrap();
+        }
+        assert_eq!(cache.get(&page(3).url), Some(page(3)));
+
+        let report = cache.compress_all(true).unwrap();
+        assert_eq!(report.entries, 20);
+        assert!(report.bytes_after < report.bytes_before);
+        assert_eq!(report.dictionaries[0].host, "blog.example");
+        assert!(!dir
+            .join(format!("{}.json", entry_name(&page(3).url)))
+            .exists());
+        assert_eq!(RevisitCache::at(&dir).get(&page(3).url), Some(page(3)));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_persona_names() {
+        assert!(RevisitCache::open("alice-laptop").is_ok());
+        assert!(RevisitCache::open("../etc").is_err());
+        assert!(RevisitCache::open("").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Page JS Sandbox Limits
+//!
+//! Hard caps for scripts executed by the SPA JS engine:
+//! - Wall-clock execution budget (`QuickJS` interrupt handler)
+//! - Heap memory limit (`QuickJS` allocator limit)
+//! - Network policy for `fetch()` calls made by the page
+//!
+//! Violations are collected instead of hanging or aborting the CLI, so they
+//! can be reported alongside the extracted data.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(feature = "spa")]
+use std::time::Instant;
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// Network policy for `fetch()` calls made by page scripts
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum NetworkPolicy {
+    /// Any URL may be fetched
+    #[default]
+    Allow,
+    /// No network access at all
+    Deny,
+    /// Only URLs on the page's own origin
+    SameOrigin,
+    /// Only URLs whose host matches (or is a subdomain of) a listed host
+    Allowlist(Vec<String>),
+}
+
+impl NetworkPolicy {
+    /// Parse a policy name: `allow`, `deny`, `same-origin`, or `allowlist`
+    ///
+    /// `allowlist` takes its hosts from `hosts`; they are ignored otherwise.
+    pub fn parse(name: &str, hosts: &[String]) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "allow" | "all" => Ok(Self::Allow),
+            "deny" | "none" => Ok(Self::Deny),
+            "same-origin" | "same_origin" => Ok(Self::SameOrigin),
+            "allowlist" => {
+                if hosts.is_empty() {
+                    anyhow::bail!("Network policy 'allowlist' needs at least one --js-allow host");
+                }
+                Ok(Self::Allowlist(
+                    hosts.iter().map(|h| h.trim().to_lowercase()).collect(),
+                ))
+            }
+            other => anyhow::bail!(
+                "Unknown network policy: {other}. Use allow, deny, same-origin, or allowlist."
+            ),
+        }
+    }
+
+    /// Check whether `url` may be fetched by a page loaded from `page_origin`
+    #[must_use]
+    pub fn permits(&self, url: &str, page_origin: &str) -> bool {
+        match self {
+            Self::Allow => true,
+            Self::Deny => false,
+            Self::SameOrigin => url::Url::parse(url).is_ok_and(|u| {
+                !page_origin.is_empty() && u.origin().unicode_serialization() == page_origin
+            }),
+            Self::Allowlist(hosts) => url::Url::parse(url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_lowercase))
+                .is_some_and(|host| {
+                    hosts
+                        .iter()
+                        .any(|h| host == *h || host.ends_with(&format!(".{h}")))
+                }),
+        }
+    }
+
+    /// Short name for reporting
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Deny => "deny",
+            Self::SameOrigin => "same-origin",
+            Self::Allowlist(_) => "allowlist",
+        }
+    }
+}
+
+/// Resource limits for page JavaScript
+#[derive(Debug, Clone)]
+pub struct SandboxLimits {
+    /// Total JS execution time across all scripts (None = unlimited)
+    pub timeout: Option<Duration>,
+    /// Heap memory limit in bytes
+    pub memory_limit: usize,
+    /// Policy for `fetch()` calls
+    pub network: NetworkPolicy,
+}
+
+impl Default for SandboxLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Some(Duration::from_secs(30)),
+            memory_limit: 32 * 1024 * 1024,
+            network: NetworkPolicy::Allow,
+        }
+    }
+}
+
+/// A sandbox limit that page JS ran into
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SandboxViolation {
+    /// Execution budget exhausted; script was interrupted
+    Timeout { limit_ms: u64 },
+    /// Heap limit reached; script was aborted
+    Memory { limit_bytes: usize },
+    /// A `fetch()` call was refused by the network policy
+    Network { url: String, policy: String },
+}
+
+impl std::fmt::Display for SandboxViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout { limit_ms } => write!(f, "execution time limit ({limit_ms}ms) exceeded"),
+            Self::Memory { limit_bytes } => {
+                write!(
+                    f,
+                    "memory limit ({} MB) exceeded",
+                    limit_bytes / (1024 * 1024)
+                )
+            }
+            Self::Network { url, policy } => write!(f, "fetch blocked by {policy} policy: {url}"),
+        }
+    }
+}
+
+/// Shared, cloneable log of sandbox violations
+#[derive(Debug, Clone, Default)]
+pub struct ViolationLog(Arc<Mutex<Vec<SandboxViolation>>>);
+
+impl ViolationLog {
+    /// Record a violation (duplicates are kept once)
+    pub fn record(&self, violation: SandboxViolation) {
+        if let Ok(mut log) = self.0.lock() {
+            if !log.contains(&violation) {
+                log.push(violation);
+            }
+        }
+    }
+
+    /// Snapshot of all recorded violations
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<SandboxViolation> {
+        self.0.lock().map(|l| l.clone()).unwrap_or_default()
+    }
+}
+
+/// Execution-time budget shared with the `QuickJS` interrupt handler
+///
+/// Only time spent inside `eval` counts, so waiting for async work between
+/// scripts does not eat into the budget.
+#[cfg(feature = "spa")]
+#[derive(Debug)]
+pub(crate) struct ExecBudget {
+    limit: Option<Duration>,
+    spent: Duration,
+    running_since: Option<Instant>,
+}
+
+#[cfg(feature = "spa")]
+impl ExecBudget {
+    pub(crate) fn new(limit: Option<Duration>) -> Self {
+        Self {
+            limit,
+            spent: Duration::ZERO,
+            running_since: None,
+        }
+    }
+
+    pub(crate) fn start(&mut self) {
+        self.running_since = Some(Instant::now());
+    }
+
+    pub(crate) fn stop(&mut self) {
+        if let Some(since) = self.running_since.take() {
+            self.spent += since.elapsed();
+        }
+    }
+
+    /// Whether a sandboxed script is running and has used up the budget
+    pub(crate) fn exhausted(&self) -> bool {
+        match (self.limit, self.running_since) {
+            (Some(limit), Some(since)) => self.spent + since.elapsed() > limit,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_parse() {
+        assert_eq!(
+            NetworkPolicy::parse("deny", &[]).unwrap(),
+            NetworkPolicy::Deny
+        );
+        assert_eq!(
+            NetworkPolicy::parse("same-origin", &[]).unwrap(),
+            NetworkPolicy::SameOrigin
+        );
+        assert!(NetworkPolicy::parse("allowlist", &[]).is_err());
+        assert!(NetworkPolicy::parse("sometimes", &[]).is_err());
+    }
+
+    #[test]
+    fn test_same_origin() {
+        let policy = NetworkPolicy::SameOrigin;
+        let origin = "https://example.com";
+        assert!(policy.permits("https://example.com/api/data", origin));
+        assert!(!policy.permits("https://api.example.com/data", origin));
+        assert!(!policy.permits("http://example.com/api", origin));
+    }
+
+    #[test]
+    fn test_allowlist_subdomains() {
+        let policy = NetworkPolicy::parse("allowlist", &["Example.com".to_string()]).unwrap();
+        assert!(policy.permits("https://example.com/a", ""));
+        assert!(policy.permits("https://api.example.com/a", ""));
+        assert!(!policy.permits("https://notexample.com/a", ""));
+        assert!(!NetworkPolicy::Deny.permits("https://example.com/a", ""));
+    }
+
+    #[test]
+    fn test_violation_json() {
+        let v = SandboxViolation::Network {
+            url: "https://tracker.example/".into(),
+            policy: "deny".into(),
+        };
+        let json = serde_json::to_value(&v).unwrap();
+        assert_eq!(json["kind"], "network");
+        assert_eq!(json["policy"], "deny");
+    }
+}
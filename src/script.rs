@@ -0,0 +1,260 @@
+//! Page Scripts
+//!
+//! `nab fetch --script transform.rhai` runs a [Rhai](https://rhai.rs) script's
+//! hooks on the page, for light cleanup without recompiling or piping through
+//! other tools:
+//!
+//! ```rhai
+//! // Before conversion: drop the cookie notice markup
+//! fn on_response(html, meta) {
+//!     regex_replace(html, "(?s)<div id=\"cookie-notice\">.*?</div>", "")
+//! }
+//!
+//! // After conversion: absolute links, plus a field for the JSON output
+//! fn on_markdown(md, meta) {
+//!     md.replace("](/", "](https://example.com/");
+//!     #{ text: md, source: meta.url }
+//! }
+//! ```
+//!
+//! Both hooks are optional. `meta` has `url`, `final_url`, `status`,
+//! `content_type`, and `is_html`. A hook returns the new text, `()` to leave
+//! it alone, or a map whose `text` entry (if any) replaces it and whose other
+//! entries become fields of the page (`fields` in `--format json`).
+//! Besides Rhai's string functions, scripts get `regex_replace(text, pattern,
+//! replacement)` and `regex_match(text, pattern)`.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use serde_json::Value;
+
+/// Operations a hook may run before it's stopped (runaway loops)
+const MAX_OPERATIONS: u64 = 50_000_000;
+
+/// What hooks know about the page
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageMeta {
+    /// URL as requested
+    pub url: String,
+    /// URL after redirects
+    pub final_url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub is_html: bool,
+}
+
+impl PageMeta {
+    fn to_map(&self) -> Map {
+        let mut map = Map::new();
+        map.insert("url".into(), self.url.clone().into());
+        map.insert("final_url".into(), self.final_url.clone().into());
+        map.insert("status".into(), i64::from(self.status).into());
+        map.insert(
+            "content_type".into(),
+            self.content_type.clone().map_or(Dynamic::UNIT, Into::into),
+        );
+        map.insert("is_html".into(), self.is_html.into());
+        map
+    }
+}
+
+/// A compiled script with `on_response` and/or `on_markdown` hooks
+pub struct PageScript {
+    engine: Engine,
+    ast: AST,
+    /// Fields added by hooks
+    fields: Mutex<serde_json::Map<String, Value>>,
+}
+
+impl std::fmt::Debug for PageScript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PageScript")
+            .field("on_response", &self.has_hook("on_response"))
+            .field("on_markdown", &self.has_hook("on_markdown"))
+            .finish_non_exhaustive()
+    }
+}
+
+impl PageScript {
+    /// Compile the script at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::compile(&source).with_context(|| format!("Invalid script {}", path.display()))
+    }
+
+    /// Compile a script; it must define at least one hook
+    pub fn compile(source: &str) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.register_fn("regex_replace", regex_replace);
+        engine.register_fn("regex_match", regex_match);
+        let ast = engine.compile(source).map_err(|e| anyhow::anyhow!("{e}"))?;
+        let script = Self {
+            engine,
+            ast,
+            fields: Mutex::default(),
+        };
+        if !script.has_hook("on_response") && !script.has_hook("on_markdown") {
+            anyhow::bail!(
+                "Script defines neither on_response(html, meta) nor on_markdown(md, meta)"
+            );
+        }
+        Ok(script)
+    }
+
+    fn has_hook(&self, name: &str) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == 2)
+    }
+
+    /// Run `on_response` on the body as downloaded
+    pub fn on_response(&self, body: String, meta: &PageMeta) -> Result<String> {
+        self.call("on_response", body, meta)
+    }
+
+    /// Run `on_markdown` on the converted page
+    pub fn on_markdown(&self, markdown: String, meta: &PageMeta) -> Result<String> {
+        self.call("on_markdown", markdown, meta)
+    }
+
+    /// Fields the hooks have added so far
+    #[must_use]
+    pub fn fields(&self) -> serde_json::Map<String, Value> {
+        self.fields
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    fn call(&self, hook: &str, text: String, meta: &PageMeta) -> Result<String> {
+        if !self.has_hook(hook) {
+            return Ok(text);
+        }
+        let result: Dynamic = self
+            .engine
+            .call_fn(
+                &mut Scope::new(),
+                &self.ast,
+                hook,
+                (text.clone(), meta.to_map()),
+            )
+            .map_err(|e| anyhow::anyhow!("{hook} failed: {e}"))?;
+
+        if result.is_unit() {
+            return Ok(text);
+        }
+        if result.is_string() {
+            return Ok(result.into_string().unwrap_or(text));
+        }
+        let Some(map) = result.try_cast::<Map>() else {
+            anyhow::bail!("{hook} must return a string, a map, or ()");
+        };
+        let mut text = text;
+        let mut fields = self
+            .fields
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        for (key, value) in map {
+            if key == "text" {
+                text = value
+                    .into_string()
+                    .map_err(|t| anyhow::anyhow!("{hook}: text must be a string, not {t}"))?;
+            } else {
+                let value: Value = rhai::serde::from_dynamic(&value)
+                    .map_err(|e| anyhow::anyhow!("{hook}: field {key}: {e}"))?;
+                fields.insert(key.to_string(), value);
+            }
+        }
+        Ok(text)
+    }
+}
+
+fn regex_replace(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+) -> Result<String, Box<EvalAltResult>> {
+    let re = regex::Regex::new(pattern).map_err(|e| e.to_string())?;
+    Ok(re.replace_all(text, replacement).into_owned())
+}
+
+fn regex_match(text: &str, pattern: &str) -> Result<bool, Box<EvalAltResult>> {
+    let re = regex::Regex::new(pattern).map_err(|e| e.to_string())?;
+    Ok(re.is_match(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta() -> PageMeta {
+        PageMeta {
+            url: "https://example.com/a".to_string(),
+            final_url: "https://example.com/a".to_string(),
+            status: 200,
+            content_type: Some("text/html".to_string()),
+            is_html: true,
+        }
+    }
+
+    #[test]
+    fn test_hooks_transform_and_add_fields() {
+        let script = PageScript::compile(
+            r#"
+            fn on_response(html, meta) {
+                regex_replace(html, "(?s)<aside>.*?</aside>", "")
+            }
+            fn on_markdown(md, meta) {
+                if meta.status != 200 { return; }
+                md.replace("](/", "](https://example.com/");
+                #{ text: md, source: meta.url, words: 3 }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let html = script
+            .on_response("<p>Hi</p><aside>ads\nmore</aside>".to_string(), &meta())
+            .unwrap();
+        assert_eq!(html, "<p>Hi</p>");
+
+        let md = script
+            .on_markdown("[Home](/index)".to_string(), &meta())
+            .unwrap();
+        assert_eq!(md, "[Home](https://example.com/index)");
+        let fields = script.fields();
+        assert_eq!(fields["source"], "https://example.com/a");
+        assert_eq!(fields["words"], 3);
+
+        let failed = PageMeta {
+            status: 404,
+            ..meta()
+        };
+        assert_eq!(script.on_markdown("x".to_string(), &failed).unwrap(), "x");
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert!(PageScript::compile("let x = 1;").is_err());
+        assert!(PageScript::compile("fn on_markdown(md, meta) {").is_err());
+
+        let only_markdown = PageScript::compile("fn on_markdown(md, meta) { md + \"!\" }").unwrap();
+        assert_eq!(
+            only_markdown
+                .on_response("<p>".to_string(), &meta())
+                .unwrap(),
+            "<p>"
+        );
+
+        let bad = PageScript::compile("fn on_markdown(md, meta) { 42 }").unwrap();
+        assert!(bad.on_markdown("x".to_string(), &meta()).is_err());
+
+        let endless = PageScript::compile("fn on_markdown(md, meta) { loop {} }").unwrap();
+        assert!(endless.on_markdown("x".to_string(), &meta()).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+//! Self-Update (`nab self update`)
+//!
+//! Replaces the running binary with the newest GitHub release for this
+//! platform, so fleet machines don't need separate update tooling:
+//!
+//! 1. the releases are listed and the newest one of the channel picked
+//!    (`stable`: full releases, `beta`: pre-releases too)
+//! 2. the platform binary (`nab-<target>`) is downloaded and its SHA-256
+//!    compared with the release's `checksums-sha256.txt`
+//! 3. the checksums must carry a valid Ed25519 signature
+//!    (`checksums-sha256.txt.sig`) by the release key compiled into release
+//!    builds, or by `self_update.public_key`, so a tampered release can't
+//!    push a binary
+//! 4. the new binary is written next to the old one and renamed over it, so
+//!    an interrupted update leaves the old binary in place
+//!
+//! `"self_update": {"disabled": true}` turns the command off, e.g. on
+//! air-gapped hosts or machines updated by a package manager.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::SelfUpdateConfig;
+
+/// Release listing of the nab repository
+pub const DEFAULT_RELEASES_URL: &str = "https://api.github.com/repos/MikkoParkkola/nab/releases";
+
+/// Release asset with the SHA-256 of every binary (`sha256sum` format)
+pub const CHECKSUMS: &str = "checksums-sha256.txt";
+
+/// Release asset with the Ed25519 signature of [`CHECKSUMS`]
+pub const SIGNATURE: &str = "checksums-sha256.txt.sig";
+
+/// Hex Ed25519 key releases are signed with, set by the release workflow
+/// (`NAB_RELEASE_PUBLIC_KEY`); `self_update.public_key` overrides it
+pub const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("NAB_RELEASE_PUBLIC_KEY");
+
+/// Which releases to follow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Full releases only
+    #[default]
+    Stable,
+    /// Pre-releases too
+    Beta,
+}
+
+impl FromStr for Channel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "stable" => Ok(Self::Stable),
+            "beta" => Ok(Self::Beta),
+            _ => bail!("Unknown channel '{s}', expected stable or beta"),
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Stable => "stable",
+            Self::Beta => "beta",
+        })
+    }
+}
+
+/// A GitHub release
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+impl Release {
+    /// Version without the tag's `v`
+    #[must_use]
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// A downloadable file of a release
+#[derive(Debug, Clone, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+/// Name of this platform's binary in a release, if nab is released for it
+#[must_use]
+pub fn asset_name() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("macos", "aarch64") => Some("nab-aarch64-apple-darwin"),
+        ("macos", "x86_64") => Some("nab-x86_64-apple-darwin"),
+        ("linux", "x86_64") => Some("nab-x86_64-unknown-linux-gnu"),
+        ("linux", "aarch64") => Some("nab-aarch64-unknown-linux-gnu"),
+        ("windows", "x86_64") => Some("nab-x86_64-pc-windows-msvc.exe"),
+        _ => None,
+    }
+}
+
+/// Newest release of `channel` (GitHub lists the newest first)
+#[must_use]
+pub fn pick(releases: &[Release], channel: Channel) -> Option<&Release> {
+    releases
+        .iter()
+        .filter(|release| !release.draft)
+        .find(|release| channel == Channel::Beta || !release.prerelease)
+}
+
+/// Whether version `candidate` is newer than `current` (`1.2.0-beta.1` < `1.2.0`)
+#[must_use]
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    fn parse(version: &str) -> (Vec<u64>, Option<&str>) {
+        let (numbers, pre) = match version.split_once('-') {
+            Some((numbers, pre)) => (numbers, Some(pre)),
+            None => (version, None),
+        };
+        let numbers = numbers.split('.').map(|n| n.parse().unwrap_or(0)).collect();
+        (numbers, pre)
+    }
+    let (candidate, candidate_pre) = parse(candidate);
+    let (current, current_pre) = parse(current);
+    match candidate.cmp(&current) {
+        std::cmp::Ordering::Equal => match (candidate_pre, current_pre) {
+            (None, Some(_)) => true,
+            (Some(a), Some(b)) => a > b,
+            _ => false,
+        },
+        order => order.is_gt(),
+    }
+}
+
+/// SHA-256 of `asset` in a `sha256sum` listing
+#[must_use]
+pub fn expected_checksum(checksums: &str, asset: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == asset).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+/// Lower-case hex SHA-256 of `data`
+#[must_use]
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Check the Ed25519 `signature` of `message` against a hex `public_key`
+pub fn verify_signature(public_key: &str, message: &[u8], signature: &[u8]) -> Result<()> {
+    let key = decode_hex(public_key.trim()).context("self_update.public_key isn't hex")?;
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key)
+        .verify(message, signature)
+        .map_err(|_| anyhow::anyhow!("The release's {CHECKSUMS} signature is invalid"))
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        bail!("Expected an even number of hex digits");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).context("Invalid hex digit"))
+        .collect()
+}
+
+/// Replace the executable at `path` with `binary` in one step
+pub fn replace_executable(path: &Path, binary: &[u8]) -> Result<()> {
+    let staged = path.with_extension("new");
+    std::fs::write(&staged, binary)
+        .with_context(|| format!("Failed to write {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+    // Windows can't replace a running executable, but it can rename it
+    #[cfg(windows)]
+    let old = {
+        let old = path.with_extension("old.exe");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(path, &old)
+            .with_context(|| format!("Failed to move {} aside", path.display()))?;
+        old
+    };
+    std::fs::rename(&staged, path).with_context(|| {
+        let _ = std::fs::remove_file(&staged);
+        // Put the old binary back rather than leave no nab at all
+        #[cfg(windows)]
+        let _ = std::fs::rename(&old, path);
+        format!("Failed to replace {}", path.display())
+    })
+}
+
+/// Checks for and installs new releases
+pub struct Updater {
+    config: SelfUpdateConfig,
+    http: reqwest::Client,
+}
+
+/// A newer release for this platform
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub release: Release,
+    pub asset: Asset,
+}
+
+impl Updater {
+    /// Updater following `config`; fails if updates are disabled there
+    pub fn new(config: SelfUpdateConfig) -> Result<Self> {
+        if config.disabled {
+            bail!("Self-update is disabled in the config (self_update.disabled)");
+        }
+        Ok(Self {
+            config,
+            http: crate::policy::client_builder()
+                .user_agent(concat!("nab/", env!("CARGO_PKG_VERSION")))
+                .timeout(Duration::from_secs(300))
+                .build()?,
+        })
+    }
+
+    /// The newest release of `channel` if it's newer than `current`
+    pub async fn check(&self, channel: Channel, current: &str) -> Result<Option<Update>> {
+        let url = self
+            .config
+            .releases_url
+            .as_deref()
+            .unwrap_or(DEFAULT_RELEASES_URL);
+        let releases: Vec<Release> = self
+            .http
+            .get(url)
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Failed to list releases at {url}"))?
+            .json()
+            .await
+            .context("Unexpected release listing")?;
+        let Some(release) = pick(&releases, channel) else {
+            bail!("No {channel} releases at {url}");
+        };
+        if !is_newer(release.version(), current) {
+            return Ok(None);
+        }
+        let Some(name) = asset_name() else {
+            bail!(
+                "No nab releases for {}-{}",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            );
+        };
+        let Some(asset) = release.asset(name) else {
+            bail!("Release {} has no {name}", release.tag_name);
+        };
+        Ok(Some(Update {
+            asset: asset.clone(),
+            release: release.clone(),
+        }))
+    }
+
+    /// Download, verify, and install `update` over the executable at `path`
+    pub async fn install(&self, update: &Update, path: &Path) -> Result<()> {
+        let release = &update.release;
+        let Some(checksums) = release.asset(CHECKSUMS) else {
+            bail!("Release {} has no {CHECKSUMS}", release.tag_name);
+        };
+        let checksums = self.download(checksums).await?;
+        let public_key = self.public_key()?;
+        let Some(signature) = release.asset(SIGNATURE) else {
+            bail!(
+                "Release {} isn't signed ({SIGNATURE} missing)",
+                release.tag_name
+            );
+        };
+        verify_signature(public_key, &checksums, &self.download(signature).await?)?;
+        let Some(expected) =
+            expected_checksum(&String::from_utf8_lossy(&checksums), &update.asset.name)
+        else {
+            bail!("{CHECKSUMS} has no entry for {}", update.asset.name);
+        };
+
+        let binary = self.download(&update.asset).await?;
+        let actual = sha256_hex(&binary);
+        if actual != expected {
+            bail!(
+                "Checksum mismatch for {}: expected {expected}, got {actual}",
+                update.asset.name
+            );
+        }
+        replace_executable(path, &binary)
+    }
+
+    /// Key the release checksums must be signed with; there is always one
+    fn public_key(&self) -> Result<&str> {
+        match self.config.public_key.as_deref().or(RELEASE_PUBLIC_KEY) {
+            Some(key) => Ok(key),
+            None => bail!(
+                "This build has no release key to verify updates with; set self_update.public_key"
+            ),
+        }
+    }
+
+    async fn download(&self, asset: &Asset) -> Result<Vec<u8>> {
+        let response = self
+            .http
+            .get(&asset.browser_download_url)
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Failed to download {}", asset.name))?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+/// The running executable, with symlinks resolved
+pub fn current_executable() -> Result<PathBuf> {
+    let path = std::env::current_exe().context("Can't locate the nab executable")?;
+    Ok(std::fs::canonicalize(&path).unwrap_or(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag: &str, prerelease: bool) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            prerelease,
+            draft: false,
+            assets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_channels_and_versions() {
+        let releases = [release("v0.5.0-beta.1", true), release("v0.4.1", false)];
+        assert_eq!(pick(&releases, Channel::Stable).unwrap().version(), "0.4.1");
+        assert_eq!(
+            pick(&releases, Channel::Beta).unwrap().version(),
+            "0.5.0-beta.1"
+        );
+        assert!(is_newer("0.4.1", "0.3.0"));
+        assert!(is_newer("0.10.0", "0.9.9"));
+        assert!(is_newer("1.0.0", "1.0.0-beta.2"));
+        assert!(is_newer("1.0.0-beta.2", "1.0.0-beta.1"));
+        assert!(!is_newer("1.0.0-beta.1", "1.0.0"));
+        assert!(!is_newer("0.3.0", "0.3.0"));
+        assert_eq!("Beta".parse::<Channel>().unwrap(), Channel::Beta);
+        assert!("nightly".parse::<Channel>().is_err());
+    }
+
+    #[test]
+    fn test_checksum_and_signature() {
+        let listing =
+            "ABC123  nab-x86_64-unknown-linux-gnu\ndef456 *nab-x86_64-pc-windows-msvc.exe\n";
+        assert_eq!(
+            expected_checksum(listing, "nab-x86_64-unknown-linux-gnu").as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(
+            expected_checksum(listing, "nab-x86_64-pc-windows-msvc.exe").as_deref(),
+            Some("def456")
+        );
+        assert_eq!(expected_checksum(listing, "nab-riscv"), None);
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        use ring::signature::KeyPair;
+        let pair = ring::signature::Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+        let public_key: String = pair
+            .public_key()
+            .as_ref()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        let signature = pair.sign(listing.as_bytes());
+        assert!(verify_signature(&public_key, listing.as_bytes(), signature.as_ref()).is_ok());
+        assert!(verify_signature(&public_key, b"tampered", signature.as_ref()).is_err());
+    }
+
+    #[test]
+    fn test_signatures_are_always_checked() {
+        let updater = |public_key: Option<&str>| {
+            Updater::new(SelfUpdateConfig {
+                public_key: public_key.map(str::to_string),
+                ..SelfUpdateConfig::default()
+            })
+            .unwrap()
+        };
+        assert_eq!(updater(Some("abcd")).public_key().unwrap(), "abcd");
+        // Without a configured key, only builds carrying the release key can update
+        assert_eq!(updater(None).public_key().ok(), RELEASE_PUBLIC_KEY);
+    }
+
+    #[test]
+    fn test_replace_executable() {
+        let dir = std::env::temp_dir().join(format!("nab-self-update-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("nab");
+        std::fs::write(&exe, b"old").unwrap();
+        replace_executable(&exe, b"new").unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), b"new");
+        assert!(!exe.with_extension("new").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,97 @@
+//! Graceful Shutdown (SIGINT/SIGTERM)
+//!
+//! Long-running commands (`crawl`, `batch`, `linkcheck`, `stream`) stop
+//! cleanly on the first Ctrl-C or SIGTERM instead of dying mid-write:
+//!
+//! - no new requests or segments are started; those in flight finish
+//! - stream outputs are closed so partial media stays playable (ffmpeg is
+//!   asked to finalize its container, a preallocated `--mmap` file is cut back
+//!   to the segments that arrived)
+//! - the crawl frontier and manifest, the batch job manifest, and caches are
+//!   saved, and the command prints its summary
+//!
+//! Work still running [`GRACE`] after the signal is abandoned, as it is on a
+//! second signal. An interrupted command exits with status [`EXIT_CODE`], the
+//! shell's code for SIGINT (a `--deadline` uses 124).
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+/// Time in-flight work gets to finish after the signal
+pub const GRACE: Duration = Duration::from_secs(10);
+
+/// Exit status of an interrupted command
+pub const EXIT_CODE: i32 = 130;
+
+fn stop() -> &'static watch::Sender<bool> {
+    static STOP: OnceLock<watch::Sender<bool>> = OnceLock::new();
+    STOP.get_or_init(|| watch::channel(false).0)
+}
+
+/// Ask running work to stop
+pub fn request() {
+    stop().send_replace(true);
+}
+
+/// Whether a stop was requested
+#[must_use]
+pub fn is_requested() -> bool {
+    *stop().borrow()
+}
+
+/// Resolves once a stop is requested
+pub async fn requested() {
+    let _ = stop().subscribe().wait_for(|stop| *stop).await;
+}
+
+/// Handle SIGINT/SIGTERM by [requesting](request) a stop
+///
+/// The process exits after [`GRACE`], or at once on a second signal. Without
+/// this, signals keep their default effect of ending the process immediately.
+pub fn install() {
+    tokio::spawn(async {
+        #[cfg(unix)]
+        let mut term =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).ok();
+        #[cfg(not(unix))]
+        let mut term: Option<()> = None;
+
+        interrupted(&mut term).await;
+        eprintln!("\n⏸️  Stopping after the work in flight (signal again to quit now)");
+        request();
+        tokio::spawn(async {
+            tokio::time::sleep(GRACE).await;
+            eprintln!(
+                "⏸️  Still busy {}s after the signal, quitting",
+                GRACE.as_secs()
+            );
+            std::process::exit(EXIT_CODE);
+        });
+
+        interrupted(&mut term).await;
+        std::process::exit(EXIT_CODE);
+    });
+}
+
+#[cfg(unix)]
+async fn interrupted(term: &mut Option<tokio::signal::unix::Signal>) {
+    let sigterm = async {
+        match term {
+            Some(term) => {
+                term.recv().await;
+            }
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        () = sigterm => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn interrupted(_: &mut Option<()>) {
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,288 @@
+//! State Files
+//!
+//! Everything nab keeps between runs (browser version cache, crawl frontier,
+//! revisit caches, cassettes, secrets, batch job manifests) is written through here:
+//!
+//! - [`write_atomic`] writes a temp file in the same directory, syncs it, and
+//!   renames it over the old one, so an interrupted write leaves the previous
+//!   version rather than half a file.
+//! - [`lock`] takes an advisory lock on a sidecar `.<name>.lock` file, for
+//!   read-modify-write cycles and for state one process owns (a crawl).
+//! - [`load`] and [`save`] add a `schema_version` to JSON state and run the
+//!   type's [`Versioned::migrate`] steps when an older file is loaded.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+/// JSON field holding a state file's schema version
+pub const SCHEMA_FIELD: &str = "schema_version";
+
+/// How long to wait for another process's short read-modify-write
+pub const LOCK_WAIT: Duration = Duration::from_secs(10);
+
+/// Pause between attempts to take a busy lock
+const LOCK_POLL: Duration = Duration::from_millis(50);
+
+/// Distinguishes temp files of concurrent writes in one process
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// JSON state with a schema version
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Version written by this build
+    const SCHEMA_VERSION: u32;
+
+    /// Upgrade `value` from schema `from` to `from + 1`
+    ///
+    /// Files from before versioning are schema 0. The default accepts older
+    /// files as they are, which is right while fields are only added (with
+    /// serde defaults).
+    fn migrate(value: Value, from: u32) -> Result<Value> {
+        let _ = from;
+        Ok(value)
+    }
+}
+
+/// Replace `path` with `contents` in one step
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    write_with(path, contents, None)
+}
+
+/// Like [`write_atomic`], readable only by the current user
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    write_with(path, contents, Some(0o600))
+}
+
+fn write_with(path: &Path, contents: &[u8], mode: Option<u32>) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let temp = sidecar(
+        path,
+        &format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ),
+    );
+
+    let written = (|| -> std::io::Result<()> {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode);
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        let mut file = options.open(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    })();
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e).with_context(|| format!("Failed to write {}", path.display()));
+    }
+    // Make the rename itself durable (not possible on every platform)
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Exclusive lock on a state file, released on drop
+#[derive(Debug)]
+pub struct StateLock {
+    _file: File,
+    path: PathBuf,
+}
+
+impl StateLock {
+    /// The lock file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Lock `path` against other nab processes, waiting up to `wait` for a holder
+pub fn lock(path: &Path, wait: Duration) -> Result<StateLock> {
+    let lock_path = sidecar(path, "lock");
+    if let Some(dir) = lock_path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+    let deadline = Instant::now() + wait;
+    loop {
+        match file.try_lock() {
+            Ok(()) => {
+                return Ok(StateLock {
+                    _file: file,
+                    path: lock_path,
+                })
+            }
+            Err(std::fs::TryLockError::WouldBlock) if Instant::now() < deadline => {
+                std::thread::sleep(LOCK_POLL);
+            }
+            Err(std::fs::TryLockError::WouldBlock) => {
+                bail!("{} is in use by another nab process", path.display())
+            }
+            Err(std::fs::TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+            }
+        }
+    }
+}
+
+/// Load JSON state, migrating older schemas; `None` if the file doesn't exist
+pub fn load<T: Versioned>(path: &Path) -> Result<Option<T>> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let invalid = || format!("Invalid state file {}", path.display());
+    let mut value: Value = serde_json::from_slice(&data).with_context(invalid)?;
+    let version = match value.get(SCHEMA_FIELD) {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .with_context(invalid)?,
+    };
+    if version > T::SCHEMA_VERSION {
+        bail!(
+            "{} was written by a newer nab (schema {version}, this build reads up to {})",
+            path.display(),
+            T::SCHEMA_VERSION
+        );
+    }
+    for from in version..T::SCHEMA_VERSION {
+        value = T::migrate(value, from)
+            .with_context(|| format!("Failed to migrate {} from schema {from}", path.display()))?;
+    }
+    if let Value::Object(map) = &mut value {
+        map.remove(SCHEMA_FIELD);
+    }
+    serde_json::from_value(value)
+        .with_context(invalid)
+        .map(Some)
+}
+
+/// Save JSON state atomically with its schema version
+pub fn save<T: Versioned>(path: &Path, state: &T) -> Result<()> {
+    let mut value = serde_json::to_value(state)?;
+    let Value::Object(map) = &mut value else {
+        bail!("State must serialize to a JSON object");
+    };
+    map.insert(SCHEMA_FIELD.to_string(), T::SCHEMA_VERSION.into());
+    write_atomic(path, &serde_json::to_vec_pretty(&value)?)
+}
+
+/// `.<file name>.<suffix>` beside `path`
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or_else(|| "state".into(), |n| n.to_string_lossy());
+    path.with_file_name(format!(".{name}.{suffix}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Counter {
+        hits: u64,
+        #[serde(default)]
+        label: String,
+    }
+
+    impl Versioned for Counter {
+        const SCHEMA_VERSION: u32 = 2;
+
+        fn migrate(mut value: Value, from: u32) -> Result<Value> {
+            // Schema 1 renamed `count` to `hits`
+            if from == 0 {
+                if let Some(count) = value.as_object_mut().and_then(|m| m.remove("count")) {
+                    value["hits"] = count;
+                }
+            }
+            Ok(value)
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("nab-state-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn test_versioned_round_trip_and_migration() {
+        let dir = temp_dir("versioned");
+        let path = dir.join("counter.json");
+        assert_eq!(load::<Counter>(&path).unwrap(), None);
+
+        let counter = Counter {
+            hits: 3,
+            label: "x".into(),
+        };
+        save(&path, &counter).unwrap();
+        let saved: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved[SCHEMA_FIELD], 2);
+        assert_eq!(load::<Counter>(&path).unwrap(), Some(counter));
+
+        std::fs::write(&path, r#"{"count": 7}"#).unwrap();
+        assert_eq!(
+            load::<Counter>(&path).unwrap(),
+            Some(Counter {
+                hits: 7,
+                label: String::new()
+            })
+        );
+
+        std::fs::write(&path, r#"{"hits": 1, "schema_version": 9}"#).unwrap();
+        let newer = load::<Counter>(&path).unwrap_err();
+        assert!(newer.to_string().contains("newer nab"), "{newer}");
+
+        // Nothing but the file itself is left behind
+        let names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["counter.json"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_lock_excludes_other_holders() {
+        let dir = temp_dir("lock");
+        let path = dir.join("crawl.json");
+        let held = lock(&path, Duration::ZERO).unwrap();
+        assert!(held.path().ends_with(".crawl.json.lock"));
+        let busy = lock(&path, Duration::from_millis(120)).unwrap_err();
+        assert!(busy.to_string().contains("in use"), "{busy}");
+        drop(held);
+        lock(&path, Duration::ZERO).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
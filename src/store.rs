@@ -0,0 +1,256 @@
+//! Content-Addressed Body Store
+//!
+//! `nab crawl --store DIR` keeps every page body once, named by its SHA-256:
+//! a page that is unchanged since the last run, or served under several
+//! URLs, costs one blob. Each run writes a manifest mapping its URLs to
+//! their blobs, so older versions of a page stay readable for as long as a
+//! manifest refers to them.
+//!
+//! ```text
+//! DIR/blobs/ab/ab12…ef.zst          zstd-compressed body
+//! DIR/manifests/<run>.json          URL → SHA-256 of one crawl
+//! DIR/dictionaries/                 per-host zstd dictionaries
+//! ```
+//!
+//! Deleting old manifests (or [`BodyStore::gc`] with `keep_runs`) leaves
+//! their blobs behind; `gc` then removes the blobs no manifest refers to.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::compress::Dictionaries;
+use crate::self_update::sha256_hex;
+use crate::state::Versioned;
+
+/// One run's URLs and the blobs of their bodies
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub started_at: Option<DateTime<Utc>>,
+    /// URL to SHA-256 (hex) of its body
+    pub pages: BTreeMap<String, String>,
+}
+
+impl Versioned for RunManifest {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+/// What [`BodyStore::gc`] removed
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    /// Manifests dropped by `keep_runs`
+    pub runs_removed: usize,
+    pub blobs_kept: usize,
+    pub blobs_removed: usize,
+    pub bytes_removed: u64,
+}
+
+/// A store directory, with the manifest of the run writing to it
+#[derive(Debug)]
+pub struct BodyStore {
+    dir: PathBuf,
+    dictionaries: Dictionaries,
+    /// This run's manifest file
+    manifest_path: PathBuf,
+    run: Mutex<RunManifest>,
+}
+
+impl BodyStore {
+    /// Store in `dir`; [`Self::put`] records pages in a new run manifest
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(dir.join("blobs"))
+            .with_context(|| format!("Failed to create store {}", dir.display()))?;
+        let started_at = Utc::now();
+        let name = format!(
+            "{}-{}.json",
+            started_at.format("%Y%m%dT%H%M%S%.3fZ"),
+            std::process::id()
+        );
+        Ok(Self {
+            dictionaries: Dictionaries::open(dir.join("dictionaries")),
+            manifest_path: dir.join("manifests").join(name),
+            run: Mutex::new(RunManifest {
+                started_at: Some(started_at),
+                pages: BTreeMap::new(),
+            }),
+            dir,
+        })
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Store `body` (unless a blob already has it) as `url`'s; its SHA-256
+    pub fn put(&self, url: &str, body: &[u8]) -> Result<String> {
+        let hash = sha256_hex(body);
+        let path = self.blob_path(&hash);
+        if !path.exists() {
+            let host = url::Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(String::from))
+                .unwrap_or_default();
+            let data = self.dictionaries.compress(&host, body)?;
+            crate::state::write_atomic(&path, &data)?;
+        }
+        self.run
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pages
+            .insert(url.to_string(), hash.clone());
+        Ok(hash)
+    }
+
+    /// Body with SHA-256 `hash`
+    pub fn get(&self, hash: &str) -> Result<Vec<u8>> {
+        let path = self.blob_path(hash);
+        let data = std::fs::read(&path)
+            .with_context(|| format!("No blob {hash} in {}", self.dir.display()))?;
+        self.dictionaries.decompress(&data)
+    }
+
+    /// SHA-256 of `url`'s body in the newest run that has it
+    pub fn lookup(&self, url: &str) -> Result<Option<String>> {
+        if let Some(hash) = self.lock_run().pages.get(url) {
+            return Ok(Some(hash.clone()));
+        }
+        for (_, run) in self.runs()?.into_iter().rev() {
+            if let Some(hash) = run.pages.get(url) {
+                return Ok(Some(hash.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Write this run's manifest (done again as the run goes on)
+    pub fn save(&self) -> Result<()> {
+        let run = self.lock_run();
+        if run.pages.is_empty() {
+            return Ok(());
+        }
+        crate::state::save(&self.manifest_path, &*run)
+    }
+
+    /// Remove all but the newest `keep_runs` manifests (all kept if `None`),
+    /// then every blob no remaining manifest refers to; with `dry_run`, only
+    /// count them
+    pub fn gc(&self, keep_runs: Option<usize>, dry_run: bool) -> Result<GcReport> {
+        let mut report = GcReport::default();
+        let mut runs = self.runs()?;
+        let drop = keep_runs.map_or(0, |keep| runs.len().saturating_sub(keep));
+        for (path, _) in runs.drain(..drop) {
+            if !dry_run {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            report.runs_removed += 1;
+        }
+        let mut referenced: HashSet<String> = self.lock_run().pages.values().cloned().collect();
+        for (_, run) in &runs {
+            referenced.extend(run.pages.values().cloned());
+        }
+
+        for blob in walk_files(&self.dir.join("blobs")) {
+            let hash = blob
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".zst"));
+            if hash.is_some_and(|hash| referenced.contains(hash)) {
+                report.blobs_kept += 1;
+                continue;
+            }
+            report.bytes_removed += std::fs::metadata(&blob).map_or(0, |meta| meta.len());
+            report.blobs_removed += 1;
+            if !dry_run {
+                std::fs::remove_file(&blob)
+                    .with_context(|| format!("Failed to remove {}", blob.display()))?;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Earlier runs' manifests, oldest first (this run's excluded)
+    fn runs(&self) -> Result<Vec<(PathBuf, RunManifest)>> {
+        let mut paths: Vec<PathBuf> = walk_files(&self.dir.join("manifests"))
+            .into_iter()
+            .filter(|path| {
+                path.extension().is_some_and(|ext| ext == "json") && *path != self.manifest_path
+            })
+            .collect();
+        // Names start with the run's start time
+        paths.sort();
+        paths
+            .into_iter()
+            .filter_map(|path| match crate::state::load(&path) {
+                Ok(run) => run.map(|run| Ok((path, run))),
+                Err(e) => Some(Err(e)),
+            })
+            .collect()
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir
+            .join("blobs")
+            .join(&hash[..2.min(hash.len())])
+            .join(format!("{hash}.zst"))
+    }
+
+    fn lock_run(&self) -> std::sync::MutexGuard<'_, RunManifest> {
+        self.run.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Files under `dir`, recursively
+fn walk_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(walk_files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_and_gc() {
+        let dir = std::env::temp_dir().join(format!("nab-store-{}", std::process::id()));
+        let first = BodyStore::open(&dir).unwrap();
+        let a = first.put("https://example.com/a", b"<p>same</p>").unwrap();
+        let b = first.put("https://example.com/b", b"<p>same</p>").unwrap();
+        assert_eq!(a, b);
+        first.put("https://example.com/c", b"<p>old</p>").unwrap();
+        first.save().unwrap();
+        assert_eq!(walk_files(&dir.join("blobs")).len(), 2);
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let second = BodyStore::open(&dir).unwrap();
+        let c = second.put("https://example.com/c", b"<p>new</p>").unwrap();
+        second.save().unwrap();
+        assert_eq!(second.lookup("https://example.com/c").unwrap(), Some(c));
+        assert_eq!(second.get(&a).unwrap(), b"<p>same</p>");
+
+        // Every blob is still in a manifest
+        let store = BodyStore::open(&dir).unwrap();
+        assert_eq!(store.gc(None, false).unwrap().blobs_removed, 0);
+        // Pruning the first run orphans its pages' blobs
+        let report = store.gc(Some(1), false).unwrap();
+        assert_eq!(report.runs_removed, 1);
+        assert_eq!(report.blobs_removed, 2);
+        assert_eq!(report.blobs_kept, 1);
+        assert!(store.lookup("https://example.com/a").unwrap().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -19,6 +19,8 @@ pub struct StreamConfig {
     pub quality: super::StreamQuality,
     pub headers: HashMap<String, String>,
     pub cookies: Option<String>,
+    /// Audio tracks to mux, by language (empty: the stream's default track)
+    pub audio_languages: Vec<String>,
 }
 
 impl Default for StreamConfig {
@@ -27,6 +29,7 @@ impl Default for StreamConfig {
             quality: super::StreamQuality::Best,
             headers: HashMap::new(),
             cookies: None,
+            audio_languages: Vec::new(),
         }
     }
 }
@@ -42,6 +45,23 @@ pub struct StreamProgress {
     pub elapsed_seconds: f64,
 }
 
+/// Callback for `StreamQuality::Auto` variant decisions
+pub type QualityCallback = Box<dyn Fn(QualitySwitch) + Send + Sync>;
+
+/// A variant picked by `StreamQuality::Auto`: the initial probe, or a
+/// switch after re-measuring throughput during a live capture
+#[derive(Debug, Clone)]
+pub struct QualitySwitch {
+    /// Previous variant height, `None` for the initial probe
+    pub from: Option<u32>,
+    pub to: u32,
+    /// Declared bandwidth of the new variant
+    pub bandwidth: u64,
+    /// Measured throughput the decision was based on
+    pub throughput_bps: u64,
+    pub elapsed_seconds: f64,
+}
+
 /// Backend for streaming data
 #[async_trait]
 pub trait StreamBackend: Send + Sync {
@@ -5,18 +5,22 @@
 //! - Encrypted HLS (Widevine/AES)
 //! - Transcoding
 //! - Complex format handling
+//! - Muxing several audio languages ([`crate::stream::tracks`])
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use std::collections::HashMap;
 use std::path::Path;
 use std::process::Stdio;
 use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
-use tokio::process::Command;
+use tokio::process::{Child, Command};
 use tracing::{debug, info, warn};
 
 use crate::stream::backend::{
     BackendType, ProgressCallback, StreamBackend, StreamConfig, StreamProgress,
 };
+use crate::stream::container::Container;
+use crate::stream::tracks;
 
 /// ffmpeg-based streaming backend
 pub struct FfmpegBackend {
@@ -26,6 +30,8 @@ pub struct FfmpegBackend {
     extra_args: Vec<String>,
     /// Transcoding options (e.g., "-c:v libx265 -crf 28")
     transcode_opts: Option<String>,
+    /// Output container (default: from the file extension, MPEG-TS on a pipe)
+    container: Option<Container>,
 }
 
 impl FfmpegBackend {
@@ -40,6 +46,7 @@ impl FfmpegBackend {
             ffmpeg_path,
             extra_args: Vec::new(),
             transcode_opts: None,
+            container: None,
         })
     }
 
@@ -57,6 +64,13 @@ impl FfmpegBackend {
         self
     }
 
+    /// Write this container (MP4 files get `+faststart`)
+    #[must_use]
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+
     /// Add extra ffmpeg arguments
     #[must_use]
     pub fn with_extra_args(mut self, args: Vec<String>) -> Self {
@@ -64,6 +78,28 @@ impl FfmpegBackend {
         self
     }
 
+    /// ffprobe next to the ffmpeg binary
+    fn ffprobe_path(&self) -> String {
+        let path = Path::new(&self.ffmpeg_path);
+        if path.file_stem().is_some_and(|stem| stem == "ffmpeg") {
+            path.with_file_name("ffprobe").to_string_lossy().to_string()
+        } else {
+            "ffprobe".to_string()
+        }
+    }
+
+    /// `-map` arguments for `config.audio_languages`; none (ffmpeg's default
+    /// single video + audio) when no languages are asked for
+    async fn track_args(&self, manifest_url: &str, config: &StreamConfig) -> Result<Vec<String>> {
+        if config.audio_languages.is_empty() {
+            return Ok(Vec::new());
+        }
+        let streams = tracks::probe(&self.ffprobe_path(), manifest_url, &config.headers).await?;
+        let selection = tracks::select(&streams, config.quality, &config.audio_languages)?;
+        info!("Selected tracks: {:?}", selection);
+        Ok(selection.ffmpeg_args())
+    }
+
     /// Build ffmpeg command arguments
     fn build_args(
         &self,
@@ -71,6 +107,7 @@ impl FfmpegBackend {
         config: &StreamConfig,
         output_path: Option<&str>,
         duration_secs: Option<u64>,
+        tracks: &[String],
     ) -> Vec<String> {
         let mut args = Vec::new();
 
@@ -83,14 +120,8 @@ impl FfmpegBackend {
 
         // Headers
         if !config.headers.is_empty() {
-            let header_str = config
-                .headers
-                .iter()
-                .map(|(k, v)| format!("{k}: {v}"))
-                .collect::<Vec<_>>()
-                .join("\r\n");
             args.push("-headers".to_string());
-            args.push(format!("{header_str}\r\n"));
+            args.push(header_arg(&config.headers));
         }
 
         // Duration limit for live streams
@@ -128,6 +159,9 @@ impl FfmpegBackend {
         args.push("-i".to_string());
         args.push(manifest_url.to_string());
 
+        // Selected video and audio tracks
+        args.extend(tracks.iter().cloned());
+
         // Transcoding or copy
         if let Some(ref opts) = self.transcode_opts {
             // Parse transcode options
@@ -142,15 +176,18 @@ impl FfmpegBackend {
 
         // Output
         if let Some(path) = output_path {
+            if let Some(container) = self.container {
+                args.extend(container.output_args(false));
+            }
             args.push("-y".to_string()); // Overwrite
             args.push(path.to_string());
         } else {
-            // Output to stdout as MPEG-TS (streamable)
-            args.extend(
-                ["-f", "mpegts", "pipe:1"]
-                    .iter()
-                    .map(std::string::ToString::to_string),
-            );
+            // Output to stdout as MPEG-TS (streamable) unless told otherwise
+            match self.container {
+                Some(container) => args.extend(container.output_args(true)),
+                None => args.extend(["-f".to_string(), "mpegts".to_string()]),
+            }
+            args.push("pipe:1".to_string());
         }
 
         args
@@ -215,14 +252,17 @@ impl FfmpegBackend {
         duration_secs: u64,
         progress: Option<ProgressCallback>,
     ) -> Result<()> {
-        let args = self.build_args(manifest_url, config, None, Some(duration_secs));
+        let tracks = self.track_args(manifest_url, config).await?;
+        let args = self.build_args(manifest_url, config, None, Some(duration_secs), &tracks);
         debug!("ffmpeg args (with duration): {:?}", args);
 
         let mut child = Command::new(&self.ffmpeg_path)
             .args(&args)
+            .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()?;
+        let quit = quit_on_shutdown(&mut child);
 
         let stdout = child
             .stdout
@@ -254,6 +294,7 @@ impl FfmpegBackend {
         }
 
         let status = child.wait().await?;
+        quit.abort();
 
         if !status.success() {
             // Duration limit often causes ffmpeg to exit with signal, which is ok
@@ -268,6 +309,30 @@ impl FfmpegBackend {
     }
 }
 
+/// Request headers as ffmpeg's `-headers` value
+pub(crate) fn header_arg(headers: &HashMap<String, String>) -> String {
+    let header_str = headers
+        .iter()
+        .map(|(k, v)| format!("{k}: {v}"))
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    format!("{header_str}\r\n")
+}
+
+/// Ask ffmpeg to finish (as if `q` was typed) once nab is interrupted
+///
+/// ffmpeg then writes the container trailer, so a partial file still plays.
+fn quit_on_shutdown(child: &mut Child) -> tokio::task::JoinHandle<()> {
+    let stdin = child.stdin.take();
+    tokio::spawn(async move {
+        crate::shutdown::requested().await;
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(b"q").await;
+            let _ = stdin.flush().await;
+        }
+    })
+}
+
 #[async_trait]
 impl StreamBackend for FfmpegBackend {
     fn backend_type(&self) -> BackendType {
@@ -286,14 +351,17 @@ impl StreamBackend for FfmpegBackend {
         output: &mut W,
         progress: Option<ProgressCallback>,
     ) -> Result<()> {
-        let args = self.build_args(manifest_url, config, None, None);
+        let tracks = self.track_args(manifest_url, config).await?;
+        let args = self.build_args(manifest_url, config, None, None, &tracks);
         debug!("ffmpeg args: {:?}", args);
 
         let mut child = Command::new(&self.ffmpeg_path)
             .args(&args)
+            .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()?;
+        let quit = quit_on_shutdown(&mut child);
 
         let stdout = child
             .stdout
@@ -354,8 +422,9 @@ impl StreamBackend for FfmpegBackend {
         // Wait for process to complete
         let status = child.wait().await?;
         stderr_handle.abort(); // Stop stderr reader
+        quit.abort();
 
-        if !status.success() {
+        if !status.success() && !crate::shutdown::is_requested() {
             return Err(anyhow!("ffmpeg exited with status: {status}"));
         }
 
@@ -374,14 +443,23 @@ impl StreamBackend for FfmpegBackend {
         duration_secs: Option<u64>,
     ) -> Result<()> {
         let path_str = path.to_string_lossy();
-        let args = self.build_args(manifest_url, config, Some(&path_str), duration_secs);
+        let tracks = self.track_args(manifest_url, config).await?;
+        let args = self.build_args(
+            manifest_url,
+            config,
+            Some(&path_str),
+            duration_secs,
+            &tracks,
+        );
         debug!("ffmpeg args: {:?}", args);
 
         let mut child = Command::new(&self.ffmpeg_path)
             .args(&args)
+            .stdin(Stdio::piped())
             .stdout(Stdio::null())
             .stderr(Stdio::piped())
             .spawn()?;
+        let quit = quit_on_shutdown(&mut child);
 
         let stderr = child
             .stderr
@@ -412,8 +490,10 @@ impl StreamBackend for FfmpegBackend {
         }
 
         let status = child.wait().await?;
+        quit.abort();
 
-        if !status.success() {
+        // An interrupted ffmpeg (SIGINT or `q`) still finalizes the file
+        if !status.success() && !crate::shutdown::is_requested() {
             return Err(anyhow!("ffmpeg exited with status: {status}"));
         }
 
@@ -452,15 +532,17 @@ mod tests {
             ffmpeg_path: "ffmpeg".to_string(),
             extra_args: vec![],
             transcode_opts: None,
+            container: None,
         };
 
         let config = StreamConfig {
             quality: crate::stream::StreamQuality::Best,
             headers: HashMap::new(),
             cookies: None,
+            audio_languages: Vec::new(),
         };
 
-        let args = backend.build_args("https://example.com/master.m3u8", &config, None, None);
+        let args = backend.build_args("https://example.com/master.m3u8", &config, None, None, &[]);
 
         assert!(args.contains(&"-i".to_string()));
         assert!(args.contains(&"https://example.com/master.m3u8".to_string()));
@@ -475,15 +557,17 @@ mod tests {
             ffmpeg_path: "ffmpeg".to_string(),
             extra_args: vec![],
             transcode_opts: Some("-c:v libx265 -crf 28".to_string()),
+            container: None,
         };
 
         let config = StreamConfig {
             quality: crate::stream::StreamQuality::Best,
             headers: HashMap::new(),
             cookies: None,
+            audio_languages: Vec::new(),
         };
 
-        let args = backend.build_args("https://example.com/master.m3u8", &config, None, None);
+        let args = backend.build_args("https://example.com/master.m3u8", &config, None, None, &[]);
 
         assert!(args.contains(&"-c:v".to_string()));
         assert!(args.contains(&"libx265".to_string()));
@@ -496,6 +580,7 @@ mod tests {
             ffmpeg_path: "ffmpeg".to_string(),
             extra_args: vec![],
             transcode_opts: None,
+            container: None,
         };
 
         let mut headers = HashMap::new();
@@ -506,9 +591,10 @@ mod tests {
             quality: crate::stream::StreamQuality::Best,
             headers,
             cookies: None,
+            audio_languages: Vec::new(),
         };
 
-        let args = backend.build_args("https://example.com/master.m3u8", &config, None, None);
+        let args = backend.build_args("https://example.com/master.m3u8", &config, None, None, &[]);
 
         assert!(args.contains(&"-headers".to_string()));
         // Check that headers string contains both headers
@@ -518,17 +604,46 @@ mod tests {
         assert!(headers_value.contains("Cookie:"));
     }
 
+    #[test]
+    fn test_build_args_with_container() {
+        let backend = FfmpegBackend::new().unwrap().with_container(Container::Mp4);
+        let config = StreamConfig::default();
+
+        let args = backend.build_args(
+            "https://example.com/master.m3u8",
+            &config,
+            Some("out.mp4"),
+            None,
+            &[],
+        );
+        let at = args.iter().position(|a| a == "-movflags").unwrap();
+        assert_eq!(args[at + 1], "+faststart");
+        assert_eq!(args.last().unwrap(), "out.mp4");
+
+        let args = backend.build_args("https://example.com/master.m3u8", &config, None, None, &[]);
+        let at = args.iter().position(|a| a == "-movflags").unwrap();
+        assert!(args[at + 1].starts_with("frag_keyframe"));
+        assert!(!args.contains(&"mpegts".to_string()));
+    }
+
     #[test]
     fn test_build_args_with_duration() {
         let backend = FfmpegBackend {
             ffmpeg_path: "ffmpeg".to_string(),
             extra_args: vec![],
             transcode_opts: None,
+            container: None,
         };
 
         let config = StreamConfig::default();
 
-        let args = backend.build_args("https://example.com/master.m3u8", &config, None, Some(3600));
+        let args = backend.build_args(
+            "https://example.com/master.m3u8",
+            &config,
+            None,
+            Some(3600),
+            &[],
+        );
 
         assert!(args.contains(&"-t".to_string()));
         assert!(args.contains(&"3600".to_string()));
@@ -3,22 +3,37 @@
 //! Fetches and concatenates HLS segments without external dependencies.
 //! Supports:
 //! - Multi-quality master playlists (quality selection)
+//! - `StreamQuality::Auto`: a probe download picks the highest variant the
+//!   measured throughput sustains, re-evaluated periodically on live streams
 //! - VOD playlists (finite segments)
 //! - Live playlists (continuous refresh)
 //! - Parallel segment fetching
+//! - Byte-range segments (`#EXT-X-BYTERANGE`), checked before they're written
 //! - Retry on segment failure
+//! - Memory-mapped VOD output: segments written at their final offsets as
+//!   they complete (sizes from byte ranges or `Content-Length`)
+//! - `AES-128` and packed-audio `SAMPLE-AES` segments (`#EXT-X-KEY`), with
+//!   keys fetched once per URI under a browser fingerprint and the stream's
+//!   cookies, and decrypted before they're written
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING};
 use reqwest::Client;
 use std::collections::HashMap;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
 use tracing::{debug, info};
 
 use super::super::backend::{
-    BackendType, ProgressCallback, StreamBackend, StreamConfig, StreamProgress,
+    BackendType, ProgressCallback, QualityCallback, QualitySwitch, StreamBackend, StreamConfig,
+    StreamProgress,
 };
+use super::super::decrypt::{self, SegmentKey};
+use super::super::mmap::{self, MappedFile};
+use super::super::range::{self, ByteRange};
 use super::super::StreamQuality;
 
 /// Native HLS streaming backend
@@ -28,21 +43,49 @@ pub struct NativeHlsBackend {
     max_concurrent: usize,
     /// Retry count for failed segments
     max_retries: u32,
+    /// Write VOD files through a preallocated memory map
+    mmap_output: bool,
+    /// Told about each `StreamQuality::Auto` decision
+    quality_log: Option<QualityCallback>,
+    /// Browser headers for key requests (key servers often check them)
+    key_headers: HeaderMap,
+    /// Fetched `#EXT-X-KEY` keys by URI
+    keys: Mutex<HashMap<String, Vec<u8>>>,
 }
 
+/// Share of the measured throughput a variant's bandwidth may use
+const AUTO_HEADROOM: f64 = 0.8;
+
+/// How often live captures re-measure throughput for `StreamQuality::Auto`
+const AUTO_REEVALUATE: Duration = Duration::from_secs(30);
+
 impl NativeHlsBackend {
     pub fn new() -> Result<Self> {
-        let client = Client::builder()
+        Self::with_options(&crate::ClientOptions::default())
+    }
+
+    /// Backend whose client uses `options` (TLS, proxy, HTTP version)
+    pub fn with_options(options: &crate::ClientOptions) -> Result<Self> {
+        let builder = crate::policy::client_builder()
             .timeout(Duration::from_secs(30))
             .pool_max_idle_per_host(16) // Keep more connections alive for speed
             .pool_idle_timeout(Duration::from_secs(60))
-            .tcp_nodelay(true) // Reduce latency
-            .build()?;
+            .tcp_nodelay(true); // Reduce latency
+        let client = options.apply(builder)?.build()?;
+
+        // Keys are raw bytes; let reqwest negotiate what it can decode
+        let mut key_headers = crate::fingerprint::random_profile().to_headers();
+        key_headers.remove(ACCEPT_ENCODING);
+        key_headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
 
         Ok(Self {
             client,
             max_concurrent: 8, // Higher concurrency for faster VOD downloads
             max_retries: 3,
+            mmap_output: false,
+            quality_log: None,
+            key_headers,
+            keys: Mutex::new(HashMap::new()),
         })
     }
 
@@ -52,6 +95,21 @@ impl NativeHlsBackend {
         self
     }
 
+    /// Preallocate and memory-map VOD output files, writing segments at
+    /// their final offsets in whatever order they finish
+    #[must_use]
+    pub fn with_mmap_output(mut self, enabled: bool) -> Self {
+        self.mmap_output = enabled;
+        self
+    }
+
+    /// Report the variant `StreamQuality::Auto` starts with and each later switch
+    #[must_use]
+    pub fn with_quality_log(mut self, callback: QualityCallback) -> Self {
+        self.quality_log = Some(callback);
+        self
+    }
+
     /// Parse master playlist and return quality variants
     async fn parse_master_playlist(
         &self,
@@ -95,7 +153,7 @@ impl NativeHlsBackend {
         }
 
         // Sort by bandwidth (quality) descending
-        variants.sort_by(|a, b| b.bandwidth.cmp(&a.bandwidth));
+        variants.sort_by_key(|v| std::cmp::Reverse(v.bandwidth));
 
         Ok(variants)
     }
@@ -114,6 +172,11 @@ impl NativeHlsBackend {
         let mut media_sequence = 0u64;
         let mut target_duration = 10.0f64;
         let mut current_duration = 0.0f64;
+        let mut current_range: Option<&str> = None;
+        // A range without an offset continues the previous one of the same URI
+        let mut last_range: Option<(String, ByteRange)> = None;
+        // Applies to every segment until the next #EXT-X-KEY
+        let mut current_key: Option<SegmentKey> = None;
 
         for line in content.lines() {
             if line.starts_with("#EXT-X-ENDLIST") {
@@ -128,12 +191,32 @@ impl NativeHlsBackend {
                     .next()
                     .and_then(|d| d.parse().ok())
                     .unwrap_or(target_duration);
+            } else if let Some(rest) = line.strip_prefix("#EXT-X-BYTERANGE:") {
+                current_range = Some(rest);
+            } else if let Some(rest) = line.strip_prefix("#EXT-X-KEY:") {
+                current_key = SegmentKey::from_attributes(&Self::parse_attributes(rest), |uri| {
+                    Self::resolve_url(base_url, uri)
+                })?;
             } else if !line.starts_with('#') && !line.is_empty() {
                 let uri = Self::resolve_url(base_url, line);
+                let range = match current_range.take() {
+                    Some(value) => {
+                        let previous = last_range
+                            .as_ref()
+                            .filter(|(previous_uri, _)| *previous_uri == uri)
+                            .map(|(_, range)| range);
+                        let range = ByteRange::parse_hls(value, previous)?;
+                        last_range = Some((uri.clone(), range));
+                        Some(range)
+                    }
+                    None => None,
+                };
                 segments.push(HlsSegment {
                     sequence: media_sequence + segments.len() as u64,
                     duration: current_duration,
                     uri,
+                    range,
+                    key: current_key.clone(),
                 });
             }
         }
@@ -152,7 +235,7 @@ impl NativeHlsBackend {
             req = req.header(k.as_str(), v.as_str());
         }
 
-        let resp = req.send().await?;
+        let resp = crate::policy::send(req).await?;
         if !resp.status().is_success() {
             return Err(anyhow!("Failed to fetch playlist: {}", resp.status()));
         }
@@ -160,24 +243,77 @@ impl NativeHlsBackend {
         Ok(resp.text().await?)
     }
 
-    async fn fetch_segment(&self, url: &str, headers: &HashMap<String, String>) -> Result<Vec<u8>> {
+    /// Segment contents, decrypted
+    async fn fetch_segment(
+        &self,
+        segment: &HlsSegment,
+        headers: &HashMap<String, String>,
+    ) -> Result<Vec<u8>> {
+        let data = self.fetch_segment_data(segment, headers).await?;
+        let Some(key) = &segment.key else {
+            return Ok(data);
+        };
+        let key_bytes = self.fetch_key(&key.uri, headers).await?;
+        decrypt::decrypt_segment(key, &key_bytes, segment.sequence, &data)
+    }
+
+    /// The key at `uri`, fetched on first use
+    async fn fetch_key(&self, uri: &str, headers: &HashMap<String, String>) -> Result<Vec<u8>> {
+        // Held across the request, so concurrent segments fetch a key once
+        let mut keys = self.keys.lock().await;
+        if let Some(key) = keys.get(uri) {
+            return Ok(key.clone());
+        }
+        let mut req = self.client.get(uri).headers(self.key_headers.clone());
+        for (k, v) in headers {
+            req = req.header(k.as_str(), v.as_str());
+        }
+        let resp = crate::policy::send(req).await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("Key fetch failed: {} for {uri}", resp.status()));
+        }
+        let key = resp.bytes().await?.to_vec();
+        debug!("Fetched {}-byte key {uri}", key.len());
+        keys.insert(uri.to_string(), key.clone());
+        Ok(key)
+    }
+
+    /// Segment bytes as served
+    async fn fetch_segment_data(
+        &self,
+        segment: &HlsSegment,
+        headers: &HashMap<String, String>,
+    ) -> Result<Vec<u8>> {
         let mut last_error = None;
 
         for attempt in 0..self.max_retries {
-            let mut req = self.client.get(url);
+            let mut req = self.client.get(&segment.uri);
             for (k, v) in headers {
                 req = req.header(k.as_str(), v.as_str());
             }
+            if let Some(range) = &segment.range {
+                req = req.header(reqwest::header::RANGE, range.header());
+            }
 
-            match req.send().await {
+            match crate::policy::send(req).await {
                 Ok(resp) if resp.status().is_success() => {
-                    return Ok(resp.bytes().await?.to_vec());
+                    let Some(range) = &segment.range else {
+                        return Ok(resp.bytes().await?.to_vec());
+                    };
+                    let status = resp.status();
+                    let response_headers = resp.headers().clone();
+                    let body = resp.bytes().await?;
+                    // A mismatched range is retried like any other failure
+                    match range::slice(range, status, &response_headers, &body) {
+                        Ok(data) => return Ok(data),
+                        Err(e) => last_error = Some(e),
+                    }
                 }
                 Ok(resp) => {
                     last_error = Some(anyhow!("Segment fetch failed: {}", resp.status()));
                 }
                 Err(e) => {
-                    last_error = Some(e.into());
+                    last_error = Some(e);
                 }
             }
 
@@ -247,7 +383,8 @@ impl NativeHlsBackend {
 
         match quality {
             StreamQuality::Best => variants.first(),
-            StreamQuality::Worst => variants.last(),
+            // Until throughput is measured, the lowest variant is the safe one
+            StreamQuality::Worst | StreamQuality::Auto => variants.last(),
             StreamQuality::Specific(height) => {
                 // Find closest match
                 variants
@@ -257,6 +394,36 @@ impl NativeHlsBackend {
         }
     }
 
+    /// Throughput in bits/s, from downloading one segment of `variant`
+    async fn probe_throughput(
+        &self,
+        variant: &HlsVariant,
+        headers: &HashMap<String, String>,
+    ) -> Result<u64> {
+        let playlist = self.parse_media_playlist(&variant.uri, headers).await?;
+        // The newest segment of a live stream is the one the capture starts with
+        let segment = if playlist.is_live {
+            playlist.segments.last()
+        } else {
+            playlist.segments.first()
+        }
+        .ok_or_else(|| anyhow!("Variant playlist has no segments to probe"))?;
+        let started = Instant::now();
+        let data = self.fetch_segment(segment, headers).await?;
+        Ok(bits_per_second(data.len() as u64, started.elapsed()))
+    }
+
+    fn log_quality(&self, switch: QualitySwitch) {
+        info!(
+            "Auto quality: {:?}p -> {}p @ {} bps (measured {} bps)",
+            switch.from, switch.to, switch.bandwidth, switch.throughput_bps
+        );
+        if let Some(ref cb) = self.quality_log {
+            cb(switch);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn stream_live_with_duration<W: AsyncWrite + Unpin + Send>(
         &self,
         playlist_url: &str,
@@ -265,12 +432,19 @@ impl NativeHlsBackend {
         progress: &Option<ProgressCallback>,
         start_time: std::time::Instant,
         duration_secs: Option<u64>,
+        mut auto: Option<AutoQuality>,
     ) -> Result<()> {
+        let mut playlist_url = playlist_url.to_string();
         let mut last_sequence = 0u64;
         let mut bytes_downloaded = 0u64;
         let mut segments_completed = 0u32;
 
         loop {
+            if crate::shutdown::is_requested() {
+                info!("Interrupted, stopping live stream");
+                break;
+            }
+
             // Check if we've reached duration limit
             if let Some(max_dur) = duration_secs {
                 if start_time.elapsed().as_secs() >= max_dur {
@@ -279,7 +453,7 @@ impl NativeHlsBackend {
                 }
             }
 
-            let playlist = self.parse_media_playlist(playlist_url, headers).await?;
+            let playlist = self.parse_media_playlist(&playlist_url, headers).await?;
 
             // Find new segments
             let new_segments: Vec<_> = playlist
@@ -292,7 +466,11 @@ impl NativeHlsBackend {
                 debug!("Found {} new segments", new_segments.len());
 
                 for seg in new_segments {
-                    let data = self.fetch_segment(&seg.uri, headers).await?;
+                    let started = Instant::now();
+                    let data = self.fetch_segment(seg, headers).await?;
+                    if let Some(auto) = auto.as_mut() {
+                        auto.record(data.len() as u64, started.elapsed());
+                    }
                     bytes_downloaded += data.len() as u64;
                     segments_completed += 1;
                     last_sequence = seg.sequence;
@@ -315,6 +493,10 @@ impl NativeHlsBackend {
                             return Ok(());
                         }
                     }
+                    if crate::shutdown::is_requested() {
+                        info!("Interrupted, stopping live stream");
+                        return Ok(());
+                    }
                 }
             }
 
@@ -323,6 +505,23 @@ impl NativeHlsBackend {
                 break;
             }
 
+            // Variants share media sequence numbers, so the capture carries on
+            // from `last_sequence` in the new playlist
+            if let Some(auto) = auto.as_mut() {
+                let from = auto.variants[auto.current].height;
+                if let Some(throughput_bps) = auto.reevaluate() {
+                    let variant = &auto.variants[auto.current];
+                    playlist_url.clone_from(&variant.uri);
+                    self.log_quality(QualitySwitch {
+                        from: Some(from),
+                        to: variant.height,
+                        bandwidth: variant.bandwidth,
+                        throughput_bps,
+                        elapsed_seconds: start_time.elapsed().as_secs_f64(),
+                    });
+                }
+            }
+
             // Wait before next poll (half of target duration is typical)
             tokio::time::sleep(Duration::from_secs_f64(playlist.target_duration / 2.0)).await;
         }
@@ -330,30 +529,42 @@ impl NativeHlsBackend {
         Ok(())
     }
 
-    /// Internal streaming with optional duration limit
-    async fn stream_to_internal<W: AsyncWrite + Unpin + Send>(
+    /// Media playlist of `manifest_url`, picking a variant if it's a master
+    /// playlist, plus the throughput tracking `StreamQuality::Auto` continues with
+    async fn resolve_playlist(
         &self,
         manifest_url: &str,
         config: &StreamConfig,
-        output: &mut W,
-        progress: Option<ProgressCallback>,
-        duration_secs: Option<u64>,
-    ) -> Result<()> {
+    ) -> Result<(String, HlsPlaylist, Option<AutoQuality>)> {
         let headers = &config.headers;
-        let start_time = std::time::Instant::now();
 
         // Check if master playlist (has variants) or media playlist (has segments)
         let content = self.fetch_playlist(manifest_url, headers).await?;
         let is_master = content.contains("#EXT-X-STREAM-INF:");
 
+        let mut auto = None;
         let media_url = if is_master {
             let variants = self.parse_master_playlist(manifest_url, headers).await?;
             debug!("Found {} quality variants", variants.len());
 
-            let variant = self
+            let mut variant = self
                 .select_variant(&variants, &config.quality)
                 .ok_or_else(|| anyhow!("No suitable quality variant found"))?;
 
+            if config.quality == StreamQuality::Auto {
+                let throughput_bps = self.probe_throughput(variant, headers).await?;
+                let current = pick_variant(&variants, throughput_bps);
+                variant = &variants[current];
+                self.log_quality(QualitySwitch {
+                    from: None,
+                    to: variant.height,
+                    bandwidth: variant.bandwidth,
+                    throughput_bps,
+                    elapsed_seconds: 0.0,
+                });
+                auto = Some(AutoQuality::new(variants.clone(), current));
+            }
+
             info!(
                 "Selected variant: {}p @ {} bps",
                 variant.height, variant.bandwidth
@@ -369,6 +580,131 @@ impl NativeHlsBackend {
             playlist.segments.len(),
             playlist.is_live
         );
+        Ok((media_url, playlist, auto))
+    }
+
+    /// Size of each segment: its byte range, or the `Content-Length` of a
+    /// HEAD request. `None` if any size is unknown.
+    async fn segment_lengths(
+        &self,
+        segments: &[&HlsSegment],
+        headers: &HashMap<String, String>,
+    ) -> Option<Vec<u64>> {
+        let heads: Vec<_> = segments
+            .iter()
+            .map(|segment| async move {
+                if let Some(range) = &segment.range {
+                    return Some(range.length);
+                }
+                let mut req = self.client.head(&segment.uri);
+                for (k, v) in headers {
+                    req = req.header(k.as_str(), v.as_str());
+                }
+                let resp = crate::policy::send(req).await.ok()?;
+                resp.headers()
+                    .get(reqwest::header::CONTENT_LENGTH)?
+                    .to_str()
+                    .ok()?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        let lengths: Vec<Option<u64>> = futures::stream::iter(heads)
+            .buffered(self.max_concurrent.max(1))
+            .collect()
+            .await;
+        lengths.into_iter().collect()
+    }
+
+    /// Download VOD segments into a preallocated, memory-mapped `path`
+    ///
+    /// Returns `false` (nothing written) when segment sizes aren't known.
+    async fn download_mapped(
+        &self,
+        segments: &[&HlsSegment],
+        headers: &HashMap<String, String>,
+        path: &std::path::Path,
+        progress: Option<&ProgressCallback>,
+    ) -> Result<bool> {
+        let start_time = std::time::Instant::now();
+        let Some(lengths) = self.segment_lengths(segments, headers).await else {
+            info!("Segment sizes unknown, writing the file sequentially");
+            return Ok(false);
+        };
+        let offsets = mmap::offsets(&lengths);
+        let file = MappedFile::create(path, lengths.iter().sum())?;
+        info!(
+            "Preallocated {} bytes for {} segments",
+            file.len(),
+            segments.len()
+        );
+
+        let fetches: Vec<_> = segments
+            .iter()
+            .enumerate()
+            .map(|(i, segment)| async move { (i, self.fetch_segment(segment, headers).await) })
+            .collect();
+        let mut fetches =
+            futures::stream::iter(fetches).buffer_unordered(self.max_concurrent.max(1));
+        let mut bytes_downloaded = 0u64;
+        let mut segments_completed = 0u32;
+        let mut arrived = vec![false; segments.len()];
+        loop {
+            let next = tokio::select! {
+                next = fetches.next() => next,
+                () = crate::shutdown::requested() => None,
+            };
+            let Some((i, data)) = next else {
+                break;
+            };
+            let data = data?;
+            if data.len() as u64 != lengths[i] {
+                return Err(anyhow!(
+                    "Segment {} is {} bytes, expected {}",
+                    segments[i].uri,
+                    data.len(),
+                    lengths[i]
+                ));
+            }
+            file.write_at(offsets[i], &data)?;
+            arrived[i] = true;
+            bytes_downloaded += data.len() as u64;
+            segments_completed += 1;
+            if let Some(cb) = progress {
+                cb(StreamProgress {
+                    bytes_downloaded,
+                    segments_completed,
+                    segments_total: Some(segments.len() as u32),
+                    elapsed_seconds: start_time.elapsed().as_secs_f64(),
+                });
+            }
+        }
+        // Interrupted: keep the segments up to the first gap, so the file still plays
+        let complete = arrived.iter().take_while(|&&done| done).count();
+        if complete < segments.len() {
+            info!(
+                "Interrupted, keeping the first {complete} of {} segments",
+                segments.len()
+            );
+            file.truncate(offsets[complete])?;
+        } else {
+            file.flush()?;
+        }
+        Ok(true)
+    }
+
+    /// Internal streaming with optional duration limit
+    async fn stream_to_internal<W: AsyncWrite + Unpin + Send>(
+        &self,
+        manifest_url: &str,
+        config: &StreamConfig,
+        output: &mut W,
+        progress: Option<ProgressCallback>,
+        duration_secs: Option<u64>,
+    ) -> Result<()> {
+        let headers = &config.headers;
+        let start_time = std::time::Instant::now();
+        let (media_url, playlist, auto) = self.resolve_playlist(manifest_url, config).await?;
 
         let total_segments = if playlist.is_live {
             None
@@ -388,31 +724,21 @@ impl NativeHlsBackend {
                 &progress,
                 start_time,
                 duration_secs,
+                auto,
             )
             .await?;
         } else {
-            // Calculate max segments if duration is limited
-            let max_segments = duration_secs.and_then(|dur| {
-                if playlist.segments.is_empty() {
-                    None
-                } else {
-                    // Estimate segments from target duration
-                    let avg_seg_duration = playlist.target_duration;
-                    Some((dur as f64 / avg_seg_duration).ceil() as usize)
-                }
-            });
-
-            let segments_to_fetch = if let Some(max) = max_segments {
-                playlist.segments.iter().take(max).collect::<Vec<_>>()
-            } else {
-                playlist.segments.iter().collect::<Vec<_>>()
-            };
+            let segments_to_fetch = vod_segments(&playlist, duration_secs);
 
-            // Fetch segments with concurrency
+            // Fetch segments with concurrency; when interrupted, stop after whole segments
             for chunk in segments_to_fetch.chunks(self.max_concurrent) {
+                if crate::shutdown::is_requested() {
+                    info!("Interrupted after {segments_completed} segments");
+                    break;
+                }
                 let futures: Vec<_> = chunk
                     .iter()
-                    .map(|seg| self.fetch_segment(&seg.uri, headers))
+                    .map(|seg| self.fetch_segment(seg, headers))
                     .collect();
 
                 let results = futures::future::join_all(futures).await;
@@ -471,6 +797,23 @@ impl StreamBackend for NativeHlsBackend {
         progress: Option<ProgressCallback>,
         duration_secs: Option<u64>,
     ) -> Result<()> {
+        if self.mmap_output {
+            let (_, playlist, _) = self.resolve_playlist(manifest_url, config).await?;
+            if playlist.is_live {
+                info!("Live playlists have no final size, writing the file sequentially");
+            } else if playlist.segments.iter().any(|s| s.key.is_some()) {
+                info!("Decrypted segments differ in size, writing the file sequentially");
+            } else {
+                let segments = vod_segments(&playlist, duration_secs);
+                if self
+                    .download_mapped(&segments, &config.headers, path, progress.as_ref())
+                    .await?
+                {
+                    return Ok(());
+                }
+            }
+        }
+
         let file = tokio::fs::File::create(path).await?;
         let mut writer = tokio::io::BufWriter::new(file);
         self.stream_to_internal(manifest_url, config, &mut writer, progress, duration_secs)
@@ -478,6 +821,85 @@ impl StreamBackend for NativeHlsBackend {
     }
 }
 
+/// VOD segments to fetch, enough to cover `duration_secs` if given
+fn vod_segments(playlist: &HlsPlaylist, duration_secs: Option<u64>) -> Vec<&HlsSegment> {
+    // Estimate segments from target duration
+    let max_segments = duration_secs
+        .filter(|_| !playlist.segments.is_empty())
+        .map(|dur| (dur as f64 / playlist.target_duration).ceil() as usize);
+    playlist
+        .segments
+        .iter()
+        .take(max_segments.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Index of the highest variant (`variants` sorted by bandwidth, descending)
+/// that fits the throughput with headroom, else the lowest
+fn pick_variant(variants: &[HlsVariant], throughput_bps: u64) -> usize {
+    let budget = throughput_bps as f64 * AUTO_HEADROOM;
+    variants
+        .iter()
+        .position(|v| v.bandwidth as f64 <= budget)
+        .unwrap_or(variants.len().saturating_sub(1))
+}
+
+fn bits_per_second(bytes: u64, took: Duration) -> u64 {
+    (bytes as f64 * 8.0 / took.as_secs_f64().max(0.001)) as u64
+}
+
+/// Throughput measured from live segment downloads, for `StreamQuality::Auto`
+#[derive(Debug)]
+struct AutoQuality {
+    variants: Vec<HlsVariant>,
+    current: usize,
+    bytes: u64,
+    busy: Duration,
+    last_check: Instant,
+}
+
+impl AutoQuality {
+    fn new(variants: Vec<HlsVariant>, current: usize) -> Self {
+        Self {
+            variants,
+            current,
+            bytes: 0,
+            busy: Duration::ZERO,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// A segment of `bytes` took `took` to download
+    fn record(&mut self, bytes: u64, took: Duration) {
+        self.bytes += bytes;
+        self.busy += took;
+    }
+
+    /// Switch variants if the throughput since the last check calls for it,
+    /// at most every `AUTO_REEVALUATE`. Steps up one variant at a time, but
+    /// drops straight to one that fits. Returns the measured throughput.
+    fn reevaluate(&mut self) -> Option<u64> {
+        if self.last_check.elapsed() < AUTO_REEVALUATE || self.busy.is_zero() {
+            return None;
+        }
+        let throughput_bps = bits_per_second(self.bytes, self.busy);
+        self.bytes = 0;
+        self.busy = Duration::ZERO;
+        self.last_check = Instant::now();
+
+        let fits = pick_variant(&self.variants, throughput_bps);
+        let next = if fits < self.current {
+            self.current - 1
+        } else {
+            fits
+        };
+        (next != self.current).then(|| {
+            self.current = next;
+            throughput_bps
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 struct HlsVariant {
     bandwidth: u64,
@@ -502,6 +924,9 @@ struct HlsSegment {
     #[allow(dead_code)]
     duration: f64,
     uri: String,
+    /// Part of `uri` holding the segment
+    range: Option<ByteRange>,
+    key: Option<SegmentKey>,
 }
 
 #[cfg(test)]
@@ -541,4 +966,220 @@ mod tests {
             Some(&"avc1.4d401f,mp4a.40.2".to_string())
         );
     }
+
+    #[test]
+    fn test_auto_quality_picks_and_switches() {
+        let variants: Vec<HlsVariant> = [(1080, 6_000_000), (720, 3_000_000), (360, 800_000)]
+            .into_iter()
+            .map(|(height, bandwidth)| HlsVariant {
+                bandwidth,
+                height,
+                codecs: None,
+                uri: format!("{height}.m3u8"),
+            })
+            .collect();
+        assert_eq!(pick_variant(&variants, 10_000_000), 0);
+        // 80% of 7 Mbps doesn't fit 6 Mbps, 80% of 7.5 Mbps does
+        assert_eq!(pick_variant(&variants, 7_000_000), 1);
+        assert_eq!(pick_variant(&variants, 7_500_000), 0);
+        assert_eq!(pick_variant(&variants, 100_000), 2);
+
+        let mut auto = AutoQuality::new(variants, 2);
+        // 2 MB/s: plenty for 1080p, but steps up one variant per check
+        auto.record(2_000_000, Duration::from_secs(1));
+        assert_eq!(auto.reevaluate(), None, "too soon");
+        auto.last_check -= AUTO_REEVALUATE;
+        assert_eq!(auto.reevaluate(), Some(16_000_000));
+        assert_eq!(auto.current, 1);
+
+        // Throughput collapses: straight down to what fits
+        auto.record(50_000, Duration::from_secs(1));
+        auto.last_check -= AUTO_REEVALUATE;
+        assert_eq!(auto.reevaluate(), Some(400_000));
+        assert_eq!(auto.current, 2);
+    }
+
+    /// Serves `FILE` as `honor.ts` (206), `ignore.ts` (200, whole file),
+    /// `multi.ts` (multipart/byteranges), and `wrong.ts` (206, wrong range)
+    async fn range_server(playlist: &'static str) -> String {
+        use tokio::io::AsyncReadExt;
+
+        const FILE: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let (first, last) = request
+                    .lines()
+                    .find_map(|line| line.strip_prefix("range: bytes="))
+                    .and_then(|r| r.trim().split_once('-'))
+                    .map_or((0, FILE.len() - 1), |(a, b)| {
+                        (a.parse().unwrap(), b.parse().unwrap())
+                    });
+                let (head, body): (String, Vec<u8>) = match path.as_str() {
+                    "/index.m3u8" => ("200 OK".into(), playlist.as_bytes().to_vec()),
+                    "/honor.ts" => (
+                        format!("206 Partial Content\r\nContent-Range: bytes {first}-{last}/36"),
+                        FILE[first..=last].to_vec(),
+                    ),
+                    "/ignore.ts" => ("200 OK".into(), FILE.to_vec()),
+                    "/multi.ts" => {
+                        let mut body =
+                            b"--B\r\nContent-Range: bytes 0-3/36\r\n\r\n0123\r\n".to_vec();
+                        body.extend(
+                            format!("--B\r\nContent-Range: bytes {first}-{last}/36\r\n\r\n")
+                                .as_bytes(),
+                        );
+                        body.extend(&FILE[first..=last]);
+                        body.extend(b"\r\n--B--\r\n");
+                        (
+                            "206 Partial Content\r\nContent-Type: multipart/byteranges; boundary=B"
+                                .into(),
+                            body,
+                        )
+                    }
+                    _ => (
+                        "206 Partial Content\r\nContent-Range: bytes 0-5/36".into(),
+                        FILE[..6].to_vec(),
+                    ),
+                };
+                let head = format!(
+                    "HTTP/1.1 {head}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+        format!("http://{addr}/index.m3u8")
+    }
+
+    #[tokio::test]
+    async fn test_byte_range_segments() {
+        let url = range_server(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n\
+             #EXTINF:2,\n#EXT-X-BYTERANGE:4@0\nhonor.ts\n\
+             #EXTINF:2,\n#EXT-X-BYTERANGE:6\nhonor.ts\n\
+             #EXTINF:2,\n#EXT-X-BYTERANGE:6@10\nignore.ts\n\
+             #EXTINF:2,\n#EXT-X-BYTERANGE:3@16\nmulti.ts\n\
+             #EXT-X-ENDLIST\n",
+        )
+        .await;
+        let backend = NativeHlsBackend::new().unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        backend
+            .stream_to(&url, &StreamConfig::default(), &mut output, None)
+            .await
+            .unwrap();
+        assert_eq!(output, b"0123456789abcdefghi");
+    }
+
+    #[tokio::test]
+    async fn test_mmap_output_writes_segments_at_offsets() {
+        let url = range_server(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n\
+             #EXTINF:2,\n#EXT-X-BYTERANGE:4@0\nhonor.ts\n\
+             #EXTINF:2,\n#EXT-X-BYTERANGE:6\nhonor.ts\n\
+             #EXTINF:2,\nplain.ts\n\
+             #EXTINF:2,\n#EXT-X-BYTERANGE:3@16\nmulti.ts\n\
+             #EXT-X-ENDLIST\n",
+        )
+        .await;
+        let path = std::env::temp_dir().join(format!("nab-hls-mmap-{}.ts", std::process::id()));
+        let backend = NativeHlsBackend::new().unwrap().with_mmap_output(true);
+        backend
+            .stream_to_file(&url, &StreamConfig::default(), &path, None, None)
+            .await
+            .unwrap();
+        // plain.ts is sized by HEAD
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123456789012345ghi");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_aes128_segments_are_decrypted() {
+        use aes::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use tokio::io::AsyncReadExt;
+
+        const KEY: [u8; 16] = *b"0123456789abcdef";
+        let encrypt = |data: &[u8], iv: u128| {
+            cbc::Encryptor::<aes::Aes128>::new(&KEY.into(), &iv.to_be_bytes().into())
+                .encrypt_padded_vec_mut::<Pkcs7>(data)
+        };
+        // Sequence 5 uses the sequence-number IV, 6 an explicit one; 7 is clear
+        let files: Arc<HashMap<&str, Vec<u8>>> = Arc::new(HashMap::from([
+            (
+                "/index.m3u8",
+                b"#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:5\n\
+                  #EXT-X-KEY:METHOD=AES-128,URI=\"key.bin\"\n#EXTINF:2,\na.ts\n\
+                  #EXT-X-KEY:METHOD=AES-128,URI=\"key.bin\",IV=0x2A\n#EXTINF:2,\nb.ts\n\
+                  #EXT-X-KEY:METHOD=NONE\n#EXTINF:2,\nc.ts\n#EXT-X-ENDLIST\n"
+                    .to_vec(),
+            ),
+            ("/key.bin", KEY.to_vec()),
+            ("/a.ts", encrypt(b"first segment|", 5)),
+            ("/b.ts", encrypt(b"second segment|", 0x2A)),
+            ("/c.ts", b"clear".to_vec()),
+        ]));
+        let key_requests = Arc::new(AtomicUsize::new(0));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let counter = Arc::clone(&key_requests);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                if path == "/key.bin" {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+                let body = files.get(path).cloned().unwrap_or_default();
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+
+        let backend = NativeHlsBackend::new().unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        backend
+            .stream_to(
+                &format!("http://{addr}/index.m3u8"),
+                &StreamConfig::default(),
+                &mut output,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(output, b"first segment|second segment|clear");
+        assert_eq!(key_requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_content_range_is_rejected() {
+        let url =
+            range_server("#EXTM3U\n#EXTINF:2,\n#EXT-X-BYTERANGE:4@20\nwrong.ts\n#EXT-X-ENDLIST\n")
+                .await;
+        let backend = NativeHlsBackend {
+            max_retries: 1,
+            ..NativeHlsBackend::new().unwrap()
+        };
+        let mut output: Vec<u8> = Vec::new();
+        let err = backend
+            .stream_to(&url, &StreamConfig::default(), &mut output, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("doesn't cover"), "{err}");
+        assert!(output.is_empty());
+    }
 }
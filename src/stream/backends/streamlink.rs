@@ -57,7 +57,8 @@ impl StreamlinkBackend {
     /// Convert `StreamQuality` to streamlink quality string
     fn quality_to_string(quality: &StreamQuality) -> String {
         match quality {
-            StreamQuality::Best => "best".to_string(),
+            // streamlink has no throughput probe
+            StreamQuality::Best | StreamQuality::Auto => "best".to_string(),
             StreamQuality::Worst => "worst".to_string(),
             StreamQuality::Specific(height) => format!("{height}p"),
         }
@@ -414,6 +415,7 @@ mod tests {
             quality: StreamQuality::Best,
             headers: HashMap::new(),
             cookies: None,
+            audio_languages: Vec::new(),
         };
 
         let args = backend.build_args_stdout("https://www.twitch.tv/example", &config);
@@ -434,6 +436,7 @@ mod tests {
             quality: StreamQuality::Specific(720),
             headers: HashMap::new(),
             cookies: None,
+            audio_languages: Vec::new(),
         };
 
         let args = backend.build_args_file(
@@ -465,6 +468,7 @@ mod tests {
             quality: StreamQuality::Best,
             headers,
             cookies: None,
+            audio_languages: Vec::new(),
         };
 
         let args = backend.build_args_stdout("https://www.twitch.tv/example", &config);
@@ -485,6 +489,7 @@ mod tests {
             quality: StreamQuality::Best,
             headers: HashMap::new(),
             cookies: Some("session=abc123".to_string()),
+            audio_languages: Vec::new(),
         };
 
         let args = backend.build_args_stdout("https://www.twitch.tv/example", &config);
@@ -0,0 +1,173 @@
+//! Output containers for stream captures
+//!
+//! MP4 files get `+faststart` (the index moved to the front) so they play
+//! while still being downloaded; MP4 on a pipe is fragmented instead.
+//! [`Container::compatible`] falls back to Matroska, which takes any codec,
+//! when a stream can't live in the requested container. The native backend
+//! writes MPEG-TS segments, so its captures are [`remux`]ed afterwards.
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use tokio::process::Command;
+
+use super::tracks::ProbedStream;
+
+/// Output container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Mp4,
+    Mkv,
+    Ts,
+}
+
+impl Container {
+    /// Container for a file name's extension
+    #[must_use]
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "mp4" | "m4v" => Some(Self::Mp4),
+            "mkv" => Some(Self::Mkv),
+            "ts" | "m2ts" => Some(Self::Ts),
+            _ => None,
+        }
+    }
+
+    /// File extension
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Mkv => "mkv",
+            Self::Ts => "ts",
+        }
+    }
+
+    /// ffmpeg output options, up to the output path
+    #[must_use]
+    pub fn output_args(self, piped: bool) -> Vec<String> {
+        let args: &[&str] = match (self, piped) {
+            (Self::Mp4, false) => &["-sn", "-dn", "-f", "mp4", "-movflags", "+faststart"],
+            (Self::Mp4, true) => &[
+                "-sn",
+                "-dn",
+                "-f",
+                "mp4",
+                "-movflags",
+                "frag_keyframe+empty_moov+default_base_moof",
+            ],
+            (Self::Mkv, _) => &["-f", "matroska"],
+            (Self::Ts, _) => &["-sn", "-dn", "-f", "mpegts"],
+        };
+        args.iter().map(std::string::ToString::to_string).collect()
+    }
+
+    /// Whether an audio or video codec (ffprobe `codec_name`) can be copied in
+    fn supports(self, codec: &str) -> bool {
+        match self {
+            Self::Mkv => true,
+            Self::Mp4 => matches!(
+                codec,
+                "h264"
+                    | "hevc"
+                    | "av1"
+                    | "vp9"
+                    | "mpeg4"
+                    | "mpeg2video"
+                    | "aac"
+                    | "mp3"
+                    | "mp2"
+                    | "ac3"
+                    | "eac3"
+                    | "opus"
+                    | "flac"
+                    | "alac"
+            ),
+            Self::Ts => matches!(
+                codec,
+                "h264"
+                    | "hevc"
+                    | "mpeg2video"
+                    | "mpeg1video"
+                    | "mpeg4"
+                    | "aac"
+                    | "mp3"
+                    | "mp2"
+                    | "ac3"
+                    | "eac3"
+                    | "opus"
+            ),
+        }
+    }
+
+    /// This container, or Matroska when an audio/video stream can't go in it
+    /// (with that stream's codec)
+    #[must_use]
+    pub fn compatible(self, streams: &[ProbedStream]) -> (Self, Option<String>) {
+        let unsupported = streams
+            .iter()
+            .filter(|s| s.kind == "video" || s.kind == "audio")
+            .find(|s| !self.supports(&s.codec));
+        match unsupported {
+            Some(stream) => (Self::Mkv, Some(format!("{} {}", stream.kind, stream.codec))),
+            None => (self, None),
+        }
+    }
+}
+
+/// Copy the audio and video of `input` into `output` as `container`
+pub async fn remux(
+    ffmpeg_path: &str,
+    input: &Path,
+    output: &Path,
+    container: Container,
+) -> Result<()> {
+    let status = Command::new(ffmpeg_path)
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-map", "0:v?", "-map", "0:a?", "-c", "copy"])
+        .args(container.output_args(false))
+        .arg(output)
+        .status()
+        .await
+        .map_err(|e| anyhow!("Failed to run {ffmpeg_path}: {e}"))?;
+    if !status.success() {
+        return Err(anyhow!("Remuxing to {} failed: {status}", output.display()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_fallback() {
+        let stream = |kind: &str, codec: &str| ProbedStream {
+            index: 0,
+            kind: kind.to_string(),
+            codec: codec.to_string(),
+            height: 0,
+            bitrate: 0,
+            language: None,
+        };
+        let streams = [
+            stream("video", "h264"),
+            stream("audio", "aac"),
+            stream("subtitle", "webvtt"),
+        ];
+        assert_eq!(Container::Mp4.compatible(&streams), (Container::Mp4, None));
+
+        let streams = [stream("video", "vp8"), stream("audio", "vorbis")];
+        assert_eq!(
+            Container::Ts.compatible(&streams),
+            (Container::Mkv, Some("video vp8".to_string()))
+        );
+        assert_eq!(
+            Container::from_extension(Path::new("capture.M4V")),
+            Some(Container::Mp4)
+        );
+        assert!(Container::Mp4
+            .output_args(false)
+            .contains(&"+faststart".to_string()));
+    }
+}
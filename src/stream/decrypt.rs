@@ -0,0 +1,236 @@
+//! HLS segment decryption (`#EXT-X-KEY`)
+//!
+//! `AES-128` encrypts whole segments with AES-128-CBC and PKCS#7 padding.
+//! `SAMPLE-AES` encrypts inside the media samples; it's handled here for
+//! packed ADTS audio (`.aac` segments). Elementary streams inside MPEG-TS need
+//! a demux and remux to decrypt, which the ffmpeg backend does.
+//!
+//! Without an `IV` attribute, a segment's IV is its media sequence number.
+
+use aes::cipher::{block_padding::NoPadding, block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+/// Encryption method of an `#EXT-X-KEY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMethod {
+    Aes128,
+    SampleAes,
+}
+
+/// The key a segment is encrypted with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentKey {
+    pub method: KeyMethod,
+    /// Absolute key URI
+    pub uri: String,
+    /// Explicit IV; `None` means the media sequence number
+    pub iv: Option<[u8; 16]>,
+}
+
+impl SegmentKey {
+    /// Key from `#EXT-X-KEY` attributes, `None` for `METHOD=NONE`;
+    /// `resolve` makes the key URI absolute
+    pub fn from_attributes(
+        attrs: &HashMap<String, String>,
+        resolve: impl Fn(&str) -> String,
+    ) -> Result<Option<Self>> {
+        let method = match attrs.get("METHOD").map(String::as_str) {
+            Some("NONE") => return Ok(None),
+            Some("AES-128") => KeyMethod::Aes128,
+            Some("SAMPLE-AES") => KeyMethod::SampleAes,
+            Some(other) => bail!("Unsupported HLS encryption method {other}"),
+            None => bail!("#EXT-X-KEY without METHOD"),
+        };
+        // Other key formats are DRM systems (FairPlay, Widevine, PlayReady)
+        if let Some(format) = attrs.get("KEYFORMAT").filter(|f| *f != "identity") {
+            bail!("DRM-protected stream ({format}), can't be decrypted");
+        }
+        let uri = attrs
+            .get("URI")
+            .ok_or_else(|| anyhow!("#EXT-X-KEY without URI"))?;
+        let iv = attrs.get("IV").map(|iv| parse_iv(iv)).transpose()?;
+        Ok(Some(Self {
+            method,
+            uri: resolve(uri),
+            iv,
+        }))
+    }
+
+    /// IV for the segment with media sequence number `sequence`
+    #[must_use]
+    pub fn iv_for(&self, sequence: u64) -> [u8; 16] {
+        self.iv
+            .unwrap_or_else(|| u128::from(sequence).to_be_bytes())
+    }
+}
+
+/// `0x`-prefixed, 32-digit hex IV
+fn parse_iv(value: &str) -> Result<[u8; 16]> {
+    let hex = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    let iv = u128::from_str_radix(hex, 16).map_err(|_| anyhow!("Invalid HLS key IV {value}"))?;
+    Ok(iv.to_be_bytes())
+}
+
+/// Decrypt one segment with its 16-byte `key`
+pub fn decrypt_segment(
+    key: &SegmentKey,
+    key_bytes: &[u8],
+    sequence: u64,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    let key_bytes: &[u8; 16] = key_bytes.try_into().map_err(|_| {
+        anyhow!(
+            "HLS key {} is {} bytes, expected 16",
+            key.uri,
+            key_bytes.len()
+        )
+    })?;
+    let iv = key.iv_for(sequence);
+    match key.method {
+        KeyMethod::Aes128 => Aes128CbcDec::new(key_bytes.into(), &iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .map_err(|_| anyhow!("AES-128 segment {sequence} didn't decrypt (wrong key?)")),
+        KeyMethod::SampleAes => {
+            let mut data = data.to_vec();
+            decrypt_sample_aes_adts(key_bytes, &iv, &mut data)?;
+            Ok(data)
+        }
+    }
+}
+
+/// Decrypt SAMPLE-AES packed ADTS audio in place: in each frame, the first
+/// 16 bytes after the header and a trailing partial block are clear, and the
+/// CBC chain restarts from the IV
+fn decrypt_sample_aes_adts(key: &[u8; 16], iv: &[u8; 16], data: &mut [u8]) -> Result<()> {
+    // MPEG-TS packets start with a 0x47 sync byte
+    if data.first() == Some(&0x47) {
+        bail!("SAMPLE-AES in MPEG-TS segments isn't supported natively, try --ffmpeg");
+    }
+    let mut pos = id3_len(data);
+    while pos + 7 <= data.len() {
+        let header = &data[pos..];
+        if header[0] != 0xFF || header[1] & 0xF0 != 0xF0 {
+            bail!("SAMPLE-AES segment isn't ADTS audio, try --ffmpeg");
+        }
+        let header_len = if header[1] & 0x01 == 0 { 9 } else { 7 };
+        let frame_len = (usize::from(header[3] & 0x03) << 11)
+            | (usize::from(header[4]) << 3)
+            | usize::from(header[5] >> 5);
+        if frame_len < header_len || pos + frame_len > data.len() {
+            bail!("Truncated ADTS frame at byte {pos}");
+        }
+        let payload = &mut data[pos + header_len..pos + frame_len];
+        if payload.len() > 16 {
+            let encrypted = (payload.len() - 16) / 16 * 16;
+            Aes128CbcDec::new(key.into(), iv.into())
+                .decrypt_padded_mut::<NoPadding>(&mut payload[16..16 + encrypted])
+                .map_err(|_| anyhow!("ADTS frame at byte {pos} didn't decrypt"))?;
+        }
+        pos += frame_len;
+    }
+    Ok(())
+}
+
+/// Length of a leading ID3v2 tag (packed audio carries its timestamp in one)
+fn id3_len(data: &[u8]) -> usize {
+    if data.len() < 10 || &data[..3] != b"ID3" {
+        return 0;
+    }
+    let size = data[6..10]
+        .iter()
+        .fold(0usize, |size, &b| (size << 7) | usize::from(b & 0x7F));
+    let footer = if data[5] & 0x10 == 0 { 0 } else { 10 };
+    (10 + size + footer).min(data.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockEncryptMut;
+
+    type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+
+    const KEY: [u8; 16] = *b"0123456789abcdef";
+
+    fn segment_key(method: KeyMethod, iv: Option<&str>) -> SegmentKey {
+        let mut attrs = HashMap::from([
+            ("METHOD".to_string(), "AES-128".to_string()),
+            ("URI".to_string(), "key.bin".to_string()),
+        ]);
+        if let Some(iv) = iv {
+            attrs.insert("IV".to_string(), iv.to_string());
+        }
+        let mut key = SegmentKey::from_attributes(&attrs, |uri| format!("https://cdn/{uri}"))
+            .unwrap()
+            .unwrap();
+        key.method = method;
+        key
+    }
+
+    #[test]
+    fn test_aes128_segment() {
+        let key = segment_key(KeyMethod::Aes128, None);
+        assert_eq!(key.uri, "https://cdn/key.bin");
+        assert_eq!(key.iv_for(7)[15], 7);
+
+        let segment = b"G@\x00\x10 a transport stream segment".to_vec();
+        let encrypted = Aes128CbcEnc::new(&KEY.into(), &key.iv_for(7).into())
+            .encrypt_padded_vec_mut::<Pkcs7>(&segment);
+        assert_eq!(decrypt_segment(&key, &KEY, 7, &encrypted).unwrap(), segment);
+        let err = decrypt_segment(&key, b"short", 7, &encrypted).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "HLS key https://cdn/key.bin is 5 bytes, expected 16"
+        );
+
+        let explicit = segment_key(
+            KeyMethod::Aes128,
+            Some("0x000000000000000000000000000000FF"),
+        );
+        assert_eq!(explicit.iv_for(7)[15], 0xFF);
+        let none = HashMap::from([("METHOD".to_string(), "NONE".to_string())]);
+        assert_eq!(
+            SegmentKey::from_attributes(&none, str::to_string).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sample_aes_adts() {
+        let key = segment_key(
+            KeyMethod::SampleAes,
+            Some("0x0102030405060708090a0b0c0d0e0f10"),
+        );
+        let iv = key.iv_for(0);
+        // 7-byte header, 16 clear bytes, two encrypted blocks, 5 clear bytes
+        let payload: Vec<u8> = (0..16 + 32 + 5).collect();
+        let frame_len = 7 + payload.len();
+        let mut frame = vec![
+            0xFF,
+            0xF1,
+            0x50,
+            0x80 | (frame_len >> 11) as u8,
+            (frame_len >> 3) as u8,
+            ((frame_len & 7) << 5) as u8 | 0x1F,
+            0xFC,
+        ];
+        frame.extend(&payload);
+        let mut encrypted = frame.clone();
+        Aes128CbcEnc::new(&KEY.into(), &iv.into())
+            .encrypt_padded_mut::<NoPadding>(&mut encrypted[7 + 16..7 + 48], 32)
+            .unwrap();
+        let mut segment = b"ID3\x04\x00\x00\x00\x00\x00\x02ab".to_vec();
+        segment.extend(&encrypted);
+        segment.extend(&encrypted);
+
+        let decrypted = decrypt_segment(&key, &KEY, 0, &segment).unwrap();
+        assert_eq!(&decrypted[12..12 + frame_len], frame);
+        assert_eq!(&decrypted[12 + frame_len..], frame);
+    }
+}
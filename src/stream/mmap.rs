@@ -0,0 +1,153 @@
+//! Memory-Mapped Output
+//!
+//! For multi-GB captures whose segment sizes are known up front: the output
+//! file is preallocated at its final size and mapped, and each segment is
+//! copied straight to its offset as soon as it arrives. Segments can finish
+//! in any order without a reorder buffer, and nothing is buffered twice.
+
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{bail, Context, Result};
+use memmap2::MmapMut;
+
+/// Where each part goes in the output: offsets of consecutive lengths
+#[must_use]
+pub fn offsets(lengths: &[u64]) -> Vec<u64> {
+    lengths
+        .iter()
+        .scan(0, |offset, length| {
+            let start = *offset;
+            *offset += length;
+            Some(start)
+        })
+        .collect()
+}
+
+/// Output file mapped into memory at its final size
+pub struct MappedFile {
+    /// `None` for an empty file (zero-length maps aren't portable)
+    map: Option<Mutex<MmapMut>>,
+    file: File,
+    len: u64,
+}
+
+impl MappedFile {
+    /// Create (or truncate) `path` with `len` bytes and map it
+    pub fn create(path: &Path, len: u64) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        file.set_len(len)
+            .with_context(|| format!("Failed to preallocate {len} bytes for {}", path.display()))?;
+        if len == 0 {
+            return Ok(Self {
+                map: None,
+                file,
+                len,
+            });
+        }
+        // SAFETY: the file was just created and truncated by us; nothing else
+        // in this process maps or resizes it while the map is alive. Another
+        // process changing it underneath is the usual mmap caveat and would
+        // only corrupt the output, which it could do anyway.
+        let map = unsafe { MmapMut::map_mut(&file) }
+            .with_context(|| format!("Failed to map {}", path.display()))?;
+        Ok(Self {
+            map: Some(Mutex::new(map)),
+            file,
+            len,
+        })
+    }
+
+    /// Final size
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy `data` to `offset`
+    pub fn write_at(&self, offset: u64, data: &[u8]) -> Result<()> {
+        let end = offset + data.len() as u64;
+        if end > self.len {
+            bail!(
+                "Write of {} bytes at {offset} is past the end of the {}-byte output",
+                data.len(),
+                self.len
+            );
+        }
+        let Some(map) = &self.map else {
+            return Ok(());
+        };
+        let (start, end) = (usize::try_from(offset)?, usize::try_from(end)?);
+        map.lock().unwrap_or_else(PoisonError::into_inner)[start..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Write the mapped pages back to the file
+    pub fn flush(&self) -> Result<()> {
+        if let Some(map) = &self.map {
+            map.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .flush()
+                .context("Failed to flush mapped output")?;
+        }
+        Ok(())
+    }
+
+    /// Flush and cut the file back to its first `len` bytes (an output that stopped early)
+    pub fn truncate(self, len: u64) -> Result<()> {
+        self.flush()?;
+        let Self {
+            map,
+            file,
+            len: full,
+        } = self;
+        drop(map);
+        file.set_len(len.min(full))
+            .context("Failed to truncate mapped output")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_out_of_order_writes() {
+        let path = std::env::temp_dir().join(format!("nab-mmap-{}.bin", std::process::id()));
+        let parts: [&[u8]; 3] = [b"first-", b"second-", b"third"];
+        let lengths: Vec<u64> = parts.iter().map(|p| p.len() as u64).collect();
+        let offsets = offsets(&lengths);
+        assert_eq!(offsets, vec![0, 6, 13]);
+
+        let file = MappedFile::create(&path, lengths.iter().sum()).unwrap();
+        for i in [2, 0, 1] {
+            file.write_at(offsets[i], parts[i]).unwrap();
+        }
+        assert!(file.write_at(15, b"overflow").is_err());
+        file.flush().unwrap();
+        drop(file);
+        assert_eq!(std::fs::read(&path).unwrap(), b"first-second-third");
+
+        let file = MappedFile::create(&path, lengths.iter().sum()).unwrap();
+        file.write_at(offsets[0], parts[0]).unwrap();
+        file.truncate(offsets[1]).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"first-");
+
+        let empty = MappedFile::create(&path, 0).unwrap();
+        assert!(empty.is_empty());
+        assert!(empty.write_at(0, b"").is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
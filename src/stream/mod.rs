@@ -5,8 +5,14 @@
 
 pub mod backend;
 pub mod backends;
+pub mod container;
+pub mod decrypt;
+pub mod mmap;
+pub mod player;
 pub mod provider;
 pub mod providers;
+pub mod range;
+pub mod tracks;
 
 pub use backend::{BackendType, StreamBackend};
 pub use provider::{StreamInfo, StreamProvider, StreamQuality};
@@ -0,0 +1,112 @@
+//! Piping streams into a media player
+//!
+//! The player reads from stdin. Backends write into a bounded in-memory pipe
+//! ([`PLAYER_BUFFER`]) that a task copies into the player, so downloads run a
+//! little ahead of playback and then wait for it. When the player exits the
+//! pipe breaks, and [`is_closed`] tells that apart from a failed download.
+
+use anyhow::{anyhow, Result};
+use std::process::{ExitStatus, Stdio};
+use tokio::io::DuplexStream;
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+
+/// Bytes buffered ahead of the player
+pub const PLAYER_BUFFER: usize = 8 * 1024 * 1024;
+
+/// Arguments that make a player read its input from stdin
+#[must_use]
+pub fn stdin_args(player: &str) -> &'static [&'static str] {
+    let name = std::path::Path::new(player)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(player);
+    match name {
+        "vlc" | "cvlc" => &["-", "--intf", "dummy", "--play-and-exit"],
+        "mpv" => &["--cache=yes", "-"],
+        "ffplay" => &["-autoexit", "-i", "-"],
+        "iina" => &["--stdin"],
+        _ => &["-"], // Most players accept - for stdin
+    }
+}
+
+/// A running player fed through a bounded pipe
+pub struct Player {
+    child: Child,
+    feeder: JoinHandle<std::io::Result<u64>>,
+}
+
+impl Player {
+    /// Start `command` with `extra_args` (before the stdin arguments), returning
+    /// the writer end of its input
+    pub fn spawn(command: &str, extra_args: &[String]) -> Result<(Self, DuplexStream)> {
+        let mut child = Command::new(command)
+            .args(extra_args)
+            .args(stdin_args(command))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to spawn {command}: {e}"))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Failed to get stdin for {command}"))?;
+
+        let (input, mut output) = tokio::io::duplex(PLAYER_BUFFER);
+        let feeder = tokio::spawn(async move {
+            let copied = tokio::io::copy(&mut output, &mut stdin).await;
+            // Closing stdin is the player's end of file
+            drop(stdin);
+            copied
+        });
+        Ok((Self { child, feeder }, input))
+    }
+
+    /// Wait for the player to exit on its own
+    pub async fn exited(&mut self) -> Result<ExitStatus> {
+        Ok(self.child.wait().await?)
+    }
+
+    /// Close the input and wait for the player to play what's buffered
+    pub async fn finish(mut self, input: DuplexStream) -> Result<ExitStatus> {
+        drop(input);
+        match self.feeder.await? {
+            Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => {}
+        }
+        Ok(self.child.wait().await?)
+    }
+}
+
+/// Whether a streaming error means the player went away
+#[must_use]
+pub fn is_closed(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn test_stdin_args() {
+        assert_eq!(stdin_args("/usr/bin/mpv"), ["--cache=yes", "-"]);
+        assert_eq!(stdin_args("vlc")[0], "-");
+        assert_eq!(stdin_args("my-player"), ["-"]);
+    }
+
+    #[tokio::test]
+    async fn test_closed_pipe_is_detected() {
+        let (mut input, output) = tokio::io::duplex(16);
+        drop(output);
+        let err = input.write_all(b"segment").await.unwrap_err();
+        assert!(is_closed(&anyhow::Error::new(err).context("streaming")));
+        assert!(!is_closed(&anyhow!("HTTP 404")));
+    }
+}
@@ -9,6 +9,8 @@ pub enum StreamQuality {
     Best,
     Worst,
     Specific(u32), // Height in pixels (720, 1080, etc.)
+    /// Highest variant the measured throughput sustains (native HLS)
+    Auto,
 }
 
 /// Quality information for a stream variant
@@ -16,7 +16,7 @@ pub struct DrProvider {
 
 impl DrProvider {
     pub fn new() -> Result<Self> {
-        let client = Client::builder().user_agent("nab/1.0").build()?;
+        let client = crate::policy::client_builder().user_agent("nab/1.0").build()?;
         Ok(Self { client })
     }
 
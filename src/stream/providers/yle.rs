@@ -17,7 +17,7 @@ pub struct YleProvider {
 
 impl YleProvider {
     pub fn new() -> Result<Self> {
-        let client = Client::builder().user_agent("nab/1.0").build()?;
+        let client = crate::policy::client_builder().user_agent("nab/1.0").build()?;
         Ok(Self { client })
     }
 
@@ -0,0 +1,306 @@
+//! Byte-Range Requests
+//!
+//! HLS playlists can address segments as byte ranges of one file
+//! (`#EXT-X-BYTERANGE:<length>[@<offset>]`). A server answers a `Range`
+//! request in one of three ways, and the bytes are only used once they are
+//! known to be the ones asked for:
+//! - `206` with `Content-Range`: checked against the request and the body length
+//! - `206` with a `multipart/byteranges` body: the part covering the range is used
+//! - `200` with the whole file (`Range` ignored): the range is cut out of it
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use reqwest::header::{HeaderMap, CONTENT_RANGE, CONTENT_TYPE};
+use reqwest::StatusCode;
+
+/// `length` bytes from `offset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub length: u64,
+}
+
+impl ByteRange {
+    /// Parse an `#EXT-X-BYTERANGE` value; without `@offset` the range starts
+    /// where `previous` (the previous range of the same URI) ended
+    pub fn parse_hls(value: &str, previous: Option<&Self>) -> Result<Self> {
+        let (length, offset) = match value.trim().split_once('@') {
+            Some((length, offset)) => (length, Some(offset)),
+            None => (value.trim(), None),
+        };
+        let length: u64 = length
+            .parse()
+            .with_context(|| format!("Invalid byte range '{value}'"))?;
+        let offset = match (offset, previous) {
+            (Some(offset), _) => offset
+                .parse()
+                .with_context(|| format!("Invalid byte range '{value}'"))?,
+            (None, Some(previous)) => previous.end(),
+            (None, None) => bail!("Byte range '{value}' has no offset and no previous range"),
+        };
+        if length == 0 {
+            bail!("Empty byte range '{value}'");
+        }
+        if offset.checked_add(length).is_none() {
+            bail!("Byte range '{value}' ends past the largest possible offset");
+        }
+        Ok(Self { offset, length })
+    }
+
+    /// First byte after the range (never overflows for parsed ranges)
+    #[must_use]
+    pub fn end(&self) -> u64 {
+        self.offset + self.length
+    }
+
+    /// `Range` header value
+    #[must_use]
+    pub fn header(&self) -> String {
+        format!("bytes={}-{}", self.offset, self.end() - 1)
+    }
+}
+
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.offset, self.end() - 1)
+    }
+}
+
+/// `Content-Range: bytes <first>-<last>/<complete length or *>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    pub first: u64,
+    /// Inclusive
+    pub last: u64,
+    pub complete_length: Option<u64>,
+}
+
+impl FromStr for ContentRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = || -> Option<Self> {
+            let (range, complete) = s.trim().strip_prefix("bytes ")?.split_once('/')?;
+            let (first, last) = range.split_once('-')?;
+            let (first, last) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+            let complete_length = match complete.trim() {
+                "*" => None,
+                length => Some(length.parse().ok()?),
+            };
+            (first <= last).then_some(Self {
+                first,
+                last,
+                complete_length,
+            })
+        };
+        parse().with_context(|| format!("Invalid Content-Range '{s}'"))
+    }
+}
+
+/// The bytes of `range` from a response to a request for it
+pub fn slice(
+    range: &ByteRange,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Vec<u8>> {
+    match status {
+        StatusCode::PARTIAL_CONTENT => {
+            let content_type = headers
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default();
+            if content_type
+                .to_ascii_lowercase()
+                .starts_with("multipart/byteranges")
+            {
+                let boundary = boundary(content_type)
+                    .context("multipart/byteranges response without a boundary")?;
+                return multipart_slice(range, boundary, body);
+            }
+            let content_range: ContentRange = headers
+                .get(CONTENT_RANGE)
+                .context("206 response without Content-Range")?
+                .to_str()?
+                .parse()?;
+            covered(range, &content_range, body)
+        }
+        StatusCode::OK => {
+            // Range ignored: the whole file
+            let end = usize::try_from(range.end())?;
+            if body.len() < end {
+                bail!(
+                    "Server ignored the Range header and sent {} bytes, short of {range}",
+                    body.len()
+                );
+            }
+            Ok(body[usize::try_from(range.offset)?..end].to_vec())
+        }
+        status => bail!("Range {range} request failed: {status}"),
+    }
+}
+
+/// `range` out of `body`, the bytes `content_range` says it holds
+fn covered(range: &ByteRange, content_range: &ContentRange, body: &[u8]) -> Result<Vec<u8>> {
+    if content_range.first > range.offset || content_range.last < range.end() - 1 {
+        bail!(
+            "Content-Range {}-{} doesn't cover the requested {range}",
+            content_range.first,
+            content_range.last
+        );
+    }
+    if body.len() as u64 != content_range.last - content_range.first + 1 {
+        bail!(
+            "Content-Range {}-{} announces {} bytes, got {}",
+            content_range.first,
+            content_range.last,
+            content_range.last - content_range.first + 1,
+            body.len()
+        );
+    }
+    let start = usize::try_from(range.offset - content_range.first)?;
+    Ok(body[start..start + usize::try_from(range.length)?].to_vec())
+}
+
+fn boundary(content_type: &str) -> Option<&str> {
+    content_type.split(';').find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// The part of a `multipart/byteranges` body that covers `range`
+fn multipart_slice(range: &ByteRange, boundary: &str, body: &[u8]) -> Result<Vec<u8>> {
+    let delimiter = format!("--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    let mut pos = find(body, delimiter, 0).context("multipart body without parts")?;
+    loop {
+        let start = pos + delimiter.len();
+        // `--boundary--` closes the body
+        if body[start..].starts_with(b"--") {
+            break;
+        }
+        let next = find(body, delimiter, start).context("Unterminated multipart body")?;
+        let part = &body[start..next];
+        let head_end = find(part, b"\r\n\r\n", 0).context("multipart part without headers")?;
+        let head = String::from_utf8_lossy(&part[..head_end]);
+        let content_range = head.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("content-range")
+                .then(|| value.trim().parse::<ContentRange>())
+        });
+        // The CRLF before the next delimiter belongs to it
+        let data = &part[head_end + 4..];
+        let data = data.strip_suffix(b"\r\n").unwrap_or(data);
+        if let Some(content_range) = content_range.transpose()? {
+            if content_range.first <= range.offset && content_range.last >= range.end() - 1 {
+                return covered(range, &content_range, data);
+            }
+        }
+        pos = next;
+    }
+    bail!("No part of the multipart response covers {range}")
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|i| from + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    const FILE: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_parse_hls() {
+        let first = ByteRange::parse_hls("10@5", None).unwrap();
+        assert_eq!(first.header(), "bytes=5-14");
+        let next = ByteRange::parse_hls("4", Some(&first)).unwrap();
+        assert_eq!((next.offset, next.length), (15, 4));
+        assert!(ByteRange::parse_hls("4", None).is_err());
+        assert!(ByteRange::parse_hls("0@3", None).is_err());
+        // Ranges running past u64::MAX are rejected, not wrapped
+        let last = ByteRange::parse_hls(&format!("2@{}", u64::MAX - 2), None).unwrap();
+        assert_eq!(last.end(), u64::MAX);
+        assert!(ByteRange::parse_hls(&format!("10@{}", u64::MAX - 5), None).is_err());
+        assert!(ByteRange::parse_hls("1", Some(&last)).is_err());
+
+        let content_range: ContentRange = "bytes 0-99/*".parse().unwrap();
+        assert_eq!(content_range.complete_length, None);
+        assert!("bytes 9-3/10".parse::<ContentRange>().is_err());
+        assert!("items 0-1/2".parse::<ContentRange>().is_err());
+    }
+
+    #[test]
+    fn test_slice() {
+        let range = ByteRange {
+            offset: 10,
+            length: 6,
+        };
+        let exact = headers(&[("content-range", "bytes 10-15/36")]);
+        let body = slice(&range, StatusCode::PARTIAL_CONTENT, &exact, &FILE[10..16]).unwrap();
+        assert_eq!(body, b"abcdef");
+
+        // Wider range than asked for, and one that misses the start
+        let wider = headers(&[("content-range", "bytes 8-19/36")]);
+        let body = slice(&range, StatusCode::PARTIAL_CONTENT, &wider, &FILE[8..20]).unwrap();
+        assert_eq!(body, b"abcdef");
+        let late = headers(&[("content-range", "bytes 12-15/36")]);
+        assert!(slice(&range, StatusCode::PARTIAL_CONTENT, &late, &FILE[12..16]).is_err());
+        // Truncated body
+        assert!(slice(&range, StatusCode::PARTIAL_CONTENT, &exact, &FILE[10..14]).is_err());
+        assert!(slice(&range, StatusCode::PARTIAL_CONTENT, &HeaderMap::new(), b"x").is_err());
+
+        // Range ignored
+        let body = slice(&range, StatusCode::OK, &HeaderMap::new(), FILE).unwrap();
+        assert_eq!(body, b"abcdef");
+        assert!(slice(&range, StatusCode::OK, &HeaderMap::new(), &FILE[..12]).is_err());
+        let unsatisfiable = slice(
+            &range,
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            &HeaderMap::new(),
+            b"",
+        );
+        assert!(unsatisfiable.is_err());
+    }
+
+    #[test]
+    fn test_multipart_slice() {
+        let body = b"--XYZ\r\nContent-Type: video/mp2t\r\nContent-Range: bytes 0-3/36\r\n\r\n0123\r\n\
+                     --XYZ\r\nContent-Type: video/mp2t\r\nContent-Range: bytes 10-15/36\r\n\r\nabcdef\r\n\
+                     --XYZ--\r\n";
+        let multipart = headers(&[("content-type", "multipart/byteranges; boundary=\"XYZ\"")]);
+        let range = ByteRange {
+            offset: 11,
+            length: 3,
+        };
+        let data = slice(&range, StatusCode::PARTIAL_CONTENT, &multipart, body).unwrap();
+        assert_eq!(data, b"bcd");
+
+        let missing = ByteRange {
+            offset: 20,
+            length: 2,
+        };
+        let err = slice(&missing, StatusCode::PARTIAL_CONTENT, &multipart, body).unwrap_err();
+        assert!(err.to_string().contains("No part"), "{err}");
+    }
+}
@@ -0,0 +1,275 @@
+//! Track selection for multi-audio muxing
+//!
+//! ffprobe lists every stream of an HLS or DASH manifest: each variant and
+//! each audio rendition or adaptation set. [`select`] picks one video stream
+//! by quality and one audio stream per requested language, and
+//! [`TrackSelection::ffmpeg_args`] maps exactly those, tagged with ISO 639-2
+//! language codes (what Matroska and MPEG-TS players read).
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::process::Command;
+
+use super::StreamQuality;
+
+/// ISO 639-1, 639-2/B, and 639-2/T codes of common languages
+const LANGUAGES: &[(&str, &str, &str)] = &[
+    ("ar", "ara", "ara"),
+    ("cs", "cze", "ces"),
+    ("da", "dan", "dan"),
+    ("de", "ger", "deu"),
+    ("el", "gre", "ell"),
+    ("en", "eng", "eng"),
+    ("es", "spa", "spa"),
+    ("et", "est", "est"),
+    ("fi", "fin", "fin"),
+    ("fr", "fre", "fra"),
+    ("he", "heb", "heb"),
+    ("hi", "hin", "hin"),
+    ("hu", "hun", "hun"),
+    ("it", "ita", "ita"),
+    ("ja", "jpn", "jpn"),
+    ("ko", "kor", "kor"),
+    ("nb", "nob", "nob"),
+    ("nl", "dut", "nld"),
+    ("no", "nor", "nor"),
+    ("pl", "pol", "pol"),
+    ("pt", "por", "por"),
+    ("ru", "rus", "rus"),
+    ("se", "sme", "sme"),
+    ("sv", "swe", "swe"),
+    ("tr", "tur", "tur"),
+    ("uk", "ukr", "ukr"),
+    ("zh", "chi", "zho"),
+];
+
+/// A stream as ffprobe reports it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbedStream {
+    /// Input stream index (`-map 0:<index>`)
+    pub index: u32,
+    /// `video`, `audio`, `subtitle`, ...
+    pub kind: String,
+    /// ffprobe `codec_name` (`h264`, `aac`, ...)
+    pub codec: String,
+    pub height: u32,
+    /// Variant or stream bitrate, 0 if unknown
+    pub bitrate: u64,
+    pub language: Option<String>,
+}
+
+/// Streams to mux
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackSelection {
+    pub video: Option<u32>,
+    /// `(stream index, ISO 639-2 language)`, in the requested order
+    pub audio: Vec<(u32, String)>,
+}
+
+impl TrackSelection {
+    /// `-map`/`-metadata` arguments, placed after the input
+    #[must_use]
+    pub fn ffmpeg_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(index) = self.video {
+            args.push("-map".to_string());
+            args.push(format!("0:{index}"));
+        }
+        for (index, _) in &self.audio {
+            args.push("-map".to_string());
+            args.push(format!("0:{index}"));
+        }
+        for (n, (_, language)) in self.audio.iter().enumerate() {
+            args.push(format!("-metadata:s:a:{n}"));
+            args.push(format!("language={language}"));
+        }
+        args
+    }
+}
+
+/// The streams of `manifest_url`, via ffprobe
+pub async fn probe(
+    ffprobe_path: &str,
+    manifest_url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<Vec<ProbedStream>> {
+    let mut cmd = Command::new(ffprobe_path);
+    cmd.args(["-v", "error", "-show_streams", "-of", "json"]);
+    if !headers.is_empty() {
+        cmd.arg("-headers")
+            .arg(super::backends::ffmpeg::header_arg(headers));
+    }
+    let output = cmd
+        .arg(manifest_url)
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to run {ffprobe_path}: {e}"))?;
+    if !output.status.success() {
+        bail!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_probe(&output.stdout)
+}
+
+fn parse_probe(json: &[u8]) -> Result<Vec<ProbedStream>> {
+    #[derive(Deserialize)]
+    struct Probe {
+        #[serde(default)]
+        streams: Vec<Stream>,
+    }
+    #[derive(Deserialize)]
+    struct Stream {
+        index: u32,
+        #[serde(default)]
+        codec_type: String,
+        #[serde(default)]
+        codec_name: String,
+        height: Option<u32>,
+        bit_rate: Option<String>,
+        #[serde(default)]
+        tags: HashMap<String, String>,
+    }
+
+    let probe: Probe = serde_json::from_slice(json)?;
+    Ok(probe
+        .streams
+        .into_iter()
+        .map(|s| ProbedStream {
+            index: s.index,
+            kind: s.codec_type,
+            codec: s.codec_name,
+            height: s.height.unwrap_or(0),
+            bitrate: s
+                .tags
+                .get("variant_bitrate")
+                .or(s.bit_rate.as_ref())
+                .and_then(|b| b.parse().ok())
+                .unwrap_or(0),
+            language: s.tags.get("language").cloned(),
+        })
+        .collect())
+}
+
+/// One video stream by `quality` (none for audio-only manifests) and the
+/// highest-bitrate audio stream of each language
+pub fn select(
+    streams: &[ProbedStream],
+    quality: StreamQuality,
+    languages: &[String],
+) -> Result<TrackSelection> {
+    let videos = streams.iter().filter(|s| s.kind == "video" && s.height > 0);
+    let key = |s: &&ProbedStream| (s.height, s.bitrate);
+    let video = match quality {
+        StreamQuality::Best | StreamQuality::Auto => videos.max_by_key(key),
+        StreamQuality::Worst => videos.min_by_key(key),
+        StreamQuality::Specific(height) => {
+            videos.min_by_key(|s| (s.height.abs_diff(height), std::cmp::Reverse(s.bitrate)))
+        }
+    };
+
+    let mut audio = Vec::new();
+    for wanted in languages {
+        let wanted = primary(wanted);
+        let stream = streams
+            .iter()
+            .filter(|s| s.kind == "audio")
+            .filter(|s| s.language.as_deref().map(primary).as_ref() == Some(&wanted))
+            .max_by_key(|s| s.bitrate)
+            .ok_or_else(|| {
+                let mut available: Vec<&str> = streams
+                    .iter()
+                    .filter(|s| s.kind == "audio")
+                    .map(|s| s.language.as_deref().unwrap_or("und"))
+                    .collect();
+                available.sort_unstable();
+                available.dedup();
+                anyhow!(
+                    "No {wanted} audio track (available: {})",
+                    available.join(", ")
+                )
+            })?;
+        audio.push((stream.index, iso639_2(&wanted)));
+    }
+
+    Ok(TrackSelection {
+        video: video.map(|s| s.index),
+        audio,
+    })
+}
+
+/// Lowercase primary subtag, as ISO 639-1 when it's a known 639-2 code
+/// (`en-US`, `eng`, and `EN` are all `en`)
+fn primary(tag: &str) -> String {
+    let tag = tag
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(_, b, t)| *b == tag || *t == tag)
+        .map_or(tag, |(code, _, _)| (*code).to_string())
+}
+
+/// ISO 639-2/B code for a primary subtag, or the subtag as is
+fn iso639_2(primary: &str) -> String {
+    LANGUAGES
+        .iter()
+        .find(|(code, _, _)| *code == primary)
+        .map_or_else(|| primary.to_string(), |(_, b, _)| (*b).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_video_and_languages() {
+        let streams = parse_probe(
+            br#"{"streams": [
+                {"index": 0, "codec_type": "video", "height": 720, "tags": {"variant_bitrate": "3000000"}},
+                {"index": 1, "codec_type": "audio", "tags": {"variant_bitrate": "3000000", "language": "en"}},
+                {"index": 2, "codec_type": "video", "height": 1080, "tags": {"variant_bitrate": "6000000"}},
+                {"index": 3, "codec_type": "audio", "bit_rate": "128000", "tags": {"language": "deu"}},
+                {"index": 4, "codec_type": "audio", "bit_rate": "96000", "tags": {"language": "de-DE"}},
+                {"index": 5, "codec_type": "audio", "bit_rate": "64000", "tags": {"language": "fin"}}
+            ]}"#,
+        )
+        .unwrap();
+
+        let languages = ["de".to_string(), "EN".to_string()];
+        let selection = select(&streams, StreamQuality::Best, &languages).unwrap();
+        assert_eq!(selection.video, Some(2));
+        assert_eq!(
+            selection.audio,
+            [(3, "ger".to_string()), (1, "eng".to_string())]
+        );
+        assert_eq!(
+            selection.ffmpeg_args(),
+            [
+                "-map",
+                "0:2",
+                "-map",
+                "0:3",
+                "-map",
+                "0:1",
+                "-metadata:s:a:0",
+                "language=ger",
+                "-metadata:s:a:1",
+                "language=eng"
+            ]
+        );
+
+        let selection = select(&streams, StreamQuality::Specific(700), &[]).unwrap();
+        assert_eq!(selection.video, Some(0));
+
+        let err = select(&streams, StreamQuality::Best, &["sv".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No sv audio track (available: de-DE, deu, en, fin)"
+        );
+    }
+}
@@ -0,0 +1,438 @@
+//! Browser-like Subresource Loads
+//!
+//! Real browsers fetch a page's favicon and stylesheets right after the HTML,
+//! and some anti-bot systems score sessions that never do. `--warm-resources`
+//! loads the first few subresources of the kinds asked for, with the
+//! `Accept`, `Referer`, and `Sec-Fetch-*` headers a browser would send.
+//! The `preload` kind adds the style, script, and image preloads the page
+//! hints at (`Link: rel=preload` headers and `<link rel="preload">`), which
+//! browsers fetch before anything else.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
+
+use anyhow::Result;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, COOKIE, REFERER};
+use scraper::{Html, Selector};
+use serde::Serialize;
+use url::Url;
+
+use crate::har::HarEntry;
+use crate::navigation::{fetch_site, referer};
+use crate::prefetch::EarlyHintLink;
+
+/// Kinds loaded by a bare `--warm-resources`
+pub const DEFAULT_KINDS: &str = "favicon,css";
+
+/// Kind of subresource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceKind {
+    Favicon,
+    Css,
+    Js,
+    Img,
+}
+
+impl ResourceKind {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Favicon => "favicon",
+            Self::Css => "css",
+            Self::Js => "js",
+            Self::Img => "img",
+        }
+    }
+
+    /// Resource type as named in browser HAR exports
+    #[must_use]
+    pub fn har_type(self) -> &'static str {
+        match self {
+            Self::Favicon | Self::Img => "image",
+            Self::Css => "stylesheet",
+            Self::Js => "script",
+        }
+    }
+
+    /// Kind of a preload's `as` destination; fonts and others aren't loaded
+    fn from_destination(destination: &str) -> Option<Self> {
+        match destination.to_ascii_lowercase().as_str() {
+            "style" => Some(Self::Css),
+            "script" => Some(Self::Js),
+            "image" => Some(Self::Img),
+            _ => None,
+        }
+    }
+
+    /// `Accept` sent by Chromium for this kind
+    fn accept(self) -> &'static str {
+        match self {
+            Self::Favicon | Self::Img => {
+                "image/avif,image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8"
+            }
+            Self::Css => "text/css,*/*;q=0.1",
+            Self::Js => "*/*",
+        }
+    }
+
+    fn fetch_dest(self) -> &'static str {
+        match self {
+            Self::Favicon | Self::Img => "image",
+            Self::Css => "style",
+            Self::Js => "script",
+        }
+    }
+
+    /// Selector and URL attribute of elements referencing this kind
+    fn selector(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Favicon => ("link[rel~='icon'][href]", "href"),
+            Self::Css => ("link[rel~='stylesheet'][href]", "href"),
+            Self::Js => ("script[src]", "src"),
+            Self::Img => ("img[src]", "src"),
+        }
+    }
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ResourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "favicon" | "icon" => Ok(Self::Favicon),
+            "css" | "stylesheet" => Ok(Self::Css),
+            "js" | "script" => Ok(Self::Js),
+            "img" | "image" => Ok(Self::Img),
+            other => anyhow::bail!(
+                "Unknown resource kind '{other}' (use favicon, css, js, img, preload)"
+            ),
+        }
+    }
+}
+
+/// Which subresources to load, and how many of each kind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WarmPlan {
+    pub kinds: Vec<ResourceKind>,
+    pub per_kind: usize,
+    /// Also load every hinted preload, ahead of the kinds
+    pub preload: bool,
+}
+
+impl WarmPlan {
+    /// Parse a comma-separated kind list, e.g. `favicon,css` or `preload,css`
+    pub fn parse(kinds: &str, per_kind: usize) -> Result<Self> {
+        let mut parsed: Vec<ResourceKind> = Vec::new();
+        let mut preload = false;
+        for kind in kinds.split(',').filter(|k| !k.trim().is_empty()) {
+            if kind.trim().eq_ignore_ascii_case("preload") {
+                preload = true;
+                continue;
+            }
+            let kind = kind.parse()?;
+            if !parsed.contains(&kind) {
+                parsed.push(kind);
+            }
+        }
+        if parsed.is_empty() && !preload {
+            anyhow::bail!("No resource kinds given (use favicon, css, js, img, preload)");
+        }
+        if per_kind == 0 {
+            anyhow::bail!("Load at least one resource of each kind");
+        }
+        Ok(Self {
+            kinds: parsed,
+            per_kind,
+            preload,
+        })
+    }
+
+    /// Subresource URLs of `html` (served from `base`), in plan order
+    #[must_use]
+    pub fn select(&self, html: &str, base: &Url) -> Vec<(ResourceKind, Url)> {
+        self.select_hinted(html, base, &[])
+    }
+
+    /// Like [`Self::select`], with the preloads among `hints` first when the
+    /// plan includes them
+    #[must_use]
+    pub fn select_hinted(
+        &self,
+        html: &str,
+        base: &Url,
+        hints: &[EarlyHintLink],
+    ) -> Vec<(ResourceKind, Url)> {
+        let mut selected: Vec<(ResourceKind, Url)> = Vec::new();
+        if self.preload {
+            for hint in hints {
+                let rels: Vec<&str> = hint.rel.split_whitespace().collect();
+                let kind = if rels.iter().any(|r| r.eq_ignore_ascii_case("modulepreload")) {
+                    Some(ResourceKind::Js)
+                } else if rels.iter().any(|r| r.eq_ignore_ascii_case("preload")) {
+                    hint.as_type
+                        .as_deref()
+                        .and_then(ResourceKind::from_destination)
+                } else {
+                    None
+                };
+                let url = base
+                    .join(hint.url.trim())
+                    .ok()
+                    .filter(|url| matches!(url.scheme(), "http" | "https"));
+                if let (Some(kind), Some(url)) = (kind, url) {
+                    if !selected.iter().any(|(_, u)| *u == url) {
+                        selected.push((kind, url));
+                    }
+                }
+            }
+        }
+        let preloaded = selected.len();
+
+        let document = Html::parse_document(html);
+        for &kind in &self.kinds {
+            let (selector, attr) = kind.selector();
+            let Ok(selector) = Selector::parse(selector) else {
+                continue;
+            };
+            let mut urls: Vec<Url> = document
+                .select(&selector)
+                .filter_map(|el| el.value().attr(attr))
+                .filter_map(|href| base.join(href.trim()).ok())
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+                .collect();
+            // Browsers ask for /favicon.ico when the page declares no icon
+            if kind == ResourceKind::Favicon && urls.is_empty() {
+                urls.extend(base.join("/favicon.ico").ok());
+            }
+            for url in urls {
+                let count = selected[preloaded..]
+                    .iter()
+                    .filter(|(k, _)| *k == kind)
+                    .count();
+                if count == self.per_kind {
+                    break;
+                }
+                if !selected.iter().any(|(_, u)| *u == url) {
+                    selected.push((kind, url));
+                }
+            }
+        }
+        selected
+    }
+}
+
+/// Outcome of one subresource load
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadedResource {
+    pub kind: ResourceKind,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// HAR entry, when a response arrived
+    #[serde(skip)]
+    pub har: Option<HarEntry>,
+}
+
+/// Load `resources` of the page at `page` one after another, like a browser
+///
+/// `cookies` (the page request's `Cookie` header) go to same-host resources
+/// only; the client's cookie store covers the rest.
+pub async fn load(
+    client: &reqwest::Client,
+    page: &Url,
+    resources: &[(ResourceKind, Url)],
+    cookies: &str,
+) -> Vec<LoadedResource> {
+    let mut loaded = Vec::with_capacity(resources.len());
+    for (kind, url) in resources {
+        let mut headers = request_headers(*kind, page, url);
+        if !cookies.is_empty() && url.host_str() == page.host_str() {
+            if let Ok(value) = HeaderValue::from_str(cookies) {
+                headers.insert(COOKIE, value);
+            }
+        }
+        let outcome = load_one(client, *kind, url, headers).await;
+        loaded.push(outcome.unwrap_or_else(|e| LoadedResource {
+            kind: *kind,
+            url: url.to_string(),
+            status: None,
+            size: 0,
+            error: Some(format!("{e:#}")),
+            har: None,
+        }));
+    }
+    loaded
+}
+
+async fn load_one(
+    client: &reqwest::Client,
+    kind: ResourceKind,
+    url: &Url,
+    headers: HeaderMap,
+) -> Result<LoadedResource> {
+    crate::policy::check_url(url)?;
+    let request = client.get(url.clone()).headers(headers).build()?;
+    let har_request = request.try_clone();
+    let started = chrono::Utc::now();
+    let start = Instant::now();
+    let response = client.execute(request).await?;
+    let wait = start.elapsed();
+    let status = response.status().as_u16();
+    let entry = har_request.map(|r| HarEntry::new(&r, &response, started, wait, kind.har_type()));
+
+    let receive_start = Instant::now();
+    let size = response.bytes().await?.len();
+    Ok(LoadedResource {
+        kind,
+        url: url.to_string(),
+        status: Some(status),
+        size,
+        error: None,
+        har: entry.map(|e| e.with_body(size, receive_start.elapsed())),
+    })
+}
+
+/// Headers a browser sends for a `kind` subresource of `page`
+fn request_headers(kind: ResourceKind, page: &Url, url: &Url) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static(kind.accept()));
+    if let Some(value) = referer(page, url).and_then(|r| HeaderValue::from_str(&r).ok()) {
+        headers.insert(REFERER, value);
+    }
+    headers.insert(
+        "Sec-Fetch-Dest",
+        HeaderValue::from_static(kind.fetch_dest()),
+    );
+    headers.insert("Sec-Fetch-Mode", HeaderValue::from_static("no-cors"));
+    headers.insert(
+        "Sec-Fetch-Site",
+        HeaderValue::from_static(fetch_site(page, url)),
+    );
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><head>
+        <link rel="stylesheet" href="/a.css">
+        <link rel="shortcut icon" href="/img/icon.png">
+        <link rel="stylesheet" href="https://cdn.example.net/b.css">
+        <script src="app.js"></script>
+        </head><body><img src="data:image/png;base64,AAAA"><img src="/hero.jpg"></body></html>"#;
+
+    #[test]
+    fn test_select() {
+        let base = Url::parse("https://www.example.com/news/").unwrap();
+        let plan = WarmPlan::parse(DEFAULT_KINDS, 1).unwrap();
+        let selected: Vec<String> = plan
+            .select(PAGE, &base)
+            .into_iter()
+            .map(|(kind, url)| format!("{kind} {url}"))
+            .collect();
+        assert_eq!(
+            selected,
+            [
+                "favicon https://www.example.com/img/icon.png",
+                "css https://www.example.com/a.css"
+            ]
+        );
+
+        let plan = WarmPlan::parse("css,js,img", 2).unwrap();
+        let urls: Vec<String> = plan
+            .select(PAGE, &base)
+            .into_iter()
+            .map(|(_, url)| url.to_string())
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                "https://www.example.com/a.css",
+                "https://cdn.example.net/b.css",
+                "https://www.example.com/news/app.js",
+                "https://www.example.com/hero.jpg",
+            ]
+        );
+
+        // No declared icon: /favicon.ico, like a browser
+        let plan = WarmPlan::parse("favicon", 1).unwrap();
+        let selected = plan.select("<html></html>", &base);
+        assert_eq!(
+            selected[0].1.as_str(),
+            "https://www.example.com/favicon.ico"
+        );
+
+        // Preloads come first and don't count against --warm-count
+        let hints = crate::prefetch::EarlyHints::parse(&[
+            "</a.css>; rel=preload; as=style",
+            "</font.woff2>; rel=preload; as=font",
+            "</mod.js>; rel=modulepreload",
+            "<https://cdn.example.net>; rel=preconnect",
+        ]);
+        let plan = WarmPlan::parse("preload,css", 1).unwrap();
+        let urls: Vec<String> = plan
+            .select_hinted(PAGE, &base, &hints.links)
+            .into_iter()
+            .map(|(kind, url)| format!("{kind} {url}"))
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                "css https://www.example.com/a.css",
+                "js https://www.example.com/mod.js",
+                "css https://cdn.example.net/b.css",
+            ]
+        );
+        assert!(WarmPlan::parse(DEFAULT_KINDS, 1)
+            .unwrap()
+            .select_hinted("", &base, &hints.links)
+            .iter()
+            .all(|(kind, _)| *kind == ResourceKind::Favicon));
+        assert!(WarmPlan::parse("preload", 1).unwrap().kinds.is_empty());
+
+        assert!(WarmPlan::parse("fonts", 1).is_err());
+        assert!(WarmPlan::parse(",", 1).is_err());
+        assert!(WarmPlan::parse("css", 0).is_err());
+    }
+
+    #[test]
+    fn test_request_headers() {
+        let page = Url::parse("https://www.example.com/news/story?id=1").unwrap();
+        let same = request_headers(
+            ResourceKind::Css,
+            &page,
+            &Url::parse("https://www.example.com/a.css").unwrap(),
+        );
+        assert_eq!(same["Sec-Fetch-Site"], "same-origin");
+        assert_eq!(same["Sec-Fetch-Dest"], "style");
+        assert_eq!(same[REFERER], page.as_str());
+
+        let site = request_headers(
+            ResourceKind::Img,
+            &page,
+            &Url::parse("https://static.example.com/i.png").unwrap(),
+        );
+        assert_eq!(site["Sec-Fetch-Site"], "same-site");
+        assert_eq!(site[REFERER], "https://www.example.com/");
+
+        let cross = request_headers(
+            ResourceKind::Js,
+            &page,
+            &Url::parse("https://cdn.example.net/app.js").unwrap(),
+        );
+        assert_eq!(cross["Sec-Fetch-Site"], "cross-site");
+        assert_eq!(cross[ACCEPT], "*/*");
+    }
+}
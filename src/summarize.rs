@@ -0,0 +1,295 @@
+//! Summarization Hook
+//!
+//! Sends extracted Markdown to a user-configured summarizer:
+//! - External command: prompt on stdin, summary on stdout (`llm`, `ollama run`, ...)
+//! - OpenAI-compatible `/chat/completions` endpoint
+//!
+//! Long documents are split on paragraph boundaries, summarized per chunk,
+//! and the partial summaries merged with a second prompt.
+
+use anyhow::{Context, Result};
+use tokio::io::AsyncWriteExt;
+
+use crate::config::SummarizeConfig;
+
+/// Where prompts are sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SummarizeBackend {
+    /// Shell command reading the prompt on stdin
+    Command(String),
+    /// OpenAI-compatible chat completions API
+    Endpoint {
+        url: String,
+        model: String,
+        api_key: Option<String>,
+    },
+}
+
+impl SummarizeBackend {
+    /// Pick the backend from config (a command wins over an endpoint)
+    pub fn from_config(config: &SummarizeConfig) -> Result<Self> {
+        if let Some(command) = &config.command {
+            return Ok(Self::Command(command.clone()));
+        }
+        if let Some(endpoint) = &config.endpoint {
+            let base = endpoint.trim_end_matches('/');
+            let url = if base.ends_with("/chat/completions") {
+                base.to_string()
+            } else {
+                format!("{base}/chat/completions")
+            };
+            return Ok(Self::Endpoint {
+                url,
+                model: config.model.clone(),
+                api_key: std::env::var(&config.api_key_env).ok(),
+            });
+        }
+        anyhow::bail!(
+            "No summarizer configured. Set summarize.command or summarize.endpoint in {}",
+            crate::config::NabConfig::path().display()
+        )
+    }
+
+    /// Send one prompt and return the model's reply
+    pub async fn complete(&self, prompt: &str) -> Result<String> {
+        match self {
+            Self::Command(command) => run_command(command, prompt).await,
+            Self::Endpoint {
+                url,
+                model,
+                api_key,
+            } => call_endpoint(url, model, api_key.as_deref(), prompt).await,
+        }
+    }
+}
+
+/// Summarize Markdown from `url`, chunking long documents
+pub async fn summarize(markdown: &str, url: &str, config: &SummarizeConfig) -> Result<String> {
+    let backend = SummarizeBackend::from_config(config)?;
+    let chunks = chunk_markdown(markdown, config.chunk_chars);
+
+    let mut partials = Vec::with_capacity(chunks.len());
+    for chunk in &chunks {
+        let reply = backend
+            .complete(&render(&config.prompt, chunk, url))
+            .await?;
+        partials.push(reply.trim().to_string());
+    }
+
+    if partials.len() == 1 {
+        return Ok(partials.remove(0));
+    }
+    let merged = partials.join("\n\n");
+    let reply = backend
+        .complete(&render(&config.combine_prompt, &merged, url))
+        .await?;
+    Ok(reply.trim().to_string())
+}
+
+/// Fill a prompt template
+#[must_use]
+pub fn render(template: &str, content: &str, url: &str) -> String {
+    let prompt = template.replace("{url}", url);
+    if prompt.contains("{content}") {
+        prompt.replace("{content}", content)
+    } else {
+        format!("{prompt}\n\n{content}")
+    }
+}
+
+/// Split Markdown into chunks of at most `max_chars`, preferring paragraph breaks
+#[must_use]
+pub fn chunk_markdown(markdown: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for paragraph in markdown.split("\n\n").filter(|p| !p.trim().is_empty()) {
+        for piece in split_oversized(paragraph, max_chars) {
+            if !current.is_empty() && current.len() + piece.len() + 2 > max_chars {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.push_str(&piece);
+        }
+    }
+    if !current.is_empty() || chunks.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Break a paragraph longer than `max_chars` at line breaks, then at char boundaries
+fn split_oversized(paragraph: &str, max_chars: usize) -> Vec<String> {
+    if paragraph.len() <= max_chars {
+        return vec![paragraph.to_string()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    for line in paragraph.lines() {
+        if !piece.is_empty() && piece.len() + line.len() + 1 > max_chars {
+            pieces.push(std::mem::take(&mut piece));
+        }
+        if line.len() > max_chars {
+            for c in line.chars() {
+                if piece.len() + c.len_utf8() > max_chars {
+                    pieces.push(std::mem::take(&mut piece));
+                }
+                piece.push(c);
+            }
+            continue;
+        }
+        if !piece.is_empty() {
+            piece.push('\n');
+        }
+        piece.push_str(line);
+    }
+    if !piece.is_empty() {
+        pieces.push(piece);
+    }
+    pieces
+}
+
+async fn run_command(command: &str, prompt: &str) -> Result<String> {
+    let mut child = tokio::process::Command::new(if cfg!(windows) { "cmd" } else { "sh" })
+        .arg(if cfg!(windows) { "/C" } else { "-c" })
+        .arg(command)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start summarizer: {command}"))?;
+
+    // Write the prompt while reading the output: a command that prints
+    // before it has read everything would otherwise block on a full pipe
+    let mut stdin = child.stdin.take().context("Summarizer stdin unavailable")?;
+    let input = prompt.as_bytes().to_vec();
+    let writer = tokio::spawn(async move { stdin.write_all(&input).await });
+    let output = child.wait_with_output().await?;
+    // A command that stops reading early is judged by its exit status
+    let _ = writer.await;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Summarizer '{command}' failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn call_endpoint(
+    url: &str,
+    model: &str,
+    api_key: Option<&str>,
+    prompt: &str,
+) -> Result<String> {
+    let client = crate::policy::client_builder().build()?;
+    let mut request = client.post(url).json(&serde_json::json!({
+        "model": model,
+        "messages": [{"role": "user", "content": prompt}],
+        "temperature": 0.2,
+    }));
+    if let Some(key) = api_key {
+        request = request.bearer_auth(key);
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await?;
+    if !status.is_success() {
+        anyhow::bail!("Summarizer endpoint returned {status}: {body}");
+    }
+    body["choices"][0]["message"]["content"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow::anyhow!("Summarizer endpoint returned no message content"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_markdown() {
+        let md = "# Title\n\nFirst paragraph.\n\nSecond paragraph.\n\nThird paragraph.";
+        assert_eq!(chunk_markdown(md, 1000), vec![md]);
+
+        let chunks = chunk_markdown(md, 40);
+        assert_eq!(
+            chunks,
+            vec![
+                "# Title\n\nFirst paragraph.",
+                "Second paragraph.\n\nThird paragraph."
+            ]
+        );
+
+        // Single-newline Markdown (as produced by fetch) splits at line breaks
+        let lines = "line one\nline two\nline three";
+        assert_eq!(
+            chunk_markdown(lines, 18),
+            vec!["line one\nline two", "line three"]
+        );
+
+        let long = "ä".repeat(25);
+        let chunks = chunk_markdown(&long, 20);
+        assert!(chunks.iter().all(|c| c.len() <= 20));
+        assert_eq!(chunks.concat(), long);
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("From {url}:\n{content}", "body", "https://a.example"),
+            "From https://a.example:\nbody"
+        );
+        assert_eq!(render("Summarize.", "body", ""), "Summarize.\n\nbody");
+    }
+
+    #[test]
+    fn test_backend_selection() {
+        let config = SummarizeConfig {
+            endpoint: Some("http://localhost:11434/v1/".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            SummarizeBackend::from_config(&config).unwrap(),
+            SummarizeBackend::Endpoint { url, .. } if url == "http://localhost:11434/v1/chat/completions"
+        ));
+        assert!(SummarizeBackend::from_config(&SummarizeConfig::default()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_with_large_prompt() {
+        let prompt = "x".repeat(4 << 20);
+        let summary = tokio::time::timeout(
+            std::time::Duration::from_secs(30),
+            run_command("cat", &prompt),
+        )
+        .await
+        .expect("summarizer deadlocked")
+        .unwrap();
+        assert_eq!(summary.len(), prompt.len());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_backend_with_chunking() {
+        let config = SummarizeConfig {
+            command: Some("head -c 12".into()),
+            prompt: "S:{content}".into(),
+            combine_prompt: "C:{content}".into(),
+            chunk_chars: 10,
+            ..Default::default()
+        };
+        let summary = summarize("alpha beta\n\ngamma delta", "", &config)
+            .await
+            .unwrap();
+        // Each chunk is summarized, then the partial summaries are merged
+        assert_eq!(summary, "C:S:alpha be");
+    }
+}
@@ -0,0 +1,347 @@
+//! Request Timing Breakdown
+//!
+//! Per-phase timings of a fetch, in milliseconds:
+//! - `dns`, `connect`, `tls`: measured on a probe connection opened just
+//!   before the request (reqwest doesn't expose its own connection phases)
+//! - `tls_handshake`: whether the probe resumed a TLS session, did a full
+//!   handshake, or needed a `HelloRetryRequest` (see [`crate::tls_session`])
+//! - `ttfb`: request sent → response headers (after any redirects)
+//! - `download`: reading the response body
+//! - `parse`: HTML → Markdown conversion
+//! - `redirects`: each hop with its status and time since the previous hop
+
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio::net::TcpStream;
+
+/// Give up on probe phases after this long
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+static TLS_CONFIG: LazyLock<Arc<rustls::ClientConfig>> = LazyLock::new(|| {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs().certs {
+        let _ = roots.add(cert);
+    }
+    let mut config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Arc::new(config)
+});
+
+/// Connection setup phases of a fresh connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ConnectionTimings {
+    pub dns_ms: f64,
+    pub connect_ms: f64,
+    /// `None` for plain HTTP
+    pub tls_ms: Option<f64>,
+    pub tls_handshake: Option<TlsHandshake>,
+}
+
+/// Kind of TLS handshake a connection made
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsHandshake {
+    Full,
+    /// Full, after the server asked for another key exchange group
+    HelloRetry,
+    Resumed,
+}
+
+impl From<rustls::HandshakeKind> for TlsHandshake {
+    fn from(kind: rustls::HandshakeKind) -> Self {
+        match kind {
+            rustls::HandshakeKind::Full => Self::Full,
+            rustls::HandshakeKind::FullWithHelloRetryRequest => Self::HelloRetry,
+            rustls::HandshakeKind::Resumed => Self::Resumed,
+        }
+    }
+}
+
+/// One followed redirect
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RedirectHop {
+    pub from: String,
+    pub to: String,
+    pub status: u16,
+    /// Time since the request started or the previous hop
+    pub ms: f64,
+}
+
+/// Full timing breakdown of one fetch
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Timings {
+    /// `None` when the probe connection failed (proxy, firewall, ...)
+    pub dns_ms: Option<f64>,
+    pub connect_ms: Option<f64>,
+    pub tls_ms: Option<f64>,
+    pub tls_handshake: Option<TlsHandshake>,
+    pub ttfb_ms: f64,
+    pub download_ms: f64,
+    /// `None` for non-HTML responses
+    pub parse_ms: Option<f64>,
+    /// `ttfb + download + parse`
+    pub total_ms: f64,
+    pub redirects: Vec<RedirectHop>,
+}
+
+impl Timings {
+    /// Assemble the breakdown from measured phases
+    #[must_use]
+    pub fn new(
+        connection: Option<ConnectionTimings>,
+        ttfb: Duration,
+        download: Duration,
+        parse: Option<Duration>,
+        redirects: Vec<RedirectHop>,
+    ) -> Self {
+        Self {
+            dns_ms: connection.map(|c| c.dns_ms),
+            connect_ms: connection.map(|c| c.connect_ms),
+            tls_ms: connection.and_then(|c| c.tls_ms),
+            tls_handshake: connection.and_then(|c| c.tls_handshake),
+            ttfb_ms: ms(ttfb),
+            download_ms: ms(download),
+            parse_ms: parse.map(ms),
+            total_ms: ms(ttfb + download + parse.unwrap_or_default()),
+            redirects,
+        }
+    }
+
+    /// Record the time spent converting the body (after [`Self::new`])
+    pub fn add_parse(&mut self, parse: Duration) {
+        let parse_ms = ms(parse);
+        self.parse_ms = Some(parse_ms);
+        self.total_ms = ((self.ttfb_ms + self.download_ms + parse_ms) * 100.0).round() / 100.0;
+    }
+}
+
+/// Time DNS, TCP connect, and TLS handshake on a throwaway connection to `url`'s host
+///
+/// Pass the clients' rustls config (see [`crate::tls::TlsOptions::rustls_config`])
+/// so the probe resumes their sessions and the request resumes the probe's.
+/// The probe always connects directly: don't use it when requests go through
+/// a proxy (see [`crate::ClientOptions::is_proxied`]).
+pub async fn probe_connection(
+    url: &url::Url,
+    tls_config: Option<Arc<rustls::ClientConfig>>,
+) -> Result<ConnectionTimings> {
+    let host = match url.host().context("URL has no host")? {
+        url::Host::Domain(d) => d.to_string(),
+        url::Host::Ipv4(ip) => ip.to_string(),
+        url::Host::Ipv6(ip) => ip.to_string(),
+    };
+    let port = url.port_or_known_default().context("URL has no port")?;
+    crate::policy::check_host(&host)?;
+
+    let start = Instant::now();
+    let addr = tokio::time::timeout(PROBE_TIMEOUT, crate::hosts::lookup_host(&host, port))
+        .await
+        .context("DNS lookup timed out")??
+        .into_iter()
+        .next()
+        .with_context(|| format!("No addresses for {host}"))?;
+    let dns = start.elapsed();
+
+    let start = Instant::now();
+    let stream = tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .context("TCP connect timed out")??;
+    let connect = start.elapsed();
+
+    let (tls_ms, tls_handshake) = if url.scheme() == "https" {
+        let server_name = rustls::pki_types::ServerName::try_from(host)?;
+        let config = tls_config.unwrap_or_else(|| Arc::clone(&TLS_CONFIG));
+        let connector = tokio_rustls::TlsConnector::from(config);
+        let start = Instant::now();
+        let stream = tokio::time::timeout(PROBE_TIMEOUT, connector.connect(server_name, stream))
+            .await
+            .context("TLS handshake timed out")??;
+        let kind = stream.get_ref().1.handshake_kind().map(TlsHandshake::from);
+        (Some(ms(start.elapsed())), kind)
+    } else {
+        (None, None)
+    };
+
+    Ok(ConnectionTimings {
+        dns_ms: ms(dns),
+        connect_ms: ms(connect),
+        tls_ms,
+        tls_handshake,
+    })
+}
+
+/// Shared record of redirect hops, filled in by a reqwest redirect policy
+#[derive(Debug, Clone)]
+pub struct RedirectLog {
+    state: Arc<Mutex<RedirectState>>,
+}
+
+#[derive(Debug)]
+struct RedirectState {
+    last: Instant,
+    hops: Vec<RedirectHop>,
+}
+
+impl Default for RedirectLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RedirectLog {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(RedirectState {
+                last: Instant::now(),
+                hops: Vec::new(),
+            })),
+        }
+    }
+
+    /// Reset the log at the start of a request
+    pub fn start(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.last = Instant::now();
+        state.hops.clear();
+    }
+
+    /// Hops recorded since the last [`Self::start`]
+    #[must_use]
+    pub fn hops(&self) -> Vec<RedirectHop> {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .hops
+            .clone()
+    }
+
+    /// Redirect policy that follows up to `max` hops and records each one
+    #[must_use]
+    pub fn policy(&self, max: usize) -> reqwest::redirect::Policy {
+        let state = Arc::clone(&self.state);
+        reqwest::redirect::Policy::custom(move |attempt| {
+            {
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                let hop = RedirectHop {
+                    from: attempt
+                        .previous()
+                        .last()
+                        .map(ToString::to_string)
+                        .unwrap_or_default(),
+                    to: attempt.url().to_string(),
+                    status: attempt.status().as_u16(),
+                    ms: ms(now - state.last),
+                };
+                state.last = now;
+                state.hops.push(hop);
+            }
+            crate::policy::follow(attempt, max)
+        })
+    }
+}
+
+/// Duration in milliseconds, rounded to 0.01
+#[must_use]
+pub fn ms(d: Duration) -> f64 {
+    (d.as_secs_f64() * 100_000.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Plain HTTP/1.1 server: /a → 302 /b → 301 /c → 200
+    async fn redirect_server() -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let head = match path {
+                    "/a" => "302 Found\r\nLocation: /b".to_string(),
+                    "/b" => "301 Moved Permanently\r\nLocation: /c".to_string(),
+                    _ => "200 OK".to_string(),
+                };
+                let response =
+                    format!("HTTP/1.1 {head}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_redirect_log_records_hops() {
+        let addr = redirect_server().await;
+        let log = RedirectLog::new();
+        let client = reqwest::Client::builder()
+            .redirect(log.policy(10))
+            .build()
+            .unwrap();
+
+        log.start();
+        let response = client.get(format!("http://{addr}/a")).send().await.unwrap();
+        assert_eq!(response.url().path(), "/c");
+
+        let hops = log.hops();
+        assert_eq!(hops.len(), 2);
+        assert_eq!(hops[0].status, 302);
+        assert_eq!(hops[0].from, format!("http://{addr}/a"));
+        assert_eq!(hops[0].to, format!("http://{addr}/b"));
+        assert_eq!(hops[1].status, 301);
+
+        log.start();
+        assert!(log.hops().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_log_limit() {
+        let addr = redirect_server().await;
+        let fetch = |max: usize| {
+            let client = reqwest::Client::builder()
+                .redirect(RedirectLog::new().policy(max))
+                .build()
+                .unwrap();
+            async move { client.get(format!("http://{addr}/a")).send().await }
+        };
+        // /a takes two redirects
+        assert!(fetch(2).await.is_ok());
+        assert!(fetch(1).await.unwrap_err().is_redirect());
+    }
+
+    #[tokio::test]
+    async fn test_probe_plain_http() {
+        let addr = redirect_server().await;
+        let url = url::Url::parse(&format!("http://{addr}/")).unwrap();
+        let timings = probe_connection(&url, None).await.unwrap();
+        assert!(timings.dns_ms >= 0.0 && timings.connect_ms >= 0.0);
+        assert_eq!(timings.tls_ms, None);
+        assert_eq!(timings.tls_handshake, None);
+    }
+
+    #[test]
+    fn test_timings_total() {
+        let t = Timings::new(
+            None,
+            Duration::from_millis(120),
+            Duration::from_millis(30),
+            Some(Duration::from_millis(5)),
+            Vec::new(),
+        );
+        assert_eq!(t.total_ms, 155.0);
+        assert_eq!(t.dns_ms, None);
+        assert_eq!(ms(Duration::from_micros(1234)), 1.23);
+    }
+}
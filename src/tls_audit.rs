@@ -0,0 +1,222 @@
+//! TLS Inspection (`nab audit tls`)
+//!
+//! Connects the way nab's clients do (trusted roots, `--cacert`, client
+//! certificate, ALPN, and the same `--proxy`, `--proxy-chain`, `--geo`, or
+//! site proxy) and reports what the server presented:
+//!
+//! - the certificate chain: subject, issuer, serial, validity, SANs, and key pin
+//! - whether the chain validates for the host, and why not
+//! - the protocol version, cipher suite, key exchange group, and ALPN protocol
+//! - whether an OCSP response was stapled
+//!
+//! Failed validation (expired, wrong host, unknown issuer) is reported instead
+//! of ending the handshake, so broken certificates can be inspected too.
+
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::net::TcpStream;
+use x509_cert::der::Decode;
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::SubjectAltName;
+
+use crate::http_client::ClientOptions;
+use crate::tls::{spki_pin, Inspection};
+
+/// Connect and handshake timeout without `--timeout`
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// What a server presented on one handshake
+#[derive(Debug, Clone, Serialize)]
+pub struct TlsReport {
+    pub host: String,
+    pub port: u16,
+    /// Proxies the connection went through
+    pub via: Option<String>,
+    pub inspected_at: DateTime<Utc>,
+    /// Whether the chain validates for `host` (and matches any `--pin`)
+    pub valid: bool,
+    pub verify_error: Option<String>,
+    pub protocol: Option<String>,
+    pub cipher: Option<String>,
+    pub key_exchange: Option<String>,
+    pub alpn: Option<String>,
+    pub ocsp_stapled: bool,
+    /// Days until the server certificate expires (negative once it has)
+    pub expires_in_days: Option<i64>,
+    /// Server certificate first, then the intermediates as sent
+    pub chain: Vec<CertificateInfo>,
+}
+
+/// One certificate of a chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    /// Hex serial number
+    pub serial: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// DNS names and IP addresses
+    pub sans: Vec<String>,
+    /// `sha256//` pin of the public key, for `--pin`
+    pub key_pin: Option<String>,
+}
+
+impl CertificateInfo {
+    /// Decode a DER certificate
+    pub fn parse(der: &[u8]) -> Result<Self> {
+        let cert = x509_cert::Certificate::from_der(der).context("Unreadable certificate")?;
+        let tbs = &cert.tbs_certificate;
+        let time = |time: x509_cert::time::Time| {
+            let secs = i64::try_from(time.to_unix_duration().as_secs()).unwrap_or(i64::MAX);
+            DateTime::from_timestamp(secs, 0).unwrap_or(DateTime::<Utc>::MAX_UTC)
+        };
+        let sans = match tbs.get::<SubjectAltName>() {
+            Ok(Some((_, names))) => names.0.iter().filter_map(san).collect(),
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            subject: tbs.subject.to_string(),
+            issuer: tbs.issuer.to_string(),
+            serial: tbs
+                .serial_number
+                .as_bytes()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect(),
+            not_before: time(tbs.validity.not_before),
+            not_after: time(tbs.validity.not_after),
+            sans,
+            key_pin: spki_pin(der),
+        })
+    }
+}
+
+fn san(name: &GeneralName) -> Option<String> {
+    match name {
+        GeneralName::DnsName(dns) => Some(dns.as_str().to_string()),
+        GeneralName::IpAddress(ip) => match ip.as_bytes() {
+            &[a, b, c, d] => Some(IpAddr::from([a, b, c, d]).to_string()),
+            bytes => <[u8; 16]>::try_from(bytes)
+                .ok()
+                .map(|octets| IpAddr::from(octets).to_string()),
+        },
+        _ => None,
+    }
+}
+
+/// `host`, `host:port`, or a URL as host and port (443 unless given)
+pub fn target(spec: &str) -> Result<(String, u16)> {
+    let url = if spec.contains("://") {
+        url::Url::parse(spec)
+    } else {
+        url::Url::parse(&format!("https://{spec}"))
+    }
+    .with_context(|| format!("Invalid host '{spec}'"))?;
+    let host = url
+        .host_str()
+        .with_context(|| format!("No host in '{spec}'"))?
+        .trim_matches(['[', ']'])
+        .to_string();
+    Ok((host, url.port_or_known_default().unwrap_or(443)))
+}
+
+/// Handshake with `host:port` using `options`' TLS settings and proxy
+pub async fn inspect(host: &str, port: u16, options: &ClientOptions) -> Result<TlsReport> {
+    crate::policy::check_host(host)?;
+    let seen = Arc::new(Mutex::new(Inspection::default()));
+    let config = options.tls.inspecting_config(Arc::clone(&seen))?;
+    let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
+        .with_context(|| format!("Invalid host '{host}'"))?;
+
+    let handshake = async {
+        let stream = match &options.proxy {
+            Some(proxy) => proxy.connect(host, port).await?,
+            None => TcpStream::connect(&*crate::hosts::lookup_host(host, port).await?)
+                .await
+                .with_context(|| format!("Failed to connect to {host}:{port}"))?,
+        };
+        tokio_rustls::TlsConnector::from(config)
+            .connect(server_name, stream)
+            .await
+            .context("TLS handshake failed")
+    };
+    let stream = tokio::time::timeout(options.timeout.unwrap_or(TIMEOUT), handshake)
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out connecting to {host}:{port}"))??;
+
+    let (_, connection) = stream.get_ref();
+    let chain = connection
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .map(|cert| CertificateInfo::parse(cert))
+        .collect::<Result<Vec<_>>>()?;
+    let seen = seen.lock().unwrap_or_else(PoisonError::into_inner);
+    let now = Utc::now();
+    Ok(TlsReport {
+        host: host.to_string(),
+        port,
+        via: options.proxy.as_ref().map(crate::ProxyConfig::describe),
+        inspected_at: now,
+        valid: seen.verify_error.is_none(),
+        verify_error: seen.verify_error.clone(),
+        protocol: connection
+            .protocol_version()
+            .map(|version| format!("{version:?}").replace('_', ".")),
+        cipher: connection
+            .negotiated_cipher_suite()
+            .map(|suite| format!("{:?}", suite.suite())),
+        key_exchange: connection
+            .negotiated_key_exchange_group()
+            .map(|group| format!("{:?}", group.name())),
+        alpn: connection
+            .alpn_protocol()
+            .map(|alpn| String::from_utf8_lossy(alpn).into_owned()),
+        ocsp_stapled: !seen.ocsp_response.is_empty(),
+        expires_in_days: chain.first().map(|leaf| (leaf.not_after - now).num_days()),
+        chain,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target() {
+        assert_eq!(target("example.com").unwrap(), ("example.com".into(), 443));
+        assert_eq!(
+            target("https://example.com:8443/path").unwrap(),
+            ("example.com".into(), 8443)
+        );
+        assert_eq!(target("[::1]:4433").unwrap(), ("::1".into(), 4433));
+        assert!(target("exa mple.com").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_inspect_self_signed() {
+        let addr = crate::tls::tests::tls_server().await;
+        let report = inspect("127.0.0.1", addr.port(), &ClientOptions::default())
+            .await
+            .unwrap();
+        assert!(!report.valid);
+        assert!(report.verify_error.is_some());
+        assert_eq!(report.protocol.as_deref(), Some("TLSv1.3"));
+        assert!(report.cipher.unwrap().starts_with("TLS13_"));
+        assert!(!report.ocsp_stapled);
+
+        let leaf = &report.chain[0];
+        assert_eq!(report.chain.len(), 1);
+        assert_eq!(leaf.subject, "CN=nab-test-client");
+        assert_eq!(leaf.issuer, leaf.subject);
+        assert_eq!(leaf.not_after.format("%Y-%m-%d").to_string(), "2126-09-22");
+        assert!(report.expires_in_days.unwrap() > 36_000);
+        assert!(leaf.key_pin.as_ref().unwrap().starts_with("sha256//"));
+    }
+}
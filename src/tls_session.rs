@@ -0,0 +1,196 @@
+//! TLS Session Resumption
+//!
+//! A browser's second connection to a site resumes the TLS session of the
+//! first (presenting a session ticket, skipping the certificate exchange); a
+//! client doing a full handshake every time stands out. Each persona (the
+//! default one, or `--revisit <persona>`) gets one [`SessionStore`], shared by
+//! all of its clients in a process and keyed by host, so connections resume
+//! across clients the way they do across a browser's tabs.
+//!
+//! rustls can't export session tickets, so tickets last as long as the
+//! process. What carries over between runs is each host's key exchange
+//! group: the first `ClientHello` of the next run offers the group the server
+//! picked, as a returning browser does, instead of drawing a
+//! `HelloRetryRequest`. These hints live in `~/.cache/nab/tls-sessions.json`
+//! (`tls-sessions/<persona>.json` for personas, in the
+//! [workspace](crate::workspace)'s cache when one is selected).
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use rustls::client::{ClientSessionMemoryCache, ClientSessionStore};
+use rustls::client::{Tls12ClientSessionValue, Tls13ClientSessionValue};
+use rustls::pki_types::ServerName;
+use rustls::NamedGroup;
+use serde::{Deserialize, Serialize};
+
+use crate::state::Versioned;
+
+/// Sessions held in memory per store (rustls' default)
+const SESSIONS_IN_MEMORY: usize = 256;
+
+/// Stores handed out this process, by persona
+static STORES: LazyLock<Mutex<HashMap<Option<String>, Arc<SessionStore>>>> =
+    LazyLock::new(Mutex::default);
+
+/// Persisted per-host handshake hints
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionHints {
+    hosts: BTreeMap<String, HostHint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct HostHint {
+    /// IANA code of the key exchange group the server chose
+    kx_group: u16,
+    updated: DateTime<Utc>,
+}
+
+impl Versioned for SessionHints {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+/// Resumable TLS sessions of one persona
+#[derive(Debug)]
+pub struct SessionStore {
+    sessions: ClientSessionMemoryCache,
+    /// Where hints are saved (`None`: kept in memory only)
+    path: Option<PathBuf>,
+    hints: Mutex<SessionHints>,
+}
+
+impl SessionStore {
+    /// The process-wide store of `persona` (`None`: the default persona)
+    #[must_use]
+    pub fn for_persona(persona: Option<&str>) -> Arc<Self> {
+        let mut stores = STORES.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = persona.map(String::from);
+        Arc::clone(stores.entry(key).or_insert_with(|| {
+            let cache = crate::workspace::cache_dir();
+            let path = match persona {
+                Some(persona) => cache.join("tls-sessions").join(format!("{persona}.json")),
+                None => cache.join("tls-sessions.json"),
+            };
+            Arc::new(Self::open(path))
+        }))
+    }
+
+    /// A store saving hints to `path`
+    #[must_use]
+    pub fn open(path: PathBuf) -> Self {
+        let hints = crate::state::load(&path)
+            .unwrap_or_else(|e| {
+                tracing::debug!("Ignoring TLS session hints: {e:#}");
+                None
+            })
+            .unwrap_or_default();
+        Self {
+            sessions: ClientSessionMemoryCache::new(SESSIONS_IN_MEMORY),
+            path: Some(path),
+            hints: Mutex::new(hints),
+        }
+    }
+
+    /// A store that forgets everything at exit
+    #[must_use]
+    pub fn in_memory() -> Self {
+        Self {
+            sessions: ClientSessionMemoryCache::new(SESSIONS_IN_MEMORY),
+            path: None,
+            hints: Mutex::default(),
+        }
+    }
+
+    /// Key exchange group remembered for `host`
+    #[must_use]
+    pub fn kx_group(&self, host: &str) -> Option<NamedGroup> {
+        self.hints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .hosts
+            .get(host)
+            .map(|hint| NamedGroup::from(hint.kx_group))
+    }
+}
+
+impl ClientSessionStore for SessionStore {
+    fn set_kx_hint(&self, server_name: ServerName<'static>, group: NamedGroup) {
+        let host = server_name.to_str().into_owned();
+        self.sessions.set_kx_hint(server_name, group);
+
+        let mut hints = self.hints.lock().unwrap_or_else(PoisonError::into_inner);
+        let kx_group = u16::from(group);
+        if hints.hosts.get(&host).map(|hint| hint.kx_group) == Some(kx_group) {
+            return;
+        }
+        hints.hosts.insert(
+            host,
+            HostHint {
+                kx_group,
+                updated: Utc::now(),
+            },
+        );
+        if let Some(path) = &self.path {
+            if let Err(e) = crate::state::save(path, &*hints) {
+                tracing::debug!("Failed to save TLS session hints: {e:#}");
+            }
+        }
+    }
+
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        self.sessions
+            .kx_hint(server_name)
+            .or_else(|| self.kx_group(&server_name.to_str()))
+    }
+
+    fn set_tls12_session(&self, server_name: ServerName<'static>, value: Tls12ClientSessionValue) {
+        self.sessions.set_tls12_session(server_name, value);
+    }
+
+    fn tls12_session(&self, server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        self.sessions.tls12_session(server_name)
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        self.sessions.remove_tls12_session(server_name);
+    }
+
+    fn insert_tls13_ticket(
+        &self,
+        server_name: ServerName<'static>,
+        value: Tls13ClientSessionValue,
+    ) {
+        self.sessions.insert_tls13_ticket(server_name, value);
+    }
+
+    fn take_tls13_ticket(
+        &self,
+        server_name: &ServerName<'static>,
+    ) -> Option<Tls13ClientSessionValue> {
+        self.sessions.take_tls13_ticket(server_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kx_hints_persist() {
+        let path = std::env::temp_dir().join(format!("nab-tls-hints-{}.json", std::process::id()));
+        let host = ServerName::try_from("shop.example.com").unwrap();
+
+        let store = SessionStore::open(path.clone());
+        assert_eq!(store.kx_hint(&host), None);
+        store.set_kx_hint(host.clone(), NamedGroup::secp256r1);
+        assert_eq!(store.kx_hint(&host), Some(NamedGroup::secp256r1));
+
+        let next_run = SessionStore::open(path.clone());
+        assert_eq!(next_run.kx_hint(&host), Some(NamedGroup::secp256r1));
+        assert_eq!(next_run.kx_group("other.example.com"), None);
+        assert_eq!(SessionStore::in_memory().kx_hint(&host), None);
+        let _ = std::fs::remove_file(path);
+    }
+}
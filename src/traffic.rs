@@ -0,0 +1,254 @@
+//! Transfer Statistics (`--stats`)
+//!
+//! Every response nab reads is tallied per host: requests by status class,
+//! failed requests, bytes sent and received, and what a `--revisit` cache
+//! saved. `nab --stats <command>` prints the tally when the command ends, per
+//! host as well when more than one was fetched; `batch` and `crawl` also end
+//! their JSON lines with a `{"stats": ...}` line.
+//!
+//! Received sizes are bodies as read, after decompression. `transfer_bytes`
+//! is the `Content-Length` the server declared, where the response still has
+//! one: reqwest drops it from bodies it decompresses, which count their
+//! decoded size instead.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::quota::format_size;
+
+/// Tallies by host
+static TRAFFIC: Mutex<BTreeMap<String, HostTraffic>> = Mutex::new(BTreeMap::new());
+
+/// When the command started
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Responses by status class
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct StatusClasses {
+    #[serde(rename = "1xx")]
+    pub informational: u64,
+    #[serde(rename = "2xx")]
+    pub success: u64,
+    #[serde(rename = "3xx")]
+    pub redirect: u64,
+    #[serde(rename = "4xx")]
+    pub client_error: u64,
+    #[serde(rename = "5xx")]
+    pub server_error: u64,
+}
+
+/// What was transferred with one host (or all of them)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct HostTraffic {
+    /// Requests answered, whatever the status
+    pub requests: u64,
+    pub status: StatusClasses,
+    /// Requests that failed without a response
+    pub errors: u64,
+    /// Request bodies
+    pub bytes_sent: u64,
+    /// Response bodies, decompressed
+    pub bytes_received: u64,
+    /// Response bodies as declared by `Content-Length`, else as received
+    pub transfer_bytes: u64,
+    /// Pages answered `304 Not Modified` from the revisit cache
+    pub cache_hits: u64,
+    /// Cached bodies that didn't have to be downloaded again
+    pub cache_saved_bytes: u64,
+}
+
+impl HostTraffic {
+    fn add(&mut self, other: &Self) {
+        self.requests += other.requests;
+        self.status.informational += other.status.informational;
+        self.status.success += other.status.success;
+        self.status.redirect += other.status.redirect;
+        self.status.client_error += other.status.client_error;
+        self.status.server_error += other.status.server_error;
+        self.errors += other.errors;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+        self.transfer_bytes += other.transfer_bytes;
+        self.cache_hits += other.cache_hits;
+        self.cache_saved_bytes += other.cache_saved_bytes;
+    }
+
+    /// `10 requests (9 2xx, 1 4xx), 1.2 MB received`
+    fn line(&self) -> String {
+        let status = self.status;
+        let classes: Vec<String> = [
+            (status.informational, "1xx"),
+            (status.success, "2xx"),
+            (status.redirect, "3xx"),
+            (status.client_error, "4xx"),
+            (status.server_error, "5xx"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, class)| format!("{count} {class}"))
+        .collect();
+        let mut line = format!("{} requests", self.requests);
+        if !classes.is_empty() {
+            line.push_str(&format!(" ({})", classes.join(", ")));
+        }
+        if self.errors > 0 {
+            line.push_str(&format!(", {} failed", self.errors));
+        }
+        line.push_str(&format!(", {} received", format_size(self.bytes_received)));
+        line
+    }
+}
+
+/// The tally so far
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub elapsed_seconds: f64,
+    pub total: HostTraffic,
+    pub hosts: BTreeMap<String, HostTraffic>,
+}
+
+impl Summary {
+    /// Human-readable report, one line per host after the totals when there
+    /// are several
+    #[must_use]
+    pub fn render(&self) -> String {
+        let total = &self.total;
+        let mut out = format!(
+            "📊 {} in {:.1}s\n   ↓ {} received ({} transferred), ↑ {} sent",
+            total.line(),
+            self.elapsed_seconds,
+            format_size(total.bytes_received),
+            format_size(total.transfer_bytes),
+            format_size(total.bytes_sent),
+        );
+        if total.cache_hits > 0 {
+            out.push_str(&format!(
+                "\n   🔁 {} cache hits saved {}",
+                total.cache_hits,
+                format_size(total.cache_saved_bytes)
+            ));
+        }
+        if self.hosts.len() > 1 {
+            for (host, traffic) in &self.hosts {
+                out.push_str(&format!("\n   {host}: {}", traffic.line()));
+            }
+        }
+        out
+    }
+}
+
+/// Start the clock for [`summary`] (otherwise it starts with the first request)
+pub fn start() {
+    let _ = STARTED.set(Instant::now());
+}
+
+fn record(url: &str, update: impl FnOnce(&mut HostTraffic)) {
+    start();
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_else(|| "(unknown)".to_string());
+    let mut traffic = TRAFFIC
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    update(traffic.entry(host).or_default());
+}
+
+/// A response from `url`: `received` body bytes (decompressed) and its
+/// `Content-Length`, if any
+pub fn response(url: &str, status: u16, received: usize, declared: Option<u64>) {
+    record(url, |traffic| {
+        traffic.requests += 1;
+        match status / 100 {
+            1 => traffic.status.informational += 1,
+            2 => traffic.status.success += 1,
+            3 => traffic.status.redirect += 1,
+            4 => traffic.status.client_error += 1,
+            _ => traffic.status.server_error += 1,
+        }
+        traffic.bytes_received += received as u64;
+        traffic.transfer_bytes += declared.unwrap_or(received as u64);
+    });
+}
+
+/// A `bytes`-byte request body sent to `url`
+pub fn sent(url: &str, bytes: usize) {
+    record(url, |traffic| traffic.bytes_sent += bytes as u64);
+}
+
+/// A request to `url` that failed without a response
+pub fn error(url: &str) {
+    record(url, |traffic| traffic.errors += 1);
+}
+
+/// `url` was answered from the cache, saving a `saved`-byte download
+pub fn cache_hit(url: &str, saved: usize) {
+    record(url, |traffic| {
+        traffic.cache_hits += 1;
+        traffic.cache_saved_bytes += saved as u64;
+    });
+}
+
+/// Whether anything was recorded
+#[must_use]
+pub fn is_empty() -> bool {
+    TRAFFIC
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .is_empty()
+}
+
+/// Totals and per-host tallies since [`start`]
+#[must_use]
+pub fn summary() -> Summary {
+    let hosts = TRAFFIC
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone();
+    let mut total = HostTraffic::default();
+    for traffic in hosts.values() {
+        total.add(traffic);
+    }
+    Summary {
+        elapsed_seconds: STARTED.get().map_or(0.0, |t| t.elapsed().as_secs_f64()),
+        total,
+        hosts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_by_host() {
+        // Other tests read responses too, so only these hosts are checked
+        response("https://stats-a.test/", 200, 1500, Some(600));
+        response("https://stats-a.test/missing", 404, 100, None);
+        sent("https://stats-b.test/api", 42);
+        response("https://stats-b.test/api", 201, 10, None);
+        error("https://stats-b.test/down");
+        cache_hit("https://stats-a.test/old", 2000);
+
+        let summary = summary();
+        let a = summary.hosts["stats-a.test"];
+        assert_eq!(a.requests, 2);
+        assert_eq!(a.status.client_error, 1);
+        assert_eq!(a.transfer_bytes, 700);
+        assert_eq!((a.cache_hits, a.cache_saved_bytes), (1, 2000));
+        assert_eq!(summary.hosts["stats-b.test"].bytes_sent, 42);
+        assert!(summary.total.requests >= 3);
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["hosts"]["stats-a.test"]["status"]["2xx"], 1);
+        let report = summary.render();
+        assert!(
+            report.contains("\n   stats-a.test: 2 requests (1 2xx, 1 4xx), 1.6 KB received"),
+            "{report}"
+        );
+        assert!(report.contains("\n   stats-b.test: 1 requests (1 2xx), 1 failed, 10 B received"));
+    }
+}
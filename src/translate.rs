@@ -0,0 +1,434 @@
+//! Markdown Translation
+//!
+//! Routes extracted Markdown through a translation API while keeping its
+//! structure intact:
+//! - Fenced code blocks, rules, tables separators, and HTML lines pass through
+//! - Heading, list, and quote markers stay outside the translated text
+//! - Inline code, link targets, inline HTML, and URLs are swapped for
+//!   placeholders (`⟦N⟧`) before sending and restored afterwards
+//!
+//! Backends: `LibreTranslate` and `DeepL`, configured in the `translate`
+//! section of the config file.
+
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::config::{NabConfig, TranslateConfig, TranslateService};
+
+/// Maximum characters sent per API request
+const BATCH_CHARS: usize = 5_000;
+
+/// Maximum texts per API request (`DeepL` accepts 50)
+const BATCH_TEXTS: usize = 50;
+
+const DEEPL_FREE_URL: &str = "https://api-free.deepl.com/v2/translate";
+
+/// Inline spans that must not be translated
+static PROTECTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"`[^`\n]+`|\]\([^)\n]*\)|<[^>\n]+>|https?://[^\s)>\]]+").unwrap());
+
+/// Placeholder as it may come back from a translator (spacing can change)
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"⟦\s*(\d+)\s*⟧").unwrap());
+
+/// Block prefixes kept verbatim: headings, list items, task boxes, quotes
+static BLOCK_PREFIX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:#{1,6}\s+|[-*+]\s+(?:\[[ xX]\]\s+)?|\d+[.)]\s+|>\s*)*").unwrap()
+});
+
+/// Translation API client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranslateBackend {
+    /// `LibreTranslate` `/translate` endpoint
+    LibreTranslate {
+        url: String,
+        api_key: Option<String>,
+    },
+    /// `DeepL` `/v2/translate` endpoint
+    DeepL { url: String, api_key: String },
+}
+
+impl TranslateBackend {
+    /// Build the backend from config, reading the API key from the environment
+    pub fn from_config(config: &TranslateConfig) -> Result<Self> {
+        match config.backend {
+            TranslateService::LibreTranslate => {
+                let Some(endpoint) = &config.endpoint else {
+                    anyhow::bail!(
+                        "No translation endpoint configured. Set translate.endpoint in {}",
+                        NabConfig::path().display()
+                    );
+                };
+                let key_env = config
+                    .api_key_env
+                    .as_deref()
+                    .unwrap_or("LIBRETRANSLATE_API_KEY");
+                Ok(Self::LibreTranslate {
+                    url: endpoint_url(endpoint, "/translate"),
+                    api_key: std::env::var(key_env).ok(),
+                })
+            }
+            TranslateService::DeepL => {
+                let key_env = config.api_key_env.as_deref().unwrap_or("DEEPL_AUTH_KEY");
+                let api_key = std::env::var(key_env)
+                    .with_context(|| format!("DeepL needs an API key in ${key_env}"))?;
+                let url = config.endpoint.as_deref().map_or_else(
+                    || DEEPL_FREE_URL.to_string(),
+                    |e| {
+                        endpoint_url(
+                            e,
+                            if e.trim_end_matches('/').ends_with("/v2") {
+                                "/translate"
+                            } else {
+                                "/v2/translate"
+                            },
+                        )
+                    },
+                );
+                Ok(Self::DeepL { url, api_key })
+            }
+        }
+    }
+
+    /// Translate a batch of plain-text strings into `target`, preserving order
+    pub async fn translate(&self, texts: &[&str], target: &str) -> Result<Vec<String>> {
+        let client = crate::policy::client_builder().build()?;
+        let (request, field) = match self {
+            Self::LibreTranslate { url, api_key } => {
+                let mut body = serde_json::json!({
+                    "q": texts,
+                    "source": "auto",
+                    "target": target.to_lowercase(),
+                    "format": "text",
+                });
+                if let Some(key) = api_key {
+                    body["api_key"] = key.as_str().into();
+                }
+                (client.post(url).json(&body), "translatedText")
+            }
+            Self::DeepL { url, api_key } => (
+                client
+                    .post(url)
+                    .header("Authorization", format!("DeepL-Auth-Key {api_key}"))
+                    .json(&serde_json::json!({
+                        "text": texts,
+                        "target_lang": deepl_target(target),
+                    })),
+                "translations",
+            ),
+        };
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body: serde_json::Value = response.json().await?;
+        if !status.is_success() {
+            anyhow::bail!("Translation API returned {status}: {body}");
+        }
+
+        let translated: Vec<String> = match &body[field] {
+            serde_json::Value::String(s) => vec![s.clone()],
+            serde_json::Value::Array(items) => items
+                .iter()
+                .filter_map(|v| v.as_str().or_else(|| v["text"].as_str()))
+                .map(String::from)
+                .collect(),
+            _ => Vec::new(),
+        };
+        if translated.len() != texts.len() {
+            anyhow::bail!(
+                "Translation API returned {} texts for {} inputs",
+                translated.len(),
+                texts.len()
+            );
+        }
+        Ok(translated)
+    }
+}
+
+/// Translate Markdown into `target` (ISO 639-1, e.g. `en`), keeping its structure
+pub async fn translate_markdown(
+    markdown: &str,
+    target: &str,
+    config: &TranslateConfig,
+) -> Result<String> {
+    let backend = TranslateBackend::from_config(config)?;
+    let segmented = SegmentedMarkdown::parse(markdown);
+    let texts = segmented.texts();
+
+    let mut translated = Vec::with_capacity(texts.len());
+    let mut start = 0;
+    while start < texts.len() {
+        let mut end = start;
+        let mut chars = 0;
+        while end < texts.len()
+            && end - start < BATCH_TEXTS
+            && (end == start || chars + texts[end].len() <= BATCH_CHARS)
+        {
+            chars += texts[end].len();
+            end += 1;
+        }
+        translated.extend(backend.translate(&texts[start..end], target).await?);
+        start = end;
+    }
+    segmented.reassemble(&translated)
+}
+
+/// Markdown split into verbatim structure and translatable text
+#[derive(Debug, Clone, Default)]
+pub struct SegmentedMarkdown {
+    pieces: Vec<Piece>,
+    protected: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Piece {
+    Verbatim(String),
+    Text(String),
+}
+
+impl SegmentedMarkdown {
+    /// Split Markdown line by line into structure and translatable text
+    #[must_use]
+    pub fn parse(markdown: &str) -> Self {
+        let mut out = Self::default();
+        let mut in_fence = false;
+
+        for line in markdown.split_inclusive('\n') {
+            let content = line.trim_end_matches(['\n', '\r']);
+            let newline = &line[content.len()..];
+            let trimmed = content.trim_start();
+
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_fence = !in_fence;
+                out.verbatim(line);
+                continue;
+            }
+            if in_fence || trimmed.is_empty() || trimmed.starts_with('<') || is_rule(trimmed) {
+                out.verbatim(line);
+                continue;
+            }
+
+            if trimmed.starts_with('|') {
+                out.table_row(content);
+            } else {
+                let prefix_len = BLOCK_PREFIX.find(content).map_or(0, |m| m.end());
+                out.verbatim(&content[..prefix_len]);
+                out.text(&content[prefix_len..]);
+            }
+            out.verbatim(newline);
+        }
+        out
+    }
+
+    /// Texts to send to the translator, in document order
+    #[must_use]
+    pub fn texts(&self) -> Vec<&str> {
+        self.pieces
+            .iter()
+            .filter_map(|p| match p {
+                Piece::Text(t) => Some(t.as_str()),
+                Piece::Verbatim(_) => None,
+            })
+            .collect()
+    }
+
+    /// Rebuild the document with `translations` (one per [`Self::texts`] entry)
+    pub fn reassemble(&self, translations: &[String]) -> Result<String> {
+        let expected = self.texts().len();
+        if translations.len() != expected {
+            anyhow::bail!(
+                "Expected {expected} translated texts, got {}",
+                translations.len()
+            );
+        }
+
+        let mut translations = translations.iter();
+        let mut out = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Verbatim(s) => out.push_str(s),
+                Piece::Text(_) => {
+                    let text = translations.next().map_or("", String::as_str);
+                    out.push_str(&self.restore(text));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn verbatim(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        if let Some(Piece::Verbatim(last)) = self.pieces.last_mut() {
+            last.push_str(s);
+        } else {
+            self.pieces.push(Piece::Verbatim(s.to_string()));
+        }
+    }
+
+    /// Add translatable text, protecting inline spans and surrounding whitespace
+    fn text(&mut self, s: &str) {
+        let core = s.trim();
+        if core.is_empty() {
+            self.verbatim(s);
+            return;
+        }
+        let lead = &s[..s.len() - s.trim_start().len()];
+        let trail = &s[s.trim_end().len()..];
+
+        let mark = self.protected.len();
+        let masked = PROTECTED
+            .replace_all(core, |caps: &regex::Captures<'_>| {
+                self.protected.push(caps[0].to_string());
+                format!("⟦{}⟧", self.protected.len() - 1)
+            })
+            .into_owned();
+
+        self.verbatim(lead);
+        if PLACEHOLDER
+            .replace_all(&masked, "")
+            .chars()
+            .any(char::is_alphabetic)
+        {
+            self.pieces.push(Piece::Text(masked));
+        } else {
+            // Nothing to translate (only URLs, code, punctuation)
+            self.protected.truncate(mark);
+            self.verbatim(core);
+        }
+        self.verbatim(trail);
+    }
+
+    fn table_row(&mut self, row: &str) {
+        let is_separator = row
+            .chars()
+            .all(|c| matches!(c, '|' | '-' | ':' | ' ' | '\t'));
+        if is_separator {
+            self.verbatim(row);
+            return;
+        }
+        for (i, cell) in row.split('|').enumerate() {
+            if i > 0 {
+                self.verbatim("|");
+            }
+            self.text(cell);
+        }
+    }
+
+    fn restore(&self, text: &str) -> String {
+        PLACEHOLDER
+            .replace_all(text, |caps: &regex::Captures<'_>| {
+                caps[1]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| self.protected.get(i))
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .into_owned()
+    }
+}
+
+/// `---`, `***`, `___` (optionally spaced) thematic breaks
+fn is_rule(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3 && matches!(chars[0], '-' | '*' | '_') && chars.iter().all(|c| *c == chars[0])
+}
+
+fn endpoint_url(endpoint: &str, path: &str) -> String {
+    let base = endpoint.trim_end_matches('/');
+    if base.ends_with("/translate") {
+        base.to_string()
+    } else {
+        format!("{base}{path}")
+    }
+}
+
+/// `DeepL` wants upper-case codes and a regional variant for English/Portuguese
+fn deepl_target(target: &str) -> String {
+    match target.to_lowercase().as_str() {
+        "en" => "EN-US".to_string(),
+        "pt" => "PT-PT".to_string(),
+        other => other.to_uppercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = "# Otsikko\n\nTämä on [linkki](https://example.com/a) ja `koodi`.\n\n\
+                       - kohta yksi\n1. kohta kaksi\n> lainaus\n\n```rust\nlet x = 1;\n```\n\n\
+                       ---\n| Nimi | Arvo |\n|---|---|\n| kissa | 3 |\n<https://example.com>\n";
+
+    #[test]
+    fn test_segment_keeps_structure() {
+        let segmented = SegmentedMarkdown::parse(DOC);
+        let texts = segmented.texts();
+        assert_eq!(
+            texts,
+            vec![
+                "Otsikko",
+                "Tämä on [linkki⟦0⟧ ja ⟦1⟧.",
+                "kohta yksi",
+                "kohta kaksi",
+                "lainaus",
+                "Nimi",
+                "Arvo",
+                "kissa",
+            ]
+        );
+
+        // Identity translation reproduces the document exactly
+        let same: Vec<String> = texts.iter().map(|t| (*t).to_string()).collect();
+        assert_eq!(segmented.reassemble(&same).unwrap(), DOC);
+    }
+
+    #[test]
+    fn test_reassemble_translated() {
+        let segmented = SegmentedMarkdown::parse(DOC);
+        let translated: Vec<String> = segmented
+            .texts()
+            .iter()
+            // Translators may add spaces inside placeholders
+            .map(|t| t.to_uppercase().replace("⟦0⟧", "⟦ 0 ⟧"))
+            .collect();
+        let out = segmented.reassemble(&translated).unwrap();
+
+        assert!(out.starts_with("# OTSIKKO\n"));
+        assert!(out.contains("TÄMÄ ON [LINKKI](https://example.com/a) JA `koodi`."));
+        assert!(out.contains("- KOHTA YKSI\n1. KOHTA KAKSI\n> LAINAUS\n"));
+        assert!(out.contains("```rust\nlet x = 1;\n```"));
+        assert!(out.contains("| NIMI | ARVO |\n|---|---|\n| KISSA | 3 |"));
+        assert!(segmented.reassemble(&[]).is_err());
+    }
+
+    #[test]
+    fn test_backend_from_config() {
+        assert!(TranslateBackend::from_config(&TranslateConfig::default()).is_err());
+
+        let config = TranslateConfig {
+            endpoint: Some("http://localhost:5000/".into()),
+            api_key_env: Some("NAB_TEST_UNSET_TRANSLATE_KEY".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            TranslateBackend::from_config(&config).unwrap(),
+            TranslateBackend::LibreTranslate {
+                url: "http://localhost:5000/translate".into(),
+                api_key: None,
+            }
+        );
+
+        let deepl = TranslateConfig {
+            backend: TranslateService::DeepL,
+            api_key_env: Some("NAB_TEST_UNSET_TRANSLATE_KEY".into()),
+            ..Default::default()
+        };
+        assert!(TranslateBackend::from_config(&deepl).is_err());
+        assert_eq!(deepl_target("en"), "EN-US");
+        assert_eq!(deepl_target("fi"), "FI");
+    }
+}
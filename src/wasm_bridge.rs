@@ -0,0 +1,761 @@
+//! WebAssembly Bridge - Runs page WASM modules on wasmtime
+//!
+//! `QuickJS` has no `WebAssembly` global, but a number of sites compute API
+//! request signatures inside WASM blobs. This module installs a minimal
+//! `WebAssembly` shim whose `compile`/`instantiate` calls are backed by
+//! wasmtime.
+//!
+//! Architecture:
+//! ```text
+//! JavaScript:  WebAssembly.instantiate(bytes, imports)
+//!      ↓       Native function call
+//! Rust:        wasmtime::Module::new() + Linker (function imports → JS callbacks)
+//!      ↓       Epoch deadline + memory cap per store
+//! JavaScript:  { instance: { exports: { sign(ptr, len), memory } } }
+//! ```
+//!
+//! Exported linear memory is mirrored into a JS `ArrayBuffer` and copied
+//! across every JS ↔ WASM boundary crossing. That is cheap enough for signing
+//! routines, but not meant for compute-heavy modules.
+
+use std::cell::Cell;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use rquickjs::{qjs, Array, ArrayBuffer, Context, Ctx, Exception, Function, Value};
+use tracing::debug;
+use wasmtime::{
+    Caller, Config, Engine, ExternType, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, Trap, Val, ValType,
+};
+
+/// Granularity of the epoch ticker that enforces call time limits
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Limits applied to page WASM modules
+#[derive(Debug, Clone)]
+pub struct WasmConfig {
+    /// Wall-clock limit for instantiation and for each exported call
+    pub call_timeout: Duration,
+    /// Maximum linear memory per module instance, in bytes
+    pub max_memory_bytes: usize,
+}
+
+impl Default for WasmConfig {
+    fn default() -> Self {
+        Self {
+            call_timeout: Duration::from_millis(1000),
+            max_memory_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// wasmtime-backed implementation of the JS `WebAssembly` API
+#[derive(Clone)]
+pub struct WasmBridge {
+    engine: Engine,
+    config: WasmConfig,
+    state: Arc<Mutex<WasmState>>,
+}
+
+#[derive(Default)]
+struct WasmState {
+    modules: Vec<Module>,
+    instances: Vec<Arc<Mutex<WasmInstance>>>,
+}
+
+struct WasmInstance {
+    store: Store<HostState>,
+    instance: Instance,
+}
+
+struct HostState {
+    limits: StoreLimits,
+    /// Exported memory, mirrored into JS around import callbacks
+    memory: Option<Memory>,
+}
+
+thread_local! {
+    /// JS context of the native call currently running WASM code.
+    ///
+    /// Import trampolines run inside `Func::call`, which happens synchronously
+    /// on the JS thread, so they can re-enter the very context that called us.
+    static ACTIVE_CTX: Cell<Option<NonNull<qjs::JSContext>>> = const { Cell::new(None) };
+}
+
+/// Parks a JS context in `ACTIVE_CTX` for the duration of a WASM call
+struct ActiveCtx(Option<NonNull<qjs::JSContext>>);
+
+impl ActiveCtx {
+    fn enter(ctx: &Ctx<'_>) -> Self {
+        Self(ACTIVE_CTX.with(|c| c.replace(Some(ctx.as_raw()))))
+    }
+}
+
+impl Drop for ActiveCtx {
+    fn drop(&mut self) {
+        ACTIVE_CTX.with(|c| c.set(self.0));
+    }
+}
+
+impl WasmBridge {
+    /// Create a new WASM bridge with the given limits
+    pub fn new(config: WasmConfig) -> Result<Self> {
+        let mut engine_config = Config::new();
+        engine_config.epoch_interruption(true);
+        let engine = Engine::new(&engine_config)?;
+
+        // Advance the epoch in the background; stops once the engine is dropped
+        let weak = engine.weak();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(EPOCH_TICK);
+            match weak.upgrade() {
+                Some(engine) => engine.increment_epoch(),
+                None => break,
+            }
+        });
+
+        Ok(Self {
+            engine,
+            config,
+            state: Arc::new(Mutex::new(WasmState::default())),
+        })
+    }
+
+    /// Number of modules compiled by page scripts
+    #[must_use]
+    pub fn module_count(&self) -> usize {
+        self.state.lock().map(|s| s.modules.len()).unwrap_or(0)
+    }
+
+    /// Number of module instances created by page scripts
+    #[must_use]
+    pub fn instance_count(&self) -> usize {
+        self.state.lock().map(|s| s.instances.len()).unwrap_or(0)
+    }
+
+    fn deadline_ticks(&self) -> u64 {
+        let ticks = self.config.call_timeout.as_millis() / EPOCH_TICK.as_millis();
+        u64::try_from(ticks).unwrap_or(u64::MAX).max(1)
+    }
+
+    fn describe_error(&self, err: &anyhow::Error) -> String {
+        if matches!(err.downcast_ref::<Trap>(), Some(Trap::Interrupt)) {
+            format!(
+                "WASM execution exceeded {}ms time limit",
+                self.config.call_timeout.as_millis()
+            )
+        } else {
+            format!("{err:#}")
+        }
+    }
+
+    /// Compile a module and return its id
+    pub fn compile(&self, bytes: &[u8]) -> Result<usize> {
+        let module = Module::new(&self.engine, bytes)?;
+        let mut state = self.state.lock().unwrap();
+        state.modules.push(module);
+        debug!("Compiled WASM module ({} bytes)", bytes.len());
+        Ok(state.modules.len() - 1)
+    }
+
+    fn module(&self, module_id: usize) -> Result<Module> {
+        let state = self.state.lock().unwrap();
+        state
+            .modules
+            .get(module_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown WASM module: {module_id}"))
+    }
+
+    fn instance(&self, instance_id: usize) -> Result<Arc<Mutex<WasmInstance>>> {
+        let state = self.state.lock().unwrap();
+        state
+            .instances
+            .get(instance_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown WASM instance: {instance_id}"))
+    }
+
+    /// Describe module imports as JSON: `[{"module", "name", "kind"}]`
+    pub fn imports_json(&self, module_id: usize) -> Result<String> {
+        let module = self.module(module_id)?;
+        let imports: Vec<serde_json::Value> = module
+            .imports()
+            .map(|i| {
+                serde_json::json!({
+                    "module": i.module(),
+                    "name": i.name(),
+                    "kind": extern_kind(&i.ty()),
+                })
+            })
+            .collect();
+        Ok(serde_json::to_string(&imports)?)
+    }
+
+    /// Describe module exports as JSON: `[{"name", "kind"}]`
+    pub fn exports_json(&self, module_id: usize) -> Result<String> {
+        let module = self.module(module_id)?;
+        let exports: Vec<serde_json::Value> = module
+            .exports()
+            .map(|e| serde_json::json!({ "name": e.name(), "kind": extern_kind(&e.ty()) }))
+            .collect();
+        Ok(serde_json::to_string(&exports)?)
+    }
+
+    /// Instantiate a module, routing function imports to JS import table `slot`
+    fn instantiate(&self, module_id: usize, slot: u32) -> Result<usize> {
+        let module = self.module(module_id)?;
+
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.config.max_memory_bytes)
+            .build();
+        let mut store = Store::new(
+            &self.engine,
+            HostState {
+                limits,
+                memory: None,
+            },
+        );
+        store.limiter(|s| &mut s.limits);
+        store.set_epoch_deadline(self.deadline_ticks());
+
+        let mut linker: Linker<HostState> = Linker::new(&self.engine);
+        for (index, import) in module.imports().enumerate() {
+            let ExternType::Func(ty) = import.ty() else {
+                anyhow::bail!(
+                    "Unsupported WASM import {}.{} ({})",
+                    import.module(),
+                    import.name(),
+                    extern_kind(&import.ty())
+                );
+            };
+            let index = u32::try_from(index)?;
+            let result_ty = ty.results().next();
+            linker.func_new(
+                import.module(),
+                import.name(),
+                ty,
+                move |caller, params, results| {
+                    call_js_import(caller, slot, index, result_ty.as_ref(), params, results)
+                },
+            )?;
+        }
+
+        let instance = linker.instantiate(&mut store, &module)?;
+        let memory = module
+            .exports()
+            .find(|e| matches!(e.ty(), ExternType::Memory(_)))
+            .and_then(|e| instance.get_memory(&mut store, e.name()));
+        store.data_mut().memory = memory;
+
+        let mut state = self.state.lock().unwrap();
+        state
+            .instances
+            .push(Arc::new(Mutex::new(WasmInstance { store, instance })));
+        Ok(state.instances.len() - 1)
+    }
+
+    /// Call an exported function; numbers in, at most one number out
+    fn call(&self, instance_id: usize, name: &str, args: &[f64]) -> Result<Option<f64>> {
+        let instance = self.instance(instance_id)?;
+        let Ok(mut guard) = instance.try_lock() else {
+            anyhow::bail!("Re-entrant call into WASM instance {instance_id} is not supported");
+        };
+        let WasmInstance { store, instance } = &mut *guard;
+
+        let func = instance
+            .get_func(&mut *store, name)
+            .ok_or_else(|| anyhow::anyhow!("WASM export {name} is not a function"))?;
+        let ty = func.ty(&*store);
+
+        let params: Vec<Val> = ty
+            .params()
+            .enumerate()
+            .map(|(i, t)| to_val(&t, args.get(i).copied().unwrap_or(0.0)))
+            .collect::<Result<_>>()?;
+        let mut results = vec![Val::I32(0); ty.results().len()];
+
+        store.set_epoch_deadline(self.deadline_ticks());
+        func.call(&mut *store, &params, &mut results)?;
+
+        Ok(results.first().map(from_val))
+    }
+
+    /// Copy an instance's exported memory out
+    fn read_memory(&self, instance_id: usize) -> Result<Vec<u8>> {
+        let instance = self.instance(instance_id)?;
+        let guard = instance
+            .try_lock()
+            .map_err(|_| anyhow::anyhow!("WASM instance {instance_id} is busy"))?;
+        Ok(guard
+            .store
+            .data()
+            .memory
+            .map(|m| m.data(&guard.store).to_vec())
+            .unwrap_or_default())
+    }
+
+    /// Copy JS-side changes back into an instance's exported memory
+    fn write_memory(&self, instance_id: usize, bytes: &[u8]) -> Result<()> {
+        let instance = self.instance(instance_id)?;
+        let mut guard = instance
+            .try_lock()
+            .map_err(|_| anyhow::anyhow!("WASM instance {instance_id} is busy"))?;
+        if let Some(memory) = guard.store.data().memory {
+            let data = memory.data_mut(&mut guard.store);
+            let len = data.len().min(bytes.len());
+            data[..len].copy_from_slice(&bytes[..len]);
+        }
+        Ok(())
+    }
+}
+
+fn extern_kind(ty: &ExternType) -> &'static str {
+    match ty {
+        ExternType::Func(_) => "function",
+        ExternType::Memory(_) => "memory",
+        ExternType::Table(_) => "table",
+        ExternType::Global(_) => "global",
+        ExternType::Tag(_) => "tag",
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn to_val(ty: &ValType, n: f64) -> Result<Val> {
+    Ok(match ty {
+        // JS ToInt32 semantics: wrap rather than saturate
+        ValType::I32 => Val::I32(n as i64 as i32),
+        ValType::I64 => Val::I64(n as i64),
+        ValType::F32 => Val::F32((n as f32).to_bits()),
+        ValType::F64 => Val::F64(n.to_bits()),
+        other => anyhow::bail!("Unsupported WASM value type: {other}"),
+    })
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn from_val(val: &Val) -> f64 {
+    match val {
+        Val::I32(i) => f64::from(*i),
+        Val::I64(i) => *i as f64,
+        Val::F32(bits) => f64::from(f32::from_bits(*bits)),
+        Val::F64(bits) => f64::from_bits(*bits),
+        _ => f64::NAN,
+    }
+}
+
+/// Trampoline for a WASM function import: calls `__nabWasmImport` in JS
+fn call_js_import(
+    mut caller: Caller<'_, HostState>,
+    slot: u32,
+    index: u32,
+    result_ty: Option<&ValType>,
+    params: &[Val],
+    results: &mut [Val],
+) -> wasmtime::Result<()> {
+    let raw = ACTIVE_CTX
+        .with(Cell::get)
+        .ok_or_else(|| anyhow::anyhow!("WASM import called outside of a JS call"))?;
+    // SAFETY: `ACTIVE_CTX` only holds a context while the native function that
+    // parked it is on the stack, and we are running synchronously beneath it.
+    let ctx = unsafe { Ctx::from_raw(raw) };
+
+    let args: Vec<f64> = params.iter().map(from_val).collect();
+    let memory = caller.data().memory;
+    let snapshot = memory.map(|m| m.data(&caller).to_vec()).unwrap_or_default();
+
+    let js_result = (|| -> rquickjs::Result<(f64, Option<Vec<u8>>)> {
+        let dispatch: Function = ctx.globals().get("__nabWasmImport")?;
+        let buffer = ArrayBuffer::new(ctx.clone(), snapshot)?;
+        let reply: Array = dispatch.call((slot, index, args, buffer))?;
+        let value: f64 = reply.get(0)?;
+        let mirror: Option<ArrayBuffer> = reply.get(1)?;
+        Ok((value, mirror.and_then(|m| m.as_bytes().map(<[u8]>::to_vec))))
+    })();
+    let (value, mirror) = js_result.map_err(|e| anyhow::anyhow!("WASM import failed: {e}"))?;
+
+    if let (Some(memory), Some(bytes)) = (memory, mirror) {
+        let data = memory.data_mut(&mut caller);
+        let len = data.len().min(bytes.len());
+        data[..len].copy_from_slice(&bytes[..len]);
+    }
+
+    if let (Some(slot), Some(ty)) = (results.first_mut(), result_ty) {
+        *slot = to_val(ty, value)?;
+    }
+    Ok(())
+}
+
+fn throw(ctx: &Ctx<'_>, msg: &str) -> rquickjs::Error {
+    Exception::throw_message(ctx, msg)
+}
+
+/// Inject a `WebAssembly` global backed by the bridge into `QuickJS` context
+pub fn inject_wasm_sync(ctx: &Context, bridge: WasmBridge) -> Result<()> {
+    ctx.with(|ctx| {
+        install_natives(&ctx, bridge)?;
+        ctx.eval::<(), _>(WEBASSEMBLY_SHIM)?;
+        Ok(())
+    })
+}
+
+/// Register the `__nabWasm*` native functions the JS shim is built on
+fn install_natives<'js>(ctx: &Ctx<'js>, bridge: WasmBridge) -> rquickjs::Result<()> {
+    let globals = ctx.globals();
+
+    let b = bridge.clone();
+    globals.set(
+        "__nabWasmCompile",
+        Function::new(
+            ctx.clone(),
+            move |ctx: Ctx<'js>, bytes: ArrayBuffer<'js>| -> rquickjs::Result<usize> {
+                let bytes = bytes.as_bytes().unwrap_or_default();
+                b.compile(bytes)
+                    .map_err(|e| throw(&ctx, &format!("CompileError: {}", b.describe_error(&e))))
+            },
+        )?,
+    )?;
+
+    let b = bridge.clone();
+    globals.set(
+        "__nabWasmImports",
+        Function::new(
+            ctx.clone(),
+            move |ctx: Ctx<'js>, id: usize| -> rquickjs::Result<String> {
+                b.imports_json(id).map_err(|e| throw(&ctx, &e.to_string()))
+            },
+        )?,
+    )?;
+
+    let b = bridge.clone();
+    globals.set(
+        "__nabWasmExports",
+        Function::new(
+            ctx.clone(),
+            move |ctx: Ctx<'js>, id: usize| -> rquickjs::Result<String> {
+                b.exports_json(id).map_err(|e| throw(&ctx, &e.to_string()))
+            },
+        )?,
+    )?;
+
+    let b = bridge.clone();
+    globals.set(
+        "__nabWasmInstantiate",
+        Function::new(
+            ctx.clone(),
+            move |ctx: Ctx<'js>, id: usize, slot: u32| -> rquickjs::Result<usize> {
+                let _active = ActiveCtx::enter(&ctx);
+                b.instantiate(id, slot)
+                    .map_err(|e| throw(&ctx, &format!("LinkError: {}", b.describe_error(&e))))
+            },
+        )?,
+    )?;
+
+    let b = bridge.clone();
+    globals.set(
+        "__nabWasmCall",
+        Function::new(
+            ctx.clone(),
+            move |ctx: Ctx<'js>, id: usize, name: String, args: Vec<f64>| {
+                let result = {
+                    let _active = ActiveCtx::enter(&ctx);
+                    b.call(id, &name, &args)
+                };
+                match result {
+                    Ok(Some(n)) => Ok(Value::new_float(ctx, n)),
+                    Ok(None) => Ok(Value::new_undefined(ctx)),
+                    Err(e) => Err(throw(
+                        &ctx,
+                        &format!("RuntimeError: {}", b.describe_error(&e)),
+                    )),
+                }
+            },
+        )?,
+    )?;
+
+    let b = bridge.clone();
+    globals.set(
+        "__nabWasmMemoryRead",
+        Function::new(
+            ctx.clone(),
+            move |ctx: Ctx<'js>, id: usize| -> rquickjs::Result<ArrayBuffer<'js>> {
+                let bytes = b.read_memory(id).map_err(|e| throw(&ctx, &e.to_string()))?;
+                ArrayBuffer::new(ctx, bytes)
+            },
+        )?,
+    )?;
+
+    let b = bridge;
+    globals.set(
+        "__nabWasmMemoryWrite",
+        Function::new(
+            ctx.clone(),
+            move |ctx: Ctx<'js>, id: usize, bytes: ArrayBuffer<'_>| -> rquickjs::Result<()> {
+                b.write_memory(id, bytes.as_bytes().unwrap_or_default())
+                    .map_err(|e| throw(&ctx, &e.to_string()))
+            },
+        )?,
+    )?;
+
+    Ok(())
+}
+
+/// JS side of the `WebAssembly` API (Module, Instance, Memory, instantiate)
+const WEBASSEMBLY_SHIM: &str = r#"
+    (function() {
+        // Import tables, indexed by the slot passed to __nabWasmInstantiate
+        var importTables = [];
+
+        function toBuffer(src) {
+            if (src instanceof ArrayBuffer) return src;
+            if (ArrayBuffer.isView(src)) {
+                return src.buffer.slice(src.byteOffset, src.byteOffset + src.byteLength);
+            }
+            throw new TypeError('WebAssembly: expected an ArrayBuffer or typed array');
+        }
+
+        function settle(fn) {
+            var P = (typeof SyncPromise !== 'undefined') ? SyncPromise : Promise;
+            try { return P.resolve(fn()); } catch (e) { return P.reject(e); }
+        }
+
+        function makeError(name) {
+            function E(message) { this.name = name; this.message = message || ''; }
+            E.prototype = Object.create(Error.prototype);
+            E.prototype.constructor = E;
+            return E;
+        }
+        var CompileError = makeError('CompileError');
+        var LinkError = makeError('LinkError');
+        var RuntimeError = makeError('RuntimeError');
+
+        function rethrow(e) {
+            var msg = String(e && e.message || e);
+            var m = /^(CompileError|LinkError|RuntimeError): ([\s\S]*)$/.exec(msg);
+            if (!m) throw e;
+            if (m[1] === 'CompileError') throw new CompileError(m[2]);
+            if (m[1] === 'LinkError') throw new LinkError(m[2]);
+            throw new RuntimeError(m[2]);
+        }
+
+        function copyInto(target, source) {
+            new Uint8Array(target).set(new Uint8Array(source).subarray(0, target.byteLength));
+        }
+
+        function Memory(instanceId) {
+            this._instance = instanceId;
+            this._buffer = __nabWasmMemoryRead(instanceId);
+        }
+        Object.defineProperty(Memory.prototype, 'buffer', {
+            get: function() { return this._buffer; }
+        });
+        Memory.prototype._push = function() {
+            __nabWasmMemoryWrite(this._instance, this._buffer);
+        };
+        Memory.prototype._refresh = function(fresh) {
+            if (fresh.byteLength === this._buffer.byteLength) {
+                copyInto(this._buffer, fresh);
+            } else {
+                // Memory grew: detach stale views like a real engine would
+                if (this._buffer.transfer) this._buffer.transfer();
+                this._buffer = fresh;
+            }
+        };
+        Memory.prototype._pull = function() {
+            this._refresh(__nabWasmMemoryRead(this._instance));
+        };
+
+        globalThis.__nabWasmImport = function(slot, index, args, snapshot) {
+            var entry = importTables[slot];
+            if (entry.memory) entry.memory._refresh(snapshot);
+            var result = entry.fns[index].apply(null, args);
+            var value = (result === undefined || result === null) ? 0 : Number(result);
+            return [value, entry.memory ? entry.memory._buffer : undefined];
+        };
+
+        function Module(bytes) {
+            try {
+                this._id = __nabWasmCompile(toBuffer(bytes));
+            } catch (e) {
+                rethrow(e);
+            }
+        }
+        Module.imports = function(module) { return JSON.parse(__nabWasmImports(module._id)); };
+        Module.exports = function(module) { return JSON.parse(__nabWasmExports(module._id)); };
+        Module.customSections = function() { return []; };
+
+        function Instance(module, importObject) {
+            var entry = { fns: [], memory: null };
+            Module.imports(module).forEach(function(imp) {
+                var ns = importObject && importObject[imp.module];
+                var fn = ns && ns[imp.name];
+                if (typeof fn !== 'function') {
+                    throw new LinkError('missing function import ' + imp.module + '.' + imp.name);
+                }
+                entry.fns.push(fn);
+            });
+            var slot = importTables.push(entry) - 1;
+
+            var id;
+            try {
+                id = __nabWasmInstantiate(module._id, slot);
+            } catch (e) {
+                rethrow(e);
+            }
+
+            var exports = {};
+            Module.exports(module).forEach(function(exp) {
+                if (exp.kind === 'memory') {
+                    entry.memory = entry.memory || new Memory(id);
+                    exports[exp.name] = entry.memory;
+                } else if (exp.kind === 'function') {
+                    exports[exp.name] = function() {
+                        var args = Array.prototype.map.call(arguments, Number);
+                        if (entry.memory) entry.memory._push();
+                        try {
+                            return __nabWasmCall(id, exp.name, args);
+                        } catch (e) {
+                            rethrow(e);
+                        } finally {
+                            if (entry.memory) entry.memory._pull();
+                        }
+                    };
+                }
+            });
+            this.exports = Object.freeze(exports);
+        }
+
+        globalThis.WebAssembly = {
+            Module: Module,
+            Instance: Instance,
+            Memory: function() {
+                throw new TypeError('WebAssembly.Memory: host-created memories are not supported');
+            },
+            CompileError: CompileError,
+            LinkError: LinkError,
+            RuntimeError: RuntimeError,
+            validate: function(bytes) {
+                try { new Module(bytes); return true; } catch (e) { return false; }
+            },
+            compile: function(bytes) {
+                return settle(function() { return new Module(bytes); });
+            },
+            instantiate: function(source, importObject) {
+                return settle(function() {
+                    if (source instanceof Module) return new Instance(source, importObject);
+                    var module = new Module(source);
+                    return { module: module, instance: new Instance(module, importObject) };
+                });
+            },
+            instantiateStreaming: function() {
+                return settle(function() {
+                    throw new TypeError('WebAssembly.instantiateStreaming is not supported');
+                });
+            }
+        };
+        if (typeof window !== 'undefined') window.WebAssembly = globalThis.WebAssembly;
+    })();
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JsEngine;
+
+    /// (module (import "env" "twice" (func $twice (param i32) (result i32)))
+    ///   (memory (export "memory") 1)
+    ///   (func (export "add") (param i32 i32) (result i32) local.get 0 local.get 1 i32.add)
+    ///   (func (export "twice") (param i32) (result i32) local.get 0 call $twice)
+    ///   (func (export "load") (param i32) (result i32) local.get 0 i32.load8_u)
+    ///   (func (export "spin") (loop br 0)))
+    const TEST_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic + version
+        0x01, 0x0f, 0x03, // type section: 3 types
+        0x60, 0x01, 0x7f, 0x01, 0x7f, // (i32) -> i32
+        0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // (i32, i32) -> i32
+        0x60, 0x00, 0x00, // () -> ()
+        0x02, 0x0d, 0x01, // import section: 1 import
+        0x03, b'e', b'n', b'v', 0x05, b't', b'w', b'i', b'c', b'e', 0x00, 0x00, 0x03, 0x05, 0x04,
+        0x01, 0x00, 0x00, 0x02, // function section: 4 funcs
+        0x05, 0x03, 0x01, 0x00, 0x01, // memory section: 1 page
+        0x07, 0x26, 0x05, // export section: 5 exports
+        0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00, 0x03, b'a', b'd', b'd', 0x00, 0x01,
+        0x05, b't', b'w', b'i', b'c', b'e', 0x00, 0x02, 0x04, b'l', b'o', b'a', b'd', 0x00, 0x03,
+        0x04, b's', b'p', b'i', b'n', 0x00, 0x04, 0x0a, 0x20, 0x04, // code section: 4 bodies
+        0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // add
+        0x06, 0x00, 0x20, 0x00, 0x10, 0x00, 0x0b, // twice
+        0x07, 0x00, 0x20, 0x00, 0x2d, 0x00, 0x00, 0x0b, // load
+        0x07, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x0b, // spin
+    ];
+
+    fn engine_with_wasm(config: WasmConfig) -> (JsEngine, WasmBridge) {
+        let engine = JsEngine::new().unwrap();
+        let bridge = WasmBridge::new(config).unwrap();
+        inject_wasm_sync(engine.context(), bridge.clone()).unwrap();
+
+        let bytes: Vec<String> = TEST_WASM.iter().map(u8::to_string).collect();
+        engine
+            .eval(&format!(
+                "var bytes = new Uint8Array([{}]);
+                 var inst = new WebAssembly.Instance(new WebAssembly.Module(bytes), {{
+                     env: {{ twice: function(x) {{ return x * 2; }} }}
+                 }});",
+                bytes.join(",")
+            ))
+            .unwrap();
+        (engine, bridge)
+    }
+
+    #[test]
+    fn test_exported_function_call() {
+        let (engine, bridge) = engine_with_wasm(WasmConfig::default());
+        assert_eq!(engine.eval("inst.exports.add(40, 2)").unwrap(), "42");
+        assert_eq!(bridge.module_count(), 1);
+        assert_eq!(bridge.instance_count(), 1);
+    }
+
+    #[test]
+    fn test_import_callback() {
+        let (engine, _) = engine_with_wasm(WasmConfig::default());
+        assert_eq!(engine.eval("inst.exports.twice(21)").unwrap(), "42");
+    }
+
+    #[test]
+    fn test_memory_mirroring() {
+        let (engine, _) = engine_with_wasm(WasmConfig::default());
+        let result = engine
+            .eval(
+                "var view = new Uint8Array(inst.exports.memory.buffer);
+                 view[7] = 99;
+                 inst.exports.load(7);",
+            )
+            .unwrap();
+        assert_eq!(result, "99");
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let (engine, _) = engine_with_wasm(WasmConfig {
+            call_timeout: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let result = engine
+            .eval("try { inst.exports.spin(); 'finished' } catch (e) { e.name + ': ' + e.message }")
+            .unwrap();
+        assert!(result.starts_with("RuntimeError"), "got: {result}");
+        assert!(result.contains("50ms"), "got: {result}");
+    }
+
+    #[test]
+    fn test_invalid_module() {
+        let (engine, _) = engine_with_wasm(WasmConfig::default());
+        assert_eq!(
+            engine
+                .eval("WebAssembly.validate(new Uint8Array([1, 2, 3]))")
+                .unwrap(),
+            "false"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Workspaces
+//!
+//! `--workspace client-a` (or `NAB_WORKSPACE=client-a`) keeps all of nab's
+//! state for one client apart from every other: the config file, login
+//! sessions and other secrets, recipes, persona (`--revisit`) caches, and the
+//! browser version cache live in `~/.config/nab/workspaces/client-a/` instead
+//! of `~/.config/nab/` and `~/.cache/nab/`. Keychain entries use the service
+//! name `nab:client-a`.
+//!
+//! Since a workspace is one directory, `nab workspace archive` and
+//! `nab workspace rm` hand over or remove a client's state in one step;
+//! `rm` deletes the workspace's Keychain or Secret Service entries first.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context, Result};
+
+use crate::epub::ZipWriter;
+use crate::secrets::SecretStore;
+
+/// Environment variable selecting a workspace
+pub const WORKSPACE_ENV: &str = "NAB_WORKSPACE";
+
+/// Workspace chosen with `--workspace`
+static SELECTED: OnceLock<String> = OnceLock::new();
+
+/// Use workspace `name` for the rest of the process
+pub fn select(name: &str) -> Result<()> {
+    validate(name)?;
+    if SELECTED.set(name.to_string()).is_err() && current().as_deref() != Some(name) {
+        bail!("Another workspace is already selected");
+    }
+    Ok(())
+}
+
+/// The selected workspace: `--workspace`, else `NAB_WORKSPACE`
+#[must_use]
+pub fn current() -> Option<String> {
+    SELECTED.get().cloned().or_else(|| {
+        std::env::var(WORKSPACE_ENV)
+            .ok()
+            .filter(|name| validate(name).is_ok())
+    })
+}
+
+/// Directory of nab's settings and secrets (`~/.config/nab`, or the workspace)
+#[must_use]
+pub fn config_dir() -> PathBuf {
+    match current() {
+        Some(name) => workspaces_dir().join(name),
+        None => base_config_dir(),
+    }
+}
+
+/// Directory of nab's caches (`~/.cache/nab`, or the workspace's `cache/`)
+#[must_use]
+pub fn cache_dir() -> PathBuf {
+    match current() {
+        Some(name) => workspaces_dir().join(name).join("cache"),
+        None => dirs::cache_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("nab"),
+    }
+}
+
+/// Directory holding all workspaces
+#[must_use]
+pub fn workspaces_dir() -> PathBuf {
+    base_config_dir().join("workspaces")
+}
+
+fn base_config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("nab")
+}
+
+/// Directory of workspace `name`
+pub fn path(name: &str) -> Result<PathBuf> {
+    validate(name)?;
+    Ok(workspaces_dir().join(name))
+}
+
+/// Names of the existing workspaces, sorted
+pub fn list() -> Result<Vec<String>> {
+    let dir = workspaces_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names: Vec<String> = std::fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            (entry.path().is_dir() && validate(&name).is_ok()).then_some(name)
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Delete workspace `name` and its secrets; returns whether it existed
+///
+/// Secrets outside the directory (Keychain, Secret Service) go first; if
+/// they can't all be deleted, the workspace is kept so `rm` can be retried.
+/// The directory is renamed out of the way first, so other nab processes
+/// never see a half-deleted workspace.
+pub fn remove(name: &str) -> Result<bool> {
+    let dir = path(name)?;
+    let store = SecretStore::detect();
+    let secrets = store.clear_workspace(name).with_context(|| {
+        format!(
+            "Failed to delete the {} entries of workspace '{name}'; the workspace was kept",
+            store.name()
+        )
+    })?;
+    if !dir.exists() {
+        return Ok(secrets > 0);
+    }
+    let doomed = workspaces_dir().join(format!(".{name}.removing-{}", std::process::id()));
+    std::fs::rename(&dir, &doomed)
+        .with_context(|| format!("Failed to remove {}", dir.display()))?;
+    std::fs::remove_dir_all(&doomed)
+        .with_context(|| format!("Failed to remove {}", doomed.display()))?;
+    Ok(true)
+}
+
+/// Zip every file of workspace `name`; returns the archive and its file count
+///
+/// Keychain entries aren't files; use the file secret store
+/// (`NAB_SECRET_BACKEND=file`) for workspaces that will be archived.
+pub fn archive(name: &str) -> Result<(Vec<u8>, usize)> {
+    let dir = path(name)?;
+    if !dir.is_dir() {
+        bail!("No workspace '{name}'");
+    }
+    let mut files = Vec::new();
+    collect_files(&dir, &mut files)?;
+    files.sort();
+
+    let mut zip = ZipWriter::default();
+    for file in &files {
+        let relative = file.strip_prefix(&dir).unwrap_or(file);
+        let entry = format!("{name}/{}", relative.to_string_lossy().replace('\\', "/"));
+        let contents =
+            std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        zip.add(&entry, &contents);
+    }
+    Ok((zip.finish(), files.len()))
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Workspace names are directory names: letters, digits, '-', '_', and '.'
+fn validate(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        bail!("Invalid workspace '{name}': use letters, digits, '-', '_', and '.'");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        for good in ["client-a", "acme_2026", "v1.2"] {
+            assert!(validate(good).is_ok(), "{good}");
+        }
+        for bad in ["", ".hidden", "../etc", "a/b", "x y"] {
+            assert!(validate(bad).is_err(), "{bad:?}");
+        }
+    }
+}
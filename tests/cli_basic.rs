@@ -23,13 +23,26 @@ fn help_flag_shows_usage() {
         .success()
         .stdout(predicate::str::contains("Usage: nab"))
         .stdout(predicate::str::contains("fetch"))
-        .stdout(predicate::str::contains("spa"))
-        .stdout(predicate::str::contains("stream"))
-        .stdout(predicate::str::contains("analyze"))
-        .stdout(predicate::str::contains("annotate"))
         .stdout(predicate::str::contains("otp"));
 }
 
+#[test]
+fn help_lists_only_compiled_in_commands() {
+    let output = nab().arg("--help").output().unwrap();
+    let help = String::from_utf8(output.stdout).unwrap();
+    for (command, built) in [
+        ("spa", cfg!(feature = "spa")),
+        ("stream", cfg!(feature = "stream")),
+        ("analyze", cfg!(feature = "analyze")),
+        ("annotate", cfg!(feature = "analyze")),
+    ] {
+        let listed = help
+            .lines()
+            .any(|line| line.trim_start().starts_with(&format!("{command} ")));
+        assert_eq!(listed, built, "{command} in:\n{help}");
+    }
+}
+
 #[test]
 fn short_help_flag_shows_usage() {
     nab()
@@ -86,22 +99,28 @@ fn fetch_help() {
         .stdout(predicate::str::contains("<URL>"))
         .stdout(predicate::str::contains("--cookies"))
         .stdout(predicate::str::contains("--raw-html"))
-        .stdout(predicate::str::contains("--method"));
+        .stdout(predicate::str::contains("--method"))
+        .stdout(predicate::str::contains("epub"));
 }
 
 #[test]
+#[cfg(feature = "spa")]
 fn spa_help() {
     nab()
         .args(["spa", "--help"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Extract data from JavaScript-heavy SPA pages"))
+        .stdout(predicate::str::contains(
+            "Extract data from JavaScript-heavy SPA pages",
+        ))
         .stdout(predicate::str::contains("<URL>"))
         .stdout(predicate::str::contains("--extract"))
-        .stdout(predicate::str::contains("--summary"));
+        .stdout(predicate::str::contains("--summary"))
+        .stdout(predicate::str::contains("--cert-password"));
 }
 
 #[test]
+#[cfg(feature = "stream")]
 fn stream_help() {
     nab()
         .args(["stream", "--help"])
@@ -110,10 +129,13 @@ fn stream_help() {
         .stdout(predicate::str::contains("Stream media"))
         .stdout(predicate::str::contains("<SOURCE>"))
         .stdout(predicate::str::contains("<ID>"))
-        .stdout(predicate::str::contains("--quality"));
+        .stdout(predicate::str::contains("--quality"))
+        .stdout(predicate::str::contains("--audio-lang"))
+        .stdout(predicate::str::contains("--container"));
 }
 
 #[test]
+#[cfg(feature = "analyze")]
 fn analyze_help() {
     nab()
         .args(["analyze", "--help"])
@@ -122,10 +144,52 @@ fn analyze_help() {
         .stdout(predicate::str::contains("Analyze video"))
         .stdout(predicate::str::contains("<VIDEO>"))
         .stdout(predicate::str::contains("--audio-only"))
-        .stdout(predicate::str::contains("--diarize"));
+        .stdout(predicate::str::contains("--diarize"))
+        .stdout(predicate::str::contains("--integrity"))
+        .stdout(predicate::str::contains("--chapters"))
+        .stdout(predicate::str::contains("--storyboard"));
 }
 
 #[test]
+#[cfg(feature = "analyze")]
+fn analyze_integrity_reads_transport_streams() {
+    let dir = std::env::temp_dir().join(format!("nab-cli-integrity-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // Three null packets, then half of one
+    let mut capture = Vec::new();
+    for _ in 0..3 {
+        capture.extend_from_slice(&[0x47, 0x1f, 0xff, 0x10]);
+        capture.extend_from_slice(&[0xff; 184]);
+    }
+    capture.extend_from_slice(&[0x47, 0x1f, 0xff, 0x10]);
+    capture.extend_from_slice(&[0xff; 90]);
+    let ts = dir.join("capture.ts");
+    std::fs::write(&ts, &capture).unwrap();
+    let output = nab()
+        .args(["analyze", "--integrity"])
+        .arg(&ts)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["packets"], 3);
+    assert_eq!(report["issues"][0]["kind"], "sync-loss");
+    assert_eq!(report["issues"][0]["byte_start"], 564);
+    assert_eq!(report["issues"][0]["byte_end"], 658);
+
+    let text = dir.join("notes.txt");
+    std::fs::write(&text, "not video\n".repeat(500)).unwrap();
+    nab()
+        .args(["analyze", "--integrity"])
+        .arg(&text)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not an MPEG transport stream"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+#[cfg(feature = "analyze")]
 fn annotate_help() {
     nab()
         .args(["annotate", "--help"])
@@ -134,7 +198,10 @@ fn annotate_help() {
         .stdout(predicate::str::contains("Add overlays to video"))
         .stdout(predicate::str::contains("<VIDEO>"))
         .stdout(predicate::str::contains("--subtitles"))
-        .stdout(predicate::str::contains("--speaker-labels"));
+        .stdout(predicate::str::contains("--speaker-labels"))
+        .stdout(predicate::str::contains("--chapters"))
+        .stdout(predicate::str::contains("--normalize-loudness"))
+        .stdout(predicate::str::contains("--extract-audio"));
 }
 
 #[test]
@@ -154,7 +221,18 @@ fn fingerprint_help() {
         .assert()
         .success()
         .stdout(predicate::str::contains("browser fingerprint"))
-        .stdout(predicate::str::contains("--count"));
+        .stdout(predicate::str::contains("--count"))
+        .stdout(predicate::str::contains("--seed"));
+}
+
+#[test]
+fn fingerprint_verify_help() {
+    nab()
+        .args(["fingerprint", "verify", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--profile"))
+        .stdout(predicate::str::contains("--endpoint"));
 }
 
 #[test]
@@ -168,6 +246,136 @@ fn bench_help() {
         .stdout(predicate::str::contains("--iterations"));
 }
 
+#[test]
+fn compile_help() {
+    nab()
+        .args(["compile", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Compile multiple URLs"))
+        .stdout(predicate::str::contains("<INPUT>"))
+        .stdout(predicate::str::contains("--output"));
+}
+
+#[test]
+fn compile_empty_url_list_fails() {
+    nab()
+        .args(["compile", "-", "--output", "book.epub"])
+        .write_stdin("# nothing here\n\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No URLs found"));
+}
+
+#[test]
+fn extract_help() {
+    nab()
+        .args(["extract", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--preset"))
+        .stdout(predicate::str::contains("product"))
+        .stdout(predicate::str::contains("article"))
+        .stdout(predicate::str::contains("job"))
+        .stdout(predicate::str::contains("listing"));
+}
+
+#[test]
+fn extract_rejects_unknown_preset() {
+    nab()
+        .args(["extract", "--preset", "recipe", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'recipe'"));
+}
+
+#[test]
+fn batch_help() {
+    nab()
+        .args(["batch", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--per-host-concurrency"))
+        .stdout(predicate::str::contains("--global-concurrency"))
+        .stdout(predicate::str::contains("--user"))
+        .stdout(predicate::str::contains("--cacert"))
+        .stdout(predicate::str::contains("--pin"))
+        .stdout(predicate::str::contains("--proxy-chain"))
+        .stdout(predicate::str::contains("--referer"));
+}
+
+#[test]
+fn batch_empty_url_list_fails() {
+    nab()
+        .args(["batch", "-"])
+        .write_stdin("# nothing here\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No URLs found"));
+}
+
+#[test]
+fn crawl_help() {
+    nab()
+        .args(["crawl", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--max-depth"))
+        .stdout(predicate::str::contains("--delay-ms"))
+        .stdout(predicate::str::contains("--state"))
+        .stdout(predicate::str::contains("--include"))
+        .stdout(predicate::str::contains("--max-pages"))
+        .stdout(predicate::str::contains("--revisit"));
+}
+
+#[test]
+fn crawl_rejects_invalid_regex_filter() {
+    nab()
+        .args(["crawl", "https://example.com", "--include", "regex:("])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid regex filter"));
+}
+
+#[test]
+fn crawl_requires_seed() {
+    nab().arg("crawl").assert().failure();
+}
+
+#[test]
+fn crawl_shared_frontier_excludes_state() {
+    nab()
+        .args(["crawl", "https://example.com/", "--frontier-redis", "redis://localhost/"])
+        .args(["--state", "crawl.json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    nab()
+        .args(["crawl", "https://example.com/", "--crawl-name", "docs"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--frontier-redis"));
+}
+
+#[test]
+fn login_help() {
+    nab()
+        .args(["login", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("<RECIPE>"))
+        .stdout(predicate::str::contains("--name"));
+}
+
+#[test]
+fn login_missing_recipe_fails() {
+    nab()
+        .args(["login", "/nonexistent/recipe.json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Login recipe not found"));
+}
+
 #[test]
 fn auth_help() {
     nab()
@@ -190,6 +398,7 @@ fn fetch_missing_url_fails() {
 }
 
 #[test]
+#[cfg(feature = "spa")]
 fn spa_missing_url_fails() {
     nab()
         .arg("spa")
@@ -199,6 +408,7 @@ fn spa_missing_url_fails() {
 }
 
 #[test]
+#[cfg(feature = "stream")]
 fn stream_missing_args_fails() {
     nab()
         .arg("stream")
@@ -208,6 +418,7 @@ fn stream_missing_args_fails() {
 }
 
 #[test]
+#[cfg(feature = "analyze")]
 fn analyze_missing_video_fails() {
     nab()
         .arg("analyze")
@@ -217,6 +428,7 @@ fn analyze_missing_video_fails() {
 }
 
 #[test]
+#[cfg(feature = "analyze")]
 fn annotate_missing_args_fails() {
     nab()
         .arg("annotate")
@@ -264,6 +476,51 @@ fn fetch_invalid_format_fails() {
 }
 
 #[test]
+fn fetch_invalid_jq_filter_fails() {
+    nab()
+        .args(["fetch", "--jq", ".items[", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid jq filter"));
+}
+
+#[test]
+fn curl_emits_equivalent_command() {
+    nab()
+        .args([
+            "curl",
+            "curl -X PUT 'http://127.0.0.1:9/items?id=1' -H 'x-api-key: k1' --data-raw '{\"a\":1}'",
+            "--emit-curl",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "curl 'http://127.0.0.1:9/items?id=1' \\\n  -X PUT",
+        ))
+        .stdout(predicate::str::contains("-H 'x-api-key: k1'"))
+        .stdout(predicate::str::contains("--data-raw '{\"a\":1}'"));
+}
+
+#[test]
+fn curl_unsupported_option_fails() {
+    nab()
+        .args(["curl", "curl -F file=@a.txt https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported curl option -F"));
+}
+
+#[test]
+fn fetch_jq_needs_json_format() {
+    nab()
+        .args(["fetch", "--jq", ".status", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--format json"));
+}
+
+#[test]
+#[cfg(feature = "analyze")]
 fn analyze_invalid_format_fails() {
     nab()
         .args(["analyze", "--format", "csv", "video.mp4"])
@@ -273,6 +530,7 @@ fn analyze_invalid_format_fails() {
 }
 
 #[test]
+#[cfg(feature = "analyze")]
 fn annotate_invalid_style_fails() {
     nab()
         .args(["annotate", "--style", "neon", "in.mp4", "out.mp4"])
@@ -280,3 +538,275 @@ fn annotate_invalid_style_fails() {
         .failure()
         .stderr(predicate::str::contains("invalid value"));
 }
+
+// ─── Workspaces ──────────────────────────────────────────────────────────────
+
+#[test]
+fn workspaces_keep_state_apart() {
+    let config = std::env::temp_dir().join(format!("nab-cli-workspace-{}", std::process::id()));
+    let run = |args: &[&str]| {
+        let mut cmd = nab();
+        cmd.args(args)
+            .env("XDG_CONFIG_HOME", &config)
+            .env("NAB_SECRET_BACKEND", "file")
+            .env_remove("NAB_WORKSPACE");
+        cmd
+    };
+
+    run(&[
+        "--workspace",
+        "client-a",
+        "secrets",
+        "set",
+        "token",
+        "a-only",
+    ])
+    .assert()
+    .success();
+    run(&["secrets", "get", "token"]).assert().failure();
+    run(&["secrets", "get", "token"])
+        .env("NAB_WORKSPACE", "client-a")
+        .assert()
+        .success()
+        .stdout("a-only\n");
+
+    run(&["workspace", "list"])
+        .assert()
+        .success()
+        .stdout("  client-a\n");
+    run(&["workspace", "path", "client-a"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("nab/workspaces/client-a"));
+
+    let zip = config.join("client-a.zip");
+    run(&[
+        "workspace",
+        "archive",
+        "client-a",
+        "-o",
+        zip.to_str().unwrap(),
+    ])
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("client-a"));
+    assert!(std::fs::read(&zip).unwrap().starts_with(b"PK"));
+
+    run(&["workspace", "rm", "client-a"]).assert().success();
+    run(&["workspace", "rm", "client-a"]).assert().failure();
+    run(&["--workspace", "../etc", "workspace", "list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid workspace"));
+    let _ = std::fs::remove_dir_all(&config);
+}
+
+#[cfg(unix)]
+#[test]
+fn workspace_rm_deletes_its_secret_service_entries() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = std::env::temp_dir().join(format!("nab-cli-workspace-ss-{}", std::process::id()));
+    let (bin, store, config) = (root.join("bin"), root.join("store"), root.join("config"));
+    std::fs::create_dir_all(&bin).unwrap();
+    std::fs::create_dir_all(&store).unwrap();
+    // Stand-in for secret-tool keeping each entry in a `<service>#<account>` file
+    let secret_tool = bin.join("secret-tool");
+    std::fs::write(
+        &secret_tool,
+        r#"#!/bin/sh
+cmd=$1; shift
+[ "$1" = "--label" ] && shift 2
+[ "$1" = "--all" ] && shift
+entry="$FAKE_SECRETS/$2#"
+case $cmd in
+    store) cat > "$entry$4" ;;
+    lookup) cat "$entry$4" 2>/dev/null ;;
+    clear) if [ -n "$4" ]; then rm -f "$entry$4"; else rm -f "$entry"*; fi ;;
+    search) for f in "$entry"*; do [ -e "$f" ] && echo "[/fake/${f##*/}]"; done ;;
+esac
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&secret_tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let run = |workspace: &str, args: &[&str]| {
+        let mut cmd = nab();
+        cmd.args(["--workspace", workspace])
+            .args(args)
+            .env("PATH", &path)
+            .env("FAKE_SECRETS", &store)
+            .env("XDG_CONFIG_HOME", &config)
+            .env("NAB_SECRET_BACKEND", "secret-service")
+            .env_remove("NAB_WORKSPACE");
+        cmd
+    };
+
+    for workspace in ["client-b", "client-c"] {
+        run(workspace, &["secrets", "set", "token", "t"])
+            .assert()
+            .success();
+        run(workspace, &["secrets", "set", "cookie", "c"])
+            .assert()
+            .success();
+    }
+    std::fs::create_dir_all(config.join("nab/workspaces/client-b")).unwrap();
+
+    run("client-c", &["workspace", "rm", "client-b"])
+        .assert()
+        .success();
+    assert!(!config.join("nab/workspaces/client-b").exists());
+    run("client-b", &["secrets", "get", "token"])
+        .assert()
+        .failure();
+    run("client-b", &["secrets", "get", "cookie"])
+        .assert()
+        .failure();
+    run("client-c", &["secrets", "get", "token"])
+        .assert()
+        .success()
+        .stdout("t\n");
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+// ─── State files ─────────────────────────────────────────────────────────────
+
+#[test]
+fn crawl_state_is_locked_while_in_use() {
+    let state =
+        std::env::temp_dir().join(format!("nab-cli-crawl-lock-{}.json", std::process::id()));
+    let held = nab::state::lock(&state, std::time::Duration::ZERO).unwrap();
+
+    nab()
+        .args(["crawl", "https://example.invalid/", "--state"])
+        .arg(&state)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("in use by another nab process"));
+    let _ = std::fs::remove_file(held.path());
+}
+
+// ─── Proxy check ─────────────────────────────────────────────────────────────
+
+#[test]
+fn proxy_check_needs_a_proxy_list() {
+    let list = std::env::temp_dir().join(format!("nab-cli-proxies-{}.txt", std::process::id()));
+    std::fs::write(&list, "# nothing yet\n\n").unwrap();
+    nab()
+        .args(["proxy", "check", "--file"])
+        .arg(&list)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No proxies in"));
+    let _ = std::fs::remove_file(&list);
+}
+
+#[test]
+fn geo_needs_a_tagged_proxy() {
+    let config = std::env::temp_dir().join(format!("nab-cli-geo-{}.json", std::process::id()));
+    std::fs::write(
+        &config,
+        r#"{"proxy_pool": [{"url": "http://127.0.0.1:9", "country": "fr"}]}"#,
+    )
+    .unwrap();
+    nab()
+        .env("NAB_CONFIG", &config)
+        .args(["fetch", "--geo", "de", "https://example.com/"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No proxy for --geo DE"));
+    nab()
+        .env("NAB_CONFIG", &config)
+        .args(["fetch", "--geo", "atlantis", "https://example.com/"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown country 'atlantis'"));
+    let _ = std::fs::remove_file(&config);
+}
+
+#[test]
+fn compare_needs_two_valid_variants() {
+    nab()
+        .args(["compare", "https://example.com/", "--variants", "geo=us"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("at least two variants"));
+    nab()
+        .args([
+            "compare",
+            "https://example.com/",
+            "--variants",
+            "colour=red,blue",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown variant kind 'colour'"));
+}
+
+#[test]
+fn cache_is_capped_and_cleared() {
+    let home = std::env::temp_dir().join(format!("nab-cli-cache-{}", std::process::id()));
+    let revisit = home.join("nab/revisit/reader");
+    std::fs::create_dir_all(&revisit).unwrap();
+    for name in ["a.json", "b.json", "c.json"] {
+        std::fs::write(revisit.join(name), [b'x'; 1000]).unwrap();
+    }
+    let run = |args: &[&str]| {
+        let mut cmd = nab();
+        cmd.args(args)
+            .env("XDG_CACHE_HOME", &home)
+            .env_remove("NAB_WORKSPACE");
+        cmd
+    };
+
+    run(&["cache", "stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("revisit/").and(predicate::str::contains("3.0 KB")));
+    run(&["--cache-max-size", "2.5KB", "cache", "stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2.0 KB  2 files"));
+    run(&["cache", "clear"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Cleared 2 files"));
+    run(&["--cache-max-size", "lots", "cache", "stats"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid size 'lots'"));
+    let _ = std::fs::remove_dir_all(&home);
+}
+
+#[test]
+fn self_update_can_be_disabled() {
+    let config = std::env::temp_dir().join(format!("nab-cli-self-{}.json", std::process::id()));
+    std::fs::write(&config, r#"{"self_update": {"disabled": true}}"#).unwrap();
+    nab()
+        .env("NAB_CONFIG", &config)
+        .args(["self", "update", "--check"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("self_update.disabled"));
+    nab()
+        .args(["self", "update", "--channel", "nightly"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown channel 'nightly'"));
+    let _ = std::fs::remove_file(&config);
+}
+
+#[test]
+fn audit_tls_reports_unreachable_hosts() {
+    nab()
+        .args(["audit", "tls", "127.0.0.1:1", "--timeout", "5"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("❌ 127.0.0.1:1"))
+        .stderr(predicate::str::contains("1 of 1 handshakes failed"));
+    nab()
+        .args(["audit", "tls", "example.com", "--proxy", "https://proxy"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Only http, socks5, and socks5h"));
+}
@@ -342,6 +342,206 @@ fn fetch_cookies_flag_accepts_browser_names() {
     }
 }
 
+#[test]
+fn fetch_consent_rejects_unknown_mode() {
+    nab()
+        .args(["fetch", "--consent", "maybe", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("accept"));
+}
+
+#[test]
+fn fetch_gated_retry_accepts_known_crawlers() {
+    for crawler in &["googlebot", "bingbot"] {
+        nab()
+            .args(["fetch", "--gated-retry", crawler, "--help"])
+            .assert()
+            .success();
+    }
+    nab()
+        .args(["fetch", "--gated-retry", "curl", "https://example.com"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn fetch_summarize_without_config_fails_early() {
+    nab()
+        .env("NAB_CONFIG", "/nonexistent/nab/config.json")
+        .args(["fetch", "--summarize", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No summarizer configured"));
+}
+
+#[test]
+fn fetch_translate_without_config_fails_early() {
+    nab()
+        .env("NAB_CONFIG", "/nonexistent/nab/config.json")
+        .args(["fetch", "--translate", "en", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No translation endpoint"));
+}
+
+#[test]
+fn fetch_unknown_oauth2_client_fails_early() {
+    nab()
+        .env("NAB_CONFIG", "/nonexistent/nab/config.json")
+        .args(["fetch", "--auth", "oauth2:reports", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No OAuth2 client 'reports'"));
+}
+
+#[test]
+fn fetch_help_shows_conditional_flags() {
+    nab()
+        .args(["fetch", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--etag"))
+        .stdout(predicate::str::contains("--if-modified-since"))
+        .stdout(predicate::str::contains("--auth"))
+        .stdout(predicate::str::contains("--user"))
+        .stdout(predicate::str::contains("--warm-resources"))
+        .stdout(predicate::str::contains("--har"))
+        .stdout(predicate::str::contains("--solve-js-challenge"))
+        .stdout(predicate::str::contains("--captcha-solver"))
+        .stdout(predicate::str::contains("--referer"))
+        .stdout(predicate::str::contains("--sec-fetch"));
+}
+
+#[test]
+fn fetch_rejects_unknown_captcha_solver() {
+    nab()
+        .args([
+            "fetch",
+            "--captcha-solver",
+            "2captcha:key",
+            "https://example.com",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown CAPTCHA solver"));
+}
+
+#[test]
+fn fetch_rejects_unknown_resource_kind() {
+    nab()
+        .args(["fetch", "--warm-resources=fonts", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown resource kind 'fonts'"));
+}
+
+#[test]
+fn fetch_warm_count_requires_warm_resources() {
+    nab()
+        .args(["fetch", "--warm-count", "2", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--warm-resources"));
+}
+
+#[test]
+fn fetch_user_without_password_fails() {
+    nab()
+        .args(["fetch", "--user", "ada", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Expected USER:PASSWORD"));
+}
+
+#[test]
+fn fetch_user_conflicts_with_auth() {
+    nab()
+        .args([
+            "fetch",
+            "--user",
+            "ada:secret",
+            "--auth",
+            "work",
+            "https://example.com",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn fetch_missing_client_cert_fails_early() {
+    nab()
+        .args([
+            "fetch",
+            "--cert",
+            "/nonexistent/client.pem",
+            "https://example.com",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Failed to read /nonexistent/client.pem",
+        ));
+}
+
+#[test]
+fn fetch_rejects_malformed_pin() {
+    nab()
+        .args(["fetch", "--pin", "deadbeef", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sha256//<base64 SPKI hash>"));
+}
+
+#[test]
+fn fetch_rejects_long_proxy_chain() {
+    let chain = ["socks5h://127.0.0.1:1080"; 9].join(",");
+    nab()
+        .args(["fetch", "--proxy-chain", &chain, "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("1 to 8 proxies"));
+}
+
+#[test]
+fn fetch_rejects_unsupported_chain_hop() {
+    nab()
+        .args([
+            "fetch",
+            "--proxy-chain",
+            "socks5h://127.0.0.1:1080,ftp://egress:21",
+            "https://example.com",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported proxy scheme 'ftp'"));
+}
+
+#[test]
+fn fetch_key_requires_cert() {
+    nab()
+        .args(["fetch", "--key", "client.key", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--cert"));
+}
+
+#[test]
+fn fetch_translate_conflicts_with_raw_html() {
+    nab()
+        .args([
+            "fetch",
+            "--translate",
+            "en",
+            "--raw-html",
+            "https://example.com",
+        ])
+        .assert()
+        .failure();
+}
+
 // ─── No-redirect flag ────────────────────────────────────────────────────────
 
 #[test]
@@ -395,3 +595,12 @@ fn fetch_post_with_data() {
         // httpbin echoes posted data; the json field will contain parsed key/value
         .stdout(predicate::str::contains(r#""key": "value""#));
 }
+
+#[test]
+fn fetch_rejects_invalid_referer() {
+    nab()
+        .args(["fetch", "--referer", "sometimes", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid referer"));
+}
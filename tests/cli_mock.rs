@@ -0,0 +1,1509 @@
+//! Integration tests for `nab fetch` against `nab mock-server`.
+//!
+//! The server serves `tests/fixtures/mock/`, so redirects, compression,
+//! latency, and challenge pages are covered without network access.
+
+#![cfg(feature = "mock-server")]
+#![allow(deprecated)] // cargo_bin deprecation — replacement not yet stable
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Stdio};
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+
+/// Helper: get a Command for the `nab` binary.
+fn nab() -> Command {
+    Command::cargo_bin("nab").expect("binary 'nab' should be built")
+}
+
+/// `nab mock-server` on a free port, killed on drop
+struct MockServer {
+    child: Child,
+    url: String,
+}
+
+impl MockServer {
+    fn start() -> Self {
+        Self::start_with(&[])
+    }
+
+    /// Started with extra `nab mock-server` flags
+    fn start_with(args: &[&str]) -> Self {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/mock");
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("nab"))
+            .args(["mock-server", "--fixtures", fixtures])
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("mock server should start");
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let url = line
+            .trim()
+            .strip_prefix("Listening on ")
+            .expect("mock server should print its address")
+            .to_string();
+        Self { child, url }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.url)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn fetch_json(args: &[&str]) -> serde_json::Value {
+    let output = nab()
+        .args(["fetch", "--cookies", "none", "--format", "json"])
+        .args(args)
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = stdout.lines().last().unwrap();
+    serde_json::from_str(line).unwrap()
+}
+
+#[test]
+fn mock_server_is_hidden_from_help() {
+    nab()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mock-server").not());
+}
+
+#[test]
+fn fetch_page_from_mock_server() {
+    let server = MockServer::start();
+    nab()
+        .args(["fetch", "--cookies", "none", "--body", &server.url("/")])
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Status: 200"))
+        .stdout(predicate::str::contains("Mock Home"));
+
+    nab()
+        .args(["fetch", "--cookies", "none", "--format", "compact"])
+        .arg(server.url("/missing"))
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("404 "));
+}
+
+#[test]
+fn fetch_follows_redirects_and_decodes_gzip() {
+    let server = MockServer::start();
+    let redirected = fetch_json(&[&server.url("/old")]);
+    assert_eq!(redirected["status"], 200);
+    assert_eq!(
+        redirected["timings"]["redirects"].as_array().unwrap().len(),
+        1
+    );
+
+    nab()
+        .args(["fetch", "--cookies", "none", "--body", &server.url("/gz")])
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Mock Home"));
+}
+
+#[test]
+fn fetch_json_through_jq() {
+    let server = MockServer::start();
+    nab()
+        .args(["fetch", "--cookies", "none", "--format", "json"])
+        .args(["--jq", "{status, gated: .content_gated}"])
+        .arg(server.url("/"))
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""gated":false"#));
+}
+
+#[test]
+fn fetch_and_batch_report_links() {
+    let server = MockServer::start();
+    let page = server.url("/links.html");
+    let fetched = fetch_json(&["--links", &page]);
+    let links = fetched["links"].as_array().unwrap();
+    assert_eq!(links.len(), 3, "{links:?}");
+    assert_eq!(links[1]["url"], server.url("/old").as_str());
+    assert_eq!(links[1]["text"], "The old home page");
+    assert_eq!(links[1]["external"], false);
+    assert_eq!(links[1]["nofollow"], false);
+
+    let output = nab()
+        .args(["batch", "-", "--links"])
+        .write_stdin(page)
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let line: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(line["links"], fetched["links"]);
+}
+
+#[test]
+fn fetch_dispatches_on_sniffed_content() {
+    let server = MockServer::start();
+    let fetch = |path: &str| {
+        let mut cmd = nab();
+        cmd.args(["fetch", "--cookies", "none", &server.url(path)])
+            .timeout(std::time::Duration::from_secs(30));
+        cmd
+    };
+    // JSON labeled text/plain is pretty-printed
+    fetch("/api/plain")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"items\": [\n"));
+    // An RSS feed served as application/xml becomes Markdown
+    fetch("/feed.xml")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "## [First post](https://example.com/first)",
+        ))
+        .stdout(predicate::str::contains("Canned *feed* item."));
+    // A PDF served as application/octet-stream gives its metadata and its
+    // text laid out as Markdown, paragraphs running on across pages
+    fetch("/doc.pdf")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("📑 PDF: 2 pages, 0 images"))
+        .stdout(predicate::str::contains("   Author: Nab Tests"))
+        .stdout(predicate::str::contains(
+            "# Mock Report\n\n## Hello from a PDF\n\n\
+             Text is laid out in paragraphs, not lines.\n\n\
+             A second paragraph.\n\nPage two closes the document.",
+        ));
+    fetch("/doc.pdf")
+        .args(["--format", "json", "--pdf-pages"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""title":"Mock Report""#))
+        .stdout(predicate::str::contains(r#""created":"2026-01-01T12:00:00Z""#))
+        .stdout(predicate::str::contains(
+            r#"{"number":2,"markdown":"Page two closes the document.","images":0}"#,
+        ));
+    // Word documents become Markdown, Excel workbooks CSV
+    fetch("/minutes.docx")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "# Mock Minutes\n\nThe board met on **Tuesday**.\n\n\
+             | Item | Owner |\n| --- | --- |\n| Budget | Finance |",
+        ));
+    fetch("/results.xlsx")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Quarter,Revenue\r\nQ1,1200\r\n\"Q2, est.\",1350.5\r\n",
+        ));
+
+    // Images are saved under their URL's file name
+    let dir = std::env::temp_dir().join(format!("nab-cli-download-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    fetch("/pixel.png")
+        .current_dir(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "💾 Saved image (image/png, 69 bytes) to pixel.png",
+        ));
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/mock/pixel.png");
+    assert_eq!(
+        std::fs::read(dir.join("pixel.png")).unwrap(),
+        std::fs::read(fixture).unwrap()
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn paginate_follows_rel_next() {
+    let server = MockServer::start();
+    nab()
+        .args(["fetch", "--cookies", "none", "--paginate"])
+        .arg(server.url("/shop-1.html"))
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("## Page 1"))
+        .stdout(predicate::str::contains("Steel Kettle"))
+        .stdout(predicate::str::contains("## Page 2"))
+        .stdout(predicate::str::contains("Glass Kettle"))
+        .stdout(predicate::str::contains("## Page 3").not());
+
+    let output = nab()
+        .args(["extract", "--cookies", "none", "--preset", "product", "--paginate"])
+        .arg(server.url("/shop-1.html"))
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let items: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let names: Vec<&str> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Steel Kettle", "Glass Kettle"]);
+}
+
+#[test]
+fn fetch_reports_server_latency() {
+    let server = MockServer::start();
+    let slow = fetch_json(&[&server.url("/slow")]);
+    assert!(slow["time_ms"].as_f64().unwrap() >= 300.0, "{slow}");
+}
+
+#[test]
+fn fetch_resolves_from_hosts_file() {
+    let server = MockServer::start();
+    let hosts = std::env::temp_dir().join(format!("nab-hosts-{}.txt", std::process::id()));
+    std::fs::write(&hosts, "# staging\n127.0.0.1  www.staging.test\n").unwrap();
+    let url = server.url("/").replace("127.0.0.1", "www.staging.test");
+    nab()
+        .args(["fetch", "--cookies", "none", "--body", &url, "--hosts-file"])
+        .arg(&hosts)
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Mock Home"));
+
+    let json = fetch_json(&["--hosts-file", hosts.to_str().unwrap(), &url]);
+    assert_eq!(json["status"], 200);
+    let _ = std::fs::remove_file(&hosts);
+}
+
+#[test]
+fn domain_policy_blocks_fetches() {
+    let server = MockServer::start();
+    let url = server.url("/");
+    let config = std::env::temp_dir().join(format!("nab-policy-{}.json", std::process::id()));
+    let with_policy = |policy: &str, args: &[&str]| {
+        std::fs::write(&config, format!(r#"{{"policy": {policy}}}"#)).unwrap();
+        nab()
+            .env("NAB_CONFIG", &config)
+            .args(args)
+            .timeout(std::time::Duration::from_secs(30))
+            .output()
+            .unwrap()
+    };
+
+    let fetch = ["fetch", "--cookies", "none", url.as_str()];
+    let output = with_policy(r#"{"deny": ["127.0.0.1"]}"#, &fetch);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("127.0.0.1 is denied by the domain policy"), "{stderr}");
+    let output = with_policy(r#"{"allow": ["127.0.0.1"]}"#, &fetch);
+    assert!(output.status.success(), "{output:?}");
+
+    let crawl = ["crawl", "--delay-ms", "0", url.as_str()];
+    let output = with_policy(r#"{"allow": ["example.com"]}"#, &crawl);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("isn't allowed by the domain policy"), "{stdout}");
+    let _ = std::fs::remove_file(&config);
+}
+
+#[test]
+fn denied_hosts_are_never_contacted() {
+    // Any connection would wait in the backlog, so accept() tells if one came
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let host = url.trim_start_matches("http://").trim_end_matches('/');
+    let config = std::env::temp_dir().join(format!("nab-policy-deny-{}.json", std::process::id()));
+    std::fs::write(&config, r#"{"policy": {"deny": ["127.0.0.1"]}}"#).unwrap();
+
+    let warmup = format!("{url}warmup");
+    let commands: [&[&str]; 6] = [
+        &[
+            "fetch",
+            "--cookies",
+            "none",
+            "--format",
+            "json",
+            "--warmup-url",
+            &warmup,
+            &url,
+        ],
+        &["audit", "headers", "--cookies", "none", &url],
+        &["audit", "tls", host],
+        &["linkcheck", "--delay-ms", "0", &url],
+        &["extract", "--preset", "article", "--cookies", "none", &url],
+        &["fingerprint", "verify", "--endpoint", &url],
+    ];
+    for args in commands {
+        let output = nab()
+            .env("NAB_CONFIG", &config)
+            .args(args)
+            .timeout(std::time::Duration::from_secs(30))
+            .output()
+            .unwrap();
+        assert!(
+            listener.accept().is_err(),
+            "{args:?} contacted the denied host: {output:?}"
+        );
+        let report =
+            String::from_utf8_lossy(&output.stdout) + String::from_utf8_lossy(&output.stderr);
+        assert!(
+            report.contains("denied by the domain policy"),
+            "{args:?}: {report}"
+        );
+    }
+    let _ = std::fs::remove_file(&config);
+}
+
+#[test]
+fn timed_fetch_through_a_proxy_never_connects_directly() {
+    let proxy = MockServer::start();
+    // Any connection would wait in the backlog, so accept() tells if one came
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+
+    let page = fetch_json(&["--proxy", &proxy.url(""), &url]);
+    assert!(
+        listener.accept().is_err(),
+        "the timing probe went around the proxy"
+    );
+    assert_eq!(page["status"], 200, "{page}");
+    assert!(page["timings"]["dns_ms"].is_null(), "{page}");
+}
+
+/// A token endpoint (`POST /token`) that also serves pages, keeping each
+/// page request's `Host` and `Authorization` headers
+fn oauth2_server() -> (u16, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let kept = requests.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 8192];
+            let n = stream.read(&mut buf).unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+            let body = if request.starts_with("post /token") {
+                r#"{"access_token":"t1","token_type":"bearer","expires_in":3600}"#.to_string()
+            } else {
+                let header = |name: &str| {
+                    request
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .unwrap_or("-")
+                        .trim()
+                        .to_string()
+                };
+                let host = header("host:");
+                let host = host.split(':').next().unwrap_or_default();
+                kept.lock()
+                    .unwrap()
+                    .push(format!("{host} {}", header("authorization:")));
+                "<html><body><p>Page</p></body></html>".to_string()
+            };
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    (port, requests)
+}
+
+#[test]
+fn batch_sends_oauth2_tokens_only_to_their_domains() {
+    let (port, requests) = oauth2_server();
+    let home = std::env::temp_dir().join(format!("nab-oauth2-batch-{}", std::process::id()));
+    // Current browser versions, so the fresh config dir doesn't fetch them
+    std::fs::create_dir_all(home.join("nab")).unwrap();
+    std::fs::write(
+        home.join("nab/versions.json"),
+        serde_json::to_string(&nab::fingerprint::autoupdate::BrowserVersions::default()).unwrap(),
+    )
+    .unwrap();
+    let config = home.join("config.json");
+    std::fs::write(
+        &config,
+        format!(
+            r#"{{"oauth2": {{"api": {{"token_url": "http://127.0.0.1:{port}/token",
+                "client_id": "nab", "domains": ["localhost"]}}}}}}"#
+        ),
+    )
+    .unwrap();
+    let output = nab()
+        .env("NAB_CONFIG", &config)
+        .env("NAB_SECRET_BACKEND", "file")
+        .env("XDG_CONFIG_HOME", &home)
+        .env_remove("NAB_WORKSPACE")
+        .args(["--http", "1", "batch", "-", "--auth", "oauth2:api"])
+        .write_stdin(format!(
+            "http://localhost:{port}/a\nhttp://127.0.0.1:{port}/b\n"
+        ))
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let mut requests = requests.lock().unwrap().clone();
+    requests.sort();
+    assert_eq!(requests, ["127.0.0.1 -", "localhost bearer t1"]);
+    let _ = std::fs::remove_dir_all(&home);
+}
+
+#[test]
+fn fetch_picks_http_version() {
+    let server = MockServer::start();
+    let url = server.url("/");
+    let version = |args: &[&str], config: &std::path::Path| {
+        nab()
+            .env("NAB_CONFIG", config)
+            .args(["fetch", "--cookies", "none", &url])
+            .args(args)
+            .timeout(std::time::Duration::from_secs(30))
+            .assert()
+    };
+    let config = std::env::temp_dir().join(format!("nab-http-{}.json", std::process::id()));
+    std::fs::write(&config, "{}").unwrap();
+    version(&[], &config)
+        .success()
+        .stdout(predicate::str::contains("Version: HTTP/2.0"));
+    version(&["--http", "1"], &config)
+        .success()
+        .stdout(predicate::str::contains("Version: HTTP/1.1"));
+    version(&["--http", "3"], &config)
+        .failure()
+        .stderr(predicate::str::contains("doesn't speak HTTP/3 yet"));
+    version(&["--http", "0.9"], &config).failure();
+
+    // A legacy domain in the config, overridden by --http
+    std::fs::write(&config, r#"{"domains": {"127.0.0.1": {"http": "1"}}}"#).unwrap();
+    version(&[], &config)
+        .success()
+        .stdout(predicate::str::contains("Version: HTTP/1.1"));
+    version(&["--http", "2"], &config)
+        .success()
+        .stdout(predicate::str::contains("Version: HTTP/2.0"));
+    std::fs::remove_file(&config).unwrap();
+}
+
+/// A server speaking only HTTP/1.x, like many legacy origins; counts the
+/// connections that opened with an HTTP/2 preface
+fn http1_only_server() -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let prefaces = std::sync::Arc::new(AtomicUsize::new(0));
+    let counter = prefaces.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 4096];
+            let n = stream.read(&mut buf).unwrap_or(0);
+            let response = if buf[..n].starts_with(b"PRI * HTTP/2.0") {
+                counter.fetch_add(1, Ordering::SeqCst);
+                "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string()
+            } else {
+                let body = "<html><body><h1>Legacy Page</h1></body></html>";
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nAlt-Svc: h3=\":443\"; ma=600\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    (url, prefaces)
+}
+
+#[test]
+fn fetch_remembers_http1_only_hosts() {
+    use std::sync::atomic::Ordering;
+
+    let (url, prefaces) = http1_only_server();
+    let cache = std::env::temp_dir().join(format!("nab-protocols-{}", std::process::id()));
+    let fetch = |args: &[&str]| {
+        nab()
+            .env("XDG_CACHE_HOME", &cache)
+            .env_remove("NAB_WORKSPACE")
+            .args(["fetch", "--cookies", "none", &url])
+            .args(args)
+            .timeout(std::time::Duration::from_secs(30))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Version: HTTP/1.1"))
+            .stdout(predicate::str::contains("Legacy Page"))
+    };
+
+    fetch(&["--no-protocol-cache"]);
+    assert_eq!(prefaces.load(Ordering::SeqCst), 1);
+    assert!(!cache.join("nab/protocols.json").exists());
+
+    // HTTP/2 breaks down once; the next run goes straight to HTTP/1.1
+    fetch(&[]);
+    assert_eq!(prefaces.load(Ordering::SeqCst), 2);
+    let memory: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(cache.join("nab/protocols.json")).unwrap())
+            .unwrap();
+    let origin = &memory["origins"][url.trim_end_matches('/')];
+    assert_eq!(origin["version"], "1", "{memory}");
+    assert_eq!(origin["alt_svc"][0]["protocol"], "h3");
+    fetch(&[]);
+    assert_eq!(prefaces.load(Ordering::SeqCst), 2);
+    let _ = std::fs::remove_dir_all(&cache);
+}
+
+#[test]
+fn fetch_logs_in_to_http1_only_servers() {
+    let server = MockServer::start_with(&["--http1-only"]);
+    for path in ["/basic", "/digest"] {
+        let fetch = |user: &str| {
+            nab()
+                .args(["fetch", "--cookies", "none", "--no-protocol-cache", "--body"])
+                .args(["--user", user, &server.url(path)])
+                .timeout(std::time::Duration::from_secs(30))
+                .assert()
+                .success()
+        };
+        fetch("ada:pa")
+            .stdout(predicate::str::contains("Status: 200"))
+            .stdout(predicate::str::contains("Version: HTTP/1.1"))
+            .stdout(predicate::str::contains("Mock Home"));
+        fetch("ada:wrong").stdout(predicate::str::contains("Status: 401"));
+    }
+
+    // Batch falls back to HTTP/1.1 for the first request and the login alike
+    let urls = format!("{}\n{}", server.url("/basic"), server.url("/digest"));
+    let output = nab()
+        .args(["batch", "-", "--no-protocol-cache", "--user", "ada:pa"])
+        .write_stdin(urls)
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let statuses: Vec<u64> = stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter_map(|line| line["status"].as_u64())
+        .collect();
+    assert_eq!(statuses, [200, 200], "{stdout}");
+}
+
+#[test]
+#[cfg(feature = "spa")]
+fn fetch_solves_js_challenge() {
+    let server = MockServer::start();
+    nab()
+        .args([
+            "fetch",
+            "--cookies",
+            "none",
+            "--body",
+            &server.url("/members"),
+        ])
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Status: 503"))
+        .stdout(predicate::str::contains("Members Article").not());
+
+    nab()
+        .args([
+            "fetch",
+            "--cookies",
+            "none",
+            "--body",
+            "--solve-js-challenge",
+        ])
+        .arg(server.url("/members"))
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Status: 200"))
+        .stdout(predicate::str::contains("Members Article"));
+}
+
+#[test]
+fn extract_replays_recorded_cassette_offline() {
+    let cassette =
+        std::env::temp_dir().join(format!("nab-cli-cassette-{}.json", std::process::id()));
+    let server = MockServer::start();
+    let url = server.url("/article.html");
+    let recorded = nab()
+        .args([
+            "extract",
+            "--preset",
+            "article",
+            "--cookies",
+            "none",
+            "--record",
+        ])
+        .arg(&cassette)
+        .arg(&url)
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(recorded.status.success(), "{recorded:?}");
+    drop(server);
+
+    nab()
+        .args(["extract", "--preset", "article", "--replay"])
+        .arg(&cassette)
+        .arg(&url)
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(recorded.stdout);
+
+    nab()
+        .args(["extract", "--preset", "article", "--replay"])
+        .arg(&cassette)
+        .arg(format!("{url}?page=2"))
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No recorded response"));
+    std::fs::remove_file(&cassette).unwrap();
+}
+
+#[test]
+fn fetch_streams_markdown() {
+    let server = MockServer::start();
+    nab()
+        .args(["fetch", "--cookies", "none", "--stream", &server.url("/")])
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Mock Home"))
+        .stdout(predicate::str::contains(
+            "[Read the article](/article.html)",
+        ))
+        .stdout(predicate::str::contains("Body: "));
+
+    nab()
+        .args(["fetch", "--stream", "--format", "json", &server.url("/")])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--format full"));
+}
+
+#[test]
+fn batch_converts_pages_on_parse_threads() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-batch-parse-{}", std::process::id()));
+    let urls = ["/", "/article.html", "/gz"]
+        .map(|path| server.url(path))
+        .join("\n");
+    let output = nab()
+        .args(["batch", "-", "--parse-threads", "2", "--output-dir"])
+        .arg(&dir)
+        .write_stdin(urls)
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3, "{stdout}");
+    for line in &lines {
+        let file = line["file"].as_str().expect("each page is saved");
+        assert!(!std::fs::read_to_string(file).unwrap().trim().is_empty());
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn batch_ends_with_stats() {
+    let server = MockServer::start();
+    let urls = ["/", "/article.html", "/missing"]
+        .map(|path| server.url(path))
+        .join("\n");
+    let output = nab()
+        .args(["--stats", "batch", "-"])
+        .write_stdin(urls)
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("📊 3 requests (2 2xx, 1 4xx)"), "{stderr}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stats: serde_json::Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+    assert_eq!(stats["stats"]["total"]["requests"], 3, "{stdout}");
+    assert_eq!(stats["stats"]["total"]["status"]["4xx"], 1, "{stdout}");
+}
+
+#[test]
+fn batch_resumes_a_job() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-batch-resume-{}", std::process::id()));
+    let manifest = dir.join("job.json");
+    let run = |paths: &[&str]| {
+        let urls = paths
+            .iter()
+            .map(|path| server.url(path))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let output = nab()
+            .args(["batch", "-", "--resume-job"])
+            .arg(&manifest)
+            .arg("--output-dir")
+            .arg(&dir)
+            .write_stdin(urls)
+            .timeout(std::time::Duration::from_secs(30))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        (
+            stdout.lines().count(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+
+    assert_eq!(run(&["/", "/article.html"]).0, 2);
+    let (fetched, stderr) = run(&["/", "/article.html", "/gz"]);
+    assert_eq!(fetched, 1, "{stderr}");
+    assert!(stderr.contains("Skipping 2 URLs"), "{stderr}");
+    let saved: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest).unwrap()).unwrap();
+    assert_eq!(saved["completed"].as_object().unwrap().len(), 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn batch_stops_at_the_deadline() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-batch-deadline-{}", std::process::id()));
+    let manifest = dir.join("job.json");
+    let urls = (0..8)
+        .map(|i| server.url(&format!("/slow?page={i}")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let output = nab()
+        .args(["--deadline", "1s", "batch", "-"])
+        .args(["--per-host-concurrency", "1", "--resume-job"])
+        .arg(&manifest)
+        .write_stdin(urls)
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(124), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Deadline reached"), "{stderr}");
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest).unwrap()).unwrap();
+    let completed = saved["completed"].as_object().unwrap().len();
+    assert!((1..8).contains(&completed), "{completed} URLs completed");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn batch_stops_at_the_output_limit() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-batch-quota-{}", std::process::id()));
+    let urls = (0..8)
+        .map(|i| server.url(&format!("/?page={i}")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let output = nab()
+        .args(["batch", "-", "--per-host-concurrency", "1", "-o"])
+        .arg(&dir)
+        .args(["--output-max-size", "150B"])
+        .write_stdin(urls)
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Output limit of 150 B reached"), "{stderr}");
+    let written: u64 = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .sum();
+    assert!(written <= 150, "{written} bytes written");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn batch_saves_its_job_on_sigterm() {
+    use std::io::Write;
+
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-batch-sigterm-{}", std::process::id()));
+    let manifest = dir.join("job.json");
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("nab"))
+        .args(["batch", "-", "--per-host-concurrency", "1", "--resume-job"])
+        .arg(&manifest)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let urls = (0..20)
+        .map(|i| server.url(&format!("/slow?page={i}")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(urls.as_bytes()).unwrap();
+    drop(stdin);
+
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+    let killed = std::process::Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(130), "{stderr}");
+    assert!(stderr.contains("Interrupted"), "{stderr}");
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest).unwrap()).unwrap();
+    let completed = saved["completed"].as_object().unwrap().len();
+    assert!((1..20).contains(&completed), "{completed} URLs completed");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dry_run_estimates_batch_and_crawl() {
+    let server = MockServer::start();
+    let estimate = |args: &[&str], stdin: String| {
+        let output = nab()
+            .args(args)
+            .write_stdin(stdin)
+            .timeout(std::time::Duration::from_secs(30))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("🧮 Dry run"), "{stderr}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let line: serde_json::Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+        line["estimate"].clone()
+    };
+
+    let urls = format!("{}\n{}", server.url("/"), server.url("/article.html"));
+    let batch = estimate(&["batch", "-", "--dry-run"], urls);
+    assert_eq!(batch["pages"], 2);
+    assert_eq!(batch["bytes"], 204 + 221);
+
+    // The seed, plus the pages of the mock's sitemap.xml
+    let seed = server.url("/");
+    let crawl = estimate(
+        &["crawl", &seed, "--max-depth", "1", "--delay-ms", "2000", "--dry-run"],
+        String::new(),
+    );
+    assert_eq!(crawl["pages"], 3, "{crawl}");
+    assert_eq!(crawl["sitemap_pages"], 2);
+    assert_eq!(crawl["unexplored"], 1);
+    assert!(crawl["seconds"].as_f64().unwrap() >= 4.0, "{crawl}");
+}
+
+#[test]
+fn self_update_checks_the_channel() {
+    let server = MockServer::start();
+    let config = std::env::temp_dir().join(format!("nab-self-update-{}.json", std::process::id()));
+    std::fs::write(
+        &config,
+        serde_json::json!({"self_update": {"releases_url": server.url("/releases.json")}})
+            .to_string(),
+    )
+    .unwrap();
+    let check = |channel: &str| {
+        nab()
+            .env("NAB_CONFIG", &config)
+            .args(["self", "update", "--check", "--channel", channel])
+            .assert()
+    };
+
+    check("stable")
+        .success()
+        .stdout(predicate::str::contains("nab 99.0.0 is available"));
+    // The newest beta has no binaries yet
+    let no_binaries = predicate::str::contains("Release v99.1.0-beta.1 has no nab-");
+    check("beta").failure().stderr(no_binaries);
+    std::fs::remove_file(&config).unwrap();
+}
+
+#[test]
+fn audit_grades_security_headers() {
+    let server = MockServer::start();
+    let url = server.url("/hardened");
+    let output = nab()
+        .args(["audit", "headers", &url, "--format", "json"])
+        .args(["--min-grade", "C"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let reports: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    // Everything passes except HSTS, which needs HTTPS
+    assert_eq!(reports[0]["grade"], "C");
+    let findings = reports[0]["findings"].as_array().unwrap();
+    let failed: Vec<_> = findings
+        .iter()
+        .filter(|finding| finding["status"] != "pass")
+        .map(|finding| finding["check"].as_str().unwrap())
+        .collect();
+    assert_eq!(failed, ["hsts"]);
+
+    nab()
+        .args(["audit", "headers", &url, "--min-grade", "B"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "| hsts | ❌ fail | served over plain HTTP |",
+        ))
+        .stderr(predicate::str::contains("Graded below B"));
+}
+
+#[test]
+fn audit_a11y_reports_missing_landmark() {
+    let server = MockServer::start();
+    let url = server.url("/");
+    let output = nab()
+        .args(["audit", "a11y", &url, "--max-errors", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let reports: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    // The canned home page has a title, lang, and one h1, but no <main>
+    assert_eq!(reports[0]["errors"], 1);
+    assert_eq!(reports[0]["issues"][0]["rule"], "landmark-main");
+    assert_eq!(reports[0]["headings"][0]["text"], "Mock Home");
+
+    nab()
+        .args(["audit", "a11y", &url, "--max-errors", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("More than 0 accessibility errors"));
+}
+
+#[test]
+fn image_metadata_for_fetch_and_crawl() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-image-meta-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = nab()
+        .args(["fetch", "--cookies", "none", "--format", "json", "--image-meta"])
+        .arg(server.url("/photo.jpg"))
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let fetched: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let image = &fetched["image"];
+    assert_eq!(image["format"], "jpeg");
+    assert_eq!((image["width"].as_u64(), image["height"].as_u64()), (Some(32), Some(16)));
+    assert_eq!(image["exif"]["Model"], "Mock Camera");
+    assert_eq!(image["exif"]["ExposureTime"], "1/250");
+    assert_eq!(image["exif"]["GPSLatitude"], "60.17");
+    assert_eq!(image["exif"]["GPSLongitude"], "24.94");
+    assert_eq!(image["xmp"]["dc:creator"], "Nab Tests");
+    assert_eq!(image["xmp"]["dc:rights"], "CC0 & public domain");
+    let phash = image["phash"].as_str().unwrap();
+
+    // Each image is saved once and listed, with its metadata, under the page
+    let out = dir.join("crawl");
+    nab()
+        .args(["crawl", "--max-depth", "0", "--delay-ms", "0"])
+        .arg(server.url("/gallery.html"))
+        .arg("--output-dir")
+        .arg(&out)
+        .args(["--download-images", "--image-meta"])
+        .assert()
+        .success();
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(out.join("manifest.json")).unwrap()).unwrap();
+    let images = manifest["pages"][0]["images"].as_array().unwrap();
+    assert_eq!(images.len(), 2);
+    assert_eq!(images[0]["url"], server.url("/photo.jpg").as_str());
+    assert_eq!(images[0]["image"]["phash"], phash);
+    assert_eq!(images[1]["image"]["format"], "png");
+    let saved = std::path::Path::new(images[0]["file"].as_str().unwrap());
+    assert_eq!(saved.parent(), Some(out.join("images").as_path()));
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/mock/photo.jpg");
+    assert_eq!(std::fs::read(saved).unwrap(), std::fs::read(fixture).unwrap());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn crawl_exports_its_link_graph() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-graph-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let graph = dir.join("site.json");
+    let output = nab()
+        .args(["crawl", "--max-depth", "1", "--delay-ms", "0"])
+        .arg(server.url("/"))
+        .arg("--graph")
+        .arg(&graph)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("🕸️  Link graph"), "{stderr}");
+
+    let graph: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&graph).unwrap()).unwrap();
+    let home = &graph["nodes"][0];
+    assert_eq!(home["id"], server.url("/").as_str());
+    assert_eq!((home["status"].as_u64(), home["depth"].as_u64()), (Some(200), Some(0)));
+    let edge = &graph["edges"][0];
+    assert_eq!(edge["target"], server.url("/article.html").as_str());
+    assert_eq!(edge["text"], "Read the article");
+
+    nab()
+        .args(["crawl", "--graph", "site.txt"])
+        .arg(server.url("/"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(".graphml"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn audit_canonical_checks_crawled_hreflang() {
+    let server = MockServer::start();
+    let crawl = nab()
+        .args(["crawl", "--max-depth", "1", "--delay-ms", "0"])
+        .arg(server.url("/en.html"))
+        .output()
+        .unwrap();
+    assert!(crawl.status.success(), "{crawl:?}");
+    let lines = String::from_utf8(crawl.stdout).unwrap();
+    assert!(lines.contains(r#""noindex":true"#), "{lines}");
+
+    let output = nab()
+        .args(["audit", "canonical", "-", "--max-issues", "0"])
+        .write_stdin(lines)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("🔗 2 pages, 0 canonical clusters, 1 issues"), "{stderr}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let issue = &report["issues"][0];
+    assert_eq!(issue["kind"], "hreflang_missing_return");
+    assert_eq!(issue["page"], server.url("/en.html").as_str());
+    assert_eq!(issue["target"], server.url("/de").as_str());
+    assert_eq!(issue["language"], "de");
+}
+
+#[test]
+fn crawl_honors_noindex() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-noindex-{}", std::process::id()));
+    let output = nab()
+        .args(["crawl", "--max-depth", "1", "--delay-ms", "0", "--honor-noindex"])
+        .arg(server.url("/en.html"))
+        .arg("-o")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("🚫 1 noindex pages not saved"), "{stderr}");
+
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("manifest.json")).unwrap()).unwrap();
+    let pages = manifest["pages"].as_array().unwrap();
+    let de = pages
+        .iter()
+        .find(|page| page["url"] == server.url("/de").as_str())
+        .unwrap();
+    assert_eq!((de["noindex"].as_bool(), de["nofollow"].as_bool()), (Some(true), Some(true)));
+    assert!(de.get("file").is_none(), "{de}");
+    let en = pages
+        .iter()
+        .find(|page| page["url"] == server.url("/en.html").as_str())
+        .unwrap();
+    assert!(en.get("noindex").is_none());
+    assert!(std::path::Path::new(en["file"].as_str().unwrap()).exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn worker_fetches_what_coordinator_leases() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-coordinator-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let urls = dir.join("urls.txt");
+    let pages = ["/", "/article.html", "/en.html", "/missing"];
+    let list: Vec<String> = pages.iter().map(|page| server.url(page)).collect();
+    std::fs::write(&urls, list.join("\n")).unwrap();
+
+    let mut coordinator = std::process::Command::new(assert_cmd::cargo::cargo_bin("nab"))
+        .args(["coordinator", "--listen", "127.0.0.1:0", "--token", "s3cret"])
+        .arg(&urls)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(coordinator.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    let address = line
+        .split_whitespace()
+        .find(|word| word.starts_with("http://"))
+        .unwrap_or_else(|| panic!("no address in {line}"))
+        .to_string();
+
+    nab()
+        .args(["worker", &address, "--name", "w1"])
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("401"));
+    nab()
+        .args(["worker", &address, "--name", "w1", "--token", "s3cret"])
+        .timeout(std::time::Duration::from_secs(60))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Worker w1: 4 pages"));
+
+    let output = coordinator.wait_with_output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let lines: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .filter(|line: &serde_json::Value| line["url"].is_string())
+        .collect();
+    assert_eq!(lines.len(), 4, "{lines:?}");
+    assert!(lines.iter().all(|line| line["worker"] == "w1"));
+    let home = lines.iter().find(|line| line["url"] == server.url("/")).unwrap();
+    assert_eq!(home["status"], 200);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn crawl_store_keeps_each_body_once() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-store-{}", std::process::id()));
+    let crawl = || {
+        let output = nab()
+            .args(["crawl", "--max-depth", "1", "--delay-ms", "0", "--store"])
+            .arg(&dir)
+            .arg(server.url("/"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let blobs = || {
+        std::fs::read_dir(dir.join("blobs"))
+            .unwrap()
+            .flatten()
+            .map(|sub| std::fs::read_dir(sub.path()).unwrap().count())
+            .sum::<usize>()
+    };
+    let lines = crawl();
+    assert!(lines.contains(r#""sha256":""#), "{lines}");
+    let stored = blobs();
+    assert!(stored > 0);
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    crawl();
+    // The unchanged pages are in the second run's manifest, not stored again
+    assert_eq!(blobs(), stored);
+
+    nab()
+        .args(["store", "cat"])
+        .arg(&dir)
+        .arg(server.url("/"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("<html"));
+    nab()
+        .args(["store", "gc", "--keep-runs", "1"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(format!(
+            "Removed 1 runs and 0 blobs (0 B); {stored} blobs kept"
+        )));
+    nab()
+        .args(["store", "gc"])
+        .arg(dir.join("missing"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a crawl --store directory"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn export_converts_a_crawl() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-export-{}", std::process::id()));
+    let output = nab()
+        .args(["crawl", "--max-depth", "1", "--delay-ms", "0", "-o"])
+        .arg(dir.join("pages"))
+        .arg(server.url("/"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let manifest = dir.join("pages/manifest.json");
+
+    let output = nab()
+        .arg("export")
+        .arg(&manifest)
+        .args(["-", "--format", "jsonl"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let home: serde_json::Value = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .find(|row: &serde_json::Value| row["url"] == server.url("/").as_str())
+        .unwrap();
+    assert_eq!((home["status"].as_u64(), home["title"].as_str()), (Some(200), Some("Mock Home")));
+    assert!(home["timestamp"].is_string(), "{home}");
+    assert!(home["metadata"].as_str().unwrap().contains(r#""links":1"#), "{home}");
+
+    let parquet = dir.join("crawl.parquet");
+    let export = nab().arg("export").arg(&manifest).arg(&parquet).assert();
+    if cfg!(feature = "parquet") {
+        export
+            .success()
+            .stderr(predicate::str::contains("📦 Exported 2 pages"));
+        assert!(std::fs::read(&parquet).unwrap().starts_with(b"PAR1"));
+    } else {
+        export
+            .failure()
+            .stderr(predicate::str::contains("built without Parquet support"));
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// A NATS server that answers pings and keeps each published message
+/// (headers and payload)
+#[cfg(feature = "nats")]
+fn nats_server() -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let messages = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let kept = messages.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut writer = stream.try_clone().unwrap();
+            let _ = writer.write_all(
+                b"INFO {\"server_id\":\"mock\",\"version\":\"2.10.0\",\"proto\":1,\
+                  \"headers\":true,\"max_payload\":1048576}\r\n",
+            );
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                match words.first().copied() {
+                    Some("PING") => {
+                        let _ = writer.write_all(b"PONG\r\n");
+                    }
+                    Some("HPUB" | "PUB") => {
+                        let size: usize = words.last().unwrap().parse().unwrap();
+                        let mut message = vec![0; size + 2];
+                        reader.read_exact(&mut message).unwrap();
+                        message.truncate(size);
+                        kept.lock()
+                            .unwrap()
+                            .push(String::from_utf8(message).unwrap());
+                    }
+                    _ => {}
+                }
+                line.clear();
+            }
+        }
+    });
+    (address, messages)
+}
+
+#[test]
+#[cfg(feature = "nats")]
+fn crawl_publishes_pages_to_nats() {
+    let server = MockServer::start();
+    let (address, messages) = nats_server();
+    let output = nab()
+        .args(["crawl", "--max-depth", "1", "--delay-ms", "0", "--publish"])
+        .arg(format!("nats://{address}/nab.pages"))
+        .arg(server.url("/"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("📡 Published 2 pages"), "{stderr}");
+
+    let messages = messages.lock().unwrap();
+    let home = messages
+        .iter()
+        .find(|message| message.contains(&format!("Nab-Key: {}", server.url("/"))))
+        .unwrap();
+    let payload: serde_json::Value =
+        serde_json::from_str(home.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+    assert_eq!(payload["status"], 200);
+    assert!(payload["markdown"].as_str().unwrap().contains("Mock Home"), "{payload}");
+
+    nab()
+        .args(["crawl", "--publish", "amqp://localhost/q"])
+        .arg(server.url("/"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("nats or kafka"));
+}
+
+#[test]
+fn linkcheck_reports_broken_links_by_page() {
+    let server = MockServer::start();
+    let page = server.url("/links.html");
+    let output = nab()
+        .args(["linkcheck", &page, "--depth", "1", "--delay-ms", "0"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("1 links are broken"), "{stderr}");
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["summary"]["broken"], 1);
+    assert_eq!(report["summary"]["redirected"], 1);
+    let pages = report["pages"].as_array().unwrap();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0]["page"], page.as_str());
+    let links = &pages[0]["links"];
+    assert_eq!(links[0]["url"], server.url("/missing.html").as_str());
+    assert_eq!(links[0]["status"], 404);
+    assert_eq!(links[1]["outcome"], "redirected");
+    assert_eq!(links[1]["redirects"][0], server.url("/").as_str());
+}
+
+#[cfg(unix)]
+#[test]
+fn plugin_fetches_through_the_pipe() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-plugin-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let plugin = dir.join("nab-probe");
+    std::fs::write(
+        &plugin,
+        r#"#!/bin/sh
+read hello
+echo "$hello" | grep -o '"type":"hello"'
+printf '{"nab": 1, "type": "fetch", "id": 1, "url": "%s", "options": {"cookies": "none"}}\n' "$1"
+read reply
+echo "$reply" | grep -o '"status":200'
+echo "$reply" | grep -o '"kind":"markdown"'
+exit 3
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!(
+        "{}:{}",
+        dir.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    nab()
+        .args(["probe", &server.url("/")])
+        .env("PATH", &path)
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains(r#""type":"hello""#))
+        .stdout(predicate::str::contains(r#""status":200"#))
+        .stdout(predicate::str::contains(r#""kind":"markdown""#));
+
+    nab()
+        .arg("plugins")
+        .env("PATH", &path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("probe"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "script")]
+#[test]
+fn fetch_runs_script_hooks() {
+    let server = MockServer::start();
+    let dir = std::env::temp_dir().join(format!("nab-script-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("transform.rhai");
+    std::fs::write(
+        &script,
+        r#"
+        fn on_response(html, meta) { html.replace("Mock Home", "Scripted Home"); html }
+        fn on_markdown(md, meta) { #{ text: md, status: meta.status } }
+        "#,
+    )
+    .unwrap();
+
+    let page = fetch_json(&["--script", script.to_str().unwrap(), &server.url("/")]);
+    assert_eq!(page["fields"]["status"], 200);
+
+    nab()
+        .args(["fetch", "--cookies", "none", "--body", "--script"])
+        .arg(&script)
+        .arg(server.url("/"))
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Scripted Home"))
+        .stdout(predicate::str::contains("Mock Home").not());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn curl_command_runs_through_nab() {
+    let server = MockServer::start();
+    // The cookie passes the browser check; curl doesn't follow redirects without -L
+    let command = format!(
+        "curl '{}' -H 'accept: text/html' -b 'passed=42' --compressed",
+        server.url("/members")
+    );
+    nab()
+        .args(["curl", &command, "--body"])
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Status: 200"))
+        .stdout(predicate::str::contains("Members Article"));
+
+    nab()
+        .args(["curl", &format!("curl -s {}", server.url("/old"))])
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Status: 301"));
+}
@@ -7,6 +7,7 @@
 //! drop panic — tests that trigger this path assert on the partial output
 //! rather than exit code.
 
+#![cfg(feature = "spa")]
 #![allow(deprecated)] // cargo_bin deprecation — replacement not yet stable
 
 use assert_cmd::Command;
@@ -48,7 +49,21 @@ fn spa_help_lists_all_options() {
         .stdout(predicate::str::contains("--max-depth"))
         .stdout(predicate::str::contains("--http1"))
         .stdout(predicate::str::contains("--console"))
-        .stdout(predicate::str::contains("--wait"));
+        .stdout(predicate::str::contains("--wait"))
+        .stdout(predicate::str::contains("--no-wasm"))
+        .stdout(predicate::str::contains("--js-timeout"))
+        .stdout(predicate::str::contains("--js-memory"))
+        .stdout(predicate::str::contains("--js-network"))
+        .stdout(predicate::str::contains("--consent"));
+}
+
+#[test]
+fn spa_rejects_unknown_network_policy() {
+    nab()
+        .args(["spa", "--js-network", "sometimes", "https://example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown network policy"));
 }
 
 // ─── Basic SPA invocation ────────────────────────────────────────────────────
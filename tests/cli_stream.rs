@@ -46,7 +46,8 @@ fn stream_help_shows_all_options() {
         .stdout(predicate::str::contains("--info"))
         .stdout(predicate::str::contains("--list"))
         .stdout(predicate::str::contains("--duration"))
-        .stdout(predicate::str::contains("--player"));
+        .stdout(predicate::str::contains("--player"))
+        .stdout(predicate::str::contains("--player-args"));
 }
 
 // ─── Analyze/Annotate argument validation ────────────────────────────────────